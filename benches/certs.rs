@@ -0,0 +1,197 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Benchmarks of encoding, decoding and validating certificates and CSRs. Run them using
+//! `cargo bench --features ed25519,p256`, adding `aws-lc` to include the aws-lc backend.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use der::asn1::{Uint, UtcTime};
+use der::pem::LineEnding;
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::{actor_name, SessionId, Target};
+use polyproto::key::KeyGen;
+use polyproto::signature::Signature;
+use polyproto::timestamp::Timestamp;
+use polyproto::{Constrained, Name};
+use x509_cert::ext::Extensions;
+use x509_cert::time::{Time, Validity};
+
+fn actor_subject() -> Name {
+    actor_name(
+        "flori",
+        "polyphony.chat",
+        &SessionId::from_str("client1").unwrap(),
+    )
+    .unwrap()
+}
+
+fn home_server_subject() -> Name {
+    Name::from_str("DC=polyphony,DC=chat").unwrap()
+}
+
+fn validity() -> Validity {
+    Validity {
+        not_before: Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(10)).unwrap()),
+        not_after: Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(1000)).unwrap()),
+    }
+}
+
+/// Benchmarks the certificate and CSR operations using the keys of type `K`, naming the
+/// benchmarks after `backend`.
+fn bench_backend<S: Signature, K: KeyGen<S>>(c: &mut Criterion, backend: &str) {
+    let mut rng = rand::rngs::OsRng;
+    let home_server_key = K::generate(&mut rng);
+    let actor_key = K::generate(&mut rng);
+    let csr = IdCsr::new(
+        &actor_subject(),
+        &actor_key,
+        &Capabilities::actor_default(),
+        Some(Target::Actor),
+    )
+    .unwrap();
+    let cert = IdCert::from_actor_csr(
+        csr.clone(),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        validity(),
+    )
+    .unwrap();
+    let csr_der = csr.to_der().unwrap();
+    let cert_der = cert.to_der().unwrap();
+    let cert_pem = cert.to_pem(LineEnding::LF).unwrap();
+    let time = Timestamp::from_unix_seconds(100);
+    let home_server_public_key = home_server_key.pubkey();
+
+    let mut group = c.benchmark_group(backend);
+    group.bench_function("csr/new", |b| {
+        b.iter(|| {
+            IdCsr::new(
+                &actor_subject(),
+                &actor_key,
+                &Capabilities::actor_default(),
+                Some(Target::Actor),
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("csr/to_der", |b| b.iter(|| csr.to_der().unwrap()));
+    group.bench_function("csr/from_der", |b| {
+        b.iter(|| IdCsr::<S, K::PublicKey>::from_der(black_box(&csr_der), Some(Target::Actor)))
+    });
+    group.bench_function("cert/from_actor_csr", |b| {
+        b.iter(|| {
+            IdCert::from_actor_csr(
+                csr.clone(),
+                &home_server_key,
+                Uint::new(&[1]).unwrap(),
+                home_server_subject(),
+                validity(),
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("cert/to_der", |b| b.iter(|| cert.to_der().unwrap()));
+    // Borrowing the cached encoding of the IdCertTbs, compared to encoding it again.
+    group.bench_function("tbs/encoded_der", |b| {
+        b.iter(|| black_box(cert.id_cert_tbs.encoded_der().unwrap()).len())
+    });
+    group.bench_function("tbs/to_der", |b| {
+        b.iter(|| cert.id_cert_tbs.to_der().unwrap())
+    });
+    group.bench_function("cert/from_der", |b| {
+        b.iter(|| {
+            IdCert::from_der(
+                black_box(&cert_der),
+                Target::Actor,
+                time,
+                home_server_public_key,
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("cert/from_der_unchecked", |b| {
+        b.iter(|| IdCert::<S, K::PublicKey>::from_der_unchecked(black_box(&cert_der)).unwrap())
+    });
+    group.bench_function("cert/to_pem", |b| {
+        b.iter(|| cert.to_pem(LineEnding::LF).unwrap())
+    });
+    group.bench_function("cert/from_pem", |b| {
+        b.iter(|| {
+            IdCert::from_pem(
+                black_box(&cert_pem),
+                Target::Actor,
+                time,
+                home_server_public_key,
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("cert/validate", |b| {
+        b.iter(|| cert.validate(Some(Target::Actor)).unwrap())
+    });
+    group.bench_function("cert/verify_signature", |b| {
+        b.iter(|| cert.verify_signature(home_server_public_key).unwrap())
+    });
+    group.finish();
+}
+
+/// Benchmarks the conversions between [Capabilities] and extensions and the encoding of [Name]s,
+/// which take part in every encoding and validation of a certificate.
+fn capabilities_and_names(c: &mut Criterion) {
+    let mut group = c.benchmark_group("capabilities");
+    for (target, capabilities) in [
+        ("actor", Capabilities::actor_default()),
+        ("home_server", Capabilities::home_server_default()),
+    ] {
+        let extensions = Extensions::try_from(capabilities.clone()).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("to_extensions", target),
+            &capabilities,
+            |b, capabilities| b.iter(|| Extensions::try_from(capabilities.clone()).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("from_extensions", target),
+            &extensions,
+            |b, extensions| b.iter(|| Capabilities::try_from(extensions.clone()).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("validate", target),
+            &capabilities,
+            |b, capabilities| b.iter(|| capabilities.validate(None).unwrap()),
+        );
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("name");
+    let name = actor_subject();
+    group.bench_function("actor_name", |b| b.iter(actor_subject));
+    group.bench_function("validate", |b| {
+        b.iter(|| name.validate(Some(Target::Actor)).unwrap())
+    });
+    group.finish();
+}
+
+fn backends(c: &mut Criterion) {
+    #[cfg(all(feature = "ed25519", not(feature = "fips")))]
+    bench_backend::<_, polyproto::backends::ed25519::Ed25519PrivateKey>(c, "ed25519");
+    #[cfg(all(feature = "p256", not(feature = "fips")))]
+    bench_backend::<_, polyproto::backends::p256::P256PrivateKey>(c, "p256");
+    #[cfg(any(feature = "aws-lc", feature = "fips"))]
+    {
+        bench_backend::<_, polyproto::backends::aws_lc::ed25519::Ed25519PrivateKey>(
+            c,
+            "aws-lc/ed25519",
+        );
+        bench_backend::<_, polyproto::backends::aws_lc::p256::P256PrivateKey>(c, "aws-lc/p256");
+    }
+}
+
+criterion_group!(benches, backends, capabilities_and_names);
+criterion_main!(benches);
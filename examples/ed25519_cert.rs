@@ -49,11 +49,11 @@ fn main() {
         )
         .unwrap(),
         &priv_key,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(Target::Actor),
     )
     .unwrap();
-    let data = csr.clone().to_der().unwrap();
+    let data = csr.to_der().unwrap();
     let file_name_with_extension = "cert.csr";
     std::fs::write(file_name_with_extension, data).unwrap();
 
@@ -12,6 +12,7 @@ use polyproto::certs::idcert::IdCert;
 use polyproto::certs::{PublicKeyInfo, Target};
 use polyproto::key::{PrivateKey, PublicKey};
 use polyproto::signature::Signature;
+use polyproto::timestamp::Timestamp;
 use rand::rngs::OsRng;
 use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
 use x509_cert::name::RdnSequence;
@@ -44,12 +45,12 @@ fn main() {
         )
         .unwrap(),
         &priv_key_actor,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(polyproto::certs::Target::Actor),
     )
     .unwrap();
 
-    let data = csr.clone().to_der().unwrap();
+    let data = csr.to_der().unwrap();
     let file_name_with_extension = "cert.csr";
     #[cfg(not(target_arch = "wasm32"))]
     std::fs::write(file_name_with_extension, data).unwrap();
@@ -69,15 +70,23 @@ fn main() {
         },
     )
     .unwrap();
-    let data = cert.clone().to_der().unwrap();
+    let data = cert.to_der().unwrap();
     // ``::from_der()` performs a full check of the certificate, including signature verification.
-    let cert_from_der =
-        IdCert::from_der(&data, Target::Actor, 15, &priv_key_home_server.public_key).unwrap();
+    let cert_from_der = IdCert::from_der(
+        &data,
+        Target::Actor,
+        Timestamp::from_unix_seconds(15),
+        &priv_key_home_server.public_key,
+    )
+    .unwrap();
     assert_eq!(cert_from_der, cert);
     // ...so technically, we don't need to verify the signature again. This is just for demonstration
     // of how you would manually verify a certificate.
     assert!(cert_from_der
-        .full_verify_actor(15, &priv_key_home_server.public_key)
+        .full_verify_actor(
+            Timestamp::from_unix_seconds(15),
+            &priv_key_home_server.public_key
+        )
         .is_ok())
 }
 
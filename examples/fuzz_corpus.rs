@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Writes the seed corpora of the fuzz targets in `fuzz/fuzz_targets`. Run it from the root of the
+// repository before fuzzing, optionally passing the corpus directory (`fuzz/corpus` by default):
+//
+//     cargo run --example fuzz_corpus --features ed25519,serde
+//     cargo fuzz run id_cert_from_der
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::{BitString, Uint, UtcTime};
+use der::pem::LineEnding;
+use polyproto::backends::ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature};
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::pem::PemLabels;
+use polyproto::certs::{actor_name, SessionId, Target};
+use polyproto::types::spki::AlgorithmIdentifierOwned;
+use polyproto::types::x509_cert::SerialNumber;
+use polyproto::types::{EncryptedPkm, PrivateKeyInfo};
+use polyproto::Name;
+use spki::ObjectIdentifier;
+use x509_cert::time::{Time, Validity};
+
+type Csr = IdCsr<Ed25519Signature, Ed25519PublicKey>;
+
+fn main() {
+    let corpus = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("fuzz/corpus"));
+    let home_server_key = Ed25519PrivateKey::gen_keypair(&mut rand::rngs::OsRng);
+    let actor_key = Ed25519PrivateKey::gen_keypair(&mut rand::rngs::OsRng);
+    let home_server_subject = Name::from_str("DC=polyphony,DC=chat").unwrap();
+    let validity = Validity {
+        not_before: Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(10)).unwrap()),
+        not_after: Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(1000)).unwrap()),
+    };
+
+    let actor_csr = Csr::new(
+        &actor_name(
+            "flori",
+            "polyphony.chat",
+            &SessionId::from_str("client1").unwrap(),
+        )
+        .unwrap(),
+        &actor_key,
+        &Capabilities::actor_default(),
+        Some(Target::Actor),
+    )
+    .unwrap();
+    let home_server_csr = Csr::new(
+        &home_server_subject,
+        &home_server_key,
+        &Capabilities::home_server_default(),
+        Some(Target::HomeServer),
+    )
+    .unwrap();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr.clone(),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject.clone(),
+        validity,
+    )
+    .unwrap();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr.clone(),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject,
+        validity,
+    )
+    .unwrap();
+
+    write_seeds(
+        &corpus.join("id_cert_from_der"),
+        [
+            actor_cert.to_der().unwrap(),
+            home_server_cert.to_der().unwrap(),
+        ],
+    );
+    let mut pems = Vec::new();
+    for csr in [&actor_csr, &home_server_csr] {
+        for labels in [PemLabels::Standard, PemLabels::TargetSpecific] {
+            pems.push(
+                csr.to_pem_with_labels(LineEnding::LF, labels)
+                    .unwrap()
+                    .into_bytes(),
+            );
+        }
+    }
+    write_seeds(&corpus.join("id_csr_from_pem"), pems);
+
+    let pkm = EncryptedPkm {
+        serial_number: SerialNumber::new(&[1]).unwrap(),
+        key_data: PrivateKeyInfo {
+            algorithm: AlgorithmIdentifierOwned::new(
+                ObjectIdentifier::new_unwrap("1.3.101.112"),
+                None,
+            ),
+            encrypted_private_key_bitstring: BitString::from_bytes(&[7; 48]).unwrap(),
+        },
+        encryption_algorithm: AlgorithmIdentifierOwned::new(
+            ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.1.46"),
+            None,
+        ),
+    };
+    write_seeds(
+        &corpus.join("encrypted_pkm_json"),
+        [serde_json::to_vec(&pkm).unwrap()],
+    );
+
+    write_seeds(
+        &corpus.join("federation_id"),
+        [
+            "xenia@example.com",
+            "flori@polyphony.chat",
+            "a.b_c+d@sub.do-main.example",
+        ]
+        .map(|id| id.as_bytes().to_vec()),
+    );
+    println!("Wrote the seed corpora to {}", corpus.display());
+}
+
+/// Writes each of `seeds` to a file in `directory`, creating the directory if necessary.
+fn write_seeds(directory: &Path, seeds: impl IntoIterator<Item = Vec<u8>>) {
+    fs::create_dir_all(directory).unwrap();
+    for (index, seed) in seeds.into_iter().enumerate() {
+        fs::write(directory.join(format!("seed-{}", index)), seed).unwrap();
+    }
+}
@@ -46,7 +46,7 @@ async fn main() {
     // string from the server, we would call:
     let challenge = client.get_challenge_string().await.unwrap();
     println!("Challenge string: {}", challenge.challenge);
-    println!("Challenge expires at UNIX timestamp: {}", challenge.expires);
+    println!("Challenge expires at: {}", challenge.expires);
 }
 
 #[test]
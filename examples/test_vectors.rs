@@ -0,0 +1,141 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Regenerates the frozen fixtures of the `test_vectors` module in `src/test_vectors`. The
+// fixtures are deterministic, so that running this example without changing it reproduces them
+// byte for byte:
+//
+//     cargo run --example test_vectors --features ed25519,test-utils
+
+use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::{Uint, UtcTime};
+use der::pem::LineEnding;
+use polyproto::backends::ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature};
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::{actor_name, SessionId, Target};
+use polyproto::key::PrivateKey;
+use polyproto::signature::Signature;
+use polyproto::test_vectors::{
+    ACTOR_SECRET_KEY, HOME_SERVER_PUBLIC_KEY, HOME_SERVER_SECRET_KEY, NOT_AFTER, NOT_BEFORE,
+    VERIFICATION_TIME,
+};
+use polyproto::Name;
+use x509_cert::time::{Time, Validity};
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn main() {
+    let home_server_key = Ed25519PrivateKey::from_bytes(&HOME_SERVER_SECRET_KEY);
+    let actor_key = Ed25519PrivateKey::from_bytes(&ACTOR_SECRET_KEY);
+    assert_eq!(
+        home_server_key.pubkey().to_bytes(),
+        HOME_SERVER_PUBLIC_KEY,
+        "HOME_SERVER_PUBLIC_KEY does not belong to HOME_SERVER_SECRET_KEY"
+    );
+    let home_server_name = Name::from_str("DC=polyphony,DC=chat").unwrap();
+    let actor_subject = actor_name(
+        "flori",
+        "polyphony.chat",
+        &SessionId::from_str("client1").unwrap(),
+    )
+    .unwrap();
+    let valid = validity(NOT_BEFORE, NOT_AFTER);
+
+    let home_server = IdCert::from_ca_csr(
+        IdCsr::new(
+            &home_server_name,
+            &home_server_key,
+            &Capabilities::home_server_default(),
+            Some(Target::HomeServer),
+        )
+        .unwrap(),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_name.clone(),
+        valid,
+    )
+    .unwrap();
+    let actor_csr = IdCsr::new(
+        &actor_subject,
+        &actor_key,
+        &Capabilities::actor_default(),
+        Some(Target::Actor),
+    )
+    .unwrap();
+    let actor = IdCert::from_actor_csr(
+        actor_csr.clone(),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_name.clone(),
+        valid,
+    )
+    .unwrap();
+
+    let mut bad_signature = actor.clone();
+    let mut signature = bad_signature.signature.as_signature().to_bytes();
+    signature[0] ^= 1;
+    bad_signature.signature = Signature::from_bytes(&signature);
+
+    let expired = IdCert::from_actor_csr(
+        actor_csr,
+        &home_server_key,
+        Uint::new(&[3]).unwrap(),
+        home_server_name,
+        validity(
+            NOT_BEFORE - 5 * 365 * 86400,
+            VERIFICATION_TIME - 365 * 86400,
+        ),
+    )
+    .unwrap();
+
+    let mut issuer_domain_mismatch = actor.clone();
+    issuer_domain_mismatch.id_cert_tbs.serial_number = Uint::new(&[4]).unwrap();
+    issuer_domain_mismatch.id_cert_tbs.issuer = Name::from_str("DC=example,DC=com").unwrap();
+    let issuer_domain_mismatch = resign(issuer_domain_mismatch, &home_server_key);
+
+    let mut actor_with_ca_flag = actor.clone();
+    actor_with_ca_flag.id_cert_tbs.serial_number = Uint::new(&[5]).unwrap();
+    actor_with_ca_flag.id_cert_tbs.capabilities = Capabilities::home_server_default();
+    let actor_with_ca_flag = resign(actor_with_ca_flag, &home_server_key);
+
+    let mut home_server_without_ca_flag = home_server.clone();
+    home_server_without_ca_flag.id_cert_tbs.serial_number = Uint::new(&[6]).unwrap();
+    home_server_without_ca_flag.id_cert_tbs.capabilities = Capabilities::actor_default();
+    let home_server_without_ca_flag = resign(home_server_without_ca_flag, &home_server_key);
+
+    for (name, cert) in [
+        ("home_server_valid", home_server),
+        ("actor_valid", actor),
+        ("actor_bad_signature", bad_signature),
+        ("actor_expired", expired),
+        ("actor_issuer_domain_mismatch", issuer_domain_mismatch),
+        ("actor_with_ca_flag", actor_with_ca_flag),
+        ("home_server_without_ca_flag", home_server_without_ca_flag),
+    ] {
+        let path = format!("src/test_vectors/{}.pem", name);
+        fs::write(&path, cert.to_pem(LineEnding::LF).unwrap()).unwrap();
+        println!("Wrote {}", path);
+    }
+}
+
+fn validity(not_before: u64, not_after: u64) -> Validity {
+    let time =
+        |seconds| Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(seconds)).unwrap());
+    Validity {
+        not_before: time(not_before),
+        not_after: time(not_after),
+    }
+}
+
+/// Signs the [IdCertTbs](polyproto::certs::idcerttbs::IdCertTbs) of `cert` again, without
+/// validating the certificate.
+fn resign(mut cert: Cert, signing_key: &Ed25519PrivateKey) -> Cert {
+    cert.signature = signing_key.sign(&cert.id_cert_tbs.to_der().unwrap());
+    cert
+}
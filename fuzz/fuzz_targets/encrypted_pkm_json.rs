@@ -0,0 +1,14 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use polyproto::types::EncryptedPkm;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(pkm) = serde_json::from_slice::<EncryptedPkm>(data) {
+        let _ = serde_json::to_string(&pkm);
+    }
+});
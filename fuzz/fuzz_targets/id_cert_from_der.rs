@@ -0,0 +1,19 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use polyproto::backends::ed25519::{Ed25519PublicKey, Ed25519Signature};
+use polyproto::certs::idcert::IdCert;
+use polyproto::Constrained;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(cert) = IdCert::<Ed25519Signature, Ed25519PublicKey>::from_der_unchecked(data) {
+        // Decoded certificates must survive validation and encoding without panicking.
+        let _ = cert.validate(None);
+        let _ = cert.to_der();
+        let _ = cert.check_canonical(data);
+    }
+});
@@ -0,0 +1,22 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use polyproto::backends::ed25519::{Ed25519PublicKey, Ed25519Signature};
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+
+fuzz_target!(|data: &[u8]| {
+    let pem = match std::str::from_utf8(data) {
+        Ok(pem) => pem,
+        Err(_) => return,
+    };
+    for target in [None, Some(Target::Actor), Some(Target::HomeServer)] {
+        if let Ok(csr) = IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_pem(pem, target) {
+            let _ = csr.to_pem(polyproto::der::pem::LineEnding::LF);
+        }
+    }
+});
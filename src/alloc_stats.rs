@@ -0,0 +1,170 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Implementing `GlobalAlloc` requires `unsafe`. This module is the only place in the crate where
+// `unsafe` is permitted, and only when the `alloc-stats` feature is enabled. Every unsafe block
+// forwards to the wrapped allocator without touching the memory itself.
+#![allow(unsafe_code)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static BYTES_DEALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static THREAD_DEALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static THREAD_BYTES_ALLOCATED: Cell<u64> = const { Cell::new(0) };
+    static THREAD_BYTES_DEALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// A [GlobalAlloc] wrapper counting the allocations made through it. Register it as the global
+/// allocator to make [AllocStats::global()] and [AllocStats::current_thread()] report the
+/// allocations of the program:
+///
+/// ```rs
+/// #[global_allocator]
+/// static ALLOCATOR: polyproto::alloc_stats::CountingAllocator =
+///     polyproto::alloc_stats::CountingAllocator::system();
+/// ```
+///
+/// Counting adds a few atomic operations to every allocation, which makes this unsuitable for
+/// production builds where allocation performance matters.
+pub struct CountingAllocator<A: GlobalAlloc = System> {
+    inner: A,
+}
+
+impl CountingAllocator<System> {
+    /// Creates a [CountingAllocator] wrapping the [System] allocator.
+    pub const fn system() -> Self {
+        Self { inner: System }
+    }
+}
+
+impl<A: GlobalAlloc> CountingAllocator<A> {
+    /// Creates a [CountingAllocator] wrapping `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+fn record_allocation(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    // `try_with` fails while the thread local storage of the thread is being torn down, in which
+    // case the allocation is only counted globally.
+    let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    let _ = THREAD_BYTES_ALLOCATED.try_with(|bytes| bytes.set(bytes.get() + size as u64));
+}
+
+fn record_deallocation(size: usize) {
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_DEALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    let _ = THREAD_DEALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    let _ = THREAD_BYTES_DEALLOCATED.try_with(|bytes| bytes.set(bytes.get() + size as u64));
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        record_deallocation(layout.size());
+    }
+
+    /// A reallocation is counted as a deallocation of the old and an allocation of the new block.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record_deallocation(layout.size());
+            record_allocation(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+/// Allocation counters, as recorded by a [CountingAllocator]. All counters are cumulative; use
+/// [AllocStats::since()] or [measure()] to get the allocations of a specific piece of code.
+///
+/// If no [CountingAllocator] is registered as the global allocator, all counters stay at `0`.
+pub struct AllocStats {
+    /// The number of allocations.
+    pub allocations: u64,
+    /// The number of deallocations.
+    pub deallocations: u64,
+    /// The total number of bytes allocated.
+    pub bytes_allocated: u64,
+    /// The total number of bytes deallocated.
+    pub bytes_deallocated: u64,
+}
+
+impl AllocStats {
+    /// Returns the counters of all threads of the program.
+    pub fn global() -> Self {
+        Self {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+            bytes_allocated: BYTES_ALLOCATED.load(Ordering::Relaxed),
+            bytes_deallocated: BYTES_DEALLOCATED.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the counters of the current thread. Unlike [AllocStats::global()], these are not
+    /// affected by allocations made concurrently on other threads.
+    pub fn current_thread() -> Self {
+        Self {
+            allocations: THREAD_ALLOCATIONS.with(Cell::get),
+            deallocations: THREAD_DEALLOCATIONS.with(Cell::get),
+            bytes_allocated: THREAD_BYTES_ALLOCATED.with(Cell::get),
+            bytes_deallocated: THREAD_BYTES_DEALLOCATED.with(Cell::get),
+        }
+    }
+
+    /// Returns the difference between these counters and an earlier snapshot.
+    pub fn since(&self, earlier: &AllocStats) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations.saturating_sub(earlier.allocations),
+            deallocations: self.deallocations.saturating_sub(earlier.deallocations),
+            bytes_allocated: self.bytes_allocated.saturating_sub(earlier.bytes_allocated),
+            bytes_deallocated: self
+                .bytes_deallocated
+                .saturating_sub(earlier.bytes_deallocated),
+        }
+    }
+
+    /// Returns the number of bytes allocated, but not yet deallocated. Negative, if more memory
+    /// was freed than allocated, e.g. when measuring code which drops data allocated earlier.
+    pub fn net_bytes(&self) -> i128 {
+        self.bytes_allocated as i128 - self.bytes_deallocated as i128
+    }
+}
+
+/// Runs `f` and returns its result, together with the allocations it made on the current thread.
+/// Allocations made by threads spawned by `f` are not included.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, AllocStats) {
+    let before = AllocStats::current_thread();
+    let result = f();
+    let stats = AllocStats::current_thread().since(&before);
+    (result, stats)
+}
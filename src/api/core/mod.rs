@@ -2,40 +2,40 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::time::UNIX_EPOCH;
-
 use crate::types::x509_cert::SerialNumber;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+use crate::certs::certid::CertId;
 use crate::certs::idcert::IdCert;
 use crate::certs::idcsr::IdCsr;
+use crate::certs::status::{StatusRequest, StatusResponse};
+use crate::certs::superseded::SupersededNotice;
 use crate::certs::{PublicKeyInfo, SessionId};
 use crate::errors::{ConversionError, RequestError};
 use crate::key::PublicKey;
 use crate::signature::Signature;
+use crate::timestamp::Timestamp;
 use crate::types::routes::core::v1::*;
-use crate::types::{ChallengeString, EncryptedPkm};
+use crate::types::{ChallengeString, EncryptedPkm, Endpoint};
 
 use super::{HttpClient, HttpResult};
 
 /// Get the current UNIX timestamp according to the system clock.
 pub fn current_unix_time() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
+    Timestamp::now().unix_seconds()
 }
 
 // Core Routes: No registration needed
 impl HttpClient {
     /// Request a [ChallengeString] from the server.
     pub async fn get_challenge_string(&self) -> HttpResult<ChallengeString> {
-        let request_url = self.url.join(GET_CHALLENGE_STRING.path)?;
         let request_response = self
-            .client
-            .request(GET_CHALLENGE_STRING.method.clone(), request_url)
-            .send()
+            .send_request(
+                &GET_CHALLENGE_STRING.method,
+                GET_CHALLENGE_STRING.path,
+                None,
+            )
             .await;
         HttpClient::handle_response(request_response).await
     }
@@ -50,28 +50,24 @@ impl HttpClient {
     pub async fn rotate_server_identity_key<S: Signature, P: PublicKey<S>>(
         &self,
     ) -> HttpResult<IdCert<S, P>> {
-        let request_url = self.url.join(ROTATE_SERVER_IDENTITY_KEY.path)?;
         let request_response = self
-            .client
-            .request(ROTATE_SERVER_IDENTITY_KEY.method.clone(), request_url)
-            .send()
+            .send_request(
+                &ROTATE_SERVER_IDENTITY_KEY.method,
+                ROTATE_SERVER_IDENTITY_KEY.path,
+                None,
+            )
             .await;
         let pem = HttpClient::handle_response::<String>(request_response).await?;
         log::debug!("Received IdCert: \n{}", pem);
         let id_cert = IdCert::<S, P>::from_pem_unchecked(&pem)?;
-        match id_cert.full_verify_home_server(
-            std::time::SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        ) {
+        match id_cert.full_verify_home_server(self.time_source.now()) {
             Ok(_) => (),
             Err(e) => return Err(RequestError::ConversionError(e.into())),
         };
         Ok(id_cert)
     }
 
-    /// Request the server's public [IdCert]. Specify a unix timestamp to get the IdCert which was
+    /// Request the server's public [IdCert]. Specify a [Timestamp] to get the IdCert which was
     /// valid at that time. If no timestamp is provided, the current IdCert is returned.
     ///
     /// ## Safety guarantees
@@ -80,40 +76,50 @@ impl HttpClient {
     /// [IdCert::full_verify_home_server()], as this method calls that method internally.
     pub async fn get_server_id_cert<S: Signature, P: PublicKey<S>>(
         &self,
-        unix_time: Option<u64>,
+        time: Option<Timestamp>,
     ) -> HttpResult<IdCert<S, P>> {
-        let request_url = self.url.join(GET_SERVER_PUBLIC_IDCERT.path)?;
-        let mut request = self
-            .client
-            .request(GET_SERVER_PUBLIC_IDCERT.method.clone(), request_url);
-        if let Some(time) = unix_time {
-            request = request.body(json!({ "timestamp": time }).to_string());
-        }
-        let response = request.send().await;
+        let body = time.map(|time| json!({ "timestamp": time }).to_string());
+        let response = self
+            .send_request(
+                &GET_SERVER_PUBLIC_IDCERT.method,
+                GET_SERVER_PUBLIC_IDCERT.path,
+                body,
+            )
+            .await;
         let pem = HttpClient::handle_response::<String>(response).await?;
         let id_cert = IdCert::<S, P>::from_pem_unchecked(&pem)?;
-        match id_cert.full_verify_home_server(unix_time.unwrap_or(current_unix_time())) {
+        match id_cert.full_verify_home_server(time.unwrap_or_else(|| self.time_source.now())) {
             Ok(_) => (),
             Err(e) => return Err(RequestError::ConversionError(e.into())),
         };
         Ok(id_cert)
     }
 
-    /// Request the server's [PublicKeyInfo]. Specify a unix timestamp to get the public key which
+    /// Request the API and gateway [Endpoint]s published by the server. Larger home servers may
+    /// publish several endpoints, which can be passed to [HttpClient::set_endpoints()] or
+    /// [HttpClient::new_with_endpoints()] to distribute requests across them.
+    pub async fn get_endpoints(&self) -> HttpResult<Vec<Endpoint>> {
+        let response = self
+            .send_request(&GET_ENDPOINTS.method, GET_ENDPOINTS.path, None)
+            .await;
+        HttpClient::handle_response::<Vec<Endpoint>>(response).await
+    }
+
+    /// Request the server's [PublicKeyInfo]. Specify a [Timestamp] to get the public key which
     /// the home server used at that time. If no timestamp is provided, the current public key is
     /// returned.
     pub async fn get_server_public_key_info(
         &self,
-        unix_time: Option<u64>,
+        time: Option<Timestamp>,
     ) -> HttpResult<PublicKeyInfo> {
-        let request_url = self.url.join(GET_SERVER_PUBLIC_KEY.path)?;
-        let mut request = self
-            .client
-            .request(GET_SERVER_PUBLIC_KEY.method.clone(), request_url);
-        if let Some(time) = unix_time {
-            request = request.body(json!({ "timestamp": time }).to_string());
-        }
-        let response = request.send().await;
+        let body = time.map(|time| json!({ "timestamp": time }).to_string());
+        let response = self
+            .send_request(
+                &GET_SERVER_PUBLIC_KEY.method,
+                GET_SERVER_PUBLIC_KEY.path,
+                body,
+            )
+            .await;
         let pem = HttpClient::handle_response::<String>(response).await?;
         Ok(PublicKeyInfo::from_pem(pem.as_str())?)
     }
@@ -129,16 +135,10 @@ impl HttpClient {
     pub async fn get_actor_id_certs<S: Signature, P: PublicKey<S>>(
         &self,
         fid: &str,
-        unix_time: Option<u64>,
+        time: Option<Timestamp>,
         session_id: Option<&SessionId>,
     ) -> HttpResult<Vec<IdCertExt<S, P>>> {
-        let request_url = self
-            .url
-            .join(&format!("{}{}", GET_ACTOR_IDCERTS.path, fid))?;
-        let mut request = self
-            .client
-            .request(GET_ACTOR_IDCERTS.method.clone(), request_url);
-        let body = match (unix_time, session_id) {
+        let body = match (time, session_id) {
             // PRETTYFYME
             (Some(time), Some(session)) => {
                 Some(json!({ "timestamp": time, "session_id": session.to_string() }))
@@ -147,10 +147,13 @@ impl HttpClient {
             (None, Some(session)) => Some(json!({"session_id": session.to_string()})),
             (None, None) => None,
         };
-        if let Some(body) = body {
-            request = request.body(body.to_string());
-        }
-        let response = request.send().await;
+        let response = self
+            .send_request(
+                &GET_ACTOR_IDCERTS.method,
+                &format!("{}{}", GET_ACTOR_IDCERTS.path, fid),
+                body.map(|body| body.to_string()),
+            )
+            .await;
         let pems = HttpClient::handle_response::<Vec<IdCertExtJson>>(response).await?;
         let mut vec_idcert = Vec::new();
         for json in pems.into_iter() {
@@ -159,29 +162,72 @@ impl HttpClient {
         Ok(vec_idcert)
     }
 
+    /// Request the [SupersededNotice] for the certificate with the given [CertId], which links the
+    /// certificate to the certificate superseding it after a key rotation.
+    ///
+    /// ## Safety guarantees
+    ///
+    /// The resulting [SupersededNotice] is not verified. The caller is responsible for verifying
+    /// it, e.g. by adding it to a [SupersededCerts](crate::certs::superseded::SupersededCerts)
+    /// store, before acting upon it.
+    pub async fn get_superseded_notice<S: Signature>(
+        &self,
+        cert_id: &CertId,
+    ) -> HttpResult<SupersededNotice<S>> {
+        let response = self
+            .send_request(
+                &GET_SUPERSEDED_NOTICE.method,
+                &format!("{}{}", GET_SUPERSEDED_NOTICE.path, cert_id),
+                None,
+            )
+            .await;
+        HttpClient::handle_response::<SupersededNotice<S>>(response).await
+    }
+
+    /// Request the revocation status of a certificate from the home server which issued it.
+    ///
+    /// ## Safety guarantees
+    ///
+    /// The resulting [StatusResponse] is not verified. The caller is responsible for verifying it
+    /// using [StatusResponse::verify()] before acting upon it, or may use a
+    /// [RevocationChecker](crate::api::revocation::RevocationChecker), which does so.
+    pub async fn get_revocation_status<S: Signature>(
+        &self,
+        request: &StatusRequest,
+    ) -> HttpResult<StatusResponse<S>> {
+        let response = self
+            .send_request(
+                &CHECK_REVOCATION_STATUS.method,
+                CHECK_REVOCATION_STATUS.path,
+                Some(serde_json::to_string(request)?),
+            )
+            .await;
+        HttpClient::handle_response::<StatusResponse<S>>(response).await
+    }
+
     /// Inform a foreign server about a new [IdCert] for a session.
     pub async fn update_session_id_cert<S: Signature, P: PublicKey<S>>(
         &self,
         new_cert: IdCert<S, P>,
     ) -> HttpResult<()> {
-        let request_url = self.url.join(UPDATE_SESSION_IDCERT.path)?;
-        self.client
-            .request(UPDATE_SESSION_IDCERT.method.clone(), request_url)
-            .body(new_cert.to_pem(der::pem::LineEnding::LF)?)
-            .send()
-            .await?;
+        self.send_request(
+            &UPDATE_SESSION_IDCERT.method,
+            UPDATE_SESSION_IDCERT.path,
+            Some(new_cert.to_pem(der::pem::LineEnding::LF)?),
+        )
+        .await?;
         Ok(())
     }
 
     /// Tell a server to delete a session, revoking the session token.
     pub async fn delete_session(&self, session_id: &SessionId) -> HttpResult<()> {
-        let request_url = self.url.join(DELETE_SESSION.path)?;
         let body = json!({ "session_id": session_id.to_string() });
-        self.client
-            .request(DELETE_SESSION.method.clone(), request_url)
-            .body(body.to_string())
-            .send()
-            .await?;
+        self.send_request(
+            &DELETE_SESSION.method,
+            DELETE_SESSION.path,
+            Some(body.to_string()),
+        )
+        .await?;
         Ok(())
     }
 }
@@ -201,12 +247,12 @@ impl HttpClient {
         &self,
         csr: IdCsr<S, P>,
     ) -> HttpResult<(IdCert<S, P>, String)> {
-        let request_url = self.url.join(ROTATE_SESSION_IDCERT.path)?;
         let request_response = self
-            .client
-            .request(ROTATE_SESSION_IDCERT.method.clone(), request_url)
-            .body(csr.to_pem(der::pem::LineEnding::LF)?)
-            .send()
+            .send_request(
+                &ROTATE_SESSION_IDCERT.method,
+                ROTATE_SESSION_IDCERT.path,
+                Some(csr.to_pem(der::pem::LineEnding::LF)?),
+            )
             .await;
         let response_value = HttpClient::handle_response::<IdCertToken>(request_response).await?;
         let id_cert = IdCert::<S, P>::from_pem_unchecked(&response_value.id_cert.to_string())?;
@@ -226,12 +272,12 @@ impl HttpClient {
         for pkm in data.iter() {
             body.push(json!(pkm));
         }
-        let request_url = self.url.join(UPLOAD_ENCRYPTED_PKM.path)?;
-        self.client
-            .request(UPLOAD_ENCRYPTED_PKM.method.clone(), request_url)
-            .body(json!(body).to_string())
-            .send()
-            .await?;
+        self.send_request(
+            &UPLOAD_ENCRYPTED_PKM.method,
+            UPLOAD_ENCRYPTED_PKM.path,
+            Some(json!(body).to_string()),
+        )
+        .await?;
         Ok(())
     }
 
@@ -243,17 +289,18 @@ impl HttpClient {
         &self,
         serials: Vec<SerialNumber>,
     ) -> HttpResult<Vec<EncryptedPkm>> {
-        let request_url = self.url.join(GET_ENCRYPTED_PKM.path)?;
         let mut body = Vec::new();
         for serial in serials.iter() {
             body.push(json!(serial.try_as_u128()?));
         }
-        let request = self
-            .client
-            .request(GET_ENCRYPTED_PKM.method.clone(), request_url)
-            .body(json!(body).to_string());
-        let response =
-            HttpClient::handle_response::<Vec<EncryptedPkm>>(request.send().await).await?;
+        let request_response = self
+            .send_request(
+                &GET_ENCRYPTED_PKM.method,
+                GET_ENCRYPTED_PKM.path,
+                Some(json!(body).to_string()),
+            )
+            .await;
+        let response = HttpClient::handle_response::<Vec<EncryptedPkm>>(request_response).await?;
         let mut vec_pkm = Vec::new();
         for pkm in response.into_iter() {
             vec_pkm.push(pkm);
@@ -264,26 +311,28 @@ impl HttpClient {
     /// Delete encrypted private key material from the server. The serials must match the
     /// serial numbers of ID-Certs that the client has uploaded key material for.
     pub async fn delete_encrypted_pkm(&self, serials: Vec<SerialNumber>) -> HttpResult<()> {
-        let request_url = self.url.join(DELETE_ENCRYPTED_PKM.path)?;
         let mut body = Vec::new();
         for serial in serials.iter() {
             body.push(json!(serial.try_as_u128()?));
         }
-        self.client
-            .request(DELETE_ENCRYPTED_PKM.method.clone(), request_url)
-            .body(json!(body).to_string())
-            .send()
-            .await?;
+        self.send_request(
+            &DELETE_ENCRYPTED_PKM.method,
+            DELETE_ENCRYPTED_PKM.path,
+            Some(json!(body).to_string()),
+        )
+        .await?;
         Ok(())
     }
 
     /// Retrieve the maximum upload size for encrypted private key material, in bytes.
     pub async fn get_pkm_upload_size_limit(&self) -> HttpResult<u64> {
-        let request = self.client.request(
-            GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT.method.clone(),
-            self.url.join(GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT.path)?,
-        );
-        let response = request.send().await;
+        let response = self
+            .send_request(
+                &GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT.method,
+                GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT.path,
+                None,
+            )
+            .await;
         HttpClient::handle_response::<u64>(response).await
     }
 }
@@ -0,0 +1,315 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::types::{Endpoint, EndpointKind};
+
+use super::HttpResult;
+
+/// The default number of consecutive failures after which an endpoint is considered unhealthy.
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+/// The default amount of time an unhealthy endpoint is skipped for, before it is tried again.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct EndpointState {
+    url: Url,
+    priority: u16,
+    weight: u16,
+    current_weight: i64,
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl EndpointState {
+    fn is_healthy(&self, now: Instant) -> bool {
+        match self.unhealthy_until {
+            Some(until) => now >= until,
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EndpointPoolInner {
+    endpoints: Vec<EndpointState>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+#[derive(Debug, Clone)]
+/// A set of [Endpoint]s of a single home server, shared between clones of an
+/// [HttpClient](super::HttpClient).
+///
+/// Endpoints are selected using smooth weighted round-robin among the healthy endpoints with the
+/// lowest `priority` value. An endpoint is marked unhealthy after a configurable number of
+/// consecutive failures, and is skipped until a cooldown period has elapsed. If every endpoint is
+/// unhealthy, the one which will recover the soonest is selected, so that a request is always
+/// attempted.
+pub struct EndpointPool {
+    inner: Arc<Mutex<EndpointPoolInner>>,
+}
+
+impl EndpointPool {
+    /// Creates a new [EndpointPool] from a list of [Endpoint]s, using [DEFAULT_FAILURE_THRESHOLD]
+    /// and [DEFAULT_COOLDOWN].
+    ///
+    /// Fails, if any of the endpoint URLs cannot be parsed.
+    pub fn new(endpoints: &[Endpoint]) -> HttpResult<Self> {
+        Self::with_health_settings(endpoints, DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+
+    /// Creates a new [EndpointPool] from a list of [Endpoint]s. An endpoint is considered unhealthy
+    /// after `failure_threshold` consecutive failures, and is skipped for `cooldown` afterwards.
+    /// A `failure_threshold` of `0` is treated as `1`. Only [EndpointKind::Api] endpoints are added
+    /// to the pool; gateway endpoints are skipped.
+    ///
+    /// Fails, if any of the endpoint URLs cannot be parsed.
+    pub fn with_health_settings(
+        endpoints: &[Endpoint],
+        failure_threshold: u32,
+        cooldown: Duration,
+    ) -> HttpResult<Self> {
+        let mut states = Vec::with_capacity(endpoints.len());
+        for endpoint in endpoints.iter() {
+            if endpoint.kind != EndpointKind::Api {
+                continue;
+            }
+            states.push(EndpointState {
+                url: Url::parse(&endpoint.url)?,
+                priority: endpoint.priority,
+                weight: endpoint.weight,
+                current_weight: 0,
+                consecutive_failures: 0,
+                unhealthy_until: None,
+            });
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(EndpointPoolInner {
+                endpoints: states,
+                failure_threshold: failure_threshold.max(1),
+                cooldown,
+            })),
+        })
+    }
+
+    /// Returns the number of endpoints in this pool.
+    pub fn len(&self) -> usize {
+        self.lock().endpoints.len()
+    }
+
+    /// Returns `true`, if this pool does not contain any endpoints.
+    pub fn is_empty(&self) -> bool {
+        self.lock().endpoints.is_empty()
+    }
+
+    /// Selects the next endpoint to send a request to. Returns `None`, if the pool is empty.
+    pub fn select(&self) -> Option<Url> {
+        self.lock().select(Instant::now())
+    }
+
+    /// Returns all endpoints in the order in which they should be tried for a single request: The
+    /// endpoint returned by [Self::select()] first, followed by the remaining healthy endpoints
+    /// ordered by priority, followed by the unhealthy endpoints.
+    pub fn failover_order(&self) -> Vec<Url> {
+        let now = Instant::now();
+        let mut inner = self.lock();
+        let first = match inner.select(now) {
+            Some(url) => url,
+            None => return Vec::new(),
+        };
+        let mut rest: Vec<&EndpointState> = inner
+            .endpoints
+            .iter()
+            .filter(|state| state.url != first)
+            .collect();
+        rest.sort_by_key(|state| {
+            (
+                !state.is_healthy(now),
+                state.priority,
+                u16::MAX - state.weight,
+            )
+        });
+        let mut order = vec![first];
+        order.extend(rest.into_iter().map(|state| state.url.clone()));
+        order
+    }
+
+    /// Records a successful request to the endpoint with the given base `url`, resetting its
+    /// failure count and marking it healthy.
+    pub fn report_success(&self, url: &Url) {
+        let mut inner = self.lock();
+        if let Some(state) = inner.endpoints.iter_mut().find(|state| &state.url == url) {
+            state.consecutive_failures = 0;
+            state.unhealthy_until = None;
+        }
+    }
+
+    /// Records a failed request to the endpoint with the given base `url`. If the endpoint has
+    /// reached the failure threshold, it is marked unhealthy for the configured cooldown period.
+    pub fn report_failure(&self, url: &Url) {
+        let mut inner = self.lock();
+        let threshold = inner.failure_threshold;
+        let cooldown = inner.cooldown;
+        if let Some(state) = inner.endpoints.iter_mut().find(|state| &state.url == url) {
+            state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+            if state.consecutive_failures >= threshold {
+                log::debug!(
+                    "[EndpointPool::report_failure()] Marking endpoint {} as unhealthy",
+                    state.url
+                );
+                state.unhealthy_until = Some(Instant::now() + cooldown);
+            }
+        }
+    }
+
+    /// Returns `true`, if the endpoint with the given base `url` is part of this pool and is
+    /// currently considered healthy.
+    pub fn is_healthy(&self, url: &Url) -> bool {
+        let now = Instant::now();
+        self.lock()
+            .endpoints
+            .iter()
+            .any(|state| &state.url == url && state.is_healthy(now))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, EndpointPoolInner> {
+        // A poisoned lock only means that another thread panicked while updating counters, which
+        // cannot leave the pool in an unusable state.
+        match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl EndpointPoolInner {
+    fn select(&mut self, now: Instant) -> Option<Url> {
+        if self.endpoints.is_empty() {
+            return None;
+        }
+        let best_priority = self
+            .endpoints
+            .iter()
+            .filter(|state| state.is_healthy(now))
+            .map(|state| state.priority)
+            .min();
+        let best_priority = match best_priority {
+            Some(priority) => priority,
+            None => {
+                // Every endpoint is unhealthy. Fail open and try the one recovering the soonest.
+                return self
+                    .endpoints
+                    .iter()
+                    .min_by_key(|state| state.unhealthy_until)
+                    .map(|state| state.url.clone());
+            }
+        };
+        let candidates: Vec<usize> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| state.is_healthy(now) && state.priority == best_priority)
+            .map(|(index, _)| index)
+            .collect();
+        let mut total_weight: i64 = candidates
+            .iter()
+            .map(|index| self.endpoints[*index].weight as i64)
+            .sum();
+        // If only zero-weight endpoints are left in this tier, treat them as equally weighted.
+        let all_zero = total_weight == 0;
+        if all_zero {
+            total_weight = candidates.len() as i64;
+        }
+        let mut selected = candidates[0];
+        for index in candidates.iter() {
+            let state = &mut self.endpoints[*index];
+            state.current_weight += if all_zero { 1 } else { state.weight as i64 };
+            let current_weight = state.current_weight;
+            if current_weight > self.endpoints[selected].current_weight {
+                selected = *index;
+            }
+        }
+        self.endpoints[selected].current_weight -= total_weight;
+        Some(self.endpoints[selected].url.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pool(endpoints: &[(&str, u16, u16)]) -> EndpointPool {
+        let endpoints: Vec<Endpoint> = endpoints
+            .iter()
+            .map(|(url, priority, weight)| Endpoint::new(url, *priority, *weight))
+            .collect();
+        EndpointPool::with_health_settings(&endpoints, 2, Duration::from_secs(60)).unwrap()
+    }
+
+    fn count(pool: &EndpointPool, url: &str, rounds: usize) -> usize {
+        let url = Url::parse(url).unwrap();
+        (0..rounds)
+            .filter(|_| pool.select().unwrap() == url)
+            .count()
+    }
+
+    #[test]
+    fn selection_respects_weights() {
+        let pool = pool(&[("https://a.example/", 0, 3), ("https://b.example/", 0, 1)]);
+        assert_eq!(count(&pool, "https://a.example/", 400), 300);
+    }
+
+    #[test]
+    fn selection_is_interleaved() {
+        let pool = pool(&[("https://a.example/", 0, 1), ("https://b.example/", 0, 1)]);
+        let first = pool.select().unwrap();
+        let second = pool.select().unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn lower_priority_value_is_preferred() {
+        let pool = pool(&[
+            ("https://a.example/", 10, 100),
+            ("https://b.example/", 0, 1),
+        ]);
+        assert_eq!(count(&pool, "https://b.example/", 10), 10);
+    }
+
+    #[test]
+    fn unhealthy_endpoints_are_skipped() {
+        let pool = pool(&[("https://a.example/", 0, 1), ("https://b.example/", 1, 1)]);
+        let a = Url::parse("https://a.example/").unwrap();
+        pool.report_failure(&a);
+        assert!(pool.is_healthy(&a));
+        pool.report_failure(&a);
+        assert!(!pool.is_healthy(&a));
+        assert_eq!(count(&pool, "https://b.example/", 10), 10);
+        assert_eq!(pool.failover_order().last().unwrap(), &a);
+        pool.report_success(&a);
+        assert_eq!(count(&pool, "https://a.example/", 10), 10);
+    }
+
+    #[test]
+    fn all_unhealthy_still_selects() {
+        let pool = pool(&[("https://a.example/", 0, 1)]);
+        let a = Url::parse("https://a.example/").unwrap();
+        pool.report_failure(&a);
+        pool.report_failure(&a);
+        assert_eq!(pool.select(), Some(a));
+    }
+
+    #[test]
+    fn empty_pool() {
+        let pool = pool(&[]);
+        assert!(pool.is_empty());
+        assert_eq!(pool.select(), None);
+        assert!(pool.failover_order().is_empty());
+    }
+}
@@ -2,14 +2,43 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::sync::Arc;
+
 use serde::Deserialize;
 use serde_json::from_str;
 use url::Url;
 
 use crate::errors::RequestError;
+use crate::timestamp::{SystemTimeSource, TimeSource};
+use crate::types::{Endpoint, EndpointKind};
 
 /// The `core` module contains all API routes for implementing the core polyproto protocol in a client or server.
 pub mod core;
+/// The `endpoints` module contains the [EndpointPool](endpoints::EndpointPool), which handles
+/// endpoint selection, failover and health tracking for home servers with multiple endpoints.
+pub mod endpoints;
+/// The `multi` module contains the [MultiClient](multi::MultiClient), which manages identities on
+/// multiple home servers and routes requests by federation ID.
+pub mod multi;
+/// The `prefetch` module contains the [CertPrefetcher](prefetch::CertPrefetcher), which resolves
+/// the certificates of active peers ahead of time.
+pub mod prefetch;
+/// The `ratelimit` module contains the [RateLimiter](ratelimit::RateLimiter), which limits the
+/// number of requests a client sends to a home server.
+pub mod ratelimit;
+/// The `resolver` module contains the [CertResolver](resolver::CertResolver), which fetches,
+/// validates and caches the certificates of actors and home servers.
+pub mod resolver;
+/// The `revocation` module contains the [RevocationChecker](revocation::RevocationChecker), which
+/// queries the revocation status of certificates from the home servers which issued them.
+pub mod revocation;
+/// The `vcr` module contains the [Vcr](vcr::Vcr), which records HTTP interactions of a client and
+/// replays them in tests.
+pub mod vcr;
+
+use endpoints::EndpointPool;
+use ratelimit::RateLimiter;
+use vcr::Vcr;
 
 #[derive(Debug, Clone)]
 /// A client for making HTTP requests to a polyproto home server. Stores headers such as the
@@ -27,11 +56,21 @@ pub mod core;
 ///
 /// let challenge: ChallengeString = client.get_challenge_string().await.unwrap();
 /// ```
+///
+/// If a home server publishes multiple endpoints, the client can be created using
+/// [HttpClient::new_with_endpoints()]. Requests made through the route methods of the client are
+/// then distributed across these endpoints, and retried on another endpoint if one of them is
+/// unreachable. Idempotent requests are also retried if an endpoint responds with a server error.
 pub struct HttpClient {
     /// The reqwest client used to make requests.
     pub client: reqwest::Client,
     headers: reqwest::header::HeaderMap,
     pub(crate) url: Url,
+    pub(crate) endpoints: Option<EndpointPool>,
+    pub(crate) gateway_endpoints: Vec<Endpoint>,
+    pub(crate) vcr: Option<Vcr>,
+    pub(crate) rate_limiter: Option<RateLimiter>,
+    pub(crate) time_source: Arc<dyn TimeSource>,
 }
 
 /// A type alias for the result of an HTTP request.
@@ -53,9 +92,117 @@ impl HttpClient {
             client,
             headers,
             url,
+            endpoints: None,
+            gateway_endpoints: Vec::new(),
+            vcr: None,
+            rate_limiter: None,
+            time_source: Arc::new(SystemTimeSource),
         })
     }
 
+    /// Creates a new instance of the client, which distributes requests across multiple API
+    /// endpoints of the same home server. The base URL of the client is set to the first of the
+    /// most preferred API endpoints. Gateway endpoints are not used for requests, and are
+    /// available through [HttpClient::gateway_endpoints()].
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoints` - The endpoints of a polyproto home server, as returned by
+    ///   [HttpClient::get_endpoints()]. Must contain at least one [EndpointKind::Api] endpoint.
+    pub fn new_with_endpoints(endpoints: &[Endpoint]) -> HttpResult<Self> {
+        let mut client = Self::new(Self::preferred_endpoint(endpoints)?)?;
+        client.set_endpoints(endpoints)?;
+        Ok(client)
+    }
+
+    /// Replaces the endpoints of the client, resetting all health information. The base URL of the
+    /// client is set to the first of the most preferred API endpoints.
+    pub fn set_endpoints(&mut self, endpoints: &[Endpoint]) -> HttpResult<()> {
+        self.url = Url::parse(Self::preferred_endpoint(endpoints)?)?;
+        self.endpoints = Some(EndpointPool::new(endpoints)?);
+        self.gateway_endpoints = endpoints
+            .iter()
+            .filter(|endpoint| endpoint.kind == EndpointKind::Gateway)
+            .cloned()
+            .collect();
+        Ok(())
+    }
+
+    /// Returns the [EndpointKind::Gateway] endpoints passed to [HttpClient::new_with_endpoints()]
+    /// or [HttpClient::set_endpoints()].
+    pub fn gateway_endpoints(&self) -> &[Endpoint] {
+        &self.gateway_endpoints
+    }
+
+    /// Sets a preconfigured [EndpointPool] for the client. Passing `None` makes the client send
+    /// all requests to its base URL again.
+    pub fn set_endpoint_pool(&mut self, pool: Option<EndpointPool>) {
+        self.endpoints = pool;
+    }
+
+    /// Returns the [EndpointPool] of the client, if one has been set.
+    pub fn endpoint_pool(&self) -> Option<&EndpointPool> {
+        self.endpoints.as_ref()
+    }
+
+    /// Sets a [Vcr] for the client, which records or replays all requests made through the route
+    /// methods of the client. Passing `None` disables recording and replaying.
+    pub fn set_vcr(&mut self, vcr: Option<Vcr>) {
+        self.vcr = vcr;
+    }
+
+    /// Returns the [Vcr] of the client, if one has been set.
+    pub fn vcr(&self) -> Option<&Vcr> {
+        self.vcr.as_ref()
+    }
+
+    /// Sets a [RateLimiter] for the client. Requests made through the route methods of the client
+    /// which exceed the limit fail with [RequestError::RateLimited]. Replayed requests do not
+    /// count towards the limit. Passing `None` disables rate limiting.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<RateLimiter>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// Returns the [RateLimiter] of the client, if one has been set.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Sets the [TimeSource] the client uses to determine the current time, e.g. when verifying
+    /// the [IdCert](crate::certs::idcert::IdCert) of the home server without a specific point in
+    /// time being requested. Defaults to the [SystemTimeSource].
+    pub fn set_time_source(&mut self, time_source: impl TimeSource + 'static) {
+        self.time_source = Arc::new(time_source);
+    }
+
+    /// Returns the [TimeSource] of the client.
+    pub fn time_source(&self) -> &dyn TimeSource {
+        self.time_source.as_ref()
+    }
+
+    fn preferred_endpoint(endpoints: &[Endpoint]) -> HttpResult<&str> {
+        match endpoints
+            .iter()
+            .filter(|endpoint| endpoint.kind == EndpointKind::Api)
+            .min_by_key(|endpoint| endpoint.priority)
+        {
+            Some(endpoint) => Ok(&endpoint.url),
+            None => Err(RequestError::ConversionError(
+                crate::errors::InvalidInput::Length {
+                    min_length: 1,
+                    max_length: usize::MAX,
+                    actual_length: 0.to_string(),
+                }
+                .into(),
+            )),
+        }
+    }
+
+    /// Returns the headers of the client.
+    pub fn get_headers(&self) -> &reqwest::header::HeaderMap {
+        &self.headers
+    }
+
     /// Sets the headers for the client.
     pub fn headers(&mut self, headers: reqwest::header::HeaderMap) {
         self.headers = headers;
@@ -88,9 +235,96 @@ impl HttpClient {
         Ok(request.send().await?)
     }
 
+    /// Sends a request to the route `path`, relative to the base URL of the client. If the client
+    /// has an [EndpointPool], the endpoint is chosen from the pool, and the request is retried on
+    /// the next endpoint if the previous one could not be reached. Requests using an idempotent
+    /// method, `GET`, `HEAD`, `OPTIONS` or `DELETE`, are also retried if the previous endpoint
+    /// responded with a server error or timed out, as retrying other requests could apply them
+    /// twice.
+    ///
+    /// If the client has a [Vcr], the request is recorded, or answered from the recording without
+    /// contacting the server.
+    pub(crate) async fn send_request(
+        &self,
+        method: &reqwest::Method,
+        path: &str,
+        body: Option<String>,
+    ) -> HttpResult<reqwest::Response> {
+        let vcr = match &self.vcr {
+            Some(vcr) => vcr,
+            None => return self.send_live_request(method, path, body).await,
+        };
+        let request = vcr.request(method, path, body.as_deref());
+        if !vcr.is_recording() {
+            return vcr.replay_response(&request);
+        }
+        let response = self.send_live_request(method, path, body).await?;
+        vcr.record_response(request, response).await
+    }
+
+    async fn send_live_request(
+        &self,
+        method: &reqwest::Method,
+        path: &str,
+        body: Option<String>,
+    ) -> HttpResult<reqwest::Response> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .try_acquire()
+                .map_err(RequestError::RateLimited)?;
+        }
+        let pool = match &self.endpoints {
+            Some(pool) if !pool.is_empty() => pool,
+            _ => {
+                let request_url = self.url.join(path)?;
+                let mut request = self
+                    .client
+                    .request(method.clone(), request_url)
+                    .headers(self.headers.clone());
+                if let Some(body) = body {
+                    request = request.body(body);
+                }
+                return Ok(request.send().await?);
+            }
+        };
+        let idempotent = is_idempotent(method);
+        let mut last_result = None;
+        for base_url in pool.failover_order() {
+            let request_url = base_url.join(path)?;
+            let mut request = self
+                .client
+                .request(method.clone(), request_url)
+                .headers(self.headers.clone());
+            if let Some(body) = &body {
+                request = request.body(body.clone());
+            }
+            let result = request.send().await;
+            // A server error or a timeout does not tell whether the endpoint has already processed
+            // the request, so only idempotent requests are retried in that case.
+            let failed = match &result {
+                Ok(response) => idempotent && response.status().is_server_error(),
+                Err(e) => e.is_connect() || (idempotent && e.is_timeout()),
+            };
+            if !failed {
+                pool.report_success(&base_url);
+                return Ok(result?);
+            }
+            log::debug!(
+                "[HttpClient::send_request()] Request to endpoint {} failed, trying next endpoint",
+                base_url
+            );
+            pool.report_failure(&base_url);
+            last_result = Some(result);
+        }
+        match last_result {
+            Some(result) => Ok(result?),
+            None => unreachable!("failover_order() of a non-empty pool is non-empty"),
+        }
+    }
+
     /// Sends a request, handles the response, and returns the deserialized object.
     pub(crate) async fn handle_response<T: for<'a> Deserialize<'a>>(
-        response: Result<reqwest::Response, reqwest::Error>,
+        response: HttpResult<reqwest::Response>,
     ) -> Result<T, RequestError> {
         let response = response?;
         let response_text = response.text().await?;
@@ -98,3 +332,16 @@ impl HttpClient {
         Ok(object)
     }
 }
+
+/// Returns `true`, if requests using `method` can safely be sent more than once. `PUT` is
+/// idempotent according to RFC 9110, but polyproto uses it for routes such as
+/// [HttpClient::rotate_server_identity_key()], which must not be applied twice.
+fn is_idempotent(method: &reqwest::Method) -> bool {
+    matches!(
+        *method,
+        reqwest::Method::GET
+            | reqwest::Method::HEAD
+            | reqwest::Method::OPTIONS
+            | reqwest::Method::DELETE
+    )
+}
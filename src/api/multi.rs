@@ -0,0 +1,277 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use url::Url;
+
+use crate::certs::idcert::IdCert;
+use crate::errors::{InvalidCert, InvalidInput, PublicKeyError, RequestError};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::types::FederationId;
+
+use super::ratelimit::{RateLimit, RateLimiter};
+use super::{HttpClient, HttpResult};
+
+#[derive(Debug, Clone)]
+/// Everything a [MultiClient] knows about a single home server: the [HttpClient] used to talk to
+/// it, including credentials and rate limit, the trusted public keys of the home server and the
+/// URL of its gateway.
+pub struct HomeServer<S: Signature, P: PublicKey<S>> {
+    client: HttpClient,
+    trusted_keys: Vec<P>,
+    trust_store_generation: u64,
+    gateway: Option<Url>,
+    _signature: PhantomData<S>,
+}
+
+impl<S: Signature, P: PublicKey<S>> HomeServer<S, P> {
+    /// Creates a new [HomeServer] using `client`, with an empty trust store and no gateway.
+    pub fn new(client: HttpClient) -> Self {
+        Self {
+            client,
+            trusted_keys: Vec::new(),
+            trust_store_generation: 0,
+            gateway: None,
+            _signature: PhantomData,
+        }
+    }
+
+    /// Sets the authentication token sent with every request to this home server.
+    ///
+    /// Fails, if `token` cannot be used as a header value.
+    pub fn with_credentials(mut self, token: &str) -> HttpResult<Self> {
+        let mut value = match HeaderValue::from_str(token) {
+            Ok(value) => value,
+            Err(_) => {
+                return Err(RequestError::ConversionError(
+                    InvalidInput::Malformed(
+                        "The token contains characters not permitted in a header".to_string(),
+                    )
+                    .into(),
+                ))
+            }
+        };
+        value.set_sensitive(true);
+        let mut headers = self.client.get_headers().clone();
+        headers.insert(AUTHORIZATION, value);
+        self.client.headers(headers);
+        Ok(self)
+    }
+
+    /// Limits the number of requests sent to this home server.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.client
+            .set_rate_limiter(Some(RateLimiter::new(rate_limit)));
+        self
+    }
+
+    /// Adds a public key of this home server to its trust store.
+    pub fn with_trusted_key(mut self, public_key: P) -> Self {
+        self.trust(public_key);
+        self
+    }
+
+    /// Sets the URL of the gateway of this home server.
+    pub fn with_gateway(mut self, gateway: &str) -> HttpResult<Self> {
+        self.gateway = Some(Url::parse(gateway)?);
+        Ok(self)
+    }
+
+    /// Returns the [HttpClient] used to talk to this home server.
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// Returns a mutable reference to the [HttpClient] used to talk to this home server.
+    pub fn client_mut(&mut self) -> &mut HttpClient {
+        &mut self.client
+    }
+
+    /// Returns the URL of the gateway of this home server, if one has been set.
+    pub fn gateway(&self) -> Option<&Url> {
+        self.gateway.as_ref()
+    }
+
+    /// Adds a public key of this home server to its trust store. Keys already in the trust store
+    /// are not added again.
+    pub fn trust(&mut self, public_key: P) {
+        if !self.trusted_keys.contains(&public_key) {
+            self.trusted_keys.push(public_key);
+            self.trust_store_generation += 1;
+        }
+    }
+
+    /// Removes a public key from the trust store of this home server. Returns `true`, if the key
+    /// was trusted before.
+    pub fn distrust(&mut self, public_key: &P) -> bool {
+        let len = self.trusted_keys.len();
+        self.trusted_keys.retain(|key| key != public_key);
+        if self.trusted_keys.len() == len {
+            return false;
+        }
+        self.trust_store_generation += 1;
+        true
+    }
+
+    /// Returns the trusted public keys of this home server.
+    pub fn trusted_keys(&self) -> &[P] {
+        &self.trusted_keys
+    }
+
+    /// Returns the generation of the trust store of this home server, which is incremented
+    /// whenever a key is added to or removed from it. Use it with
+    /// [CacheGeneration::with_trust_store()](crate::cache::CacheGeneration::with_trust_store), so
+    /// that cached verification results are invalidated when the trust store changes.
+    pub fn trust_store_generation(&self) -> u64 {
+        self.trust_store_generation
+    }
+
+    /// Verifies an actor [IdCert] issued by this home server against its trust store. The
+    /// certificate is accepted, if it passes [IdCert::full_verify_actor()] for any trusted key.
+    ///
+    /// Fails with [PublicKeyError::BadSignature], if the trust store is empty.
+    pub fn verify_actor(&self, cert: &IdCert<S, P>, time: Timestamp) -> Result<(), InvalidCert> {
+        let mut result = Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature));
+        for key in self.trusted_keys.iter() {
+            result = cert.full_verify_actor(time, key);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A client managing identities on multiple home servers at once, as needed by bridges and users
+/// with accounts on several servers.
+///
+/// Each home server is registered under its domain, together with its own credentials, trust
+/// store, rate limit and gateway (see [HomeServer]). Requests for an actor are routed to the home
+/// server responsible for the domain part of the actor's [FederationId].
+///
+/// # Example
+///
+/// ```rs
+/// let mut multi = MultiClient::new();
+/// multi.insert(
+///     "polyphony.chat",
+///     HomeServer::new(HttpClient::new("https://polyphony.chat")?).with_credentials("token")?,
+/// );
+/// let fid = FederationId::new("flori@polyphony.chat")?;
+/// let challenge = multi.client_for(&fid)?.get_challenge_string().await?;
+/// ```
+pub struct MultiClient<S: Signature, P: PublicKey<S>> {
+    home_servers: BTreeMap<String, HomeServer<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> Default for MultiClient<S, P> {
+    fn default() -> Self {
+        Self {
+            home_servers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> MultiClient<S, P> {
+    /// Creates a new [MultiClient] without any home servers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `home_server` for `domain`, returning the [HomeServer] previously registered for
+    /// it. Domains are compared case-insensitively.
+    pub fn insert(
+        &mut self,
+        domain: &str,
+        home_server: HomeServer<S, P>,
+    ) -> Option<HomeServer<S, P>> {
+        self.home_servers.insert(domain.to_lowercase(), home_server)
+    }
+
+    /// Removes and returns the [HomeServer] registered for `domain`.
+    pub fn remove(&mut self, domain: &str) -> Option<HomeServer<S, P>> {
+        self.home_servers.remove(&domain.to_lowercase())
+    }
+
+    /// Returns the [HomeServer] registered for `domain`.
+    pub fn get(&self, domain: &str) -> Option<&HomeServer<S, P>> {
+        self.home_servers.get(&domain.to_lowercase())
+    }
+
+    /// Returns a mutable reference to the [HomeServer] registered for `domain`.
+    pub fn get_mut(&mut self, domain: &str) -> Option<&mut HomeServer<S, P>> {
+        self.home_servers.get_mut(&domain.to_lowercase())
+    }
+
+    /// Returns the [HomeServer] responsible for `federation_id`.
+    ///
+    /// Fails with [RequestError::UnknownHomeServer], if no home server is registered for the
+    /// domain of `federation_id`.
+    pub fn route(&self, federation_id: &FederationId) -> HttpResult<&HomeServer<S, P>> {
+        let domain = Self::domain_of(federation_id);
+        match self.home_servers.get(&domain) {
+            Some(home_server) => Ok(home_server),
+            None => {
+                log::debug!(
+                    "[MultiClient::route()] No home server configured for {}",
+                    federation_id
+                );
+                Err(RequestError::UnknownHomeServer(domain))
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the [HomeServer] responsible for `federation_id`.
+    ///
+    /// Fails with [RequestError::UnknownHomeServer], if no home server is registered for the
+    /// domain of `federation_id`.
+    pub fn route_mut(&mut self, federation_id: &FederationId) -> HttpResult<&mut HomeServer<S, P>> {
+        let domain = Self::domain_of(federation_id);
+        match self.home_servers.get_mut(&domain) {
+            Some(home_server) => Ok(home_server),
+            None => Err(RequestError::UnknownHomeServer(domain)),
+        }
+    }
+
+    /// Returns the [HttpClient] of the home server responsible for `federation_id`.
+    pub fn client_for(&self, federation_id: &FederationId) -> HttpResult<&HttpClient> {
+        Ok(self.route(federation_id)?.client())
+    }
+
+    /// Returns the domains of all registered home servers, in lexicographical order.
+    pub fn domains(&self) -> impl Iterator<Item = &str> {
+        self.home_servers.keys().map(String::as_str)
+    }
+
+    /// Returns an iterator over all registered home servers and their domains.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &HomeServer<S, P>)> {
+        self.home_servers
+            .iter()
+            .map(|(domain, home_server)| (domain.as_str(), home_server))
+    }
+
+    /// Returns the number of registered home servers.
+    pub fn len(&self) -> usize {
+        self.home_servers.len()
+    }
+
+    /// Returns `true`, if no home servers are registered.
+    pub fn is_empty(&self) -> bool {
+        self.home_servers.is_empty()
+    }
+
+    pub(super) fn domain_of(federation_id: &FederationId) -> String {
+        federation_id
+            .split('@')
+            .nth(1)
+            .unwrap_or_default()
+            .to_lowercase()
+    }
+}
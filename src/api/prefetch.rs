@@ -0,0 +1,443 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::cache::{ActorCache, CacheGeneration};
+use crate::certs::custody::uid;
+use crate::certs::idcert::IdCert;
+use crate::errors::{InvalidInput, RequestError};
+use crate::gateway::GatewayEvent;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::types::FederationId;
+
+use super::multi::{HomeServer, MultiClient};
+use super::HttpResult;
+
+/// The maximum number of observations kept per peer. Peers observed more often within the activity
+/// window are all considered equally active.
+const MAX_OBSERVATIONS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Configuration of a [CertPrefetcher].
+pub struct PrefetchConfig {
+    /// The maximum number of certificates fetched at the same time.
+    pub max_concurrency: usize,
+    /// The maximum number of certificates held in the cache of the prefetcher. Once reached, the
+    /// certificate of a new peer is only prefetched if that peer is more active than the least
+    /// active cached peer, whose certificate is then evicted.
+    pub max_cached: usize,
+    /// How long an observation of a peer counts towards its activity.
+    pub activity_window: Duration,
+    /// The number of observations within the activity window after which the certificate of a
+    /// peer is prefetched.
+    pub min_activity: usize,
+    /// The age after which a prefetched certificate is refreshed, and after which a failed
+    /// prefetch is retried.
+    pub refresh_after: Duration,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 4,
+            max_cached: 256,
+            activity_window: Duration::from_secs(600),
+            min_activity: 2,
+            refresh_after: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// Counters describing the work done by a [CertPrefetcher].
+pub struct PrefetchMetrics {
+    /// The number of certificates of peers which were not cached before.
+    pub prefetched: u64,
+    /// The number of cached certificates which were refreshed.
+    pub refreshed: u64,
+    /// The number of prefetches which failed.
+    pub failed: u64,
+    /// The number of certificates evicted to make room for the certificates of more active
+    /// peers, or because their peer became inactive.
+    pub evicted: u64,
+    /// The number of prefetches skipped because the cache was full of more active peers.
+    pub skipped: u64,
+}
+
+#[derive(Debug, Default)]
+struct Peer {
+    observations: VecDeque<Timestamp>,
+    fetched_at: Option<Timestamp>,
+    cached: bool,
+    in_flight: bool,
+}
+
+impl Peer {
+    fn activity(&self) -> usize {
+        self.observations.len()
+    }
+
+    fn forget_before(&mut self, cutoff: Timestamp) {
+        while self
+            .observations
+            .front()
+            .is_some_and(|observed| *observed < cutoff)
+        {
+            self.observations.pop_front();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State<S: Signature, P: PublicKey<S>> {
+    peers: HashMap<FederationId, Peer>,
+    cache: ActorCache<S, P>,
+    generation: CacheGeneration,
+    in_flight: usize,
+    metrics: PrefetchMetrics,
+}
+
+#[derive(Debug, Clone)]
+/// Resolves the certificates of peers ahead of time, so that the first message of a peer can be
+/// verified without waiting for its certificate to be fetched.
+///
+/// The prefetcher is fed the federation IDs appearing in gateway traffic using
+/// [CertPrefetcher::observe()] or [CertPrefetcher::observe_event()]. Peers observed at least
+/// [PrefetchConfig::min_activity] times within the [PrefetchConfig::activity_window] are
+/// prefetched by [CertPrefetcher::run()], most active peers first: their certificates are fetched
+/// from the home server responsible for them, verified against the trust store of that home server
+/// and cached in an [ActorCache]. Cached certificates are refreshed once they are older than
+/// [PrefetchConfig::refresh_after].
+///
+/// [CertPrefetcher::run()] is meant to be run off the hot path, e.g. in a background task woken
+/// up periodically. Up to [PrefetchConfig::max_concurrency] instances of it may run concurrently;
+/// further instances return immediately. Clones of a [CertPrefetcher] share their state.
+pub struct CertPrefetcher<S: Signature, P: PublicKey<S>> {
+    config: PrefetchConfig,
+    state: Arc<Mutex<State<S, P>>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CertPrefetcher<S, P> {
+    /// Creates a new [CertPrefetcher] with an empty cache.
+    pub fn new(config: PrefetchConfig) -> Self {
+        Self {
+            config,
+            state: Arc::new(Mutex::new(State {
+                peers: HashMap::new(),
+                cache: ActorCache::new(),
+                generation: CacheGeneration::new(),
+                in_flight: 0,
+                metrics: PrefetchMetrics::default(),
+            })),
+        }
+    }
+
+    /// Returns the configuration of this prefetcher.
+    pub fn config(&self) -> PrefetchConfig {
+        self.config
+    }
+
+    /// Returns the counters describing the work done by this prefetcher.
+    pub fn metrics(&self) -> PrefetchMetrics {
+        self.lock().metrics
+    }
+
+    /// Sets the [CacheGeneration] of the cache, e.g. after the trust store of a home server
+    /// changed. Certificates cached under another generation are evicted, and fetched again by
+    /// the next run.
+    pub fn set_generation(&self, generation: CacheGeneration) {
+        let mut state = self.lock();
+        if state.generation == generation {
+            return;
+        }
+        state.generation = generation;
+        state.cache.purge_stale(generation);
+        for peer in state.peers.values_mut().filter(|peer| peer.cached) {
+            peer.cached = false;
+            peer.fetched_at = None;
+        }
+    }
+
+    /// Returns the cached certificate of `federation_id`, if it has been prefetched.
+    pub fn cert(&self, federation_id: &FederationId) -> Option<IdCert<S, P>> {
+        let mut state = self.lock();
+        let generation = state.generation;
+        state.cache.get(federation_id, generation).cloned()
+    }
+
+    /// Records that `federation_id` appeared in gateway traffic at `time`.
+    pub fn observe(&self, federation_id: &FederationId, time: Timestamp) {
+        let cutoff = self.cutoff(time);
+        let mut state = self.lock();
+        let peer = state.peers.entry(federation_id.clone()).or_default();
+        peer.forget_before(cutoff);
+        if peer.observations.len() == MAX_OBSERVATIONS {
+            peer.observations.pop_front();
+        }
+        peer.observations.push_back(time);
+    }
+
+    /// Records all federation IDs appearing in the data of a gateway event received at `time`,
+    /// returning their number. Every string in the data which is a valid federation ID counts as
+    /// an appearance.
+    pub fn observe_event(&self, event: &GatewayEvent, time: Timestamp) -> usize {
+        let mut federation_ids = Vec::new();
+        collect_federation_ids(&event.data, &mut federation_ids);
+        for federation_id in federation_ids.iter() {
+            self.observe(federation_id, time);
+        }
+        federation_ids.len()
+    }
+
+    /// Returns the number of observations of `federation_id` within the activity window ending at
+    /// `now`.
+    pub fn activity(&self, federation_id: &FederationId, now: Timestamp) -> usize {
+        let cutoff = self.cutoff(now);
+        match self.lock().peers.get(federation_id) {
+            Some(peer) => peer
+                .observations
+                .iter()
+                .filter(|observed| **observed >= cutoff)
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Forgets peers which have not been observed within the activity window ending at `now`, and
+    /// evicts their certificates. Returns the number of forgotten peers.
+    pub fn prune(&self, now: Timestamp) -> usize {
+        let cutoff = self.cutoff(now);
+        let mut state = self.lock();
+        let mut inactive = Vec::new();
+        for (federation_id, peer) in state.peers.iter_mut() {
+            peer.forget_before(cutoff);
+            if peer.observations.is_empty() && !peer.in_flight {
+                inactive.push((federation_id.clone(), peer.cached));
+            }
+        }
+        for (federation_id, cached) in inactive.iter() {
+            state.peers.remove(federation_id);
+            if *cached {
+                state.cache.remove(federation_id);
+                state.metrics.evicted += 1;
+            }
+        }
+        inactive.len()
+    }
+
+    /// Prefetches the certificates of active peers until no peer is due or the concurrency limit
+    /// is reached, using `multi` to reach their home servers. Returns the number of prefetched
+    /// certificates, including failed prefetches.
+    pub async fn run(&self, multi: &MultiClient<S, P>, now: Timestamp) -> usize {
+        let mut count = 0;
+        while self.prefetch_next(multi, now).await.is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Prefetches the certificate of the most active peer which is due, returning its federation
+    /// ID and the result. Returns `None`, if no peer is due or the concurrency limit is reached.
+    pub async fn prefetch_next(
+        &self,
+        multi: &MultiClient<S, P>,
+        now: Timestamp,
+    ) -> Option<(FederationId, HttpResult<()>)> {
+        let (federation_id, in_flight) = self.claim(now)?;
+        log::trace!(
+            "[CertPrefetcher::prefetch_next()] Prefetching certificate of {}",
+            federation_id
+        );
+        let result = match multi.route(&federation_id) {
+            Ok(home_server) => resolve(home_server, &federation_id, None, now).await,
+            Err(e) => Err(e),
+        };
+        let mut state = self.lock();
+        let generation = state.generation;
+        let peer = state.peers.entry(federation_id.clone()).or_default();
+        peer.fetched_at = Some(now);
+        let refreshed = peer.cached;
+        let result = match result {
+            Ok(id_cert) => {
+                peer.cached = true;
+                state
+                    .cache
+                    .insert(federation_id.clone(), id_cert, generation);
+                match refreshed {
+                    true => state.metrics.refreshed += 1,
+                    false => state.metrics.prefetched += 1,
+                }
+                Ok(())
+            }
+            Err(e) => {
+                log::debug!(
+                    "[CertPrefetcher::prefetch_next()] Prefetching certificate of {} failed: {}",
+                    federation_id,
+                    e
+                );
+                state.metrics.failed += 1;
+                Err(e)
+            }
+        };
+        drop(state);
+        drop(in_flight);
+        Some((federation_id, result))
+    }
+
+    /// Picks the most active peer which is due and marks it as in flight, evicting the certificate
+    /// of a less active peer if the cache is full.
+    fn claim(&self, now: Timestamp) -> Option<(FederationId, InFlight<S, P>)> {
+        let cutoff = self.cutoff(now);
+        let refresh_cutoff = now
+            .checked_sub(self.config.refresh_after)
+            .unwrap_or(Timestamp::UNIX_EPOCH);
+        let mut state = self.lock();
+        if state.in_flight >= self.config.max_concurrency {
+            return None;
+        }
+        let mut candidates: Vec<(usize, FederationId, bool)> = Vec::new();
+        for (federation_id, peer) in state.peers.iter_mut() {
+            peer.forget_before(cutoff);
+            let due = peer
+                .fetched_at
+                .map_or(true, |fetched_at| fetched_at <= refresh_cutoff);
+            if !peer.in_flight && due && peer.activity() >= self.config.min_activity {
+                candidates.push((peer.activity(), federation_id.clone(), peer.cached));
+            }
+        }
+        // Most active peers first, ties broken by federation ID for a stable order.
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        for (activity, federation_id, cached) in candidates {
+            if !cached && state.cache.len() >= self.config.max_cached {
+                let least_active = state
+                    .peers
+                    .iter()
+                    .filter(|(_, peer)| peer.cached && !peer.in_flight)
+                    .min_by_key(|(_, peer)| peer.activity())
+                    .map(|(federation_id, peer)| (federation_id.clone(), peer.activity()));
+                match least_active {
+                    Some((evicted, evicted_activity)) if evicted_activity < activity => {
+                        log::trace!(
+                            "[CertPrefetcher::claim()] Evicting certificate of {} for {}",
+                            evicted,
+                            federation_id
+                        );
+                        state.cache.remove(&evicted);
+                        if let Some(peer) = state.peers.get_mut(&evicted) {
+                            peer.cached = false;
+                            peer.fetched_at = None;
+                        }
+                        state.metrics.evicted += 1;
+                    }
+                    _ => {
+                        state.metrics.skipped += 1;
+                        continue;
+                    }
+                }
+            }
+            if let Some(peer) = state.peers.get_mut(&federation_id) {
+                peer.in_flight = true;
+            }
+            state.in_flight += 1;
+            let in_flight = InFlight {
+                federation_id: federation_id.clone(),
+                state: self.state.clone(),
+            };
+            return Some((federation_id, in_flight));
+        }
+        None
+    }
+
+    fn cutoff(&self, now: Timestamp) -> Timestamp {
+        now.checked_sub(self.config.activity_window)
+            .unwrap_or(Timestamp::UNIX_EPOCH)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<S, P>> {
+        // The lock is never held across an await point or while calling into user code.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Releases the concurrency slot of a prefetch when dropped, even if the prefetch was cancelled.
+struct InFlight<S: Signature, P: PublicKey<S>> {
+    federation_id: FederationId,
+    state: Arc<Mutex<State<S, P>>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> Drop for InFlight<S, P> {
+    fn drop(&mut self) {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.in_flight = state.in_flight.saturating_sub(1);
+        if let Some(peer) = state.peers.get_mut(&self.federation_id) {
+            peer.in_flight = false;
+        }
+    }
+}
+
+/// Fetches the certificates of `federation_id`, optionally those valid at `time`, and returns the
+/// most recent one which is valid at `now`, has not been invalidated and is trusted by
+/// `home_server`.
+pub(super) async fn resolve<S: Signature, P: PublicKey<S>>(
+    home_server: &HomeServer<S, P>,
+    federation_id: &FederationId,
+    time: Option<Timestamp>,
+    now: Timestamp,
+) -> HttpResult<IdCert<S, P>> {
+    let id_certs = home_server
+        .client()
+        .get_actor_id_certs::<S, P>(federation_id, time, None)
+        .await?;
+    id_certs
+        .into_iter()
+        .filter(|id_cert| !id_cert.invalidated)
+        .map(|id_cert| id_cert.id_cert)
+        .filter(|id_cert| {
+            uid(&id_cert.id_cert_tbs.subject).as_deref() == Some(federation_id.as_str())
+        })
+        .filter(|id_cert| home_server.verify_actor(id_cert, now).is_ok())
+        .max_by_key(|id_cert| Timestamp::from(id_cert.id_cert_tbs.validity.not_before))
+        .ok_or_else(|| {
+            RequestError::ConversionError(
+                InvalidInput::Malformed(format!(
+                    "The home server returned no valid certificate for {}",
+                    federation_id
+                ))
+                .into(),
+            )
+        })
+}
+
+fn collect_federation_ids(value: &serde_json::Value, federation_ids: &mut Vec<FederationId>) {
+    match value {
+        serde_json::Value::String(string) if string.contains('@') => {
+            if let Ok(federation_id) = FederationId::new(string) {
+                if federation_id.as_str() == string && !federation_ids.contains(&federation_id) {
+                    federation_ids.push(federation_id);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values.iter() {
+                collect_federation_ids(value, federation_ids);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for value in map.values() {
+                collect_federation_ids(value, federation_ids);
+            }
+        }
+        _ => (),
+    }
+}
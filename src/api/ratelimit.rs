@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The number of requests a client may send to a home server within a sliding window of time.
+pub struct RateLimit {
+    /// The maximum number of requests within `window`. A value of `0` is treated as `1`.
+    pub max_requests: u32,
+    /// The length of the sliding window.
+    pub window: Duration,
+}
+
+impl RateLimit {
+    /// Creates a new [RateLimit], permitting `max_requests` requests per `window`.
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Enforces a [RateLimit] for an [HttpClient](super::HttpClient). The state of the limiter is
+/// shared between its clones, so that clones of a client draw from the same budget.
+///
+/// Requests exceeding the limit are not delayed, but rejected with
+/// [RequestError::RateLimited](crate::errors::RequestError::RateLimited), which carries the time
+/// until the next request is permitted. This leaves the decision on how to wait to the caller and
+/// its async runtime.
+pub struct RateLimiter {
+    limit: RateLimit,
+    sent: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Creates a new [RateLimiter] enforcing `limit`.
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            sent: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Returns the [RateLimit] enforced by this limiter.
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Records a request, if the limit permits one. Otherwise, returns the time until the next
+    /// request is permitted.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut sent = match self.sent.lock() {
+            Ok(sent) => sent,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        while let Some(oldest) = sent.front() {
+            if now.duration_since(*oldest) >= self.limit.window {
+                sent.pop_front();
+            } else {
+                break;
+            }
+        }
+        if sent.len() < self.limit.max_requests.max(1) as usize {
+            sent.push_back(now);
+            return Ok(());
+        }
+        match sent.front() {
+            Some(oldest) => Err(self.limit.window - now.duration_since(*oldest)),
+            None => unreachable!("a full window contains at least one request"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn sliding_window() {
+        let limiter = RateLimiter::new(RateLimit::new(2, Duration::from_secs(10)));
+        let start = Instant::now();
+        assert!(limiter.try_acquire_at(start).is_ok());
+        assert!(limiter
+            .try_acquire_at(start + Duration::from_secs(4))
+            .is_ok());
+        assert_eq!(
+            limiter.try_acquire_at(start + Duration::from_secs(6)),
+            Err(Duration::from_secs(4))
+        );
+        // Clones share the same budget.
+        assert!(limiter
+            .clone()
+            .try_acquire_at(start + Duration::from_secs(9))
+            .is_err());
+        assert!(limiter
+            .try_acquire_at(start + Duration::from_secs(10))
+            .is_ok());
+        assert_eq!(
+            limiter.try_acquire_at(start + Duration::from_secs(11)),
+            Err(Duration::from_secs(3))
+        );
+    }
+}
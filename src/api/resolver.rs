@@ -0,0 +1,274 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+
+use x509_cert::name::Name;
+
+use crate::cache::{ActorCache, CacheGeneration, GenerationalCache};
+use crate::certs::equal_domain_components;
+use crate::certs::idcert::IdCert;
+use crate::errors::{ConversionError, InvalidCert, PublicKeyError, RequestError};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::types::FederationId;
+use crate::ConstraintError;
+
+use super::multi::{HomeServer, MultiClient};
+use super::prefetch::resolve;
+use super::{HttpClient, HttpResult};
+
+/// The URL template used by [CertResolver::with_discovery()] in most deployments: home servers are
+/// expected to serve the polyproto API at the root of their domain.
+pub static DEFAULT_DISCOVERY_TEMPLATE: &str = "https://{domain}";
+
+#[derive(Debug)]
+struct State<S: Signature, P: PublicKey<S>> {
+    multi: MultiClient<S, P>,
+    actors: ActorCache<S, P>,
+    servers: GenerationalCache<String, IdCert<S, P>>,
+}
+
+#[derive(Debug)]
+/// Resolves the certificates of actors and home servers by their federation ID or domain,
+/// fetching, validating and caching them as needed.
+///
+/// Home servers are looked up in the [MultiClient] of the resolver. If discovery is enabled using
+/// [CertResolver::with_discovery()], unknown home servers are discovered on first use: the
+/// certificate of the home server is fetched from the URL the template resolves to, checked to
+/// have been issued for the domain of the home server, and its public key is added to the trust
+/// store of the new [HomeServer]. This trust on first use is only as strong as the transport
+/// security of the discovery URL.
+///
+/// Actor certificates are accepted, if they pass [HomeServer::verify_actor()]. Home server
+/// certificates are accepted, if they pass [IdCert::full_verify_home_server()] and their public
+/// key is in the trust store of the home server. Accepted certificates are cached in process,
+/// keyed by federation ID or domain, until they are no longer valid at the requested time or the
+/// trust store of their home server changes.
+///
+/// # Example
+///
+/// ```rs
+/// let resolver = CertResolver::new(MultiClient::new()).with_discovery(DEFAULT_DISCOVERY_TEMPLATE);
+/// let id_cert = resolver.actor_cert("alice@example.com", Timestamp::now()).await?;
+/// ```
+pub struct CertResolver<S: Signature, P: PublicKey<S>> {
+    state: Mutex<State<S, P>>,
+    discovery: Option<String>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CertResolver<S, P> {
+    /// Creates a new [CertResolver] resolving certificates using the home servers registered in
+    /// `multi`, with discovery disabled.
+    pub fn new(multi: MultiClient<S, P>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                multi,
+                actors: ActorCache::new(),
+                servers: GenerationalCache::new(),
+            }),
+            discovery: None,
+        }
+    }
+
+    /// Enables the discovery of home servers which are not registered in the [MultiClient] of the
+    /// resolver. `template` is the base URL of the API of a home server, in which `{domain}` is
+    /// replaced by the domain of the home server, such as [DEFAULT_DISCOVERY_TEMPLATE].
+    pub fn with_discovery(mut self, template: &str) -> Self {
+        self.discovery = Some(template.to_string());
+        self
+    }
+
+    /// Returns the [HomeServer] registered or discovered for `domain`.
+    pub fn home_server(&self, domain: &str) -> Option<HomeServer<S, P>> {
+        self.lock().multi.get(domain).cloned()
+    }
+
+    /// Registers `home_server` for `domain`, returning the [HomeServer] previously registered for
+    /// it. Clears all cached certificates, since they may have been validated against the trust
+    /// store of the replaced home server.
+    pub fn insert_home_server(
+        &self,
+        domain: &str,
+        home_server: HomeServer<S, P>,
+    ) -> Option<HomeServer<S, P>> {
+        let mut state = self.lock();
+        state.actors.clear();
+        state.servers.clear();
+        state.multi.insert(domain, home_server)
+    }
+
+    /// Returns a validated certificate of the actor `federation_id` which is valid at `time`,
+    /// fetching it from the home server of the actor, if no such certificate is cached.
+    ///
+    /// Fails with [RequestError::UnknownHomeServer], if the home server of the actor is not
+    /// registered and discovery is disabled.
+    pub async fn actor_cert(
+        &self,
+        federation_id: &str,
+        time: Timestamp,
+    ) -> HttpResult<IdCert<S, P>> {
+        let federation_id = FederationId::new(federation_id).map_err(ConversionError::from)?;
+        let domain = MultiClient::<S, P>::domain_of(&federation_id);
+        let home_server = self.route(&domain, time).await?;
+        let generation = generation_of(&home_server);
+        if let Some(id_cert) = self
+            .lock()
+            .actors
+            .get(&federation_id, generation)
+            .filter(|id_cert| id_cert.valid_at(time))
+        {
+            return Ok(id_cert.clone());
+        }
+        log::trace!(
+            "[CertResolver::actor_cert()] Fetching certificate of {}",
+            federation_id
+        );
+        let id_cert = resolve(&home_server, &federation_id, Some(time), time).await?;
+        self.lock()
+            .actors
+            .insert(federation_id, id_cert.clone(), generation);
+        Ok(id_cert)
+    }
+
+    /// Returns a validated certificate of the home server of `domain` which is valid at `time`,
+    /// fetching it from the home server, if no such certificate is cached.
+    ///
+    /// Fails with [RequestError::UnknownHomeServer], if the home server is not registered and
+    /// discovery is disabled.
+    pub async fn server_cert(&self, domain: &str, time: Timestamp) -> HttpResult<IdCert<S, P>> {
+        let domain = domain.to_lowercase();
+        let home_server = self.route(&domain, time).await?;
+        let generation = generation_of(&home_server);
+        if let Some(id_cert) = self
+            .lock()
+            .servers
+            .get(&domain, generation)
+            .filter(|id_cert| id_cert.valid_at(time))
+        {
+            return Ok(id_cert.clone());
+        }
+        log::trace!(
+            "[CertResolver::server_cert()] Fetching certificate of {}",
+            domain
+        );
+        let id_cert = home_server
+            .client()
+            .get_server_id_cert::<S, P>(Some(time))
+            .await?;
+        check_domain(&id_cert, &domain)?;
+        if !home_server
+            .trusted_keys()
+            .contains(&id_cert.id_cert_tbs.subject_public_key)
+        {
+            return Err(ConversionError::from(InvalidCert::PublicKeyError(
+                PublicKeyError::BadSignature,
+            ))
+            .into());
+        }
+        self.lock()
+            .servers
+            .insert(domain, id_cert.clone(), generation);
+        Ok(id_cert)
+    }
+
+    /// Removes the cached certificate of the actor `federation_id`. Returns `true`, if one was
+    /// cached.
+    pub fn invalidate(&self, federation_id: &FederationId) -> bool {
+        self.lock().actors.remove(federation_id).is_some()
+    }
+
+    /// Removes all cached certificates. Registered and discovered home servers are kept.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.actors.clear();
+        state.servers.clear();
+    }
+
+    /// Returns the [HomeServer] of `domain`, discovering it if necessary.
+    async fn route(&self, domain: &str, time: Timestamp) -> HttpResult<HomeServer<S, P>> {
+        if let Some(home_server) = self.home_server(domain) {
+            return Ok(home_server);
+        }
+        match &self.discovery {
+            Some(template) => self.discover(template, domain, time).await,
+            None => {
+                log::debug!(
+                    "[CertResolver::route()] No home server configured for {}",
+                    domain
+                );
+                Err(RequestError::UnknownHomeServer(domain.to_string()))
+            }
+        }
+    }
+
+    async fn discover(
+        &self,
+        template: &str,
+        domain: &str,
+        time: Timestamp,
+    ) -> HttpResult<HomeServer<S, P>> {
+        let url = template.replace("{domain}", domain);
+        log::debug!(
+            "[CertResolver::discover()] Discovering home server {} at {}",
+            domain,
+            url
+        );
+        let mut home_server = HomeServer::new(HttpClient::new(&url)?);
+        // The certificate is verified to be self-signed and valid at `time`.
+        let id_cert = home_server
+            .client()
+            .get_server_id_cert::<S, P>(Some(time))
+            .await?;
+        check_domain(&id_cert, domain)?;
+        home_server.trust(id_cert.id_cert_tbs.subject_public_key.clone());
+        let mut state = self.lock();
+        // Another task may have registered the home server in the meantime.
+        if let Some(existing) = state.multi.get(domain) {
+            return Ok(existing.clone());
+        }
+        state.multi.insert(domain, home_server.clone());
+        state
+            .servers
+            .insert(domain.to_string(), id_cert, generation_of(&home_server));
+        Ok(home_server)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<S, P>> {
+        // The lock is never held across an await point.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn generation_of<S: Signature, P: PublicKey<S>>(home_server: &HomeServer<S, P>) -> CacheGeneration {
+    CacheGeneration::new().with_trust_store(home_server.trust_store_generation())
+}
+
+/// Checks, that `id_cert` was issued for the home server of `domain`.
+fn check_domain<S: Signature, P: PublicKey<S>>(
+    id_cert: &IdCert<S, P>,
+    domain: &str,
+) -> HttpResult<()> {
+    let expected = Name::from_str(&format!(
+        "DC={}",
+        domain.split('.').collect::<Vec<_>>().join(",DC=")
+    ))
+    .map_err(ConversionError::from)?;
+    if equal_domain_components(&id_cert.id_cert_tbs.subject, &expected) {
+        return Ok(());
+    }
+    Err(
+        ConversionError::from(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+            Some(format!(
+                "The certificate of the home server was not issued for {}",
+                domain
+            )),
+        )))
+        .into(),
+    )
+}
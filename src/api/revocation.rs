@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::{Mutex, MutexGuard};
+
+use rand_core::CryptoRngCore;
+
+use crate::certs::idcert::IdCert;
+use crate::certs::status::{RevocationCheck, RevocationStatus, StatusFuture, StatusRequest};
+use crate::errors::ConversionError;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::{HttpClient, HttpResult};
+
+#[derive(Debug)]
+/// Queries the revocation status of certificates from the revocation endpoint of the home server
+/// which issued them, and verifies the signed answers.
+///
+/// Every request carries a fresh nonce drawn from the random number generator of the checker, so
+/// that a recorded answer cannot be replayed. A [RevocationChecker] implements [RevocationCheck],
+/// and can therefore be passed to
+/// [IdCertChain::validate_chain_with_revocation()](crate::certs::chain::IdCertChain::validate_chain_with_revocation()).
+pub struct RevocationChecker<R: CryptoRngCore> {
+    client: HttpClient,
+    rng: Mutex<R>,
+}
+
+impl<R: CryptoRngCore> RevocationChecker<R> {
+    /// Creates a new [RevocationChecker], sending requests through `client` and drawing nonces
+    /// from `rng`.
+    pub fn new(client: HttpClient, rng: R) -> Self {
+        Self {
+            client,
+            rng: Mutex::new(rng),
+        }
+    }
+
+    /// Returns the [HttpClient] of the checker.
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// Requests the revocation status of `cert` at `time`, and verifies the answer using `issuer`,
+    /// the certificate of the home server which issued `cert`. Fails with
+    /// [RequestError::ConversionError](crate::errors::RequestError::ConversionError), if the
+    /// answer does not verify.
+    pub async fn check<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+        issuer: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> HttpResult<RevocationStatus> {
+        let request = StatusRequest::fresh(cert, &mut *self.lock())?;
+        log::trace!(
+            "[RevocationChecker::check()] Requesting revocation status of {}",
+            request.cert_id
+        );
+        let response = self.client.get_revocation_status::<S>(&request).await?;
+        response
+            .verify(&request, issuer, time)
+            .map_err(ConversionError::from)?;
+        Ok(response.status)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, R> {
+        // The lock is never held across an await point.
+        self.rng
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S, P, R> RevocationCheck<S, P> for RevocationChecker<R>
+where
+    S: Signature + Send + Sync,
+    P: PublicKey<S> + Send + Sync,
+    R: CryptoRngCore + Send,
+{
+    /// Like [RevocationChecker::check()], but reports [RevocationStatus::Unknown] if the request
+    /// fails or the answer does not verify.
+    fn status<'a>(
+        &'a self,
+        cert: &'a IdCert<S, P>,
+        issuer: &'a IdCert<S, P>,
+        time: Timestamp,
+    ) -> StatusFuture<'a> {
+        Box::pin(async move {
+            match self.check(cert, issuer, time).await {
+                Ok(status) => status,
+                Err(error) => {
+                    log::debug!(
+                        "[RevocationChecker::status()] Failed to check revocation status: {}",
+                        error
+                    );
+                    RevocationStatus::Unknown
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,424 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::RequestError;
+
+use super::HttpResult;
+
+/// The value secrets are replaced with when recording interactions.
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A request, as recorded by a [Vcr].
+pub struct RecordedRequest {
+    /// The HTTP method of the request.
+    pub method: String,
+    /// The route of the request, relative to the base URL of the client.
+    pub path: String,
+    /// The body of the request, with secrets redacted.
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A response, as recorded by a [Vcr].
+pub struct RecordedResponse {
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// The headers of the response, with secrets redacted.
+    pub headers: Vec<(String, String)>,
+    /// The body of the response, with secrets redacted.
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A single request and the response it received.
+pub struct Interaction {
+    /// The recorded request.
+    pub request: RecordedRequest,
+    /// The recorded response.
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Describes which parts of recorded interactions contain secrets, and are replaced with
+/// [REDACTED] before being stored.
+///
+/// By default, the `Authorization`, `Cookie` and `Set-Cookie` headers, as well as all JSON object
+/// fields named `token`, are redacted.
+pub struct Redaction {
+    /// Names of headers to redact. Compared case-insensitively.
+    pub headers: Vec<String>,
+    /// Names of JSON object fields to redact, at any depth of a JSON request or response body.
+    pub json_fields: Vec<String>,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self {
+            headers: vec![
+                "authorization".to_string(),
+                "cookie".to_string(),
+                "set-cookie".to_string(),
+            ],
+            json_fields: vec!["token".to_string()],
+        }
+    }
+}
+
+impl Redaction {
+    /// Adds a header to redact.
+    pub fn header(mut self, name: &str) -> Self {
+        self.headers.push(name.to_lowercase());
+        self
+    }
+
+    /// Adds a JSON object field to redact.
+    pub fn json_field(mut self, name: &str) -> Self {
+        self.json_fields.push(name.to_string());
+        self
+    }
+
+    /// Redacts the configured JSON object fields in `body`. Bodies which are not valid JSON are
+    /// returned unchanged.
+    pub fn redact_body(&self, body: &str) -> String {
+        let mut value = match serde_json::from_str::<Value>(body) {
+            Ok(value) => value,
+            Err(_) => return body.to_string(),
+        };
+        if self.redact_value(&mut value) {
+            value.to_string()
+        } else {
+            body.to_string()
+        }
+    }
+
+    fn redact_header(&self, name: &str, value: &str) -> String {
+        match self
+            .headers
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name))
+        {
+            true => REDACTED.to_string(),
+            false => value.to_string(),
+        }
+    }
+
+    /// Returns `true`, if anything was redacted.
+    fn redact_value(&self, value: &mut Value) -> bool {
+        let mut redacted = false;
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.json_fields.contains(key) {
+                        *value = Value::String(REDACTED.to_string());
+                        redacted = true;
+                    } else {
+                        redacted |= self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values.iter_mut() {
+                    redacted |= self.redact_value(value);
+                }
+            }
+            _ => (),
+        }
+        redacted
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+/// A recording of HTTP interactions, which can be stored as JSON and replayed by a [Vcr].
+pub struct Cassette {
+    /// The [Redaction] which was applied when recording. It is also applied to requests before
+    /// they are matched against the recording.
+    pub redaction: Redaction,
+    /// The recorded interactions, in the order in which they happened.
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Serializes this cassette to a JSON string.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Deserializes a cassette from a JSON string.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Reads a cassette from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json).map_err(std::io::Error::from)
+    }
+
+    /// Writes this cassette to a JSON file, replacing the file if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json().map_err(std::io::Error::from)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// How requests are matched against recorded interactions when replaying a [Cassette].
+pub enum MatchMode {
+    #[default]
+    /// Requests must arrive in the recorded order, and must match the method, path and redacted
+    /// body of the recorded request exactly.
+    Strict,
+    /// A request is answered with the first not yet replayed interaction with the same method and
+    /// path. Request bodies and the order of requests are ignored.
+    Lenient,
+}
+
+#[derive(Debug)]
+enum VcrState {
+    Recording,
+    Replaying {
+        mode: MatchMode,
+        replayed: Vec<bool>,
+    },
+}
+
+#[derive(Debug)]
+struct VcrInner {
+    cassette: Cassette,
+    state: VcrState,
+}
+
+#[derive(Debug, Clone)]
+/// Records the HTTP interactions of an [HttpClient](super::HttpClient), or replays previously
+/// recorded ones instead of contacting a server.
+///
+/// Set a [Vcr] on a client using [HttpClient::set_vcr()](super::HttpClient::set_vcr()). All
+/// requests made through the route methods of the client are then recorded or replayed. Clones of
+/// a [Vcr] share the same [Cassette].
+///
+/// ## Example
+///
+/// ```rs
+/// // Record once against a live server...
+/// let vcr = Vcr::record(Redaction::default());
+/// client.set_vcr(Some(vcr.clone()));
+/// client.get_challenge_string().await?;
+/// vcr.cassette().save("tests/cassettes/challenge.json")?;
+///
+/// // ...and replay the recording in tests.
+/// let cassette = Cassette::load("tests/cassettes/challenge.json")?;
+/// client.set_vcr(Some(Vcr::replay(cassette, MatchMode::Strict)));
+/// client.get_challenge_string().await?;
+/// ```
+pub struct Vcr {
+    inner: Arc<Mutex<VcrInner>>,
+}
+
+impl Vcr {
+    /// Creates a [Vcr] which records all interactions, applying the given [Redaction].
+    pub fn record(redaction: Redaction) -> Self {
+        Self::from_inner(VcrInner {
+            cassette: Cassette {
+                redaction,
+                interactions: Vec::new(),
+            },
+            state: VcrState::Recording,
+        })
+    }
+
+    /// Creates a [Vcr] which replays the interactions of `cassette`, matching requests according
+    /// to `mode`.
+    pub fn replay(cassette: Cassette, mode: MatchMode) -> Self {
+        let replayed = vec![false; cassette.interactions.len()];
+        Self::from_inner(VcrInner {
+            cassette,
+            state: VcrState::Replaying { mode, replayed },
+        })
+    }
+
+    /// Returns `true`, if this [Vcr] records interactions, and `false`, if it replays them.
+    pub fn is_recording(&self) -> bool {
+        matches!(self.lock().state, VcrState::Recording)
+    }
+
+    /// Returns a copy of the [Cassette], including all interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.lock().cassette.clone()
+    }
+
+    /// Returns the number of recorded interactions which have not been replayed yet. Always `0`
+    /// when recording.
+    pub fn remaining(&self) -> usize {
+        match &self.lock().state {
+            VcrState::Recording => 0,
+            VcrState::Replaying { replayed, .. } => {
+                replayed.iter().filter(|replayed| !**replayed).count()
+            }
+        }
+    }
+
+    /// Builds the [RecordedRequest] for a request, applying the [Redaction] of the cassette.
+    pub(crate) fn request(
+        &self,
+        method: &reqwest::Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> RecordedRequest {
+        let inner = self.lock();
+        RecordedRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.map(|body| inner.cassette.redaction.redact_body(body)),
+        }
+    }
+
+    /// Records `response` as the answer to `request`, returning an equivalent response for the
+    /// caller to consume. The returned response is not redacted.
+    pub(crate) async fn record_response(
+        &self,
+        request: RecordedRequest,
+        response: reqwest::Response,
+    ) -> HttpResult<reqwest::Response> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        {
+            let mut inner = self.lock();
+            let redaction = &inner.cassette.redaction;
+            let recorded = RecordedResponse {
+                status: status.as_u16(),
+                headers: headers
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = String::from_utf8_lossy(value.as_bytes());
+                        (
+                            name.to_string(),
+                            redaction.redact_header(name.as_str(), &value),
+                        )
+                    })
+                    .collect(),
+                body: redaction.redact_body(&String::from_utf8_lossy(&body)),
+            };
+            inner.cassette.interactions.push(Interaction {
+                request,
+                response: recorded,
+            });
+        }
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers.iter() {
+            builder = builder.header(name, value);
+        }
+        Ok(reqwest::Response::from(builder.body(body).map_err(
+            |e| RequestError::ReplayError(format!("Could not rebuild recorded response: {}", e)),
+        )?))
+    }
+
+    /// Returns the recorded response for `request`. Fails, if no recorded interaction matches the
+    /// request.
+    pub(crate) fn replay_response(
+        &self,
+        request: &RecordedRequest,
+    ) -> HttpResult<reqwest::Response> {
+        let mut inner = self.lock();
+        let VcrInner { cassette, state } = &mut *inner;
+        let (mode, replayed) = match state {
+            VcrState::Replaying { mode, replayed } => (*mode, replayed),
+            VcrState::Recording => {
+                return Err(RequestError::ReplayError(
+                    "The Vcr is recording, not replaying".to_string(),
+                ))
+            }
+        };
+        let index = match mode {
+            MatchMode::Strict => replayed
+                .iter()
+                .position(|replayed| !*replayed)
+                .filter(|index| &cassette.interactions[*index].request == request),
+            MatchMode::Lenient => {
+                cassette
+                    .interactions
+                    .iter()
+                    .enumerate()
+                    .position(|(index, interaction)| {
+                        !replayed[index]
+                            && interaction.request.method == request.method
+                            && interaction.request.path == request.path
+                    })
+            }
+        };
+        let index = match index {
+            Some(index) => index,
+            None => {
+                log::debug!(
+                    "[Vcr::replay_response()] No recorded interaction matches {} {}",
+                    request.method,
+                    request.path
+                );
+                return Err(RequestError::ReplayError(format!(
+                    "No recorded interaction matches {} {}",
+                    request.method, request.path
+                )));
+            }
+        };
+        replayed[index] = true;
+        let recorded = &cassette.interactions[index].response;
+        let mut builder = http::Response::builder().status(recorded.status);
+        for (name, value) in recorded.headers.iter() {
+            builder = builder.header(name, value);
+        }
+        match builder.body(recorded.body.clone()) {
+            Ok(response) => Ok(reqwest::Response::from(response)),
+            Err(e) => Err(RequestError::ReplayError(format!(
+                "Recorded response is invalid: {}",
+                e
+            ))),
+        }
+    }
+
+    fn from_inner(inner: VcrInner) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VcrInner> {
+        match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redact_nested_json_fields() {
+        let redaction = Redaction::default().json_field("secret");
+        let body = r#"{"token":"abc","nested":[{"secret":"def","public":"ghi"}]}"#;
+        let redacted: Value = serde_json::from_str(&redaction.redact_body(body)).unwrap();
+        assert_eq!(redacted["token"], REDACTED);
+        assert_eq!(redacted["nested"][0]["secret"], REDACTED);
+        assert_eq!(redacted["nested"][0]["public"], "ghi");
+        assert_eq!(redaction.redact_body("not json"), "not json");
+    }
+
+    #[test]
+    fn redact_headers_case_insensitively() {
+        let redaction = Redaction::default().header("X-Api-Key");
+        assert_eq!(
+            redaction.redact_header("Authorization", "Bearer a"),
+            REDACTED
+        );
+        assert_eq!(redaction.redact_header("x-api-key", "b"), REDACTED);
+        assert_eq!(redaction.redact_header("content-type", "c"), "c");
+    }
+}
@@ -0,0 +1,396 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::{BitString, Uint, UtcTime};
+use proptest::prelude::*;
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha256};
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
+use x509_cert::name::Name;
+use x509_cert::time::{Time, Validity};
+
+use crate::certs::capabilities::{Capabilities, ExtendedKeyUsage};
+use crate::certs::idcert::IdCert;
+use crate::certs::idcsr::IdCsr;
+use crate::certs::{actor_name, PublicKeyInfo, Target};
+use crate::errors::{ConversionError, InvalidCert, PublicKeyError};
+use crate::key::{KeyGen, PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::types::{FederationId, SessionId};
+
+/// The OID of the [DummySignature] algorithm. It is placed below the reserved private enterprise
+/// number 0, so that it is not used by any real signature algorithm.
+pub const OID_DUMMY_SIGNATURE: &str = "1.3.6.1.4.1.0.1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The signature of the dummy signature backend: the SHA-256 hash of the public key of the signer
+/// and the signed data. Creating and verifying dummy signatures is cheap, which keeps property
+/// tests fast, but they do not protect anything: anyone knowing the public key can create them.
+pub struct DummySignature {
+    digest: [u8; 32],
+}
+
+impl DummySignature {
+    fn create(public_key: &[u8; 32], data: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(public_key);
+        hasher.update(data);
+        Self {
+            digest: hasher.finalize().into(),
+        }
+    }
+}
+
+impl std::fmt::Display for DummySignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.digest)
+    }
+}
+
+impl Signature for DummySignature {
+    type Signature = [u8; 32];
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.digest
+    }
+
+    fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::new_unwrap(OID_DUMMY_SIGNATURE),
+            parameters: None,
+        }
+    }
+
+    /// Creates a [DummySignature] from its 32 byte encoding. Inputs of a different length are
+    /// truncated or padded with zeroes.
+    fn from_bytes(signature: &[u8]) -> Self {
+        let mut digest = [0u8; 32];
+        let length = signature.len().min(32);
+        digest[..length].copy_from_slice(&signature[..length]);
+        Self { digest }
+    }
+}
+
+impl SignatureBitStringEncoding for DummySignature {
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        BitString::from_bytes(&self.digest)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The private key of the dummy signature backend, see [DummySignature]. Only use it in tests.
+pub struct DummyPrivateKey {
+    public_key: DummyPublicKey,
+}
+
+impl DummyPrivateKey {
+    /// Creates the private key whose public key consists of `bytes`.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self {
+            public_key: DummyPublicKey { key: bytes },
+        }
+    }
+}
+
+impl PrivateKey<DummySignature> for DummyPrivateKey {
+    type PublicKey = DummyPublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> DummySignature {
+        DummySignature::create(&self.public_key.key, data)
+    }
+}
+
+impl KeyGen<DummySignature> for DummyPrivateKey {
+    fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        let mut bytes = [0u8; 32];
+        rng.fill_bytes(&mut bytes);
+        Self::from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for DummyPrivateKey {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<[u8; 32]>().prop_map(Self::from_bytes).boxed()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The public key of the dummy signature backend, see [DummySignature].
+pub struct DummyPublicKey {
+    key: [u8; 32],
+}
+
+impl PublicKey<DummySignature> for DummyPublicKey {
+    fn verify_signature(
+        &self,
+        signature: &DummySignature,
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        match DummySignature::create(&self.key, data) == *signature {
+            true => Ok(()),
+            false => Err(PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: DummySignature::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&self.key).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError> {
+        if public_key_info.algorithm != DummySignature::algorithm_identifier() {
+            return Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into());
+        }
+        match public_key_info.public_key_bitstring.raw_bytes().try_into() {
+            Ok(key) => Ok(Self { key }),
+            Err(_) => Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into()),
+        }
+    }
+}
+
+/// The local part of a [FederationId], also used as the common name of actors.
+const LOCAL_NAME_REGEX: &str = "[a-z0-9][a-z0-9_-]{0,15}";
+/// A domain of one to three labels.
+const DOMAIN_REGEX: &str = "[a-z][a-z0-9]{0,9}(\\.[a-z][a-z0-9]{0,9}){0,2}";
+
+impl Arbitrary for FederationId {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            "[a-z0-9][a-z0-9._%+-]{0,15}",
+            "[a-z0-9]([a-z0-9-]{0,8}[a-z0-9])?(\\.[a-z0-9]([a-z0-9-]{0,8}[a-z0-9])?){0,2}",
+        )
+            .prop_filter_map("not a valid federation ID", |(local_name, domain)| {
+                let id = format!("{}@{}", local_name, domain);
+                FederationId::new(&id)
+                    .ok()
+                    .filter(|federation_id| federation_id.as_str() == id)
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for SessionId {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        "[a-zA-Z0-9._-]{1,32}"
+            .prop_map(|id| SessionId::new_validated(&id).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for Capabilities {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            capabilities(Target::Actor),
+            capabilities(Target::HomeServer)
+        ]
+        .boxed()
+    }
+}
+
+/// Generates [Capabilities] which are valid for `target`.
+pub fn capabilities(target: Target) -> BoxedStrategy<Capabilities> {
+    match target {
+        Target::Actor => (
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(content_commitment, key_encipherment, data_encipherment, key_agreement, eku)| {
+                    let mut builder = Capabilities::builder();
+                    builder = match content_commitment {
+                        true => builder.with_content_commitment(),
+                        false => builder.with_digital_signature(),
+                    };
+                    if key_encipherment {
+                        builder = builder.with_key_encipherment();
+                    }
+                    if data_encipherment {
+                        builder = builder.with_data_encipherment();
+                    }
+                    if key_agreement {
+                        builder = builder.with_key_agreement();
+                    }
+                    if eku {
+                        builder = builder.with_extended_key_usage(ExtendedKeyUsage::ClientAuth);
+                    }
+                    builder.build().unwrap()
+                },
+            )
+            .boxed(),
+        Target::HomeServer => (any::<bool>(), any::<bool>())
+            .prop_map(|(crl_sign, eku)| {
+                let mut builder = Capabilities::builder().with_ca(Some(0));
+                if crl_sign {
+                    builder = builder.with_crl_sign();
+                }
+                if eku {
+                    builder = builder.with_extended_key_usage(ExtendedKeyUsage::ServerAuth);
+                }
+                builder.build().unwrap()
+            })
+            .boxed(),
+    }
+}
+
+/// Generates [Validity] periods of one second to ten years, starting between 1970 and 2030.
+pub fn validity() -> impl Strategy<Value = Validity> {
+    (0u64..1_900_000_000, 1u64..315_360_000).prop_map(|(not_before, lifetime)| Validity {
+        not_before: utc_time(not_before),
+        not_after: utc_time(not_before + lifetime),
+    })
+}
+
+fn utc_time(unix_seconds: u64) -> Time {
+    Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(unix_seconds)).unwrap())
+}
+
+/// Generates subject [Name]s which are valid for `target`.
+pub fn subject(target: Target) -> BoxedStrategy<Name> {
+    match target {
+        Target::Actor => (LOCAL_NAME_REGEX, DOMAIN_REGEX, any::<SessionId>())
+            .prop_map(|(local_name, domain, session_id)| {
+                actor_name(&local_name, &domain, &session_id).unwrap()
+            })
+            .boxed(),
+        Target::HomeServer => DOMAIN_REGEX.prop_map(|domain| domain_name(&domain)).boxed(),
+    }
+}
+
+fn domain_name(domain: &str) -> Name {
+    let components = domain
+        .split('.')
+        .map(|component| format!("DC={}", component))
+        .collect::<Vec<_>>();
+    Name::from_str(&components.join(",")).unwrap()
+}
+
+/// Generates serial numbers of one to 16 bytes, which are positive and not zero.
+pub fn serial_number() -> impl Strategy<Value = Uint> {
+    (1u8..=u8::MAX, prop::collection::vec(any::<u8>(), 0..16)).prop_map(|(first, rest)| {
+        let mut bytes = vec![first];
+        bytes.extend(rest);
+        Uint::new(&bytes).unwrap()
+    })
+}
+
+/// Generates [IdCsr]s for `target`, signed using keys generated by `key`. Pass the strategy of
+/// another [PrivateKey] type to test with a real signature backend, instead of the
+/// [DummySignature] backend used by the [Arbitrary] implementation of [IdCsr].
+pub fn id_csr<S, K>(
+    target: Target,
+    key: impl Strategy<Value = K> + 'static,
+) -> BoxedStrategy<IdCsr<S, K::PublicKey>>
+where
+    S: Signature + std::fmt::Debug + 'static,
+    K: PrivateKey<S> + std::fmt::Debug + 'static,
+    K::PublicKey: std::fmt::Debug,
+{
+    (subject(target), key, capabilities(target))
+        .prop_map(move |(subject, key, capabilities)| {
+            IdCsr::new(&subject, &key, &capabilities, Some(target)).unwrap()
+        })
+        .boxed()
+}
+
+/// Generates [IdCert]s for `target`, issued by a home server using keys generated by `key`. Home
+/// server certificates are self-signed. See [id_csr()] for using another signature backend.
+pub fn id_cert<S, K>(
+    target: Target,
+    key: impl Strategy<Value = K> + Clone + 'static,
+) -> BoxedStrategy<IdCert<S, K::PublicKey>>
+where
+    S: Signature + std::fmt::Debug + 'static,
+    K: PrivateKey<S> + std::fmt::Debug + 'static,
+    K::PublicKey: std::fmt::Debug,
+{
+    (
+        DOMAIN_REGEX,
+        LOCAL_NAME_REGEX,
+        any::<SessionId>(),
+        key.clone(),
+        key,
+        capabilities(target),
+        serial_number(),
+        validity(),
+    )
+        .prop_map(
+            move |(
+                domain,
+                local_name,
+                session_id,
+                subject_key,
+                issuer_key,
+                capabilities,
+                serial,
+                validity,
+            )| {
+                // The issuer is the home server of the domain of the subject.
+                let issuer = domain_name(&domain);
+                match target {
+                    Target::Actor => {
+                        let subject = actor_name(&local_name, &domain, &session_id).unwrap();
+                        let csr = IdCsr::new(&subject, &subject_key, &capabilities, Some(target))
+                            .unwrap();
+                        IdCert::from_actor_csr(csr, &issuer_key, serial, issuer, validity).unwrap()
+                    }
+                    Target::HomeServer => {
+                        let csr =
+                            IdCsr::new(&issuer, &subject_key, &capabilities, Some(target)).unwrap();
+                        IdCert::from_ca_csr(csr, &subject_key, serial, issuer, validity).unwrap()
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+impl Arbitrary for IdCsr<DummySignature, DummyPublicKey> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            id_csr(Target::Actor, any::<DummyPrivateKey>()),
+            id_csr(Target::HomeServer, any::<DummyPrivateKey>()),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for IdCert<DummySignature, DummyPublicKey> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            id_cert(Target::Actor, any::<DummyPrivateKey>()),
+            id_cert(Target::HomeServer, any::<DummyPrivateKey>()),
+        ]
+        .boxed()
+    }
+}
@@ -0,0 +1,281 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use aws_lc_rs::signature::{Ed25519KeyPair, KeyPair, ParsedPublicKey, ED25519};
+use der::asn1::BitString;
+use rand_core::CryptoRngCore;
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
+
+use crate::certs::PublicKeyInfo;
+use crate::errors::{ConversionError, InvalidCert, InvalidInput, PublicKeyError};
+use crate::key::{KeyGen, PrivateKey, PublicKey};
+use crate::signature::Signature;
+
+/// The OID of the Ed25519 signature algorithm, as defined in RFC 8410.
+pub const OID_ED25519: &str = "1.3.101.112";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// An Ed25519 signature.
+pub struct Ed25519Signature {
+    signature: [u8; 64],
+}
+
+impl std::fmt::Display for Ed25519Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.signature)
+    }
+}
+
+impl Signature for Ed25519Signature {
+    /// The 64 byte encoding of the signature.
+    type Signature = [u8; 64];
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_ED25519).unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Creates an [Ed25519Signature] from its 64 byte encoding. Inputs of a different length are
+    /// truncated or padded with zeroes, which results in a signature that will not verify.
+    fn from_bytes(signature: &[u8]) -> Self {
+        let mut signature_array = [0u8; 64];
+        let length = signature.len().min(64);
+        signature_array[..length].copy_from_slice(&signature[..length]);
+        Self {
+            signature: signature_array,
+        }
+    }
+}
+
+impl SignatureBitStringEncoding for Ed25519Signature {
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        BitString::from_bytes(&self.signature)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An Ed25519 private key held by aws-lc, together with its [Ed25519PublicKey].
+///
+/// Two private keys are considered equal, if their public keys are equal.
+pub struct Ed25519PrivateKey {
+    public_key: Ed25519PublicKey,
+    key: Arc<Ed25519KeyPair>,
+}
+
+impl PartialEq for Ed25519PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key == other.public_key
+    }
+}
+
+impl Eq for Ed25519PrivateKey {}
+
+impl Ed25519PrivateKey {
+    /// Generates a new key pair using the random number generator of aws-lc.
+    ///
+    /// ## Panics
+    ///
+    /// Panics, if aws-lc is unable to generate a key, which only happens if the module is in an
+    /// error state, e.g. after a failed FIPS self-test.
+    pub fn gen_keypair() -> Self {
+        Self::from_key_pair(
+            Ed25519KeyPair::generate().expect("aws-lc failed to generate an Ed25519 key pair"),
+        )
+    }
+
+    /// Creates a private key from its 32 byte seed.
+    pub fn from_seed(seed: &[u8; 32]) -> Result<Self, ConversionError> {
+        match Ed25519KeyPair::from_seed_unchecked(seed) {
+            Ok(key) => Ok(Self::from_key_pair(key)),
+            Err(_) => Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into()),
+        }
+    }
+
+    /// Creates a private key from a DER encoded PKCS#8 v1 or v2 document.
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, ConversionError> {
+        match Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+            Ok(key) => Ok(Self::from_key_pair(key)),
+            Err(_) => Err(InvalidInput::Malformed(
+                "Input is not a valid Ed25519 PKCS#8 document".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    /// Returns the DER encoded PKCS#8 v1 document of this private key.
+    pub fn to_pkcs8(&self) -> Result<Vec<u8>, ConversionError> {
+        match self.key.to_pkcs8v1() {
+            Ok(document) => Ok(document.as_ref().to_vec()),
+            Err(_) => Err(InvalidInput::Malformed(
+                "aws-lc failed to encode the private key as PKCS#8".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    fn from_key_pair(key: Ed25519KeyPair) -> Self {
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(key.public_key().as_ref());
+        Self {
+            public_key: Ed25519PublicKey { key: public_key },
+            key: Arc::new(key),
+        }
+    }
+}
+
+impl PrivateKey<Ed25519Signature> for Ed25519PrivateKey {
+    type PublicKey = Ed25519PublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> Ed25519Signature {
+        Ed25519Signature::from_bytes(self.key.sign(data).as_ref())
+    }
+}
+
+impl KeyGen<Ed25519Signature> for Ed25519PrivateKey {
+    /// Generates a new key pair. To stay within the FIPS boundary, the key is generated using the
+    /// random number generator of aws-lc; `rng` is not used.
+    fn generate(_rng: &mut impl CryptoRngCore) -> Self {
+        Self::gen_keypair()
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl crate::key::Pkcs8PrivateKey<Ed25519Signature> for Ed25519PrivateKey {
+    fn to_pkcs8_der(&self) -> Result<pkcs8::SecretDocument, ConversionError> {
+        Ok(pkcs8::SecretDocument::try_from(
+            self.to_pkcs8()?.as_slice(),
+        )?)
+    }
+
+    fn from_pkcs8_der(der: &[u8]) -> Result<Self, ConversionError> {
+        Self::from_pkcs8(der)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An Ed25519 public key.
+pub struct Ed25519PublicKey {
+    key: [u8; 32],
+}
+
+impl Ed25519PublicKey {
+    /// Creates a public key from its 32 byte encoding. Fails, if aws-lc rejects the key.
+    pub fn from_bytes(public_key: &[u8; 32]) -> Result<Self, PublicKeyError> {
+        match ParsedPublicKey::new(&ED25519, public_key) {
+            Ok(_) => Ok(Self { key: *public_key }),
+            Err(_) => Err(PublicKeyError::BadPublicKeyInfo),
+        }
+    }
+
+    /// Returns the 32 byte encoding of this public key.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.key
+    }
+}
+
+impl PublicKey<Ed25519Signature> for Ed25519PublicKey {
+    fn verify_signature(
+        &self,
+        signature: &Ed25519Signature,
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        let key = match ParsedPublicKey::new(&ED25519, self.key) {
+            Ok(key) => key,
+            Err(_) => return Err(PublicKeyError::BadPublicKeyInfo),
+        };
+        match key.verify_sig(data, signature.as_signature()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: Ed25519Signature::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&self.key).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError> {
+        if public_key_info.algorithm != Ed25519Signature::algorithm_identifier() {
+            return Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into());
+        }
+        let key_bytes = public_key_info.public_key_bitstring.raw_bytes();
+        let key_array: [u8; 32] = match key_bytes.try_into() {
+            Ok(array) => array,
+            Err(_) => {
+                return Err(InvalidInput::Length {
+                    min_length: 32,
+                    max_length: 32,
+                    actual_length: key_bytes.len().to_string(),
+                }
+                .into())
+            }
+        };
+        match Self::from_bytes(&key_array) {
+            Ok(key) => Ok(key),
+            Err(e) => Err(InvalidCert::PublicKeyError(e).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn sign_and_verify() {
+        let private_key = Ed25519PrivateKey::gen_keypair();
+        let signature = private_key.sign(b"polyproto");
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyproto")
+            .is_ok());
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyphony")
+            .is_err());
+        assert_eq!(
+            Ed25519PrivateKey::from_pkcs8(&private_key.to_pkcs8().unwrap()).unwrap(),
+            private_key
+        );
+        assert_eq!(
+            Ed25519PublicKey::try_from_public_key_info(private_key.pubkey().public_key_info())
+                .unwrap(),
+            *private_key.pubkey()
+        );
+    }
+
+    #[cfg(all(feature = "ed25519", not(feature = "fips")))]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn interoperates_with_rustcrypto_backend() {
+        let private_key = Ed25519PrivateKey::gen_keypair();
+        let signature = private_key.sign(b"polyproto");
+        let public_key = crate::backends::ed25519::Ed25519PublicKey::from_bytes(
+            &private_key.pubkey().to_bytes(),
+        )
+        .unwrap();
+        assert!(public_key
+            .verify_signature(
+                &crate::backends::ed25519::Ed25519Signature::from_bytes(signature.as_signature()),
+                b"polyproto"
+            )
+            .is_ok());
+    }
+}
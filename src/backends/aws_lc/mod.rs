@@ -0,0 +1,16 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// Ed25519, backed by aws-lc. Drop-in replacement for
+/// [backends::ed25519](crate::backends#modules) with the same type names.
+pub mod ed25519;
+/// ECDSA with the NIST P-256 curve and SHA-256, backed by aws-lc. Drop-in replacement for
+/// [backends::p256](crate::backends#modules) with the same type names.
+pub mod p256;
+
+/// Returns `true`, if aws-lc is running in FIPS mode, i.e. if polyproto was built with the `fips`
+/// feature and the FIPS power-on self-tests of the module have passed.
+pub fn fips_mode_active() -> bool {
+    aws_lc_rs::try_fips_mode().is_ok()
+}
@@ -0,0 +1,306 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use aws_lc_rs::rand::SystemRandom;
+use aws_lc_rs::signature::{
+    EcdsaKeyPair, KeyPair, ParsedPublicKey, ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_ASN1_SIGNING,
+};
+use der::asn1::BitString;
+use der::Any;
+use rand_core::CryptoRngCore;
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
+
+use crate::certs::PublicKeyInfo;
+use crate::encoding::encode_hex;
+use crate::errors::{ConversionError, InvalidCert, InvalidInput, PublicKeyError};
+use crate::key::{KeyGen, PrivateKey, PublicKey};
+use crate::signature::Signature;
+
+/// The OID of the `ecdsa-with-SHA256` signature algorithm, as defined in RFC 5758.
+pub const OID_ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+/// The OID of the `id-ecPublicKey` public key algorithm, as defined in RFC 5480.
+pub const OID_EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+/// The OID of the `secp256r1`/`prime256v1` named curve, as defined in RFC 5480.
+pub const OID_SECP256R1: &str = "1.2.840.10045.3.1.7";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// An ECDSA P-256 signature over the SHA-256 digest of the signed data.
+///
+/// The signature is stored in its DER encoding (`ECDSA-Sig-Value`), which is how it is embedded
+/// into X.509 certificates and CSRs.
+pub struct P256Signature {
+    signature: Vec<u8>,
+}
+
+impl std::fmt::Display for P256Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", encode_hex(&self.signature))
+    }
+}
+
+impl Signature for P256Signature {
+    /// The DER encoded `ECDSA-Sig-Value`.
+    type Signature = Vec<u8>;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    /// Returns the `ecdsa-with-SHA256` algorithm identifier. As mandated by RFC 5758, the
+    /// parameters are absent.
+    fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_ECDSA_WITH_SHA256).unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Creates a [P256Signature] from its DER encoding. The encoding is only checked when the
+    /// signature is verified.
+    fn from_bytes(signature: &[u8]) -> Self {
+        Self {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+impl SignatureBitStringEncoding for P256Signature {
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        BitString::from_bytes(&self.signature)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An ECDSA P-256 private key held by aws-lc, together with its [P256PublicKey].
+///
+/// Two private keys are considered equal, if their public keys are equal.
+pub struct P256PrivateKey {
+    public_key: P256PublicKey,
+    key: Arc<EcdsaKeyPair>,
+}
+
+impl PartialEq for P256PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key == other.public_key
+    }
+}
+
+impl Eq for P256PrivateKey {}
+
+impl P256PrivateKey {
+    /// Generates a new key pair using the random number generator of aws-lc.
+    ///
+    /// ## Panics
+    ///
+    /// Panics, if aws-lc is unable to generate a key, which only happens if the module is in an
+    /// error state, e.g. after a failed FIPS self-test.
+    pub fn gen_keypair() -> Self {
+        Self::from_key_pair(
+            EcdsaKeyPair::generate(&ECDSA_P256_SHA256_ASN1_SIGNING)
+                .expect("aws-lc failed to generate a P-256 key pair"),
+        )
+    }
+
+    /// Creates a private key from a DER encoded PKCS#8 document.
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, ConversionError> {
+        match EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, der) {
+            Ok(key) => Ok(Self::from_key_pair(key)),
+            Err(_) => Err(InvalidInput::Malformed(
+                "Input is not a valid P-256 PKCS#8 document".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    /// Returns the DER encoded PKCS#8 v1 document of this private key.
+    pub fn to_pkcs8(&self) -> Result<Vec<u8>, ConversionError> {
+        match self.key.to_pkcs8v1() {
+            Ok(document) => Ok(document.as_ref().to_vec()),
+            Err(_) => Err(InvalidInput::Malformed(
+                "aws-lc failed to encode the private key as PKCS#8".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    fn from_key_pair(key: EcdsaKeyPair) -> Self {
+        Self {
+            public_key: P256PublicKey {
+                key: key.public_key().as_ref().to_vec(),
+            },
+            key: Arc::new(key),
+        }
+    }
+}
+
+impl PrivateKey<P256Signature> for P256PrivateKey {
+    type PublicKey = P256PublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    /// Signs `data` using the random number generator of aws-lc.
+    ///
+    /// ## Panics
+    ///
+    /// Panics, if aws-lc is unable to create a signature, which only happens if the module is in
+    /// an error state, e.g. after a failed FIPS self-test.
+    fn sign(&self, data: &[u8]) -> P256Signature {
+        let signature = self
+            .key
+            .sign(&SystemRandom::new(), data)
+            .expect("aws-lc failed to create a P-256 signature");
+        P256Signature::from_bytes(signature.as_ref())
+    }
+}
+
+impl KeyGen<P256Signature> for P256PrivateKey {
+    /// Generates a new key pair. To stay within the FIPS boundary, the key is generated using the
+    /// random number generator of aws-lc; `rng` is not used.
+    fn generate(_rng: &mut impl CryptoRngCore) -> Self {
+        Self::gen_keypair()
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl crate::key::Pkcs8PrivateKey<P256Signature> for P256PrivateKey {
+    fn to_pkcs8_der(&self) -> Result<pkcs8::SecretDocument, ConversionError> {
+        Ok(pkcs8::SecretDocument::try_from(
+            self.to_pkcs8()?.as_slice(),
+        )?)
+    }
+
+    fn from_pkcs8_der(der: &[u8]) -> Result<Self, ConversionError> {
+        Self::from_pkcs8(der)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An ECDSA P-256 public key.
+pub struct P256PublicKey {
+    key: Vec<u8>,
+}
+
+impl P256PublicKey {
+    /// Creates a public key from its uncompressed SEC1 encoding. Fails, if the encoding is not
+    /// uncompressed, or if aws-lc rejects the key.
+    pub fn from_sec1_bytes(public_key: &[u8]) -> Result<Self, PublicKeyError> {
+        if public_key.len() != 65 {
+            return Err(PublicKeyError::BadPublicKeyInfo);
+        }
+        match ParsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, public_key) {
+            Ok(_) => Ok(Self {
+                key: public_key.to_vec(),
+            }),
+            Err(_) => Err(PublicKeyError::BadPublicKeyInfo),
+        }
+    }
+
+    /// Returns the uncompressed SEC1 encoding of this public key.
+    pub fn to_sec1_bytes(&self) -> Vec<u8> {
+        self.key.clone()
+    }
+
+    /// Returns the `id-ecPublicKey` algorithm identifier with the `secp256r1` named curve as its
+    /// parameters, as used in the `SubjectPublicKeyInfo` of P-256 keys.
+    pub fn public_key_algorithm_identifier() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_EC_PUBLIC_KEY).unwrap(),
+            parameters: Some(
+                Any::encode_from(&ObjectIdentifier::from_str(OID_SECP256R1).unwrap()).unwrap(),
+            ),
+        }
+    }
+}
+
+impl PublicKey<P256Signature> for P256PublicKey {
+    /// Verifies a DER encoded signature. Signatures which are not validly DER encoded are
+    /// rejected.
+    fn verify_signature(
+        &self,
+        signature: &P256Signature,
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        let key = match ParsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &self.key) {
+            Ok(key) => key,
+            Err(_) => return Err(PublicKeyError::BadPublicKeyInfo),
+        };
+        match key.verify_sig(data, signature.as_signature()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: Self::public_key_algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&self.key).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError> {
+        if public_key_info.algorithm != Self::public_key_algorithm_identifier() {
+            return Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into());
+        }
+        match Self::from_sec1_bytes(public_key_info.public_key_bitstring.raw_bytes()) {
+            Ok(key) => Ok(key),
+            Err(e) => Err(InvalidCert::PublicKeyError(e).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn sign_and_verify() {
+        let private_key = P256PrivateKey::gen_keypair();
+        let signature = private_key.sign(b"polyproto");
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyproto")
+            .is_ok());
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyphony")
+            .is_err());
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&P256Signature::from_bytes(&[0x30, 0x00]), b"polyproto")
+            .is_err());
+        assert_eq!(
+            P256PrivateKey::from_pkcs8(&private_key.to_pkcs8().unwrap()).unwrap(),
+            private_key
+        );
+        assert_eq!(
+            &P256PublicKey::try_from_public_key_info(private_key.pubkey().public_key_info())
+                .unwrap(),
+            private_key.pubkey()
+        );
+    }
+
+    #[cfg(all(feature = "p256", not(feature = "fips")))]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn interoperates_with_rustcrypto_backend() {
+        let private_key = P256PrivateKey::gen_keypair();
+        let signature = private_key.sign(b"polyproto");
+        let public_key = crate::backends::p256::P256PublicKey::try_from_public_key_info(
+            private_key.pubkey().public_key_info(),
+        )
+        .unwrap();
+        assert!(public_key
+            .verify_signature(
+                &crate::backends::p256::P256Signature::from_bytes(signature.as_signature()),
+                b"polyproto"
+            )
+            .is_ok());
+    }
+}
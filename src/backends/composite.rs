@@ -0,0 +1,370 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use der::asn1::{Any, BitString};
+use der::{Decode, Encode};
+use rand_core::CryptoRngCore;
+use spki::{
+    AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding,
+    SubjectPublicKeyInfoOwned,
+};
+
+use crate::certs::PublicKeyInfo;
+use crate::errors::{ConversionError, InvalidCert, InvalidInput, PublicKeyError};
+use crate::key::{KeyGen, PrivateKey, PublicKey};
+use crate::signature::Signature;
+
+/// The OID of the generic composite signature algorithm. Its parameters are a
+/// `SEQUENCE OF AlgorithmIdentifier`, listing the component algorithms in order.
+pub const OID_COMPOSITE_SIGNATURE: &str = "1.3.6.1.4.1.18227.2.1";
+/// The OID of the generic composite public key algorithm. The public key is a
+/// `SEQUENCE OF SubjectPublicKeyInfo`, listing the component keys in order.
+pub const OID_COMPOSITE_PUBLIC_KEY: &str = "2.16.840.1.114027.80.4.1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A composite signature, made up of two [Signature]s of different algorithms over the same data.
+///
+/// Composite signatures are meant for migration periods, where neither algorithm alone is trusted,
+/// for example when pairing a classical algorithm like Ed25519 with a post-quantum algorithm like
+/// ML-DSA. A [CompositeSignature] only verifies if both component signatures verify.
+///
+/// The signature value is encoded as a `SEQUENCE OF BIT STRING`, and the algorithm identifier
+/// returned by [Signature::algorithm_identifier()] is [OID_COMPOSITE_SIGNATURE], parameterized
+/// with the algorithm identifiers of `A` and `B`.
+pub struct CompositeSignature<A: Signature, B: Signature> {
+    signatures: (A, B),
+}
+
+impl<A: Signature, B: Signature> CompositeSignature<A, B> {
+    /// Creates a new [CompositeSignature] from its component signatures.
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            signatures: (first, second),
+        }
+    }
+
+    /// Returns the first component signature.
+    pub fn first(&self) -> &A {
+        &self.signatures.0
+    }
+
+    /// Returns the second component signature.
+    pub fn second(&self) -> &B {
+        &self.signatures.1
+    }
+}
+
+impl<A: Signature, B: Signature> std::fmt::Display for CompositeSignature<A, B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.signatures.0.to_string(),
+            self.signatures.1.to_string()
+        )
+    }
+}
+
+impl<A: Signature, B: Signature> Signature for CompositeSignature<A, B> {
+    type Signature = (A, B);
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signatures
+    }
+
+    fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        let components = vec![A::algorithm_identifier(), B::algorithm_identifier()];
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_COMPOSITE_SIGNATURE).unwrap(),
+            parameters: Some(Any::encode_from(&components).unwrap()),
+        }
+    }
+
+    /// Creates a [CompositeSignature] from its `SEQUENCE OF BIT STRING` encoding. If the input
+    /// cannot be decoded into exactly two bit strings, the component signatures are created from
+    /// empty byte slices, which results in a signature that will not verify.
+    fn from_bytes(signature: &[u8]) -> Self {
+        let components = match Vec::<BitString>::from_der(signature) {
+            Ok(components) if components.len() == 2 => components,
+            _ => {
+                log::warn!("[CompositeSignature::from_bytes()] Malformed composite signature");
+                return Self::new(A::from_bytes(&[]), B::from_bytes(&[]));
+            }
+        };
+        Self::new(
+            A::from_bytes(components[0].raw_bytes()),
+            B::from_bytes(components[1].raw_bytes()),
+        )
+    }
+}
+
+impl<A: Signature, B: Signature> SignatureBitStringEncoding for CompositeSignature<A, B> {
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        let components = vec![
+            self.signatures.0.to_bitstring()?,
+            self.signatures.1.to_bitstring()?,
+        ];
+        BitString::from_bytes(&components.to_der()?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A composite public key, made up of the public keys for both components of a
+/// [CompositeSignature].
+pub struct CompositePublicKey<A: Signature, B: Signature, PA: PublicKey<A>, PB: PublicKey<B>> {
+    keys: (PA, PB),
+    s: PhantomData<(A, B)>,
+}
+
+impl<A: Signature, B: Signature, PA: PublicKey<A>, PB: PublicKey<B>>
+    CompositePublicKey<A, B, PA, PB>
+{
+    /// Creates a new [CompositePublicKey] from its component public keys.
+    pub fn new(first: PA, second: PB) -> Self {
+        Self {
+            keys: (first, second),
+            s: PhantomData,
+        }
+    }
+
+    /// Returns the first component public key.
+    pub fn first(&self) -> &PA {
+        &self.keys.0
+    }
+
+    /// Returns the second component public key.
+    pub fn second(&self) -> &PB {
+        &self.keys.1
+    }
+}
+
+impl<A: Signature, B: Signature, PA: PublicKey<A>, PB: PublicKey<B>>
+    PublicKey<CompositeSignature<A, B>> for CompositePublicKey<A, B, PA, PB>
+{
+    /// Verifies both component signatures. Fails, if either of them does not verify.
+    fn verify_signature(
+        &self,
+        signature: &CompositeSignature<A, B>,
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        self.keys.0.verify_signature(signature.first(), data)?;
+        self.keys.1.verify_signature(signature.second(), data)
+    }
+
+    fn public_key_info(&self) -> PublicKeyInfo {
+        let components: Vec<SubjectPublicKeyInfoOwned> = vec![
+            self.keys.0.public_key_info().into(),
+            self.keys.1.public_key_info().into(),
+        ];
+        PublicKeyInfo {
+            algorithm: AlgorithmIdentifierOwned {
+                oid: ObjectIdentifier::from_str(OID_COMPOSITE_PUBLIC_KEY).unwrap(),
+                parameters: None,
+            },
+            public_key_bitstring: BitString::from_bytes(&components.to_der().unwrap()).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError> {
+        if public_key_info.algorithm.oid != ObjectIdentifier::from_str(OID_COMPOSITE_PUBLIC_KEY)? {
+            return Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into());
+        }
+        let components = Vec::<SubjectPublicKeyInfoOwned>::from_der(
+            public_key_info.public_key_bitstring.raw_bytes(),
+        )?;
+        let [first, second]: [SubjectPublicKeyInfoOwned; 2] = match components.try_into() {
+            Ok(components) => components,
+            Err(components) => {
+                return Err(InvalidInput::Length {
+                    min_length: 2,
+                    max_length: 2,
+                    actual_length: components.len().to_string(),
+                }
+                .into())
+            }
+        };
+        Ok(Self::new(
+            PA::try_from_public_key_info(first.into())?,
+            PB::try_from_public_key_info(second.into())?,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A composite private key, made up of the private keys for both components of a
+/// [CompositeSignature]. Signing produces a signature with each component key.
+pub struct CompositePrivateKey<A: Signature, B: Signature, KA: PrivateKey<A>, KB: PrivateKey<B>> {
+    keys: (KA, KB),
+    public_key: CompositePublicKey<A, B, KA::PublicKey, KB::PublicKey>,
+}
+
+impl<A: Signature, B: Signature, KA: PrivateKey<A>, KB: PrivateKey<B>>
+    CompositePrivateKey<A, B, KA, KB>
+{
+    /// Creates a new [CompositePrivateKey] from its component private keys.
+    pub fn new(first: KA, second: KB) -> Self {
+        let public_key = CompositePublicKey::new(first.pubkey().clone(), second.pubkey().clone());
+        Self {
+            keys: (first, second),
+            public_key,
+        }
+    }
+
+    /// Returns the first component private key.
+    pub fn first(&self) -> &KA {
+        &self.keys.0
+    }
+
+    /// Returns the second component private key.
+    pub fn second(&self) -> &KB {
+        &self.keys.1
+    }
+}
+
+impl<A: Signature, B: Signature, KA: PrivateKey<A>, KB: PrivateKey<B>>
+    PrivateKey<CompositeSignature<A, B>> for CompositePrivateKey<A, B, KA, KB>
+{
+    type PublicKey = CompositePublicKey<A, B, KA::PublicKey, KB::PublicKey>;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> CompositeSignature<A, B> {
+        CompositeSignature::new(self.keys.0.sign(data), self.keys.1.sign(data))
+    }
+}
+
+impl<A: Signature, B: Signature, KA: KeyGen<A>, KB: KeyGen<B>> KeyGen<CompositeSignature<A, B>>
+    for CompositePrivateKey<A, B, KA, KB>
+{
+    /// Generates both component keys using the same random number generator.
+    fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        Self::new(KA::generate(rng), KB::generate(rng))
+    }
+}
+
+#[cfg(all(test, feature = "ed25519", feature = "p256"))]
+mod test {
+    use der::asn1::Uint;
+    use x509_cert::name::Name;
+    use x509_cert::time::Validity;
+
+    use super::*;
+    use crate::backends::ed25519::{Ed25519PrivateKey, Ed25519Signature};
+    use crate::backends::p256::{P256PrivateKey, P256Signature};
+    use crate::certs::capabilities::Capabilities;
+    use crate::certs::idcert::IdCert;
+    use crate::certs::idcsr::IdCsr;
+    use crate::certs::Target;
+    use crate::timestamp::Timestamp;
+
+    type HybridSignature = CompositeSignature<Ed25519Signature, P256Signature>;
+    type HybridPrivateKey =
+        CompositePrivateKey<Ed25519Signature, P256Signature, Ed25519PrivateKey, P256PrivateKey>;
+
+    fn gen_keypair() -> HybridPrivateKey {
+        HybridPrivateKey::generate(&mut rand::rngs::OsRng)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn both_components_must_verify() {
+        let private_key = gen_keypair();
+        let signature = private_key.sign(b"polyproto");
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyproto")
+            .is_ok());
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyphony")
+            .is_err());
+
+        let other = gen_keypair();
+        let other_signature = other.sign(b"polyproto");
+        let forged_first =
+            CompositeSignature::new(*other_signature.first(), signature.second().clone());
+        let forged_second =
+            CompositeSignature::new(*signature.first(), other_signature.second().clone());
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&forged_first, b"polyproto")
+            .is_err());
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&forged_second, b"polyproto")
+            .is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn encoding_roundtrip() {
+        let private_key = gen_keypair();
+        let signature = private_key.sign(b"polyproto");
+        let restored = HybridSignature::from_bytes(signature.to_bitstring().unwrap().raw_bytes());
+        assert_eq!(restored, signature);
+        let malformed = HybridSignature::from_bytes(&[0x30, 0x00]);
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&malformed, b"polyproto")
+            .is_err());
+
+        let public_key_info = private_key.pubkey().public_key_info();
+        assert_eq!(
+            &CompositePublicKey::try_from_public_key_info(public_key_info).unwrap(),
+            private_key.pubkey()
+        );
+
+        let algorithm = HybridSignature::algorithm_identifier();
+        let components: Vec<AlgorithmIdentifierOwned> =
+            algorithm.parameters.unwrap().decode_as().unwrap();
+        assert_eq!(
+            components,
+            vec![
+                Ed25519Signature::algorithm_identifier(),
+                P256Signature::algorithm_identifier()
+            ]
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn issue_home_server_cert() {
+        let private_key = gen_keypair();
+        let subject = Name::from_str("DC=polyphony,DC=chat").unwrap();
+        let csr = IdCsr::new(
+            &subject,
+            &private_key,
+            &Capabilities::home_server_default(),
+            Some(Target::HomeServer),
+        )
+        .unwrap();
+        let validity = Validity::from_now(std::time::Duration::from_secs(60)).unwrap();
+        let cert = IdCert::from_ca_csr(
+            csr,
+            &private_key,
+            Uint::new(&[1]).unwrap(),
+            subject,
+            validity,
+        )
+        .unwrap();
+        assert_eq!(
+            cert.id_cert_tbs.signature_algorithm,
+            HybridSignature::algorithm_identifier()
+        );
+        let der = cert.to_der().unwrap();
+        let restored = IdCert::from_der(
+            &der,
+            Target::HomeServer,
+            Timestamp::now(),
+            private_key.pubkey(),
+        )
+        .unwrap();
+        assert_eq!(restored, cert);
+    }
+}
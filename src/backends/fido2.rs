@@ -0,0 +1,479 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{BitString, OctetString, Utf8StringRef};
+use der::{Any, Decode, Encode};
+use sha2::{Digest, Sha256};
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
+
+use crate::certs::PublicKeyInfo;
+use crate::encoding::encode_hex;
+use crate::errors::{ConversionError, InvalidCert, InvalidInput, PublicKeyError};
+use crate::key::{AsyncPrivateKey, PublicKey, SignFuture};
+use crate::signature::Signature;
+
+use super::p256::{P256PublicKey, P256Signature};
+
+/// The OID of the FIDO2 assertion signature algorithm: an ECDSA P-256 signature with SHA-256 over
+/// the authenticator data, followed by the SHA-256 digest of the signed data.
+pub const OID_FIDO2_ECDSA_WITH_SHA256: &str = "1.3.6.1.4.1.18227.2.2";
+/// The OID of the FIDO2 public key algorithm. Its parameters are the relying party ID of the
+/// credential, as a `UTF8String`.
+pub const OID_FIDO2_PUBLIC_KEY: &str = "1.3.6.1.4.1.18227.2.3";
+/// The "user present" flag of the authenticator data.
+pub const FLAG_USER_PRESENT: u8 = 0x01;
+/// The "user verified" flag of the authenticator data.
+pub const FLAG_USER_VERIFIED: u8 = 0x04;
+
+/// The length of the authenticator data without attested credential data and extensions: the
+/// SHA-256 digest of the relying party ID, one byte of flags and a four byte signature counter.
+const MIN_AUTHENTICATOR_DATA_LENGTH: usize = 37;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The result of a CTAP2 `authenticatorGetAssertion` operation.
+pub struct Fido2Assertion {
+    /// The authenticator data, as returned by the authenticator.
+    pub authenticator_data: Vec<u8>,
+    /// The DER encoded ECDSA signature over the authenticator data, followed by the client data
+    /// hash.
+    pub signature: Vec<u8>,
+}
+
+/// A FIDO2 authenticator, such as a hardware security key, holding a P-256 credential.
+///
+/// polyproto does not talk to authenticators itself. Implement this trait on top of a CTAP2
+/// library of your choice to use a security key as an [AsyncPrivateKey] through [Fido2Signer].
+pub trait Fido2Authenticator {
+    /// Performs `authenticatorGetAssertion` for the credential `credential_id` of the relying
+    /// party `rp_id`, using `client_data_hash` as the client data hash. Fails with
+    /// [ConversionError::SignerError], if the authenticator could not produce an assertion, e.g.
+    /// because it was removed or the user did not confirm their presence.
+    fn get_assertion(
+        &self,
+        rp_id: &str,
+        credential_id: &[u8],
+        client_data_hash: &[u8; 32],
+    ) -> Result<Fido2Assertion, ConversionError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A signature produced by a FIDO2 authenticator.
+///
+/// Authenticators do not sign arbitrary data. Instead, the SHA-256 digest of the data is passed
+/// to the authenticator as the client data hash, and the authenticator signs its authenticator
+/// data followed by that hash. A [Fido2Signature] therefore carries the authenticator data along
+/// with the ECDSA signature, and is only understood by verifiers supporting
+/// [OID_FIDO2_ECDSA_WITH_SHA256].
+///
+/// The signature value is encoded as a `SEQUENCE OF OCTET STRING`, containing the authenticator
+/// data and the DER encoded ECDSA signature.
+pub struct Fido2Signature {
+    signature: Fido2Assertion,
+}
+
+impl Fido2Signature {
+    /// Creates a [Fido2Signature] from a [Fido2Assertion].
+    pub fn new(assertion: Fido2Assertion) -> Self {
+        Self {
+            signature: assertion,
+        }
+    }
+
+    /// Returns the authenticator data of the assertion.
+    pub fn authenticator_data(&self) -> &[u8] {
+        &self.signature.authenticator_data
+    }
+
+    /// Returns the flags of the authenticator data, or `None` if the authenticator data is too
+    /// short.
+    pub fn flags(&self) -> Option<u8> {
+        self.signature.authenticator_data.get(32).copied()
+    }
+}
+
+impl std::fmt::Display for Fido2Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            encode_hex(&self.signature.authenticator_data),
+            encode_hex(&self.signature.signature)
+        )
+    }
+}
+
+impl Signature for Fido2Signature {
+    type Signature = Fido2Assertion;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_FIDO2_ECDSA_WITH_SHA256).unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Creates a [Fido2Signature] from its `SEQUENCE OF OCTET STRING` encoding. If the input
+    /// cannot be decoded into exactly two octet strings, an empty signature is created, which
+    /// will not verify.
+    fn from_bytes(signature: &[u8]) -> Self {
+        let components = match Vec::<OctetString>::from_der(signature) {
+            Ok(components) if components.len() == 2 => components,
+            _ => {
+                log::warn!("[Fido2Signature::from_bytes()] Malformed FIDO2 signature");
+                return Self::new(Fido2Assertion {
+                    authenticator_data: Vec::new(),
+                    signature: Vec::new(),
+                });
+            }
+        };
+        Self::new(Fido2Assertion {
+            authenticator_data: components[0].as_bytes().to_vec(),
+            signature: components[1].as_bytes().to_vec(),
+        })
+    }
+}
+
+impl SignatureBitStringEncoding for Fido2Signature {
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        let components = vec![
+            OctetString::new(self.signature.authenticator_data.clone())?,
+            OctetString::new(self.signature.signature.clone())?,
+        ];
+        BitString::from_bytes(&components.to_der()?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The public key of a FIDO2 credential, together with the relying party ID it is scoped to.
+pub struct Fido2PublicKey {
+    key: P256PublicKey,
+    rp_id: String,
+    require_user_verification: bool,
+}
+
+impl Fido2PublicKey {
+    /// Creates a new [Fido2PublicKey] for a credential of the relying party `rp_id`.
+    pub fn new(key: P256PublicKey, rp_id: &str) -> Self {
+        Self {
+            key,
+            rp_id: rp_id.to_string(),
+            require_user_verification: false,
+        }
+    }
+
+    /// Makes [PublicKey::verify_signature()] reject signatures for which the authenticator did not
+    /// verify the user, e.g. using a PIN or biometrics. By default, only user presence is
+    /// required.
+    pub fn require_user_verification(mut self, required: bool) -> Self {
+        self.require_user_verification = required;
+        self
+    }
+
+    /// Returns the P-256 public key of the credential.
+    pub fn key(&self) -> &P256PublicKey {
+        &self.key
+    }
+
+    /// Returns the relying party ID of the credential.
+    pub fn rp_id(&self) -> &str {
+        &self.rp_id
+    }
+
+    fn public_key_algorithm_identifier(
+        rp_id: &str,
+    ) -> Result<AlgorithmIdentifierOwned, der::Error> {
+        Ok(AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_FIDO2_PUBLIC_KEY).unwrap(),
+            parameters: Some(Any::encode_from(&Utf8StringRef::new(rp_id)?)?),
+        })
+    }
+}
+
+impl PublicKey<Fido2Signature> for Fido2PublicKey {
+    /// Verifies a [Fido2Signature]. Fails, if the authenticator data was not created for the
+    /// relying party of this key, if the "user present" flag (or, if required, the "user
+    /// verified" flag) is not set, or if the ECDSA signature does not verify.
+    fn verify_signature(
+        &self,
+        signature: &Fido2Signature,
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        let authenticator_data = signature.authenticator_data();
+        if authenticator_data.len() < MIN_AUTHENTICATOR_DATA_LENGTH
+            || authenticator_data[..32] != Sha256::digest(self.rp_id.as_bytes())[..]
+        {
+            return Err(PublicKeyError::BadSignature);
+        }
+        let flags = authenticator_data[32];
+        let mut required_flags = FLAG_USER_PRESENT;
+        if self.require_user_verification {
+            required_flags |= FLAG_USER_VERIFIED;
+        }
+        if flags & required_flags != required_flags {
+            return Err(PublicKeyError::BadSignature);
+        }
+        let mut signed_data = authenticator_data.to_vec();
+        signed_data.extend_from_slice(&Sha256::digest(data));
+        self.key.verify_signature(
+            &P256Signature::from_bytes(&signature.as_signature().signature),
+            &signed_data,
+        )
+    }
+
+    fn public_key_info(&self) -> PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: Self::public_key_algorithm_identifier(&self.rp_id).unwrap(),
+            public_key_bitstring: BitString::from_bytes(&self.key.to_sec1_bytes()).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError> {
+        if public_key_info.algorithm.oid != ObjectIdentifier::from_str(OID_FIDO2_PUBLIC_KEY)? {
+            return Err(InvalidCert::PublicKeyError(PublicKeyError::BadPublicKeyInfo).into());
+        }
+        let rp_id = match &public_key_info.algorithm.parameters {
+            Some(parameters) => parameters.decode_as::<Utf8StringRef>()?.to_string(),
+            None => {
+                return Err(InvalidInput::Malformed(
+                    "FIDO2 public keys must specify a relying party ID".to_string(),
+                )
+                .into())
+            }
+        };
+        match P256PublicKey::from_sec1_bytes(public_key_info.public_key_bitstring.raw_bytes()) {
+            Ok(key) => Ok(Self::new(key, &rp_id)),
+            Err(e) => Err(InvalidCert::PublicKeyError(e).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An [AsyncPrivateKey] whose key is a credential on a FIDO2 authenticator, such as a hardware
+/// security key. Allows actors to keep their identity key on a security key, while still creating
+/// valid [IdCsr](crate::certs::idcsr::IdCsr)s and message signatures.
+///
+/// Each signature requires an assertion by the authenticator, which usually involves the user
+/// touching the security key. The user may decline, or unplug the key, so [Fido2Signer] does not
+/// implement the infallible [PrivateKey](crate::key::PrivateKey). Use
+/// [IdCsr::new_async()](crate::certs::idcsr::IdCsr::new_async) or [Fido2Signer::try_sign()] to
+/// sign with it.
+///
+/// Two signers are considered equal, if they use the same credential and public key.
+pub struct Fido2Signer<A: Fido2Authenticator> {
+    authenticator: A,
+    credential_id: Vec<u8>,
+    public_key: Fido2PublicKey,
+}
+
+impl<A: Fido2Authenticator> PartialEq for Fido2Signer<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.credential_id == other.credential_id && self.public_key == other.public_key
+    }
+}
+
+impl<A: Fido2Authenticator> Eq for Fido2Signer<A> {}
+
+impl<A: Fido2Authenticator> Fido2Signer<A> {
+    /// Creates a signer for the credential `credential_id` on `authenticator`. `public_key` is the
+    /// public key of the credential, as returned when the credential was created.
+    pub fn new(authenticator: A, credential_id: &[u8], public_key: Fido2PublicKey) -> Self {
+        Self {
+            authenticator,
+            credential_id: credential_id.to_vec(),
+            public_key,
+        }
+    }
+
+    /// Returns the ID of the credential used by this signer.
+    pub fn credential_id(&self) -> &[u8] {
+        &self.credential_id
+    }
+
+    /// Requests an assertion over `data` from the authenticator. Fails, if the authenticator
+    /// could not produce an assertion.
+    pub fn try_sign(&self, data: &[u8]) -> Result<Fido2Signature, ConversionError> {
+        log::trace!("[Fido2Signer::try_sign()] requesting assertion from authenticator");
+        let client_data_hash: [u8; 32] = Sha256::digest(data).into();
+        let assertion = self.authenticator.get_assertion(
+            &self.public_key.rp_id,
+            &self.credential_id,
+            &client_data_hash,
+        )?;
+        Ok(Fido2Signature::new(assertion))
+    }
+}
+
+impl<A: Fido2Authenticator> AsyncPrivateKey<Fido2Signature> for Fido2Signer<A> {
+    type PublicKey = Fido2PublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    /// Requests an assertion over `data` from the authenticator. CTAP2 operations are blocking;
+    /// the assertion is requested when this method is called, and the returned future resolves
+    /// immediately.
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignFuture<'a, Fido2Signature> {
+        Box::pin(std::future::ready(self.try_sign(data)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use ::p256::ecdsa::signature::Signer;
+    use ::p256::ecdsa::{Signature as EcdsaSignature, SigningKey};
+    use sha2::{Digest, Sha256};
+    use spki::SignatureBitStringEncoding;
+    use x509_cert::name::Name;
+
+    use super::{
+        Fido2Assertion, Fido2Authenticator, Fido2PublicKey, Fido2Signature, Fido2Signer,
+        FLAG_USER_PRESENT, FLAG_USER_VERIFIED,
+    };
+    use crate::backends::p256::P256PublicKey;
+    use crate::certs::capabilities::Capabilities;
+    use crate::certs::idcsr::IdCsr;
+    use crate::certs::Target;
+    use crate::errors::ConversionError;
+    use crate::key::{AsyncPrivateKey, PublicKey};
+    use crate::signature::Signature;
+
+    /// A software authenticator, behaving like a security key which is always touched.
+    #[derive(Debug)]
+    struct SoftAuthenticator {
+        key: SigningKey,
+        flags: u8,
+        counter: AtomicU32,
+    }
+
+    impl SoftAuthenticator {
+        fn new(flags: u8) -> Self {
+            Self {
+                key: SigningKey::random(&mut rand::rngs::OsRng),
+                flags,
+                counter: AtomicU32::new(0),
+            }
+        }
+
+        fn public_key(&self, rp_id: &str) -> Fido2PublicKey {
+            let key = P256PublicKey::from_sec1_bytes(
+                self.key.verifying_key().to_encoded_point(false).as_bytes(),
+            )
+            .unwrap();
+            Fido2PublicKey::new(key, rp_id)
+        }
+    }
+
+    impl Fido2Authenticator for SoftAuthenticator {
+        fn get_assertion(
+            &self,
+            rp_id: &str,
+            credential_id: &[u8],
+            client_data_hash: &[u8; 32],
+        ) -> Result<Fido2Assertion, ConversionError> {
+            if credential_id != b"credential" {
+                return Err(ConversionError::SignerError(
+                    "No such credential".to_string(),
+                ));
+            }
+            let mut authenticator_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+            authenticator_data.push(self.flags);
+            let counter = self.counter.fetch_add(1, Ordering::SeqCst) + 1;
+            authenticator_data.extend_from_slice(&counter.to_be_bytes());
+            let mut signed_data = authenticator_data.clone();
+            signed_data.extend_from_slice(client_data_hash);
+            let signature: EcdsaSignature = self.key.sign(&signed_data);
+            Ok(Fido2Assertion {
+                authenticator_data,
+                signature: signature.to_der().as_bytes().to_vec(),
+            })
+        }
+    }
+
+    fn signer(flags: u8) -> Fido2Signer<SoftAuthenticator> {
+        let authenticator = SoftAuthenticator::new(flags);
+        let public_key = authenticator.public_key("polyphony.chat");
+        Fido2Signer::new(authenticator, b"credential", public_key)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn sign_and_verify() {
+        let signer = signer(FLAG_USER_PRESENT);
+        let signature = signer.try_sign(b"polyproto").unwrap();
+        assert!(signer
+            .pubkey()
+            .verify_signature(&signature, b"polyproto")
+            .is_ok());
+        assert!(signer
+            .pubkey()
+            .verify_signature(&signature, b"polyphony")
+            .is_err());
+        let encoded = signature.to_bitstring().unwrap();
+        assert_eq!(Fido2Signature::from_bytes(encoded.raw_bytes()), signature);
+        // The credential is scoped to its relying party.
+        let other_rp = Fido2PublicKey::new(*signer.pubkey().key(), "example.com");
+        assert!(other_rp.verify_signature(&signature, b"polyproto").is_err());
+        assert_eq!(
+            Fido2PublicKey::try_from_public_key_info(signer.pubkey().public_key_info()).unwrap(),
+            *signer.pubkey()
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn user_presence_and_verification() {
+        let absent = signer(0);
+        assert!(absent
+            .pubkey()
+            .verify_signature(&absent.try_sign(b"polyproto").unwrap(), b"polyproto")
+            .is_err());
+        let present = signer(FLAG_USER_PRESENT);
+        let public_key = present.pubkey().clone().require_user_verification(true);
+        assert!(public_key
+            .verify_signature(&present.try_sign(b"polyproto").unwrap(), b"polyproto")
+            .is_err());
+        let verified = signer(FLAG_USER_PRESENT | FLAG_USER_VERIFIED);
+        let public_key = verified.pubkey().clone().require_user_verification(true);
+        assert!(public_key
+            .verify_signature(&verified.try_sign(b"polyproto").unwrap(), b"polyproto")
+            .is_ok());
+        let unknown_credential = Fido2Signer::new(
+            SoftAuthenticator::new(FLAG_USER_PRESENT),
+            b"unknown",
+            present.pubkey().clone(),
+        );
+        assert!(unknown_credential.try_sign(b"polyproto").is_err());
+    }
+
+    #[tokio::test]
+    async fn create_actor_csr() {
+        let signer = signer(FLAG_USER_PRESENT);
+        let csr = IdCsr::new_async(
+            &Name::from_str(
+                "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+            )
+            .unwrap(),
+            &signer,
+            &Capabilities::actor_default(),
+            Some(Target::Actor),
+        )
+        .await
+        .unwrap();
+        let der = csr.to_der().unwrap();
+        assert_eq!(
+            IdCsr::<Fido2Signature, Fido2PublicKey>::from_der(&der, Some(Target::Actor)).unwrap(),
+            csr
+        );
+    }
+}
@@ -0,0 +1,504 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use frost_ed25519 as frost;
+use frost_ed25519::keys::{IdentifierList, KeyPackage, PublicKeyPackage, SecretShare};
+use frost_ed25519::round1::{SigningCommitments, SigningNonces};
+use frost_ed25519::round2::SignatureShare;
+use frost_ed25519::{Identifier, SigningPackage};
+use rand_core::CryptoRngCore;
+
+use crate::errors::{ConversionError, InvalidCert, InvalidInput};
+use crate::key::{AsyncPrivateKey, PublicKey, SignFuture};
+use crate::signature::Signature;
+
+use super::ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature};
+
+/// The future returned by the methods of a [ThresholdParticipant].
+#[cfg(not(target_arch = "wasm32"))]
+pub type ParticipantFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, ConversionError>> + Send + 'a>>;
+/// The future returned by the methods of a [ThresholdParticipant]. On `wasm32`, the future is not
+/// required to be [Send].
+#[cfg(target_arch = "wasm32")]
+pub type ParticipantFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, ConversionError>> + 'a>>;
+
+/// Splits a freshly generated Ed25519 key into `max_signers` [ThresholdShare]s, any `min_signers`
+/// of which can produce a signature together. The key itself is never assembled; it only exists
+/// in the form of its shares.
+///
+/// The shares are generated by a trusted dealer, the caller, which must hand each share to its
+/// participant over a secure channel and then forget it. Returns the shares and the
+/// [PublicKeyPackage] of the group, which the [ThresholdSigner] needs.
+pub fn generate_with_dealer(
+    min_signers: u16,
+    max_signers: u16,
+    rng: &mut impl CryptoRngCore,
+) -> Result<(Vec<ThresholdShare>, PublicKeyPackage), ConversionError> {
+    log::trace!(
+        "[generate_with_dealer()] Generating a {} of {} threshold key",
+        min_signers,
+        max_signers
+    );
+    let (shares, public_key_package) =
+        frost::keys::generate_with_dealer(max_signers, min_signers, IdentifierList::Default, rng)?;
+    Ok((into_threshold_shares(shares)?, public_key_package))
+}
+
+/// Like [generate_with_dealer()], but splits an existing [Ed25519PrivateKey], such as the current
+/// CA key of a home server. Signatures created by the group verify against the public key of
+/// `private_key`, so already issued certificates stay valid. `private_key` must be destroyed after
+/// it has been split.
+pub fn split(
+    private_key: &Ed25519PrivateKey,
+    min_signers: u16,
+    max_signers: u16,
+    rng: &mut impl CryptoRngCore,
+) -> Result<(Vec<ThresholdShare>, PublicKeyPackage), ConversionError> {
+    log::trace!(
+        "[split()] Splitting an Ed25519 key into a {} of {} threshold key",
+        min_signers,
+        max_signers
+    );
+    let scalar = ed25519_dalek::SigningKey::from_bytes(&private_key.to_bytes()).to_scalar();
+    let signing_key = frost::SigningKey::deserialize(scalar.as_bytes())?;
+    let (shares, public_key_package) = frost::keys::split(
+        &signing_key,
+        max_signers,
+        min_signers,
+        IdentifierList::Default,
+        rng,
+    )?;
+    Ok((into_threshold_shares(shares)?, public_key_package))
+}
+
+fn into_threshold_shares(
+    shares: BTreeMap<Identifier, SecretShare>,
+) -> Result<Vec<ThresholdShare>, ConversionError> {
+    shares
+        .into_values()
+        .map(ThresholdShare::from_secret_share)
+        .collect()
+}
+
+/// Returns the public key of a threshold key, as a regular [Ed25519PublicKey].
+pub fn group_public_key(
+    public_key_package: &PublicKeyPackage,
+) -> Result<Ed25519PublicKey, ConversionError> {
+    let bytes = public_key_package.verifying_key().serialize()?;
+    match <[u8; 32]>::try_from(bytes.as_slice()) {
+        Ok(bytes) => match Ed25519PublicKey::from_bytes(&bytes) {
+            Ok(public_key) => Ok(public_key),
+            Err(e) => Err(InvalidCert::PublicKeyError(e).into()),
+        },
+        Err(_) => Err(InvalidInput::Length {
+            min_length: 32,
+            max_length: 32,
+            actual_length: bytes.len().to_string(),
+        }
+        .into()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The share of a threshold key held by a single participant, such as one of the machines the CA
+/// key of a home server is split across.
+pub struct ThresholdShare {
+    key_package: KeyPackage,
+}
+
+impl ThresholdShare {
+    /// Creates a [ThresholdShare] from the [SecretShare] handed to the participant by the dealer.
+    /// Fails, if the share does not match the commitment of the dealer.
+    pub fn from_secret_share(secret_share: SecretShare) -> Result<Self, ConversionError> {
+        Ok(Self {
+            key_package: KeyPackage::try_from(secret_share)?,
+        })
+    }
+
+    /// Creates a [ThresholdShare] from an existing [KeyPackage], such as one created by a
+    /// distributed key generation.
+    pub fn from_key_package(key_package: KeyPackage) -> Self {
+        Self { key_package }
+    }
+
+    /// Returns the [KeyPackage] of this share, e.g. to persist it.
+    pub fn key_package(&self) -> &KeyPackage {
+        &self.key_package
+    }
+
+    /// Returns the [Identifier] of the participant holding this share.
+    pub fn identifier(&self) -> Identifier {
+        *self.key_package.identifier()
+    }
+
+    /// Creates the single-use nonces of this participant for the first round of signing, and the
+    /// commitments to them, which are sent to the coordinator. The nonces must be kept secret, and
+    /// must not be used for more than one [ThresholdShare::sign()].
+    pub fn commit(&self, rng: &mut impl CryptoRngCore) -> (SigningNonces, SigningCommitments) {
+        frost::round1::commit(self.key_package.signing_share(), rng)
+    }
+
+    /// Creates the [SignatureShare] of this participant for the second round of signing, using
+    /// the nonces created for the commitment of this participant in `signing_package`.
+    pub fn sign(
+        &self,
+        signing_package: &SigningPackage,
+        nonces: &SigningNonces,
+    ) -> Result<SignatureShare, ConversionError> {
+        Ok(frost::round2::sign(
+            signing_package,
+            nonces,
+            &self.key_package,
+        )?)
+    }
+}
+
+/// A participant in the creation of a threshold signature, such as a remote machine holding one
+/// [ThresholdShare]. Signing takes two rounds: every participant first commits to a set of
+/// single-use nonces, and then signs the message together with the commitments of all
+/// participants. The participant is responsible for keeping its nonces between the two rounds.
+///
+/// [LocalParticipant] implements this trait for a share held in memory.
+pub trait ThresholdParticipant {
+    /// Returns the [Identifier] of this participant.
+    fn identifier(&self) -> Identifier;
+    /// Creates and keeps a set of nonces, returning the commitments to them.
+    fn commit(&self) -> ParticipantFuture<'_, SigningCommitments>;
+    /// Creates the [SignatureShare] of this participant over the message in `signing_package`,
+    /// using the nonces committed to in `signing_package`. The nonces are discarded afterwards.
+    fn sign<'a>(
+        &'a self,
+        signing_package: &'a SigningPackage,
+    ) -> ParticipantFuture<'a, SignatureShare>;
+}
+
+struct ParticipantState<R: CryptoRngCore> {
+    rng: R,
+    nonces: Vec<(SigningCommitments, SigningNonces)>,
+}
+
+/// A [ThresholdParticipant] holding its [ThresholdShare] in memory, creating its nonces using the
+/// cryptographically secure random number generator `R`. Cloning a [LocalParticipant] shares its
+/// random number generator and pending nonces with the clone.
+pub struct LocalParticipant<R: CryptoRngCore> {
+    share: ThresholdShare,
+    state: Arc<Mutex<ParticipantState<R>>>,
+}
+
+impl<R: CryptoRngCore> Clone for LocalParticipant<R> {
+    fn clone(&self) -> Self {
+        Self {
+            share: self.share.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<R: CryptoRngCore> std::fmt::Debug for LocalParticipant<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalParticipant")
+            .field("identifier", &self.share.identifier())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: CryptoRngCore> LocalParticipant<R> {
+    /// Creates a new [LocalParticipant] from its share.
+    pub fn new(share: ThresholdShare, rng: R) -> Self {
+        Self {
+            share,
+            state: Arc::new(Mutex::new(ParticipantState {
+                rng,
+                nonces: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns the share of this participant.
+    pub fn share(&self) -> &ThresholdShare {
+        &self.share
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ParticipantState<R>> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn try_sign(
+        &self,
+        signing_package: &SigningPackage,
+    ) -> Result<SignatureShare, ConversionError> {
+        let identifier = self.share.identifier();
+        let commitments = match signing_package.signing_commitment(&identifier) {
+            Some(commitments) => commitments,
+            None => {
+                return Err(ConversionError::SignerError(format!(
+                    "The signing package contains no commitments of participant {:?}",
+                    identifier
+                )))
+            }
+        };
+        let nonces = {
+            let mut pending = self.lock();
+            match pending
+                .nonces
+                .iter()
+                .position(|(pending, _)| *pending == commitments)
+            {
+                Some(index) => pending.nonces.remove(index).1,
+                None => {
+                    return Err(ConversionError::SignerError(format!(
+                        "Participant {:?} has no nonces for the commitments in the signing package",
+                        identifier
+                    )))
+                }
+            }
+        };
+        self.share.sign(signing_package, &nonces)
+    }
+}
+
+impl<R: CryptoRngCore> ThresholdParticipant for LocalParticipant<R> {
+    fn identifier(&self) -> Identifier {
+        self.share.identifier()
+    }
+
+    fn commit(&self) -> ParticipantFuture<'_, SigningCommitments> {
+        let mut state = self.lock();
+        let (nonces, commitments) = self.share.commit(&mut state.rng);
+        state.nonces.push((commitments, nonces));
+        Box::pin(std::future::ready(Ok(commitments)))
+    }
+
+    fn sign<'a>(
+        &'a self,
+        signing_package: &'a SigningPackage,
+    ) -> ParticipantFuture<'a, SignatureShare> {
+        Box::pin(std::future::ready(self.try_sign(signing_package)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type BoxedParticipant = Box<dyn ThresholdParticipant + Send + Sync>;
+#[cfg(target_arch = "wasm32")]
+type BoxedParticipant = Box<dyn ThresholdParticipant>;
+
+/// The coordinator of a threshold signature: collects the commitments and [SignatureShare]s of the
+/// participants holding a [ThresholdShare] of a key, and aggregates the shares into a regular
+/// [Ed25519Signature], which verifies against the [Ed25519PublicKey] of the group. Certificates
+/// signed this way are indistinguishable from certificates signed by a single Ed25519 key.
+///
+/// [ThresholdSigner] implements [AsyncPrivateKey], so that a home server can issue certificates
+/// using [IdCert::from_ca_csr_async()](crate::certs::idcert::IdCert::from_ca_csr_async) and
+/// [IdCert::from_actor_csr_async()](crate::certs::idcert::IdCert::from_actor_csr_async) while its
+/// CA key is split across multiple machines. Every signature requires the cooperation of at least
+/// as many participants as the threshold of the key; signing fails otherwise.
+pub struct ThresholdSigner {
+    public_key: Ed25519PublicKey,
+    public_key_package: PublicKeyPackage,
+    participants: Vec<BoxedParticipant>,
+}
+
+impl std::fmt::Debug for ThresholdSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThresholdSigner")
+            .field("public_key", &self.public_key)
+            .field("participants", &self.participants.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ThresholdSigner {
+    /// Creates a [ThresholdSigner] for the group described by `public_key_package`, without
+    /// participants.
+    pub fn new(public_key_package: PublicKeyPackage) -> Result<Self, ConversionError> {
+        Ok(Self {
+            public_key: group_public_key(&public_key_package)?,
+            public_key_package,
+            participants: Vec::new(),
+        })
+    }
+
+    /// Adds a participant, which is asked for its signature share whenever a signature is created.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_participant(
+        mut self,
+        participant: impl ThresholdParticipant + Send + Sync + 'static,
+    ) -> Self {
+        self.participants.push(Box::new(participant));
+        self
+    }
+
+    /// Adds a participant, which is asked for its signature share whenever a signature is created.
+    #[cfg(target_arch = "wasm32")]
+    pub fn with_participant(mut self, participant: impl ThresholdParticipant + 'static) -> Self {
+        self.participants.push(Box::new(participant));
+        self
+    }
+
+    /// Returns the [PublicKeyPackage] of the group.
+    pub fn public_key_package(&self) -> &PublicKeyPackage {
+        &self.public_key_package
+    }
+
+    /// Aggregates the [SignatureShare]s created for `signing_package` into an [Ed25519Signature].
+    /// Fails, if there are fewer shares than the threshold of the key, or if any share is invalid.
+    /// Use this method to coordinate a signature without the [ThresholdParticipant] trait, e.g.
+    /// when the participants are reached through another protocol.
+    pub fn aggregate(
+        &self,
+        signing_package: &SigningPackage,
+        signature_shares: &BTreeMap<Identifier, SignatureShare>,
+    ) -> Result<Ed25519Signature, ConversionError> {
+        let signature =
+            frost::aggregate(signing_package, signature_shares, &self.public_key_package)?;
+        let signature = Ed25519Signature::from_bytes(&signature.serialize()?);
+        self.public_key
+            .verify_signature(&signature, signing_package.message())
+            .map_err(InvalidCert::PublicKeyError)?;
+        Ok(signature)
+    }
+
+    async fn sign_with_participants(
+        &self,
+        data: &[u8],
+    ) -> Result<Ed25519Signature, ConversionError> {
+        log::trace!(
+            "[ThresholdSigner::sign()] Collecting commitments of {} participants",
+            self.participants.len()
+        );
+        let mut commitments = BTreeMap::new();
+        for participant in self.participants.iter() {
+            commitments.insert(participant.identifier(), participant.commit().await?);
+        }
+        let signing_package = SigningPackage::new(commitments, data);
+        log::trace!("[ThresholdSigner::sign()] Collecting signature shares");
+        let mut signature_shares = BTreeMap::new();
+        for participant in self.participants.iter() {
+            signature_shares.insert(
+                participant.identifier(),
+                participant.sign(&signing_package).await?,
+            );
+        }
+        self.aggregate(&signing_package, &signature_shares)
+    }
+}
+
+impl AsyncPrivateKey<Ed25519Signature> for ThresholdSigner {
+    type PublicKey = Ed25519PublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    /// Creates a signature in two rounds, asking every participant for its commitments and then
+    /// for its signature share, and aggregates the shares.
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignFuture<'a, Ed25519Signature> {
+        Box::pin(self.sign_with_participants(data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use der::asn1::Uint;
+    use x509_cert::name::Name;
+    use x509_cert::time::Validity;
+
+    use super::*;
+    use crate::certs::capabilities::Capabilities;
+    use crate::certs::idcert::IdCert;
+    use crate::certs::idcsr::IdCsr;
+    use crate::certs::Target;
+    use crate::key::PrivateKey;
+    use crate::timestamp::Timestamp;
+
+    fn signer(shares: &[ThresholdShare], public_key_package: PublicKeyPackage) -> ThresholdSigner {
+        shares.iter().fold(
+            ThresholdSigner::new(public_key_package).unwrap(),
+            |signer, share| {
+                signer.with_participant(LocalParticipant::new(share.clone(), rand::rngs::OsRng))
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn issue_actor_cert_with_threshold_key() {
+        let (shares, public_key_package) =
+            generate_with_dealer(2, 3, &mut rand::rngs::OsRng).unwrap();
+        assert_eq!(shares.len(), 3);
+        let signer = signer(&[shares[0].clone(), shares[2].clone()], public_key_package);
+
+        let actor_key = Ed25519PrivateKey::gen_keypair(&mut rand::rngs::OsRng);
+        let csr = IdCsr::new(
+            &Name::from_str(
+                "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+            )
+            .unwrap(),
+            &actor_key,
+            &Capabilities::actor_default(),
+            Some(Target::Actor),
+        )
+        .unwrap();
+        let cert = IdCert::from_actor_csr_async(
+            csr,
+            &signer,
+            Uint::new(&[1]).unwrap(),
+            Name::from_str("DC=polyphony,DC=chat").unwrap(),
+            Validity::from_now(std::time::Duration::from_secs(60)).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert!(cert
+            .full_verify_actor(Timestamp::now(), signer.pubkey())
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn split_existing_key() {
+        let private_key = Ed25519PrivateKey::gen_keypair(&mut rand::rngs::OsRng);
+        let (shares, public_key_package) =
+            split(&private_key, 2, 3, &mut rand::rngs::OsRng).unwrap();
+        assert_eq!(
+            &group_public_key(&public_key_package).unwrap(),
+            private_key.pubkey()
+        );
+        let signer = signer(&shares[1..], public_key_package);
+        let signature = signer.sign(b"polyproto").await.unwrap();
+        assert!(private_key
+            .pubkey()
+            .verify_signature(&signature, b"polyproto")
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn too_few_participants() {
+        let (shares, public_key_package) =
+            generate_with_dealer(2, 3, &mut rand::rngs::OsRng).unwrap();
+        let signer = signer(&shares[..1], public_key_package);
+        assert!(signer.sign(b"polyproto").await.is_err());
+
+        // Nonces are single-use: a participant cannot sign twice for the same commitments.
+        let participant = LocalParticipant::new(shares[0].clone(), rand::rngs::OsRng);
+        let other = LocalParticipant::new(shares[1].clone(), rand::rngs::OsRng);
+        let commitments = BTreeMap::from([
+            (
+                participant.identifier(),
+                participant.commit().await.unwrap(),
+            ),
+            (other.identifier(), other.commit().await.unwrap()),
+        ]);
+        let signing_package = SigningPackage::new(commitments, b"polyproto");
+        assert!(participant.sign(&signing_package).await.is_ok());
+        assert!(participant.sign(&signing_package).await.is_err());
+    }
+}
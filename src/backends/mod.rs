@@ -0,0 +1,153 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// Ready-made implementations of [Signature](crate::signature::Signature),
+/// [PrivateKey](crate::key::PrivateKey) and [PublicKey](crate::key::PublicKey) for Ed25519 and
+/// ECDSA P-256, backed by `aws-lc-rs`. With the `fips` feature, the FIPS validated build of aws-lc
+/// is used.
+#[cfg(any(feature = "aws-lc", feature = "fips"))]
+pub mod aws_lc;
+/// [CompositeSignature](composite::CompositeSignature), a hybrid signature made up of two
+/// signatures of different algorithms, together with the matching composite key types.
+#[cfg(not(feature = "fips"))]
+pub mod composite;
+/// Ready-made implementations of [Signature](crate::signature::Signature),
+/// [PrivateKey](crate::key::PrivateKey) and [PublicKey](crate::key::PublicKey) for Ed25519,
+/// using the `ed25519-dalek` crate.
+#[cfg(all(feature = "ed25519", not(feature = "fips")))]
+pub mod ed25519;
+/// [Fido2Signer](fido2::Fido2Signer), an [AsyncPrivateKey](crate::key::AsyncPrivateKey) backed
+/// by a credential on a FIDO2 authenticator, together with the matching signature and public key
+/// types.
+#[cfg(all(feature = "fido2", not(feature = "fips")))]
+pub mod fido2;
+/// [ThresholdSigner](frost::ThresholdSigner), a FROST threshold signer for Ed25519, which allows
+/// splitting the CA key of a home server across multiple machines.
+#[cfg(all(feature = "frost", not(feature = "fips")))]
+pub mod frost;
+/// Ready-made implementations of [Signature](crate::signature::Signature),
+/// [PrivateKey](crate::key::PrivateKey) and [PublicKey](crate::key::PublicKey) for ECDSA with the
+/// NIST P-256 curve and SHA-256, using the `p256` crate.
+#[cfg(all(feature = "p256", not(feature = "fips")))]
+pub mod p256;
+/// [Pkcs11Signer](pkcs11::Pkcs11Signer), an adapter implementing
+/// [AsyncPrivateKey](crate::key::AsyncPrivateKey) for keys stored on a PKCS#11 token, such as a
+/// hardware security module.
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+/// [WebCryptoSigner](webcrypto::WebCryptoSigner), an ECDSA P-256 signer backed by the WebCrypto
+/// API of browsers and web workers, for use on `wasm32` targets.
+#[cfg(all(feature = "webcrypto", not(feature = "fips")))]
+pub mod webcrypto;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The cryptographic library backing an algorithm in the [compatibility_matrix()].
+pub enum Backend {
+    /// Crates of the RustCrypto project, such as `ed25519-dalek` and `p256`.
+    RustCrypto,
+    /// `aws-lc-rs`, a binding to the aws-lc library.
+    AwsLc,
+    /// No library of its own; combines the signatures of other backends.
+    Composite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// An entry in the [compatibility_matrix()], describing a signature algorithm provided by one of
+/// the backends compiled into this build.
+pub struct AlgorithmSupport {
+    /// The name of the algorithm.
+    pub algorithm: &'static str,
+    /// The OID of the signature algorithm.
+    pub oid: &'static str,
+    /// The module path of the backend, relative to [crate::backends].
+    pub module: &'static str,
+    /// The library backing the algorithm.
+    pub backend: Backend,
+    /// Whether the algorithm is provided by a FIPS validated module. Only `true` for the `aws_lc`
+    /// backends in builds with the `fips` feature.
+    pub fips_validated: bool,
+}
+
+/// Returns `true`, if polyproto was built with the `fips` feature. In FIPS builds, only algorithms
+/// provided by the FIPS validated build of aws-lc are available; the RustCrypto and composite
+/// backends are disabled at compile time.
+pub const fn fips_build() -> bool {
+    cfg!(feature = "fips")
+}
+
+/// Returns the signature algorithms available in this build, depending on the enabled features.
+/// Allows applications to report at runtime which algorithms they can interoperate with, and
+/// whether these are backed by a FIPS validated module.
+pub fn compatibility_matrix() -> Vec<AlgorithmSupport> {
+    let matrix: &[AlgorithmSupport] = &[
+        #[cfg(all(feature = "ed25519", not(feature = "fips")))]
+        AlgorithmSupport {
+            algorithm: "Ed25519",
+            oid: ed25519::OID_ED25519,
+            module: "ed25519",
+            backend: Backend::RustCrypto,
+            fips_validated: false,
+        },
+        #[cfg(all(feature = "p256", not(feature = "fips")))]
+        AlgorithmSupport {
+            algorithm: "ECDSA P-256 with SHA-256",
+            oid: p256::OID_ECDSA_WITH_SHA256,
+            module: "p256",
+            backend: Backend::RustCrypto,
+            fips_validated: false,
+        },
+        #[cfg(any(feature = "aws-lc", feature = "fips"))]
+        AlgorithmSupport {
+            algorithm: "Ed25519",
+            oid: aws_lc::ed25519::OID_ED25519,
+            module: "aws_lc::ed25519",
+            backend: Backend::AwsLc,
+            fips_validated: fips_build(),
+        },
+        #[cfg(any(feature = "aws-lc", feature = "fips"))]
+        AlgorithmSupport {
+            algorithm: "ECDSA P-256 with SHA-256",
+            oid: aws_lc::p256::OID_ECDSA_WITH_SHA256,
+            module: "aws_lc::p256",
+            backend: Backend::AwsLc,
+            fips_validated: fips_build(),
+        },
+        #[cfg(not(feature = "fips"))]
+        AlgorithmSupport {
+            algorithm: "Composite signature",
+            oid: composite::OID_COMPOSITE_SIGNATURE,
+            module: "composite",
+            backend: Backend::Composite,
+            fips_validated: false,
+        },
+    ];
+    matrix.to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn compatibility_matrix_matches_features() {
+        let matrix = compatibility_matrix();
+        assert_eq!(
+            matrix
+                .iter()
+                .any(|entry| entry.backend == Backend::RustCrypto),
+            !fips_build() && (cfg!(feature = "ed25519") || cfg!(feature = "p256"))
+        );
+        assert_eq!(
+            matrix.iter().any(|entry| entry.backend == Backend::AwsLc),
+            cfg!(any(feature = "aws-lc", feature = "fips"))
+        );
+        assert!(
+            matrix
+                .iter()
+                .all(|entry| entry.fips_validated
+                    == (fips_build() && entry.backend == Backend::AwsLc))
+        );
+    }
+}
@@ -0,0 +1,331 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, ObjectClass, ObjectHandle};
+use cryptoki::session::Session;
+use der::asn1::{BitString, OctetString, SequenceOf, UintRef};
+use der::{Any, Decode, Encode};
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier};
+
+use crate::certs::PublicKeyInfo;
+use crate::errors::{ConversionError, InvalidInput};
+use crate::key::{AsyncPrivateKey, PublicKey, SignFuture};
+use crate::signature::Signature;
+
+/// The OID of the Ed25519 signature algorithm, as defined in RFC 8410.
+pub const OID_ED25519: &str = "1.3.101.112";
+/// The OID of the `ecdsa-with-SHA256` signature algorithm, as defined in RFC 5758.
+pub const OID_ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+/// The OID of the `id-ecPublicKey` public key algorithm, as defined in RFC 5480.
+pub const OID_EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+/// The OID of the `secp256r1`/`prime256v1` named curve, as defined in RFC 5480.
+pub const OID_SECP256R1: &str = "1.2.840.10045.3.1.7";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A PKCS#11 signing mechanism supported by [Pkcs11Signer], together with the signature
+/// algorithm it corresponds to.
+pub enum Pkcs11Mechanism {
+    /// `CKM_EDDSA` with an Ed25519 key, corresponding to the Ed25519 signature algorithm.
+    Ed25519,
+    /// `CKM_ECDSA_SHA256` with a P-256 key, corresponding to `ecdsa-with-SHA256`.
+    EcdsaP256Sha256,
+}
+
+impl Pkcs11Mechanism {
+    /// Returns the mechanism matching a signature [AlgorithmIdentifierOwned], such as the one
+    /// returned by [Signature::algorithm_identifier()]. Returns `None`, if the signature algorithm
+    /// is not supported.
+    pub fn from_algorithm_identifier(algorithm: &AlgorithmIdentifierOwned) -> Option<Self> {
+        match algorithm.oid.to_string().as_str() {
+            OID_ED25519 => Some(Self::Ed25519),
+            OID_ECDSA_WITH_SHA256 => Some(Self::EcdsaP256Sha256),
+            _ => None,
+        }
+    }
+
+    /// Returns the [AlgorithmIdentifierOwned] of the signature algorithm of this mechanism.
+    pub fn algorithm_identifier(&self) -> AlgorithmIdentifierOwned {
+        let oid = match self {
+            Self::Ed25519 => OID_ED25519,
+            Self::EcdsaP256Sha256 => OID_ECDSA_WITH_SHA256,
+        };
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(oid).unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Returns the [AlgorithmIdentifierOwned] used in the `SubjectPublicKeyInfo` of keys for this
+    /// mechanism.
+    pub fn public_key_algorithm_identifier(&self) -> AlgorithmIdentifierOwned {
+        match self {
+            Self::Ed25519 => self.algorithm_identifier(),
+            Self::EcdsaP256Sha256 => AlgorithmIdentifierOwned {
+                oid: ObjectIdentifier::from_str(OID_EC_PUBLIC_KEY).unwrap(),
+                parameters: Some(
+                    Any::encode_from(&ObjectIdentifier::from_str(OID_SECP256R1).unwrap()).unwrap(),
+                ),
+            },
+        }
+    }
+
+    /// Returns the `cryptoki` [Mechanism] used to sign with this mechanism.
+    pub fn mechanism(&self) -> Mechanism<'static> {
+        match self {
+            Self::Ed25519 => Mechanism::Eddsa,
+            Self::EcdsaP256Sha256 => Mechanism::EcdsaSha256,
+        }
+    }
+
+    /// Converts a signature as returned by the token into the encoding used by polyproto. Ed25519
+    /// signatures are used as-is. ECDSA signatures are returned by PKCS#11 as the concatenation of
+    /// `r` and `s`, and are converted into a DER encoded `ECDSA-Sig-Value`.
+    pub fn encode_signature(&self, signature: &[u8]) -> Result<Vec<u8>, ConversionError> {
+        match self {
+            Self::Ed25519 => Ok(signature.to_vec()),
+            Self::EcdsaP256Sha256 => {
+                if signature.len() != 64 {
+                    return Err(InvalidInput::Length {
+                        min_length: 64,
+                        max_length: 64,
+                        actual_length: signature.len().to_string(),
+                    }
+                    .into());
+                }
+                let mut sequence = SequenceOf::<UintRef, 2>::new();
+                sequence.add(UintRef::new(&signature[..32])?)?;
+                sequence.add(UintRef::new(&signature[32..])?)?;
+                Ok(sequence.to_der()?)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A private key stored on a PKCS#11 token, such as a hardware security module (HSM) or a smart
+/// card, implementing [AsyncPrivateKey]. Allows server operators to keep the CA key of their home
+/// server in hardware, where it never leaves the device.
+///
+/// Signing on a token can fail at any time, e.g. when the token is removed or the session expires,
+/// so [Pkcs11Signer] does not implement the infallible [PrivateKey](crate::key::PrivateKey). Use
+/// [IdCert::from_ca_csr_async()](crate::certs::idcert::IdCert::from_ca_csr_async) and the other
+/// `*_async` constructors, or [Pkcs11Signer::try_sign()], to sign with it.
+///
+/// The [Pkcs11Mechanism] used for signing is chosen from [Signature::algorithm_identifier()] of
+/// `S`. The token session is shared between clones of the signer and used by one signing
+/// operation at a time.
+///
+/// Two signers are considered equal, if they use the same key handle and public key.
+pub struct Pkcs11Signer<S: Signature, P: PublicKey<S>> {
+    session: Arc<Mutex<Session>>,
+    key: ObjectHandle,
+    mechanism: Pkcs11Mechanism,
+    public_key: P,
+    _signature: PhantomData<S>,
+}
+
+impl<S: Signature, P: PublicKey<S>> Clone for Pkcs11Signer<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            session: self.session.clone(),
+            key: self.key,
+            mechanism: self.mechanism,
+            public_key: self.public_key.clone(),
+            _signature: PhantomData,
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> PartialEq for Pkcs11Signer<S, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.public_key == other.public_key
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Eq for Pkcs11Signer<S, P> {}
+
+impl<S: Signature, P: PublicKey<S>> Pkcs11Signer<S, P> {
+    /// Creates a signer for the private key `key` of an opened and, if required by the token,
+    /// logged in `session`. `public_key` must be the public key corresponding to `key`.
+    ///
+    /// Fails, if the signature algorithm of `S` has no matching [Pkcs11Mechanism].
+    pub fn new(
+        session: Session,
+        key: ObjectHandle,
+        public_key: P,
+    ) -> Result<Self, ConversionError> {
+        Ok(Self {
+            session: Arc::new(Mutex::new(session)),
+            key,
+            mechanism: Self::signature_mechanism()?,
+            public_key,
+            _signature: PhantomData,
+        })
+    }
+
+    /// Creates a signer for the private key labelled `label`. The public key is read from the
+    /// `CKA_EC_POINT` attribute of the public key object with the same label.
+    ///
+    /// Fails, if the token does not hold exactly one private and one public key with this label,
+    /// or if the public key cannot be converted into `P`.
+    pub fn from_label(session: Session, label: &str) -> Result<Self, ConversionError> {
+        let mechanism = Self::signature_mechanism()?;
+        let key = find_key(&session, ObjectClass::PRIVATE_KEY, label)?;
+        let public_key_handle = find_key(&session, ObjectClass::PUBLIC_KEY, label)?;
+        let attributes = session
+            .get_attributes(public_key_handle, &[AttributeType::EcPoint])
+            .map_err(token_error)?;
+        let point = match attributes.into_iter().next() {
+            Some(Attribute::EcPoint(point)) => point,
+            _ => {
+                return Err(InvalidInput::Malformed(format!(
+                    "The public key \"{}\" has no CKA_EC_POINT attribute",
+                    label
+                ))
+                .into())
+            }
+        };
+        // PKCS#11 mandates a DER encoded OCTET STRING, but some tokens return the raw point.
+        let point = match OctetString::from_der(&point) {
+            Ok(octet_string) => octet_string.into_bytes(),
+            Err(_) => point,
+        };
+        let public_key = P::try_from_public_key_info(PublicKeyInfo {
+            algorithm: mechanism.public_key_algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&point)?,
+        })?;
+        Self::new(session, key, public_key)
+    }
+
+    /// Returns the [Pkcs11Mechanism] used by this signer.
+    pub fn mechanism(&self) -> Pkcs11Mechanism {
+        self.mechanism
+    }
+
+    /// Returns the handle of the private key on the token.
+    pub fn key_handle(&self) -> ObjectHandle {
+        self.key
+    }
+
+    /// Signs `data` on the token. Fails with [ConversionError::SignerError], if the token rejects
+    /// the operation, e.g. because the session has been closed or the user is not logged in.
+    pub fn try_sign(&self, data: &[u8]) -> Result<S, ConversionError> {
+        log::trace!(
+            "[Pkcs11Signer::try_sign()] signing using {:?}",
+            self.mechanism
+        );
+        let signature = {
+            let session = match self.session.lock() {
+                Ok(session) => session,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            session
+                .sign(&self.mechanism.mechanism(), self.key, data)
+                .map_err(token_error)?
+        };
+        Ok(S::from_bytes(&self.mechanism.encode_signature(&signature)?))
+    }
+
+    fn signature_mechanism() -> Result<Pkcs11Mechanism, ConversionError> {
+        let algorithm = S::algorithm_identifier();
+        match Pkcs11Mechanism::from_algorithm_identifier(&algorithm) {
+            Some(mechanism) => Ok(mechanism),
+            None => Err(InvalidInput::Malformed(format!(
+                "Signature algorithm {} is not supported by the PKCS#11 adapter",
+                algorithm.oid
+            ))
+            .into()),
+        }
+    }
+}
+
+impl<S: Signature + Send, P: PublicKey<S>> AsyncPrivateKey<S> for Pkcs11Signer<S, P> {
+    type PublicKey = P;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    /// Signs `data` on the token. PKCS#11 calls are blocking; the signature is created when this
+    /// method is called, and the returned future resolves immediately.
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignFuture<'a, S> {
+        Box::pin(std::future::ready(self.try_sign(data)))
+    }
+}
+
+fn find_key(
+    session: &Session,
+    class: ObjectClass,
+    label: &str,
+) -> Result<ObjectHandle, ConversionError> {
+    let objects = session
+        .find_objects(&[
+            Attribute::Class(class),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ])
+        .map_err(token_error)?;
+    match objects.as_slice() {
+        [object] => Ok(*object),
+        _ => Err(InvalidInput::Malformed(format!(
+            "Expected exactly one {} labelled \"{}\", found {}",
+            class,
+            label,
+            objects.len()
+        ))
+        .into()),
+    }
+}
+
+fn token_error(error: cryptoki::error::Error) -> ConversionError {
+    log::debug!("[Pkcs11Signer] token returned an error: {}", error);
+    ConversionError::SignerError(error.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn mechanisms_match_algorithm_identifiers() {
+        for mechanism in [Pkcs11Mechanism::Ed25519, Pkcs11Mechanism::EcdsaP256Sha256] {
+            assert_eq!(
+                Pkcs11Mechanism::from_algorithm_identifier(&mechanism.algorithm_identifier()),
+                Some(mechanism)
+            );
+        }
+        assert_eq!(
+            Pkcs11Mechanism::from_algorithm_identifier(
+                &Pkcs11Mechanism::EcdsaP256Sha256.public_key_algorithm_identifier()
+            ),
+            None
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn ecdsa_signatures_are_der_encoded() {
+        let mut raw = [0u8; 64];
+        raw[31] = 1;
+        raw[32] = 0x80;
+        assert_eq!(
+            Pkcs11Mechanism::EcdsaP256Sha256
+                .encode_signature(&raw)
+                .unwrap()[..8],
+            [0x30, 0x26, 0x02, 0x01, 0x01, 0x02, 0x21, 0x00]
+        );
+        assert!(Pkcs11Mechanism::EcdsaP256Sha256
+            .encode_signature(&raw[..63])
+            .is_err());
+        assert_eq!(
+            Pkcs11Mechanism::Ed25519.encode_signature(&raw).unwrap(),
+            raw.to_vec()
+        );
+    }
+}
@@ -0,0 +1,224 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use js_sys::{Array, ArrayBuffer, Reflect, Uint8Array};
+use p256::ecdsa::Signature as EcdsaSignature;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Crypto, CryptoKey, CryptoKeyPair, EcKeyGenParams, EcdsaParams, SubtleCrypto};
+
+use crate::errors::{ConversionError, InvalidCert, PublicKeyError};
+#[cfg(target_arch = "wasm32")]
+use crate::key::{AsyncPrivateKey, SignFuture};
+use crate::signature::Signature;
+
+use super::p256::{P256PublicKey, P256Signature};
+
+/// The WebCrypto name of the ECDSA algorithm.
+const ALGORITHM: &str = "ECDSA";
+/// The WebCrypto name of the P-256 curve.
+const NAMED_CURVE: &str = "P-256";
+/// The WebCrypto name of the SHA-256 digest.
+const HASH: &str = "SHA-256";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An ECDSA P-256 key pair held by the WebCrypto API (`SubtleCrypto`) of a browser or web worker.
+/// Lets browser clients sign using the native implementation of the platform, instead of
+/// shipping pure-Rust cryptography in their WebAssembly bundle.
+///
+/// WebCrypto operations are asynchronous, so [WebCryptoSigner] implements
+/// [AsyncPrivateKey](crate::key::AsyncPrivateKey) on `wasm32` targets, but not
+/// [PrivateKey](crate::key::PrivateKey). Use
+/// [IdCsr::new_async()](crate::certs::idcsr::IdCsr::new_async) to create CSRs with it.
+/// Signatures are regular [P256Signature]s, and can be verified synchronously using the
+/// [P256PublicKey] returned by [WebCryptoSigner::public_key()], or asynchronously by WebCrypto
+/// using [WebCryptoSigner::verify()].
+///
+/// Private keys are non-extractable, unless requested otherwise: their key material can not be
+/// read by JavaScript or WebAssembly code. A non-extractable [CryptoKey] can still be persisted,
+/// e.g. by storing it in IndexedDB, and later be passed to
+/// [WebCryptoSigner::from_key_pair()].
+///
+/// All methods fail with [ConversionError::SignerError], if WebCrypto is not available or
+/// rejects the operation.
+pub struct WebCryptoSigner {
+    private_key: CryptoKey,
+    public_crypto_key: CryptoKey,
+    public_key: P256PublicKey,
+}
+
+impl WebCryptoSigner {
+    /// Generates a new P-256 key pair. If `extractable` is `false`, the private key can not be
+    /// exported from WebCrypto.
+    pub async fn generate(extractable: bool) -> Result<Self, ConversionError> {
+        log::trace!(
+            "[WebCryptoSigner::generate()] generating key pair, extractable: {}",
+            extractable
+        );
+        let params = EcKeyGenParams::new(ALGORITHM, NAMED_CURVE);
+        let usages = Array::of2(&JsValue::from_str("sign"), &JsValue::from_str("verify"));
+        let promise = subtle_crypto()?
+            .generate_key_with_object(&params, extractable, &usages)
+            .map_err(webcrypto_error)?;
+        let key_pair: CryptoKeyPair = JsFuture::from(promise)
+            .await
+            .map_err(webcrypto_error)?
+            .unchecked_into();
+        Self::from_key_pair(&key_pair).await
+    }
+
+    /// Creates a signer from an existing ECDSA P-256 [CryptoKeyPair], e.g. one loaded from
+    /// IndexedDB. The public key is exported from WebCrypto; the private key may be
+    /// non-extractable.
+    pub async fn from_key_pair(key_pair: &CryptoKeyPair) -> Result<Self, ConversionError> {
+        let private_key = key_pair.get_private_key();
+        let public_crypto_key = key_pair.get_public_key();
+        if private_key.type_() != "private" || public_crypto_key.type_() != "public" {
+            return Err(ConversionError::SignerError(
+                "The key pair does not consist of a private and a public key".to_string(),
+            ));
+        }
+        let promise = subtle_crypto()?
+            .export_key("raw", &public_crypto_key)
+            .map_err(webcrypto_error)?;
+        let raw: ArrayBuffer = JsFuture::from(promise)
+            .await
+            .map_err(webcrypto_error)?
+            .unchecked_into();
+        let public_key = match P256PublicKey::from_sec1_bytes(&Uint8Array::new(&raw).to_vec()) {
+            Ok(public_key) => public_key,
+            Err(e) => return Err(InvalidCert::PublicKeyError(e).into()),
+        };
+        Ok(Self {
+            private_key,
+            public_crypto_key,
+            public_key,
+        })
+    }
+
+    /// Returns the [P256PublicKey] of this signer, which can be used to verify its signatures
+    /// without WebCrypto.
+    pub fn public_key(&self) -> &P256PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the private [CryptoKey] of this signer, e.g. to persist it in IndexedDB.
+    pub fn crypto_key(&self) -> &CryptoKey {
+        &self.private_key
+    }
+
+    /// Returns the public [CryptoKey] of this signer.
+    pub fn public_crypto_key(&self) -> &CryptoKey {
+        &self.public_crypto_key
+    }
+
+    /// Returns `true`, if the private key of this signer can be exported from WebCrypto.
+    pub fn is_extractable(&self) -> bool {
+        self.private_key.extractable()
+    }
+
+    /// Signs `data` using WebCrypto.
+    pub async fn try_sign(&self, data: &[u8]) -> Result<P256Signature, ConversionError> {
+        log::trace!("[WebCryptoSigner::try_sign()] signing {} bytes", data.len());
+        let promise = subtle_crypto()?
+            .sign_with_object_and_buffer_source(
+                &ecdsa_params(),
+                &self.private_key,
+                &Uint8Array::from(data),
+            )
+            .map_err(webcrypto_error)?;
+        let raw: ArrayBuffer = JsFuture::from(promise)
+            .await
+            .map_err(webcrypto_error)?
+            .unchecked_into();
+        // WebCrypto returns the concatenation of r and s, while polyproto uses the DER encoding.
+        let signature = match EcdsaSignature::from_slice(&Uint8Array::new(&raw).to_vec()) {
+            Ok(signature) => signature,
+            Err(e) => return Err(ConversionError::SignerError(e.to_string())),
+        };
+        Ok(P256Signature::from_bytes(signature.to_der().as_bytes()))
+    }
+
+    /// Verifies `signature` over `data` using WebCrypto.
+    pub async fn verify(
+        &self,
+        signature: &P256Signature,
+        data: &[u8],
+    ) -> Result<(), ConversionError> {
+        let signature = match EcdsaSignature::from_der(signature.as_signature()) {
+            Ok(signature) => signature.to_bytes(),
+            Err(_) => return Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature).into()),
+        };
+        let promise = subtle_crypto()?
+            .verify_with_object_and_buffer_source_and_buffer_source(
+                &ecdsa_params(),
+                &self.public_crypto_key,
+                &Uint8Array::from(signature.as_slice()),
+                &Uint8Array::from(data),
+            )
+            .map_err(webcrypto_error)?;
+        match JsFuture::from(promise)
+            .await
+            .map_err(webcrypto_error)?
+            .as_bool()
+        {
+            Some(true) => Ok(()),
+            _ => Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature).into()),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AsyncPrivateKey<P256Signature> for WebCryptoSigner {
+    type PublicKey = P256PublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignFuture<'a, P256Signature> {
+        Box::pin(self.try_sign(data))
+    }
+}
+
+/// Returns the `SubtleCrypto` interface of the global scope, which may be a window, a worker or a
+/// JavaScript runtime such as Node.js.
+fn subtle_crypto() -> Result<SubtleCrypto, ConversionError> {
+    let crypto =
+        Reflect::get(&js_sys::global(), &JsValue::from_str("crypto")).map_err(webcrypto_error)?;
+    if crypto.is_undefined() || crypto.is_null() {
+        return Err(ConversionError::SignerError(
+            "WebCrypto is not available in this environment".to_string(),
+        ));
+    }
+    Ok(crypto.unchecked_into::<Crypto>().subtle())
+}
+
+fn ecdsa_params() -> EcdsaParams {
+    EcdsaParams::new(ALGORITHM, &JsValue::from_str(HASH))
+}
+
+fn webcrypto_error(error: JsValue) -> ConversionError {
+    log::debug!("[WebCryptoSigner] WebCrypto operation failed: {:?}", error);
+    ConversionError::SignerError(format!("WebCrypto operation failed: {:?}", error))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use super::WebCryptoSigner;
+    use crate::key::{AsyncPrivateKey, PublicKey};
+
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    async fn sign_and_verify() {
+        let signer = WebCryptoSigner::generate(false).await.unwrap();
+        assert!(!signer.is_extractable());
+        let signature = AsyncPrivateKey::sign(&signer, b"polyproto").await.unwrap();
+        assert!(signer
+            .public_key()
+            .verify_signature(&signature, b"polyproto")
+            .is_ok());
+        assert!(signer.verify(&signature, b"polyproto").await.is_ok());
+        assert!(signer.verify(&signature, b"polyphony").await.is_err());
+    }
+}
@@ -0,0 +1,199 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::certs::certid::CertId;
+#[cfg(feature = "types")]
+use crate::certs::idcert::IdCert;
+use crate::errors::InvalidCert;
+
+#[cfg(feature = "redis")]
+/// [RedisCertCache](redis::RedisCertCache), sharing the certificates of foreign home servers and
+/// their validation results between processes using Redis.
+pub mod redis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// The circumstances under which a cached value was computed: the validation profile, the
+/// algorithm policy and the generation of the trust store.
+///
+/// Profiles and policies are identified by a hash of their value, so that any change to them
+/// results in a different [CacheGeneration], without anyone having to remember to announce the
+/// change. Trust stores are identified by a generation counter, such as the one returned by
+/// `HomeServer::trust_store_generation()` of the `reqwest` API, which is incremented whenever the
+/// trust store is modified.
+///
+/// A [GenerationalCache] only returns values computed under the current [CacheGeneration], and
+/// evicts all others.
+pub struct CacheGeneration {
+    spec_profile: u64,
+    algorithm_policy: u64,
+    trust_store: u64,
+}
+
+impl CacheGeneration {
+    /// Creates a [CacheGeneration] with no profile, no policy and trust store generation zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the validation profile, such as a
+    /// [GuestProfile](crate::certs::guest::GuestProfile) or a
+    /// [WarningPolicy](crate::verifier::WarningPolicy), values are validated against.
+    pub fn with_spec_profile(mut self, profile: &impl Hash) -> Self {
+        self.spec_profile = hash(profile);
+        self
+    }
+
+    /// Sets the algorithm policy, such as the list of accepted signature algorithm OIDs, values are
+    /// validated under.
+    pub fn with_algorithm_policy(mut self, policy: &impl Hash) -> Self {
+        self.algorithm_policy = hash(policy);
+        self
+    }
+
+    /// Sets the generation of the trust store values are verified against.
+    pub fn with_trust_store(mut self, generation: u64) -> Self {
+        self.trust_store = generation;
+        self
+    }
+
+    /// Returns the generation of the trust store.
+    pub fn trust_store(&self) -> u64 {
+        self.trust_store
+    }
+}
+
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    value: V,
+    generation: CacheGeneration,
+}
+
+#[derive(Debug, Clone)]
+/// A cache whose entries record the [CacheGeneration] they were computed under. Looking up an
+/// entry under a different generation evicts it, so that changing a profile, a policy or the
+/// trust store invalidates all affected entries without a manual flush.
+///
+/// See [ValidationCache] for caching verification results of certificates.
+pub struct GenerationalCache<K: Eq + Hash, V> {
+    entries: HashMap<K, Entry<V>>,
+    invalidations: u64,
+}
+
+impl<K: Eq + Hash, V> Default for GenerationalCache<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            invalidations: 0,
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> GenerationalCache<K, V> {
+    /// Creates an empty [GenerationalCache].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value cached for `key`, if it was computed under `generation`. Values computed
+    /// under another generation are evicted.
+    pub fn get(&mut self, key: &K, generation: CacheGeneration) -> Option<&V> {
+        self.evict_stale(key, generation);
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Caches `value` for `key`, computed under `generation`. Returns the value previously
+    /// cached for `key`, regardless of its generation.
+    pub fn insert(&mut self, key: K, value: V, generation: CacheGeneration) -> Option<V> {
+        self.entries
+            .insert(key, Entry { value, generation })
+            .map(|entry| entry.value)
+    }
+
+    /// Returns the value cached for `key` under `generation`, computing and caching it using `f`
+    /// if there is none.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: K,
+        generation: CacheGeneration,
+        f: impl FnOnce() -> V,
+    ) -> &V {
+        self.evict_stale(&key, generation);
+        &self
+            .entries
+            .entry(key)
+            .or_insert_with(|| Entry {
+                value: f(),
+                generation,
+            })
+            .value
+    }
+
+    /// Removes the value cached for `key`, regardless of its generation.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key).map(|entry| entry.value)
+    }
+
+    /// Evicts all entries not computed under `generation`. Returns the number of evicted
+    /// entries.
+    pub fn purge_stale(&mut self, generation: CacheGeneration) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, entry| entry.generation == generation);
+        let evicted = before - self.entries.len();
+        self.invalidations += evicted as u64;
+        evicted
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the number of cached entries, including those of previous generations which have
+    /// not been evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true`, if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of entries evicted because they were computed under a previous
+    /// generation.
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations
+    }
+
+    fn evict_stale(&mut self, key: &K, generation: CacheGeneration) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.generation != generation)
+        {
+            log::trace!("[GenerationalCache] Evicting entry of a previous generation");
+            self.entries.remove(key);
+            self.invalidations += 1;
+        }
+    }
+}
+
+/// A [GenerationalCache] of the verification results of certificates, keyed by their [CertId].
+pub type ValidationCache = GenerationalCache<CertId, Result<(), InvalidCert>>;
+
+#[cfg(feature = "types")]
+/// A [GenerationalCache] of the certificates of actors, keyed by their
+/// [FederationId](crate::types::FederationId).
+pub type ActorCache<S, P> = GenerationalCache<crate::types::FederationId, IdCert<S, P>>;
@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::pem::LineEnding;
+use redis::aio::ConnectionLike;
+use serde::{Deserialize, Serialize};
+
+use crate::certs::idcert::IdCert;
+use crate::errors::{CertStoreError, InvalidCert};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+/// The prefix of the keys written by a [RedisCertCache], unless configured otherwise using
+/// [RedisCertCache::with_prefix()].
+pub static DEFAULT_KEY_PREFIX: &str = "polyproto:server-cert:";
+
+#[derive(Debug, Clone, PartialEq)]
+/// A certificate of a foreign home server, together with the result of validating it, as
+/// returned by [RedisCertCache::get()].
+pub struct CachedServerCert<S: Signature, P: PublicKey<S>> {
+    /// The certificate of the home server.
+    pub cert: IdCert<S, P>,
+    /// `None`, if the certificate passed validation, or a description of the reason it was
+    /// rejected.
+    pub validation_error: Option<String>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CachedServerCert<S, P> {
+    /// Returns `true`, if the certificate passed validation.
+    pub fn is_valid(&self) -> bool {
+        self.validation_error.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    pem: String,
+    validation_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// A cache of the [IdCert]s of foreign home servers and the results of validating them, stored
+/// in Redis, so that all processes of a home server deployment share one warm cache.
+///
+/// Each entry is keyed by the domain of the home server and expires together with the
+/// [Validity](x509_cert::time::Validity) of its certificate. Rejected certificates are only
+/// cached for the negative TTL, which defaults to five minutes, so that a home server fixing its
+/// certificate is not shunned until the broken certificate would have expired.
+///
+/// The cache works with any asynchronous Redis connection which can be cloned cheaply, such as a
+/// [MultiplexedConnection](redis::aio::MultiplexedConnection).
+pub struct RedisCertCache<C> {
+    connection: C,
+    prefix: String,
+    negative_ttl: Duration,
+}
+
+impl<C: ConnectionLike + Clone + Send + Sync> RedisCertCache<C> {
+    /// Creates a [RedisCertCache] sending commands through `connection`.
+    pub fn new(connection: C) -> Self {
+        Self {
+            connection,
+            prefix: DEFAULT_KEY_PREFIX.to_string(),
+            negative_ttl: Duration::from_secs(300),
+        }
+    }
+
+    /// Sets the prefix of the keys written by the cache. Deployments sharing one Redis instance
+    /// between several caches can use this to keep them apart.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Sets how long rejected certificates are cached.
+    pub fn with_negative_ttl(mut self, negative_ttl: Duration) -> Self {
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    /// Returns the Redis key the certificate of the home server `server` is stored under.
+    pub fn key(&self, server: &str) -> String {
+        format!("{}{}", self.prefix, server)
+    }
+
+    /// Caches `cert`, the certificate of the home server `server`, together with `validation`,
+    /// the result of validating it. The entry expires once `cert` is no longer valid or, if
+    /// `validation` failed, after the negative TTL.
+    ///
+    /// Returns `false` without caching anything, if `cert` has already expired at `now`.
+    pub async fn insert<S: Signature, P: PublicKey<S>>(
+        &self,
+        server: &str,
+        cert: &IdCert<S, P>,
+        validation: &Result<(), InvalidCert>,
+        now: Timestamp,
+    ) -> Result<bool, CertStoreError> {
+        let not_after = Timestamp::from(cert.id_cert_tbs.validity.not_after).unix_seconds();
+        let mut ttl = not_after.saturating_sub(now.unix_seconds());
+        if validation.is_err() {
+            ttl = ttl.min(self.negative_ttl.as_secs());
+        }
+        if ttl == 0 {
+            log::trace!(
+                "[RedisCertCache::insert()] Not caching expired certificate of {}",
+                server
+            );
+            return Ok(false);
+        }
+        let entry = Entry {
+            pem: cert.to_pem(LineEnding::LF)?,
+            validation_error: validation.as_ref().err().map(|error| error.to_string()),
+        };
+        let value = serde_json::to_string(&entry)
+            .map_err(|error| CertStoreError::Backend(error.to_string()))?;
+        log::trace!(
+            "[RedisCertCache::insert()] Caching certificate of {} for {} seconds",
+            server,
+            ttl
+        );
+        redis::cmd("SET")
+            .arg(self.key(server))
+            .arg(value)
+            .arg("EX")
+            .arg(ttl)
+            .query_async::<()>(&mut self.connection.clone())
+            .await?;
+        Ok(true)
+    }
+
+    /// Returns the cached certificate of the home server `server` and the result of validating
+    /// it, or `None`, if there is none or the cached certificate is not valid at `now`.
+    pub async fn get<S: Signature, P: PublicKey<S>>(
+        &self,
+        server: &str,
+        now: Timestamp,
+    ) -> Result<Option<CachedServerCert<S, P>>, CertStoreError> {
+        let value = redis::cmd("GET")
+            .arg(self.key(server))
+            .query_async::<Option<String>>(&mut self.connection.clone())
+            .await?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let entry: Entry = serde_json::from_str(&value)
+            .map_err(|error| CertStoreError::Backend(error.to_string()))?;
+        let cert = IdCert::from_pem_unchecked(&entry.pem)?;
+        // Clocks of the processes sharing the cache may disagree on when an entry expires.
+        if !cert.valid_at(now) {
+            return Ok(None);
+        }
+        Ok(Some(CachedServerCert {
+            cert,
+            validation_error: entry.validation_error,
+        }))
+    }
+
+    /// Removes the cached certificate of the home server `server`. Returns `false`, if there was
+    /// none.
+    pub async fn remove(&self, server: &str) -> Result<bool, CertStoreError> {
+        let removed = redis::cmd("DEL")
+            .arg(self.key(server))
+            .query_async::<u64>(&mut self.connection.clone())
+            .await?;
+        Ok(removed > 0)
+    }
+}
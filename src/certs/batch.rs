@@ -0,0 +1,42 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rayon::prelude::*;
+
+use crate::errors::InvalidCert;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::idcert::IdCert;
+use super::policy::ValidationPolicy;
+use super::Target;
+
+/// Verifies each of `certs` using [IdCert::full_verify_with()], spreading the certificates over
+/// the rayon thread pool. Returns one result per certificate, in the order of `certs`.
+///
+/// This is meant for revalidating many certificates at once, for example the certificate cache of
+/// a server after its CRL has been updated. The global rayon thread pool is used, unless this
+/// function is called from within
+/// [ThreadPool::install()](https://docs.rs/rayon/latest/rayon/struct.ThreadPool.html#method.install).
+pub fn validate_all<S, P>(
+    certs: &[IdCert<S, P>],
+    target: Option<Target>,
+    time: Timestamp,
+    home_server_public_key: &P,
+    policy: &ValidationPolicy,
+) -> Vec<Result<(), InvalidCert>>
+where
+    S: Signature + Send + Sync,
+    P: PublicKey<S> + Send + Sync,
+{
+    log::trace!(
+        "[validate_all()] Verifying {} certificates in parallel",
+        certs.len()
+    );
+    certs
+        .par_iter()
+        .map(|cert| cert.full_verify_with(target, time, home_server_public_key, policy))
+        .collect()
+}
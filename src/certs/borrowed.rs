@@ -0,0 +1,473 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{AnyRef, BitStringRef, ContextSpecific, OctetStringRef, UintRef};
+use der::{Decode, Reader, SliceReader, Tag, TagNumber, Tagged};
+use spki::{
+    AlgorithmIdentifierRef, ObjectIdentifier, SubjectPublicKeyInfoOwned, SubjectPublicKeyInfoRef,
+};
+use x509_cert::name::Name;
+use x509_cert::time::Validity;
+
+use crate::errors::{ConstraintError, ConversionError, InvalidCert};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::capabilities::OID_EXTENSION_REQUEST;
+use super::idcert::IdCert;
+use super::idcsr::IdCsr;
+
+/// The message of the panics when re-reading encodings which were checked when creating a view.
+static ERR_CHECKED: &str =
+    "Illegal state. Please report this error to https://github.com/polyphony-chat/polyproto";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A distinguished name borrowed from the DER encoding of an [IdCertRef] or [IdCsrRef].
+pub struct NameRef<'a> {
+    der: &'a [u8],
+}
+
+impl<'a> NameRef<'a> {
+    /// Checks that `der` encodes a SEQUENCE of relative distinguished names.
+    fn new(der: &'a [u8]) -> Result<Self, ConversionError> {
+        let name = AnyRef::from_der(der)?;
+        name.tag().assert_eq(Tag::Sequence)?;
+        for rdn in Elements::new(name.value())? {
+            let rdn = rdn?;
+            rdn.tag().assert_eq(Tag::Set)?;
+            for attribute in Elements::new(rdn.value())? {
+                decode_attribute(attribute?)?;
+            }
+        }
+        Ok(Self { der })
+    }
+
+    /// The DER encoding of the name.
+    pub fn der(&self) -> &'a [u8] {
+        self.der
+    }
+
+    /// Iterates over the attributes of the name, in the order in which they are encoded. The
+    /// attributes of multi-valued relative distinguished names are returned one after another.
+    pub fn attributes(&self) -> impl Iterator<Item = (ObjectIdentifier, AnyRef<'a>)> + 'a {
+        let name = AnyRef::from_der(self.der).expect(ERR_CHECKED);
+        Elements::new(name.value())
+            .expect(ERR_CHECKED)
+            .flat_map(|rdn| Elements::new(rdn.expect(ERR_CHECKED).value()).expect(ERR_CHECKED))
+            .map(|attribute| decode_attribute(attribute.expect(ERR_CHECKED)).expect(ERR_CHECKED))
+    }
+
+    /// Returns the value of the first attribute with the type `oid`, if there is one.
+    pub fn get(&self, oid: ObjectIdentifier) -> Option<AnyRef<'a>> {
+        self.attributes()
+            .find(|(attribute_oid, _)| *attribute_oid == oid)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value of the first attribute with the type `oid` as a string, if there is
+    /// one and its value is valid UTF-8.
+    pub fn get_str(&self, oid: ObjectIdentifier) -> Option<&'a str> {
+        std::str::from_utf8(self.get(oid)?.value()).ok()
+    }
+
+    /// Decodes the name into an owned [Name].
+    pub fn to_name(&self) -> Result<Name, ConversionError> {
+        Ok(Name::from_der(self.der)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An X.509 extension borrowed from the DER encoding of an [IdCertRef] or [IdCsrRef].
+pub struct ExtensionRef<'a> {
+    /// The Object Identifier of the extension.
+    pub oid: ObjectIdentifier,
+    /// Whether the extension is marked as critical.
+    pub critical: bool,
+    /// The DER encoded value of the extension.
+    pub value: &'a [u8],
+}
+
+impl<'a> ExtensionRef<'a> {
+    fn decode(any: AnyRef<'a>) -> Result<Self, ConversionError> {
+        any.tag().assert_eq(Tag::Sequence)?;
+        let mut reader = SliceReader::new(any.value())?;
+        let oid = ObjectIdentifier::decode(&mut reader)?;
+        let critical = Option::<bool>::decode(&mut reader)?.unwrap_or(false);
+        let value = OctetStringRef::decode(&mut reader)?.as_bytes();
+        reader.finish(())?;
+        Ok(Self {
+            oid,
+            critical,
+            value,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A read-only view of a DER encoded [IdCert], which borrows all of its fields from the input
+/// instead of decoding them into owned values.
+///
+/// Creating an [IdCertRef] only checks that the input is structured like an X.509 certificate; it
+/// does not validate the certificate against the polyproto specification. Use
+/// [IdCertRef::to_id_cert()] to decode it into an [IdCert] when that is needed. Signatures are
+/// verified over the [TBSCertificate](IdCertRef::tbs_der()) as it was transmitted.
+pub struct IdCertRef<'a> {
+    der: &'a [u8],
+    tbs_der: &'a [u8],
+    serial_number: UintRef<'a>,
+    issuer: NameRef<'a>,
+    validity: Validity,
+    subject: NameRef<'a>,
+    subject_public_key_info: SubjectPublicKeyInfoRef<'a>,
+    extensions: Option<&'a [u8]>,
+    signature_algorithm: AlgorithmIdentifierRef<'a>,
+    signature: BitStringRef<'a>,
+}
+
+impl<'a> IdCertRef<'a> {
+    /// Creates an [IdCertRef] borrowing from the DER encoded X.509 certificate `der`.
+    pub fn from_der(der: &'a [u8]) -> Result<Self, ConversionError> {
+        let certificate = AnyRef::from_der(der)?;
+        certificate.tag().assert_eq(Tag::Sequence)?;
+        let mut reader = SliceReader::new(certificate.value())?;
+        let tbs_der = reader.tlv_bytes()?;
+        let signature_algorithm = AlgorithmIdentifierRef::decode(&mut reader)?;
+        let signature = BitStringRef::decode(&mut reader)?;
+        reader.finish(())?;
+
+        let tbs_certificate = AnyRef::from_der(tbs_der)?;
+        tbs_certificate.tag().assert_eq(Tag::Sequence)?;
+        let mut reader = SliceReader::new(tbs_certificate.value())?;
+        // The version is optional, but always present in polyproto certificates.
+        ContextSpecific::<AnyRef<'a>>::decode_explicit(&mut reader, TagNumber::N0)?;
+        let serial_number = UintRef::decode(&mut reader)?;
+        AlgorithmIdentifierRef::decode(&mut reader)?;
+        let issuer = decode_name(&mut reader)?;
+        let validity = Validity::decode(&mut reader)?;
+        let subject = decode_name(&mut reader)?;
+        let subject_public_key_info = SubjectPublicKeyInfoRef::decode(&mut reader)?;
+        let mut extensions = None;
+        while !reader.is_finished() {
+            let field = AnyRef::decode(&mut reader)?;
+            if field.tag() == context_specific(TagNumber::N3) {
+                let sequence = AnyRef::from_der(field.value())?;
+                sequence.tag().assert_eq(Tag::Sequence)?;
+                for extension in Elements::new(sequence.value())? {
+                    ExtensionRef::decode(extension?)?;
+                }
+                extensions = Some(sequence.value());
+            }
+        }
+        Ok(Self {
+            der,
+            tbs_der,
+            serial_number,
+            issuer,
+            validity,
+            subject,
+            subject_public_key_info,
+            extensions,
+            signature_algorithm,
+            signature,
+        })
+    }
+
+    /// The DER encoding of the certificate.
+    pub fn der(&self) -> &'a [u8] {
+        self.der
+    }
+
+    /// The DER encoding of the TBSCertificate, which is the data the signature of the certificate
+    /// is computed over.
+    pub fn tbs_der(&self) -> &'a [u8] {
+        self.tbs_der
+    }
+
+    /// The serial number of the certificate.
+    pub fn serial_number(&self) -> UintRef<'a> {
+        self.serial_number
+    }
+
+    /// The name of the issuer of the certificate.
+    pub fn issuer(&self) -> NameRef<'a> {
+        self.issuer
+    }
+
+    /// The validity period of the certificate.
+    pub fn validity(&self) -> Validity {
+        self.validity
+    }
+
+    /// The name of the subject of the certificate.
+    pub fn subject(&self) -> NameRef<'a> {
+        self.subject
+    }
+
+    /// The public key of the subject of the certificate.
+    pub fn subject_public_key_info(&self) -> &SubjectPublicKeyInfoRef<'a> {
+        &self.subject_public_key_info
+    }
+
+    /// Iterates over the extensions of the certificate.
+    pub fn extensions(&self) -> impl Iterator<Item = ExtensionRef<'a>> + 'a {
+        extension_refs(self.extensions)
+    }
+
+    /// Returns the extension with the Object Identifier `oid`, if the certificate carries it.
+    pub fn extension(&self, oid: ObjectIdentifier) -> Option<ExtensionRef<'a>> {
+        self.extensions().find(|extension| extension.oid == oid)
+    }
+
+    /// The algorithm the certificate was signed with.
+    pub fn signature_algorithm(&self) -> &AlgorithmIdentifierRef<'a> {
+        &self.signature_algorithm
+    }
+
+    /// The raw signature of the certificate.
+    pub fn signature(&self) -> &'a [u8] {
+        self.signature.raw_bytes()
+    }
+
+    /// Checks if the certificate is valid at `time`. Does not validate the certificate against the
+    /// polyproto specification.
+    pub fn valid_at(&self, time: impl Into<Timestamp>) -> bool {
+        let time = time.into();
+        time >= Timestamp::from(self.validity.not_before)
+            && time <= Timestamp::from(self.validity.not_after)
+    }
+
+    /// Verifies the signature of the certificate over its [TBSCertificate](IdCertRef::tbs_der())
+    /// using the public key of its issuer. For a self-signed home server certificate, pass the
+    /// subject public key of the certificate itself.
+    pub fn verify_signature<S: Signature, P: PublicKey<S>>(
+        &self,
+        issuer_public_key: &P,
+    ) -> Result<(), InvalidCert> {
+        Ok(issuer_public_key.verify_signature(&S::from_bytes(self.signature()), self.tbs_der)?)
+    }
+
+    /// Decodes the certificate into an owned [IdCert], without validating it.
+    pub fn to_id_cert<S: Signature, P: PublicKey<S>>(
+        &self,
+    ) -> Result<IdCert<S, P>, ConversionError> {
+        IdCert::from_der_unchecked(self.der)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A read-only view of a DER encoded [IdCsr], which borrows all of its fields from the input
+/// instead of decoding them into owned values.
+///
+/// Like [IdCertRef], creating an [IdCsrRef] does not validate the CSR. Use
+/// [IdCsrRef::to_id_csr()] to decode it into an [IdCsr] when that is needed.
+pub struct IdCsrRef<'a> {
+    der: &'a [u8],
+    info_der: &'a [u8],
+    subject: NameRef<'a>,
+    subject_public_key_info_der: &'a [u8],
+    subject_public_key_info: SubjectPublicKeyInfoRef<'a>,
+    attributes: &'a [u8],
+    extensions: Option<&'a [u8]>,
+    signature_algorithm: AlgorithmIdentifierRef<'a>,
+    signature: BitStringRef<'a>,
+}
+
+impl<'a> IdCsrRef<'a> {
+    /// Creates an [IdCsrRef] borrowing from the DER encoded PKCS #10 CSR `der`.
+    pub fn from_der(der: &'a [u8]) -> Result<Self, ConversionError> {
+        let csr = AnyRef::from_der(der)?;
+        csr.tag().assert_eq(Tag::Sequence)?;
+        let mut reader = SliceReader::new(csr.value())?;
+        let info_der = reader.tlv_bytes()?;
+        let signature_algorithm = AlgorithmIdentifierRef::decode(&mut reader)?;
+        let signature = BitStringRef::decode(&mut reader)?;
+        reader.finish(())?;
+
+        let info = AnyRef::from_der(info_der)?;
+        info.tag().assert_eq(Tag::Sequence)?;
+        let mut reader = SliceReader::new(info.value())?;
+        u8::decode(&mut reader)?;
+        let subject = decode_name(&mut reader)?;
+        let subject_public_key_info_der = reader.tlv_bytes()?;
+        let subject_public_key_info =
+            SubjectPublicKeyInfoRef::from_der(subject_public_key_info_der)?;
+        let attributes = AnyRef::decode(&mut reader)?;
+        attributes
+            .tag()
+            .assert_eq(context_specific(TagNumber::N0))?;
+        reader.finish(())?;
+
+        let extension_request = ObjectIdentifier::from_str(OID_EXTENSION_REQUEST)?;
+        let mut extensions = None;
+        for attribute in Elements::new(attributes.value())? {
+            let (oid, values) = decode_attribute(attribute?)?;
+            values.tag().assert_eq(Tag::Set)?;
+            if oid != extension_request {
+                continue;
+            }
+            for value in Elements::new(values.value())? {
+                let sequence = value?;
+                sequence.tag().assert_eq(Tag::Sequence)?;
+                for extension in Elements::new(sequence.value())? {
+                    ExtensionRef::decode(extension?)?;
+                }
+                extensions = Some(sequence.value());
+            }
+        }
+        Ok(Self {
+            der,
+            info_der,
+            subject,
+            subject_public_key_info_der,
+            subject_public_key_info,
+            attributes: attributes.value(),
+            extensions,
+            signature_algorithm,
+            signature,
+        })
+    }
+
+    /// The DER encoding of the CSR.
+    pub fn der(&self) -> &'a [u8] {
+        self.der
+    }
+
+    /// The DER encoding of the CertificationRequestInfo, which is the data the signature of the
+    /// CSR is computed over.
+    pub fn info_der(&self) -> &'a [u8] {
+        self.info_der
+    }
+
+    /// The name of the subject requesting the certificate.
+    pub fn subject(&self) -> NameRef<'a> {
+        self.subject
+    }
+
+    /// The public key of the subject requesting the certificate.
+    pub fn subject_public_key_info(&self) -> &SubjectPublicKeyInfoRef<'a> {
+        &self.subject_public_key_info
+    }
+
+    /// Iterates over the attributes of the CSR, returning the type and the undecoded SET of values
+    /// of each attribute. polyproto requests the
+    /// [Capabilities](super::capabilities::Capabilities) of a CSR using attributes named after
+    /// the OIDs of the corresponding extensions.
+    pub fn attributes(&self) -> impl Iterator<Item = (ObjectIdentifier, AnyRef<'a>)> + 'a {
+        Elements::new(self.attributes)
+            .expect(ERR_CHECKED)
+            .map(|attribute| decode_attribute(attribute.expect(ERR_CHECKED)).expect(ERR_CHECKED))
+    }
+
+    /// Returns the values of the attribute with the type `oid`, if the CSR carries it.
+    pub fn attribute(&self, oid: ObjectIdentifier) -> Option<AnyRef<'a>> {
+        self.attributes()
+            .find(|(attribute_oid, _)| *attribute_oid == oid)
+            .map(|(_, values)| values)
+    }
+
+    /// Iterates over the custom extensions requested using the PKCS #9 `extensionRequest`
+    /// attribute.
+    pub fn extensions(&self) -> impl Iterator<Item = ExtensionRef<'a>> + 'a {
+        extension_refs(self.extensions)
+    }
+
+    /// Returns the requested extension with the Object Identifier `oid`, if the CSR carries it.
+    pub fn extension(&self, oid: ObjectIdentifier) -> Option<ExtensionRef<'a>> {
+        self.extensions().find(|extension| extension.oid == oid)
+    }
+
+    /// The algorithm the CSR was signed with.
+    pub fn signature_algorithm(&self) -> &AlgorithmIdentifierRef<'a> {
+        &self.signature_algorithm
+    }
+
+    /// The raw signature of the CSR.
+    pub fn signature(&self) -> &'a [u8] {
+        self.signature.raw_bytes()
+    }
+
+    /// Verifies the signature of the CSR over its
+    /// [CertificationRequestInfo](IdCsrRef::info_der()) using the public key of its subject.
+    pub fn verify_signature<S: Signature, P: PublicKey<S>>(&self) -> Result<(), InvalidCert> {
+        let public_key = SubjectPublicKeyInfoOwned::from_der(self.subject_public_key_info_der)
+            .map_err(ConversionError::from)
+            .and_then(|public_key_info| P::try_from_public_key_info(public_key_info.into()));
+        let public_key = match public_key {
+            Ok(public_key) => public_key,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        Ok(public_key.verify_signature(&S::from_bytes(self.signature()), self.info_der)?)
+    }
+
+    /// Decodes the CSR into an owned [IdCsr], without validating it.
+    pub fn to_id_csr<S: Signature, P: PublicKey<S>>(&self) -> Result<IdCsr<S, P>, ConversionError> {
+        IdCsr::from_der_unchecked(self.der)
+    }
+}
+
+/// Iterates over the elements of the encoded contents of a SEQUENCE or SET, without decoding
+/// them.
+struct Elements<'a> {
+    reader: SliceReader<'a>,
+}
+
+impl<'a> Elements<'a> {
+    fn new(contents: &'a [u8]) -> Result<Self, ConversionError> {
+        Ok(Self {
+            reader: SliceReader::new(contents)?,
+        })
+    }
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = Result<AnyRef<'a>, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.is_finished() || self.reader.is_failed() {
+            return None;
+        }
+        Some(AnyRef::decode(&mut self.reader).map_err(ConversionError::from))
+    }
+}
+
+/// Decodes the next element of `reader` as a [NameRef].
+fn decode_name<'a>(reader: &mut SliceReader<'a>) -> Result<NameRef<'a>, ConversionError> {
+    NameRef::new(reader.tlv_bytes()?)
+}
+
+/// Decodes an AttributeTypeAndValue or an Attribute into its type and its undecoded value.
+fn decode_attribute(
+    attribute: AnyRef<'_>,
+) -> Result<(ObjectIdentifier, AnyRef<'_>), ConversionError> {
+    attribute.tag().assert_eq(Tag::Sequence)?;
+    let mut reader = SliceReader::new(attribute.value())?;
+    let oid = ObjectIdentifier::decode(&mut reader)?;
+    let value = AnyRef::decode(&mut reader)?;
+    reader.finish(())?;
+    Ok((oid, value))
+}
+
+/// Iterates over the extensions encoded in `contents`, which have been checked to be well-formed.
+fn extension_refs<'a>(contents: Option<&'a [u8]>) -> impl Iterator<Item = ExtensionRef<'a>> + 'a {
+    contents
+        .and_then(|contents| Elements::new(contents).ok())
+        .into_iter()
+        .flatten()
+        .map(|extension| extension.and_then(ExtensionRef::decode).expect(ERR_CHECKED))
+}
+
+/// The tag of a constructed, context-specific field with the tag `number`.
+fn context_specific(number: TagNumber) -> Tag {
+    Tag::ContextSpecific {
+        constructed: true,
+        number,
+    }
+}
@@ -100,11 +100,7 @@ impl TryFrom<Attribute> for BasicConstraints {
                 _ => return Err(ConversionError::InvalidInput(InvalidInput::Malformed(format!("Encountered unexpected tag {:?}, when tag should have been either Boolean or Integer", value.tag())))),
             }
         }
-        if num_ca == 0 {
-            return Err(ConversionError::InvalidInput(InvalidInput::Malformed(
-                "Expected 1 Boolean tag, found 0".to_string(),
-            )));
-        }
+        // If no Boolean is present, `cA` has its default value, false.
         Ok(BasicConstraints { ca, path_length })
     }
 }
@@ -113,15 +109,12 @@ impl TryFrom<BasicConstraints> for Attribute {
     type Error = ConversionError;
     fn try_from(value: BasicConstraints) -> Result<Self, Self::Error> {
         let mut sequence = SequenceOf::<Any, 2>::new();
-        sequence.add(Any::new(
-            der::Tag::Boolean,
-            match value.ca {
-                true => vec![0xff],
-                false => vec![0x00],
-            },
-        )?)?;
+        // `cA` defaults to false, and DER requires default values to be left out.
+        if value.ca {
+            sequence.add(Any::new(der::Tag::Boolean, vec![0xff])?)?;
+        }
         if let Some(length) = value.path_length {
-            sequence.add(Any::new(der::Tag::Integer, length.to_be_bytes())?)?;
+            sequence.add(Any::encode_from(&length)?)?;
         }
         let any = Any::from_der(sequence.to_der()?.as_slice())?;
         let mut sov = SetOfVec::new();
@@ -250,10 +243,17 @@ fn any_to_u64(value: Any) -> Result<u64, ConstraintError> {
     match value.tag() {
         Tag::Integer => {
             // The value is given to us a a byte slice of u8. We need to convert this
-            // into a u64.
+            // into a u64. Leading zero bytes are skipped, so that both the minimal DER encoding
+            // and the zero-padded encoding of earlier versions of this crate are accepted.
+            let bytes = value.value();
+            let bytes = &bytes[bytes.iter().take_while(|byte| **byte == 0).count()..];
+            if bytes.len() > 8 {
+                return Err(ConstraintError::Malformed(Some(
+                    "Integer value does not fit into a u64".to_string(),
+                )));
+            }
             let mut buf = [0u8; 8];
-            let len = 8.min(value.value().len());
-            buf[..len].copy_from_slice(value.value());
+            buf[8 - bytes.len()..].copy_from_slice(bytes);
             Ok(u64::from_be_bytes(buf))
         }
         _ => {
@@ -282,6 +282,58 @@ mod test {
         let _extension = Extension::try_from(basic_constraints).unwrap();
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn basic_constraints_der_encoding() {
+        init_logger();
+        for (basic_constraints, encoded) in [
+            (
+                BasicConstraints {
+                    ca: false,
+                    path_length: None,
+                },
+                vec![0x30, 0x00],
+            ),
+            (
+                BasicConstraints {
+                    ca: true,
+                    path_length: Some(0),
+                },
+                vec![0x30, 0x06, 0x01, 0x01, 0xff, 0x02, 0x01, 0x00],
+            ),
+            (
+                BasicConstraints {
+                    ca: true,
+                    path_length: Some(128),
+                },
+                vec![0x30, 0x07, 0x01, 0x01, 0xff, 0x02, 0x02, 0x00, 0x80],
+            ),
+        ] {
+            let extension = Extension::try_from(basic_constraints).unwrap();
+            assert_eq!(extension.extn_value.as_bytes(), encoded.as_slice());
+            assert_eq!(
+                BasicConstraints::try_from(extension).unwrap(),
+                basic_constraints
+            );
+        }
+        // The zero-padded path length encoding of earlier versions is still accepted.
+        let extension = Extension {
+            extn_id: ObjectIdentifier::from_str(OID_BASIC_CONSTRAINTS).unwrap(),
+            critical: true,
+            extn_value: OctetString::new(vec![
+                0x30, 0x0d, 0x01, 0x01, 0xff, 0x02, 0x08, 0, 0, 0, 0, 0, 0, 0, 3,
+            ])
+            .unwrap(),
+        };
+        assert_eq!(
+            BasicConstraints::try_from(extension).unwrap(),
+            BasicConstraints {
+                ca: true,
+                path_length: Some(3)
+            }
+        );
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), test)]
     fn extension_to_basic_constraints() {
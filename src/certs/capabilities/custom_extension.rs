@@ -0,0 +1,160 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{OctetString, SetOfVec};
+use der::{Any, Decode, Encode};
+use spki::ObjectIdentifier;
+use x509_cert::attr::Attribute;
+use x509_cert::ext::{Extension, Extensions};
+
+use crate::certs::claims::OID_CUSTOM_CLAIMS;
+use crate::certs::crldp::OID_CRL_DISTRIBUTION_POINTS;
+use crate::certs::keyid::{OID_AUTHORITY_KEY_IDENTIFIER, OID_SUBJECT_KEY_IDENTIFIER};
+use crate::certs::san::OID_SUBJECT_ALT_NAME;
+use crate::errors::{ConversionError, InvalidInput};
+
+use super::*;
+
+/// The Object Identifiers of the extensions polyproto handles itself, which cannot be attached as
+/// a [CustomExtension].
+const RESERVED_OIDS: [&str; 8] = [
+    OID_BASIC_CONSTRAINTS,
+    OID_KEY_USAGE,
+    OID_EXTENDED_KEY_USAGE,
+    OID_SUBJECT_KEY_IDENTIFIER,
+    OID_AUTHORITY_KEY_IDENTIFIER,
+    OID_SUBJECT_ALT_NAME,
+    OID_CRL_DISTRIBUTION_POINTS,
+    OID_CUSTOM_CLAIMS,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// An X.509 extension not modeled by polyproto, attached to an [IdCsr]/[IdCert] by the
+/// application through its [Capabilities], or preserved when parsing a certificate carrying it.
+///
+/// Parsing follows RFC 5280, section 4.2: unknown non-critical extensions are preserved as
+/// [CustomExtension]s, while unknown critical extensions are rejected with
+/// [ConversionError::UnknownCriticalExtension]. Critical custom extensions can therefore only be
+/// processed by relying parties which understand them without parsing the certificate using
+/// polyproto.
+///
+/// In an [IdCsr], custom extensions are requested using the PKCS #9 `extensionRequest` attribute.
+///
+/// [IdCsr]: crate::certs::idcsr::IdCsr
+/// [IdCert]: crate::certs::idcert::IdCert
+pub struct CustomExtension {
+    oid: ObjectIdentifier,
+    critical: bool,
+    value: Vec<u8>,
+}
+
+impl CustomExtension {
+    /// Creates a new [CustomExtension] with the DER encoded `value`. Fails, if `oid` identifies an
+    /// extension polyproto handles itself, or if `value` is not a single DER encoded value.
+    pub fn new(oid: ObjectIdentifier, critical: bool, value: &[u8]) -> Result<Self, InvalidInput> {
+        if RESERVED_OIDS.contains(&oid.to_string().as_str()) {
+            return Err(InvalidInput::Malformed(format!(
+                "The extension {} is handled by polyproto and cannot be attached as a custom extension",
+                oid
+            )));
+        }
+        if Any::from_der(value).is_err() {
+            return Err(InvalidInput::Malformed(format!(
+                "The value of the custom extension {} is not a single DER encoded value",
+                oid
+            )));
+        }
+        Ok(Self {
+            oid,
+            critical,
+            value: value.to_vec(),
+        })
+    }
+
+    /// The Object Identifier of the extension.
+    pub fn oid(&self) -> ObjectIdentifier {
+        self.oid
+    }
+
+    /// Whether the extension is marked as critical.
+    pub fn is_critical(&self) -> bool {
+        self.critical
+    }
+
+    /// The DER encoded value of the extension.
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl TryFrom<CustomExtension> for Extension {
+    type Error = ConversionError;
+
+    fn try_from(value: CustomExtension) -> Result<Self, Self::Error> {
+        Ok(Extension {
+            extn_id: value.oid,
+            critical: value.critical,
+            extn_value: OctetString::new(value.value)?,
+        })
+    }
+}
+
+impl TryFrom<Extension> for CustomExtension {
+    type Error = ConversionError;
+
+    /// Performs the conversion.
+    ///
+    /// Fails with [ConversionError::UnknownCriticalExtension], if the extension is marked as
+    /// critical, as polyproto does not understand it. Also fails, if [CustomExtension::new()]
+    /// fails.
+    fn try_from(value: Extension) -> Result<Self, Self::Error> {
+        if value.critical {
+            log::warn!("Unknown critical extension: {:#?}", value.extn_id);
+            return Err(ConversionError::UnknownCriticalExtension { oid: value.extn_id });
+        }
+        Ok(CustomExtension::new(
+            value.extn_id,
+            value.critical,
+            value.extn_value.as_bytes(),
+        )?)
+    }
+}
+
+/// Encodes `custom_extensions` as a PKCS #9 `extensionRequest` attribute.
+pub(super) fn to_extension_request(
+    custom_extensions: Vec<CustomExtension>,
+) -> Result<Attribute, ConversionError> {
+    let extensions = custom_extensions
+        .into_iter()
+        .map(Extension::try_from)
+        .collect::<Result<Extensions, ConversionError>>()?;
+    let mut sov = SetOfVec::new();
+    sov.insert(Any::from_der(&extensions.to_der()?)?)?;
+    Ok(Attribute {
+        oid: ObjectIdentifier::from_str(OID_EXTENSION_REQUEST)?,
+        values: sov,
+    })
+}
+
+/// Decodes the custom extensions from a PKCS #9 `extensionRequest` attribute.
+pub(super) fn from_extension_request(
+    attribute: &Attribute,
+) -> Result<Vec<CustomExtension>, ConversionError> {
+    if attribute.values.len() != 1 {
+        return Err(ConversionError::InvalidInput(InvalidInput::Length {
+            min_length: 1,
+            max_length: 1,
+            actual_length: attribute.values.len().to_string(),
+        }));
+    }
+    let value = attribute.values.get(0).expect(
+        "Illegal state. Please report this error to https://github.com/polyphony-chat/polyproto",
+    );
+    Extensions::from_der(&value.to_der()?)?
+        .into_iter()
+        .map(CustomExtension::try_from)
+        .collect()
+}
@@ -0,0 +1,206 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{OctetString, SetOfVec};
+use der::{Any, Decode, Encode};
+use spki::ObjectIdentifier;
+use x509_cert::attr::Attribute;
+use x509_cert::ext::pkix::ExtendedKeyUsage as ExtendedKeyUsageSyntax;
+use x509_cert::ext::Extension;
+
+use crate::errors::{ConversionError, InvalidInput};
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A purpose the key contained in a certificate may be used for, in addition to or in place of
+/// the basic purposes indicated by its [KeyUsages]. See RFC 5280, section 4.2.1.12.
+///
+/// The named variants cover the purposes relevant to polyproto. Any other purpose can be
+/// expressed using [ExtendedKeyUsage::Other].
+pub enum ExtendedKeyUsage {
+    /// The key authenticates the instance of a home server to its clients, e.g. in TLS. Reserved
+    /// for home server certificates.
+    ServerAuth,
+    /// The key authenticates an actor or a home server to the instance it connects to, e.g. in
+    /// mutual TLS.
+    ClientAuth,
+    /// The key binds the hash of an object to a time, e.g. to timestamp signed messages.
+    TimeStamping,
+    /// The key signs revocation status responses, such as
+    /// [StatusResponse](crate::certs::status::StatusResponse)s. Reserved for home server
+    /// certificates and their delegated responders.
+    OcspSigning,
+    /// The key may be used for any purpose. Reserved for home server certificates.
+    AnyExtendedKeyUsage,
+    /// Any other purpose, identified by its Object Identifier.
+    Other(ObjectIdentifier),
+}
+
+impl ExtendedKeyUsage {
+    /// Returns the Object Identifier of the purpose.
+    pub fn oid(&self) -> ObjectIdentifier {
+        let oid = match self {
+            ExtendedKeyUsage::ServerAuth => OID_EXTENDED_KEY_USAGE_SERVER_AUTH,
+            ExtendedKeyUsage::ClientAuth => OID_EXTENDED_KEY_USAGE_CLIENT_AUTH,
+            ExtendedKeyUsage::TimeStamping => OID_EXTENDED_KEY_USAGE_TIME_STAMPING,
+            ExtendedKeyUsage::OcspSigning => OID_EXTENDED_KEY_USAGE_OCSP_SIGNING,
+            ExtendedKeyUsage::AnyExtendedKeyUsage => OID_ANY_EXTENDED_KEY_USAGE,
+            ExtendedKeyUsage::Other(oid) => return *oid,
+        };
+        ObjectIdentifier::from_str(oid).expect("Error occurred when converting ExtendedKeyUsage to ObjectIdentifier. Please report this crash at https://github.com/polyphony-chat/polyproto")
+    }
+}
+
+impl From<ObjectIdentifier> for ExtendedKeyUsage {
+    /// Maps the Object Identifiers of the named purposes to their variants, and all other Object
+    /// Identifiers to [ExtendedKeyUsage::Other].
+    fn from(value: ObjectIdentifier) -> Self {
+        match value.to_string().as_str() {
+            OID_EXTENDED_KEY_USAGE_SERVER_AUTH => ExtendedKeyUsage::ServerAuth,
+            OID_EXTENDED_KEY_USAGE_CLIENT_AUTH => ExtendedKeyUsage::ClientAuth,
+            OID_EXTENDED_KEY_USAGE_TIME_STAMPING => ExtendedKeyUsage::TimeStamping,
+            OID_EXTENDED_KEY_USAGE_OCSP_SIGNING => ExtendedKeyUsage::OcspSigning,
+            OID_ANY_EXTENDED_KEY_USAGE => ExtendedKeyUsage::AnyExtendedKeyUsage,
+            _ => ExtendedKeyUsage::Other(value),
+        }
+    }
+}
+
+impl From<ExtendedKeyUsage> for ObjectIdentifier {
+    fn from(value: ExtendedKeyUsage) -> Self {
+        value.oid()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+/// The ExtendedKeyUsages struct is a collection of ExtendedKeyUsage variants. Empty
+/// ExtendedKeyUsages do not restrict the purposes of a key, and are not encoded.
+pub struct ExtendedKeyUsages {
+    /// Vector of ExtendedKeyUsage variants.
+    pub extended_key_usages: Vec<ExtendedKeyUsage>,
+}
+
+impl ExtendedKeyUsages {
+    /// Creates a new ExtendedKeyUsages struct from a slice of ExtendedKeyUsage variants.
+    pub fn new(extended_key_usages: &[ExtendedKeyUsage]) -> Self {
+        ExtendedKeyUsages {
+            extended_key_usages: extended_key_usages.to_vec(),
+        }
+    }
+
+    /// Returns `true`, if `extended_key_usage` is listed.
+    pub fn contains(&self, extended_key_usage: &ExtendedKeyUsage) -> bool {
+        self.extended_key_usages.contains(extended_key_usage)
+    }
+
+    /// Returns `true`, if no purposes are listed.
+    pub fn is_empty(&self) -> bool {
+        self.extended_key_usages.is_empty()
+    }
+
+    /// Encodes the purposes as a DER `SEQUENCE OF KeyPurposeId`.
+    fn to_syntax_der(&self) -> Result<Vec<u8>, ConversionError> {
+        if self.is_empty() {
+            return Err(ConversionError::InvalidInput(InvalidInput::Malformed(
+                "ExtendedKeyUsages must not be empty when encoded".to_string(),
+            )));
+        }
+        let syntax = ExtendedKeyUsageSyntax(
+            self.extended_key_usages
+                .iter()
+                .map(ExtendedKeyUsage::oid)
+                .collect(),
+        );
+        Ok(syntax.to_der()?)
+    }
+
+    /// Decodes the purposes from a DER `SEQUENCE OF KeyPurposeId`.
+    fn from_syntax_der(bytes: &[u8]) -> Result<Self, ConversionError> {
+        let syntax = ExtendedKeyUsageSyntax::from_der(bytes)?;
+        if syntax.0.is_empty() {
+            return Err(ConversionError::InvalidInput(InvalidInput::Malformed(
+                "Expected at least one ExtendedKeyUsage, found none".to_string(),
+            )));
+        }
+        Ok(ExtendedKeyUsages {
+            extended_key_usages: syntax.0.into_iter().map(ExtendedKeyUsage::from).collect(),
+        })
+    }
+}
+
+impl TryFrom<Attribute> for ExtendedKeyUsages {
+    type Error = ConversionError;
+
+    /// Performs the conversion.
+    ///
+    /// Fails, if the attribute does not have the OID of the ExtendedKeyUsage extension, does not
+    /// contain exactly one value, or if that value is not a non-empty sequence of OIDs.
+    fn try_from(value: Attribute) -> Result<Self, Self::Error> {
+        if value.oid.to_string() != OID_EXTENDED_KEY_USAGE {
+            return Err(ConversionError::InvalidInput(InvalidInput::Malformed(
+                format!(
+                    "Expected OID {} for ExtendedKeyUsages, found OID {}",
+                    OID_EXTENDED_KEY_USAGE, value.oid
+                ),
+            )));
+        }
+        if value.values.len() != 1 {
+            return Err(ConversionError::InvalidInput(InvalidInput::Length {
+                min_length: 1,
+                max_length: 1,
+                actual_length: value.values.len().to_string(),
+            }));
+        }
+        let inner_value = value.values.get(0).expect("Illegal state. Please report this error to https://github.com/polyphony-chat/polyproto");
+        ExtendedKeyUsages::from_syntax_der(&inner_value.to_der()?)
+    }
+}
+
+impl TryFrom<Extension> for ExtendedKeyUsages {
+    type Error = ConversionError;
+
+    /// Performs the conversion. The extension may be marked as critical or non-critical.
+    fn try_from(value: Extension) -> Result<Self, Self::Error> {
+        if value.extn_id.to_string() != OID_EXTENDED_KEY_USAGE {
+            return Err(ConversionError::InvalidInput(InvalidInput::Malformed(
+                format!(
+                    "Expected OID {} for ExtendedKeyUsages, found OID {}",
+                    OID_EXTENDED_KEY_USAGE, value.extn_id
+                ),
+            )));
+        }
+        ExtendedKeyUsages::from_syntax_der(value.extn_value.as_bytes())
+    }
+}
+
+impl TryFrom<ExtendedKeyUsages> for Attribute {
+    type Error = ConversionError;
+
+    /// Performs the conversion. Fails, if no purposes are listed.
+    fn try_from(value: ExtendedKeyUsages) -> Result<Self, Self::Error> {
+        let mut sov = SetOfVec::new();
+        sov.insert(Any::from_der(&value.to_syntax_der()?)?)?;
+        Ok(Attribute {
+            oid: ObjectIdentifier::from_str(OID_EXTENDED_KEY_USAGE)?,
+            values: sov,
+        })
+    }
+}
+
+impl TryFrom<ExtendedKeyUsages> for Extension {
+    type Error = ConversionError;
+
+    /// Performs the conversion, producing a non-critical extension. Fails, if no purposes are
+    /// listed.
+    fn try_from(value: ExtendedKeyUsages) -> Result<Self, Self::Error> {
+        Ok(Extension {
+            extn_id: ObjectIdentifier::from_str(OID_EXTENDED_KEY_USAGE)?,
+            critical: false,
+            extn_value: OctetString::new(value.to_syntax_der()?)?,
+        })
+    }
+}
@@ -173,15 +173,12 @@ impl KeyUsages {
         let mut encoded_numbers_vec = encoded_numbers.to_vec();
         if encoded_numbers[0] == 0 {
             encoded_numbers_vec.remove(0);
-            // {:08b} means that we want to format the number as a binary string with 8 bits.
-            let binary = format!("{:08b}", encoded_numbers[1].to_be());
-            for bit in binary.chars() {
-                // If the bit is 0, increment the unused_bits counter.
-                if bit == '0' {
-                    unused_bits += 1;
-                } else {
-                    break;
-                }
+            // The unused bits are the trailing zero bits of the last byte, which DER requires to be
+            // left out of the named bit list. An empty list is encoded without any bytes.
+            if encoded_numbers[1] == 0 {
+                encoded_numbers_vec.clear();
+            } else {
+                unused_bits = encoded_numbers[1].trailing_zeros() as u8;
             }
         } else {
             // If encoded_numbers[0] is not 0, this means that DecipherOnly is set. Since only
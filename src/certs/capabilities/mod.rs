@@ -4,19 +4,26 @@
 
 /// "basicConstraints" IdCert/Csr capabilities
 pub mod basic_constraints;
+/// Custom X.509 extensions attached to IdCert/Csr capabilities
+pub mod custom_extension;
+/// "extKeyUsage" IdCert/Csr capabilities
+pub mod extended_key_usage;
 /// "keyUsage" IdCert/Csr capabilities
 pub mod key_usage;
 
 pub use basic_constraints::*;
+pub use custom_extension::*;
+pub use extended_key_usage::*;
 pub use key_usage::*;
 
 use der::asn1::SetOfVec;
+use spki::ObjectIdentifier;
 
 use x509_cert::attr::{Attribute, Attributes};
 use x509_cert::ext::{Extension, Extensions};
 
 use crate::{
-    errors::{ConversionError, InvalidInput},
+    errors::{ConstraintError, ConversionError, InvalidInput},
     Constrained,
 };
 
@@ -42,6 +49,21 @@ pub const OID_KEY_USAGE_DECIPHER_ONLY: &str = "1.3.6.1.5.5.7.3.6";
 pub const OID_BASIC_CONSTRAINTS: &str = "2.5.29.19";
 /// Object Identifier for the KeyUsage flag.
 pub const OID_KEY_USAGE: &str = "2.5.29.15";
+/// Object Identifier for the PKCS #9 extensionRequest attribute, carrying the
+/// [CustomExtension]s requested in a CSR.
+pub const OID_EXTENSION_REQUEST: &str = "1.2.840.113549.1.9.14";
+/// Object Identifier for the ExtendedKeyUsage extension.
+pub const OID_EXTENDED_KEY_USAGE: &str = "2.5.29.37";
+/// Object Identifier for the ExtendedKeyUsage::ServerAuth variant.
+pub const OID_EXTENDED_KEY_USAGE_SERVER_AUTH: &str = "1.3.6.1.5.5.7.3.1";
+/// Object Identifier for the ExtendedKeyUsage::ClientAuth variant.
+pub const OID_EXTENDED_KEY_USAGE_CLIENT_AUTH: &str = "1.3.6.1.5.5.7.3.2";
+/// Object Identifier for the ExtendedKeyUsage::TimeStamping variant.
+pub const OID_EXTENDED_KEY_USAGE_TIME_STAMPING: &str = "1.3.6.1.5.5.7.3.8";
+/// Object Identifier for the ExtendedKeyUsage::OcspSigning variant.
+pub const OID_EXTENDED_KEY_USAGE_OCSP_SIGNING: &str = "1.3.6.1.5.5.7.3.9";
+/// Object Identifier for the ExtendedKeyUsage::AnyExtendedKeyUsage variant.
+pub const OID_ANY_EXTENDED_KEY_USAGE: &str = "2.5.29.37.0";
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// An abstraction over X.509 Extensions and PKCS#10 Attributes, representing the capabilities
@@ -54,6 +76,13 @@ pub struct Capabilities {
     /// Extension type that defines whether a given certificate is allowed
     /// to sign additional certificates and what path length restrictions may exist.
     pub basic_constraints: BasicConstraints,
+    /// The extended key usage extension further restricts the purposes the key contained in the
+    /// certificate may be used for. Empty, unless set using
+    /// [Capabilities::with_extended_key_usage()].
+    pub extended_key_usage: ExtendedKeyUsages,
+    /// Additional X.509 extensions not modeled by polyproto. Empty, unless set using
+    /// [Capabilities::with_custom_extension()], or preserved when parsing a certificate.
+    pub custom_extensions: Vec<CustomExtension>,
 }
 
 impl Default for Capabilities {
@@ -64,15 +93,82 @@ impl Default for Capabilities {
                 ca: false,
                 path_length: None,
             },
+            extended_key_usage: ExtendedKeyUsages::default(),
+            custom_extensions: Vec::new(),
         }
     }
 }
 
 impl Capabilities {
-    /// Sane default for actor [IdCsr]/[IdCert] [Capabilities]. Uses the DigitalSignature flag,
-    /// not the ContentCommitment flag.
+    /// The [Capabilities] recommended by the polyproto specification for actor [IdCsr]/[IdCert]s:
+    /// the DigitalSignature flag, without the ContentCommitment flag, and no CA flag.
+    ///
+    /// [IdCsr]: crate::certs::idcsr::IdCsr
+    /// [IdCert]: crate::certs::idcert::IdCert
+    pub fn actor_default() -> Self {
+        Self {
+            key_usage: KeyUsages::new(&[KeyUsage::DigitalSignature]),
+            basic_constraints: BasicConstraints {
+                ca: false,
+                path_length: None,
+            },
+            extended_key_usage: ExtendedKeyUsages::default(),
+            custom_extensions: Vec::new(),
+        }
+    }
+
+    /// The [Capabilities] recommended by the polyproto specification for home server
+    /// [IdCsr]/[IdCert]s: the KeyCertSign and CrlSign flags and the CA flag. As home servers only
+    /// issue end-entity certificates, the path length is constrained to `0`.
+    ///
+    /// [IdCsr]: crate::certs::idcsr::IdCsr
+    /// [IdCert]: crate::certs::idcert::IdCert
+    pub fn home_server_default() -> Self {
+        Self {
+            key_usage: KeyUsages::new(&[KeyUsage::KeyCertSign, KeyUsage::CrlSign]),
+            basic_constraints: BasicConstraints {
+                ca: true,
+                path_length: Some(0),
+            },
+            extended_key_usage: ExtendedKeyUsages::default(),
+            custom_extensions: Vec::new(),
+        }
+    }
+
+    /// The [Capabilities] recommended for certificates of services operated alongside a home
+    /// server: the DigitalSignature flag, to authenticate the service, and the KeyEncipherment
+    /// flag, to establish encrypted channels. Services are not CAs.
+    pub fn service_default() -> Self {
+        Self {
+            key_usage: KeyUsages::new(&[KeyUsage::DigitalSignature, KeyUsage::KeyEncipherment]),
+            basic_constraints: BasicConstraints {
+                ca: false,
+                path_length: None,
+            },
+            extended_key_usage: ExtendedKeyUsages::default(),
+            custom_extensions: Vec::new(),
+        }
+    }
+
+    /// Sane default for actor [IdCsr]/[IdCert] [Capabilities]. An alias of
+    /// [Capabilities::actor_default()].
+    ///
+    /// [IdCsr]: crate::certs::idcsr::IdCsr
+    /// [IdCert]: crate::certs::idcert::IdCert
     pub fn default_actor() -> Self {
-        let key_usage = KeyUsages::new(&[KeyUsage::DigitalSignature]);
+        Self::actor_default()
+    }
+
+    /// [Capabilities] of guest actor certificates. Identical to [Capabilities::actor_default()];
+    /// guest certificates must not hold any capabilities beyond these.
+    pub fn default_guest() -> Self {
+        Self::actor_default()
+    }
+
+    /// Sane default for the [Capabilities] of a delegated CRL issuer. Uses the DigitalSignature
+    /// and CrlSign flags, without the CA flag.
+    pub fn default_crl_issuer() -> Self {
+        let key_usage = KeyUsages::new(&[KeyUsage::DigitalSignature, KeyUsage::CrlSign]);
         let basic_constraints = BasicConstraints {
             ca: false,
             path_length: None,
@@ -80,10 +176,17 @@ impl Capabilities {
         Self {
             key_usage,
             basic_constraints,
+            extended_key_usage: ExtendedKeyUsages::default(),
+            custom_extensions: Vec::new(),
         }
     }
 
-    /// Sane default for home server [IdCsr]/[IdCert] [Capabilities].
+    /// Sane default for home server [IdCsr]/[IdCert] [Capabilities]. Uses the KeyCertSign flag
+    /// and the CA flag with a path length of `1`. Unlike [Capabilities::home_server_default()],
+    /// this allows home servers to issue intermediate CA certificates.
+    ///
+    /// [IdCsr]: crate::certs::idcsr::IdCsr
+    /// [IdCert]: crate::certs::idcert::IdCert
     pub fn default_home_server() -> Self {
         let key_usage = KeyUsages::new(&[KeyUsage::KeyCertSign]);
         let basic_constraints = BasicConstraints {
@@ -93,7 +196,136 @@ impl Capabilities {
         Self {
             key_usage,
             basic_constraints,
+            extended_key_usage: ExtendedKeyUsages::default(),
+            custom_extensions: Vec::new(),
+        }
+    }
+
+    /// Restricts the purposes of the key to `extended_key_usage`.
+    pub fn with_extended_key_usage(mut self, extended_key_usage: ExtendedKeyUsages) -> Self {
+        self.extended_key_usage = extended_key_usage;
+        self
+    }
+
+    /// Attaches `custom_extension`. Fails, if a custom extension with the same OID is attached
+    /// already.
+    pub fn with_custom_extension(
+        mut self,
+        custom_extension: CustomExtension,
+    ) -> Result<Self, InvalidInput> {
+        if self.custom_extension(custom_extension.oid()).is_some() {
+            return Err(InvalidInput::Malformed(format!(
+                "The custom extension {} is attached already",
+                custom_extension.oid()
+            )));
         }
+        self.custom_extensions.push(custom_extension);
+        Ok(self)
+    }
+
+    /// Returns the custom extension with the Object Identifier `oid`, if it is attached.
+    pub fn custom_extension(&self, oid: ObjectIdentifier) -> Option<&CustomExtension> {
+        self.custom_extensions
+            .iter()
+            .find(|custom_extension| custom_extension.oid() == oid)
+    }
+
+    /// Returns a [CapabilitiesBuilder] without any capabilities, to assemble [Capabilities] flag
+    /// by flag.
+    pub fn builder() -> CapabilitiesBuilder {
+        CapabilitiesBuilder::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// Fluent builder for [Capabilities], obtained using [Capabilities::builder()].
+///
+/// The builder takes care of the flag combinations required by the polyproto specification:
+/// [CapabilitiesBuilder::with_ca()] also sets the KeyCertSign flag, and the DigitalSignature and
+/// ContentCommitment flags replace each other, as certificates cannot hold both.
+/// [CapabilitiesBuilder::build()] validates the result.
+pub struct CapabilitiesBuilder {
+    key_usages: Vec<KeyUsage>,
+    basic_constraints: BasicConstraints,
+    extended_key_usages: Vec<ExtendedKeyUsage>,
+}
+
+impl CapabilitiesBuilder {
+    /// Adds `key_usage`. Prefer the dedicated methods, such as
+    /// [CapabilitiesBuilder::with_digital_signature()], which keep the flags consistent.
+    pub fn with_key_usage(mut self, key_usage: KeyUsage) -> Self {
+        if !self.key_usages.contains(&key_usage) {
+            self.key_usages.push(key_usage);
+        }
+        self
+    }
+
+    /// Sets the DigitalSignature flag, removing the ContentCommitment flag. Actors need either of
+    /// them to sign messages.
+    pub fn with_digital_signature(mut self) -> Self {
+        self.key_usages
+            .retain(|key_usage| *key_usage != KeyUsage::ContentCommitment);
+        self.with_key_usage(KeyUsage::DigitalSignature)
+    }
+
+    /// Sets the ContentCommitment flag, also known as non-repudiation, removing the
+    /// DigitalSignature flag.
+    pub fn with_content_commitment(mut self) -> Self {
+        self.key_usages
+            .retain(|key_usage| *key_usage != KeyUsage::DigitalSignature);
+        self.with_key_usage(KeyUsage::ContentCommitment)
+    }
+
+    /// Sets the KeyEncipherment flag.
+    pub fn with_key_encipherment(self) -> Self {
+        self.with_key_usage(KeyUsage::KeyEncipherment)
+    }
+
+    /// Sets the DataEncipherment flag.
+    pub fn with_data_encipherment(self) -> Self {
+        self.with_key_usage(KeyUsage::DataEncipherment)
+    }
+
+    /// Sets the KeyAgreement flag.
+    pub fn with_key_agreement(self) -> Self {
+        self.with_key_usage(KeyUsage::KeyAgreement)
+    }
+
+    /// Sets the CrlSign flag.
+    pub fn with_crl_sign(self) -> Self {
+        self.with_key_usage(KeyUsage::CrlSign)
+    }
+
+    /// Marks the subject as a certificate authority, which may issue certificates down to
+    /// `path_length` intermediate certificates, and sets the KeyCertSign flag required for that.
+    /// Home servers only issue end-entity certificates, and use a `path_length` of `Some(0)`.
+    pub fn with_ca(mut self, path_length: Option<u64>) -> Self {
+        self.basic_constraints = BasicConstraints {
+            ca: true,
+            path_length,
+        };
+        self.with_key_usage(KeyUsage::KeyCertSign)
+    }
+
+    /// Adds `extended_key_usage` to the purposes the key is restricted to.
+    pub fn with_extended_key_usage(mut self, extended_key_usage: ExtendedKeyUsage) -> Self {
+        if !self.extended_key_usages.contains(&extended_key_usage) {
+            self.extended_key_usages.push(extended_key_usage);
+        }
+        self
+    }
+
+    /// Assembles the [Capabilities]. Fails, if they do not pass [Constrained] validation, for
+    /// example if neither a signing flag nor the CA flag is set.
+    pub fn build(self) -> Result<Capabilities, ConstraintError> {
+        let capabilities = Capabilities {
+            key_usage: KeyUsages::new(&self.key_usages),
+            basic_constraints: self.basic_constraints,
+            extended_key_usage: ExtendedKeyUsages::new(&self.extended_key_usages),
+            custom_extensions: Vec::new(),
+        };
+        capabilities.validate(None)?;
+        Ok(capabilities)
     }
 }
 
@@ -109,7 +341,10 @@ impl TryFrom<Attributes> for Capabilities {
     fn try_from(value: Attributes) -> Result<Self, Self::Error> {
         let mut key_usages = KeyUsages::new(&[]);
         let mut basic_constraints = BasicConstraints::default();
+        let mut extended_key_usage = ExtendedKeyUsages::default();
         let mut num_basic_constraints = 0u8;
+        let mut num_extended_key_usages = 0u8;
+        let mut custom_extensions = None;
         for item in value.iter() {
             match item.oid.to_string().as_str() {
                 #[allow(unreachable_patterns)] // cargo thinks the below pattern is unreachable.
@@ -124,12 +359,27 @@ impl TryFrom<Attributes> for Capabilities {
                         basic_constraints = BasicConstraints::try_from(item.clone())?;
                     }
                 }
+                OID_EXTENDED_KEY_USAGE => {
+                    num_extended_key_usages += 1;
+                    if num_extended_key_usages > 1 {
+                        return Err(ConversionError::InvalidInput(InvalidInput::Malformed("Tried inserting > 1 ExtendedKeyUsages into Capabilities. Expected at most 1 ExtendedKeyUsages".to_string())));
+                    }
+                    extended_key_usage = ExtendedKeyUsages::try_from(item.clone())?;
+                }
+                OID_EXTENSION_REQUEST => {
+                    if custom_extensions.is_some() {
+                        return Err(ConversionError::InvalidInput(InvalidInput::Malformed("Tried inserting > 1 extensionRequest attributes into Capabilities. Expected at most 1 extensionRequest attribute".to_string())));
+                    }
+                    custom_extensions = Some(from_extension_request(item)?);
+                }
                 _ => (),
             }
         }
         Ok(Capabilities {
             key_usage: key_usages,
             basic_constraints,
+            extended_key_usage,
+            custom_extensions: custom_extensions.unwrap_or_default(),
         })
     }
 }
@@ -151,6 +401,18 @@ impl TryFrom<Capabilities> for Attributes {
         if insertion.is_err() {
             return Err(ConversionError::InvalidInput(InvalidInput::Malformed("Tried inserting non-unique element into SetOfVec. You likely have a duplicate value in your Capabilities".to_string())));
         }
+        if !value.extended_key_usage.is_empty() {
+            let insertion = sov.insert(Attribute::try_from(value.extended_key_usage)?);
+            if insertion.is_err() {
+                return Err(ConversionError::InvalidInput(InvalidInput::Malformed("Tried inserting non-unique element into SetOfVec. You likely have a duplicate value in your Capabilities".to_string())));
+            }
+        }
+        if !value.custom_extensions.is_empty() {
+            let insertion = sov.insert(to_extension_request(value.custom_extensions)?);
+            if insertion.is_err() {
+                return Err(ConversionError::InvalidInput(InvalidInput::Malformed("Tried inserting non-unique element into SetOfVec. You likely have a duplicate value in your Capabilities".to_string())));
+            }
+        }
         Ok(sov)
     }
 }
@@ -161,17 +423,26 @@ impl TryFrom<Capabilities> for Extensions {
     ///
     /// try_from does **not** check whether the resulting [Extensions] are well-formed.
     fn try_from(value: Capabilities) -> Result<Self, Self::Error> {
-        Ok(vec![
+        let mut extensions = vec![
             Extension::try_from(value.basic_constraints)?,
             Extension::try_from(value.key_usage)?,
-        ])
+        ];
+        if !value.extended_key_usage.is_empty() {
+            extensions.push(Extension::try_from(value.extended_key_usage)?);
+        }
+        for custom_extension in value.custom_extensions {
+            extensions.push(Extension::try_from(custom_extension)?);
+        }
+        Ok(extensions)
     }
 }
 
 impl TryFrom<Extensions> for Capabilities {
     type Error = ConversionError;
 
-    /// Performs the conversion.
+    /// Performs the conversion. Extensions other than BasicConstraints, KeyUsages and
+    /// ExtendedKeyUsages are preserved as [CustomExtension]s. Fails with
+    /// [ConversionError::UnknownCriticalExtension], if any of them is marked as critical.
     ///
     /// try_from does **not** check whether the resulting [Capabilities] are well-formed. If
     /// this property is critical, use the [Constrained] trait to verify the well-formedness of
@@ -179,21 +450,27 @@ impl TryFrom<Extensions> for Capabilities {
     fn try_from(value: Extensions) -> Result<Self, Self::Error> {
         let mut basic_constraints: BasicConstraints = BasicConstraints::default();
         let mut key_usage: KeyUsages = KeyUsages::default();
+        let mut extended_key_usage: ExtendedKeyUsages = ExtendedKeyUsages::default();
+        let mut custom_extensions: Vec<CustomExtension> = Vec::new();
         for item in value.iter() {
             #[allow(unreachable_patterns)] // cargo thinks that we have an unreachable pattern here
             match item.extn_id.to_string().as_str() {
                 OID_BASIC_CONSTRAINTS => {
                     basic_constraints = BasicConstraints::try_from(item.clone())?
                 }
-                OID_KEY_USAGE => {
-                    key_usage = KeyUsages::try_from(item.clone())?
-                },
-                _ => return Err(ConversionError::InvalidInput(InvalidInput::Malformed(format!("Invalid OID found for converting this set of Extensions to Capabilities: {} is not a valid OID for BasicConstraints or KeyUsages", item.extn_id))))
+                OID_KEY_USAGE => key_usage = KeyUsages::try_from(item.clone())?,
+                OID_EXTENDED_KEY_USAGE => {
+                    extended_key_usage = ExtendedKeyUsages::try_from(item.clone())?
+                }
+                // Unknown extensions are preserved, unless they are critical
+                _ => custom_extensions.push(CustomExtension::try_from(item.clone())?),
             };
         }
         Ok(Capabilities {
             key_usage,
             basic_constraints,
+            extended_key_usage,
+            custom_extensions,
         })
     }
 }
@@ -0,0 +1,205 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+use std::hash::Hash;
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use der::Encode;
+use sha2::{Digest, Sha256};
+use x509_cert::name::Name;
+
+use crate::encoding::{decode_hex, encode_hex};
+use crate::errors::{ConversionError, InvalidInput};
+
+/// The length of an issuer fingerprint in bytes.
+pub const ISSUER_FINGERPRINT_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Uniquely identifies an ID-Cert by the combination of its issuer and its serial number.
+///
+/// Instead of storing the full issuer [Name], the issuer is represented by the SHA-256 hash of its
+/// DER encoding. This keeps the identifier small and makes it usable as a map key or inside of
+/// URLs.
+///
+/// A [CertId] has a compact string representation of the form
+/// `<hex encoded issuer fingerprint>.<hex encoded serial number>`, which is produced by its
+/// [Display] implementation and parsed by its [FromStr] implementation. When the `serde` feature
+/// is enabled, a [CertId] is (de-)serialized as this string.
+pub struct CertId {
+    /// SHA-256 hash of the DER encoded issuer [Name].
+    pub issuer_fingerprint: [u8; ISSUER_FINGERPRINT_LENGTH],
+    /// The serial number of the certificate.
+    pub serial: Uint,
+}
+
+impl CertId {
+    /// Creates a new [CertId] from the issuer [Name] and serial number of a certificate.
+    pub fn new(issuer: &Name, serial: Uint) -> Result<Self, ConversionError> {
+        Ok(Self {
+            issuer_fingerprint: Self::issuer_fingerprint(issuer)?,
+            serial,
+        })
+    }
+
+    /// Computes the SHA-256 fingerprint of the DER encoding of an issuer [Name].
+    pub fn issuer_fingerprint(
+        issuer: &Name,
+    ) -> Result<[u8; ISSUER_FINGERPRINT_LENGTH], ConversionError> {
+        let der = issuer.to_der()?;
+        Ok(Sha256::digest(der).into())
+    }
+
+    /// Returns `true`, if this [CertId] refers to a certificate issued by `issuer`.
+    pub fn is_issued_by(&self, issuer: &Name) -> bool {
+        match Self::issuer_fingerprint(issuer) {
+            Ok(fingerprint) => fingerprint == self.issuer_fingerprint,
+            Err(_) => false,
+        }
+    }
+}
+
+impl Hash for CertId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.issuer_fingerprint.hash(state);
+        self.serial.as_bytes().hash(state);
+    }
+}
+
+impl Display for CertId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let serial = match self.serial.as_bytes() {
+            [] => "00".to_string(),
+            serial => encode_hex(serial),
+        };
+        write!(f, "{}.{}", encode_hex(&self.issuer_fingerprint), serial)
+    }
+}
+
+impl FromStr for CertId {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (fingerprint, serial) = match s.split_once('.') {
+            Some(parts) => parts,
+            None => {
+                return Err(InvalidInput::Malformed(
+                    "CertId must be of the form <fingerprint>.<serial>".to_string(),
+                )
+                .into())
+            }
+        };
+        let fingerprint = decode_hex(fingerprint)?;
+        let issuer_fingerprint: [u8; ISSUER_FINGERPRINT_LENGTH] =
+            match fingerprint.clone().try_into() {
+                Ok(fingerprint) => fingerprint,
+                Err(_) => {
+                    return Err(InvalidInput::Length {
+                        min_length: ISSUER_FINGERPRINT_LENGTH,
+                        max_length: ISSUER_FINGERPRINT_LENGTH,
+                        actual_length: fingerprint.len().to_string(),
+                    }
+                    .into())
+                }
+            };
+        let serial = decode_hex(serial)?;
+        if serial.is_empty() {
+            return Err(
+                InvalidInput::Malformed("CertId serial number is empty".to_string()).into(),
+            );
+        }
+        Ok(Self {
+            issuer_fingerprint,
+            serial: Uint::new(&serial)?,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use std::str::FromStr;
+
+    use serde::de::Visitor;
+    use serde::{Deserialize, Serialize};
+
+    use super::CertId;
+
+    impl Serialize for CertId {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+
+    struct CertIdVisitor;
+
+    impl<'de> Visitor<'de> for CertIdVisitor {
+        type Value = CertId;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a CertId of the form <fingerprint>.<serial>")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            CertId::from_str(v).map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CertId {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_str(CertIdVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn issuer() -> Name {
+        Name::from_str("DC=polyphony,DC=chat").unwrap()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn compact_string_roundtrip() {
+        let cert_id = CertId::new(&issuer(), Uint::new(&[0x01, 0xff, 0x00]).unwrap()).unwrap();
+        let string = cert_id.to_string();
+        assert!(string.ends_with(".01ff00"));
+        assert_eq!(string.len(), 2 * ISSUER_FINGERPRINT_LENGTH + 7);
+        assert_eq!(CertId::from_str(&string).unwrap(), cert_id);
+        assert!(cert_id.is_issued_by(&issuer()));
+        assert!(!cert_id.is_issued_by(&Name::from_str("DC=example,DC=com").unwrap()));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn malformed_compact_strings() {
+        assert!(CertId::from_str("").is_err());
+        assert!(CertId::from_str("abcd").is_err());
+        assert!(CertId::from_str("abcd.01").is_err());
+        assert!(CertId::from_str(&format!("{}.", "00".repeat(32))).is_err());
+        assert!(CertId::from_str(&format!("{}.0g", "00".repeat(32))).is_err());
+        assert!(CertId::from_str(&format!("{}.01", "00".repeat(32))).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn serde_as_string() {
+        let cert_id = CertId::new(&issuer(), Uint::new(&[0x2a]).unwrap()).unwrap();
+        let json = serde_json::to_string(&cert_id).unwrap();
+        assert_eq!(json, format!("\"{}\"", cert_id));
+        assert_eq!(serde_json::from_str::<CertId>(&json).unwrap(), cert_id);
+    }
+}
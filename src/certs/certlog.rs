@@ -0,0 +1,552 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::encoding::encode_hex;
+use crate::errors::{ConversionError, InvalidCert, PublicKeyError};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::idcert::IdCert;
+
+/// The prefix of the payload signed when creating a [SignedTreeHead].
+pub static TREE_HEAD_PAYLOAD_PREFIX: &str = "polyproto-tree-head-v1";
+
+/// The length of the hashes of a [CertLog], in bytes.
+pub const LOG_HASH_LENGTH: usize = 32;
+
+/// A node of the Merkle tree of a [CertLog]: the SHA-256 hash of a leaf or of two child nodes.
+pub type LogHash = [u8; LOG_HASH_LENGTH];
+
+/// Computes the leaf hash of `cert`, as appended to a [CertLog]: the SHA-256 hash of a zero byte,
+/// followed by the DER encoding of the certificate.
+pub fn leaf_hash<S: Signature, P: PublicKey<S>>(
+    cert: &IdCert<S, P>,
+) -> Result<LogHash, ConversionError> {
+    let der = cert.to_der()?;
+    Ok(Sha256::new()
+        .chain_update([0u8])
+        .chain_update(der)
+        .finalize()
+        .into())
+}
+
+fn node_hash(left: &LogHash, right: &LogHash) -> LogHash {
+    Sha256::new()
+        .chain_update([1u8])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+/// The largest power of two smaller than `n`. `n` must be greater than one.
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+fn root_of(leaves: &[LogHash]) -> LogHash {
+    match leaves {
+        [] => Sha256::digest([]).into(),
+        [leaf] => *leaf,
+        _ => {
+            let k = split_point(leaves.len());
+            node_hash(&root_of(&leaves[..k]), &root_of(&leaves[k..]))
+        }
+    }
+}
+
+fn inclusion_path(index: usize, leaves: &[LogHash]) -> Vec<LogHash> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(leaves.len());
+    let (mut path, sibling) = match index < k {
+        true => (inclusion_path(index, &leaves[..k]), root_of(&leaves[k..])),
+        false => (
+            inclusion_path(index - k, &leaves[k..]),
+            root_of(&leaves[..k]),
+        ),
+    };
+    path.push(sibling);
+    path
+}
+
+fn consistency_path(old_size: usize, leaves: &[LogHash], complete: bool) -> Vec<LogHash> {
+    if old_size == leaves.len() {
+        return match complete {
+            true => Vec::new(),
+            false => vec![root_of(leaves)],
+        };
+    }
+    let k = split_point(leaves.len());
+    let (mut path, sibling) = match old_size <= k {
+        true => (
+            consistency_path(old_size, &leaves[..k], complete),
+            root_of(&leaves[k..]),
+        ),
+        false => (
+            consistency_path(old_size - k, &leaves[k..], false),
+            root_of(&leaves[..k]),
+        ),
+    };
+    path.push(sibling);
+    path
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// An append-only transparency log of the [IdCert]s issued by a home server, backed by a Merkle
+/// tree as described in RFC 9162.
+///
+/// Home servers append every certificate they issue, and periodically publish a
+/// [SignedTreeHead] committing to the current state of the log. A client presented with a
+/// certificate can request an [InclusionProof] for it and check it against a signed tree head
+/// using [SignedTreeHead::verify_logged()], making certificates issued behind the back of the
+/// public unusable. [ConsistencyProof]s let observers check that a newer tree head extends an
+/// older one, i.e. that no certificate has been removed from the log.
+pub struct CertLog {
+    leaves: Vec<LogHash>,
+    positions: HashMap<LogHash, u64>,
+}
+
+impl CertLog {
+    /// Creates an empty [CertLog].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `cert` to the log, returning its index. A certificate appended more than once is
+    /// logged again; [CertLog::position()] reports its first index.
+    pub fn append<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+    ) -> Result<u64, ConversionError> {
+        let leaf = leaf_hash(cert)?;
+        let index = self.leaves.len() as u64;
+        log::trace!(
+            "[CertLog::append()] Appending certificate at index {}",
+            index
+        );
+        self.leaves.push(leaf);
+        self.positions.entry(leaf).or_insert(index);
+        Ok(index)
+    }
+
+    /// Returns the number of certificates in the log.
+    pub fn size(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    /// Returns `true`, if no certificates have been logged.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Returns the index of `cert` in the log, if it has been logged.
+    pub fn position<S: Signature, P: PublicKey<S>>(&self, cert: &IdCert<S, P>) -> Option<u64> {
+        let leaf = leaf_hash(cert).ok()?;
+        self.positions.get(&leaf).copied()
+    }
+
+    /// Returns the root hash of the log at its current size.
+    pub fn root_hash(&self) -> LogHash {
+        root_of(&self.leaves)
+    }
+
+    /// Returns the root hash of the log as it was when it contained `tree_size` certificates, or
+    /// `None`, if the log is smaller than that.
+    pub fn root_hash_at(&self, tree_size: u64) -> Option<LogHash> {
+        Some(root_of(
+            self.leaves.get(..usize::try_from(tree_size).ok()?)?,
+        ))
+    }
+
+    /// Creates and signs a [SignedTreeHead] for the current state of the log.
+    pub fn sign_tree_head<S: Signature>(
+        &self,
+        timestamp: Timestamp,
+        signing_key: &impl PrivateKey<S>,
+    ) -> SignedTreeHead<S> {
+        SignedTreeHead::new(self.size(), self.root_hash(), timestamp, signing_key)
+    }
+
+    /// Returns an [InclusionProof] for the certificate at `leaf_index`, relative to the tree of
+    /// size `tree_size`. Returns `None`, if `leaf_index` is not smaller than `tree_size`, or if the
+    /// log is smaller than `tree_size`.
+    pub fn inclusion_proof(&self, leaf_index: u64, tree_size: u64) -> Option<InclusionProof> {
+        let leaves = self.leaves.get(..usize::try_from(tree_size).ok()?)?;
+        let index = usize::try_from(leaf_index).ok()?;
+        if index >= leaves.len() {
+            return None;
+        }
+        Some(InclusionProof {
+            leaf_index,
+            tree_size,
+            path: inclusion_path(index, leaves),
+        })
+    }
+
+    /// Returns a [ConsistencyProof] showing that the tree of size `new_size` extends the tree of
+    /// size `old_size`. Returns `None`, if `old_size` is greater than `new_size`, or if the log is
+    /// smaller than `new_size`.
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> Option<ConsistencyProof> {
+        let leaves = self.leaves.get(..usize::try_from(new_size).ok()?)?;
+        let old = usize::try_from(old_size).ok()?;
+        let path = match old {
+            _ if old > leaves.len() => return None,
+            0 => Vec::new(),
+            _ => consistency_path(old, leaves, true),
+        };
+        Some(ConsistencyProof {
+            old_size,
+            new_size,
+            path,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Proof that a leaf is included in the tree of a [CertLog] of a given size: the hashes of the
+/// siblings on the path from the leaf to the root, ordered from the leaf upwards.
+///
+/// When the `serde` feature is enabled, the hashes of the path are (de-)serialized hex encoded.
+pub struct InclusionProof {
+    /// The index of the leaf in the log.
+    pub leaf_index: u64,
+    /// The size of the tree the proof is relative to.
+    pub tree_size: u64,
+    /// The sibling hashes on the path from the leaf to the root.
+    pub path: Vec<LogHash>,
+}
+
+impl InclusionProof {
+    /// Returns `true`, if this proof shows that `leaf` is included at
+    /// [InclusionProof::leaf_index] in the tree of size [InclusionProof::tree_size] with the
+    /// given root hash.
+    pub fn verify(&self, leaf: &LogHash, root_hash: &LogHash) -> bool {
+        if self.leaf_index >= self.tree_size {
+            return false;
+        }
+        let (mut index, mut last) = (self.leaf_index, self.tree_size - 1);
+        let mut hash = *leaf;
+        for sibling in self.path.iter() {
+            if last == 0 {
+                return false;
+            }
+            if index & 1 == 1 || index == last {
+                hash = node_hash(sibling, &hash);
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, sibling);
+            }
+            index >>= 1;
+            last >>= 1;
+        }
+        last == 0 && hash == *root_hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Proof that the tree of a [CertLog] of size [ConsistencyProof::new_size] is an extension of the
+/// tree of size [ConsistencyProof::old_size], i.e. that certificates have only been appended.
+///
+/// When the `serde` feature is enabled, the hashes of the path are (de-)serialized hex encoded.
+pub struct ConsistencyProof {
+    /// The size of the older tree.
+    pub old_size: u64,
+    /// The size of the newer tree.
+    pub new_size: u64,
+    /// The hashes proving consistency, as defined in RFC 9162.
+    pub path: Vec<LogHash>,
+}
+
+impl ConsistencyProof {
+    /// Returns `true`, if this proof shows that the tree with root hash `new_root` extends the tree
+    /// with root hash `old_root`.
+    pub fn verify(&self, old_root: &LogHash, new_root: &LogHash) -> bool {
+        if self.old_size > self.new_size {
+            return false;
+        }
+        if self.old_size == self.new_size {
+            return self.path.is_empty() && old_root == new_root;
+        }
+        if self.old_size == 0 {
+            return self.path.is_empty();
+        }
+        let mut path = self.path.iter();
+        let first = match self.old_size.is_power_of_two() {
+            true => old_root,
+            false => match path.next() {
+                Some(first) => first,
+                None => return false,
+            },
+        };
+        let (mut index, mut last) = (self.old_size - 1, self.new_size - 1);
+        while index & 1 == 1 {
+            index >>= 1;
+            last >>= 1;
+        }
+        let (mut old_hash, mut new_hash) = (*first, *first);
+        for hash in path {
+            if last == 0 {
+                return false;
+            }
+            if index & 1 == 1 || index == last {
+                old_hash = node_hash(hash, &old_hash);
+                new_hash = node_hash(hash, &new_hash);
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                new_hash = node_hash(&new_hash, hash);
+            }
+            index >>= 1;
+            last >>= 1;
+        }
+        last == 0 && old_hash == *old_root && new_hash == *new_root
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A statement of a home server, committing to the state of its [CertLog] at a point in time.
+///
+/// When the `serde` feature is enabled, a [SignedTreeHead] is (de-)serialized as an object with
+/// the tree size, the hex encoded root hash, the timestamp and the hex encoded signature.
+pub struct SignedTreeHead<S: Signature> {
+    /// The number of certificates in the log.
+    pub tree_size: u64,
+    /// The root hash of the log.
+    pub root_hash: LogHash,
+    /// The point in time at which the tree head was signed.
+    pub timestamp: Timestamp,
+    /// Signature over [SignedTreeHead::signed_payload()] by the home server operating the log.
+    pub signature: S,
+}
+
+impl<S: Signature> SignedTreeHead<S> {
+    /// Creates and signs a [SignedTreeHead]. `signing_key` must be the key of the home server
+    /// operating the log.
+    pub fn new(
+        tree_size: u64,
+        root_hash: LogHash,
+        timestamp: Timestamp,
+        signing_key: &impl PrivateKey<S>,
+    ) -> Self {
+        log::trace!(
+            "[SignedTreeHead::new()] signing tree head of size {}",
+            tree_size
+        );
+        let signature = signing_key.sign(&signed_payload(tree_size, &root_hash, timestamp));
+        Self {
+            tree_size,
+            root_hash,
+            timestamp,
+            signature,
+        }
+    }
+
+    /// Returns the payload signed by the home server: the tree size, the hex encoded root hash and
+    /// [SignedTreeHead::timestamp] in Unix epoch seconds, each separated by a newline and prefixed
+    /// with [TREE_HEAD_PAYLOAD_PREFIX].
+    pub fn signed_payload(&self) -> Vec<u8> {
+        signed_payload(self.tree_size, &self.root_hash, self.timestamp)
+    }
+
+    /// Verifies the signature of this tree head using the public key of the home server operating
+    /// the log.
+    pub fn verify<P: PublicKey<S>>(&self, public_key: &P) -> Result<(), PublicKeyError> {
+        public_key.verify_signature(&self.signature, &self.signed_payload())
+    }
+
+    /// Verifies that `cert` is included in the log committed to by this tree head, and that the
+    /// tree head was signed by `public_key`. Fails with [InvalidCert::NotLogged], if `proof` is
+    /// not relative to this tree head or does not prove the inclusion of `cert`.
+    pub fn verify_logged<P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+        proof: &InclusionProof,
+        public_key: &P,
+    ) -> Result<(), InvalidCert> {
+        self.verify(public_key)?;
+        let leaf = match leaf_hash(cert) {
+            Ok(leaf) => leaf,
+            Err(_) => return Err(InvalidCert::NotLogged),
+        };
+        if proof.tree_size != self.tree_size || !proof.verify(&leaf, &self.root_hash) {
+            log::debug!(
+                "[SignedTreeHead::verify_logged()] Certificate is not included in the tree of size {}",
+                self.tree_size
+            );
+            return Err(InvalidCert::NotLogged);
+        }
+        Ok(())
+    }
+}
+
+fn signed_payload(tree_size: u64, root_hash: &LogHash, timestamp: Timestamp) -> Vec<u8> {
+    format!(
+        "{}\n{}\n{}\n{}",
+        TREE_HEAD_PAYLOAD_PREFIX,
+        tree_size,
+        encode_hex(root_hash),
+        timestamp.unix_seconds()
+    )
+    .into_bytes()
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use serde::de::Error;
+    use serde::{Deserialize, Serialize};
+
+    use crate::encoding::{decode_hex, encode_hex};
+    use crate::signature::Signature;
+    use crate::timestamp::Timestamp;
+
+    use super::{ConsistencyProof, InclusionProof, LogHash, SignedTreeHead, LOG_HASH_LENGTH};
+
+    fn decode_hash<E: Error>(hash: &str) -> Result<LogHash, E> {
+        let bytes = decode_hex(hash).map_err(E::custom)?;
+        LogHash::try_from(bytes.as_slice()).map_err(|_| {
+            E::custom(format!(
+                "Expected a hash of {} bytes, found {} bytes",
+                LOG_HASH_LENGTH,
+                bytes.len()
+            ))
+        })
+    }
+
+    fn decode_path<E: Error>(path: &[String]) -> Result<Vec<LogHash>, E> {
+        path.iter().map(|hash| decode_hash(hash)).collect()
+    }
+
+    fn encode_path(path: &[LogHash]) -> Vec<String> {
+        path.iter().map(|hash| encode_hex(hash)).collect()
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SignedTreeHeadJson {
+        tree_size: u64,
+        root_hash: String,
+        timestamp: Timestamp,
+        signature: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct InclusionProofJson {
+        leaf_index: u64,
+        tree_size: u64,
+        path: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ConsistencyProofJson {
+        old_size: u64,
+        new_size: u64,
+        path: Vec<String>,
+    }
+
+    impl<S: Signature> Serialize for SignedTreeHead<S> {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            let signature = self
+                .signature
+                .to_bitstring()
+                .map_err(serde::ser::Error::custom)?;
+            SignedTreeHeadJson {
+                tree_size: self.tree_size,
+                root_hash: encode_hex(&self.root_hash),
+                timestamp: self.timestamp,
+                signature: encode_hex(signature.raw_bytes()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, S: Signature> Deserialize<'de> for SignedTreeHead<S> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = SignedTreeHeadJson::deserialize(deserializer)?;
+            let signature = decode_hex(&json.signature).map_err(D::Error::custom)?;
+            Ok(SignedTreeHead {
+                tree_size: json.tree_size,
+                root_hash: decode_hash(&json.root_hash)?,
+                timestamp: json.timestamp,
+                signature: S::from_bytes(&signature),
+            })
+        }
+    }
+
+    impl Serialize for InclusionProof {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            InclusionProofJson {
+                leaf_index: self.leaf_index,
+                tree_size: self.tree_size,
+                path: encode_path(&self.path),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for InclusionProof {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = InclusionProofJson::deserialize(deserializer)?;
+            Ok(InclusionProof {
+                leaf_index: json.leaf_index,
+                tree_size: json.tree_size,
+                path: decode_path(&json.path)?,
+            })
+        }
+    }
+
+    impl Serialize for ConsistencyProof {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            ConsistencyProofJson {
+                old_size: self.old_size,
+                new_size: self.new_size,
+                path: encode_path(&self.path),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for ConsistencyProof {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = ConsistencyProofJson::deserialize(deserializer)?;
+            Ok(ConsistencyProof {
+                old_size: json.old_size,
+                new_size: json.new_size,
+                path: decode_path(&json.path)?,
+            })
+        }
+    }
+}
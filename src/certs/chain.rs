@@ -0,0 +1,550 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use crate::errors::{ChainBuildError, ConstraintError, InvalidCert};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::Constrained;
+
+use super::idcert::IdCert;
+use super::pinning::PinSet;
+use super::status::{RevocationCheck, RevocationMode, RevocationStatus};
+use super::truststore::TrustStore;
+use super::Target;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A certificate chain, consisting of a leaf [IdCert], usually an actor certificate, and the home
+/// server certificates which issued it. The issuers are ordered from the direct issuer of the leaf
+/// certificate to the self-signed root certificate of the home server.
+///
+/// Use [IdCertChain::validate_chain()] to validate the whole chain in one call.
+pub struct IdCertChain<S: Signature, P: PublicKey<S>> {
+    leaf: IdCert<S, P>,
+    issuers: Vec<IdCert<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> IdCertChain<S, P> {
+    /// Creates a new [IdCertChain] from a leaf certificate and its issuers, ordered from the direct
+    /// issuer of `leaf` to the self-signed root certificate. The chain is not validated.
+    pub fn new(leaf: IdCert<S, P>, issuers: Vec<IdCert<S, P>>) -> Self {
+        Self { leaf, issuers }
+    }
+
+    /// Creates a new [IdCertChain] from a list of certificates, ordered from the leaf certificate
+    /// to the self-signed root certificate. Returns `None`, if `certs` is empty. The chain is not
+    /// validated.
+    pub fn from_certs(mut certs: Vec<IdCert<S, P>>) -> Option<Self> {
+        if certs.is_empty() {
+            return None;
+        }
+        let leaf = certs.remove(0);
+        Some(Self::new(leaf, certs))
+    }
+
+    /// The leaf certificate of the chain.
+    pub fn leaf(&self) -> &IdCert<S, P> {
+        &self.leaf
+    }
+
+    /// The issuers of the leaf certificate, ordered from its direct issuer to the root certificate.
+    pub fn issuers(&self) -> &[IdCert<S, P>] {
+        &self.issuers
+    }
+
+    /// The root certificate of the chain, if the chain has any issuers.
+    pub fn root(&self) -> Option<&IdCert<S, P>> {
+        self.issuers.last()
+    }
+
+    /// The number of certificates in the chain, including the leaf certificate.
+    pub fn len(&self) -> usize {
+        self.issuers.len() + 1
+    }
+
+    /// Always `false`, as a chain contains at least its leaf certificate.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns an iterator over the certificates of the chain, from the leaf certificate to the
+    /// root certificate.
+    pub fn iter(&self) -> impl Iterator<Item = &IdCert<S, P>> {
+        std::iter::once(&self.leaf).chain(self.issuers.iter())
+    }
+
+    /// Validates the whole chain at the given `time`, checking for the following properties:
+    ///
+    /// - The chain contains at least one issuer, and the last issuer is self-signed
+    /// - The leaf certificate is well-formed and up to polyproto specification for `target`, and
+    ///   every issuer is a well-formed home server certificate
+    /// - The `BasicConstraints` of every issuer mark it as a CA, and its path length allows for
+    ///   the number of CA certificates below it. Unlike RFC 5280, which only counts intermediate
+    ///   issuers, a CA leaf certificate is counted as well, so that a home server certificate with
+    ///   a path length of 0 cannot have issued another home server certificate
+    /// - The subject of every issuer matches the issuer of the certificate below it, and the
+    ///   SubjectKeyIdentifier of every issuer matches the AuthorityKeyIdentifier of the
+    ///   certificate below it, if both certificates carry these extensions
+    /// - The validity period of every certificate lies within the validity period of its issuer,
+    ///   and every certificate is valid at `time`
+    /// - The signature of every certificate is correct
+    ///
+    /// Cross-certificates issued during a key rollover of a home server, see
+    /// [cross_sign()](super::rotation::cross_sign), are not counted as intermediate issuers, and
+    /// the certificates they issued may outlive them. A chain through a cross-certificate is
+    /// therefore accepted for as long as the cross-certificate is valid, which lets clients
+    /// trusting either the previous or the new key of the home server accept material issued
+    /// under the other key during the overlap window.
+    pub fn validate_chain(&self, time: Timestamp, target: Target) -> Result<(), InvalidCert> {
+        self.validate_chain_with_tolerance(time, target, Duration::ZERO)
+    }
+
+    /// Validates the chain like [IdCertChain::validate_chain()], but tolerates a clock skew of up
+    /// to `tolerance` when checking whether every certificate is valid at `time`. See
+    /// [IdCert::valid_at_with_tolerance()]. The validity period of every certificate must still lie
+    /// within the validity period of its issuer.
+    pub fn validate_chain_with_tolerance(
+        &self,
+        time: Timestamp,
+        target: Target,
+        tolerance: Duration,
+    ) -> Result<(), InvalidCert> {
+        validate_certs(&self.leaf, &self.issuers, time, target, tolerance)
+    }
+
+    /// Validates the chain like [IdCertChain::validate_chain()], and additionally checks that its
+    /// issuers are anchored in `trust_store`, refusing chains whose root is not pinned for the
+    /// domain of the issuing home server.
+    ///
+    /// Fails with [InvalidCert::NotAnchored], if no [TrustAnchor](super::truststore::TrustAnchor)
+    /// registered for that domain matches the chain.
+    pub fn validate_chain_anchored(
+        &self,
+        time: Timestamp,
+        target: Target,
+        trust_store: &TrustStore<S, P>,
+    ) -> Result<(), InvalidCert> {
+        self.validate_chain(time, target)?;
+        trust_store.check(self)
+    }
+
+    /// Validates the chain like [IdCertChain::validate_chain()], and additionally checks that the
+    /// public key of at least one of its certificates is pinned in `pins`, as described in
+    /// [PinSet::check_chain()].
+    pub fn validate_chain_pinned(
+        &self,
+        time: Timestamp,
+        target: Target,
+        pins: &PinSet,
+    ) -> Result<(), InvalidCert> {
+        self.validate_chain(time, target)?;
+        pins.check_chain(self, time)
+    }
+
+    /// Validates the chain like [IdCertChain::validate_chain()], and additionally queries
+    /// `checker` for the [RevocationStatus] of every certificate except the root, which has no
+    /// issuer to vouch for it.
+    ///
+    /// Fails with [InvalidCert::Revoked], if any certificate has been revoked. Certificates with a
+    /// [RevocationStatus::Unknown] are accepted in [RevocationMode::SoftFail], and rejected with
+    /// [InvalidCert::RevocationStatusUnknown] in [RevocationMode::HardFail].
+    pub async fn validate_chain_with_revocation<C: RevocationCheck<S, P> + ?Sized>(
+        &self,
+        time: Timestamp,
+        target: Target,
+        checker: &C,
+        mode: RevocationMode,
+    ) -> Result<(), InvalidCert> {
+        self.validate_chain(time, target)?;
+        let certs: Vec<&IdCert<S, P>> = self.iter().collect();
+        for pair in certs.windows(2) {
+            match checker.status(pair[0], pair[1], time).await {
+                RevocationStatus::Good => (),
+                RevocationStatus::Revoked { revoked_at, reason } => {
+                    log::debug!(
+                        "[IdCertChain::validate_chain_with_revocation()] Certificate was revoked at {} for reason {:?}",
+                        revoked_at,
+                        reason
+                    );
+                    return Err(InvalidCert::Revoked(revoked_at));
+                }
+                RevocationStatus::Unknown => match mode {
+                    RevocationMode::SoftFail => log::debug!(
+                        "[IdCertChain::validate_chain_with_revocation()] Revocation status of certificate is unknown, accepting it"
+                    ),
+                    RevocationMode::HardFail => {
+                        return Err(InvalidCert::RevocationStatusUnknown)
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<IdCertChain<S, P>> for Vec<IdCert<S, P>> {
+    fn from(value: IdCertChain<S, P>) -> Self {
+        let mut certs = Vec::with_capacity(value.len());
+        certs.push(value.leaf);
+        certs.extend(value.issuers);
+        certs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Why a certificate in a [CertPool] was not accepted as the issuer of a certificate while
+/// building an [IdCertChain].
+pub enum IssuerRejection {
+    /// The certificate is not a well-formed home server certificate with the "CA" capability.
+    NotCa,
+    /// The certificate is not valid at the time the chain is built for.
+    InvalidValidity,
+    /// The certificate is already part of the chain being built.
+    Cycle,
+    /// The SubjectKeyIdentifier of the certificate does not match the AuthorityKeyIdentifier of
+    /// the issued certificate.
+    KeyIdentifierMismatch,
+    /// The path length constraint of the certificate does not allow for the CA certificates below
+    /// it.
+    PathLengthExceeded,
+    /// Using the certificate would exceed the maximum chain length of the [CertPool].
+    ChainTooLong,
+    /// The public key of the certificate does not verify the signature of the issued certificate.
+    BadSignature,
+    /// No issuer leading to a self-signed root was found for the certificate itself.
+    NoPathToRoot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A certificate in a [CertPool] which was considered, but not accepted, as an issuer while
+/// building an [IdCertChain].
+pub struct RejectedIssuer {
+    /// The index of the certificate in the [CertPool].
+    pub index: usize,
+    /// Why the certificate was rejected.
+    pub reason: IssuerRejection,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A pool of home server certificates, such as the certificates held in a cache, from which
+/// [IdCertChain]s can be built using [CertPool::build_chain()].
+pub struct CertPool<S: Signature, P: PublicKey<S>> {
+    certs: Vec<IdCert<S, P>>,
+    max_chain_length: usize,
+}
+
+impl<S: Signature, P: PublicKey<S>> Default for CertPool<S, P> {
+    fn default() -> Self {
+        Self {
+            certs: Vec::new(),
+            max_chain_length: 4,
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> CertPool<S, P> {
+    /// Creates a new, empty [CertPool], building chains of at most 4 certificates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of certificates, including the leaf certificate, in the chains
+    /// built from this pool.
+    pub fn with_max_chain_length(mut self, max_chain_length: usize) -> Self {
+        self.max_chain_length = max_chain_length;
+        self
+    }
+
+    /// Adds a certificate to the pool.
+    pub fn with_cert(mut self, cert: IdCert<S, P>) -> Self {
+        self.add(cert);
+        self
+    }
+
+    /// Adds a certificate to the pool.
+    pub fn add(&mut self, cert: IdCert<S, P>) {
+        self.certs.push(cert);
+    }
+
+    /// The certificates in the pool, in the order they were added in.
+    pub fn certs(&self) -> &[IdCert<S, P>] {
+        &self.certs
+    }
+
+    /// The number of certificates in the pool.
+    pub fn len(&self) -> usize {
+        self.certs.len()
+    }
+
+    /// Returns `true`, if the pool contains no certificates.
+    pub fn is_empty(&self) -> bool {
+        self.certs.is_empty()
+    }
+
+    /// Builds the [IdCertChain] from `leaf` to a self-signed root certificate in the pool, using
+    /// the certificates which are valid at `time`.
+    ///
+    /// The issuer of a certificate is selected by matching its issuer name against the subjects
+    /// of the certificates in the pool. Where multiple certificates share the same subject, such
+    /// as after a key rotation of the home server, the one whose public key verifies the signature
+    /// of the certificate is selected. If the certificate carries an AuthorityKeyIdentifier,
+    /// candidates with a matching SubjectKeyIdentifier are tried first, and candidates with a
+    /// differing one are rejected. Candidates which do not lead to a self-signed root are skipped.
+    ///
+    /// If no chain can be built, the returned [ChainBuildError] lists the candidate issuers of
+    /// `leaf` and why each of them was rejected. The resulting chain should be validated using
+    /// [IdCertChain::validate_chain()], which additionally checks the constraints of the leaf
+    /// certificate and the nesting of validity periods.
+    pub fn build_chain(
+        &self,
+        leaf: IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<IdCertChain<S, P>, ChainBuildError> {
+        log::trace!(
+            "[CertPool::build_chain()] building chain for {} from a pool of {} certificates",
+            leaf.id_cert_tbs.subject,
+            self.len()
+        );
+        let mut path = Vec::new();
+        let leaf_is_ca = counts_towards_path_length(&leaf);
+        match self.find_path(&leaf, leaf_is_ca, &mut path, time) {
+            Ok(()) => {
+                let issuers = path
+                    .iter()
+                    .map(|index| self.certs[*index].clone())
+                    .collect();
+                Ok(IdCertChain::new(leaf, issuers))
+            }
+            Err(rejected) => Err(ChainBuildError::NoIssuer {
+                subject: leaf.id_cert_tbs.subject.to_string(),
+                rejected,
+            }),
+        }
+    }
+
+    /// Searches for an issuer of `cert` leading to a self-signed root, appending the indices of
+    /// the issuers to `path`. `leaf_is_ca` tells, whether the leaf certificate of the chain counts
+    /// towards the path length constraints of its issuers. On failure, `path` is left unchanged,
+    /// and the rejected candidates are returned.
+    fn find_path(
+        &self,
+        cert: &IdCert<S, P>,
+        leaf_is_ca: bool,
+        path: &mut Vec<usize>,
+        time: Timestamp,
+    ) -> Result<(), Vec<RejectedIssuer>> {
+        let mut rejected = Vec::new();
+        let mut candidates = self
+            .certs
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| candidate.id_cert_tbs.subject == cert.id_cert_tbs.issuer)
+            .collect::<Vec<_>>();
+        // Candidates whose key identifier matches are tried first, which usually spares
+        // verifying the signature of the certificate against the other candidates.
+        candidates.sort_by_key(|(_, candidate)| !key_identifiers_match(cert, candidate));
+        for (index, candidate) in candidates {
+            let reason = match self.check_candidate(cert, candidate, index, leaf_is_ca, path, time)
+            {
+                Ok(()) if is_self_signed(candidate) => {
+                    path.push(index);
+                    return Ok(());
+                }
+                Ok(()) => {
+                    path.push(index);
+                    match self.find_path(candidate, leaf_is_ca, path, time) {
+                        Ok(()) => return Ok(()),
+                        Err(_) => {
+                            path.pop();
+                            IssuerRejection::NoPathToRoot
+                        }
+                    }
+                }
+                Err(reason) => reason,
+            };
+            log::debug!(
+                "[CertPool::build_chain()] Rejected certificate {} as issuer of {}: {:?}",
+                index,
+                cert.id_cert_tbs.subject,
+                reason
+            );
+            rejected.push(RejectedIssuer { index, reason });
+        }
+        rejected.sort_by_key(|rejection| rejection.index);
+        Err(rejected)
+    }
+
+    fn check_candidate(
+        &self,
+        cert: &IdCert<S, P>,
+        candidate: &IdCert<S, P>,
+        index: usize,
+        leaf_is_ca: bool,
+        path: &[usize],
+        time: Timestamp,
+    ) -> Result<(), IssuerRejection> {
+        if path.contains(&index) {
+            return Err(IssuerRejection::Cycle);
+        }
+        let basic_constraints = candidate.id_cert_tbs.capabilities.basic_constraints;
+        if !basic_constraints.ca || candidate.validate(Some(Target::HomeServer)).is_err() {
+            return Err(IssuerRejection::NotCa);
+        }
+        if !candidate.valid_at(time) {
+            return Err(IssuerRejection::InvalidValidity);
+        }
+        if let Some(path_length) = basic_constraints.path_length {
+            let cas_below = path
+                .iter()
+                .filter(|index| !self.certs[**index].is_cross_signed())
+                .count()
+                + usize::from(leaf_is_ca);
+            if (cas_below as u64) > path_length {
+                return Err(IssuerRejection::PathLengthExceeded);
+            }
+        }
+        if path.len() + 2 > self.max_chain_length {
+            return Err(IssuerRejection::ChainTooLong);
+        }
+        match cert.verify_signature(&candidate.id_cert_tbs.subject_public_key) {
+            Ok(()) if !key_identifiers_match(cert, candidate) => {
+                Err(IssuerRejection::KeyIdentifierMismatch)
+            }
+            Ok(()) => Ok(()),
+            Err(_) => Err(IssuerRejection::BadSignature),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<Vec<IdCert<S, P>>> for CertPool<S, P> {
+    fn from(value: Vec<IdCert<S, P>>) -> Self {
+        Self {
+            certs: value,
+            ..Default::default()
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> FromIterator<IdCert<S, P>> for CertPool<S, P> {
+    fn from_iter<T: IntoIterator<Item = IdCert<S, P>>>(iter: T) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+/// Validates the chain formed by `leaf` and `issuers`, ordered from the direct issuer of `leaf` to
+/// the self-signed root certificate. See [IdCertChain::validate_chain_with_tolerance()].
+pub(crate) fn validate_certs<S: Signature, P: PublicKey<S>>(
+    leaf: &IdCert<S, P>,
+    issuers: &[IdCert<S, P>],
+    time: Timestamp,
+    target: Target,
+    tolerance: Duration,
+) -> Result<(), InvalidCert> {
+    log::trace!(
+        "[IdCertChain::validate_chain()] validating chain of length {} for target {:?}",
+        issuers.len() + 1,
+        target
+    );
+    let root = match issuers.last() {
+        Some(root) => root,
+        None => return Err(malformed("Certificate chain contains no issuers")),
+    };
+    leaf.validate(Some(target))?;
+    for (index, issuer) in issuers.iter().enumerate() {
+        issuer.validate(Some(Target::HomeServer))?;
+        let basic_constraints = issuer.id_cert_tbs.capabilities.basic_constraints;
+        if !basic_constraints.ca {
+            return Err(malformed("Issuer in certificate chain is not a CA"));
+        }
+        if let Some(path_length) = basic_constraints.path_length {
+            let cas_below = std::iter::once(leaf)
+                .chain(&issuers[..index])
+                .filter(|cert| counts_towards_path_length(cert))
+                .count();
+            if (cas_below as u64) > path_length {
+                log::debug!(
+                    "[IdCertChain::validate_chain()] Issuer {} allows a path length of {}, but has {} CA certificates below it",
+                    index,
+                    path_length,
+                    cas_below
+                );
+                return Err(malformed(
+                    "Path length constraint of issuer in certificate chain is exceeded",
+                ));
+            }
+        }
+    }
+    for cert in std::iter::once(leaf).chain(issuers) {
+        if !cert.valid_at_with_tolerance(time, tolerance) {
+            return Err(InvalidCert::InvalidValidity);
+        }
+    }
+    let certs: Vec<&IdCert<S, P>> = std::iter::once(leaf).chain(issuers).collect();
+    for pair in certs.windows(2) {
+        let (cert, issuer) = (pair[0], pair[1]);
+        if cert.id_cert_tbs.issuer != issuer.id_cert_tbs.subject {
+            return Err(malformed(
+                "Issuer of certificate does not match subject of the next certificate in the chain",
+            ));
+        }
+        let (validity, issuer_validity) =
+            (&cert.id_cert_tbs.validity, &issuer.id_cert_tbs.validity);
+        if !issuer.is_cross_signed()
+            && (Timestamp::from(validity.not_before) < Timestamp::from(issuer_validity.not_before)
+                || Timestamp::from(validity.not_after) > Timestamp::from(issuer_validity.not_after))
+        {
+            log::debug!(
+                "[IdCertChain::validate_chain()] Validity period of certificate exceeds the validity period of its issuer"
+            );
+            return Err(InvalidCert::InvalidValidity);
+        }
+        cert.verify_signature(&issuer.id_cert_tbs.subject_public_key)?;
+        if !key_identifiers_match(cert, issuer) {
+            return Err(malformed(
+                "Authority key identifier of certificate does not match subject key identifier of the next certificate in the chain",
+            ));
+        }
+    }
+    if root.id_cert_tbs.issuer != root.id_cert_tbs.subject {
+        return Err(malformed("Root of certificate chain is not self-issued"));
+    }
+    root.verify_signature(&root.id_cert_tbs.subject_public_key)
+}
+
+/// Returns `true`, if `cert` counts towards the path length constraints of its issuers: it is a CA
+/// certificate, and not a cross-certificate.
+fn counts_towards_path_length<S: Signature, P: PublicKey<S>>(cert: &IdCert<S, P>) -> bool {
+    cert.id_cert_tbs.capabilities.basic_constraints.ca && !cert.is_cross_signed()
+}
+
+/// Returns `false`, if both `cert` and `issuer` carry key identifiers, and the
+/// AuthorityKeyIdentifier of `cert` differs from the SubjectKeyIdentifier of `issuer`.
+fn key_identifiers_match<S: Signature, P: PublicKey<S>>(
+    cert: &IdCert<S, P>,
+    issuer: &IdCert<S, P>,
+) -> bool {
+    match (
+        &cert.id_cert_tbs.authority_key_identifier,
+        &issuer.id_cert_tbs.subject_key_identifier,
+    ) {
+        (Some(authority_key_identifier), Some(subject_key_identifier)) => {
+            authority_key_identifier == subject_key_identifier
+        }
+        _ => true,
+    }
+}
+
+fn is_self_signed<S: Signature, P: PublicKey<S>>(cert: &IdCert<S, P>) -> bool {
+    cert.id_cert_tbs.issuer == cert.id_cert_tbs.subject
+        && cert
+            .verify_signature(&cert.id_cert_tbs.subject_public_key)
+            .is_ok()
+}
+
+fn malformed(reason: &str) -> InvalidCert {
+    log::debug!("[IdCertChain::validate_chain()] {}", reason);
+    InvalidCert::InvalidProperties(ConstraintError::Malformed(Some(reason.to_string())))
+}
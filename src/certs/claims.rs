@@ -0,0 +1,485 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use der::asn1::OctetString;
+use spki::ObjectIdentifier;
+use x509_cert::ext::Extension;
+
+use crate::errors::{ConversionError, InvalidInput};
+
+/// Object Identifier of the custom claims extension, in the private arc of polyproto.
+pub const OID_CUSTOM_CLAIMS: &str = "1.3.6.1.4.1.18227.4.1";
+/// The maximum number of claims a certificate may carry.
+pub const MAX_CLAIMS: usize = 16;
+/// The maximum length of the key of a claim, in bytes.
+pub const MAX_CLAIM_KEY_LENGTH: usize = 64;
+/// The maximum length of the encoded claims, in bytes.
+pub const MAX_CLAIMS_LENGTH: usize = 1024;
+
+/// CBOR major types used by the encoding of [CustomClaims].
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_SIMPLE: u8 = 7;
+const SIMPLE_FALSE: u8 = 20;
+const SIMPLE_TRUE: u8 = 21;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The value of a claim in [CustomClaims].
+pub enum ClaimValue {
+    /// A UTF-8 string.
+    Text(String),
+    /// An opaque byte string.
+    Bytes(Vec<u8>),
+    /// An unsigned integer.
+    Integer(u64),
+    /// A boolean.
+    Bool(bool),
+}
+
+impl From<&str> for ClaimValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for ClaimValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<Vec<u8>> for ClaimValue {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl From<u64> for ClaimValue {
+    fn from(value: u64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<bool> for ClaimValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Deployment-specific claims about the subject of an
+/// [IdCert](crate::certs::idcert::IdCert), such as a role or a tenant, carried in a single
+/// polyproto private extension ([OID_CUSTOM_CLAIMS]) instead of arbitrary unknown extensions.
+///
+/// Claims are a small key-value map. Keys are namespaced: they take the form
+/// `<namespace>/<name>`, where the namespace is a domain name in reverse notation controlled by
+/// whoever defines the claim, such as `chat.polyphony/role`, and the name consists of lowercase
+/// ASCII letters, digits, `-` and `_`. Keys can be at most [MAX_CLAIM_KEY_LENGTH] bytes long, and a
+/// certificate can carry at most [MAX_CLAIMS] claims taking up at most [MAX_CLAIMS_LENGTH] bytes
+/// when encoded. Use `validate()` of the [Constrained](crate::Constrained) trait to check these
+/// limits.
+///
+/// The claims are encoded as a CBOR map from text string keys to [ClaimValue]s, using the
+/// deterministic encoding of RFC 8949, section 4.2.1. The extension is always non-critical, so that
+/// verifiers which do not know about claims can still use the certificate; certificates marking it
+/// as critical are rejected. A certificate without claims does not carry the extension.
+pub struct CustomClaims {
+    claims: BTreeMap<String, ClaimValue>,
+}
+
+impl CustomClaims {
+    /// Creates an empty set of claims.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the claim `key`, returning the previous value of the claim, if any. Fails, if `key`
+    /// is not a valid namespaced key.
+    pub fn insert(
+        &mut self,
+        key: &str,
+        value: impl Into<ClaimValue>,
+    ) -> Result<Option<ClaimValue>, InvalidInput> {
+        validate_key(key)?;
+        Ok(self.claims.insert(key.to_string(), value.into()))
+    }
+
+    /// Like [CustomClaims::insert()], but returns the claims, for chaining.
+    pub fn with(mut self, key: &str, value: impl Into<ClaimValue>) -> Result<Self, InvalidInput> {
+        self.insert(key, value)?;
+        Ok(self)
+    }
+
+    /// Returns the value of the claim `key`, if present.
+    pub fn get(&self, key: &str) -> Option<&ClaimValue> {
+        self.claims.get(key)
+    }
+
+    /// Removes the claim `key`, returning its value, if it was present.
+    pub fn remove(&mut self, key: &str) -> Option<ClaimValue> {
+        self.claims.remove(key)
+    }
+
+    /// Returns an iterator over all claims, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ClaimValue)> {
+        self.claims.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Returns an iterator over the claims in `namespace`, yielding the names of the claims
+    /// without the namespace.
+    pub fn namespace<'a>(
+        &'a self,
+        namespace: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a ClaimValue)> + 'a {
+        self.claims
+            .iter()
+            .filter_map(move |(key, value)| match key.split_once('/') {
+                Some((claim_namespace, name)) if claim_namespace == namespace => {
+                    Some((name, value))
+                }
+                _ => None,
+            })
+    }
+
+    /// Returns the number of claims.
+    pub fn len(&self) -> usize {
+        self.claims.len()
+    }
+
+    /// Returns `true`, if there are no claims.
+    pub fn is_empty(&self) -> bool {
+        self.claims.is_empty()
+    }
+
+    /// Encodes the claims as a deterministically encoded CBOR map.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut cbor = Vec::new();
+        write_head(&mut cbor, MAJOR_MAP, self.claims.len() as u64);
+        // Deterministic encoding sorts map keys by their encoding: shorter keys first, keys of
+        // equal length in lexicographic order.
+        let mut claims = self.claims.iter().collect::<Vec<_>>();
+        claims.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        for (key, value) in claims {
+            write_head(&mut cbor, MAJOR_TEXT, key.len() as u64);
+            cbor.extend_from_slice(key.as_bytes());
+            match value {
+                ClaimValue::Text(text) => {
+                    write_head(&mut cbor, MAJOR_TEXT, text.len() as u64);
+                    cbor.extend_from_slice(text.as_bytes());
+                }
+                ClaimValue::Bytes(bytes) => {
+                    write_head(&mut cbor, MAJOR_BYTES, bytes.len() as u64);
+                    cbor.extend_from_slice(bytes);
+                }
+                ClaimValue::Integer(integer) => write_head(&mut cbor, MAJOR_UNSIGNED, *integer),
+                ClaimValue::Bool(false) => cbor.push(MAJOR_SIMPLE << 5 | SIMPLE_FALSE),
+                ClaimValue::Bool(true) => cbor.push(MAJOR_SIMPLE << 5 | SIMPLE_TRUE),
+            }
+        }
+        cbor
+    }
+
+    /// Decodes claims from a CBOR map. Only the deterministic encoding produced by
+    /// [CustomClaims::to_cbor()] is accepted; in particular, fails on keys which are not valid
+    /// namespaced keys, on duplicate or unordered keys and on values of other types than those of
+    /// [ClaimValue].
+    pub fn from_cbor(cbor: &[u8]) -> Result<Self, InvalidInput> {
+        if cbor.len() > MAX_CLAIMS_LENGTH {
+            return Err(InvalidInput::Length {
+                min_length: 0,
+                max_length: MAX_CLAIMS_LENGTH,
+                actual_length: cbor.len().to_string(),
+            });
+        }
+        let mut reader = Reader { input: cbor };
+        let (major, count) = reader.head()?;
+        if major != MAJOR_MAP {
+            return Err(malformed("Custom claims must be encoded as a CBOR map"));
+        }
+        if count > MAX_CLAIMS as u64 {
+            return Err(InvalidInput::Length {
+                min_length: 0,
+                max_length: MAX_CLAIMS,
+                actual_length: count.to_string(),
+            });
+        }
+        let mut claims = Self::new();
+        let mut previous_key: Option<String> = None;
+        for _ in 0..count {
+            let (major, length) = reader.head()?;
+            if major != MAJOR_TEXT {
+                return Err(malformed("The keys of custom claims must be text strings"));
+            }
+            let key = reader.text(length)?;
+            if let Some(previous_key) = &previous_key {
+                if (previous_key.len(), previous_key.as_str()) >= (key.len(), key.as_str()) {
+                    return Err(malformed(
+                        "The keys of custom claims must be unique and in deterministic order",
+                    ));
+                }
+            }
+            let value = match reader.head()? {
+                (MAJOR_UNSIGNED, integer) => ClaimValue::Integer(integer),
+                (MAJOR_BYTES, length) => ClaimValue::Bytes(reader.bytes(length)?.to_vec()),
+                (MAJOR_TEXT, length) => ClaimValue::Text(reader.text(length)?),
+                (MAJOR_SIMPLE, simple) if simple == SIMPLE_FALSE as u64 => ClaimValue::Bool(false),
+                (MAJOR_SIMPLE, simple) if simple == SIMPLE_TRUE as u64 => ClaimValue::Bool(true),
+                _ => {
+                    return Err(malformed(
+                        "The values of custom claims must be text strings, byte strings, \
+                         unsigned integers or booleans",
+                    ))
+                }
+            };
+            claims.insert(&key, value)?;
+            previous_key = Some(key);
+        }
+        if !reader.input.is_empty() {
+            return Err(malformed("Trailing data after the custom claims"));
+        }
+        Ok(claims)
+    }
+}
+
+impl TryFrom<CustomClaims> for Extension {
+    type Error = ConversionError;
+
+    /// Encodes the claims as the non-critical custom claims extension. Fails, if the encoded
+    /// claims exceed [MAX_CLAIMS_LENGTH].
+    fn try_from(value: CustomClaims) -> Result<Self, Self::Error> {
+        let cbor = value.to_cbor();
+        if cbor.len() > MAX_CLAIMS_LENGTH {
+            return Err(InvalidInput::Length {
+                min_length: 0,
+                max_length: MAX_CLAIMS_LENGTH,
+                actual_length: cbor.len().to_string(),
+            }
+            .into());
+        }
+        Ok(Extension {
+            extn_id: ObjectIdentifier::from_str(OID_CUSTOM_CLAIMS)?,
+            critical: false,
+            extn_value: OctetString::new(cbor)?,
+        })
+    }
+}
+
+impl TryFrom<Extension> for CustomClaims {
+    type Error = ConversionError;
+
+    /// Decodes the claims from the custom claims extension. Fails, if the extension has another
+    /// OID, is marked as critical or does not hold validly encoded claims.
+    fn try_from(value: Extension) -> Result<Self, Self::Error> {
+        if value.extn_id.to_string() != OID_CUSTOM_CLAIMS {
+            return Err(malformed(&format!(
+                "Expected the custom claims extension {}, found {}",
+                OID_CUSTOM_CLAIMS, value.extn_id
+            ))
+            .into());
+        }
+        if value.critical {
+            return Err(malformed("The custom claims extension must not be critical").into());
+        }
+        Ok(Self::from_cbor(value.extn_value.as_bytes())?)
+    }
+}
+
+/// Checks that `key` takes the form `<namespace>/<name>`, as described in [CustomClaims].
+pub(crate) fn validate_key(key: &str) -> Result<(), InvalidInput> {
+    if key.is_empty() || key.len() > MAX_CLAIM_KEY_LENGTH {
+        return Err(InvalidInput::Length {
+            min_length: 1,
+            max_length: MAX_CLAIM_KEY_LENGTH,
+            actual_length: key.len().to_string(),
+        });
+    }
+    let (namespace, name) = match key.split_once('/') {
+        Some(parts) => parts,
+        None => {
+            return Err(malformed(&format!(
+                "The claim key {} is not of the form <namespace>/<name>",
+                key
+            )))
+        }
+    };
+    let is_label = |label: &str| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-')
+    };
+    if namespace.split('.').count() < 2 || !namespace.split('.').all(is_label) {
+        return Err(malformed(&format!(
+            "The namespace of the claim key {} is not a domain name in reverse notation",
+            key
+        )));
+    }
+    if name.is_empty()
+        || !name.bytes().all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' || byte == b'_'
+        })
+    {
+        return Err(malformed(&format!(
+            "The name of the claim key {} may only contain lowercase letters, digits, - and _",
+            key
+        )));
+    }
+    Ok(())
+}
+
+fn malformed(message: &str) -> InvalidInput {
+    InvalidInput::Malformed(message.to_string())
+}
+
+fn write_head(cbor: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        cbor.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        cbor.push(major | 24);
+        cbor.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        cbor.push(major | 25);
+        cbor.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        cbor.push(major | 26);
+        cbor.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        cbor.push(major | 27);
+        cbor.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Reads the subset of CBOR used by [CustomClaims], rejecting anything not in deterministic
+/// encoding.
+struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], InvalidInput> {
+        if self.input.len() < length {
+            return Err(malformed("Unexpected end of the custom claims"));
+        }
+        let (taken, rest) = self.input.split_at(length);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    /// Reads the head of a data item, returning its major type and argument.
+    fn head(&mut self) -> Result<(u8, u64), InvalidInput> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let additional = initial & 0x1f;
+        let (value, minimum) = match additional {
+            0..=23 => return Ok((major, additional as u64)),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => (
+                u16::from_be_bytes([self.take(1)?[0], self.take(1)?[0]]) as u64,
+                u8::MAX as u64 + 1,
+            ),
+            26 => {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(self.take(4)?);
+                (u32::from_be_bytes(bytes) as u64, u16::MAX as u64 + 1)
+            }
+            27 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                (u64::from_be_bytes(bytes), u32::MAX as u64 + 1)
+            }
+            _ => {
+                return Err(malformed(
+                    "Indefinite lengths are not permitted in custom claims",
+                ))
+            }
+        };
+        // Simple values of major type 7 are always encoded in the initial byte.
+        if value < minimum || major == MAJOR_SIMPLE {
+            return Err(malformed(
+                "Custom claims must use the shortest possible encoding",
+            ));
+        }
+        Ok((major, value))
+    }
+
+    fn bytes(&mut self, length: u64) -> Result<&'a [u8], InvalidInput> {
+        match usize::try_from(length) {
+            Ok(length) => self.take(length),
+            Err(_) => Err(malformed("Unexpected end of the custom claims")),
+        }
+    }
+
+    fn text(&mut self, length: u64) -> Result<String, InvalidInput> {
+        match std::str::from_utf8(self.bytes(length)?) {
+            Ok(text) => Ok(text.to_string()),
+            Err(_) => Err(malformed("Custom claims contain invalid UTF-8")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn cbor_encoding() {
+        let claims = CustomClaims::new()
+            .with("chat.polyphony/role", "admin")
+            .unwrap()
+            .with("chat.polyphony/level", 500u64)
+            .unwrap()
+            .with("chat.polyphony/bot", false)
+            .unwrap();
+        let cbor = claims.to_cbor();
+        // {"chat.polyphony/bot": false, "chat.polyphony/role": "admin",
+        //  "chat.polyphony/level": 500}
+        let mut expected = vec![0xa3, 0x72];
+        expected.extend_from_slice(b"chat.polyphony/bot");
+        expected.push(0xf4);
+        expected.push(0x73);
+        expected.extend_from_slice(b"chat.polyphony/role");
+        expected.push(0x65);
+        expected.extend_from_slice(b"admin");
+        expected.push(0x74);
+        expected.extend_from_slice(b"chat.polyphony/level");
+        expected.extend_from_slice(&[0x19, 0x01, 0xf4]);
+        assert_eq!(cbor, expected);
+        assert_eq!(CustomClaims::from_cbor(&cbor).unwrap(), claims);
+
+        // 500 is not encoded in the shortest form.
+        let mut long_form = cbor.clone();
+        let position = long_form.len() - 3;
+        long_form.splice(position.., [0x1a, 0x00, 0x00, 0x01, 0xf4]);
+        assert!(CustomClaims::from_cbor(&long_form).is_err());
+        // Trailing data.
+        let mut trailing = cbor.clone();
+        trailing.push(0x00);
+        assert!(CustomClaims::from_cbor(&trailing).is_err());
+        // Truncated data.
+        assert!(CustomClaims::from_cbor(&cbor[..cbor.len() - 1]).is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn claim_keys() {
+        assert!(validate_key("chat.polyphony/role").is_ok());
+        assert!(validate_key("com.example.sub/tenant_id").is_ok());
+        assert!(validate_key("role").is_err());
+        assert!(validate_key("polyphony/role").is_err());
+        assert!(validate_key("chat.polyphony/").is_err());
+        assert!(validate_key("Chat.polyphony/role").is_err());
+        assert!(validate_key("chat.polyphony/role/admin").is_err());
+        assert!(validate_key("chat.-polyphony/role").is_err());
+        assert!(validate_key(&format!("chat.polyphony/{}", "a".repeat(64))).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use der::pem::LineEnding;
+use der::{Decode, Encode};
+use spki::AlgorithmIdentifierOwned;
+use x509_cert::crl::{CertificateList, RevokedCert, TbsCertList};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::time::{Time, Validity};
+
+use crate::errors::{
+    ConstraintError, ConversionError, InvalidCert, ERR_MSG_CRL_ISSUER_MISMATCH,
+    ERR_MSG_CRL_SIGNER_MISSING_CRL_SIGN, ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT,
+};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::Constrained;
+
+use super::capabilities::KeyUsage;
+use super::claims::CustomClaims;
+use super::equal_domain_components;
+use super::idcert::IdCert;
+use super::idcerttbs::IdCertTbs;
+use super::idcsr::IdCsr;
+use super::pem::{self, PemKind, PemLabel};
+use super::san::SubjectAltNames;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single entry of an [IdCrl], revoking the certificate with the given serial number.
+pub struct RevokedEntry {
+    /// The serial number of the revoked certificate.
+    pub serial_number: Uint,
+    /// The point in time at which the certificate was revoked.
+    pub revocation_date: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The contents of an [IdCrl], which are signed by the CRL issuer.
+///
+/// `issuer` is the [Name] of the party signing the CRL. This is either the home server itself,
+/// or a delegated CRL issuer designated by a [CrlIssuerDesignation]. In both cases, all entries
+/// refer to certificates issued by the home server.
+pub struct IdCrlTbs {
+    /// The signature algorithm used to sign this CRL.
+    pub signature_algorithm: AlgorithmIdentifierOwned,
+    /// X.501 name, identifying the issuer of the CRL.
+    pub issuer: Name,
+    /// The point in time at which this CRL was issued.
+    pub this_update: Timestamp,
+    /// The point in time by which the next CRL will be issued, if known.
+    pub next_update: Option<Timestamp>,
+    /// The revoked certificates.
+    pub revoked: Vec<RevokedEntry>,
+}
+
+impl IdCrlTbs {
+    /// Encode this type as DER, returning a byte vector.
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        Ok(TbsCertList::try_from(self.clone())?.to_der()?)
+    }
+
+    /// Returns `true`, if this CRL is current at the given time, i.e. if it has been issued, and
+    /// its `next_update` has not yet passed.
+    pub fn current_at(&self, time: Timestamp) -> bool {
+        time >= self.this_update
+            && match self.next_update {
+                Some(next_update) => time <= next_update,
+                None => true,
+            }
+    }
+}
+
+impl TryFrom<IdCrlTbs> for TbsCertList {
+    type Error = ConversionError;
+
+    fn try_from(value: IdCrlTbs) -> Result<Self, Self::Error> {
+        let mut revoked_certificates = Vec::with_capacity(value.revoked.len());
+        for entry in value.revoked.into_iter() {
+            revoked_certificates.push(RevokedCert {
+                serial_number: SerialNumber::new(entry.serial_number.as_bytes())?,
+                revocation_date: Time::try_from(entry.revocation_date)?,
+                crl_entry_extensions: None,
+            });
+        }
+        Ok(TbsCertList {
+            version: x509_cert::Version::V2,
+            signature: value.signature_algorithm,
+            issuer: value.issuer,
+            this_update: Time::try_from(value.this_update)?,
+            next_update: match value.next_update {
+                Some(next_update) => Some(Time::try_from(next_update)?),
+                None => None,
+            },
+            revoked_certificates: match revoked_certificates.is_empty() {
+                true => None,
+                false => Some(revoked_certificates),
+            },
+            crl_extensions: None,
+        })
+    }
+}
+
+impl TryFrom<TbsCertList> for IdCrlTbs {
+    type Error = ConversionError;
+
+    fn try_from(value: TbsCertList) -> Result<Self, Self::Error> {
+        let mut revoked = Vec::new();
+        for entry in value.revoked_certificates.unwrap_or_default().into_iter() {
+            revoked.push(RevokedEntry {
+                serial_number: Uint::new(entry.serial_number.as_bytes())?,
+                revocation_date: entry.revocation_date.into(),
+            });
+        }
+        Ok(Self {
+            signature_algorithm: value.signature,
+            issuer: value.issuer,
+            this_update: value.this_update.into(),
+            next_update: value.next_update.map(Timestamp::from),
+            revoked,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A signed certificate revocation list, listing revoked certificates of a home server.
+///
+/// An [IdCrl] can either be signed by the home server directly, or by a dedicated revocation key,
+/// which the home server has authorized using a [CrlIssuerDesignation]. The latter allows keeping
+/// the key of the home server offline, as it is not needed for every CRL update.
+pub struct IdCrl<S: Signature> {
+    /// Inner TBS (To be signed) CRL
+    pub id_crl_tbs: IdCrlTbs,
+    /// Signature for the TBS CRL
+    pub signature: S,
+}
+
+impl<S: Signature> IdCrl<S> {
+    /// Creates and signs a new [IdCrl]. `issuer` must be the subject [Name] of `signing_key`s
+    /// certificate.
+    pub fn new(
+        issuer: Name,
+        this_update: Timestamp,
+        next_update: Option<Timestamp>,
+        revoked: Vec<RevokedEntry>,
+        signing_key: &impl PrivateKey<S>,
+    ) -> Result<Self, ConversionError> {
+        let id_crl_tbs = IdCrlTbs {
+            signature_algorithm: signing_key.algorithm_identifier(),
+            issuer,
+            this_update,
+            next_update,
+            revoked,
+        };
+        let signature = signing_key.sign(&id_crl_tbs.to_der()?);
+        Ok(Self {
+            id_crl_tbs,
+            signature,
+        })
+    }
+
+    /// Returns `true`, if this CRL lists the certificate with the given serial number as revoked.
+    pub fn is_revoked(&self, serial_number: &Uint) -> bool {
+        self.revocation_of(serial_number).is_some()
+    }
+
+    /// Returns the entry revoking the certificate with the given serial number, if any.
+    pub fn revocation_of(&self, serial_number: &Uint) -> Option<&RevokedEntry> {
+        self.id_crl_tbs
+            .revoked
+            .iter()
+            .find(|entry| &entry.serial_number == serial_number)
+    }
+
+    /// Checks `cert` against this CRL, failing with [InvalidCert::Revoked], if the CRL lists the
+    /// serial number of `cert` as revoked.
+    ///
+    /// This CRL is expected to have been verified by the caller using [IdCrl::verify()] or
+    /// [IdCrl::verify_delegated()], with the certificate of the home server which issued `cert`.
+    pub fn check<P: PublicKey<S>>(&self, cert: &IdCert<S, P>) -> Result<(), InvalidCert> {
+        match self.revocation_of(&cert.id_cert_tbs.serial_number) {
+            Some(entry) => {
+                log::debug!(
+                    "[IdCrl::check()] Certificate of {} was revoked at {}",
+                    cert.id_cert_tbs.subject,
+                    entry.revocation_date
+                );
+                Err(InvalidCert::Revoked(entry.revocation_date))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Verifies a CRL signed directly by the home server, checking that
+    ///
+    /// - the CRL is current at the given `time`,
+    /// - the issuer of the CRL is the subject of `home_server_cert`, and
+    /// - the CRL is signed by the key of `home_server_cert`.
+    ///
+    /// `home_server_cert` is expected to have been verified by the caller.
+    pub fn verify<P: PublicKey<S>>(
+        &self,
+        home_server_cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        self.verify_signed_by(&home_server_cert.id_cert_tbs, time)
+    }
+
+    /// Verifies a CRL signed by a delegated CRL issuer. In addition to the checks performed by
+    /// [CrlIssuerDesignation::verify()], this checks that
+    ///
+    /// - the CRL is current at the given `time`,
+    /// - the issuer of the CRL is the subject of the designated CRL issuer, and
+    /// - the CRL is signed by the key of the designated CRL issuer.
+    ///
+    /// `home_server_cert` is expected to have been verified by the caller.
+    pub fn verify_delegated<P: PublicKey<S>>(
+        &self,
+        home_server_cert: &IdCert<S, P>,
+        designation: &CrlIssuerDesignation<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        designation.verify(home_server_cert, time)?;
+        self.verify_signed_by(&designation.crl_issuer.id_cert_tbs, time)
+    }
+
+    /// Encode this type as DER, returning a byte vector.
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        Ok(CertificateList::try_from(self.clone())?.to_der()?)
+    }
+
+    /// Create an [IdCrl] from a byte slice containing a DER encoded X.509 CRL, signed directly
+    /// by the home server. The resulting `IdCrl` has the same validity guarantees as when using
+    /// [IdCrl::verify()].
+    pub fn from_der<P: PublicKey<S>>(
+        bytes: &[u8],
+        home_server_cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<Self, InvalidCert> {
+        let crl = match IdCrl::from_der_unchecked(bytes) {
+            Ok(crl) => crl,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        crl.verify(home_server_cert, time)?;
+        Ok(crl)
+    }
+
+    /// Create an unchecked [IdCrl] from a byte slice containing a DER encoded X.509 CRL. The
+    /// caller is responsible for verifying the `IdCrl` using [IdCrl::verify()] or
+    /// [IdCrl::verify_delegated()] before using it.
+    pub fn from_der_unchecked(bytes: &[u8]) -> Result<Self, ConversionError> {
+        IdCrl::try_from(CertificateList::from_der(bytes)?)
+    }
+
+    /// Create an [IdCrl] from a string containing a PEM encoded X.509 CRL, signed directly by the
+    /// home server. The resulting `IdCrl` has the same validity guarantees as when using
+    /// [IdCrl::verify()].
+    pub fn from_pem<P: PublicKey<S>>(
+        pem: &str,
+        home_server_cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<Self, InvalidCert> {
+        let crl = match IdCrl::from_pem_unchecked(pem) {
+            Ok(crl) => crl,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        crl.verify(home_server_cert, time)?;
+        Ok(crl)
+    }
+
+    /// Create an unchecked [IdCrl] from a string containing a PEM encoded X.509 CRL. The caller
+    /// is responsible for verifying the `IdCrl` using [IdCrl::verify()] or
+    /// [IdCrl::verify_delegated()] before using it.
+    pub fn from_pem_unchecked(pem: &str) -> Result<Self, ConversionError> {
+        IdCrl::from_der_unchecked(&pem::decode_expecting(pem, PemKind::RevocationList, None)?)
+    }
+
+    /// Encode this type as PEM with the standard `X509 CRL` label, returning a string.
+    pub fn to_pem(&self, line_ending: LineEnding) -> Result<String, ConversionError> {
+        let label = PemLabel {
+            kind: PemKind::RevocationList,
+            target: None,
+        };
+        pem::encode(&self.to_der()?, label, line_ending)
+    }
+
+    fn verify_signed_by<P: PublicKey<S>>(
+        &self,
+        signer: &IdCertTbs<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        if !self.id_crl_tbs.current_at(time) {
+            return Err(InvalidCert::InvalidValidity);
+        }
+        if self.id_crl_tbs.issuer != signer.subject {
+            log::warn!("[IdCrl::verify()] {}", ERR_MSG_CRL_ISSUER_MISMATCH);
+            return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                Some(ERR_MSG_CRL_ISSUER_MISMATCH.to_string()),
+            )));
+        }
+        let der = match self.id_crl_tbs.to_der() {
+            Ok(der) => der,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        Ok(signer
+            .subject_public_key
+            .verify_signature(&self.signature, &der)?)
+    }
+}
+
+impl<S: Signature> TryFrom<IdCrl<S>> for CertificateList {
+    type Error = ConversionError;
+
+    fn try_from(value: IdCrl<S>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            signature_algorithm: value.id_crl_tbs.signature_algorithm.clone(),
+            tbs_cert_list: value.id_crl_tbs.try_into()?,
+            signature: value.signature.to_bitstring()?,
+        })
+    }
+}
+
+impl<S: Signature> TryFrom<CertificateList> for IdCrl<S> {
+    type Error = ConversionError;
+
+    fn try_from(value: CertificateList) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id_crl_tbs: value.tbs_cert_list.try_into()?,
+            signature: S::from_bytes(value.signature.raw_bytes()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Designates a dedicated key as a CRL issuer on behalf of a home server.
+///
+/// The designation is a certificate issued and signed by the home server, which holds the
+/// [KeyUsage::CrlSign] capability and shares the domain components of the home server. It is not
+/// a CA certificate, and as such cannot be used to issue other certificates.
+pub struct CrlIssuerDesignation<S: Signature, P: PublicKey<S>> {
+    /// The certificate of the delegated CRL issuer.
+    pub crl_issuer: IdCert<S, P>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CrlIssuerDesignation<S, P> {
+    /// Issues a new [CrlIssuerDesignation] for the subject and public key of `id_csr`, signed by
+    /// the home server key. The capabilities requested in the [IdCsr] must include
+    /// [KeyUsage::CrlSign], and must not include the CA flag.
+    pub fn issue(
+        id_csr: IdCsr<S, P>,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+        serial_number: Uint,
+        issuer: Name,
+        validity: Validity,
+    ) -> Result<Self, ConversionError> {
+        id_csr.validate(None)?;
+        let id_cert_tbs = IdCertTbs::<S, P> {
+            serial_number,
+            signature_algorithm: signing_key.algorithm_identifier(),
+            issuer,
+            validity,
+            subject: id_csr.inner_csr.subject,
+            subject_public_key: id_csr.inner_csr.subject_public_key,
+            capabilities: id_csr.inner_csr.capabilities,
+            claims: CustomClaims::new(),
+            crl_distribution_point: None,
+            subject_key_identifier: None,
+            authority_key_identifier: None,
+            subject_alt_names: SubjectAltNames::new(),
+            s: std::marker::PhantomData,
+        }
+        .with_key_identifiers(signing_key.pubkey());
+        validate_crl_issuer(&id_cert_tbs)?;
+        let signature = signing_key.sign(&id_cert_tbs.to_der()?);
+        Ok(Self {
+            crl_issuer: IdCert {
+                id_cert_tbs: id_cert_tbs.into(),
+                signature,
+            },
+        })
+    }
+
+    /// Verifies this designation, checking that
+    ///
+    /// - the certificate of the delegated CRL issuer is valid at the given `time`,
+    /// - it was issued by the subject of `home_server_cert`, and signed by its key,
+    /// - it holds the [KeyUsage::CrlSign] capability and is not a CA certificate, and
+    /// - its subject shares the domain components of the home server.
+    pub fn verify(
+        &self,
+        home_server_cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        let tbs = &self.crl_issuer.id_cert_tbs;
+        if tbs.issuer != home_server_cert.id_cert_tbs.subject {
+            return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                Some(ERR_MSG_CRL_ISSUER_MISMATCH.to_string()),
+            )));
+        }
+        validate_crl_issuer(tbs)?;
+        self.crl_issuer
+            .full_verify_actor(time, &home_server_cert.id_cert_tbs.subject_public_key)
+    }
+}
+
+fn validate_crl_issuer<S: Signature, P: PublicKey<S>>(
+    tbs: &IdCertTbs<S, P>,
+) -> Result<(), ConstraintError> {
+    tbs.capabilities.validate(None)?;
+    tbs.subject.validate(None)?;
+    if tbs.capabilities.basic_constraints.ca
+        || !tbs
+            .capabilities
+            .key_usage
+            .key_usages
+            .contains(&KeyUsage::CrlSign)
+    {
+        return Err(ConstraintError::Malformed(Some(
+            ERR_MSG_CRL_SIGNER_MISSING_CRL_SIGN.to_string(),
+        )));
+    }
+    if tbs.issuer == tbs.subject {
+        return Err(ConstraintError::Malformed(Some(
+            "Delegated CRL issuers must not share the name of the home server".to_string(),
+        )));
+    }
+    if !equal_domain_components(&tbs.issuer, &tbs.subject) {
+        return Err(ConstraintError::Malformed(Some(
+            ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT.to_string(),
+        )));
+    }
+    Ok(())
+}
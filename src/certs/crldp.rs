@@ -0,0 +1,133 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use der::asn1::{Ia5String, OctetString};
+use der::{Decode, Encode};
+use spki::ObjectIdentifier;
+use x509_cert::ext::pkix::crl::dp::DistributionPoint;
+use x509_cert::ext::pkix::name::{DistributionPointName, GeneralName};
+use x509_cert::ext::pkix::CrlDistributionPoints;
+use x509_cert::ext::Extension;
+
+use crate::errors::{ConversionError, InvalidInput};
+
+/// Object Identifier of the CRLDistributionPoints extension, as defined in RFC 5280.
+pub const OID_CRL_DISTRIBUTION_POINTS: &str = "2.5.29.31";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The URL at which the issuer of a certificate publishes its [IdCrl](super::crl::IdCrl)s, carried
+/// in the CRLDistributionPoints extension of an [IdCert](super::idcert::IdCert).
+///
+/// polyproto certificates carry at most one distribution point, consisting of a single `http` or
+/// `https` URL. Validators can fetch the current CRL of the home server from this URL, and check
+/// the certificate against it using [IdCrl::check()](super::crl::IdCrl::check()).
+pub struct CrlDistributionPoint {
+    url: String,
+}
+
+impl CrlDistributionPoint {
+    /// Creates a new [CrlDistributionPoint]. Fails, if `url` is not an `http` or `https` URL
+    /// consisting of printable ASCII characters.
+    pub fn new(url: &str) -> Result<Self, InvalidInput> {
+        let rest = match url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+        {
+            Some(rest) => rest,
+            None => {
+                return Err(InvalidInput::Malformed(format!(
+                    "The CRL distribution point {} is not an http or https URL",
+                    url
+                )))
+            }
+        };
+        if rest.is_empty() || !url.bytes().all(|byte| byte.is_ascii_graphic()) {
+            return Err(InvalidInput::Malformed(format!(
+                "The CRL distribution point {} is not a valid URL",
+                url
+            )));
+        }
+        Ok(Self {
+            url: url.to_string(),
+        })
+    }
+
+    /// The URL of the distribution point.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Display for CrlDistributionPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+impl TryFrom<CrlDistributionPoint> for Extension {
+    type Error = ConversionError;
+
+    /// Encodes the distribution point as a non-critical CRLDistributionPoints extension with a
+    /// single distribution point, naming the URL as its full name.
+    fn try_from(value: CrlDistributionPoint) -> Result<Self, Self::Error> {
+        let name = GeneralName::UniformResourceIdentifier(Ia5String::new(&value.url)?);
+        let distribution_points = CrlDistributionPoints(vec![DistributionPoint {
+            distribution_point: Some(DistributionPointName::FullName(vec![name])),
+            reasons: None,
+            crl_issuer: None,
+        }]);
+        Ok(Extension {
+            extn_id: ObjectIdentifier::from_str(OID_CRL_DISTRIBUTION_POINTS)?,
+            critical: false,
+            extn_value: OctetString::new(distribution_points.to_der()?)?,
+        })
+    }
+}
+
+impl TryFrom<Extension> for CrlDistributionPoint {
+    type Error = ConversionError;
+
+    /// Decodes the distribution point from a CRLDistributionPoints extension. Fails, if the
+    /// extension has another OID, is marked as critical, or does not consist of exactly one
+    /// distribution point naming a single, valid URL.
+    fn try_from(value: Extension) -> Result<Self, Self::Error> {
+        if value.extn_id.to_string() != OID_CRL_DISTRIBUTION_POINTS {
+            return Err(malformed(&format!(
+                "Expected the CRLDistributionPoints extension {}, found {}",
+                OID_CRL_DISTRIBUTION_POINTS, value.extn_id
+            ))
+            .into());
+        }
+        if value.critical {
+            return Err(
+                malformed("The CRLDistributionPoints extension must not be critical").into(),
+            );
+        }
+        let distribution_points = CrlDistributionPoints::from_der(value.extn_value.as_bytes())?;
+        let names = match distribution_points.0.as_slice() {
+            [DistributionPoint {
+                distribution_point: Some(DistributionPointName::FullName(names)),
+                reasons: None,
+                crl_issuer: None,
+            }] => names,
+            _ => {
+                return Err(malformed(
+                    "Expected exactly one CRL distribution point, naming its full name",
+                )
+                .into())
+            }
+        };
+        match names.as_slice() {
+            [GeneralName::UniformResourceIdentifier(url)] => Ok(Self::new(url.as_str())?),
+            _ => Err(malformed("Expected the CRL distribution point to name a single URL").into()),
+        }
+    }
+}
+
+fn malformed(reason: &str) -> InvalidInput {
+    InvalidInput::Malformed(reason.to_string())
+}
@@ -0,0 +1,397 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::{GeneralizedTime, OctetString, SetOfVec};
+use der::Any;
+use rand_core::CryptoRngCore;
+use spki::ObjectIdentifier;
+use x509_cert::attr::{Attribute, Attributes};
+use x509_cert::name::Name;
+
+use crate::errors::{ConversionError, InvalidInput, StaleCsr};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::capabilities::Capabilities;
+use super::idcsr::IdCsr;
+use super::Target;
+
+/// Object Identifier of the attribute carrying the creation time of a CSR.
+pub const OID_CSR_CREATED_AT: &str = "1.3.6.1.4.1.18227.3.1";
+/// Object Identifier of the attribute carrying the expiry time of a CSR.
+pub const OID_CSR_EXPIRES_AT: &str = "1.3.6.1.4.1.18227.3.2";
+/// Object Identifier of the attribute carrying the one-time nonce of a CSR.
+pub const OID_CSR_NONCE: &str = "1.3.6.1.4.1.18227.3.3";
+/// The length of nonces generated by [CsrMetadata::fresh()], in bytes.
+pub const CSR_NONCE_LENGTH: usize = 16;
+/// The maximum length of a CSR nonce, in bytes.
+pub const CSR_NONCE_MAX_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Optional metadata of an [IdCsr], carried as PKCS#10 attributes alongside its [Capabilities].
+/// Lets home servers reject CSRs which are stale or have been submitted before, using a
+/// [CsrReplayGuard].
+///
+/// The creation and expiry times are encoded as `GeneralizedTime`, the nonce as an
+/// `OCTET STRING`. All fields are covered by the signature of the CSR.
+pub struct CsrMetadata {
+    /// The point in time at which the CSR was created.
+    pub created_at: Option<Timestamp>,
+    /// The point in time after which the subject no longer wants the CSR to be honored.
+    pub expires_at: Option<Timestamp>,
+    /// A random value, making the CSR single-use. Must be between 1 and
+    /// [CSR_NONCE_MAX_LENGTH] bytes long.
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl CsrMetadata {
+    /// Creates empty [CsrMetadata]. CSRs without metadata encode exactly like CSRs created before
+    /// metadata was introduced.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates [CsrMetadata] for a CSR created at `now`, which expires after `lifetime` and
+    /// carries a random nonce of [CSR_NONCE_LENGTH] bytes.
+    pub fn fresh(now: Timestamp, lifetime: Duration, rng: &mut impl CryptoRngCore) -> Self {
+        let mut nonce = vec![0u8; CSR_NONCE_LENGTH];
+        rng.fill_bytes(&mut nonce);
+        Self {
+            created_at: Some(now),
+            expires_at: now.checked_add(lifetime),
+            nonce: Some(nonce),
+        }
+    }
+
+    /// Sets the creation time.
+    pub fn with_created_at(mut self, created_at: Timestamp) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Sets the expiry time.
+    pub fn with_expires_at(mut self, expires_at: Timestamp) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Sets the one-time nonce.
+    pub fn with_nonce(mut self, nonce: &[u8]) -> Self {
+        self.nonce = Some(nonce.to_vec());
+        self
+    }
+
+    /// Returns `true`, if no metadata is set.
+    pub fn is_empty(&self) -> bool {
+        self.created_at.is_none() && self.expires_at.is_none() && self.nonce.is_none()
+    }
+
+    /// Returns `true`, if an expiry time is set and `now` is at or after it.
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now >= expires_at,
+            None => false,
+        }
+    }
+
+    fn check(&self) -> Result<(), InvalidInput> {
+        if let Some(nonce) = &self.nonce {
+            if nonce.is_empty() || nonce.len() > CSR_NONCE_MAX_LENGTH {
+                return Err(InvalidInput::Length {
+                    min_length: 1,
+                    max_length: CSR_NONCE_MAX_LENGTH,
+                    actual_length: nonce.len().to_string(),
+                });
+            }
+        }
+        if let (Some(created_at), Some(expires_at)) = (self.created_at, self.expires_at) {
+            if expires_at < created_at {
+                return Err(InvalidInput::Malformed(
+                    "The CSR expires before it was created".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<Attributes> for CsrMetadata {
+    type Error = ConversionError;
+
+    /// Extracts the [CsrMetadata] from the attributes of a CSR, ignoring all other attributes.
+    ///
+    /// Fails, if a metadata attribute is present more than once, does not have exactly one
+    /// value, or its value is malformed.
+    fn try_from(value: Attributes) -> Result<Self, Self::Error> {
+        let mut metadata = CsrMetadata::new();
+        for attribute in value.iter() {
+            let oid = attribute.oid.to_string();
+            if ![OID_CSR_CREATED_AT, OID_CSR_EXPIRES_AT, OID_CSR_NONCE].contains(&oid.as_str()) {
+                continue;
+            }
+            let value = match attribute.values.as_slice() {
+                [value] => value,
+                _ => {
+                    return Err(InvalidInput::Malformed(format!(
+                        "Expected exactly one value for the CSR metadata attribute {}",
+                        oid
+                    ))
+                    .into())
+                }
+            };
+            let duplicate = match oid.as_str() {
+                OID_CSR_CREATED_AT => metadata
+                    .created_at
+                    .replace(timestamp_from_any(value)?)
+                    .is_some(),
+                OID_CSR_EXPIRES_AT => metadata
+                    .expires_at
+                    .replace(timestamp_from_any(value)?)
+                    .is_some(),
+                _ => metadata
+                    .nonce
+                    .replace(value.decode_as::<OctetString>()?.into_bytes())
+                    .is_some(),
+            };
+            if duplicate {
+                return Err(InvalidInput::Malformed(format!(
+                    "The CSR metadata attribute {} is present more than once",
+                    oid
+                ))
+                .into());
+            }
+        }
+        metadata.check()?;
+        Ok(metadata)
+    }
+}
+
+impl TryFrom<CsrMetadata> for Vec<Attribute> {
+    type Error = ConversionError;
+
+    /// Encodes the fields of [CsrMetadata] which are set as attributes.
+    fn try_from(value: CsrMetadata) -> Result<Self, Self::Error> {
+        value.check()?;
+        let mut attributes = Vec::new();
+        if let Some(created_at) = value.created_at {
+            attributes.push(attribute(
+                OID_CSR_CREATED_AT,
+                timestamp_to_any(created_at)?,
+            )?);
+        }
+        if let Some(expires_at) = value.expires_at {
+            attributes.push(attribute(
+                OID_CSR_EXPIRES_AT,
+                timestamp_to_any(expires_at)?,
+            )?);
+        }
+        if let Some(nonce) = value.nonce {
+            attributes.push(attribute(
+                OID_CSR_NONCE,
+                Any::encode_from(&OctetString::new(nonce)?)?,
+            )?);
+        }
+        Ok(attributes)
+    }
+}
+
+fn attribute(oid: &str, value: Any) -> Result<Attribute, ConversionError> {
+    let mut values = SetOfVec::new();
+    values.insert(value)?;
+    Ok(Attribute {
+        oid: ObjectIdentifier::from_str(oid)?,
+        values,
+    })
+}
+
+fn timestamp_to_any(timestamp: Timestamp) -> Result<Any, ConversionError> {
+    let time = GeneralizedTime::from_unix_duration(Duration::from_secs(timestamp.unix_seconds()))?;
+    Ok(Any::encode_from(&time)?)
+}
+
+fn timestamp_from_any(value: &Any) -> Result<Timestamp, ConversionError> {
+    let time = value.decode_as::<GeneralizedTime>()?;
+    Ok(Timestamp::from_unix_seconds(
+        time.to_unix_duration().as_secs(),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A home server side store of the nonces of accepted CSRs, rejecting CSRs which are stale or
+/// have been submitted before.
+///
+/// A CSR is accepted by [CsrReplayGuard::accept()], if it carries a creation time and a nonce,
+/// was created at most `max_age` ago, has not expired and its nonce has not been accepted before.
+/// Nonces are remembered until the CSRs carrying them would be rejected as too old anyway; call
+/// [CsrReplayGuard::prune()] periodically to forget them.
+///
+/// The guard does not verify the signature of the CSR. Only pass CSRs which have been validated,
+/// e.g. using [IdCsr::from_der()], so that the metadata can be trusted.
+pub struct CsrReplayGuard {
+    max_age: Duration,
+    clock_skew: Duration,
+    seen: HashMap<Vec<u8>, Timestamp>,
+}
+
+impl CsrReplayGuard {
+    /// The clock skew tolerated by default, when checking whether a CSR was created in the
+    /// future.
+    pub const DEFAULT_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+    /// Creates an empty guard, accepting CSRs created at most `max_age` ago.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            clock_skew: Self::DEFAULT_CLOCK_SKEW,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Sets the tolerated difference between the clocks of clients and the home server.
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Returns the maximum age of accepted CSRs.
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Returns the tolerated clock skew.
+    pub fn clock_skew(&self) -> Duration {
+        self.clock_skew
+    }
+
+    /// Checks whether CSR metadata is fresh at `now` and its nonce has not been accepted before,
+    /// without recording the nonce.
+    pub fn check(&self, metadata: &CsrMetadata, now: Timestamp) -> Result<(), StaleCsr> {
+        let (created_at, nonce) = match (metadata.created_at, &metadata.nonce) {
+            (Some(created_at), Some(nonce)) => (created_at, nonce),
+            _ => return Err(StaleCsr::MissingMetadata),
+        };
+        if let Some(expires_at) = metadata.expires_at {
+            if metadata.is_expired(now) {
+                return Err(StaleCsr::Expired(expires_at));
+            }
+        }
+        if now.checked_add(self.clock_skew).unwrap_or(now) < created_at {
+            return Err(StaleCsr::CreatedInFuture(created_at));
+        }
+        if self.forget_after(created_at) <= now {
+            return Err(StaleCsr::TooOld(created_at));
+        }
+        if self.seen.contains_key(nonce) {
+            log::debug!("[CsrReplayGuard::check()] Rejecting replayed CSR");
+            return Err(StaleCsr::Replayed);
+        }
+        Ok(())
+    }
+
+    /// Checks the metadata of `csr` like [CsrReplayGuard::check()] and, if the CSR is fresh,
+    /// records its nonce, so that the CSR is rejected if it is submitted again.
+    pub fn accept<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        csr: &IdCsr<S, P>,
+        now: Timestamp,
+    ) -> Result<(), StaleCsr> {
+        let metadata = &csr.inner_csr.metadata;
+        self.check(metadata, now)?;
+        if let (Some(created_at), Some(nonce)) = (metadata.created_at, &metadata.nonce) {
+            self.seen
+                .insert(nonce.clone(), self.forget_after(created_at));
+        }
+        Ok(())
+    }
+
+    /// Forgets the nonces of all CSRs which would be rejected as too old at `now`.
+    pub fn prune(&mut self, now: Timestamp) {
+        self.seen.retain(|_, forget_after| *forget_after > now);
+    }
+
+    /// Returns the number of remembered nonces.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Returns `true`, if no nonces are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    fn forget_after(&self, created_at: Timestamp) -> Timestamp {
+        created_at
+            .checked_add(self.max_age)
+            .unwrap_or(Timestamp::from_unix_seconds(u64::MAX))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A client side helper keeping a fresh, single-use [IdCsr] at hand. CSRs are created with
+/// [CsrMetadata::fresh()], and regenerated automatically once they expire, once they have been
+/// submitted or if the signing key changes.
+pub struct CsrGenerator<S: Signature, P: PublicKey<S>> {
+    subject: Name,
+    capabilities: Capabilities,
+    target: Option<Target>,
+    lifetime: Duration,
+    current: Option<IdCsr<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CsrGenerator<S, P> {
+    /// Creates a generator for CSRs of `subject`, requesting `capabilities` and validated against
+    /// `target`. Generated CSRs expire `lifetime` after their creation.
+    pub fn new(
+        subject: &Name,
+        capabilities: &Capabilities,
+        target: Option<Target>,
+        lifetime: Duration,
+    ) -> Self {
+        Self {
+            subject: subject.clone(),
+            capabilities: capabilities.clone(),
+            target,
+            lifetime,
+            current: None,
+        }
+    }
+
+    /// Returns the current CSR. A new CSR is created, if there is none yet, if the current one has
+    /// expired at `now`, or if it was not signed by `signing_key`.
+    pub fn csr(
+        &mut self,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+        rng: &mut impl CryptoRngCore,
+        now: Timestamp,
+    ) -> Result<IdCsr<S, P>, ConversionError> {
+        if let Some(csr) = &self.current {
+            if !csr.inner_csr.metadata.is_expired(now)
+                && csr.inner_csr.subject_public_key == *signing_key.pubkey()
+            {
+                return Ok(csr.clone());
+            }
+        }
+        log::trace!("[CsrGenerator::csr()] creating new CSR");
+        let csr = IdCsr::new_with_metadata(
+            &self.subject,
+            signing_key,
+            &self.capabilities,
+            &CsrMetadata::fresh(now, self.lifetime, rng),
+            self.target,
+        )?;
+        self.current = Some(csr.clone());
+        Ok(csr)
+    }
+
+    /// Discards the current CSR after it has been submitted, so that the next call to
+    /// [CsrGenerator::csr()] creates a CSR with a new nonce.
+    pub fn mark_used(&mut self) {
+        self.current = None;
+    }
+}
@@ -0,0 +1,507 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use x509_cert::name::Name;
+
+use crate::errors::{ConstraintError, ConversionError, InvalidCert, PublicKeyError};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::types::FederationId;
+use crate::OID_RDN_UID;
+
+use super::certid::CertId;
+use super::crl::IdCrl;
+use super::idcert::IdCert;
+use super::superseded::SupersededNotice;
+
+/// The prefix of the payload signed when exporting a [CustodyBundle].
+pub static CUSTODY_PAYLOAD_PREFIX: &str = "polyproto-custody-v1";
+/// The prefix of the payload signed when creating an [IdentityStatement].
+pub static IDENTITY_STATEMENT_PAYLOAD_PREFIX: &str = "polyproto-identity-statement-v1";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// What an [IdentityStatement] declares about the federation ID it was made for.
+pub enum IdentityStatementKind {
+    /// The actor moved to the given federation ID, for example on another home server.
+    MigratedTo(FederationId),
+    /// The actor moved here from the given federation ID.
+    MigratedFrom(FederationId),
+    /// The given federation ID is an alias of the actor.
+    Alias(FederationId),
+}
+
+impl Display for IdentityStatementKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentityStatementKind::MigratedTo(other) => write!(f, "migrated-to\n{}", other),
+            IdentityStatementKind::MigratedFrom(other) => write!(f, "migrated-from\n{}", other),
+            IdentityStatementKind::Alias(other) => write!(f, "alias\n{}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A signed statement of an actor about its federation ID, such as a migration to another home
+/// server or an alias. The statement is signed with the key of one of the actor's [IdCert]s, which
+/// must have been valid when the statement was made.
+pub struct IdentityStatement<S: Signature> {
+    /// The federation ID the statement was made for.
+    pub federation_id: FederationId,
+    /// What the statement declares.
+    pub kind: IdentityStatementKind,
+    /// The point in time at which the statement was made.
+    pub made_at: Timestamp,
+    /// The [CertId] of the certificate whose key signed the statement.
+    pub signed_by: CertId,
+    /// Signature over [IdentityStatement::signed_payload()].
+    pub signature: S,
+}
+
+impl<S: Signature> IdentityStatement<S> {
+    /// Creates and signs an [IdentityStatement] for the subject of `id_cert`. `signing_key` must
+    /// be the key certified by `id_cert`.
+    pub fn new<P: PublicKey<S>>(
+        federation_id: FederationId,
+        kind: IdentityStatementKind,
+        made_at: Timestamp,
+        id_cert: &IdCert<S, P>,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[IdentityStatement::new()] creating identity statement");
+        let signed_by = id_cert.cert_id()?;
+        let signature = signing_key.sign(&statement_payload(
+            &federation_id,
+            &kind,
+            made_at,
+            &signed_by,
+        ));
+        Ok(Self {
+            federation_id,
+            kind,
+            made_at,
+            signed_by,
+            signature,
+        })
+    }
+
+    /// Returns the payload signed by the actor: the federation ID, the kind of the statement and
+    /// the federation ID it refers to, [IdentityStatement::made_at] in Unix epoch seconds and the
+    /// [CertId] of the signing certificate, each separated by a newline and prefixed with
+    /// [IDENTITY_STATEMENT_PAYLOAD_PREFIX].
+    pub fn signed_payload(&self) -> Vec<u8> {
+        statement_payload(
+            &self.federation_id,
+            &self.kind,
+            self.made_at,
+            &self.signed_by,
+        )
+    }
+
+    /// Verifies the signature of this statement using the public key of the signing certificate.
+    pub fn verify<P: PublicKey<S>>(&self, public_key: &P) -> Result<(), PublicKeyError> {
+        public_key.verify_signature(&self.signature, &self.signed_payload())
+    }
+}
+
+fn statement_payload(
+    federation_id: &FederationId,
+    kind: &IdentityStatementKind,
+    made_at: Timestamp,
+    signed_by: &CertId,
+) -> Vec<u8> {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        IDENTITY_STATEMENT_PAYLOAD_PREFIX,
+        federation_id,
+        kind,
+        made_at.unix_seconds(),
+        signed_by
+    )
+    .into_bytes()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The proof that a certificate has been revoked: a CRL signed by the home server which issued
+/// the certificate, listing it as revoked.
+pub struct Revocation<S: Signature> {
+    /// The [CertId] of the revoked certificate.
+    pub cert_id: CertId,
+    /// The CRL listing the certificate as revoked.
+    pub crl: IdCrl<S>,
+}
+
+impl<S: Signature> Revocation<S> {
+    /// Returns the revocation date of the certificate, as listed in the CRL.
+    pub fn revoked_at(&self) -> Option<Timestamp> {
+        self.crl
+            .id_crl_tbs
+            .revoked
+            .iter()
+            .find(|entry| entry.serial_number == self.cert_id.serial)
+            .map(|entry| entry.revocation_date)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single event in the history of a federation ID, as recorded in a [CustodyBundle].
+pub enum CustodyEvent<S: Signature, P: PublicKey<S>> {
+    /// A certificate was issued.
+    Issued(Box<IdCert<S, P>>),
+    /// A certificate was superseded by another one after a key rotation.
+    Rotated(SupersededNotice<S>),
+    /// A certificate was revoked.
+    Revoked(Revocation<S>),
+    /// The actor made a statement about its identity, such as a migration or an alias.
+    Statement(IdentityStatement<S>),
+}
+
+impl<S: Signature, P: PublicKey<S>> CustodyEvent<S, P> {
+    /// Returns the point in time of the event: the start of the validity period of an issued
+    /// certificate, the point in time of a rotation or revocation, or the point in time a statement
+    /// was made.
+    pub fn at(&self) -> Timestamp {
+        match self {
+            CustodyEvent::Issued(id_cert) => {
+                Timestamp::from(id_cert.id_cert_tbs.validity.not_before)
+            }
+            CustodyEvent::Rotated(notice) => notice.superseded_at,
+            CustodyEvent::Revoked(revocation) => revocation
+                .revoked_at()
+                .unwrap_or(revocation.crl.id_crl_tbs.this_update),
+            CustodyEvent::Statement(statement) => statement.made_at,
+        }
+    }
+
+    /// Events at the same point in time are ordered by kind, so that certificates are issued
+    /// before they are referred to.
+    fn rank(&self) -> u8 {
+        match self {
+            CustodyEvent::Issued(_) => 0,
+            CustodyEvent::Rotated(_) => 1,
+            CustodyEvent::Statement(_) => 2,
+            CustodyEvent::Revoked(_) => 3,
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, ConversionError> {
+        let mut encoded = vec![self.rank()];
+        match self {
+            CustodyEvent::Issued(id_cert) => {
+                write_field(&mut encoded, &id_cert.to_der()?);
+            }
+            CustodyEvent::Rotated(notice) => {
+                write_field(&mut encoded, &notice.signed_payload());
+                write_field(&mut encoded, &signature_bytes(&notice.signature)?);
+            }
+            CustodyEvent::Revoked(revocation) => {
+                write_field(&mut encoded, revocation.cert_id.to_string().as_bytes());
+                write_field(&mut encoded, &revocation.crl.to_der()?);
+            }
+            CustodyEvent::Statement(statement) => {
+                write_field(&mut encoded, &statement.signed_payload());
+                write_field(&mut encoded, &signature_bytes(&statement.signature)?);
+            }
+        }
+        Ok(encoded)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Collects the history of a single federation ID and exports it as a signed [CustodyBundle].
+///
+/// Events may be added in any order; [CustodyExport::sign()] orders them by
+/// [CustodyEvent::at()].
+pub struct CustodyExport<S: Signature, P: PublicKey<S>> {
+    federation_id: FederationId,
+    events: Vec<CustodyEvent<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CustodyExport<S, P> {
+    /// Starts an export of the history of `federation_id`.
+    pub fn new(federation_id: FederationId) -> Self {
+        Self {
+            federation_id,
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds a certificate issued to the actor.
+    pub fn with_cert(mut self, id_cert: IdCert<S, P>) -> Self {
+        self.events.push(CustodyEvent::Issued(Box::new(id_cert)));
+        self
+    }
+
+    /// Adds a key rotation, proven by a [SupersededNotice].
+    pub fn with_rotation(mut self, notice: SupersededNotice<S>) -> Self {
+        self.events.push(CustodyEvent::Rotated(notice));
+        self
+    }
+
+    /// Adds the revocation of the certificate `cert_id`, proven by `crl`.
+    pub fn with_revocation(mut self, cert_id: CertId, crl: IdCrl<S>) -> Self {
+        self.events
+            .push(CustodyEvent::Revoked(Revocation { cert_id, crl }));
+        self
+    }
+
+    /// Adds an [IdentityStatement], such as a migration or an alias.
+    pub fn with_statement(mut self, statement: IdentityStatement<S>) -> Self {
+        self.events.push(CustodyEvent::Statement(statement));
+        self
+    }
+
+    /// Orders the events and signs the bundle with the key of the exporting home server, whose
+    /// certificate is `exporter`.
+    pub fn sign(
+        mut self,
+        exporter: &IdCert<S, P>,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+        exported_at: Timestamp,
+    ) -> Result<CustodyBundle<S, P>, ConversionError> {
+        log::trace!(
+            "[CustodyExport::sign()] exporting {} events of {}",
+            self.events.len(),
+            self.federation_id
+        );
+        self.events
+            .sort_by(|a, b| a.at().cmp(&b.at()).then(a.rank().cmp(&b.rank())));
+        let exporter = exporter.cert_id()?;
+        let signature = signing_key.sign(&bundle_payload(
+            &self.federation_id,
+            exported_at,
+            &exporter,
+            &self.events,
+        )?);
+        Ok(CustodyBundle {
+            federation_id: self.federation_id,
+            events: self.events,
+            exported_at,
+            exporter,
+            signature,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The complete, verifiable history of a single federation ID: every certificate issued to the
+/// actor, key rotations, revocations, migrations and aliases, ordered by time and signed by the
+/// exporting home server. Intended as the artifact for moderation disputes and account recovery
+/// reviews.
+///
+/// Create a [CustodyBundle] using a [CustodyExport], and check it using
+/// [CustodyBundle::verify()].
+pub struct CustodyBundle<S: Signature, P: PublicKey<S>> {
+    /// The federation ID whose history this is.
+    pub federation_id: FederationId,
+    /// The events, ordered by [CustodyEvent::at()].
+    pub events: Vec<CustodyEvent<S, P>>,
+    /// The point in time at which the bundle was exported.
+    pub exported_at: Timestamp,
+    /// The [CertId] of the certificate of the exporting home server.
+    pub exporter: CertId,
+    /// Signature over [CustodyBundle::signed_payload()] by the exporting home server.
+    pub signature: S,
+}
+
+impl<S: Signature, P: PublicKey<S>> CustodyBundle<S, P> {
+    /// Returns the payload signed by the exporting home server: [CUSTODY_PAYLOAD_PREFIX], the
+    /// federation ID, [CustodyBundle::exported_at] in Unix epoch seconds and the [CertId] of the
+    /// exporter, followed by the encoding of every event.
+    pub fn signed_payload(&self) -> Result<Vec<u8>, ConversionError> {
+        bundle_payload(
+            &self.federation_id,
+            self.exported_at,
+            &self.exporter,
+            &self.events,
+        )
+    }
+
+    /// Returns the certificates issued to the actor, in the order they were issued.
+    pub fn certs(&self) -> impl Iterator<Item = &IdCert<S, P>> {
+        self.events.iter().filter_map(|event| match event {
+            CustodyEvent::Issued(id_cert) => Some(id_cert.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Verifies the bundle against the certificates of the home servers involved, checking that
+    ///
+    /// - the bundle is signed by the exporter, which must be one of `home_server_certs`,
+    /// - the events are ordered by time,
+    /// - every certificate was issued to the federation ID of the bundle, and is signed by the
+    ///   home server named as its issuer,
+    /// - every rotation refers to certificates of the bundle, and is signed by the home server
+    ///   which issued the new certificate,
+    /// - every revocation refers to a certificate of the bundle, and is proven by a CRL signed
+    ///   directly by the home server which issued the certificate, and
+    /// - every statement is made for the federation ID of the bundle, and is signed by the key of a
+    ///   certificate of the bundle which was valid at the time.
+    ///
+    /// Events may only refer to certificates issued at or before the time of the event.
+    /// `home_server_certs` are expected to have been verified by the caller.
+    pub fn verify(&self, home_server_certs: &[IdCert<S, P>]) -> Result<(), InvalidCert> {
+        log::trace!(
+            "[CustodyBundle::verify()] verifying {} events of {}",
+            self.events.len(),
+            self.federation_id
+        );
+        let mut home_servers = HashMap::new();
+        for home_server_cert in home_server_certs {
+            home_servers.insert(cert_id(home_server_cert)?, home_server_cert);
+        }
+        let exporter = match home_servers.get(&self.exporter) {
+            Some(exporter) => exporter,
+            None => return Err(malformed("The exporter is not a known home server")),
+        };
+        let payload = match self.signed_payload() {
+            Ok(payload) => payload,
+            Err(e) => return Err(malformed(&e.to_string())),
+        };
+        exporter
+            .id_cert_tbs
+            .subject_public_key
+            .verify_signature(&self.signature, &payload)?;
+
+        let issuer_of = |issuer: &Name| {
+            home_server_certs
+                .iter()
+                .find(|home_server_cert| &home_server_cert.id_cert_tbs.subject == issuer)
+        };
+        let mut certs: HashMap<CertId, &IdCert<S, P>> = HashMap::new();
+        let mut previous = Timestamp::from_unix_seconds(0);
+        for event in self.events.iter() {
+            if event.at() < previous {
+                return Err(malformed("The events are not ordered by time"));
+            }
+            previous = event.at();
+            match event {
+                CustodyEvent::Issued(id_cert) => {
+                    if uid(&id_cert.id_cert_tbs.subject).as_deref()
+                        != Some(self.federation_id.as_str())
+                    {
+                        return Err(malformed(&format!(
+                            "A certificate was not issued to {}",
+                            self.federation_id
+                        )));
+                    }
+                    let issuer = match issuer_of(&id_cert.id_cert_tbs.issuer) {
+                        Some(issuer) => issuer,
+                        None => return Err(malformed("The issuer of a certificate is unknown")),
+                    };
+                    id_cert
+                        .full_verify_actor(event.at(), &issuer.id_cert_tbs.subject_public_key)?;
+                    certs.insert(cert_id(id_cert)?, id_cert);
+                }
+                CustodyEvent::Rotated(notice) => {
+                    let new = match (certs.get(&notice.old), certs.get(&notice.new)) {
+                        (Some(_), Some(new)) => new,
+                        _ => {
+                            return Err(malformed(
+                                "A rotation refers to a certificate not issued before it",
+                            ))
+                        }
+                    };
+                    match issuer_of(&new.id_cert_tbs.issuer) {
+                        Some(issuer) => notice.verify(&issuer.id_cert_tbs.subject_public_key)?,
+                        None => return Err(malformed("The issuer of a certificate is unknown")),
+                    }
+                }
+                CustodyEvent::Revoked(revocation) => {
+                    let revoked = match certs.get(&revocation.cert_id) {
+                        Some(revoked) => revoked,
+                        None => {
+                            return Err(malformed(
+                                "A revocation refers to a certificate not issued before it",
+                            ))
+                        }
+                    };
+                    if revocation.revoked_at().is_none() {
+                        return Err(malformed(
+                            "The CRL does not list the certificate as revoked",
+                        ));
+                    }
+                    match issuer_of(&revoked.id_cert_tbs.issuer) {
+                        Some(issuer) => revocation
+                            .crl
+                            .verify(issuer, revocation.crl.id_crl_tbs.this_update)?,
+                        None => return Err(malformed("The issuer of a certificate is unknown")),
+                    }
+                }
+                CustodyEvent::Statement(statement) => {
+                    if statement.federation_id != self.federation_id {
+                        return Err(malformed(&format!(
+                            "A statement was not made for {}",
+                            self.federation_id
+                        )));
+                    }
+                    let signer = match certs.get(&statement.signed_by) {
+                        Some(signer) => signer,
+                        None => {
+                            return Err(malformed(
+                                "A statement is signed by a certificate not issued before it",
+                            ))
+                        }
+                    };
+                    if !signer.valid_at(statement.made_at) {
+                        return Err(InvalidCert::InvalidValidity);
+                    }
+                    statement.verify(&signer.id_cert_tbs.subject_public_key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn bundle_payload<S: Signature, P: PublicKey<S>>(
+    federation_id: &FederationId,
+    exported_at: Timestamp,
+    exporter: &CertId,
+    events: &[CustodyEvent<S, P>],
+) -> Result<Vec<u8>, ConversionError> {
+    let mut payload = format!(
+        "{}\n{}\n{}\n{}\n",
+        CUSTODY_PAYLOAD_PREFIX,
+        federation_id,
+        exported_at.unix_seconds(),
+        exporter
+    )
+    .into_bytes();
+    for event in events {
+        write_field(&mut payload, &event.encode()?);
+    }
+    Ok(payload)
+}
+
+/// Appends `field` to `payload`, prefixed with its length as a big-endian `u32`, so that the
+/// boundaries between fields are unambiguous.
+fn write_field(payload: &mut Vec<u8>, field: &[u8]) {
+    payload.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    payload.extend_from_slice(field);
+}
+
+fn signature_bytes<S: Signature>(signature: &S) -> Result<Vec<u8>, ConversionError> {
+    Ok(signature.to_bitstring()?.raw_bytes().to_vec())
+}
+
+fn cert_id<S: Signature, P: PublicKey<S>>(id_cert: &IdCert<S, P>) -> Result<CertId, InvalidCert> {
+    id_cert.cert_id().map_err(|e| malformed(&e.to_string()))
+}
+
+/// Returns the value of the UID attribute of `name`, which holds the federation ID of an actor.
+pub(crate) fn uid(name: &Name) -> Option<String> {
+    name.0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|attribute| attribute.oid.to_string() == OID_RDN_UID)
+        .map(|attribute| String::from_utf8_lossy(attribute.value.value()).to_string())
+}
+
+fn malformed(message: &str) -> InvalidCert {
+    InvalidCert::InvalidProperties(ConstraintError::Malformed(Some(message.to_string())))
+}
@@ -0,0 +1,535 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::{BitString, Uint};
+use der::pem::LineEnding;
+use der::Decode;
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
+use x509_cert::name::Name;
+use x509_cert::request::CertReq;
+use x509_cert::time::Validity;
+use x509_cert::Certificate;
+
+use crate::encoding::encode_hex;
+use crate::errors::{ConstraintError, ConversionError, InvalidCert, PublicKeyError};
+use crate::key::{KeyFingerprint, PublicKey};
+use crate::registry::AlgorithmRegistry;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::Constrained;
+
+use super::capabilities::Capabilities;
+use super::certid::CertId;
+use super::claims::CustomClaims;
+use super::csrmeta::CsrMetadata;
+use super::fingerprint::{CertFingerprint, FingerprintDigest};
+use super::idcert::IdCert;
+use super::idcsr::IdCsr;
+use super::pem::{self, PemKind, PemLabels};
+use super::{PublicKeyInfo, Target};
+
+/// The raw bytes of a signature of an algorithm which is only known at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RawSignature {
+    bytes: Vec<u8>,
+}
+
+impl std::fmt::Display for RawSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", encode_hex(&self.bytes))
+    }
+}
+
+impl Signature for RawSignature {
+    type Signature = Vec<u8>;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.bytes
+    }
+
+    /// Raw signatures are never created by a private key, so this is only a placeholder. The
+    /// actual algorithm is stored alongside the signature.
+    fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::new_unwrap("0.0"),
+            parameters: None,
+        }
+    }
+
+    fn from_bytes(signature: &[u8]) -> Self {
+        Self {
+            bytes: signature.to_vec(),
+        }
+    }
+}
+
+impl SignatureBitStringEncoding for RawSignature {
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        BitString::from_bytes(&self.bytes)
+    }
+}
+
+/// The `SubjectPublicKeyInfo` of a key of an algorithm which is only known at runtime. Signatures
+/// are verified through an [AlgorithmRegistry] instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RawPublicKey {
+    public_key_info: PublicKeyInfo,
+}
+
+impl PublicKey<RawSignature> for RawPublicKey {
+    fn verify_signature(&self, _: &RawSignature, _: &[u8]) -> Result<(), PublicKeyError> {
+        Err(PublicKeyError::UnsupportedAlgorithm(
+            self.public_key_info.algorithm.oid,
+        ))
+    }
+
+    fn public_key_info(&self) -> PublicKeyInfo {
+        self.public_key_info.clone()
+    }
+
+    fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError> {
+        Ok(Self { public_key_info })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An [IdCert] whose signature algorithm and subject key algorithm are only known at runtime.
+///
+/// [IdCert] is generic over the [Signature] and [PublicKey] types of a backend, which forces every
+/// API handling certificates to be generic, too. A [DynIdCert] instead stores the raw
+/// `SubjectPublicKeyInfo` and signature, and verifies signatures using an [AlgorithmRegistry], so
+/// that certificates of different algorithms can be handled side by side, for example in a single
+/// collection. Use [DynIdCert::to_id_cert()] to obtain a typed [IdCert] once the algorithm is
+/// known.
+pub struct DynIdCert {
+    inner: IdCert<RawSignature, RawPublicKey>,
+}
+
+impl DynIdCert {
+    /// Creates a [DynIdCert] from a byte slice containing a DER encoded X.509 Certificate, and
+    /// verifies it using [DynIdCert::full_verify_actor()] or
+    /// [DynIdCert::full_verify_home_server()], depending on `target`.
+    pub fn from_der(
+        value: &[u8],
+        target: Target,
+        time: Timestamp,
+        registry: &AlgorithmRegistry,
+        home_server_public_key: &PublicKeyInfo,
+    ) -> Result<Self, InvalidCert> {
+        let cert = match DynIdCert::from_der_unchecked(value) {
+            Ok(cert) => cert,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        cert.full_verify(target, time, registry, home_server_public_key)?;
+        Ok(cert)
+    }
+
+    /// Creates an unchecked [DynIdCert] from a byte slice containing a DER encoded X.509
+    /// Certificate. The caller is responsible for verifying the correctness of this `DynIdCert`
+    /// before using it.
+    pub fn from_der_unchecked(value: &[u8]) -> Result<Self, ConversionError> {
+        Ok(Self {
+            inner: IdCert::from_der_unchecked(value)?,
+        })
+    }
+
+    /// Encode this type as DER, returning a byte vector.
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        self.inner.to_der()
+    }
+
+    /// Like [DynIdCert::from_der()], but for a PEM encoded X.509 Certificate.
+    pub fn from_pem(
+        pem: &str,
+        target: Target,
+        time: Timestamp,
+        registry: &AlgorithmRegistry,
+        home_server_public_key: &PublicKeyInfo,
+    ) -> Result<Self, InvalidCert> {
+        let cert = match pem::decode_expecting(pem, PemKind::Certificate, Some(target))
+            .and_then(|der| DynIdCert::from_der_unchecked(&der))
+        {
+            Ok(cert) => cert,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        cert.full_verify(target, time, registry, home_server_public_key)?;
+        Ok(cert)
+    }
+
+    /// Like [DynIdCert::from_der_unchecked()], but for a PEM encoded X.509 Certificate. Both the
+    /// standard and the target-specific PEM labels (see [PemLabels]) are accepted.
+    pub fn from_pem_unchecked(pem: &str) -> Result<Self, ConversionError> {
+        DynIdCert::from_der_unchecked(&pem::decode_expecting(pem, PemKind::Certificate, None)?)
+    }
+
+    /// Encode this type as PEM with the standard `CERTIFICATE` label, returning a string.
+    pub fn to_pem(&self, line_ending: LineEnding) -> Result<String, ConversionError> {
+        self.inner.to_pem(line_ending)
+    }
+
+    /// Encode this type as PEM, returning a string. See [IdCert::to_pem_with_labels()].
+    pub fn to_pem_with_labels(
+        &self,
+        line_ending: LineEnding,
+        labels: PemLabels,
+    ) -> Result<String, ConversionError> {
+        self.inner.to_pem_with_labels(line_ending, labels)
+    }
+
+    /// Converts this certificate into an [IdCert] of the given [Signature] and [PublicKey] types.
+    /// Fails, if the subject public key is not a key of type `P`.
+    pub fn to_id_cert<S: Signature, P: PublicKey<S>>(
+        &self,
+    ) -> Result<IdCert<S, P>, ConversionError> {
+        IdCert::try_from(Certificate::try_from(self.inner.clone())?)
+    }
+
+    /// Returns the serial number of the certificate.
+    pub fn serial_number(&self) -> &Uint {
+        &self.inner.id_cert_tbs.serial_number
+    }
+
+    /// Returns the algorithm the certificate was signed with.
+    pub fn signature_algorithm(&self) -> &AlgorithmIdentifierOwned {
+        &self.inner.id_cert_tbs.signature_algorithm
+    }
+
+    /// Returns the raw signature of the certificate.
+    pub fn signature(&self) -> &[u8] {
+        &self.inner.signature.bytes
+    }
+
+    /// Returns the issuer of the certificate.
+    pub fn issuer(&self) -> &Name {
+        &self.inner.id_cert_tbs.issuer
+    }
+
+    /// Returns the validity period of the certificate.
+    pub fn validity(&self) -> &Validity {
+        &self.inner.id_cert_tbs.validity
+    }
+
+    /// Returns the subject of the certificate.
+    pub fn subject(&self) -> &Name {
+        &self.inner.id_cert_tbs.subject
+    }
+
+    /// Returns the `SubjectPublicKeyInfo` of the subject public key.
+    pub fn subject_public_key(&self) -> &PublicKeyInfo {
+        &self.inner.id_cert_tbs.subject_public_key.public_key_info
+    }
+
+    /// Returns the [Capabilities] of the subject.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.inner.id_cert_tbs.capabilities
+    }
+
+    /// Returns the [CustomClaims] of the certificate.
+    pub fn claims(&self) -> &CustomClaims {
+        self.inner.id_cert_tbs.claims()
+    }
+
+    /// Returns the URL at which the issuer of this certificate publishes its certificate
+    /// revocation lists, if any.
+    pub fn crl_distribution_point(&self) -> Option<&str> {
+        self.inner.id_cert_tbs.crl_distribution_point()
+    }
+
+    /// Returns a byte vector containing the DER encoded `TBSCertificate`, over which the signature
+    /// of the certificate is created.
+    pub fn signature_data(&self) -> Result<Vec<u8>, ConversionError> {
+        self.inner.signature_data()
+    }
+
+    /// Returns the [CertId] of this certificate, which identifies it by its issuer and serial
+    /// number.
+    pub fn cert_id(&self) -> Result<CertId, ConversionError> {
+        self.inner.cert_id()
+    }
+
+    /// Returns the [CertFingerprint] of this certificate: the hash of its complete DER encoding,
+    /// computed using `digest`.
+    pub fn fingerprint(
+        &self,
+        digest: FingerprintDigest,
+    ) -> Result<CertFingerprint, ConversionError> {
+        self.inner.fingerprint(digest)
+    }
+
+    /// Returns the [KeyFingerprint] of the subject public key of this certificate.
+    pub fn key_fingerprint(&self) -> Result<KeyFingerprint, ConversionError> {
+        self.inner.key_fingerprint()
+    }
+
+    /// Returns `true`, if the certificate is valid at the given time. See [IdCert::valid_at()] for
+    /// the types `time` can be given as.
+    pub fn valid_at(&self, time: impl Into<Timestamp>) -> bool {
+        self.inner.valid_at(time)
+    }
+
+    /// Like [DynIdCert::valid_at()], but tolerates a clock skew of up to `tolerance`. See
+    /// [IdCert::valid_at_with_tolerance()].
+    pub fn valid_at_with_tolerance(&self, time: impl Into<Timestamp>, tolerance: Duration) -> bool {
+        self.inner.valid_at_with_tolerance(time, tolerance)
+    }
+
+    /// Verifies the signature of the certificate using the public key of its issuer, looking up
+    /// the signature algorithm in `registry`.
+    pub fn verify_signature(
+        &self,
+        registry: &AlgorithmRegistry,
+        issuer_public_key: &PublicKeyInfo,
+    ) -> Result<(), InvalidCert> {
+        let data = match self.signature_data() {
+            Ok(data) => data,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        Ok(registry.verify(
+            self.signature_algorithm(),
+            issuer_public_key,
+            self.signature(),
+            &data,
+        )?)
+    }
+
+    /// Verifies an actor certificate like [IdCert::full_verify_actor()], looking up the signature
+    /// algorithm in `registry`.
+    pub fn full_verify_actor(
+        &self,
+        time: Timestamp,
+        registry: &AlgorithmRegistry,
+        home_server_public_key: &PublicKeyInfo,
+    ) -> Result<(), InvalidCert> {
+        self.full_verify(Target::Actor, time, registry, home_server_public_key)
+    }
+
+    /// Verifies a self-signed home server certificate like [IdCert::full_verify_home_server()],
+    /// looking up the signature algorithm in `registry`.
+    pub fn full_verify_home_server(
+        &self,
+        time: Timestamp,
+        registry: &AlgorithmRegistry,
+    ) -> Result<(), InvalidCert> {
+        self.full_verify(
+            Target::HomeServer,
+            time,
+            registry,
+            self.subject_public_key(),
+        )
+    }
+
+    fn full_verify(
+        &self,
+        target: Target,
+        time: Timestamp,
+        registry: &AlgorithmRegistry,
+        issuer_public_key: &PublicKeyInfo,
+    ) -> Result<(), InvalidCert> {
+        if !self.valid_at(time) {
+            return Err(InvalidCert::InvalidValidity);
+        }
+        self.validate(Some(target))?;
+        log::trace!(
+            "[DynIdCert::full_verify()] verifying signature using {}",
+            self.signature_algorithm().oid
+        );
+        self.verify_signature(registry, issuer_public_key)
+    }
+}
+
+impl Constrained for DynIdCert {
+    fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
+        self.inner.validate(target)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> TryFrom<IdCert<S, P>> for DynIdCert {
+    type Error = ConversionError;
+
+    fn try_from(value: IdCert<S, P>) -> Result<Self, Self::Error> {
+        DynIdCert::try_from(Certificate::try_from(value)?)
+    }
+}
+
+impl TryFrom<Certificate> for DynIdCert {
+    type Error = ConversionError;
+
+    /// Tries to convert a [Certificate] into a [DynIdCert]. The caller is responsible for verifying
+    /// the correctness of the resulting `DynIdCert`.
+    fn try_from(value: Certificate) -> Result<Self, Self::Error> {
+        Ok(Self {
+            inner: IdCert::try_from(value)?,
+        })
+    }
+}
+
+impl TryFrom<DynIdCert> for Certificate {
+    type Error = ConversionError;
+
+    fn try_from(value: DynIdCert) -> Result<Self, Self::Error> {
+        Certificate::try_from(value.inner)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An [IdCsr] whose signature algorithm and subject key algorithm are only known at runtime. The
+/// signature of the CSR is verified using an [AlgorithmRegistry]. See [DynIdCert].
+pub struct DynIdCsr {
+    inner: IdCsr<RawSignature, RawPublicKey>,
+}
+
+impl DynIdCsr {
+    /// Creates a [DynIdCsr] from a byte slice containing a DER encoded PKCS #10 CSR, and verifies
+    /// it using [DynIdCsr::verify()].
+    pub fn from_der(
+        bytes: &[u8],
+        target: Option<Target>,
+        registry: &AlgorithmRegistry,
+    ) -> Result<Self, InvalidCert> {
+        let csr = match DynIdCsr::from_der_unchecked(bytes) {
+            Ok(csr) => csr,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        csr.verify(target, registry)?;
+        Ok(csr)
+    }
+
+    /// Creates an unchecked [DynIdCsr] from a byte slice containing a DER encoded PKCS #10 CSR.
+    /// The caller is responsible for verifying the `DynIdCsr` using [DynIdCsr::verify()] before
+    /// using it.
+    pub fn from_der_unchecked(bytes: &[u8]) -> Result<Self, ConversionError> {
+        Ok(Self {
+            inner: IdCsr::try_from(CertReq::from_der(bytes)?)?,
+        })
+    }
+
+    /// Encode this type as DER, returning a byte vector.
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        self.inner.to_der()
+    }
+
+    /// Like [DynIdCsr::from_der()], but for a PEM encoded PKCS #10 CSR.
+    pub fn from_pem(
+        pem: &str,
+        target: Option<Target>,
+        registry: &AlgorithmRegistry,
+    ) -> Result<Self, InvalidCert> {
+        let csr = match pem::decode_expecting(pem, PemKind::CertificateRequest, target)
+            .and_then(|der| DynIdCsr::from_der_unchecked(&der))
+        {
+            Ok(csr) => csr,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        csr.verify(target, registry)?;
+        Ok(csr)
+    }
+
+    /// Encode this type as PEM with the standard `CERTIFICATE REQUEST` label, returning a string.
+    pub fn to_pem(&self, line_ending: LineEnding) -> Result<String, ConversionError> {
+        self.inner.to_pem(line_ending)
+    }
+
+    /// Converts this CSR into an [IdCsr] of the given [Signature] and [PublicKey] types. Fails, if
+    /// the subject public key is not a key of type `P`.
+    pub fn to_id_csr<S: Signature, P: PublicKey<S>>(&self) -> Result<IdCsr<S, P>, ConversionError> {
+        IdCsr::try_from(CertReq::try_from(self.inner.clone())?)
+    }
+
+    /// Returns the subject of the CSR.
+    pub fn subject(&self) -> &Name {
+        &self.inner.inner_csr.subject
+    }
+
+    /// Returns the `SubjectPublicKeyInfo` of the subject public key.
+    pub fn subject_public_key(&self) -> &PublicKeyInfo {
+        &self.inner.inner_csr.subject_public_key.public_key_info
+    }
+
+    /// Returns the [Capabilities] requested by the subject.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.inner.inner_csr.capabilities
+    }
+
+    /// Returns the [CsrMetadata] of the CSR.
+    pub fn metadata(&self) -> &CsrMetadata {
+        &self.inner.inner_csr.metadata
+    }
+
+    /// Returns the algorithm the CSR was signed with.
+    pub fn signature_algorithm(&self) -> &AlgorithmIdentifierOwned {
+        &self.inner.signature_algorithm
+    }
+
+    /// Returns the raw signature of the CSR.
+    pub fn signature(&self) -> &[u8] {
+        &self.inner.signature.bytes
+    }
+
+    /// Returns a byte vector containing the DER encoded `CertificationRequestInfo`, over which the
+    /// signature of the CSR is created.
+    pub fn signature_data(&self) -> Result<Vec<u8>, ConversionError> {
+        self.inner.signature_data()
+    }
+
+    /// Checks the CSR like [IdCsr::validate()](Constrained::validate): the CSR must be
+    /// well-formed and up to polyproto specification for `target`, and it must be signed using
+    /// the subject public key. The signature algorithm is looked up in `registry`.
+    pub fn verify(
+        &self,
+        target: Option<Target>,
+        registry: &AlgorithmRegistry,
+    ) -> Result<(), InvalidCert> {
+        self.inner.inner_csr.validate(target)?;
+        let data = match self.signature_data() {
+            Ok(data) => data,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        log::trace!(
+            "[DynIdCsr::verify()] verifying signature using {}",
+            self.signature_algorithm().oid
+        );
+        Ok(registry.verify(
+            self.signature_algorithm(),
+            self.subject_public_key(),
+            self.signature(),
+            &data,
+        )?)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> TryFrom<IdCsr<S, P>> for DynIdCsr {
+    type Error = ConversionError;
+
+    fn try_from(value: IdCsr<S, P>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            inner: IdCsr::try_from(CertReq::try_from(value)?)?,
+        })
+    }
+}
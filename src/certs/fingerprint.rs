@@ -0,0 +1,117 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::encoding::{decode_hex, encode_base64, encode_hex};
+use crate::errors::InvalidInput;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// The hash function a [CertFingerprint] is computed with.
+pub enum FingerprintDigest {
+    #[default]
+    /// SHA-256, producing 32 byte fingerprints.
+    Sha256,
+    /// SHA-512, producing 64 byte fingerprints.
+    Sha512,
+}
+
+impl FingerprintDigest {
+    /// Returns the length of the fingerprints produced by this digest, in bytes.
+    pub fn output_length(&self) -> usize {
+        match self {
+            FingerprintDigest::Sha256 => 32,
+            FingerprintDigest::Sha512 => 64,
+        }
+    }
+
+    /// Hashes `data` using this digest.
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            FingerprintDigest::Sha256 => Sha256::digest(data).to_vec(),
+            FingerprintDigest::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The fingerprint of a certificate: the hash of its complete DER encoding, as returned by
+/// [IdCert::fingerprint()](super::idcert::IdCert::fingerprint). Unlike a
+/// [KeyFingerprint](crate::key::KeyFingerprint), which identifies a key, a [CertFingerprint]
+/// changes whenever any part of the certificate changes.
+///
+/// [Display](std::fmt::Display) and [FromStr](std::str::FromStr) use the lowercase hex encoding.
+/// Parsing accepts upper- and lowercase digits, optionally separated by colons, and infers the
+/// [FingerprintDigest] from the length of the fingerprint.
+pub struct CertFingerprint {
+    digest: FingerprintDigest,
+    bytes: Vec<u8>,
+}
+
+impl CertFingerprint {
+    /// Computes the fingerprint of the DER encoded certificate `der` using `digest`.
+    pub fn from_der(der: &[u8], digest: FingerprintDigest) -> Self {
+        Self {
+            digest,
+            bytes: digest.digest(der),
+        }
+    }
+
+    /// Returns the [FingerprintDigest] the fingerprint was computed with.
+    pub fn digest(&self) -> FingerprintDigest {
+        self.digest
+    }
+
+    /// Returns the raw hash.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the fingerprint as a lowercase hex string, e.g. `3a4f...`.
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.bytes)
+    }
+
+    /// Returns the fingerprint as uppercase hex bytes separated by colons, e.g. `3A:4F:...`, the
+    /// format most certificate viewers display fingerprints in.
+    pub fn to_colon_hex(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Returns the fingerprint as a padded base64 string.
+    pub fn to_base64(&self) -> String {
+        encode_base64(&self.bytes)
+    }
+}
+
+impl std::fmt::Display for CertFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl std::str::FromStr for CertFingerprint {
+    type Err = InvalidInput;
+
+    /// Parses a hex or colon-hex encoded fingerprint of a SHA-256 or SHA-512 digest.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_hex(&s.replace(':', ""))?;
+        let digest = match bytes.len() {
+            32 => FingerprintDigest::Sha256,
+            64 => FingerprintDigest::Sha512,
+            length => {
+                return Err(InvalidInput::Length {
+                    min_length: 32,
+                    max_length: 64,
+                    actual_length: length.to_string(),
+                })
+            }
+        };
+        Ok(Self { digest, bytes })
+    }
+}
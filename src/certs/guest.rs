@@ -0,0 +1,213 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::Uint;
+use x509_cert::name::{Name, RelativeDistinguishedName};
+use x509_cert::time::Validity;
+
+use crate::errors::{
+    ConstraintError, ConversionError, ERR_MSG_GUEST_CAPABILITIES, ERR_MSG_GUEST_MISSING_MARKER,
+};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::{Timestamp, ValidityExt};
+use crate::{Constrained, OID_RDN_ORGANIZATIONAL_UNIT};
+
+use super::capabilities::Capabilities;
+use super::claims::CustomClaims;
+use super::idcert::IdCert;
+use super::idcerttbs::IdCertTbs;
+use super::idcsr::IdCsr;
+use super::san::SubjectAltNames;
+use super::Target;
+
+/// The value of the `organizationalUnit` RDN which marks the subject of a certificate as a guest.
+pub const GUEST_ORGANIZATIONAL_UNIT: &str = "polyproto-guest";
+/// The lifetime of a guest certificate, if none is specified when building it.
+pub const DEFAULT_GUEST_LIFETIME: Duration = Duration::from_secs(10 * 60);
+/// The longest lifetime a guest certificate may have under the default [GuestProfile].
+pub const MAX_GUEST_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Returns `true`, if the [Name] carries the guest marker, i.e. an `organizationalUnit` RDN with
+/// the value [GUEST_ORGANIZATIONAL_UNIT].
+pub fn is_guest_name(name: &Name) -> bool {
+    name.0.iter().any(|rdn| {
+        rdn.0.iter().any(|item| {
+            item.oid.to_string().as_str() == OID_RDN_ORGANIZATIONAL_UNIT
+                && item.value.value() == GUEST_ORGANIZATIONAL_UNIT.as_bytes()
+        })
+    })
+}
+
+#[derive(Debug, Clone)]
+/// Builder for short-lived guest actor [IdCert]s.
+///
+/// Guest certificates allow "join as guest" flows without registering a regular actor. They differ
+/// from regular actor certificates in that
+///
+/// - the subject is marked as a guest using an `organizationalUnit` RDN with the value
+///   [GUEST_ORGANIZATIONAL_UNIT], which is added by the builder if the [IdCsr] does not contain it,
+/// - their validity period is measured in minutes, defaulting to [DEFAULT_GUEST_LIFETIME], and
+/// - their capabilities are always [Capabilities::default_guest()], regardless of the capabilities
+///   requested in the [IdCsr].
+///
+/// Certificates received from other parties should be checked using a [GuestProfile].
+pub struct GuestCertBuilder<S: Signature, P: PublicKey<S>> {
+    id_csr: IdCsr<S, P>,
+    serial_number: Uint,
+    issuer: Name,
+    not_before: Option<Timestamp>,
+    lifetime: Duration,
+}
+
+impl<S: Signature, P: PublicKey<S>> GuestCertBuilder<S, P> {
+    /// Creates a new [GuestCertBuilder] for the given [IdCsr], serial number and issuer.
+    pub fn new(id_csr: IdCsr<S, P>, serial_number: Uint, issuer: Name) -> Self {
+        Self {
+            id_csr,
+            serial_number,
+            issuer,
+            not_before: None,
+            lifetime: DEFAULT_GUEST_LIFETIME,
+        }
+    }
+
+    /// Sets the start of the validity period. Defaults to the current time at the moment
+    /// [Self::build()] is called.
+    pub fn not_before(mut self, not_before: Timestamp) -> Self {
+        self.not_before = Some(not_before);
+        self
+    }
+
+    /// Sets the lifetime of the certificate. Must not exceed [MAX_GUEST_LIFETIME].
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Signs and returns the guest certificate. Fails, if the [IdCsr] is not a valid actor CSR, if
+    /// the lifetime is zero or exceeds [MAX_GUEST_LIFETIME], or if the resulting certificate does
+    /// not pass validation using the default [GuestProfile].
+    pub fn build(
+        self,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+    ) -> Result<IdCert<S, P>, ConversionError> {
+        log::trace!("[GuestCertBuilder::build()] creating guest certificate");
+        self.id_csr.validate(Some(Target::Actor))?;
+        let not_before = self.not_before.unwrap_or_else(Timestamp::now);
+        let validity = Validity::starting_at(not_before, self.lifetime)?;
+        let mut subject = self.id_csr.inner_csr.subject;
+        if !is_guest_name(&subject) {
+            subject.0.push(RelativeDistinguishedName::from_str(&format!(
+                "OU={}",
+                GUEST_ORGANIZATIONAL_UNIT
+            ))?);
+        }
+        let id_cert_tbs = IdCertTbs::<S, P> {
+            serial_number: self.serial_number,
+            signature_algorithm: signing_key.algorithm_identifier(),
+            issuer: self.issuer,
+            validity,
+            subject,
+            subject_public_key: self.id_csr.inner_csr.subject_public_key,
+            capabilities: Capabilities::default_guest(),
+            claims: CustomClaims::new(),
+            crl_distribution_point: None,
+            subject_key_identifier: None,
+            authority_key_identifier: None,
+            subject_alt_names: SubjectAltNames::new(),
+            s: std::marker::PhantomData,
+        }
+        .with_key_identifiers(signing_key.pubkey());
+        GuestProfile::default().validate(&id_cert_tbs)?;
+        let signature = signing_key.sign(&id_cert_tbs.to_der()?);
+        Ok(IdCert {
+            id_cert_tbs: id_cert_tbs.into(),
+            signature,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Validation profile for guest certificates.
+///
+/// A certificate passes this profile, if it is a valid actor certificate, carries the guest marker
+/// (see [is_guest_name()]), has a validity period of at most `max_lifetime` and holds no
+/// capabilities beyond [Capabilities::default_guest()].
+pub struct GuestProfile {
+    /// The longest validity period a guest certificate may have.
+    pub max_lifetime: Duration,
+}
+
+impl Default for GuestProfile {
+    fn default() -> Self {
+        Self {
+            max_lifetime: MAX_GUEST_LIFETIME,
+        }
+    }
+}
+
+impl GuestProfile {
+    /// Validates an [IdCertTbs] against this profile.
+    pub fn validate<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCertTbs<S, P>,
+    ) -> Result<(), ConstraintError> {
+        cert.validate(Some(Target::Actor))?;
+        if !is_guest_name(&cert.subject) {
+            return Err(ConstraintError::Malformed(Some(
+                ERR_MSG_GUEST_MISSING_MARKER.to_string(),
+            )));
+        }
+        if cert.capabilities != Capabilities::default_guest() {
+            return Err(ConstraintError::Malformed(Some(
+                ERR_MSG_GUEST_CAPABILITIES.to_string(),
+            )));
+        }
+        let not_before = cert.validity.not_before.to_unix_duration();
+        let not_after = cert.validity.not_after.to_unix_duration();
+        let lifetime = not_after.saturating_sub(not_before);
+        if lifetime.is_zero() || lifetime > self.max_lifetime {
+            return Err(ConstraintError::OutOfBounds {
+                lower: 1,
+                upper: self.max_lifetime.as_secs().min(i32::MAX as u64) as i32,
+                actual: lifetime.as_secs().to_string(),
+                reason: "The validity period of a guest certificate is out of bounds".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Validates an [IdCert] against this profile. Does not verify the signature of the
+    /// certificate.
+    pub fn validate_cert<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+    ) -> Result<(), ConstraintError> {
+        self.validate(&cert.id_cert_tbs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn guest_marker() {
+        let guest = Name::from_str(
+            "CN=flori,OU=polyproto-guest,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+        )
+        .unwrap();
+        assert!(is_guest_name(&guest));
+        let regular = Name::from_str(
+            "CN=flori,OU=programmer,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+        )
+        .unwrap();
+        assert!(!is_guest_name(&regular));
+    }
+}
@@ -2,21 +2,37 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::time::Duration;
+
 use der::asn1::Uint;
 use der::pem::LineEnding;
-use der::{Decode, DecodePem, Encode, EncodePem};
+use der::{Decode, Encode, Header, Reader, SliceReader, Tag};
 use x509_cert::name::Name;
 use x509_cert::time::Validity;
 use x509_cert::Certificate;
 
-use crate::errors::{ConstraintError, ConversionError, InvalidCert, ERR_CERTIFICATE_TO_DER_ERROR};
-use crate::key::{PrivateKey, PublicKey};
+use crate::encoding::check_canonical_der;
+use crate::errors::{
+    BundleError, ConstraintError, ConversionError, InvalidCert, InvalidInput,
+    ERR_CERTIFICATE_TO_DER_ERROR,
+};
+use crate::key::{AsyncPrivateKey, KeyFingerprint, PrivateKey, PublicKey};
 use crate::signature::Signature;
+use crate::timestamp::Timestamp;
 use crate::Constrained;
 
-use super::idcerttbs::IdCertTbs;
+use super::certid::CertId;
+use super::claims::CustomClaims;
+use super::domain_of;
+use super::fingerprint::{CertFingerprint, FingerprintDigest};
+use super::idcerttbs::{CachedIdCertTbs, IdCertTbs};
 use super::idcsr::IdCsr;
-use super::Target;
+use super::limits::SizeLimits;
+use super::pem::{self, PemKind, PemLabel, PemLabels};
+use super::policy::ValidationPolicy;
+use super::san::SubjectAltNames;
+use super::security::{SecurityFacts, SecurityLevel, SecurityPolicy, StandardSecurityPolicy};
+use super::{SessionId, Target};
 
 /// A signed polyproto ID-Cert, consisting of the actual certificate, the CA-generated signature and
 /// metadata about that signature.
@@ -40,8 +56,8 @@ use super::Target;
 /// if the certificate is valid at a given time, you can use the [valid_at()] method.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IdCert<S: Signature, P: PublicKey<S>> {
-    /// Inner TBS (To be signed) certificate
-    pub id_cert_tbs: IdCertTbs<S, P>,
+    /// Inner TBS (To be signed) certificate, together with its cached DER encoding
+    pub id_cert_tbs: CachedIdCertTbs<S, P>,
     /// Signature for the TBS certificate
     pub signature: S,
 }
@@ -74,20 +90,18 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
         issuer: Name,
         validity: Validity,
     ) -> Result<Self, ConversionError> {
-        let signature_algorithm = signing_key.algorithm_identifier();
-        let id_cert_tbs = IdCertTbs::<S, P> {
+        let id_cert_tbs = tbs_from_csr(
+            id_csr,
+            signing_key.pubkey(),
+            signing_key.algorithm_identifier(),
             serial_number,
-            signature_algorithm,
             issuer,
             validity,
-            subject: id_csr.inner_csr.subject,
-            subject_public_key: id_csr.inner_csr.subject_public_key,
-            capabilities: id_csr.inner_csr.capabilities,
-            s: std::marker::PhantomData,
-        };
-        let signature = signing_key.sign(&id_cert_tbs.clone().to_der()?);
+        );
+        let der = id_cert_tbs.to_der()?;
+        let signature = signing_key.sign(&der);
         let cert = IdCert {
-            id_cert_tbs,
+            id_cert_tbs: CachedIdCertTbs::with_der(id_cert_tbs, &der),
             signature,
         };
         cert.validate(Some(Target::HomeServer))?;
@@ -129,20 +143,19 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
             "[IdCert::from_actor_csr()] Subject: {}",
             id_csr.inner_csr.subject.to_string()
         );
-        let id_cert_tbs = IdCertTbs::<S, P> {
-            serial_number,
+        let id_cert_tbs = tbs_from_csr(
+            id_csr,
+            signing_key.pubkey(),
             signature_algorithm,
+            serial_number,
             issuer,
             validity,
-            subject: id_csr.inner_csr.subject,
-            subject_public_key: id_csr.inner_csr.subject_public_key,
-            capabilities: id_csr.inner_csr.capabilities,
-            s: std::marker::PhantomData,
-        };
+        );
         log::trace!("[IdCert::from_actor_csr()] creating Signature");
-        let signature = signing_key.sign(&id_cert_tbs.clone().to_der()?);
+        let der = id_cert_tbs.to_der()?;
+        let signature = signing_key.sign(&der);
         let cert = IdCert {
-            id_cert_tbs,
+            id_cert_tbs: CachedIdCertTbs::with_der(id_cert_tbs, &der),
             signature,
         };
         log::trace!(
@@ -153,31 +166,165 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
         Ok(cert)
     }
 
+    /// Create a new [IdCert] by signing an [IdCertTbs], for example one created using
+    /// [IdCertTbs::from_actor_csr()] and amended using [IdCertTbs::with_claims()]. The signature
+    /// algorithm and key identifiers of the `IdCertTbs` are set to match `signing_key`. Returns an
+    /// error, if the resulting certificate does not pass [Constrained] verification for `target`.
+    pub fn from_tbs(
+        mut id_cert_tbs: IdCertTbs<S, P>,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+        target: Target,
+    ) -> Result<Self, ConversionError> {
+        log::trace!(
+            "[IdCert::from_tbs()] signing IdCertTbs for target {:?}",
+            target
+        );
+        id_cert_tbs.signature_algorithm = signing_key.algorithm_identifier();
+        let id_cert_tbs = id_cert_tbs.with_key_identifiers(signing_key.pubkey());
+        let der = id_cert_tbs.to_der()?;
+        let signature = signing_key.sign(&der);
+        let cert = IdCert {
+            id_cert_tbs: CachedIdCertTbs::with_der(id_cert_tbs, &der),
+            signature,
+        };
+        cert.validate(Some(target))?;
+        Ok(cert)
+    }
+
+    /// Like [IdCert::from_ca_csr()], but awaits the signing operation of an [AsyncPrivateKey],
+    /// such as a remote signer backed by a KMS or HSM. Fails with
+    /// [ConversionError::SignerError], if the signer could not produce a signature.
+    pub async fn from_ca_csr_async(
+        id_csr: IdCsr<S, P>,
+        signing_key: &impl AsyncPrivateKey<S, PublicKey = P>,
+        serial_number: Uint,
+        issuer: Name,
+        validity: Validity,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[IdCert::from_ca_csr_async()] creating home server certificate");
+        let id_cert_tbs = tbs_from_csr(
+            id_csr,
+            signing_key.pubkey(),
+            signing_key.algorithm_identifier(),
+            serial_number,
+            issuer,
+            validity,
+        );
+        let der = id_cert_tbs.to_der()?;
+        let signature = signing_key.sign(&der).await?;
+        let cert = IdCert {
+            id_cert_tbs: CachedIdCertTbs::with_der(id_cert_tbs, &der),
+            signature,
+        };
+        cert.validate(Some(Target::HomeServer))?;
+        Ok(cert)
+    }
+
+    /// Like [IdCert::from_actor_csr()], but awaits the signing operation of an
+    /// [AsyncPrivateKey], such as a remote signer backed by a KMS or HSM. Fails with
+    /// [ConversionError::SignerError], if the signer could not produce a signature.
+    pub async fn from_actor_csr_async(
+        id_csr: IdCsr<S, P>,
+        signing_key: &impl AsyncPrivateKey<S, PublicKey = P>,
+        serial_number: Uint,
+        issuer: Name,
+        validity: Validity,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[IdCert::from_actor_csr_async()] creating actor certificate");
+        let id_cert_tbs = tbs_from_csr(
+            id_csr,
+            signing_key.pubkey(),
+            signing_key.algorithm_identifier(),
+            serial_number,
+            issuer,
+            validity,
+        );
+        let der = id_cert_tbs.to_der()?;
+        let signature = signing_key.sign(&der).await?;
+        let cert = IdCert {
+            id_cert_tbs: CachedIdCertTbs::with_der(id_cert_tbs, &der),
+            signature,
+        };
+        cert.validate(Some(Target::Actor))?;
+        Ok(cert)
+    }
+
     /// Create an [IdCert] from a byte slice containing a DER encoded X.509 Certificate.
     /// The resulting `IdCert` has the same validity guarantees as when using [IdCert::full_verify_actor()]
     /// or [IdCert::full_verify_home_server()].
+    ///
+    /// `target` can be given as a [Target] or an `Option<Target>`. If it is `None`, the target is
+    /// inferred using [IdCert::infer_target()], and the certificate is validated against and
+    /// verified for the inferred target. `home_server_public_key` is not used for home server certificates, which
+    /// are self-signed.
     pub fn from_der(
         value: &[u8],
-        target: Target,
-        time: u64,
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
         home_server_public_key: &P,
     ) -> Result<Self, InvalidCert> {
-        let cert = match IdCert::from_der_unchecked(value) {
-            Ok(cert) => cert,
+        let cert = IdCert::from_der_limited(value, &SizeLimits::default())?;
+        cert.verify_decoded(target.into(), time, home_server_public_key)?;
+        Ok(cert)
+    }
+
+    /// Checks `value` against `size_limits` and decodes it, without validating the certificate.
+    fn from_der_limited(value: &[u8], size_limits: &SizeLimits) -> Result<Self, InvalidCert> {
+        match size_limits
+            .check_certificate(value)
+            .and_then(|_| IdCert::from_der_unchecked(value))
+        {
+            Ok(cert) => Ok(cert),
+            Err(ConversionError::SizeLimit(violation)) => Err(InvalidCert::SizeLimit(violation)),
+            Err(e) => Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                Some(e.to_string()),
+            ))),
+        }
+    }
+
+    /// Verifies a decoded certificate for `target`. If `target` is `None`, the certificate is
+    /// validated against and verified for the inferred target instead.
+    fn verify_decoded(
+        &self,
+        target: Option<Target>,
+        time: Timestamp,
+        home_server_public_key: &P,
+    ) -> Result<(), InvalidCert> {
+        let target = match target {
+            Some(target) => target,
+            None => {
+                let target = self.infer_target()?;
+                self.validate(Some(target))?;
+                target
+            }
+        };
+        match target {
+            Target::Actor => self.full_verify_actor(time, home_server_public_key),
+            Target::HomeServer => self.full_verify_home_server(time),
+        }
+    }
+
+    /// Like [IdCert::from_der()], but validates the certificate using [IdCert::validate_with()]
+    /// and checks its encoding and validity period as configured by `policy`.
+    pub fn from_der_with(
+        value: &[u8],
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
+        home_server_public_key: &P,
+        policy: &ValidationPolicy,
+    ) -> Result<Self, InvalidCert> {
+        policy.check_der_input(value)?;
+        let cert = IdCert::from_der_limited(value, &policy.size_limits)?;
+        let encoded = match cert.encode_uncached() {
+            Ok(encoded) => encoded,
             Err(e) => {
                 return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
                     Some(e.to_string()),
                 )))
             }
         };
-        match target {
-            Target::Actor => {
-                cert.full_verify_actor(time, home_server_public_key)?;
-            }
-            Target::HomeServer => {
-                cert.full_verify_home_server(time)?;
-            }
-        }
+        policy.check_der(value, &encoded)?;
+        cert.full_verify_with(target, time, home_server_public_key, policy)?;
         Ok(cert)
     }
 
@@ -185,74 +332,354 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
     /// The caller is responsible for verifying the correctness of this `IdCert` using
     /// the [Constrained] trait before using it.
     pub fn from_der_unchecked(value: &[u8]) -> Result<Self, ConversionError> {
-        let cert = IdCert::try_from(Certificate::from_der(value)?)?;
+        let mut cert = IdCert::try_from(Certificate::from_der(value)?)?;
+        // Certificate::from_der() has checked that `value` is a single SEQUENCE, whose first
+        // element is the IdCertTbs.
+        let mut reader = SliceReader::new(value)?;
+        Header::decode(&mut reader)?;
+        cert.id_cert_tbs =
+            CachedIdCertTbs::with_der(cert.id_cert_tbs.into_inner(), reader.tlv_bytes()?);
         Ok(cert)
     }
 
     /// Encode this type as DER, returning a byte vector.
-    pub fn to_der(self) -> Result<Vec<u8>, ConversionError> {
-        Ok(Certificate::try_from(self)?.to_der()?)
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        // Equivalent to encoding the corresponding Certificate, but reuses the cached encoding of
+        // the IdCertTbs.
+        self.encode_with_tbs(self.id_cert_tbs.encoded_der()?.to_vec())
+    }
+
+    /// Encodes this certificate as DER, without using the cached encoding of the [IdCertTbs].
+    fn encode_uncached(&self) -> Result<Vec<u8>, ConversionError> {
+        self.encode_with_tbs(self.id_cert_tbs.to_der()?)
+    }
+
+    /// Encodes this certificate as DER, given `tbs`, the encoding of its [IdCertTbs].
+    fn encode_with_tbs(&self, tbs: Vec<u8>) -> Result<Vec<u8>, ConversionError> {
+        let mut contents = tbs;
+        self.id_cert_tbs
+            .signature_algorithm
+            .encode_to_vec(&mut contents)?;
+        self.signature
+            .to_bitstring()?
+            .encode_to_vec(&mut contents)?;
+        let mut der = Vec::with_capacity(contents.len() + 4);
+        Header::new(Tag::Sequence, contents.len())?.encode_to_vec(&mut der)?;
+        der.extend(contents);
+        Ok(der)
     }
 
     /// Create an [IdCert] from a byte slice containing a PEM encoded X.509 Certificate.
     /// The resulting `IdCert` has the same validity guarantees as when using [IdCert::full_verify_actor()]
     /// or [IdCert::full_verify_home_server()].
+    ///
+    /// Like with [IdCert::from_der()], the target is inferred if `target` is `None`. Otherwise,
+    /// a target-specific PEM label must match `target`.
     pub fn from_pem(
         pem: &str,
-        target: Target,
-        time: u64,
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
         home_server_public_key: &P,
     ) -> Result<Self, InvalidCert> {
-        let cert = match IdCert::from_pem_unchecked(pem) {
-            Ok(cert) => cert,
+        let target = target.into();
+        let der = match pem::decode_expecting(pem, PemKind::Certificate, target) {
+            Ok(der) => der,
             Err(e) => {
                 return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
                     Some(e.to_string()),
                 )))
             }
         };
-        match target {
-            Target::Actor => {
-                cert.full_verify_actor(time, home_server_public_key)?;
-            }
-            Target::HomeServer => {
-                cert.full_verify_home_server(time)?;
-            }
-        }
+        let cert = IdCert::from_der_limited(&der, &SizeLimits::default())?;
+        cert.verify_decoded(target, time, home_server_public_key)?;
         Ok(cert)
     }
 
+    /// Like [IdCert::from_pem()], but decodes the certificate using [IdCert::from_der_with()].
+    pub fn from_pem_with(
+        pem: &str,
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
+        home_server_public_key: &P,
+        policy: &ValidationPolicy,
+    ) -> Result<Self, InvalidCert> {
+        let target = target.into();
+        let der = match pem::decode_expecting(pem, PemKind::Certificate, target) {
+            Ok(der) => der,
+            Err(e) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some(e.to_string()),
+                )))
+            }
+        };
+        IdCert::from_der_with(&der, target, time, home_server_public_key, policy)
+    }
+
     /// Create an unchecked [IdCert] from a byte slice containing a PEM encoded X.509 Certificate.
     /// The caller is responsible for verifying the correctness of this `IdCert` using
     /// either [IdCert::full_verify_actor()] or [IdCert::full_verify_home_server()] before using it.
+    ///
+    /// Both the standard and the target-specific PEM labels (see [PemLabels]) are accepted.
     pub fn from_pem_unchecked(pem: &str) -> Result<Self, ConversionError> {
-        let cert = IdCert::try_from(Certificate::from_pem(pem)?)?;
+        IdCert::from_der_unchecked(&pem::decode_expecting(pem, PemKind::Certificate, None)?)
+    }
+
+    /// Decodes a bundle of concatenated PEM encoded certificates, such as a certificate chain or
+    /// a CA bundle file, returning the certificates in the order they appear in. Text outside of
+    /// the PEM encapsulation boundaries is ignored, as permitted by RFC 7468.
+    ///
+    /// Like with [load_any()](pem::load_any), the [Target] of each certificate is detected from its
+    /// capabilities and the certificate is validated against the polyproto constraints for it, but
+    /// signatures are not verified. Use [IdCertChain](super::chain::IdCertChain) to verify a
+    /// certificate chain. Fails with the position of the first entry which is malformed, is not a
+    /// certificate or is invalid.
+    pub fn many_from_pem(bundle: &str) -> Result<Vec<Self>, BundleError> {
+        let mut certs = Vec::new();
+        for (index, document) in (pem::Documents { rest: bundle }).enumerate() {
+            let cert = document
+                .map_err(ConversionError::from)
+                .and_then(|document| {
+                    let (label, der) = pem::decode(document)?;
+                    if label.kind != PemKind::Certificate {
+                        return Err(InvalidInput::Malformed(format!(
+                            "Expected a PEM document containing a certificate, found \"{}\"",
+                            label
+                        ))
+                        .into());
+                    }
+                    IdCert::from_bundle_entry(&der, label.target)
+                })
+                .map_err(|error| BundleError::InvalidEntry { index, error })?;
+            certs.push(cert);
+        }
+        log::trace!(
+            "[IdCert::many_from_pem()] Decoded {} certificates",
+            certs.len()
+        );
+        Ok(certs)
+    }
+
+    /// Decodes a bundle of concatenated DER encoded certificates, returning the certificates in
+    /// the order they appear in. The certificates are validated like in [IdCert::many_from_pem()].
+    /// Fails with the position of the first entry which is malformed or invalid.
+    pub fn many_from_der(bundle: &[u8]) -> Result<Vec<Self>, BundleError> {
+        let mut certs = Vec::new();
+        let mut reader = match SliceReader::new(bundle) {
+            Ok(reader) => reader,
+            Err(e) => {
+                return Err(BundleError::InvalidEntry {
+                    index: 0,
+                    error: e.into(),
+                })
+            }
+        };
+        while !reader.is_finished() {
+            let index = certs.len();
+            let cert = reader
+                .tlv_bytes()
+                .map_err(ConversionError::from)
+                .and_then(|der| IdCert::from_bundle_entry(der, None))
+                .map_err(|error| BundleError::InvalidEntry { index, error })?;
+            certs.push(cert);
+        }
+        log::trace!(
+            "[IdCert::many_from_der()] Decoded {} certificates",
+            certs.len()
+        );
+        Ok(certs)
+    }
+
+    /// Decodes and validates a single certificate of a bundle. If `hint` is given, the detected
+    /// [Target] of the certificate must match it.
+    fn from_bundle_entry(der: &[u8], hint: Option<Target>) -> Result<Self, ConversionError> {
+        SizeLimits::default().check_certificate(der)?;
+        let cert = IdCert::from_der_unchecked(der)?;
+        let target = Target::detect(&cert.id_cert_tbs.capabilities);
+        if hint.is_some_and(|hint| hint != target) {
+            return Err(InvalidInput::Malformed(format!(
+                "The PEM label does not match the detected target {:?}",
+                target
+            ))
+            .into());
+        }
+        cert.validate(Some(target))?;
         Ok(cert)
     }
 
-    /// Encode this type as PEM, returning a string.
-    pub fn to_pem(self, line_ending: LineEnding) -> Result<String, ConversionError> {
-        Ok(Certificate::try_from(self)?.to_pem(line_ending)?)
+    /// Encode this type as PEM with the standard `CERTIFICATE` label, returning a string.
+    pub fn to_pem(&self, line_ending: LineEnding) -> Result<String, ConversionError> {
+        self.to_pem_with_labels(line_ending, PemLabels::Standard)
+    }
+
+    /// Encode this type as PEM, returning a string. With [PemLabels::TargetSpecific], the label
+    /// names the [Target] detected from the capabilities of the certificate, e.g.
+    /// `POLYPROTO HOME SERVER CERTIFICATE`.
+    pub fn to_pem_with_labels(
+        &self,
+        line_ending: LineEnding,
+        labels: PemLabels,
+    ) -> Result<String, ConversionError> {
+        let label = PemLabel::for_target(
+            PemKind::Certificate,
+            Target::detect(&self.id_cert_tbs.capabilities),
+            labels,
+        );
+        pem::encode(&self.to_der()?, label, line_ending)
     }
 
     /// Returns a byte vector containing the DER encoded IdCertTbs. This data is encoded
     /// in the signature field of the certificate, and can be used to verify the signature.
     ///
-    /// This is a shorthand for `self.id_cert_tbs.clone().to_der()`, since intuitively, one might
+    /// This is a shorthand for `self.id_cert_tbs.to_der()`, since intuitively, one might
     /// try to verify the signature of the certificate by using `self.to_der()`, which will result
-    /// in an error.
+    /// in an error. Use [IdCert::verify_signature()] to verify the signature directly.
     pub fn signature_data(&self) -> Result<Vec<u8>, ConversionError> {
-        self.id_cert_tbs.clone().to_der()
+        Ok(self.id_cert_tbs.encoded_der()?.to_vec())
+    }
+
+    /// Checks that `der`, the bytes this certificate was decoded from, are its canonical encoding,
+    /// meaning that encoding the certificate again reproduces them byte for byte.
+    ///
+    /// Signatures of decoded certificates are verified over the transmitted bytes of the
+    /// [IdCertTbs], but a certificate encoded again after one of its fields was changed is
+    /// re-encoded. Checking that the transmitted bytes are canonical ensures that both encodings
+    /// agree. Fails with [DerViolation::NonCanonical], naming the offset of the first differing
+    /// byte, if they do not.
+    ///
+    /// [DerViolation::NonCanonical]: crate::errors::DerViolation::NonCanonical
+    pub fn check_canonical(&self, der: &[u8]) -> Result<(), ConversionError> {
+        Ok(check_canonical_der(der, &self.encode_uncached()?)?)
+    }
+
+    /// Whether `der`, the bytes this certificate was decoded from, are its canonical encoding. See
+    /// [IdCert::check_canonical()].
+    pub fn is_canonical(&self, der: &[u8]) -> bool {
+        self.check_canonical(der).is_ok()
+    }
+
+    /// Returns the [CertId] of this certificate, which identifies it by its issuer and serial
+    /// number.
+    pub fn cert_id(&self) -> Result<CertId, ConversionError> {
+        self.id_cert_tbs.cert_id()
+    }
+
+    /// Returns the [CertFingerprint] of this certificate: the hash of its complete DER encoding,
+    /// computed using `digest`. Use [CertFingerprint::to_hex()], [CertFingerprint::to_colon_hex()]
+    /// or [CertFingerprint::to_base64()] to display it.
+    pub fn fingerprint(
+        &self,
+        digest: FingerprintDigest,
+    ) -> Result<CertFingerprint, ConversionError> {
+        Ok(CertFingerprint::from_der(&self.to_der()?, digest))
+    }
+
+    /// Returns the [KeyFingerprint] of the subject public key of this certificate.
+    pub fn key_fingerprint(&self) -> Result<KeyFingerprint, ConversionError> {
+        self.id_cert_tbs.subject_public_key.fingerprint()
+    }
+
+    /// Returns the RFC 7469 SPKI pin of the subject public key of this certificate: the padded
+    /// base64 encoding of the SHA-256 hash of its DER encoded `SubjectPublicKeyInfo`, as used in
+    /// `pin-sha256` directives and [PinSet](super::pinning::PinSet)s.
+    pub fn spki_pin_sha256(&self) -> Result<String, ConversionError> {
+        Ok(self.key_fingerprint()?.to_pin_sha256())
+    }
+
+    /// Infers whether this is an actor or a home server certificate from its BasicConstraints and
+    /// the structure of its subject. See [Target::infer()].
+    pub fn infer_target(&self) -> Result<Target, ConstraintError> {
+        Target::infer(&self.id_cert_tbs.capabilities, &self.id_cert_tbs.subject)
+    }
+
+    /// Returns `true`, if the subject of this certificate is marked as a guest. See
+    /// [GuestProfile](super::guest::GuestProfile) for validating guest certificates.
+    pub fn is_guest(&self) -> bool {
+        self.id_cert_tbs.is_guest()
+    }
+
+    /// Returns the [SessionId] of the subject of this certificate. Returns `None` for home server
+    /// certificates, which do not carry a session ID.
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.id_cert_tbs.session_id()
+    }
+
+    /// Returns `true`, if this certificate is a cross-certificate issued during a key rollover of
+    /// its home server. See [cross_sign()](super::rotation::cross_sign).
+    pub fn is_cross_signed(&self) -> bool {
+        self.id_cert_tbs.is_cross_signed()
+    }
+
+    /// Returns the URL at which the issuer of this certificate publishes its certificate
+    /// revocation lists, if the certificate carries a
+    /// [CrlDistributionPoint](super::crldp::CrlDistributionPoint).
+    pub fn crl_distribution_point(&self) -> Option<&str> {
+        self.id_cert_tbs.crl_distribution_point()
+    }
+
+    /// Returns the [SubjectAltNames] of this certificate.
+    pub fn subject_alt_names(&self) -> &SubjectAltNames {
+        self.id_cert_tbs.subject_alt_names()
+    }
+
+    /// Returns the DNS names listed in the SubjectAltName extension of this certificate.
+    pub fn dns_names(&self) -> &[String] {
+        self.id_cert_tbs.dns_names()
+    }
+
+    /// Checks, that this certificate is valid for the instance at `domain`. If the certificate
+    /// carries [SubjectAltNames], `domain` must be listed in them. Otherwise, `domain` must equal
+    /// the domain made up of the domain components of the subject. Domains are compared
+    /// case-insensitively.
+    ///
+    /// Fails with [InvalidCert::DomainMismatch] otherwise.
+    pub fn check_domain(&self, domain: &str) -> Result<(), InvalidCert> {
+        let subject_alt_names = self.subject_alt_names();
+        let matches = match subject_alt_names.is_empty() {
+            true => domain_of(&self.id_cert_tbs.subject) == domain.to_lowercase(),
+            false => subject_alt_names.contains(domain),
+        };
+        if matches {
+            return Ok(());
+        }
+        log::debug!(
+            "[IdCert::check_domain()] The certificate is not valid for the domain {:?}",
+            domain
+        );
+        Err(InvalidCert::DomainMismatch(domain.to_string()))
+    }
+
+    /// Returns the [SecurityLevel] of this certificate under the [StandardSecurityPolicy]. Use
+    /// [IdCert::security_level_with()] to apply a custom [SecurityPolicy].
+    pub fn security_level(&self) -> SecurityLevel {
+        self.security_level_with(&StandardSecurityPolicy::default())
+    }
+
+    /// Returns the [SecurityLevel] of this certificate under `policy`.
+    pub fn security_level_with(&self, policy: &impl SecurityPolicy) -> SecurityLevel {
+        policy.security_level(&SecurityFacts::of(self))
     }
 
     /// Checks, if the certificate is valid at a given time. Does not check if the certificate is
     /// well-formed, up to polyproto specification or if the signature is correct. If you need to
     /// verify these properties, use either [IdCert::full_verify_actor()] or [IdCert::full_verify_home_server()]
     /// instead.
-    pub fn valid_at(&self, time: u64) -> bool {
+    ///
+    /// `time` can be anything convertible into a [Timestamp]: a [Timestamp] itself, a
+    /// [SystemTime](std::time::SystemTime), a `u64` holding seconds since the Unix epoch, or, with
+    /// the `chrono` or `time` features enabled, a `chrono::DateTime<Utc>` or a
+    /// `time::OffsetDateTime`. Sub-second precision is discarded.
+    pub fn valid_at(&self, time: impl Into<Timestamp>) -> bool {
         self.id_cert_tbs.valid_at(time)
     }
 
+    /// Like [IdCert::valid_at()], but tolerates a clock skew of up to `tolerance` between the
+    /// issuer of the certificate and the caller: the certificate is considered valid from
+    /// `tolerance` before the start until `tolerance` after the end of its validity period. This
+    /// keeps freshly issued certificates from being rejected by peers whose clocks lag behind.
+    pub fn valid_at_with_tolerance(&self, time: impl Into<Timestamp>, tolerance: Duration) -> bool {
+        self.id_cert_tbs.valid_at_with_tolerance(time, tolerance)
+    }
+
     /// Performs verification of the certificate, checking for the following properties:
     ///
     /// - The certificate is valid at the given `time`
@@ -261,26 +688,14 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
     /// - All parts that make up the certificate are well-formed and up to polyproto specification
     pub fn full_verify_actor(
         &self,
-        time: u64,
+        time: Timestamp,
         home_server_public_key: &P,
     ) -> Result<(), InvalidCert> {
         if !self.valid_at(time) {
             return Err(InvalidCert::InvalidValidity);
         }
         log::trace!("[IdCert::full_verify_actor(&self)] verifying signature (actor certificate)");
-        let der = match self.id_cert_tbs.clone().to_der() {
-            Ok(der) => der,
-            Err(_) => {
-                log::warn!(
-                    "[IdCert::full_verify_actor(&self)] {}",
-                    ERR_CERTIFICATE_TO_DER_ERROR
-                );
-                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
-                    Some(ERR_CERTIFICATE_TO_DER_ERROR.to_string()),
-                )));
-            }
-        };
-        Ok(home_server_public_key.verify_signature(&self.signature, &der)?)
+        self.verify_signature(home_server_public_key)
     }
 
     /// Performs verification of the certificate, checking for the following properties:
@@ -289,15 +704,54 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
     /// - The signature of the certificate is correct
     /// - The certificate is well-formed and up to polyproto specification
     /// - All parts that make up the certificate are well-formed and up to polyproto specification
-    pub fn full_verify_home_server(&self, time: u64) -> Result<(), InvalidCert> {
+    pub fn full_verify_home_server(&self, time: Timestamp) -> Result<(), InvalidCert> {
         if !self.valid_at(time) {
             return Err(InvalidCert::InvalidValidity);
         }
-        let der = match self.id_cert_tbs.clone().to_der() {
-            Ok(data) => data,
+        log::trace!(
+            "[IdCert::full_verify_home_server(&self)] verifying signature (self-signed IdCert)"
+        );
+        self.verify_signature(&self.id_cert_tbs.subject_public_key)
+    }
+
+    /// Like [IdCert::full_verify_actor()] and [IdCert::full_verify_home_server()], but validates
+    /// the certificate using [IdCert::validate_with()] and checks its validity period with the
+    /// clock skew tolerance of `policy`. If `target` is `None`, the target is inferred using
+    /// [IdCert::infer_target()]. `home_server_public_key` is not used for home server
+    /// certificates, which are self-signed.
+    pub fn full_verify_with(
+        &self,
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
+        home_server_public_key: &P,
+        policy: &ValidationPolicy,
+    ) -> Result<(), InvalidCert> {
+        let target = match target.into() {
+            Some(target) => target,
+            None => self.infer_target()?,
+        };
+        self.validate_with(Some(target), policy)?;
+        if !self.valid_at_with_tolerance(time, policy.clock_skew_tolerance) {
+            return Err(InvalidCert::InvalidValidity);
+        }
+        match target {
+            Target::Actor => self.verify_signature(home_server_public_key),
+            Target::HomeServer => self.verify_signature(&self.id_cert_tbs.subject_public_key),
+        }
+    }
+
+    /// Verifies the signature of this certificate over its [signature data](IdCert::signature_data)
+    /// using the public key of its issuer. For a self-signed home server certificate, pass the
+    /// subject public key of the certificate itself.
+    ///
+    /// Unlike [IdCert::full_verify_actor()] and [IdCert::full_verify_home_server()], this method
+    /// does not check the validity period of the certificate.
+    pub fn verify_signature(&self, issuer_public_key: &P) -> Result<(), InvalidCert> {
+        let der = match self.id_cert_tbs.encoded_der() {
+            Ok(der) => der,
             Err(_) => {
                 log::warn!(
-                    "[IdCert::full_verify_home_server(&self)] {}",
+                    "[IdCert::verify_signature(&self)] {}",
                     ERR_CERTIFICATE_TO_DER_ERROR
                 );
                 return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
@@ -305,22 +759,42 @@ impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
                 )));
             }
         };
-        log::trace!(
-            "[IdCert::full_verify_home_server(&self)] verifying signature (self-signed IdCert)"
-        );
-        Ok(self
-            .id_cert_tbs
-            .subject_public_key
-            .verify_signature(&self.signature, &der)?)
+        Ok(issuer_public_key.verify_signature(&self.signature, der)?)
     }
 }
 
+fn tbs_from_csr<S: Signature, P: PublicKey<S>>(
+    id_csr: IdCsr<S, P>,
+    issuer_public_key: &P,
+    signature_algorithm: spki::AlgorithmIdentifierOwned,
+    serial_number: Uint,
+    issuer: Name,
+    validity: Validity,
+) -> IdCertTbs<S, P> {
+    IdCertTbs::<S, P> {
+        serial_number,
+        signature_algorithm,
+        issuer,
+        validity,
+        subject: id_csr.inner_csr.subject,
+        subject_public_key: id_csr.inner_csr.subject_public_key,
+        capabilities: id_csr.inner_csr.capabilities,
+        claims: CustomClaims::new(),
+        crl_distribution_point: None,
+        subject_key_identifier: None,
+        authority_key_identifier: None,
+        subject_alt_names: SubjectAltNames::new(),
+        s: std::marker::PhantomData,
+    }
+    .with_key_identifiers(issuer_public_key)
+}
 impl<S: Signature, P: PublicKey<S>> TryFrom<IdCert<S, P>> for Certificate {
     type Error = ConversionError;
     fn try_from(value: IdCert<S, P>) -> Result<Self, Self::Error> {
+        let id_cert_tbs = value.id_cert_tbs.into_inner();
         Ok(Self {
-            tbs_certificate: value.id_cert_tbs.clone().try_into()?,
-            signature_algorithm: value.id_cert_tbs.signature_algorithm,
+            signature_algorithm: id_cert_tbs.signature_algorithm.clone(),
+            tbs_certificate: id_cert_tbs.try_into()?,
             signature: value.signature.to_bitstring()?,
         })
     }
@@ -333,10 +807,10 @@ impl<S: Signature, P: PublicKey<S>> TryFrom<Certificate> for IdCert<S, P> {
     /// manually, the caller is responsible for verifying the correctness of this `IdCert` using
     /// the [Constrained] trait.
     fn try_from(value: Certificate) -> Result<Self, Self::Error> {
-        let id_cert_tbs = value.tbs_certificate.try_into()?;
+        let id_cert_tbs = IdCertTbs::try_from(value.tbs_certificate)?;
         let signature = S::from_bytes(value.signature.raw_bytes());
         let cert = IdCert {
-            id_cert_tbs,
+            id_cert_tbs: id_cert_tbs.into(),
             signature,
         };
         Ok(cert)
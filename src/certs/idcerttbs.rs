@@ -2,11 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
 use der::asn1::Uint;
 use der::{Decode, Encode};
 use spki::AlgorithmIdentifierOwned;
 use x509_cert::certificate::{Profile, TbsCertificateInner};
-use x509_cert::ext::Extensions;
+use x509_cert::ext::{Extension, Extensions};
 use x509_cert::name::Name;
 use x509_cert::serial_number::SerialNumber;
 use x509_cert::time::Validity;
@@ -15,11 +19,19 @@ use x509_cert::TbsCertificate;
 use crate::errors::ConversionError;
 use crate::key::PublicKey;
 use crate::signature::Signature;
+use crate::timestamp::Timestamp;
 use crate::Constrained;
 
 use super::capabilities::Capabilities;
+use super::certid::CertId;
+use super::claims::{ClaimValue, CustomClaims, OID_CUSTOM_CLAIMS};
+use super::crldp::{CrlDistributionPoint, OID_CRL_DISTRIBUTION_POINTS};
+use super::guest::is_guest_name;
 use super::idcsr::IdCsr;
-use super::{PublicKeyInfo, Target};
+use super::keyid::{KeyIdentifier, OID_AUTHORITY_KEY_IDENTIFIER, OID_SUBJECT_KEY_IDENTIFIER};
+use super::rotation::CROSS_SIGNED_CLAIM;
+use super::san::{SubjectAltNames, OID_SUBJECT_ALT_NAME};
+use super::{PublicKeyInfo, SessionId, Target};
 
 /// An unsigned polyproto ID-Cert.
 ///
@@ -57,6 +69,22 @@ pub struct IdCertTbs<S: Signature, P: PublicKey<S>> {
     pub subject_public_key: P,
     /// Capabilities assigned to the subject of the certificate.
     pub capabilities: Capabilities,
+    /// Deployment-specific [CustomClaims] about the subject of the certificate. Empty, unless set
+    /// by the issuer using [IdCertTbs::with_claims()].
+    pub claims: CustomClaims,
+    /// The URL at which the issuer publishes its certificate revocation lists, if any. Set by the
+    /// issuer using [IdCertTbs::with_crl_distribution_point()].
+    pub crl_distribution_point: Option<CrlDistributionPoint>,
+    /// The [KeyIdentifier] of the subject public key, carried in the SubjectKeyIdentifier
+    /// extension. `None` for certificates issued without the extension.
+    pub subject_key_identifier: Option<KeyIdentifier>,
+    /// The [KeyIdentifier] of the public key of the issuer, carried in the AuthorityKeyIdentifier
+    /// extension. Set when the certificate is signed, see [IdCertTbs::with_key_identifiers()].
+    /// `None` for certificates issued without the extension.
+    pub authority_key_identifier: Option<KeyIdentifier>,
+    /// The DNS names of the instance of a home server, carried in the SubjectAltName extension.
+    /// Empty, unless set using [IdCertTbs::with_subject_alt_names()].
+    pub subject_alt_names: SubjectAltNames,
     /// PhantomData
     pub(crate) s: std::marker::PhantomData<S>,
 }
@@ -87,8 +115,14 @@ impl<S: Signature, P: PublicKey<S>> IdCertTbs<S, P> {
             subject: id_csr.inner_csr.subject,
             subject_public_key: id_csr.inner_csr.subject_public_key,
             capabilities: id_csr.inner_csr.capabilities,
+            claims: CustomClaims::new(),
+            crl_distribution_point: None,
+            subject_key_identifier: None,
+            authority_key_identifier: None,
+            subject_alt_names: SubjectAltNames::new(),
             s: std::marker::PhantomData,
-        };
+        }
+        .with_subject_key_identifier();
         cert_tbs.validate(Some(Target::Actor))?;
         Ok(cert_tbs)
     }
@@ -118,15 +152,114 @@ impl<S: Signature, P: PublicKey<S>> IdCertTbs<S, P> {
             subject: id_csr.inner_csr.subject,
             subject_public_key: id_csr.inner_csr.subject_public_key,
             capabilities: id_csr.inner_csr.capabilities,
+            claims: CustomClaims::new(),
+            crl_distribution_point: None,
+            subject_key_identifier: None,
+            authority_key_identifier: None,
+            subject_alt_names: SubjectAltNames::new(),
             s: std::marker::PhantomData,
-        };
+        }
+        .with_subject_key_identifier();
         cert_tbs.validate(Some(Target::HomeServer))?;
         Ok(cert_tbs)
     }
 
+    /// Returns the [CertId] of this certificate, which identifies it by its issuer and serial
+    /// number.
+    pub fn cert_id(&self) -> Result<CertId, ConversionError> {
+        CertId::new(&self.issuer, self.serial_number.clone())
+    }
+
+    /// Returns `true`, if the subject of this certificate is marked as a guest. See
+    /// [GuestProfile](super::guest::GuestProfile) for validating guest certificates.
+    pub fn is_guest(&self) -> bool {
+        is_guest_name(&self.subject)
+    }
+
+    /// Returns the [SessionId] of the subject of this certificate. Returns `None` for home server
+    /// certificates, which do not carry a session ID.
+    pub fn session_id(&self) -> Option<SessionId> {
+        SessionId::from_name(&self.subject)
+    }
+
+    /// Returns `true`, if this certificate is a cross-certificate issued during a key rollover of
+    /// its home server: it is self-issued and carries the
+    /// [CROSS_SIGNED_CLAIM](super::rotation::CROSS_SIGNED_CLAIM).
+    pub fn is_cross_signed(&self) -> bool {
+        self.issuer == self.subject
+            && self.claims.get(CROSS_SIGNED_CLAIM) == Some(&ClaimValue::Bool(true))
+    }
+
+    /// Returns the [CustomClaims] of this certificate.
+    pub fn claims(&self) -> &CustomClaims {
+        &self.claims
+    }
+
+    /// Returns the value of the custom claim `key`, if the certificate carries it.
+    pub fn claim(&self, key: &str) -> Option<&ClaimValue> {
+        self.claims.get(key)
+    }
+
+    /// Sets the [CustomClaims] of this certificate. Must be called before the certificate is
+    /// signed, for example before passing it to [IdCert::from_tbs()](super::idcert::IdCert::from_tbs()).
+    pub fn with_claims(mut self, claims: CustomClaims) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    /// Returns the URL at which the issuer of this certificate publishes its certificate
+    /// revocation lists, if the certificate carries a [CrlDistributionPoint].
+    pub fn crl_distribution_point(&self) -> Option<&str> {
+        self.crl_distribution_point
+            .as_ref()
+            .map(CrlDistributionPoint::url)
+    }
+
+    /// Sets the [CrlDistributionPoint] of this certificate. Must be called before the certificate
+    /// is signed, for example before passing it to
+    /// [IdCert::from_tbs()](super::idcert::IdCert::from_tbs()).
+    pub fn with_crl_distribution_point(mut self, distribution_point: CrlDistributionPoint) -> Self {
+        self.crl_distribution_point = Some(distribution_point);
+        self
+    }
+
+    /// Returns the [SubjectAltNames] of this certificate.
+    pub fn subject_alt_names(&self) -> &SubjectAltNames {
+        &self.subject_alt_names
+    }
+
+    /// Returns the DNS names listed in the SubjectAltName extension of this certificate.
+    pub fn dns_names(&self) -> &[String] {
+        self.subject_alt_names.dns_names()
+    }
+
+    /// Sets the [SubjectAltNames] of this home server certificate. Must be called before the
+    /// certificate is signed, for example before passing it to
+    /// [IdCert::from_tbs()](super::idcert::IdCert::from_tbs()). The DNS names must lie within the
+    /// domain of the subject, which is checked when the certificate is validated.
+    pub fn with_subject_alt_names(mut self, subject_alt_names: SubjectAltNames) -> Self {
+        self.subject_alt_names = subject_alt_names;
+        self
+    }
+
+    /// Sets the SubjectKeyIdentifier of this certificate to the [KeyIdentifier] of its subject
+    /// public key, and the AuthorityKeyIdentifier to the [KeyIdentifier] of `issuer_public_key`.
+    /// Called when the certificate is signed, for example by
+    /// [IdCert::from_tbs()](super::idcert::IdCert::from_tbs()).
+    pub fn with_key_identifiers(mut self, issuer_public_key: &P) -> Self {
+        self.authority_key_identifier = Some(KeyIdentifier::from_public_key(issuer_public_key));
+        self.with_subject_key_identifier()
+    }
+
+    fn with_subject_key_identifier(mut self) -> Self {
+        self.subject_key_identifier =
+            Some(KeyIdentifier::from_public_key(&self.subject_public_key));
+        self
+    }
+
     /// Encode this type as DER, returning a byte vector.
-    pub fn to_der(self) -> Result<Vec<u8>, ConversionError> {
-        Ok(TbsCertificate::try_from(self)?.to_der()?)
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        Ok(TbsCertificate::try_from(self.clone())?.to_der()?)
     }
 
     /// Create an [IdCertTbs] from a byte slice containing a DER encoded PKCS #10 CSR. The resulting
@@ -146,11 +279,119 @@ impl<S: Signature, P: PublicKey<S>> IdCertTbs<S, P> {
         Ok(cert)
     }
 
-    /// Checks if the IdCertTbs was valid at a given UNIX time. Does not validate the certificate
-    /// against the polyproto specification.
-    pub(crate) fn valid_at(&self, time: u64) -> bool {
-        time >= self.validity.not_before.to_unix_duration().as_secs()
-            && time <= self.validity.not_after.to_unix_duration().as_secs()
+    /// Checks if the IdCertTbs was valid at a given point in time. Does not validate the
+    /// certificate against the polyproto specification.
+    pub(crate) fn valid_at(&self, time: impl Into<Timestamp>) -> bool {
+        self.valid_at_with_tolerance(time, Duration::ZERO)
+    }
+
+    /// Like [IdCertTbs::valid_at()], but extends the validity period by `tolerance` in both
+    /// directions, to account for clock skew.
+    pub(crate) fn valid_at_with_tolerance(
+        &self,
+        time: impl Into<Timestamp>,
+        tolerance: Duration,
+    ) -> bool {
+        let time = time.into();
+        let not_before = Timestamp::from(self.validity.not_before)
+            .checked_sub(tolerance)
+            .unwrap_or(Timestamp::UNIX_EPOCH);
+        let not_after = Timestamp::from(self.validity.not_after)
+            .checked_add(tolerance)
+            .unwrap_or(Timestamp::from_unix_seconds(u64::MAX));
+        time >= not_before && time <= not_after
+    }
+}
+
+/// The [IdCertTbs] of an [IdCert](super::idcert::IdCert), together with its DER encoding. The
+/// encoding is computed once, or kept from the bytes the certificate was decoded from, so that
+/// verifying the signature of a certificate or encoding it repeatedly only borrows it.
+///
+/// Dereferences to the [IdCertTbs]. Borrowing it mutably, for example to change one of its fields,
+/// discards the encoding, which is then computed again from the changed fields.
+pub struct CachedIdCertTbs<S: Signature, P: PublicKey<S>> {
+    tbs: IdCertTbs<S, P>,
+    der: OnceLock<Arc<[u8]>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> CachedIdCertTbs<S, P> {
+    /// Creates a [CachedIdCertTbs] holding `der`, the bytes `tbs` was decoded from.
+    pub(crate) fn with_der(tbs: IdCertTbs<S, P>, der: &[u8]) -> Self {
+        let cached = Self::from(tbs);
+        let _ = cached.der.set(der.into());
+        cached
+    }
+
+    /// Returns the DER encoding of the [IdCertTbs], encoding it if it has not been encoded or
+    /// decoded yet.
+    pub fn encoded_der(&self) -> Result<&[u8], ConversionError> {
+        if let Some(der) = self.der.get() {
+            return Ok(der);
+        }
+        log::trace!("[CachedIdCertTbs::encoded_der()] Encoding IdCertTbs");
+        let der = self.tbs.to_der()?;
+        Ok(self.der.get_or_init(|| der.into()))
+    }
+
+    /// Returns the [IdCertTbs], discarding its encoding.
+    pub fn into_inner(self) -> IdCertTbs<S, P> {
+        self.tbs
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<IdCertTbs<S, P>> for CachedIdCertTbs<S, P> {
+    fn from(tbs: IdCertTbs<S, P>) -> Self {
+        Self {
+            tbs,
+            der: OnceLock::new(),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<CachedIdCertTbs<S, P>> for IdCertTbs<S, P> {
+    fn from(cached: CachedIdCertTbs<S, P>) -> Self {
+        cached.tbs
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Deref for CachedIdCertTbs<S, P> {
+    type Target = IdCertTbs<S, P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tbs
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> DerefMut for CachedIdCertTbs<S, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.der = OnceLock::new();
+        &mut self.tbs
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Clone for CachedIdCertTbs<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            tbs: self.tbs.clone(),
+            der: self.der.clone(),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> PartialEq for CachedIdCertTbs<S, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tbs == other.tbs
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Eq for CachedIdCertTbs<S, P> {}
+
+impl<S: Signature, P: PublicKey<S>> std::fmt::Debug for CachedIdCertTbs<S, P>
+where
+    IdCertTbs<S, P>: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.tbs.fmt(f)
     }
 }
 
@@ -165,9 +406,16 @@ impl<P: Profile, S: Signature, Q: PublicKey<S>> TryFrom<TbsCertificateInner<P>>
     fn try_from(value: TbsCertificateInner<P>) -> Result<Self, Self::Error> {
         value.subject.validate(None)?;
 
-        let capabilities =
+        let SplitExtensions {
+            capabilities,
+            claims,
+            crl_distribution_point,
+            subject_key_identifier,
+            authority_key_identifier,
+            subject_alt_names,
+        } =
             match value.extensions {
-                Some(ext) => Capabilities::try_from(ext)?,
+                Some(ext) => split_extensions(ext)?,
                 None => return Err(ConversionError::InvalidInput(
                     crate::errors::base::InvalidInput::Malformed(
                         "field 'extensions' was None. Expected: Some(x509_cert::ext::Extensions)"
@@ -189,6 +437,11 @@ impl<P: Profile, S: Signature, Q: PublicKey<S>> TryFrom<TbsCertificateInner<P>>
             subject: value.subject,
             subject_public_key: subject_public_key_info,
             capabilities,
+            claims,
+            crl_distribution_point,
+            subject_key_identifier,
+            authority_key_identifier,
+            subject_alt_names,
             s: std::marker::PhantomData,
         })
     }
@@ -217,6 +470,23 @@ impl<P: Profile, S: Signature, Q: PublicKey<S>> TryFrom<IdCertTbs<S, Q>>
             parameters: value.signature_algorithm.parameters,
         };
 
+        let mut extensions = Extensions::try_from(value.capabilities)?;
+        if let Some(key_identifier) = value.subject_key_identifier {
+            extensions.push(key_identifier.to_subject_key_identifier()?);
+        }
+        if let Some(key_identifier) = value.authority_key_identifier {
+            extensions.push(key_identifier.to_authority_key_identifier()?);
+        }
+        if !value.subject_alt_names.is_empty() {
+            extensions.push(Extension::try_from(value.subject_alt_names)?);
+        }
+        if !value.claims.is_empty() {
+            extensions.push(Extension::try_from(value.claims)?);
+        }
+        if let Some(distribution_point) = value.crl_distribution_point {
+            extensions.push(Extension::try_from(distribution_point)?);
+        }
+
         Ok(TbsCertificateInner {
             version: x509_cert::Version::V3,
             serial_number,
@@ -227,7 +497,74 @@ impl<P: Profile, S: Signature, Q: PublicKey<S>> TryFrom<IdCertTbs<S, Q>>
             subject_public_key_info: value.subject_public_key.public_key_info().into(),
             issuer_unique_id: None,
             subject_unique_id: None,
-            extensions: Some(Extensions::try_from(value.capabilities)?),
+            extensions: Some(extensions),
         })
     }
 }
+
+/// The extensions of a certificate, separated by [split_extensions()].
+struct SplitExtensions {
+    capabilities: Capabilities,
+    claims: CustomClaims,
+    crl_distribution_point: Option<CrlDistributionPoint>,
+    subject_key_identifier: Option<KeyIdentifier>,
+    authority_key_identifier: Option<KeyIdentifier>,
+    subject_alt_names: SubjectAltNames,
+}
+
+/// Separates the custom claims, CRLDistributionPoints, SubjectKeyIdentifier,
+/// AuthorityKeyIdentifier and SubjectAltName extensions from the other extensions, which are converted to
+/// [Capabilities]. Fails, if any of these extensions is present more than once.
+fn split_extensions(extensions: Extensions) -> Result<SplitExtensions, ConversionError> {
+    let mut claims = None;
+    let mut crl_distribution_point = None;
+    let mut subject_key_identifier = None;
+    let mut authority_key_identifier = None;
+    let mut subject_alt_names = None;
+    let mut seen = Vec::new();
+    let mut capabilities = Extensions::new();
+    for extension in extensions.into_iter() {
+        let oid = extension.extn_id.to_string();
+        if [
+            OID_CUSTOM_CLAIMS,
+            OID_CRL_DISTRIBUTION_POINTS,
+            OID_SUBJECT_KEY_IDENTIFIER,
+            OID_AUTHORITY_KEY_IDENTIFIER,
+            OID_SUBJECT_ALT_NAME,
+        ]
+        .contains(&oid.as_str())
+        {
+            if seen.contains(&oid) {
+                return Err(crate::errors::base::InvalidInput::Malformed(format!(
+                    "The extension {} may only be present once",
+                    extension.extn_id
+                ))
+                .into());
+            }
+            seen.push(oid.clone());
+        }
+        match oid.as_str() {
+            OID_CUSTOM_CLAIMS => claims = Some(CustomClaims::try_from(extension)?),
+            OID_CRL_DISTRIBUTION_POINTS => {
+                crl_distribution_point = Some(CrlDistributionPoint::try_from(extension)?)
+            }
+            OID_SUBJECT_KEY_IDENTIFIER => {
+                subject_key_identifier =
+                    Some(KeyIdentifier::from_subject_key_identifier(&extension)?)
+            }
+            OID_AUTHORITY_KEY_IDENTIFIER => {
+                authority_key_identifier = KeyIdentifier::from_authority_key_identifier(&extension)?
+            }
+            OID_SUBJECT_ALT_NAME => subject_alt_names = Some(SubjectAltNames::try_from(extension)?),
+            _ => capabilities.push(extension),
+        }
+    }
+    Ok(SplitExtensions {
+        capabilities: Capabilities::try_from(capabilities)?,
+        claims: claims.unwrap_or_default(),
+        crl_distribution_point,
+        subject_key_identifier,
+        authority_key_identifier,
+        subject_alt_names: subject_alt_names.unwrap_or_default(),
+    })
+}
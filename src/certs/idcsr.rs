@@ -5,19 +5,25 @@
 use std::marker::PhantomData;
 
 use der::pem::LineEnding;
-use der::{Decode, DecodePem, Encode, EncodePem};
+use der::{Decode, Encode};
 use spki::AlgorithmIdentifierOwned;
-use x509_cert::attr::Attributes;
+use x509_cert::attr::{Attribute, Attributes};
 use x509_cert::name::Name;
 use x509_cert::request::{CertReq, CertReqInfo};
 
+use crate::encoding::check_canonical_der;
 use crate::errors::ConversionError;
-use crate::key::{PrivateKey, PublicKey};
+use crate::key::{AsyncPrivateKey, PrivateKey, PublicKey};
 use crate::signature::Signature;
 use crate::Constrained;
 
 use super::capabilities::Capabilities;
-use super::{PkcsVersion, PublicKeyInfo, Target};
+use super::csrmeta::CsrMetadata;
+use super::idcert::IdCert;
+use super::limits::SizeLimits;
+use super::pem::{self, PemKind, PemLabel, PemLabels};
+use super::policy::ValidationPolicy;
+use super::{PkcsVersion, PublicKeyInfo, SessionId, Target};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// A polyproto Certificate Signing Request, compatible with [IETF RFC 2986 "PKCS #10"](https://datatracker.ietf.org/doc/html/rfc2986).
@@ -69,35 +75,131 @@ impl<S: Signature, P: PublicKey<S>> IdCsr<S, P> {
         signing_key: &impl PrivateKey<S, PublicKey = P>,
         capabilities: &Capabilities,
         target: Option<Target>,
+    ) -> Result<IdCsr<S, P>, ConversionError> {
+        Self::new_with_metadata(
+            subject,
+            signing_key,
+            capabilities,
+            &CsrMetadata::default(),
+            target,
+        )
+    }
+
+    /// Like [IdCsr::new()], but additionally includes [CsrMetadata], such as a creation time,
+    /// expiry time and one-time nonce, as attributes of the CSR. Home servers can use the
+    /// metadata to reject stale or replayed CSRs; see
+    /// [CsrReplayGuard](super::csrmeta::CsrReplayGuard).
+    pub fn new_with_metadata(
+        subject: &Name,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+        capabilities: &Capabilities,
+        metadata: &CsrMetadata,
+        target: Option<Target>,
+    ) -> Result<IdCsr<S, P>, ConversionError> {
+        let inner_csr = IdCsrInner::<S, P> {
+            version: PkcsVersion::V1,
+            subject: subject.clone(),
+            subject_public_key: signing_key.pubkey().clone(),
+            capabilities: capabilities.clone(),
+            metadata: metadata.clone(),
+            phantom_data: PhantomData,
+        };
+        let signature = signing_key.sign(&inner_csr.to_der()?);
+        let signature_algorithm = S::algorithm_identifier();
+        let id_csr = IdCsr {
+            inner_csr,
+            signature_algorithm,
+            signature,
+        };
+        log::trace!(
+            "[IdCsr::new_with_metadata()] Validating self with Target: {:?}",
+            target
+        );
+        id_csr.validate(target)?;
+        Ok(id_csr)
+    }
+
+    /// Like [IdCsr::new()], but awaits the signing operation of an [AsyncPrivateKey], such as a
+    /// key held by the WebCrypto API of a browser. Fails with [ConversionError::SignerError], if
+    /// the signer could not produce a signature.
+    pub async fn new_async(
+        subject: &Name,
+        signing_key: &impl AsyncPrivateKey<S, PublicKey = P>,
+        capabilities: &Capabilities,
+        target: Option<Target>,
     ) -> Result<IdCsr<S, P>, ConversionError> {
         let inner_csr = IdCsrInner::<S, P> {
             version: PkcsVersion::V1,
             subject: subject.clone(),
             subject_public_key: signing_key.pubkey().clone(),
             capabilities: capabilities.clone(),
+            metadata: CsrMetadata::default(),
             phantom_data: PhantomData,
         };
-        let signature = signing_key.sign(&inner_csr.clone().to_der()?);
+        let signature = signing_key.sign(&inner_csr.to_der()?).await?;
         let signature_algorithm = S::algorithm_identifier();
         let id_csr = IdCsr {
             inner_csr,
             signature_algorithm,
             signature,
         };
-        log::trace!("[IdCsr::new()] Validating self with Target: {:?}", target);
+        log::trace!(
+            "[IdCsr::new_async()] Validating self with Target: {:?}",
+            target
+        );
         id_csr.validate(target)?;
         Ok(id_csr)
     }
 
+    /// Creates a CSR for renewing `cert`, carrying over its subject and capabilities, signed with
+    /// `signing_key`. Passing the private key of `cert` renews the certificate for the same key;
+    /// passing another key renews it for that key instead. The [Target] of the CSR is detected
+    /// from the capabilities of `cert` using [Target::detect()].
+    ///
+    /// The validity period, serial number, custom claims and CRL distribution point of the renewed
+    /// certificate are chosen by the issuer, and are therefore not carried over.
+    pub fn renewal_from_cert(
+        cert: &IdCert<S, P>,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+    ) -> Result<IdCsr<S, P>, ConversionError> {
+        log::trace!(
+            "[IdCsr::renewal_from_cert()] Creating renewal CSR, reusing the key of the certificate: {}",
+            signing_key.pubkey() == &cert.id_cert_tbs.subject_public_key
+        );
+        let capabilities = &cert.id_cert_tbs.capabilities;
+        Self::new(
+            &cert.id_cert_tbs.subject,
+            signing_key,
+            capabilities,
+            Some(Target::detect(capabilities)),
+        )
+    }
+
     /// Create an [IdCsr] from a byte slice containing a DER encoded PKCS #10 CSR.
     /// The resulting `IdCsr` is guaranteed to be well-formed and up to polyproto specification,
     /// if the correct [Target] for the CSRs intended usage context is provided.
     pub fn from_der(bytes: &[u8], target: Option<Target>) -> Result<Self, ConversionError> {
+        SizeLimits::default().check_csr(bytes)?;
         let csr = IdCsr::from_der_unchecked(bytes)?;
         csr.validate(target)?;
         Ok(csr)
     }
 
+    /// Like [IdCsr::from_der()], but validates the CSR using [IdCsr::validate_with()] and checks
+    /// its encoding as configured by `policy`.
+    pub fn from_der_with(
+        bytes: &[u8],
+        target: Option<Target>,
+        policy: &ValidationPolicy,
+    ) -> Result<Self, ConversionError> {
+        policy.check_der_input(bytes)?;
+        policy.size_limits.check_csr(bytes)?;
+        let csr = IdCsr::from_der_unchecked(bytes)?;
+        policy.check_der(bytes, &csr.to_der()?)?;
+        csr.validate_with(target, policy)?;
+        Ok(csr)
+    }
+
     /// Create an unchecked [IdCsr] from a byte slice containing a DER encoded PKCS #10 CSR.
     /// The caller is responsible for verifying the correctness of this `IdCsr` using
     /// the [Constrained] trait before using it.
@@ -107,40 +209,97 @@ impl<S: Signature, P: PublicKey<S>> IdCsr<S, P> {
     }
 
     /// Encode this type as DER, returning a byte vector.
-    pub fn to_der(self) -> Result<Vec<u8>, ConversionError> {
-        Ok(CertReq::try_from(self)?.to_der()?)
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        Ok(CertReq::try_from(self.clone())?.to_der()?)
     }
 
     /// Create an [IdCsr] from a string containing a PEM encoded PKCS #10 CSR.
     /// The resulting `IdCsr` is guaranteed to be well-formed and up to polyproto specification,
     /// if the correct [Target] for the CSRs intended usage context is provided.
+    ///
+    /// If the document has a target-specific PEM label naming a different [Target] than
+    /// `target`, decoding fails.
     pub fn from_pem(pem: &str, target: Option<Target>) -> Result<Self, ConversionError> {
-        let csr = IdCsr::from_pem_unchecked(pem)?;
-        csr.validate(target)?;
-        Ok(csr)
+        let der = pem::decode_expecting(pem, PemKind::CertificateRequest, target)?;
+        IdCsr::from_der(&der, target)
+    }
+
+    /// Like [IdCsr::from_pem()], but decodes the CSR using [IdCsr::from_der_with()].
+    pub fn from_pem_with(
+        pem: &str,
+        target: Option<Target>,
+        policy: &ValidationPolicy,
+    ) -> Result<Self, ConversionError> {
+        let der = pem::decode_expecting(pem, PemKind::CertificateRequest, target)?;
+        IdCsr::from_der_with(&der, target, policy)
     }
 
     /// Create an unchecked [IdCsr] from a string containing a PEM encoded PKCS #10 CSR.
     /// The caller is responsible for verifying the correctness of this `IdCsr` using
     /// the [Constrained] trait before using it.
+    ///
+    /// Both the standard and the target-specific PEM labels (see [PemLabels]) are accepted.
     pub fn from_pem_unchecked(pem: &str) -> Result<Self, ConversionError> {
-        let csr = IdCsr::try_from(CertReq::from_pem(pem)?)?;
-        Ok(csr)
+        IdCsr::from_der_unchecked(&pem::decode_expecting(
+            pem,
+            PemKind::CertificateRequest,
+            None,
+        )?)
     }
 
-    /// Encode this type as PEM, returning a string.
-    pub fn to_pem(self, line_ending: LineEnding) -> Result<String, ConversionError> {
-        Ok(CertReq::try_from(self)?.to_pem(line_ending)?)
+    /// Encode this type as PEM with the standard `CERTIFICATE REQUEST` label, returning a string.
+    pub fn to_pem(&self, line_ending: LineEnding) -> Result<String, ConversionError> {
+        self.to_pem_with_labels(line_ending, PemLabels::Standard)
+    }
+
+    /// Encode this type as PEM, returning a string. With [PemLabels::TargetSpecific], the label
+    /// names the [Target] detected from the requested capabilities, e.g.
+    /// `POLYPROTO ACTOR CERTIFICATE REQUEST`.
+    pub fn to_pem_with_labels(
+        &self,
+        line_ending: LineEnding,
+        labels: PemLabels,
+    ) -> Result<String, ConversionError> {
+        let label = PemLabel::for_target(
+            PemKind::CertificateRequest,
+            Target::detect(&self.inner_csr.capabilities),
+            labels,
+        );
+        pem::encode(&self.to_der()?, label, line_ending)
     }
 
     /// Returns a byte vector containing the DER encoded [IdCsrInner]. This data is encoded
     /// in the signature field of the IdCSR, and can be used to verify the signature of the CSR.
     ///
-    /// This is a shorthand for `self.inner_csr.clone().to_der()`, since intuitively, one might
+    /// This is a shorthand for `self.inner_csr.to_der()`, since intuitively, one might
     /// try to verify the signature of the CSR by using `self.to_der()`, which will result
     /// in an error.
     pub fn signature_data(&self) -> Result<Vec<u8>, ConversionError> {
-        self.inner_csr.clone().to_der()
+        self.inner_csr.to_der()
+    }
+
+    /// Checks that `der`, the bytes this CSR was decoded from, are its canonical encoding, meaning
+    /// that encoding the CSR again reproduces them byte for byte.
+    ///
+    /// The signature of the CSR is verified over the re-encoded [IdCsrInner]. Fails with
+    /// [DerViolation::NonCanonical], naming the offset of the first differing byte, if the bytes
+    /// are not canonical.
+    ///
+    /// [DerViolation::NonCanonical]: crate::errors::DerViolation::NonCanonical
+    pub fn check_canonical(&self, der: &[u8]) -> Result<(), ConversionError> {
+        Ok(check_canonical_der(der, &self.to_der()?)?)
+    }
+
+    /// Whether `der`, the bytes this CSR was decoded from, are its canonical encoding. See
+    /// [IdCsr::check_canonical()].
+    pub fn is_canonical(&self, der: &[u8]) -> bool {
+        self.check_canonical(der).is_ok()
+    }
+
+    /// Returns the [SessionId] of the subject requesting the certificate. Returns `None` for CSRs
+    /// of home servers, which do not carry a session ID.
+    pub fn session_id(&self) -> Option<SessionId> {
+        SessionId::from_name(&self.inner_csr.subject)
     }
 }
 
@@ -164,6 +323,8 @@ pub struct IdCsrInner<S: Signature, P: PublicKey<S>> {
     pub subject_public_key: P,
     /// Capabilities requested by the subject.
     pub capabilities: Capabilities,
+    /// Optional creation time, expiry time and nonce of the CSR.
+    pub metadata: CsrMetadata,
     phantom_data: PhantomData<S>,
 }
 
@@ -190,6 +351,7 @@ impl<S: Signature, P: PublicKey<S>> IdCsrInner<S, P> {
             subject,
             subject_public_key: subject_public_key_info,
             capabilities: capabilities.clone(),
+            metadata: CsrMetadata::default(),
             phantom_data: PhantomData,
         };
         id_csr_inner.validate(target)?;
@@ -214,8 +376,8 @@ impl<S: Signature, P: PublicKey<S>> IdCsrInner<S, P> {
     }
 
     /// Encode this type as DER, returning a byte vector.
-    pub fn to_der(self) -> Result<Vec<u8>, ConversionError> {
-        Ok(CertReqInfo::try_from(self)?.to_der()?)
+    pub fn to_der(&self) -> Result<Vec<u8>, ConversionError> {
+        Ok(CertReqInfo::try_from(self.clone())?.to_der()?)
     }
 }
 
@@ -251,7 +413,8 @@ impl<S: Signature, P: PublicKey<S>> TryFrom<CertReqInfo> for IdCsrInner<S, P> {
             version: PkcsVersion::V1,
             subject: rdn_sequence,
             subject_public_key: PublicKey::try_from_public_key_info(public_key_info)?,
-            capabilities: Capabilities::try_from(value.attributes)?,
+            capabilities: Capabilities::try_from(value.attributes.clone())?,
+            metadata: CsrMetadata::try_from(value.attributes)?,
             phantom_data: PhantomData,
         })
     }
@@ -272,11 +435,15 @@ impl<S: Signature, P: PublicKey<S>> TryFrom<IdCsr<S, P>> for CertReq {
 impl<S: Signature, P: PublicKey<S>> TryFrom<IdCsrInner<S, P>> for CertReqInfo {
     type Error = ConversionError;
     fn try_from(value: IdCsrInner<S, P>) -> Result<Self, Self::Error> {
+        let mut attributes = Attributes::try_from(value.capabilities)?;
+        for attribute in Vec::<Attribute>::try_from(value.metadata)? {
+            attributes.insert(attribute)?;
+        }
         Ok(CertReqInfo {
             version: x509_cert::request::Version::V1,
             subject: value.subject,
             public_key: value.subject_public_key.public_key_info().into(),
-            attributes: Attributes::try_from(value.capabilities)?,
+            attributes,
         })
     }
 }
@@ -0,0 +1,141 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use der::asn1::OctetString;
+use der::{Decode, Encode};
+use sha2::{Digest, Sha256};
+use spki::ObjectIdentifier;
+use x509_cert::ext::pkix::{AuthorityKeyIdentifier, SubjectKeyIdentifier};
+use x509_cert::ext::Extension;
+
+use crate::encoding::encode_hex;
+use crate::errors::{ConversionError, InvalidInput};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+
+use super::PublicKeyInfo;
+
+/// Object Identifier of the SubjectKeyIdentifier extension, as defined in RFC 5280.
+pub const OID_SUBJECT_KEY_IDENTIFIER: &str = "2.5.29.14";
+/// Object Identifier of the AuthorityKeyIdentifier extension, as defined in RFC 5280.
+pub const OID_AUTHORITY_KEY_IDENTIFIER: &str = "2.5.29.35";
+/// The length of the key identifiers derived by [KeyIdentifier::from_public_key_info()], in
+/// bytes.
+pub const KEY_IDENTIFIER_LENGTH: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Identifies a public key in the SubjectKeyIdentifier and AuthorityKeyIdentifier extensions of an
+/// [IdCert](super::idcert::IdCert), linking a certificate to the certificate of its issuer
+/// independently of their names.
+///
+/// Key identifiers derived by polyproto are the leftmost 160 bits of the SHA-256 hash of the
+/// `subjectPublicKey` bit string, as described in RFC 7093, section 2, method 1. Key identifiers
+/// of certificates issued elsewhere may be derived differently, and are only compared for
+/// equality.
+pub struct KeyIdentifier(Vec<u8>);
+
+impl KeyIdentifier {
+    /// Creates a [KeyIdentifier] from its raw bytes. Fails, if `bytes` is empty.
+    pub fn new(bytes: &[u8]) -> Result<Self, InvalidInput> {
+        if bytes.is_empty() {
+            return Err(InvalidInput::Malformed(
+                "Key identifiers must not be empty".to_string(),
+            ));
+        }
+        Ok(Self(bytes.to_vec()))
+    }
+
+    /// Derives the [KeyIdentifier] of the public key in `public_key_info`.
+    pub fn from_public_key_info(public_key_info: &PublicKeyInfo) -> Self {
+        let hash = Sha256::digest(public_key_info.public_key_bitstring.raw_bytes());
+        Self(hash[..KEY_IDENTIFIER_LENGTH].to_vec())
+    }
+
+    /// Derives the [KeyIdentifier] of `public_key`.
+    pub fn from_public_key<S: Signature, P: PublicKey<S>>(public_key: &P) -> Self {
+        Self::from_public_key_info(&public_key.public_key_info())
+    }
+
+    /// Returns the raw bytes of the key identifier.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the key identifier as a lowercase hex string.
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.0)
+    }
+
+    /// Encodes the key identifier as a non-critical SubjectKeyIdentifier extension.
+    pub fn to_subject_key_identifier(&self) -> Result<Extension, ConversionError> {
+        let value = SubjectKeyIdentifier(OctetString::new(self.0.clone())?);
+        Ok(Extension {
+            extn_id: ObjectIdentifier::from_str(OID_SUBJECT_KEY_IDENTIFIER)?,
+            critical: false,
+            extn_value: OctetString::new(value.to_der()?)?,
+        })
+    }
+
+    /// Encodes the key identifier as a non-critical AuthorityKeyIdentifier extension, naming the
+    /// key identifier only.
+    pub fn to_authority_key_identifier(&self) -> Result<Extension, ConversionError> {
+        let value = AuthorityKeyIdentifier {
+            key_identifier: Some(OctetString::new(self.0.clone())?),
+            authority_cert_issuer: None,
+            authority_cert_serial_number: None,
+        };
+        Ok(Extension {
+            extn_id: ObjectIdentifier::from_str(OID_AUTHORITY_KEY_IDENTIFIER)?,
+            critical: false,
+            extn_value: OctetString::new(value.to_der()?)?,
+        })
+    }
+
+    /// Decodes the key identifier from a SubjectKeyIdentifier extension. Fails, if the extension
+    /// has another OID, is marked as critical, or carries an empty key identifier.
+    pub fn from_subject_key_identifier(extension: &Extension) -> Result<Self, ConversionError> {
+        check_extension(extension, OID_SUBJECT_KEY_IDENTIFIER)?;
+        let value = SubjectKeyIdentifier::from_der(extension.extn_value.as_bytes())?;
+        Ok(Self::new(value.0.as_bytes())?)
+    }
+
+    /// Decodes the key identifier from an AuthorityKeyIdentifier extension. Returns `None`, if the
+    /// extension identifies the issuer by its name and serial number only. Fails, if the extension
+    /// has another OID, is marked as critical, or carries an empty key identifier.
+    pub fn from_authority_key_identifier(
+        extension: &Extension,
+    ) -> Result<Option<Self>, ConversionError> {
+        check_extension(extension, OID_AUTHORITY_KEY_IDENTIFIER)?;
+        let value = AuthorityKeyIdentifier::from_der(extension.extn_value.as_bytes())?;
+        match value.key_identifier {
+            Some(key_identifier) => Ok(Some(Self::new(key_identifier.as_bytes())?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Display for KeyIdentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+fn check_extension(extension: &Extension, oid: &str) -> Result<(), InvalidInput> {
+    if extension.extn_id.to_string() != oid {
+        return Err(InvalidInput::Malformed(format!(
+            "Expected the extension {}, found {}",
+            oid, extension.extn_id
+        )));
+    }
+    if extension.critical {
+        return Err(InvalidInput::Malformed(format!(
+            "The extension {} must not be critical",
+            oid
+        )));
+    }
+    Ok(())
+}
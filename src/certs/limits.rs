@@ -0,0 +1,202 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::AnyRef;
+use der::{Decode, Reader, SliceReader, Tag, TagNumber, Tagged};
+use spki::ObjectIdentifier;
+
+use crate::errors::{ConversionError, SizeLimitViolation};
+
+use super::capabilities::OID_EXTENSION_REQUEST;
+use super::serial::MAX_SERIAL_NUMBER_LENGTH;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Upper bounds on the sizes of the fields of DER encoded certificates and CSRs. The limits are
+/// checked on the encoded bytes before a certificate or CSR is decoded, so that a peer cannot make
+/// the decoder allocate memory for arbitrarily many names and extensions.
+///
+/// `from_der()` and `from_pem()` of [IdCert](super::idcert::IdCert) and
+/// [IdCsr](super::idcsr::IdCsr) enforce the default limits. Other limits can be configured using
+/// the `size_limits` of a [ValidationPolicy](super::policy::ValidationPolicy).
+pub struct SizeLimits {
+    /// The maximum length of the encoded serial number, in bytes. Defaults to
+    /// [MAX_SERIAL_NUMBER_LENGTH].
+    pub max_serial_number_length: usize,
+    /// The maximum number of relative distinguished names in the issuer or subject name. Each
+    /// attribute of a multi-valued relative distinguished name is counted. Defaults to 32.
+    pub max_rdns: usize,
+    /// The maximum number of extensions of a certificate, which also limits the number of
+    /// attributes and requested extensions of a CSR. Defaults to 64.
+    pub max_extensions: usize,
+    /// The maximum length of the value of an extension or CSR attribute, in bytes. Defaults to
+    /// 64 KiB.
+    pub max_extension_value_length: usize,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_serial_number_length: MAX_SERIAL_NUMBER_LENGTH,
+            max_rdns: 32,
+            max_extensions: 64,
+            max_extension_value_length: 64 * 1024,
+        }
+    }
+}
+
+impl SizeLimits {
+    /// Limits which accept every certificate and CSR.
+    pub fn unlimited() -> Self {
+        Self {
+            max_serial_number_length: usize::MAX,
+            max_rdns: usize::MAX,
+            max_extensions: usize::MAX,
+            max_extension_value_length: usize::MAX,
+        }
+    }
+
+    /// Checks the fields of the DER encoded X.509 certificate `der` against these limits. Fails
+    /// with [ConversionError::SizeLimit], if a limit is exceeded.
+    pub fn check_certificate(&self, der: &[u8]) -> Result<(), ConversionError> {
+        let certificate = AnyRef::from_der(der)?;
+        let mut reader = SliceReader::new(certificate.value())?;
+        let tbs_certificate = AnyRef::decode(&mut reader)?;
+        let mut reader = SliceReader::new(tbs_certificate.value())?;
+        let mut serial_number = AnyRef::decode(&mut reader)?;
+        if serial_number.tag() == context_specific(TagNumber::N0) {
+            // The version precedes the serial number.
+            serial_number = AnyRef::decode(&mut reader)?;
+        }
+        check_limit(
+            |limit, actual| SizeLimitViolation::SerialNumberLength { limit, actual },
+            self.max_serial_number_length,
+            serial_number.value().len(),
+        )?;
+        // The signature algorithm precedes the issuer.
+        AnyRef::decode(&mut reader)?;
+        self.check_name(AnyRef::decode(&mut reader)?)?;
+        // The validity precedes the subject.
+        AnyRef::decode(&mut reader)?;
+        self.check_name(AnyRef::decode(&mut reader)?)?;
+        while !reader.is_finished() {
+            let field = AnyRef::decode(&mut reader)?;
+            if field.tag() == context_specific(TagNumber::N3) {
+                self.check_extensions(AnyRef::from_der(field.value())?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the fields of the DER encoded PKCS #10 CSR `der` against these limits. Fails with
+    /// [ConversionError::SizeLimit], if a limit is exceeded.
+    pub fn check_csr(&self, der: &[u8]) -> Result<(), ConversionError> {
+        let csr = AnyRef::from_der(der)?;
+        let mut reader = SliceReader::new(csr.value())?;
+        let info = AnyRef::decode(&mut reader)?;
+        let mut reader = SliceReader::new(info.value())?;
+        // The version precedes the subject.
+        AnyRef::decode(&mut reader)?;
+        self.check_name(AnyRef::decode(&mut reader)?)?;
+        // The subject public key info precedes the attributes.
+        AnyRef::decode(&mut reader)?;
+        let attributes = AnyRef::decode(&mut reader)?;
+        let extension_request = ObjectIdentifier::from_str(OID_EXTENSION_REQUEST)?;
+        let count = for_each_element(attributes, |attribute| {
+            let mut reader = SliceReader::new(attribute.value())?;
+            let oid = AnyRef::decode(&mut reader)?;
+            let values = AnyRef::decode(&mut reader)?;
+            for_each_element(values, |value| {
+                if oid.value() == extension_request.as_bytes() {
+                    self.check_extensions(value)
+                } else {
+                    check_limit(
+                        |limit, actual| SizeLimitViolation::ExtensionValueLength { limit, actual },
+                        self.max_extension_value_length,
+                        value.value().len(),
+                    )
+                }
+            })?;
+            Ok(())
+        })?;
+        check_limit(
+            |limit, actual| SizeLimitViolation::ExtensionCount { limit, actual },
+            self.max_extensions,
+            count,
+        )
+    }
+
+    /// Checks the number of relative distinguished names of `name`.
+    fn check_name(&self, name: AnyRef<'_>) -> Result<(), ConversionError> {
+        let mut count = 0;
+        for_each_element(name, |rdn| {
+            count += for_each_element(rdn, |_| Ok(()))?;
+            check_limit(
+                |limit, actual| SizeLimitViolation::RdnCount { limit, actual },
+                self.max_rdns,
+                count,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Checks the number of `extensions` and the lengths of their values.
+    fn check_extensions(&self, extensions: AnyRef<'_>) -> Result<(), ConversionError> {
+        let count = for_each_element(extensions, |extension| {
+            let mut reader = SliceReader::new(extension.value())?;
+            let mut value = AnyRef::decode(&mut reader)?;
+            // The value is the last field, following the OID and the optional criticality.
+            while !reader.is_finished() {
+                value = AnyRef::decode(&mut reader)?;
+            }
+            check_limit(
+                |limit, actual| SizeLimitViolation::ExtensionValueLength { limit, actual },
+                self.max_extension_value_length,
+                value.value().len(),
+            )
+        })?;
+        check_limit(
+            |limit, actual| SizeLimitViolation::ExtensionCount { limit, actual },
+            self.max_extensions,
+            count,
+        )
+    }
+}
+
+/// The tag of a constructed, context-specific field with the tag `number`.
+fn context_specific(number: TagNumber) -> Tag {
+    Tag::ContextSpecific {
+        constructed: true,
+        number,
+    }
+}
+
+/// Calls `f` for each element of the SEQUENCE or SET `any`, without decoding the elements.
+/// Returns the number of elements.
+fn for_each_element<'a>(
+    any: AnyRef<'a>,
+    mut f: impl FnMut(AnyRef<'a>) -> Result<(), ConversionError>,
+) -> Result<usize, ConversionError> {
+    let mut reader = SliceReader::new(any.value())?;
+    let mut count = 0;
+    while !reader.is_finished() {
+        f(AnyRef::decode(&mut reader)?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Fails with the [SizeLimitViolation] created by `violation`, if `actual` exceeds `limit`.
+fn check_limit(
+    violation: impl Fn(usize, usize) -> SizeLimitViolation,
+    limit: usize,
+    actual: usize,
+) -> Result<(), ConversionError> {
+    if actual > limit {
+        log::debug!("[SizeLimits] {}", violation(limit, actual));
+        return Err(violation(limit, actual).into());
+    }
+    Ok(())
+}
@@ -2,111 +2,184 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
 use der::asn1::BitString;
 use der::pem::LineEnding;
 use der::{Decode, DecodePem, Encode, EncodePem};
 use spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
-use x509_cert::name::{Name, RdnSequence};
+use x509_cert::name::Name;
 
-use crate::errors::ConversionError;
-use crate::types::der::asn1::Ia5String;
-use crate::{Constrained, ConstraintError, OID_RDN_DOMAIN_COMPONENT};
+use crate::errors::{ConversionError, ERR_MSG_TARGET_AMBIGUOUS};
+use crate::{
+    Constrained, ConstraintError, OID_RDN_DOMAIN_COMPONENT, OID_RDN_UID, OID_RDN_UNIQUE_IDENTIFIER,
+};
 
+use self::capabilities::Capabilities;
+
+#[cfg(feature = "rayon")]
+/// [validate_all](batch::validate_all()), verifying many certificates in parallel.
+pub mod batch;
+/// [IdCertRef](borrowed::IdCertRef) and [IdCsrRef](borrowed::IdCsrRef), read-only views of DER
+/// encoded certificates and CSRs which borrow their fields from the input.
+pub mod borrowed;
 /// Additional capabilities ([x509_cert::ext::Extensions] or [x509_cert::attr::Attributes], depending
 /// on the context) of X.509 certificates.
 pub mod capabilities;
+/// [CertId], a compact identifier for an [IdCert](idcert::IdCert), made up of its issuer and
+/// serial number.
+pub mod certid;
+/// [CertLog](certlog::CertLog), an append-only Merkle tree of issued certificates, with signed tree
+/// heads and inclusion and consistency proofs.
+pub mod certlog;
+/// [IdCertChain](chain::IdCertChain), an actor certificate together with the home server
+/// certificates which issued it, validated as a whole, and the [CertPool](chain::CertPool) chains
+/// are built from.
+pub mod chain;
+/// [CustomClaims](claims::CustomClaims), namespaced deployment-specific claims carried in a single
+/// polyproto private extension of an [IdCert](idcert::IdCert).
+pub mod claims;
+/// Certificate revocation lists ([IdCrl](crl::IdCrl)), which can be signed by the home server or by
+/// a delegated CRL issuer.
+pub mod crl;
+/// [CrlDistributionPoint](crldp::CrlDistributionPoint), the URL at which the issuer of an
+/// [IdCert](idcert::IdCert) publishes its certificate revocation lists.
+pub mod crldp;
+/// [CsrMetadata](csrmeta::CsrMetadata), carrying the creation time, expiry time and one-time
+/// nonce of a CSR, and the helpers to reject stale or replayed CSRs and to regenerate expired ones.
+pub mod csrmeta;
+#[cfg(feature = "types")]
+/// [CustodyBundle](custody::CustodyBundle)s, the signed and verifiable history of a single
+/// federation ID, covering issued certificates, rotations, revocations, migrations and aliases.
+pub mod custody;
+/// [DynIdCert](dynamic::DynIdCert) and [DynIdCsr](dynamic::DynIdCsr), ID-Certs and CSRs without
+/// generic parameters, whose signatures are verified using an
+/// [AlgorithmRegistry](crate::registry::AlgorithmRegistry).
+pub mod dynamic;
+/// [CertFingerprint](fingerprint::CertFingerprint)s, hashes of the DER encoding of certificates for
+/// display, comparison and compact references in logs.
+pub mod fingerprint;
+/// Short-lived guest actor certificates, and the [GuestProfile](guest::GuestProfile) used to
+/// validate them.
+pub mod guest;
 /// Complete, signed [IdCert]
 pub mod idcert;
 /// [IdCertTbs] is an [IdCert] which has not yet been signed by
 pub mod idcerttbs;
 /// Certificate Signing Request for an [IdCert]/[IdCertTbs]
 pub mod idcsr;
+/// [KeyIdentifier](keyid::KeyIdentifier)s, linking an [IdCert](idcert::IdCert) to the certificate
+/// of its issuer through the SubjectKeyIdentifier and AuthorityKeyIdentifier extensions.
+pub mod keyid;
+/// [SizeLimits](limits::SizeLimits) on the fields of certificates and CSRs, enforced when
+/// decoding them.
+pub mod limits;
+/// PEM labels distinguishing home server and actor certificates, and [load_any](pem::load_any()) for
+/// loading PEM documents of unknown kind and [Target].
+pub mod pem;
+/// RFC 7469 style public key pinning using [PinSet](pinning::PinSet)s of SPKI hashes.
+pub mod pinning;
+/// [ValidationPolicy](policy::ValidationPolicy), tightening or relaxing the constraints checked when
+/// validating certificates and CSRs.
+pub mod policy;
+#[cfg(feature = "rcgen")]
+/// Conversions between the `CertificateParams` of the `rcgen` crate and [IdCsr](idcsr::IdCsr)s and
+/// [IdCertTbs](idcerttbs::IdCertTbs), letting certificates described using `rcgen` be checked
+/// against the polyproto constraints.
+pub mod rcgen;
+/// [KeyRotation](rotation::KeyRotation), tracking the current and previous keys of an actor or home
+/// server across key rotations.
+pub mod rotation;
+#[cfg(feature = "rustls")]
+/// Conversions between [IdCert](idcert::IdCert)s and `rustls` certificates, and the
+/// [IdCertVerifier](rustls::IdCertVerifier), securing TLS connections using
+/// [IdCert](idcert::IdCert) chains.
+pub mod rustls;
+/// [SubjectAltNames](san::SubjectAltNames), the DNS names of the instance a home server
+/// [IdCert](idcert::IdCert) is valid for.
+pub mod san;
+/// [SecurityLevel](security::SecurityLevel)s, summarizing the strength of the key of an
+/// [IdCert](idcert::IdCert), and the [SecurityPolicy](security::SecurityPolicy) deriving them.
+pub mod security;
+/// [SerialNumberGenerator](serial::SerialNumberGenerator), generating serial numbers for issued
+/// certificates which conform to RFC 5280.
+pub mod serial;
+/// [SharedIdCert](shared::SharedIdCert), a reference counted [IdCert](idcert::IdCert) which is
+/// cheap to clone.
+pub mod shared;
+/// Online revocation status checking: signed [StatusRequest](status::StatusRequest)s and
+/// [StatusResponse](status::StatusResponse)s, the [RevocationCheck](status::RevocationCheck) trait
+/// used during chain validation, and the [RevocationCache](status::RevocationCache) over it.
+pub mod status;
+/// [SupersededNotice](superseded::SupersededNotice)s, which link a certificate to the certificate
+/// superseding it after a key rotation, and the [SupersededCerts](superseded::SupersededCerts) store.
+pub mod superseded;
+/// [IdCert::to_text()](idcert::IdCert::to_text), rendering certificates in a human-readable form
+/// modeled after `openssl x509 -text`.
+pub mod text;
+/// [TrustStore](truststore::TrustStore), pinning the root certificates or public keys trusted for
+/// the home server of each domain.
+pub mod truststore;
 
-/// polyproto client Session ID. Must be unique for each client. Must be between 1 and =32
-/// characters in length. The session ID is used to uniquely identify a client in the context of
-/// polyproto. Client certificates will change over time, but the session ID of a particular client
-/// will remain the same.
-///
-/// [Constrained] is implemented for this type, meaning it can be validated using `.validate()`.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SessionId {
-    /// The session ID, represented as an [Ia5String].
-    session_id: Ia5String,
-}
-
-impl Deref for SessionId {
-    type Target = Ia5String;
+/// [SessionId] has moved to [crate::types], and is re-exported here for compatibility.
+pub use crate::types::SessionId;
 
-    fn deref(&self) -> &Self::Target {
-        &self.session_id
-    }
-}
-
-impl DerefMut for SessionId {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.session_id
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// Whether something is intended for an actor or a home server.
+#[allow(missing_docs)]
+pub enum Target {
+    Actor,
+    HomeServer,
 }
 
-impl std::fmt::Display for SessionId {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.session_id.fmt(f)
+impl Target {
+    /// Detects the [Target] of a certificate or CSR from its [Capabilities]: Home servers are
+    /// certificate authorities, actors are not.
+    pub fn detect(capabilities: &Capabilities) -> Self {
+        if capabilities.basic_constraints.ca {
+            Target::HomeServer
+        } else {
+            Target::Actor
+        }
     }
-}
 
-impl SessionId {
-    #[allow(clippy::new_ret_no_self)]
-    /// Creates a new [SessionId] which can be converted into an [Attribute] using `.as_attribute()`,
-    /// if needed. Checks if the input is a valid Ia5String and if the [SessionId] constraints have
-    /// been violated.
-    pub fn new_validated(id: &str) -> Result<Self, ConstraintError> {
-        let ia5string = match der::asn1::Ia5String::new(id) {
-            Ok(string) => string,
-            Err(_) => {
+    /// Infers the [Target] of a certificate or CSR from its [Capabilities] and the structure of
+    /// its subject: Home servers are certificate authorities whose names carry neither a UID nor a
+    /// uniqueIdentifier, while actors are not certificate authorities, and their names carry
+    /// both.
+    ///
+    /// Unlike [Target::detect()], this fails if the capabilities and the subject contradict each
+    /// other, or if the subject carries only one of UID and uniqueIdentifier.
+    pub fn infer(capabilities: &Capabilities, subject: &Name) -> Result<Self, ConstraintError> {
+        let has_attribute = |oid: &str| {
+            subject
+                .0
+                .iter()
+                .flat_map(|rdn| rdn.0.iter())
+                .any(|attribute| attribute.oid.to_string() == oid)
+        };
+        let target = match (
+            capabilities.basic_constraints.ca,
+            has_attribute(OID_RDN_UID),
+            has_attribute(OID_RDN_UNIQUE_IDENTIFIER),
+        ) {
+            (true, false, false) => Target::HomeServer,
+            (false, true, true) => Target::Actor,
+            _ => {
+                log::debug!(
+                    "[Target::infer()] Cannot infer the target of subject {} from its capabilities",
+                    subject
+                );
                 return Err(ConstraintError::Malformed(Some(
-                    "Invalid Ia5String passed as SessionId".to_string(),
-                )))
+                    ERR_MSG_TARGET_AMBIGUOUS.to_string(),
+                )));
             }
         };
-
-        let session_id = SessionId {
-            session_id: ia5string.into(),
-        };
-        session_id.validate(None)?;
-        Ok(session_id)
-    }
-
-    /// Converts this [SessionId] into a [Name] for use in a certificate.
-    pub fn to_rdn_sequence(&self) -> Name {
-        RdnSequence::from_str(&format!("uniqueIdentifier={}", self)).unwrap()
+        log::trace!("[Target::infer()] Inferred target {:?}", target);
+        Ok(target)
     }
 }
 
-impl From<SessionId> for Ia5String {
-    fn from(value: SessionId) -> Self {
-        value.session_id
-    }
-}
-
-impl TryFrom<Ia5String> for SessionId {
-    type Error = ConstraintError;
-
-    fn try_from(value: Ia5String) -> Result<Self, Self::Error> {
-        SessionId::new_validated(value.to_string().as_str())
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-/// Whether something is intended for an actor or a home server.
-#[allow(missing_docs)]
-pub enum Target {
-    Actor,
-    HomeServer,
-}
-
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(u8)]
 /// `PKCS#10` version. From the PKCS specification document (RFC 2986):
@@ -179,6 +252,56 @@ impl From<PublicKeyInfo> for SubjectPublicKeyInfoOwned {
     }
 }
 
+/// Returns the domain made up of the domain components of `name`, in lowercase, e.g.
+/// `polyphony.chat` for `DC=polyphony,DC=chat`.
+pub fn domain_of(name: &Name) -> String {
+    // RDN sequences are encoded starting with the most significant component, the top level
+    // domain.
+    let mut components = name
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .filter(|attribute| attribute.oid.to_string() == OID_RDN_DOMAIN_COMPONENT)
+        .map(|attribute| String::from_utf8_lossy(attribute.value.value()).to_lowercase())
+        .collect::<Vec<_>>();
+    components.reverse();
+    components.join(".")
+}
+
+/// Builds the [Name] of an actor with the local name `local_name` on the home server at `domain`,
+/// whose client is identified by `session_id`, e.g.
+/// `CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1`.
+///
+/// Fails, if `local_name` or `domain` contain characters with a special meaning in distinguished
+/// names, or if the resulting [Name] is not a valid actor name.
+pub fn actor_name(
+    local_name: &str,
+    domain: &str,
+    session_id: &SessionId,
+) -> Result<Name, ConstraintError> {
+    if local_name
+        .chars()
+        .chain(domain.chars())
+        .any(|c| c.is_whitespace() || ",+=\"\\<>;#".contains(c))
+    {
+        return Err(ConstraintError::Malformed(Some(
+            "Local name and domain of an actor must not contain special characters".to_string(),
+        )));
+    }
+    let domain_components = domain
+        .split('.')
+        .map(|component| format!("DC={}", component))
+        .collect::<Vec<_>>()
+        .join(",");
+    let name = Name::from_str(&format!(
+        "CN={},{},UID={}@{},uniqueIdentifier={}",
+        local_name, domain_components, local_name, domain, session_id
+    ))
+    .map_err(|e| ConstraintError::Malformed(Some(e.to_string())))?;
+    name.validate(Some(Target::Actor))?;
+    Ok(name)
+}
+
 /// Checks, if the domain components of two [Name]s are equal and ordered in the same way. Returns
 /// `true`, if the domain components are equal, `false` otherwise.
 pub fn equal_domain_components(name_1: &Name, name_2: &Name) -> bool {
@@ -0,0 +1,133 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::errors::{InvalidCert, InvalidInput};
+use crate::key::{KeyFingerprint, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::chain::IdCertChain;
+use super::idcert::IdCert;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+/// A set of pinned public keys of a home server, in the spirit of RFC 7469.
+///
+/// Clients note the SPKI pins of the keys a home server is known to use, usually its current key
+/// and a backup key, and consult the [PinSet] whenever certificates of that home server are
+/// validated. A certificate which passes validation, but whose key is not pinned, indicates that
+/// the key of the home server has been substituted, e.g. by a compromised discovery path, and is
+/// rejected with [InvalidCert::PinMismatch].
+///
+/// Like RFC 7469 pins, a [PinSet] can expire, after which it no longer restricts validation. An
+/// empty [PinSet] does not restrict validation either.
+pub struct PinSet {
+    pins: Vec<KeyFingerprint>,
+    expires_at: Option<Timestamp>,
+}
+
+impl PinSet {
+    /// Creates an empty [PinSet] which does not expire.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `pin` to the set.
+    pub fn with_pin(mut self, pin: KeyFingerprint) -> Self {
+        self.add(pin);
+        self
+    }
+
+    /// Adds the pin given in the format of the `pin-sha256` directive of RFC 7469, a padded
+    /// base64 string, to the set.
+    pub fn with_pin_sha256(self, pin: &str) -> Result<Self, InvalidInput> {
+        Ok(self.with_pin(KeyFingerprint::from_pin_sha256(pin)?))
+    }
+
+    /// Lets the set expire at `expires_at`, the point in time at which the `max-age` of the pins
+    /// has passed.
+    pub fn with_expiry(mut self, expires_at: Timestamp) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Adds `pin` to the set. Returns `false`, if it was pinned before.
+    pub fn add(&mut self, pin: KeyFingerprint) -> bool {
+        if self.pins.contains(&pin) {
+            return false;
+        }
+        self.pins.push(pin);
+        true
+    }
+
+    /// Removes `pin` from the set. Returns `true`, if it was pinned.
+    pub fn remove(&mut self, pin: &KeyFingerprint) -> bool {
+        let len = self.pins.len();
+        self.pins.retain(|pinned| pinned != pin);
+        self.pins.len() != len
+    }
+
+    /// Returns the pins of the set.
+    pub fn pins(&self) -> &[KeyFingerprint] {
+        &self.pins
+    }
+
+    /// Returns the number of pins in the set.
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    /// Returns `true`, if the set holds no pins.
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Returns the point in time at which the set expires, if any.
+    pub fn expires_at(&self) -> Option<Timestamp> {
+        self.expires_at
+    }
+
+    /// Returns `true`, if the set restricts validation at `time`: it holds pins and has not
+    /// expired.
+    pub fn is_active(&self, time: Timestamp) -> bool {
+        !self.is_empty() && self.expires_at.map_or(true, |expires_at| time < expires_at)
+    }
+
+    /// Returns `true`, if the subject public key of `cert` is pinned.
+    pub fn matches<S: Signature, P: PublicKey<S>>(&self, cert: &IdCert<S, P>) -> bool {
+        cert.key_fingerprint()
+            .is_ok_and(|fingerprint| self.pins.contains(&fingerprint))
+    }
+
+    /// Checks, that the subject public key of `cert` is pinned, if the set is active at `time`.
+    ///
+    /// Fails with [InvalidCert::PinMismatch] otherwise.
+    pub fn check<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        if !self.is_active(time) || self.matches(cert) {
+            return Ok(());
+        }
+        log::debug!("[PinSet::check()] The key of the certificate is not pinned");
+        Err(InvalidCert::PinMismatch)
+    }
+
+    /// Checks, that the public key of at least one certificate of `chain` is pinned, if the set is
+    /// active at `time`. As in RFC 7469, this allows pinning the key of the root or of an
+    /// intermediate certificate.
+    ///
+    /// Fails with [InvalidCert::PinMismatch] otherwise.
+    pub fn check_chain<S: Signature, P: PublicKey<S>>(
+        &self,
+        chain: &IdCertChain<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        if !self.is_active(time) || chain.iter().any(|cert| self.matches(cert)) {
+            return Ok(());
+        }
+        log::debug!("[PinSet::check_chain()] No key of the certificate chain is pinned");
+        Err(InvalidCert::PinMismatch)
+    }
+}
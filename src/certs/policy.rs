@@ -0,0 +1,170 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use spki::ObjectIdentifier;
+use x509_cert::time::Validity;
+
+use crate::encoding::{check_canonical_der, check_strict_der};
+use crate::errors::{
+    ConstraintError, DerViolation, ERR_MSG_POLICY_ALGORITHM, ERR_MSG_POLICY_KEY_USAGE,
+};
+use crate::rules::{HasRules, RuleSet};
+
+use super::capabilities::{Capabilities, KeyUsage};
+use super::limits::SizeLimits;
+use super::Target;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// How strictly a [ValidationPolicy] checks the DER encoding of decoded certificates and CSRs.
+///
+/// Servers which store or forward the received bytes as the signed artifact should use
+/// [DerMode::Strict], so that a certificate or CSR has exactly one accepted encoding.
+pub enum DerMode {
+    /// The input must be a single, canonically encoded value: Trailing bytes, non-minimal or
+    /// indefinite lengths and constructed strings are rejected, and encoding the decoded value
+    /// again must reproduce the input byte for byte. Violations are reported as
+    /// [DerViolation]s.
+    Strict,
+    /// Every encoding accepted by the decoder is accepted, like with `from_der()`.
+    #[default]
+    Lenient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// Deployment-specific constraints applied on top of the polyproto specification when validating
+/// [IdCert](super::idcert::IdCert)s and [IdCsr](super::idcsr::IdCsr)s using their
+/// `validate_with()`, `from_der_with()` and `from_pem_with()` methods.
+///
+/// The default policy neither tightens nor relaxes anything, so that validating with it is
+/// equivalent to using [Constrained::validate()](crate::Constrained::validate()) and
+/// `from_der()`. Rules of the specification can be relaxed by listing their IDs in
+/// `disabled_rules`; see the [rules](crate::rules) module for the available rules.
+pub struct ValidationPolicy {
+    /// The Object Identifiers of the signature algorithms certificates and CSRs may be signed
+    /// with. `None` allows all algorithms.
+    pub allowed_algorithms: Option<Vec<ObjectIdentifier>>,
+    /// The longest validity period a certificate may have. `None` allows any validity period.
+    pub max_validity: Option<Duration>,
+    /// The [KeyUsage]s actor certificates and CSRs must carry.
+    pub required_actor_key_usages: Vec<KeyUsage>,
+    /// The [KeyUsage]s home server certificates and CSRs must carry.
+    pub required_home_server_key_usages: Vec<KeyUsage>,
+    /// The clock skew tolerated when checking the validity period of a certificate in
+    /// `from_der_with()` and `from_pem_with()`. See
+    /// [IdCert::valid_at_with_tolerance()](super::idcert::IdCert::valid_at_with_tolerance()).
+    pub clock_skew_tolerance: Duration,
+    /// The [SizeLimits] enforced in `from_der_with()` and `from_pem_with()`, before decoding the
+    /// input.
+    pub size_limits: SizeLimits,
+    /// How strictly the DER encoding is checked in `from_der_with()` and `from_pem_with()`.
+    pub der_mode: DerMode,
+    /// The IDs of the [Rule](crate::rules::Rule)s which are not checked.
+    pub disabled_rules: Vec<&'static str>,
+}
+
+impl ValidationPolicy {
+    /// Returns the [KeyUsage]s certificates and CSRs for `target` must carry.
+    pub fn required_key_usages(&self, target: Target) -> &[KeyUsage] {
+        match target {
+            Target::Actor => &self.required_actor_key_usages,
+            Target::HomeServer => &self.required_home_server_key_usages,
+        }
+    }
+
+    /// Returns the rules of `T`, without the disabled rules.
+    pub(crate) fn rules<T: HasRules>(&self) -> RuleSet<T> {
+        T::rules().without_rules(&self.disabled_rules)
+    }
+
+    /// Checks the constraints of this policy which apply to certificates and CSRs alike.
+    pub(crate) fn check_common(
+        &self,
+        signature_algorithm: ObjectIdentifier,
+        capabilities: &Capabilities,
+        target: Option<Target>,
+    ) -> Result<(), ConstraintError> {
+        if let Some(allowed_algorithms) = &self.allowed_algorithms {
+            if !allowed_algorithms.contains(&signature_algorithm) {
+                log::debug!(
+                    "[ValidationPolicy] Signature algorithm {} is not allowed",
+                    signature_algorithm
+                );
+                return Err(ConstraintError::Malformed(Some(format!(
+                    "{} Found: {}",
+                    ERR_MSG_POLICY_ALGORITHM, signature_algorithm
+                ))));
+            }
+        }
+        let target = match target {
+            Some(target) => target,
+            None => return Ok(()),
+        };
+        for key_usage in self.required_key_usages(target) {
+            if !capabilities.key_usage.key_usages.contains(key_usage) {
+                log::debug!(
+                    "[ValidationPolicy] Required key usage {:?} is missing",
+                    key_usage
+                );
+                return Err(ConstraintError::Malformed(Some(format!(
+                    "{} Missing: {:?}",
+                    ERR_MSG_POLICY_KEY_USAGE, key_usage
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the validity period of a certificate against `max_validity`.
+    pub(crate) fn check_validity(&self, validity: &Validity) -> Result<(), ConstraintError> {
+        let max_validity = match self.max_validity {
+            Some(max_validity) => max_validity,
+            None => return Ok(()),
+        };
+        let lifetime = validity
+            .not_after
+            .to_unix_duration()
+            .saturating_sub(validity.not_before.to_unix_duration());
+        if lifetime > max_validity {
+            return Err(ConstraintError::OutOfBounds {
+                lower: 0,
+                upper: max_validity.as_secs().min(i32::MAX as u64) as i32,
+                actual: lifetime.as_secs().to_string(),
+                reason: "The validity period of the certificate exceeds the maximum allowed by the validation policy".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks `input` for BER artifacts before it is decoded, if the [DerMode] is
+    /// [DerMode::Strict].
+    pub(crate) fn check_der_input(&self, input: &[u8]) -> Result<(), DerViolation> {
+        if self.der_mode == DerMode::Strict {
+            check_strict_der(input).map_err(|violation| {
+                log::debug!(
+                    "[ValidationPolicy] Input is not encoded in DER: {}",
+                    violation
+                );
+                violation
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks that `encoded`, the encoding of the value decoded from `input`, is equal to `input`,
+    /// if the [DerMode] is [DerMode::Strict].
+    pub(crate) fn check_der(&self, input: &[u8], encoded: &[u8]) -> Result<(), DerViolation> {
+        if self.der_mode == DerMode::Strict {
+            check_canonical_der(input, encoded).map_err(|violation| {
+                log::debug!(
+                    "[ValidationPolicy] Input is not encoded in canonical DER: {}",
+                    violation
+                );
+                violation
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,534 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use ::rcgen::{
+    BasicConstraints as RcgenBasicConstraints, CertificateParams,
+    CustomExtension as RcgenCustomExtension, DistinguishedName, DnType, DnValue,
+    ExtendedKeyUsagePurpose, Ia5String, IsCa, KeyUsagePurpose, PrintableString, SanType,
+    SerialNumber,
+};
+use der::asn1::{Any, SetOfVec, Uint};
+use der::Tag;
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier};
+use time::OffsetDateTime;
+use x509_cert::attr::AttributeTypeAndValue;
+use x509_cert::ext::Extension;
+use x509_cert::name::{Name, RdnSequence, RelativeDistinguishedName};
+use x509_cert::time::{Time, Validity};
+
+use crate::errors::{ConversionError, InvalidInput};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::{Constrained, OID_RDN_DOMAIN_COMPONENT};
+
+use super::capabilities::{
+    BasicConstraints, Capabilities, CustomExtension, ExtendedKeyUsage, ExtendedKeyUsages, KeyUsage,
+    KeyUsages,
+};
+use super::claims::{CustomClaims, OID_CUSTOM_CLAIMS};
+use super::crldp::{CrlDistributionPoint, OID_CRL_DISTRIBUTION_POINTS};
+use super::idcerttbs::IdCertTbs;
+use super::idcsr::IdCsr;
+use super::keyid::KeyIdentifier;
+use super::san::SubjectAltNames;
+use super::Target;
+
+/// Object Identifier of the `codeSigning` extended key usage, which polyproto expresses as
+/// [ExtendedKeyUsage::Other].
+const OID_EXTENDED_KEY_USAGE_CODE_SIGNING: &str = "1.3.6.1.5.5.7.3.3";
+/// Object Identifier of the `emailProtection` extended key usage, which polyproto expresses as
+/// [ExtendedKeyUsage::Other].
+const OID_EXTENDED_KEY_USAGE_EMAIL_PROTECTION: &str = "1.3.6.1.5.5.7.3.4";
+
+impl<S: Signature, P: PublicKey<S>> IdCsr<S, P> {
+    /// Creates a new [IdCsr] for the subject and capabilities described by `params`, signed using
+    /// `signing_key`, like [IdCsr::new()]. An [IdCsr] carries neither a validity period, a serial
+    /// number nor SubjectAltNames, so these fields of `params` are ignored.
+    pub fn from_rcgen_params(
+        params: &CertificateParams,
+        signing_key: &impl PrivateKey<S, PublicKey = P>,
+        target: Option<Target>,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[IdCsr::from_rcgen_params()] Converting rcgen CertificateParams");
+        IdCsr::new(
+            &name_from_distinguished_name(&params.distinguished_name)?,
+            signing_key,
+            &Capabilities::try_from(params)?,
+            target,
+        )
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> IdCertTbs<S, P> {
+    /// Creates a new [IdCertTbs] for `subject_public_key` from the subject, validity period,
+    /// serial number, capabilities and SubjectAltNames described by `params`. Custom claims and
+    /// CRLDistributionPoints are taken from the [custom extensions](CertificateParams::custom_extensions)
+    /// of `params`, if present. Like [IdCertTbs::from_actor_csr()], the subject key identifier is
+    /// derived from `subject_public_key`, and the result is validated for `target`.
+    ///
+    /// Fails, if `params` has no serial number, if it uses features of `rcgen` polyproto does not
+    /// support, such as name constraints or SubjectAltNames other than DNS names, or if the
+    /// resulting [IdCertTbs] does not pass [Constrained] verification.
+    pub fn from_rcgen_params(
+        params: &CertificateParams,
+        subject_public_key: P,
+        signature_algorithm: AlgorithmIdentifierOwned,
+        issuer: Name,
+        target: Option<Target>,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[IdCertTbs::from_rcgen_params()] Converting rcgen CertificateParams");
+        if params.name_constraints.is_some() {
+            return Err(unsupported("Name constraints"));
+        }
+        if !params.crl_distribution_points.is_empty() {
+            return Err(InvalidInput::Malformed(
+                "CRL distribution points must be passed as a custom extension".to_string(),
+            )
+            .into());
+        }
+        let serial_number = match &params.serial_number {
+            Some(serial_number) => Uint::new(&serial_number.to_bytes())?,
+            None => {
+                return Err(InvalidInput::Malformed(
+                    "The CertificateParams do not specify a serial number".to_string(),
+                )
+                .into())
+            }
+        };
+        let mut subject_alt_names = SubjectAltNames::new();
+        for san in params.subject_alt_names.iter() {
+            match san {
+                SanType::DnsName(name) => {
+                    subject_alt_names.add_dns_name(name.as_str())?;
+                }
+                _ => return Err(unsupported("SubjectAltNames other than DNS names")),
+            }
+        }
+        let mut claims = CustomClaims::new();
+        let mut crl_distribution_point = None;
+        for extension in params.custom_extensions.iter() {
+            let oid = oid_from_arcs(extension.oid_components())?;
+            match oid.to_string().as_str() {
+                OID_CUSTOM_CLAIMS => {
+                    claims = CustomClaims::try_from(extension_from_rcgen(oid, extension)?)?
+                }
+                OID_CRL_DISTRIBUTION_POINTS => {
+                    crl_distribution_point = Some(CrlDistributionPoint::try_from(
+                        extension_from_rcgen(oid, extension)?,
+                    )?)
+                }
+                _ => (),
+            }
+        }
+        let cert_tbs = IdCertTbs {
+            serial_number,
+            signature_algorithm,
+            issuer,
+            validity: Validity {
+                not_before: time_from_offset_date_time(params.not_before)?,
+                not_after: time_from_offset_date_time(params.not_after)?,
+            },
+            subject: name_from_distinguished_name(&params.distinguished_name)?,
+            subject_key_identifier: Some(KeyIdentifier::from_public_key(&subject_public_key)),
+            subject_public_key,
+            capabilities: Capabilities::try_from(params)?,
+            claims,
+            crl_distribution_point,
+            authority_key_identifier: None,
+            subject_alt_names,
+            s: PhantomData,
+        };
+        cert_tbs.validate(target)?;
+        Ok(cert_tbs)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> TryFrom<&IdCsr<S, P>> for CertificateParams {
+    type Error = ConversionError;
+
+    /// Converts the subject and capabilities of an [IdCsr] into [CertificateParams]. The validity
+    /// period and serial number are left at the defaults of `rcgen`, as they are chosen by the
+    /// issuer.
+    fn try_from(value: &IdCsr<S, P>) -> Result<Self, Self::Error> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name_from_name(&value.inner_csr.subject)?;
+        apply_capabilities(&mut params, &value.inner_csr.capabilities)?;
+        Ok(params)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> TryFrom<&IdCertTbs<S, P>> for CertificateParams {
+    type Error = ConversionError;
+
+    /// Converts an [IdCertTbs] into [CertificateParams]. Custom claims and the
+    /// CRLDistributionPoint are carried over as custom extensions. `rcgen` derives the key
+    /// identifiers itself when signing, so only whether an authority key identifier is present is
+    /// carried over.
+    fn try_from(value: &IdCertTbs<S, P>) -> Result<Self, Self::Error> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name_from_name(&value.subject)?;
+        params.not_before = offset_date_time_from_time(value.validity.not_before)?;
+        params.not_after = offset_date_time_from_time(value.validity.not_after)?;
+        params.serial_number = Some(SerialNumber::from_slice(value.serial_number.as_bytes()));
+        params.subject_alt_names = value
+            .subject_alt_names
+            .dns_names()
+            .iter()
+            .map(|name| Ok(SanType::DnsName(ia5_string(name)?)))
+            .collect::<Result<Vec<_>, ConversionError>>()?;
+        apply_capabilities(&mut params, &value.capabilities)?;
+        if !value.claims.is_empty() {
+            params
+                .custom_extensions
+                .push(extension_to_rcgen(Extension::try_from(
+                    value.claims.clone(),
+                )?));
+        }
+        if let Some(distribution_point) = &value.crl_distribution_point {
+            params
+                .custom_extensions
+                .push(extension_to_rcgen(Extension::try_from(
+                    distribution_point.clone(),
+                )?));
+        }
+        params.use_authority_key_identifier_extension = value.authority_key_identifier.is_some();
+        Ok(params)
+    }
+}
+
+impl TryFrom<&CertificateParams> for Capabilities {
+    type Error = ConversionError;
+
+    /// Converts the key usages, basic constraints, extended key usages and custom extensions of
+    /// [CertificateParams] into [Capabilities]. Custom extensions carrying custom claims or a
+    /// CRLDistributionPoint are skipped, as they are not part of the [Capabilities] of a
+    /// certificate.
+    fn try_from(value: &CertificateParams) -> Result<Self, Self::Error> {
+        let key_usages = value
+            .key_usages
+            .iter()
+            .map(|key_usage| match key_usage {
+                KeyUsagePurpose::DigitalSignature => KeyUsage::DigitalSignature,
+                KeyUsagePurpose::ContentCommitment => KeyUsage::ContentCommitment,
+                KeyUsagePurpose::KeyEncipherment => KeyUsage::KeyEncipherment,
+                KeyUsagePurpose::DataEncipherment => KeyUsage::DataEncipherment,
+                KeyUsagePurpose::KeyAgreement => KeyUsage::KeyAgreement,
+                KeyUsagePurpose::KeyCertSign => KeyUsage::KeyCertSign,
+                KeyUsagePurpose::CrlSign => KeyUsage::CrlSign,
+                KeyUsagePurpose::EncipherOnly => KeyUsage::EncipherOnly,
+                KeyUsagePurpose::DecipherOnly => KeyUsage::DecipherOnly,
+            })
+            .collect::<Vec<_>>();
+        let basic_constraints = match &value.is_ca {
+            IsCa::NoCa | IsCa::ExplicitNoCa => BasicConstraints {
+                ca: false,
+                path_length: None,
+            },
+            IsCa::Ca(RcgenBasicConstraints::Unconstrained) => BasicConstraints {
+                ca: true,
+                path_length: None,
+            },
+            IsCa::Ca(RcgenBasicConstraints::Constrained(length)) => BasicConstraints {
+                ca: true,
+                path_length: Some(*length as u64),
+            },
+        };
+        let mut extended_key_usages = Vec::new();
+        for purpose in value.extended_key_usages.iter() {
+            extended_key_usages.push(match purpose {
+                ExtendedKeyUsagePurpose::Any => ExtendedKeyUsage::AnyExtendedKeyUsage,
+                ExtendedKeyUsagePurpose::ServerAuth => ExtendedKeyUsage::ServerAuth,
+                ExtendedKeyUsagePurpose::ClientAuth => ExtendedKeyUsage::ClientAuth,
+                ExtendedKeyUsagePurpose::TimeStamping => ExtendedKeyUsage::TimeStamping,
+                ExtendedKeyUsagePurpose::OcspSigning => ExtendedKeyUsage::OcspSigning,
+                ExtendedKeyUsagePurpose::CodeSigning => ExtendedKeyUsage::Other(
+                    ObjectIdentifier::from_str(OID_EXTENDED_KEY_USAGE_CODE_SIGNING)?,
+                ),
+                ExtendedKeyUsagePurpose::EmailProtection => ExtendedKeyUsage::Other(
+                    ObjectIdentifier::from_str(OID_EXTENDED_KEY_USAGE_EMAIL_PROTECTION)?,
+                ),
+                ExtendedKeyUsagePurpose::Other(arcs) => {
+                    ExtendedKeyUsage::Other(oid_from_arcs(arcs.iter().copied())?)
+                }
+            });
+        }
+        let mut custom_extensions = Vec::new();
+        for extension in value.custom_extensions.iter() {
+            let oid = oid_from_arcs(extension.oid_components())?;
+            if [OID_CUSTOM_CLAIMS, OID_CRL_DISTRIBUTION_POINTS].contains(&oid.to_string().as_str())
+            {
+                continue;
+            }
+            custom_extensions.push(CustomExtension::new(
+                oid,
+                extension.criticality(),
+                extension.content(),
+            )?);
+        }
+        Ok(Capabilities {
+            key_usage: KeyUsages::new(&key_usages),
+            basic_constraints,
+            extended_key_usage: ExtendedKeyUsages::new(&extended_key_usages),
+            custom_extensions,
+        })
+    }
+}
+
+/// Converts a [Name] into an `rcgen` [DistinguishedName]. All domain components of `name` are
+/// joined into a single `DC` attribute holding the dotted domain, placed where the first domain
+/// component of `name` is.
+///
+/// Fails, if `name` contains a multi-valued RDN, an attribute type other than `DC` more than once,
+/// or a value which is neither a `UTF8String`, an `IA5String` nor a `PrintableString`, as `rcgen`
+/// cannot represent these.
+pub fn distinguished_name_from_name(name: &Name) -> Result<DistinguishedName, ConversionError> {
+    let domain_component = DnType::CustomDnType(oid_to_arcs(&ObjectIdentifier::from_str(
+        OID_RDN_DOMAIN_COMPONENT,
+    )?));
+    let mut domain_components = Vec::new();
+    let mut distinguished_name = DistinguishedName::new();
+    for rdn in name.0.iter() {
+        if rdn.0.len() != 1 {
+            return Err(unsupported("Multi-valued RDNs"));
+        }
+        let attribute = rdn.0.get(0).expect("The RDN holds exactly one attribute");
+        if attribute.oid.to_string() == OID_RDN_DOMAIN_COMPONENT {
+            if domain_components.is_empty() {
+                // Reserve the position of the domain components, filled in below.
+                distinguished_name
+                    .push(domain_component.clone(), DnValue::Utf8String(String::new()));
+            }
+            domain_components.push(String::from_utf8_lossy(attribute.value.value()).to_string());
+            continue;
+        }
+        let dn_type = DnType::from_oid(&oid_to_arcs(&attribute.oid));
+        if distinguished_name.get(&dn_type).is_some() {
+            return Err(InvalidInput::Malformed(format!(
+                "The attribute {} is present more than once, which rcgen cannot represent",
+                attribute.oid
+            ))
+            .into());
+        }
+        let value = std::str::from_utf8(attribute.value.value())
+            .map_err(|e| InvalidInput::Malformed(e.to_string()))?;
+        let value = match der::Tagged::tag(&attribute.value) {
+            Tag::Utf8String => DnValue::Utf8String(value.to_string()),
+            Tag::Ia5String => DnValue::Ia5String(ia5_string(value)?),
+            Tag::PrintableString => DnValue::PrintableString(
+                PrintableString::try_from(value)
+                    .map_err(|e| InvalidInput::Malformed(e.to_string()))?,
+            ),
+            tag => {
+                return Err(InvalidInput::Malformed(format!(
+                    "The value of the attribute {} is a {}, which is not supported",
+                    attribute.oid, tag
+                ))
+                .into())
+            }
+        };
+        distinguished_name.push(dn_type, value);
+    }
+    if !domain_components.is_empty() {
+        domain_components.reverse();
+        distinguished_name.push(
+            domain_component,
+            DnValue::Ia5String(ia5_string(&domain_components.join("."))?),
+        );
+    }
+    Ok(distinguished_name)
+}
+
+/// Converts an `rcgen` [DistinguishedName] into a [Name], keeping the order of its attributes. A
+/// `DC` attribute holding a dotted domain, such as `polyphony.chat`, is split into one domain
+/// component per label.
+///
+/// Fails, if an attribute value is neither a `UTF8String`, an `IA5String` nor a
+/// `PrintableString`.
+pub fn name_from_distinguished_name(
+    distinguished_name: &DistinguishedName,
+) -> Result<Name, ConversionError> {
+    let mut rdns = Vec::new();
+    for (dn_type, value) in distinguished_name.iter() {
+        let oid = dn_type_oid(dn_type)?;
+        let (tag, value) = match value {
+            DnValue::Utf8String(value) => (Tag::Utf8String, value.as_str()),
+            DnValue::Ia5String(value) => (Tag::Ia5String, value.as_str()),
+            DnValue::PrintableString(value) => (Tag::PrintableString, value.as_str()),
+            _ => {
+                return Err(InvalidInput::Malformed(format!(
+                    "The value of the attribute {} uses a string type which is not supported",
+                    oid
+                ))
+                .into())
+            }
+        };
+        if oid.to_string() == OID_RDN_DOMAIN_COMPONENT {
+            for label in value.split('.').rev() {
+                rdns.push(rdn(oid, Tag::Ia5String, label)?);
+            }
+        } else {
+            rdns.push(rdn(oid, tag, value)?);
+        }
+    }
+    Ok(RdnSequence(rdns))
+}
+
+/// Sets the key usages, basic constraints, extended key usages and custom extensions of `params`
+/// to those of `capabilities`.
+fn apply_capabilities(
+    params: &mut CertificateParams,
+    capabilities: &Capabilities,
+) -> Result<(), ConversionError> {
+    params.key_usages = capabilities
+        .key_usage
+        .key_usages
+        .iter()
+        .map(|key_usage| match key_usage {
+            KeyUsage::DigitalSignature => KeyUsagePurpose::DigitalSignature,
+            KeyUsage::ContentCommitment => KeyUsagePurpose::ContentCommitment,
+            KeyUsage::KeyEncipherment => KeyUsagePurpose::KeyEncipherment,
+            KeyUsage::DataEncipherment => KeyUsagePurpose::DataEncipherment,
+            KeyUsage::KeyAgreement => KeyUsagePurpose::KeyAgreement,
+            KeyUsage::KeyCertSign => KeyUsagePurpose::KeyCertSign,
+            KeyUsage::CrlSign => KeyUsagePurpose::CrlSign,
+            KeyUsage::EncipherOnly => KeyUsagePurpose::EncipherOnly,
+            KeyUsage::DecipherOnly => KeyUsagePurpose::DecipherOnly,
+        })
+        .collect();
+    let basic_constraints = &capabilities.basic_constraints;
+    params.is_ca = match (basic_constraints.ca, basic_constraints.path_length) {
+        (false, _) => IsCa::ExplicitNoCa,
+        (true, None) => IsCa::Ca(RcgenBasicConstraints::Unconstrained),
+        (true, Some(length)) => IsCa::Ca(RcgenBasicConstraints::Constrained(
+            u8::try_from(length).map_err(|_| {
+                InvalidInput::Malformed(format!(
+                    "rcgen only supports path lengths up to {}, but the path length is {}",
+                    u8::MAX,
+                    length
+                ))
+            })?,
+        )),
+    };
+    params.extended_key_usages = capabilities
+        .extended_key_usage
+        .extended_key_usages
+        .iter()
+        .map(|purpose| match purpose {
+            ExtendedKeyUsage::ServerAuth => ExtendedKeyUsagePurpose::ServerAuth,
+            ExtendedKeyUsage::ClientAuth => ExtendedKeyUsagePurpose::ClientAuth,
+            ExtendedKeyUsage::TimeStamping => ExtendedKeyUsagePurpose::TimeStamping,
+            ExtendedKeyUsage::OcspSigning => ExtendedKeyUsagePurpose::OcspSigning,
+            ExtendedKeyUsage::AnyExtendedKeyUsage => ExtendedKeyUsagePurpose::Any,
+            ExtendedKeyUsage::Other(oid) => ExtendedKeyUsagePurpose::Other(oid_to_arcs(oid)),
+        })
+        .collect();
+    params.custom_extensions = capabilities
+        .custom_extensions
+        .iter()
+        .map(|extension| {
+            let mut custom_extension = RcgenCustomExtension::from_oid_content(
+                &oid_to_arcs(&extension.oid()),
+                extension.value().to_vec(),
+            );
+            custom_extension.set_criticality(extension.is_critical());
+            custom_extension
+        })
+        .collect();
+    Ok(())
+}
+
+/// Returns the Object Identifier of an `rcgen` [DnType].
+fn dn_type_oid(dn_type: &DnType) -> Result<ObjectIdentifier, ConversionError> {
+    let oid = match dn_type {
+        DnType::CountryName => "2.5.4.6",
+        DnType::LocalityName => "2.5.4.7",
+        DnType::StateOrProvinceName => "2.5.4.8",
+        DnType::OrganizationName => "2.5.4.10",
+        DnType::OrganizationalUnitName => "2.5.4.11",
+        DnType::CommonName => "2.5.4.3",
+        DnType::CustomDnType(arcs) => return oid_from_arcs(arcs.iter().copied()),
+        _ => return Err(unsupported("Attribute types unknown to polyproto")),
+    };
+    Ok(ObjectIdentifier::from_str(oid)?)
+}
+
+/// Creates a single-valued [RelativeDistinguishedName].
+fn rdn(
+    oid: ObjectIdentifier,
+    tag: Tag,
+    value: &str,
+) -> Result<RelativeDistinguishedName, ConversionError> {
+    let attribute = AttributeTypeAndValue {
+        oid,
+        value: Any::new(tag, value.as_bytes())?,
+    };
+    Ok(RelativeDistinguishedName(SetOfVec::try_from(vec![
+        attribute,
+    ])?))
+}
+
+fn oid_to_arcs(oid: &ObjectIdentifier) -> Vec<u64> {
+    oid.arcs().map(u64::from).collect()
+}
+
+fn oid_from_arcs(arcs: impl Iterator<Item = u64>) -> Result<ObjectIdentifier, ConversionError> {
+    let arcs = arcs
+        .map(|arc| {
+            u32::try_from(arc).map_err(|_| {
+                InvalidInput::Malformed(format!("The OID arc {} is out of range", arc))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ObjectIdentifier::from_arcs(arcs)?)
+}
+
+fn extension_from_rcgen(
+    oid: ObjectIdentifier,
+    extension: &RcgenCustomExtension,
+) -> Result<Extension, ConversionError> {
+    Ok(Extension {
+        extn_id: oid,
+        critical: extension.criticality(),
+        extn_value: der::asn1::OctetString::new(extension.content())?,
+    })
+}
+
+fn extension_to_rcgen(extension: Extension) -> RcgenCustomExtension {
+    let mut custom_extension = RcgenCustomExtension::from_oid_content(
+        &oid_to_arcs(&extension.extn_id),
+        extension.extn_value.into_bytes(),
+    );
+    custom_extension.set_criticality(extension.critical);
+    custom_extension
+}
+
+fn ia5_string(value: &str) -> Result<Ia5String, ConversionError> {
+    Ia5String::try_from(value).map_err(|e| InvalidInput::Malformed(e.to_string()).into())
+}
+
+fn time_from_offset_date_time(time: OffsetDateTime) -> Result<Time, ConversionError> {
+    Time::try_from(Timestamp::from(time))
+}
+
+fn offset_date_time_from_time(time: Time) -> Result<OffsetDateTime, ConversionError> {
+    let seconds = Timestamp::from(time).unix_seconds();
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| OffsetDateTime::from_unix_timestamp(seconds).ok())
+        .ok_or_else(|| {
+            InvalidInput::Malformed(format!(
+                "{} seconds after the Unix epoch are out of range",
+                seconds
+            ))
+            .into()
+        })
+}
+
+fn unsupported(feature: &str) -> ConversionError {
+    InvalidInput::Malformed(format!("{} are not supported by polyproto", feature)).into()
+}
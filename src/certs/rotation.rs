@@ -0,0 +1,387 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use x509_cert::time::Validity;
+
+use crate::errors::{ConversionError, InvalidInput};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::{Timestamp, ValidityExt};
+use crate::Constrained;
+
+use super::idcert::IdCert;
+use super::idcerttbs::IdCertTbs;
+use super::idcsr::IdCsr;
+use super::Target;
+
+/// The default overlap window of a [KeyRotation]: one week.
+pub const DEFAULT_OVERLAP: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// The key of the [CustomClaims](super::claims::CustomClaims) claim marking a cross-certificate
+/// issued using [cross_sign()].
+pub const CROSS_SIGNED_CLAIM: &str = "org.polyproto/cross-signed";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An [IdCert] together with the private key it certifies, as tracked by a [KeyRotation].
+pub struct RotatedKey<S: Signature, K: PrivateKey<S>> {
+    /// The certificate of the key.
+    pub id_cert: IdCert<S, K::PublicKey>,
+    /// The private key.
+    pub private_key: K,
+    /// The point in time at which the key was replaced by a new key, or `None`, if it is the
+    /// current key.
+    pub retired_at: Option<Timestamp>,
+}
+
+impl<S: Signature, K: PrivateKey<S>> RotatedKey<S, K> {
+    /// Returns the point in time until which this key may be used to verify material. For the
+    /// current key, this is the end of the validity period of its certificate. For a retired key,
+    /// it is the end of the overlap window following its retirement, but never later than the end
+    /// of the validity period of its certificate.
+    pub fn verifies_until(&self, overlap: Duration) -> Timestamp {
+        let not_after = Timestamp::from(self.id_cert.id_cert_tbs.validity.not_after);
+        match self.retired_at.and_then(|time| time.checked_add(overlap)) {
+            Some(end) if end < not_after => end,
+            _ => not_after,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The two cross-certificates of a key rollover of a home server, as issued by
+/// [KeyRotation::cross_sign()].
+pub struct CrossCerts<S: Signature, P: PublicKey<S>> {
+    /// The certificate of the new key, signed with the previous key. Lets clients which only
+    /// trust the previous key accept material issued under the new key.
+    pub new_by_previous: IdCert<S, P>,
+    /// The certificate of the previous key, signed with the new key. Lets clients which already
+    /// trust the new key accept material issued under the previous key.
+    pub previous_by_new: IdCert<S, P>,
+}
+
+/// Tracks the keys of a single actor or home server across key rotations: the current
+/// [IdCert] and private key, and the previous ones, which may still verify material during an
+/// overlap window after they have been replaced. Use one [KeyRotation] per identity.
+///
+/// A rotation consists of two steps:
+///
+/// 1. [KeyRotation::begin()] creates a re-keying [IdCsr] for a freshly generated key, with the
+///    subject and capabilities of the current certificate. The CSR is sent to the home server.
+/// 2. [KeyRotation::complete()] installs the certificate issued for the CSR as the current
+///    certificate. The previous key is retired, and may still verify material for the overlap
+///    window.
+///
+/// New material must always be signed with [KeyRotation::signing_key()]. A rotation is due once
+/// the overlap window before the end of the validity period of the current certificate has
+/// begun, so that the new certificate is in place before the current one expires; see
+/// [KeyRotation::rotation_due_at()].
+pub struct KeyRotation<S: Signature, K: PrivateKey<S>> {
+    current: RotatedKey<S, K>,
+    previous: Vec<RotatedKey<S, K>>,
+    pending: Option<K>,
+    overlap: Duration,
+}
+
+impl<S: Signature + std::fmt::Debug, K: PrivateKey<S> + std::fmt::Debug> std::fmt::Debug
+    for KeyRotation<S, K>
+where
+    K::PublicKey: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyRotation")
+            .field("current", &self.current)
+            .field("previous", &self.previous)
+            .field("pending", &self.pending)
+            .field("overlap", &self.overlap)
+            .finish()
+    }
+}
+
+impl<S: Signature, K: PrivateKey<S> + Clone> Clone for KeyRotation<S, K> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+            previous: self.previous.clone(),
+            pending: self.pending.clone(),
+            overlap: self.overlap,
+        }
+    }
+}
+
+impl<S: Signature, K: PrivateKey<S>> KeyRotation<S, K> {
+    /// Starts tracking the keys of the subject of `id_cert`, with `private_key` as the current
+    /// key and the [DEFAULT_OVERLAP]. Fails, if `private_key` does not belong to the subject of
+    /// `id_cert`.
+    pub fn new(id_cert: IdCert<S, K::PublicKey>, private_key: K) -> Result<Self, ConversionError> {
+        check_key(&id_cert, &private_key)?;
+        Ok(Self {
+            current: RotatedKey {
+                id_cert,
+                private_key,
+                retired_at: None,
+            },
+            previous: Vec::new(),
+            pending: None,
+            overlap: DEFAULT_OVERLAP,
+        })
+    }
+
+    /// Sets the overlap window: the time before the end of the validity period of the current
+    /// certificate at which a rotation becomes due, and the time for which a retired key may
+    /// still verify material.
+    pub fn with_overlap(mut self, overlap: Duration) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Returns the overlap window.
+    pub fn overlap(&self) -> Duration {
+        self.overlap
+    }
+
+    /// Returns the current certificate and key.
+    pub fn current(&self) -> &RotatedKey<S, K> {
+        &self.current
+    }
+
+    /// Returns the retired certificates and keys, from the most recently retired to the least
+    /// recently retired one.
+    pub fn previous(&self) -> &[RotatedKey<S, K>] {
+        &self.previous
+    }
+
+    /// Returns the key which must sign new material: the current key.
+    pub fn signing_key(&self) -> &K {
+        &self.current.private_key
+    }
+
+    /// Returns the certificates and keys which may verify material at `time`: the current one, if
+    /// its certificate is valid at `time`, followed by all retired ones still within their overlap
+    /// window.
+    pub fn verifying_keys(&self, time: Timestamp) -> Vec<&RotatedKey<S, K>> {
+        std::iter::once(&self.current)
+            .chain(self.previous.iter())
+            .filter(|key| key.id_cert.valid_at(time) && time <= key.verifies_until(self.overlap))
+            .collect()
+    }
+
+    /// Returns the point in time at which a rotation becomes due: the overlap window before the
+    /// end of the validity period of the current certificate.
+    pub fn rotation_due_at(&self) -> Timestamp {
+        let not_after = Timestamp::from(self.current.id_cert.id_cert_tbs.validity.not_after);
+        not_after
+            .checked_sub(self.overlap)
+            .unwrap_or(Timestamp::from_unix_seconds(0))
+    }
+
+    /// Returns `true`, if a rotation is due at `time` and no rotation is in progress.
+    pub fn needs_rotation(&self, time: Timestamp) -> bool {
+        self.pending.is_none() && time >= self.rotation_due_at()
+    }
+
+    /// Returns the key of the rotation in progress, if any.
+    pub fn pending(&self) -> Option<&K> {
+        self.pending.as_ref()
+    }
+
+    /// Begins a rotation to `new_key`, returning the re-keying [IdCsr] to send to the home
+    /// server. The CSR requests the subject and capabilities of the current certificate. A
+    /// rotation already in progress is replaced.
+    pub fn begin(&mut self, new_key: K) -> Result<IdCsr<S, K::PublicKey>, ConversionError> {
+        log::trace!("[KeyRotation::begin()] Creating re-keying CSR");
+        let tbs = &self.current.id_cert.id_cert_tbs;
+        let csr = IdCsr::new(
+            &tbs.subject,
+            &new_key,
+            &tbs.capabilities,
+            Some(Target::detect(&tbs.capabilities)),
+        )?;
+        self.pending = Some(new_key);
+        Ok(csr)
+    }
+
+    /// Aborts the rotation in progress, returning its key.
+    pub fn abort(&mut self) -> Option<K> {
+        self.pending.take()
+    }
+
+    /// Completes the rotation in progress at `time`, installing `new_cert` as the current
+    /// certificate and retiring the previous key. Fails, if no rotation is in progress, if
+    /// `new_cert` does not certify the key of the rotation, or if its subject differs from the
+    /// subject of the current certificate.
+    pub fn complete(
+        &mut self,
+        new_cert: IdCert<S, K::PublicKey>,
+        time: Timestamp,
+    ) -> Result<(), ConversionError> {
+        let new_key = match &self.pending {
+            Some(new_key) => new_key,
+            None => {
+                return Err(
+                    InvalidInput::Malformed("No key rotation is in progress".to_string()).into(),
+                )
+            }
+        };
+        check_key(&new_cert, new_key)?;
+        if new_cert.id_cert_tbs.subject != self.current.id_cert.id_cert_tbs.subject {
+            return Err(InvalidInput::Malformed(
+                "The new certificate has a different subject than the current certificate"
+                    .to_string(),
+            )
+            .into());
+        }
+        new_cert.validate(Some(Target::detect(&new_cert.id_cert_tbs.capabilities)))?;
+        if let Some(new_key) = self.pending.take() {
+            log::trace!("[KeyRotation::complete()] Retiring previous key");
+            let mut retired = std::mem::replace(
+                &mut self.current,
+                RotatedKey {
+                    id_cert: new_cert,
+                    private_key: new_key,
+                    retired_at: None,
+                },
+            );
+            retired.retired_at = Some(time);
+            self.previous.insert(0, retired);
+        }
+        Ok(())
+    }
+
+    /// Cross-signs the current certificate of a home server and the most recently retired one,
+    /// using the serial numbers `serial_numbers` for [CrossCerts::new_by_previous] and
+    /// [CrossCerts::previous_by_new] respectively. Both cross-certificates are valid for the
+    /// overlap window following the retirement of the previous key, limited to the validity
+    /// periods of both certificates.
+    ///
+    /// Returns `None`, if no key has been retired yet, or if the overlap window does not
+    /// intersect with the validity periods of the certificates.
+    pub fn cross_sign(
+        &self,
+        serial_numbers: [Uint; 2],
+    ) -> Result<Option<CrossCerts<S, K::PublicKey>>, ConversionError> {
+        let Some(previous) = self.previous.first() else {
+            return Ok(None);
+        };
+        let (current_validity, previous_validity) = (
+            &self.current.id_cert.id_cert_tbs.validity,
+            &previous.id_cert.id_cert_tbs.validity,
+        );
+        let not_before = [
+            previous
+                .retired_at
+                .unwrap_or(Timestamp::from_unix_seconds(0)),
+            Timestamp::from(current_validity.not_before),
+            Timestamp::from(previous_validity.not_before),
+        ]
+        .into_iter()
+        .max();
+        let not_after = [
+            previous.verifies_until(self.overlap),
+            Timestamp::from(current_validity.not_after),
+        ]
+        .into_iter()
+        .min();
+        let (Some(not_before), Some(not_after)) = (not_before, not_after) else {
+            return Ok(None);
+        };
+        if not_before >= not_after {
+            log::debug!("[KeyRotation::cross_sign()] The overlap window has passed");
+            return Ok(None);
+        }
+        let validity = Validity::between(not_before, not_after)?;
+        let [new_serial_number, previous_serial_number] = serial_numbers;
+        log::trace!(
+            "[KeyRotation::cross_sign()] Cross-signing certificates from {} until {}",
+            not_before,
+            not_after
+        );
+        Ok(Some(CrossCerts {
+            new_by_previous: cross_sign(
+                &self.current.id_cert,
+                &previous.id_cert,
+                &previous.private_key,
+                new_serial_number,
+                validity,
+            )?,
+            previous_by_new: cross_sign(
+                &previous.id_cert,
+                &self.current.id_cert,
+                &self.current.private_key,
+                previous_serial_number,
+                validity,
+            )?,
+        }))
+    }
+
+    /// Removes all retired keys which may no longer verify material at `time`. Returns the number
+    /// of removed keys.
+    pub fn prune(&mut self, time: Timestamp) -> usize {
+        let overlap = self.overlap;
+        let before = self.previous.len();
+        self.previous
+            .retain(|key| time <= key.verifies_until(overlap));
+        before - self.previous.len()
+    }
+}
+
+/// Issues a cross-certificate for the public key of the home server certificate `subject`, signed
+/// with `issuer_key`, the private key of the home server certificate `issuer`. Both certificates
+/// must have the same subject, as the previous and the new certificate of a key rollover do.
+///
+/// The cross-certificate carries the subject, capabilities and claims of `subject`, and is marked
+/// with the [CROSS_SIGNED_CLAIM]. [IdCertChain::validate_chain()](super::chain::IdCertChain::validate_chain)
+/// accepts material issued under the key of `subject` through the cross-certificate for as long as
+/// it is valid, so `validity` should span the overlap window of the rollover. It must lie within
+/// the validity period of `issuer`. See [KeyRotation::cross_sign()] for issuing both
+/// cross-certificates of a rollover at once.
+pub fn cross_sign<S: Signature, K: PrivateKey<S>>(
+    subject: &IdCert<S, K::PublicKey>,
+    issuer: &IdCert<S, K::PublicKey>,
+    issuer_key: &K,
+    serial_number: Uint,
+    validity: Validity,
+) -> Result<IdCert<S, K::PublicKey>, ConversionError> {
+    check_key(issuer, issuer_key)?;
+    if subject.id_cert_tbs.subject != issuer.id_cert_tbs.subject {
+        return Err(InvalidInput::Malformed(
+            "Cross-signed certificates must have the same subject".to_string(),
+        )
+        .into());
+    }
+    let issuer_validity = &issuer.id_cert_tbs.validity;
+    if Timestamp::from(validity.not_before) < Timestamp::from(issuer_validity.not_before)
+        || Timestamp::from(validity.not_after) > Timestamp::from(issuer_validity.not_after)
+    {
+        return Err(InvalidInput::Malformed(
+            "The validity period of the cross-certificate exceeds the validity period of its issuer"
+                .to_string(),
+        )
+        .into());
+    }
+    let mut claims = subject.id_cert_tbs.claims.clone();
+    claims.insert(CROSS_SIGNED_CLAIM, true)?;
+    let id_cert_tbs = IdCertTbs {
+        serial_number,
+        issuer: issuer.id_cert_tbs.subject.clone(),
+        validity,
+        claims,
+        ..(*subject.id_cert_tbs).clone()
+    };
+    IdCert::from_tbs(id_cert_tbs, issuer_key, Target::HomeServer)
+}
+
+fn check_key<S: Signature, K: PrivateKey<S>>(
+    id_cert: &IdCert<S, K::PublicKey>,
+    private_key: &K,
+) -> Result<(), ConversionError> {
+    if private_key.pubkey() != &id_cert.id_cert_tbs.subject_public_key {
+        return Err(InvalidInput::Malformed(
+            "The private key does not belong to the subject of the certificate".to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,475 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use ::rustls::pki_types::{CertificateDer, ServerName, SubjectPublicKeyInfoDer, UnixTime};
+use ::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use ::rustls::sign::{CertifiedKey, Signer, SigningKey};
+use ::rustls::{
+    CertificateError, DigitallySignedStruct, DistinguishedName, Error, OtherError, PeerMisbehaved,
+    SignatureAlgorithm, SignatureScheme,
+};
+use der::Encode;
+use spki::SubjectPublicKeyInfoOwned;
+
+use crate::errors::{ConversionError, InvalidCert, InvalidInput};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::Constrained;
+
+use super::chain::IdCertChain;
+#[cfg(feature = "types")]
+use super::custody::uid;
+use super::idcert::IdCert;
+use super::truststore::{TrustAnchor, TrustStore};
+use super::{domain_of, Target};
+
+impl<S: Signature, P: PublicKey<S>> TryFrom<&IdCert<S, P>> for CertificateDer<'static> {
+    type Error = ConversionError;
+
+    fn try_from(value: &IdCert<S, P>) -> Result<Self, Self::Error> {
+        Ok(CertificateDer::from(value.to_der()?))
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> TryFrom<&CertificateDer<'_>> for IdCert<S, P> {
+    type Error = ConversionError;
+
+    /// Decodes an [IdCert] from a [CertificateDer], without validating it. See
+    /// [IdCert::from_der_unchecked()].
+    fn try_from(value: &CertificateDer<'_>) -> Result<Self, Self::Error> {
+        IdCert::from_der_unchecked(value.as_ref())
+    }
+}
+
+/// Encodes `chain` as the certificate chain presented in a TLS handshake: the leaf certificate
+/// first, followed by its issuers, ending with the root certificate.
+pub fn certificate_chain<S: Signature, P: PublicKey<S>>(
+    chain: &IdCertChain<S, P>,
+) -> Result<Vec<CertificateDer<'static>>, ConversionError> {
+    chain.iter().map(CertificateDer::try_from).collect()
+}
+
+/// Returns the TLS [SignatureScheme]s handshake signatures made with keys of the signature
+/// algorithm `S` can use. Empty, if the signature algorithm of `S` has no TLS counterpart.
+pub fn signature_schemes<S: Signature>() -> Vec<SignatureScheme> {
+    match S::algorithm_identifier().oid.to_string().as_str() {
+        "1.3.101.112" => vec![SignatureScheme::ED25519],
+        "1.2.840.10045.4.3.2" => vec![SignatureScheme::ECDSA_NISTP256_SHA256],
+        "1.2.840.10045.4.3.3" => vec![SignatureScheme::ECDSA_NISTP384_SHA384],
+        _ => Vec::new(),
+    }
+}
+
+/// Verifies the handshake signature `signature` over `message`, made using `scheme` with the key
+/// of the [IdCert] encoded in `cert`. This is the signature check performed by [IdCertVerifier]
+/// during TLS 1.2 and TLS 1.3 handshakes.
+pub fn verify_handshake_signature<S: Signature, P: PublicKey<S>>(
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    scheme: SignatureScheme,
+    signature: &[u8],
+) -> Result<HandshakeSignatureValid, Error> {
+    if !signature_schemes::<S>().contains(&scheme) {
+        return Err(PeerMisbehaved::SignedHandshakeWithUnadvertisedSigScheme.into());
+    }
+    let cert = decode::<S, P>(cert)?;
+    match cert
+        .id_cert_tbs
+        .subject_public_key
+        .verify_signature(&S::from_bytes(signature), message)
+    {
+        Ok(()) => Ok(HandshakeSignatureValid::assertion()),
+        Err(_) => Err(CertificateError::BadSignature.into()),
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A `rustls` [ServerCertVerifier] and [ClientCertVerifier] accepting peers which present a valid
+/// [IdCert] chain, anchored in a [TrustStore]. This lets the same identity certificates which
+/// authenticate actors and home servers in polyproto secure TLS connections, such as those
+/// between federating home servers.
+///
+/// The certificates presented by the peer are validated using
+/// [IdCertChain::validate_chain_with_tolerance()], with the leaf certificate validated for the
+/// [Target] of the verifier, and checked using [TrustStore::check()]. A home server may present
+/// its self-signed certificate alone, which must then be a valid home server certificate and be
+/// anchored in the [TrustStore] as well. When acting as a [ServerCertVerifier], the leaf
+/// certificate must additionally be valid for the domain the client connects to, as checked by
+/// [IdCert::check_domain()]. OCSP responses stapled by the server are ignored.
+///
+/// Handshake signatures are verified using the [PublicKey] of the leaf certificate, so no
+/// `rustls` crypto provider is involved in verifying the peer. Only the [SignatureScheme]s returned
+/// by [signature_schemes()] are offered.
+pub struct IdCertVerifier<S: Signature, P: PublicKey<S>> {
+    trust_store: TrustStore<S, P>,
+    target: Target,
+    tolerance: Duration,
+    root_hint_subjects: Vec<DistinguishedName>,
+}
+
+impl<S: Signature, P: PublicKey<S>> IdCertVerifier<S, P> {
+    /// Creates a new [IdCertVerifier], accepting peers whose leaf certificate is valid for
+    /// `target` and whose issuers are anchored in `trust_store`. Servers verifying federating
+    /// home servers use [Target::HomeServer], servers verifying connecting clients use
+    /// [Target::Actor].
+    pub fn new(trust_store: TrustStore<S, P>, target: Target) -> Self {
+        let root_hint_subjects = trust_store
+            .domains()
+            .flat_map(|domain| trust_store.anchors(domain))
+            .filter_map(|anchor| match anchor {
+                TrustAnchor::Root(root) => root.id_cert_tbs.subject.to_der().ok(),
+                TrustAnchor::Pin(_) => None,
+            })
+            .map(DistinguishedName::from)
+            .collect();
+        Self {
+            trust_store,
+            target,
+            tolerance: Duration::ZERO,
+            root_hint_subjects,
+        }
+    }
+
+    /// Tolerates a clock skew of up to `tolerance` when checking the validity periods of the
+    /// certificates presented by the peer.
+    pub fn with_clock_skew_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The [TrustStore] the certificates presented by peers must be anchored in.
+    pub fn trust_store(&self) -> &TrustStore<S, P> {
+        &self.trust_store
+    }
+
+    /// The [Target] the leaf certificates presented by peers are validated for.
+    pub fn target(&self) -> Target {
+        self.target
+    }
+
+    /// Decodes and verifies the certificates presented by a peer at `now`, returning the leaf
+    /// certificate.
+    pub fn verify_peer(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<IdCert<S, P>, Error> {
+        let leaf = decode::<S, P>(end_entity)?;
+        let issuers = intermediates
+            .iter()
+            .map(decode::<S, P>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let chain = verify_chain(
+            &self.trust_store,
+            self.target,
+            self.tolerance,
+            leaf,
+            issuers,
+            Timestamp::from_unix_seconds(now.as_secs()),
+        )
+        .map_err(tls_error)?;
+        Ok(chain.leaf().clone())
+    }
+}
+
+impl<S, P> ServerCertVerifier for IdCertVerifier<S, P>
+where
+    S: Signature + Debug + Send + Sync,
+    P: PublicKey<S> + Debug + Send + Sync,
+{
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let leaf = self.verify_peer(end_entity, intermediates, now)?;
+        match server_name {
+            ServerName::DnsName(name) => leaf.check_domain(name.as_ref()).map_err(tls_error)?,
+            _ => return Err(CertificateError::NotValidForName.into()),
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_handshake_signature::<S, P>(message, cert, dss.scheme, dss.signature())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_handshake_signature::<S, P>(message, cert, dss.scheme, dss.signature())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        signature_schemes::<S>()
+    }
+}
+
+impl<S, P> ClientCertVerifier for IdCertVerifier<S, P>
+where
+    S: Signature + Debug + Send + Sync,
+    P: PublicKey<S> + Debug + Send + Sync,
+{
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &self.root_hint_subjects
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, Error> {
+        self.verify_peer(end_entity, intermediates, now)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_handshake_signature::<S, P>(message, cert, dss.scheme, dss.signature())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_handshake_signature::<S, P>(message, cert, dss.scheme, dss.signature())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        signature_schemes::<S>()
+    }
+}
+
+#[cfg(feature = "types")]
+impl crate::types::FederationId {
+    /// Authenticates the actor on the other end of a mutual TLS connection: decodes the
+    /// certificates presented by the client, such as those returned by
+    /// `ServerConnection::peer_certificates()`, validates them as an actor certificate chain at
+    /// `now` and checks them against `trust_store`. Returns the [FederationId](crate::types::FederationId)
+    /// and [SessionId](crate::types::SessionId) of the authenticated actor.
+    ///
+    /// `certificates` must start with the leaf certificate of the actor, followed by its issuers.
+    pub fn try_from_peer_certificates<S: Signature, P: PublicKey<S>>(
+        certificates: &[CertificateDer<'_>],
+        trust_store: &TrustStore<S, P>,
+        now: UnixTime,
+    ) -> Result<(Self, crate::types::SessionId), ConversionError> {
+        let (leaf, issuers) = certificates.split_first().ok_or_else(|| {
+            InvalidInput::Malformed("The peer presented no certificates".to_string())
+        })?;
+        let chain = verify_chain(
+            trust_store,
+            Target::Actor,
+            Duration::ZERO,
+            IdCert::try_from(leaf)?,
+            issuers
+                .iter()
+                .map(IdCert::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            Timestamp::from_unix_seconds(now.as_secs()),
+        )?;
+        let leaf = chain.leaf();
+        let federation_id = uid(&leaf.id_cert_tbs.subject).ok_or_else(|| {
+            InvalidInput::Malformed("The actor certificate carries no federation ID".to_string())
+        })?;
+        let session_id = leaf.session_id().ok_or_else(|| {
+            InvalidInput::Malformed("The actor certificate carries no session ID".to_string())
+        })?;
+        Ok((Self::new(&federation_id)?, session_id))
+    }
+}
+
+/// Packages `chain` and the [PrivateKey] of its leaf certificate into a `rustls` [CertifiedKey].
+/// This lets an actor authenticate to its home server using mutual TLS with its polyproto identity,
+/// instead of using a bearer token: wrap the [CertifiedKey] in a
+/// [SingleCertAndKey](::rustls::sign::SingleCertAndKey) and pass it to
+/// `ClientConfig::builder().with_client_cert_resolver()`.
+///
+/// Fails, if `private_key` does not belong to the subject public key of the leaf certificate.
+pub fn certified_key<S, P, K>(
+    chain: &IdCertChain<S, P>,
+    private_key: K,
+) -> Result<CertifiedKey, ConversionError>
+where
+    S: Signature + Debug + Send + Sync + 'static,
+    P: PublicKey<S>,
+    K: PrivateKey<S, PublicKey = P> + Debug + Send + Sync + 'static,
+{
+    if &chain.leaf().id_cert_tbs.subject_public_key != private_key.pubkey() {
+        return Err(InvalidInput::Malformed(
+            "The private key does not belong to the leaf certificate of the chain".to_string(),
+        )
+        .into());
+    }
+    Ok(CertifiedKey::new(
+        certificate_chain(chain)?,
+        Arc::new(IdCertSigningKey::new(private_key)),
+    ))
+}
+
+#[derive(Debug)]
+/// A `rustls` [SigningKey] producing handshake signatures with a polyproto [PrivateKey]. Only the
+/// [SignatureScheme]s returned by [signature_schemes()] are supported. See [certified_key()].
+pub struct IdCertSigningKey<S: Signature, K: PrivateKey<S>> {
+    key: Arc<K>,
+    _signature: PhantomData<fn() -> S>,
+}
+
+impl<S: Signature, K: PrivateKey<S>> IdCertSigningKey<S, K> {
+    /// Creates a new [IdCertSigningKey] signing with `key`.
+    pub fn new(key: K) -> Self {
+        Self {
+            key: Arc::new(key),
+            _signature: PhantomData,
+        }
+    }
+}
+
+impl<S, K> SigningKey for IdCertSigningKey<S, K>
+where
+    S: Signature + Debug + Send + Sync + 'static,
+    K: PrivateKey<S> + Debug + Send + Sync + 'static,
+{
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        let scheme = signature_schemes::<S>()
+            .into_iter()
+            .find(|scheme| offered.contains(scheme))?;
+        Some(Box::new(IdCertSigner::<S, K> {
+            key: self.key.clone(),
+            scheme,
+            _signature: PhantomData,
+        }))
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        SubjectPublicKeyInfoOwned::from(self.key.pubkey().public_key_info())
+            .to_der()
+            .ok()
+            .map(SubjectPublicKeyInfoDer::from)
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        match signature_schemes::<S>().first() {
+            Some(SignatureScheme::ED25519) => SignatureAlgorithm::ED25519,
+            Some(_) => SignatureAlgorithm::ECDSA,
+            None => SignatureAlgorithm::Anonymous,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct IdCertSigner<S: Signature, K: PrivateKey<S>> {
+    key: Arc<K>,
+    scheme: SignatureScheme,
+    _signature: PhantomData<fn() -> S>,
+}
+
+impl<S, K> Signer for IdCertSigner<S, K>
+where
+    S: Signature + Debug + Send + Sync + 'static,
+    K: PrivateKey<S> + Debug + Send + Sync + 'static,
+{
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        // The signature value of an X.509 certificate is encoded the same way as a TLS handshake
+        // signature for all supported schemes.
+        match self.key.sign(message).to_bitstring() {
+            Ok(bitstring) => Ok(bitstring.raw_bytes().to_vec()),
+            Err(error) => Err(Error::General(error.to_string())),
+        }
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+/// Validates the chain formed by `leaf` and `issuers` at `time`, with the leaf certificate validated
+/// for `target`, and checks it against `trust_store`. A home server certificate may be presented
+/// without issuers, in which case it must be self-signed.
+fn verify_chain<S: Signature, P: PublicKey<S>>(
+    trust_store: &TrustStore<S, P>,
+    target: Target,
+    tolerance: Duration,
+    leaf: IdCert<S, P>,
+    issuers: Vec<IdCert<S, P>>,
+    time: Timestamp,
+) -> Result<IdCertChain<S, P>, InvalidCert> {
+    let chain = match issuers.is_empty() {
+        true => {
+            if target != Target::HomeServer {
+                log::debug!(
+                    "[verify_chain()] The peer presented no issuers for its {:?} certificate",
+                    target
+                );
+                return Err(InvalidCert::NotAnchored(domain_of(
+                    &leaf.id_cert_tbs.subject,
+                )));
+            }
+            leaf.validate(Some(Target::HomeServer))?;
+            if !leaf.valid_at_with_tolerance(time, tolerance) {
+                return Err(InvalidCert::InvalidValidity);
+            }
+            leaf.verify_signature(&leaf.id_cert_tbs.subject_public_key)?;
+            // A self-signed certificate is its own issuer.
+            IdCertChain::new(leaf.clone(), vec![leaf])
+        }
+        false => {
+            let chain = IdCertChain::new(leaf, issuers);
+            chain.validate_chain_with_tolerance(time, target, tolerance)?;
+            chain
+        }
+    };
+    trust_store.check(&chain)?;
+    Ok(chain)
+}
+
+fn decode<S: Signature, P: PublicKey<S>>(cert: &CertificateDer<'_>) -> Result<IdCert<S, P>, Error> {
+    IdCert::try_from(cert).map_err(|error| {
+        log::debug!(
+            "[IdCertVerifier::decode()] The peer presented a certificate which is not an IdCert: {}",
+            error
+        );
+        CertificateError::BadEncoding.into()
+    })
+}
+
+/// Maps an [InvalidCert] to the closest [CertificateError].
+fn tls_error(error: InvalidCert) -> Error {
+    let error = match error {
+        InvalidCert::PublicKeyError(_) => CertificateError::BadSignature,
+        InvalidCert::InvalidValidity => CertificateError::Expired,
+        InvalidCert::Revoked(_) => CertificateError::Revoked,
+        InvalidCert::RevocationStatusUnknown => CertificateError::UnknownRevocationStatus,
+        InvalidCert::NotAnchored(_) | InvalidCert::PinMismatch => CertificateError::UnknownIssuer,
+        InvalidCert::DomainMismatch(_) => CertificateError::NotValidForName,
+        error => CertificateError::Other(OtherError(Arc::new(error))),
+    };
+    error.into()
+}
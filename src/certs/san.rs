@@ -0,0 +1,188 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{Ia5String, OctetString};
+use der::{Decode, Encode};
+use spki::ObjectIdentifier;
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::SubjectAltName;
+use x509_cert::ext::Extension;
+
+use crate::errors::{ConstraintError, ConversionError, InvalidInput};
+
+/// Object Identifier of the SubjectAltName extension, as defined in RFC 5280.
+pub const OID_SUBJECT_ALT_NAME: &str = "2.5.29.17";
+/// The maximum length of a DNS name, in characters, as defined in RFC 1035.
+pub const MAX_DNS_NAME_LENGTH: usize = 253;
+/// The maximum length of a single label of a DNS name, in characters, as defined in RFC 1035.
+pub const MAX_DNS_LABEL_LENGTH: usize = 63;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+/// The DNS names of the instance a home server is reachable at, carried as `dNSName` entries in
+/// the SubjectAltName extension of a home server [IdCert](super::idcert::IdCert).
+///
+/// DNS names are stored in lowercase, and must lie within the domain made up of the domain
+/// components of the subject of the certificate: a home server with the subject
+/// `DC=polyphony,DC=chat` may list `polyphony.chat` and `api.polyphony.chat`, but not
+/// `example.com`. Wildcard names are not supported.
+pub struct SubjectAltNames {
+    dns_names: Vec<String>,
+}
+
+impl SubjectAltNames {
+    /// Creates an empty [SubjectAltNames].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the DNS name `name`. Fails, if `name` is not a valid DNS name.
+    pub fn with_dns_name(mut self, name: &str) -> Result<Self, InvalidInput> {
+        self.add_dns_name(name)?;
+        Ok(self)
+    }
+
+    /// Adds the DNS name `name`. Returns `false`, if it was added before. Fails, if `name` is not
+    /// a valid DNS name.
+    pub fn add_dns_name(&mut self, name: &str) -> Result<bool, InvalidInput> {
+        let name = normalize_dns_name(name)?;
+        if self.dns_names.contains(&name) {
+            return Ok(false);
+        }
+        self.dns_names.push(name);
+        Ok(true)
+    }
+
+    /// Returns the DNS names, in lowercase and in the order they were added.
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    /// Returns `true`, if `name` is listed. Names are compared case-insensitively.
+    pub fn contains(&self, name: &str) -> bool {
+        self.dns_names.contains(&name.to_lowercase())
+    }
+
+    /// Returns the number of DNS names.
+    pub fn len(&self) -> usize {
+        self.dns_names.len()
+    }
+
+    /// Returns `true`, if no DNS names are listed.
+    pub fn is_empty(&self) -> bool {
+        self.dns_names.is_empty()
+    }
+
+    /// Checks, that all DNS names are equal to `domain` or a subdomain of it.
+    pub fn check_within(&self, domain: &str) -> Result<(), ConstraintError> {
+        let domain = domain.to_lowercase();
+        match self.dns_names.iter().find(|name| !is_within(name, &domain)) {
+            Some(name) => Err(ConstraintError::Malformed(Some(format!(
+                "The subject alternative name {} does not lie within the domain {:?}",
+                name, domain
+            )))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TryFrom<SubjectAltNames> for Extension {
+    type Error = ConversionError;
+
+    /// Encodes the DNS names as a non-critical SubjectAltName extension. Fails, if no DNS names
+    /// are listed.
+    fn try_from(value: SubjectAltNames) -> Result<Self, Self::Error> {
+        if value.is_empty() {
+            return Err(malformed("The SubjectAltName extension must not be empty").into());
+        }
+        let names = value
+            .dns_names
+            .iter()
+            .map(|name| Ok(GeneralName::DnsName(Ia5String::new(name)?)))
+            .collect::<Result<Vec<_>, der::Error>>()?;
+        Ok(Extension {
+            extn_id: ObjectIdentifier::from_str(OID_SUBJECT_ALT_NAME)?,
+            critical: false,
+            extn_value: OctetString::new(SubjectAltName(names).to_der()?)?,
+        })
+    }
+}
+
+impl TryFrom<Extension> for SubjectAltNames {
+    type Error = ConversionError;
+
+    /// Decodes the DNS names from a SubjectAltName extension. Fails, if the extension has another
+    /// OID, is marked as critical, is empty, or names anything but valid DNS names.
+    fn try_from(value: Extension) -> Result<Self, Self::Error> {
+        if value.extn_id.to_string() != OID_SUBJECT_ALT_NAME {
+            return Err(malformed(&format!(
+                "Expected the SubjectAltName extension {}, found {}",
+                OID_SUBJECT_ALT_NAME, value.extn_id
+            ))
+            .into());
+        }
+        if value.critical {
+            return Err(malformed("The SubjectAltName extension must not be critical").into());
+        }
+        let names = SubjectAltName::from_der(value.extn_value.as_bytes())?;
+        if names.0.is_empty() {
+            return Err(malformed("The SubjectAltName extension must not be empty").into());
+        }
+        let mut subject_alt_names = Self::new();
+        for name in names.0.iter() {
+            match name {
+                GeneralName::DnsName(name) => {
+                    subject_alt_names.add_dns_name(name.as_str())?;
+                }
+                _ => {
+                    return Err(malformed(
+                        "Expected the subject alternative names to be DNS names only",
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(subject_alt_names)
+    }
+}
+
+/// Returns `true`, if `name` is equal to `domain` or a subdomain of it. Both are expected to be in
+/// lowercase.
+fn is_within(name: &str, domain: &str) -> bool {
+    match name.strip_suffix(domain) {
+        Some("") => true,
+        Some(rest) => !domain.is_empty() && rest.ends_with('.'),
+        None => false,
+    }
+}
+
+/// Validates the DNS name `name`, as defined in RFC 1035 and RFC 1123, and returns it in
+/// lowercase.
+fn normalize_dns_name(name: &str) -> Result<String, InvalidInput> {
+    if name.is_empty() || name.len() > MAX_DNS_NAME_LENGTH {
+        return Err(InvalidInput::Length {
+            min_length: 1,
+            max_length: MAX_DNS_NAME_LENGTH,
+            actual_length: name.len().to_string(),
+        });
+    }
+    let valid = name.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_DNS_LABEL_LENGTH
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+    });
+    if !valid {
+        return Err(malformed(&format!("{:?} is not a valid DNS name", name)));
+    }
+    Ok(name.to_lowercase())
+}
+
+fn malformed(reason: &str) -> InvalidInput {
+    InvalidInput::Malformed(reason.to_string())
+}
@@ -0,0 +1,193 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::time::Duration;
+
+use spki::ObjectIdentifier;
+
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::verifier::DEPRECATED_SIGNATURE_ALGORITHMS;
+
+use super::idcert::IdCert;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+/// A summary of how much trust can be placed in the key of an [IdCert], as determined by a
+/// [SecurityPolicy]. Levels are ordered, so that a certificate can be checked against a minimum
+/// level using the comparison operators or [SecurityLevel::meets()].
+pub enum SecurityLevel {
+    /// The certificate uses an unknown or deprecated algorithm, or a key which is too weak.
+    Insufficient,
+    /// The certificate uses an acceptable algorithm and key size.
+    Basic,
+    /// The certificate uses a strong algorithm and key size, and has a short validity period.
+    Strong,
+    /// Like [SecurityLevel::Strong], and the private key is held by a hardware authenticator.
+    HardwareBacked,
+}
+
+impl SecurityLevel {
+    /// Returns `true`, if this level is at least `minimum`.
+    pub fn meets(&self, minimum: SecurityLevel) -> bool {
+        *self >= minimum
+    }
+}
+
+impl Display for SecurityLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecurityLevel::Insufficient => write!(f, "insufficient"),
+            SecurityLevel::Basic => write!(f, "basic"),
+            SecurityLevel::Strong => write!(f, "strong"),
+            SecurityLevel::HardwareBacked => write!(f, "hardware-backed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The properties of an [IdCert] a [SecurityLevel] is derived from.
+pub struct SecurityFacts {
+    /// The OID of the algorithm the certificate is signed with.
+    pub signature_algorithm: ObjectIdentifier,
+    /// The OID of the algorithm of the subject public key.
+    pub public_key_algorithm: ObjectIdentifier,
+    /// The OID in the parameters of the subject public key algorithm, such as the named curve of
+    /// an elliptic curve key, if any.
+    pub public_key_parameters: Option<ObjectIdentifier>,
+    /// The length of the validity period of the certificate.
+    pub validity: Duration,
+}
+
+impl SecurityFacts {
+    /// Collects the [SecurityFacts] of an [IdCert].
+    pub fn of<S: Signature, P: PublicKey<S>>(cert: &IdCert<S, P>) -> Self {
+        let public_key_info = cert.id_cert_tbs.subject_public_key.public_key_info();
+        let not_before = Timestamp::from(cert.id_cert_tbs.validity.not_before);
+        let not_after = Timestamp::from(cert.id_cert_tbs.validity.not_after);
+        Self {
+            signature_algorithm: cert.id_cert_tbs.signature_algorithm.oid,
+            public_key_algorithm: public_key_info.algorithm.oid,
+            public_key_parameters: public_key_info
+                .algorithm
+                .parameters
+                .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok()),
+            validity: Duration::from_secs(
+                not_after
+                    .unix_seconds()
+                    .saturating_sub(not_before.unix_seconds()),
+            ),
+        }
+    }
+}
+
+/// Maps the [SecurityFacts] of a certificate to a [SecurityLevel]. Implement this trait to
+/// customize the mapping, e.g. to treat keys of a custom backend as hardware-backed. See
+/// [StandardSecurityPolicy] for the default mapping.
+pub trait SecurityPolicy {
+    /// Returns the [SecurityLevel] of a certificate with the given [SecurityFacts].
+    fn security_level(&self, facts: &SecurityFacts) -> SecurityLevel;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The default [SecurityPolicy].
+///
+/// The security strength of a key, in bits, is looked up in
+/// [StandardSecurityPolicy::key_strengths], first by the OID in the parameters of the key
+/// algorithm, such as a named curve, and then by the OID of the key algorithm itself. A
+/// certificate is
+///
+/// - [SecurityLevel::Insufficient], if the strength of its key is unknown or below
+///   [StandardSecurityPolicy::min_strength], or if it is signed using one of the
+///   [DEPRECATED_SIGNATURE_ALGORITHMS],
+/// - [SecurityLevel::Strong], if the strength of its key is at least
+///   [StandardSecurityPolicy::strong_strength] and its validity period is at most
+///   [StandardSecurityPolicy::strong_max_validity],
+/// - [SecurityLevel::HardwareBacked], if it would be [SecurityLevel::Strong] and its key algorithm
+///   is one of the [StandardSecurityPolicy::hardware_backed_algorithms],
+/// - [SecurityLevel::Basic] otherwise.
+pub struct StandardSecurityPolicy {
+    /// The security strength in bits of keys, keyed by the OID of the key algorithm or of the
+    /// named curve.
+    pub key_strengths: BTreeMap<String, u32>,
+    /// The minimum security strength of a [SecurityLevel::Basic] key.
+    pub min_strength: u32,
+    /// The minimum security strength of a [SecurityLevel::Strong] key.
+    pub strong_strength: u32,
+    /// The maximum validity period of a [SecurityLevel::Strong] certificate.
+    pub strong_max_validity: Duration,
+    /// OIDs of key algorithms whose private keys are always held by a hardware authenticator.
+    pub hardware_backed_algorithms: Vec<String>,
+}
+
+impl Default for StandardSecurityPolicy {
+    /// Ed25519, P-256, P-384 and the FIDO2 and composite keys of this crate are known. Keys of at
+    /// least 112 bits are acceptable, keys of at least 128 bits with a validity period of at most
+    /// 90 days are strong, and FIDO2 keys are hardware-backed.
+    fn default() -> Self {
+        let key_strengths = [
+            // Ed25519
+            ("1.3.101.112", 128),
+            // secp256r1
+            ("1.2.840.10045.3.1.7", 128),
+            // secp384r1
+            ("1.3.132.0.34", 192),
+            // FIDO2 authenticator key
+            ("1.3.6.1.4.1.18227.2.3", 128),
+            // Composite key
+            ("2.16.840.1.114027.80.4.1", 128),
+        ]
+        .into_iter()
+        .map(|(oid, strength)| (oid.to_string(), strength))
+        .collect();
+        Self {
+            key_strengths,
+            min_strength: 112,
+            strong_strength: 128,
+            strong_max_validity: Duration::from_secs(90 * 24 * 60 * 60),
+            hardware_backed_algorithms: vec!["1.3.6.1.4.1.18227.2.3".to_string()],
+        }
+    }
+}
+
+impl StandardSecurityPolicy {
+    /// Returns the security strength in bits of the key described by `facts`, if it is known.
+    pub fn key_strength(&self, facts: &SecurityFacts) -> Option<u32> {
+        facts
+            .public_key_parameters
+            .and_then(|parameters| self.key_strengths.get(&parameters.to_string()))
+            .or_else(|| {
+                self.key_strengths
+                    .get(&facts.public_key_algorithm.to_string())
+            })
+            .copied()
+    }
+}
+
+impl SecurityPolicy for StandardSecurityPolicy {
+    fn security_level(&self, facts: &SecurityFacts) -> SecurityLevel {
+        let signature_algorithm = facts.signature_algorithm.to_string();
+        if DEPRECATED_SIGNATURE_ALGORITHMS.contains(&signature_algorithm.as_str()) {
+            return SecurityLevel::Insufficient;
+        }
+        let strength = match self.key_strength(facts) {
+            Some(strength) if strength >= self.min_strength => strength,
+            _ => return SecurityLevel::Insufficient,
+        };
+        if strength < self.strong_strength || facts.validity > self.strong_max_validity {
+            return SecurityLevel::Basic;
+        }
+        match self
+            .hardware_backed_algorithms
+            .contains(&facts.public_key_algorithm.to_string())
+        {
+            true => SecurityLevel::HardwareBacked,
+            false => SecurityLevel::Strong,
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use der::EncodeValue;
+use rand_core::CryptoRngCore;
+
+use crate::errors::InvalidInput;
+use crate::timestamp::Timestamp;
+
+/// The maximum length of the encoded serial number of a certificate, in bytes, as mandated by
+/// RFC 5280, section 4.1.2.2.
+pub const MAX_SERIAL_NUMBER_LENGTH: usize = 20;
+/// The length of the serial numbers generated by [SerialNumberGenerator::random()], in bytes.
+pub const DEFAULT_RANDOM_SERIAL_NUMBER_LENGTH: usize = 16;
+
+/// Checks, that `serial_number` is a valid certificate serial number according to RFC 5280,
+/// section 4.1.2.2: a positive integer, encoded in at most [MAX_SERIAL_NUMBER_LENGTH] bytes.
+pub fn check_serial_number(serial_number: &Uint) -> Result<(), InvalidInput> {
+    if serial_number.as_bytes().iter().all(|byte| *byte == 0) {
+        return Err(InvalidInput::Malformed(
+            "Serial numbers must be positive".to_string(),
+        ));
+    }
+    let length = serial_number
+        .value_len()
+        .map_err(|error| InvalidInput::Malformed(error.to_string()))?;
+    let length = u32::from(length) as usize;
+    if length > MAX_SERIAL_NUMBER_LENGTH {
+        return Err(InvalidInput::Length {
+            min_length: 1,
+            max_length: MAX_SERIAL_NUMBER_LENGTH,
+            actual_length: length.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// Implicit copies of a counter would hand out the same serial numbers twice.
+#[allow(missing_copy_implementations)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Generates serial numbers for the certificates issued by a home server, which pass
+/// [check_serial_number()]. Three strategies are available:
+///
+/// - [SerialNumberGenerator::Random]: random serial numbers, the recommended strategy. With the
+///   [DEFAULT_RANDOM_SERIAL_NUMBER_LENGTH], serial numbers carry 127 bits of entropy, so that
+///   collisions are practically impossible and serial numbers cannot be predicted.
+/// - [SerialNumberGenerator::UuidV7]: serial numbers derived from a UUIDv7, which are unique and
+///   sort by the time they were generated at.
+/// - [SerialNumberGenerator::Counter]: consecutive serial numbers. The next value has to be
+///   persisted by the issuer, as restarting the counter results in duplicate serial numbers.
+pub enum SerialNumberGenerator {
+    /// Random serial numbers of `length` bytes, with the most significant bit cleared.
+    Random {
+        /// The length of the generated serial numbers, in bytes.
+        length: usize,
+    },
+    /// Serial numbers derived from a UUIDv7, as described in RFC 9562, section 5.7.
+    UuidV7,
+    /// Consecutive serial numbers.
+    Counter {
+        /// The next serial number.
+        next: u128,
+    },
+}
+
+impl Default for SerialNumberGenerator {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+impl SerialNumberGenerator {
+    /// Creates a [SerialNumberGenerator::Random] generating serial numbers of
+    /// [DEFAULT_RANDOM_SERIAL_NUMBER_LENGTH] bytes.
+    pub fn random() -> Self {
+        Self::Random {
+            length: DEFAULT_RANDOM_SERIAL_NUMBER_LENGTH,
+        }
+    }
+
+    /// Creates a [SerialNumberGenerator::Random] generating serial numbers of `length` bytes.
+    /// Fails, if `length` is zero or exceeds [MAX_SERIAL_NUMBER_LENGTH].
+    pub fn random_with_length(length: usize) -> Result<Self, InvalidInput> {
+        if length == 0 || length > MAX_SERIAL_NUMBER_LENGTH {
+            return Err(InvalidInput::Length {
+                min_length: 1,
+                max_length: MAX_SERIAL_NUMBER_LENGTH,
+                actual_length: length.to_string(),
+            });
+        }
+        Ok(Self::Random { length })
+    }
+
+    /// Creates a [SerialNumberGenerator::UuidV7].
+    pub fn uuid_v7() -> Self {
+        Self::UuidV7
+    }
+
+    /// Creates a [SerialNumberGenerator::Counter] starting at `start`. Fails, if `start` is zero.
+    pub fn counter(start: u128) -> Result<Self, InvalidInput> {
+        if start == 0 {
+            return Err(InvalidInput::Malformed(
+                "Serial numbers must be positive".to_string(),
+            ));
+        }
+        Ok(Self::Counter { next: start })
+    }
+
+    /// Generates the next serial number at `now`, drawing randomness from `rng`. `now` is only
+    /// used by [SerialNumberGenerator::UuidV7], `rng` by [SerialNumberGenerator::Random] and
+    /// [SerialNumberGenerator::UuidV7].
+    ///
+    /// Fails, if the length of a [SerialNumberGenerator::Random] is invalid, or if a
+    /// [SerialNumberGenerator::Counter] is exhausted.
+    pub fn generate(
+        &mut self,
+        now: Timestamp,
+        rng: &mut impl CryptoRngCore,
+    ) -> Result<Uint, InvalidInput> {
+        let bytes = match self {
+            SerialNumberGenerator::Random { length } => {
+                if *length == 0 || *length > MAX_SERIAL_NUMBER_LENGTH {
+                    return Err(InvalidInput::Length {
+                        min_length: 1,
+                        max_length: MAX_SERIAL_NUMBER_LENGTH,
+                        actual_length: length.to_string(),
+                    });
+                }
+                let mut bytes = vec![0u8; *length];
+                // Serial numbers consisting of zeros only are not positive, and are drawn again.
+                while bytes.iter().all(|byte| *byte == 0) {
+                    rng.fill_bytes(&mut bytes);
+                    // Clearing the most significant bit keeps the encoding from growing by a
+                    // leading zero byte.
+                    bytes[0] &= 0x7f;
+                }
+                bytes
+            }
+            SerialNumberGenerator::UuidV7 => uuid_v7(now, rng).to_vec(),
+            SerialNumberGenerator::Counter { next } => {
+                if *next == 0 {
+                    return Err(InvalidInput::Malformed(
+                        "Serial numbers must be positive".to_string(),
+                    ));
+                }
+                let current = *next;
+                *next = next.checked_add(1).ok_or(InvalidInput::Malformed(
+                    "The serial number counter is exhausted".to_string(),
+                ))?;
+                current.to_be_bytes().to_vec()
+            }
+        };
+        log::trace!(
+            "[SerialNumberGenerator::generate()] Generated serial number of {} bytes",
+            bytes.len()
+        );
+        let serial_number =
+            Uint::new(&bytes).map_err(|error| InvalidInput::Malformed(error.to_string()))?;
+        check_serial_number(&serial_number)?;
+        Ok(serial_number)
+    }
+}
+
+/// Creates a UUIDv7: a 48 bit Unix timestamp in milliseconds, followed by the version, 12 random
+/// bits, the variant and another 62 random bits.
+fn uuid_v7(now: Timestamp, rng: &mut impl CryptoRngCore) -> [u8; 16] {
+    let millis = now.unix_seconds().saturating_mul(1000);
+    let mut uuid = [0u8; 16];
+    rng.fill_bytes(&mut uuid[6..]);
+    uuid[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+    uuid[6] = (uuid[6] & 0x0f) | 0x70;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    uuid
+}
@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::borrow::Borrow;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::errors::{ConstraintError, InvalidCert};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::Constrained;
+
+use super::idcert::IdCert;
+use super::Target;
+
+#[derive(Debug)]
+/// A reference counted, immutable [IdCert]. Cloning a [SharedIdCert] only increments a reference
+/// count, so that certificates can be passed between tasks, handlers and caches without copying
+/// their names, extensions and signature.
+///
+/// A [SharedIdCert] dereferences to the [IdCert] it holds, which makes the complete read API of
+/// [IdCert] available. Use [SharedIdCert::into_id_cert()] to obtain an [IdCert] which can be
+/// modified.
+pub struct SharedIdCert<S: Signature, P: PublicKey<S>> {
+    inner: Arc<IdCert<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> SharedIdCert<S, P> {
+    /// Creates a [SharedIdCert] holding `id_cert`.
+    pub fn new(id_cert: IdCert<S, P>) -> Self {
+        Self {
+            inner: Arc::new(id_cert),
+        }
+    }
+
+    /// Like [IdCert::from_der()], but returns a [SharedIdCert].
+    pub fn from_der(
+        value: &[u8],
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
+        home_server_public_key: &P,
+    ) -> Result<Self, InvalidCert> {
+        IdCert::from_der(value, target, time, home_server_public_key).map(Self::new)
+    }
+
+    /// Like [IdCert::from_pem()], but returns a [SharedIdCert].
+    pub fn from_pem(
+        pem: &str,
+        target: impl Into<Option<Target>>,
+        time: Timestamp,
+        home_server_public_key: &P,
+    ) -> Result<Self, InvalidCert> {
+        IdCert::from_pem(pem, target, time, home_server_public_key).map(Self::new)
+    }
+
+    /// Returns the [IdCert] this [SharedIdCert] holds.
+    pub fn id_cert(&self) -> &IdCert<S, P> {
+        &self.inner
+    }
+
+    /// Returns the [IdCert] this [SharedIdCert] holds. The certificate is only cloned if other
+    /// [SharedIdCert]s hold it as well.
+    pub fn into_id_cert(self) -> IdCert<S, P> {
+        Arc::try_unwrap(self.inner).unwrap_or_else(|inner| (*inner).clone())
+    }
+
+    /// Whether `self` and `other` hold the same allocation, as opposed to two equal certificates.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Clone for SharedIdCert<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> PartialEq for SharedIdCert<S, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || self.inner == other.inner
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Eq for SharedIdCert<S, P> {}
+
+impl<S: Signature, P: PublicKey<S>> PartialEq<IdCert<S, P>> for SharedIdCert<S, P> {
+    fn eq(&self, other: &IdCert<S, P>) -> bool {
+        *self.inner == *other
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Deref for SharedIdCert<S, P> {
+    type Target = IdCert<S, P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> AsRef<IdCert<S, P>> for SharedIdCert<S, P> {
+    fn as_ref(&self) -> &IdCert<S, P> {
+        &self.inner
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Borrow<IdCert<S, P>> for SharedIdCert<S, P> {
+    fn borrow(&self) -> &IdCert<S, P> {
+        &self.inner
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<IdCert<S, P>> for SharedIdCert<S, P> {
+    fn from(value: IdCert<S, P>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<Arc<IdCert<S, P>>> for SharedIdCert<S, P> {
+    fn from(value: Arc<IdCert<S, P>>) -> Self {
+        Self { inner: value }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> From<SharedIdCert<S, P>> for IdCert<S, P> {
+    fn from(value: SharedIdCert<S, P>) -> Self {
+        value.into_id_cert()
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> Constrained for SharedIdCert<S, P> {
+    fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
+        self.inner.validate(target)
+    }
+}
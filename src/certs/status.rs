@@ -0,0 +1,716 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use rand_core::CryptoRngCore;
+
+use crate::encoding::encode_hex;
+use crate::errors::{ConstraintError, ConversionError, InvalidCert};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::certid::CertId;
+use super::idcert::IdCert;
+
+/// The prefix of the payload signed when creating a [StatusResponse].
+pub static REVOCATION_STATUS_PAYLOAD_PREFIX: &str = "polyproto-revocation-status-v1";
+
+/// The length of nonces generated by [StatusRequest::fresh()], in bytes.
+pub const STATUS_NONCE_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The reason for which a certificate has been revoked, using the `CRLReason` codes of RFC 5280.
+pub enum RevocationReason {
+    /// No reason was given.
+    Unspecified,
+    /// The private key of the subject has been compromised.
+    KeyCompromise,
+    /// The private key of the issuing home server has been compromised.
+    CaCompromise,
+    /// The subject has changed its affiliation, e.g. by migrating to another home server.
+    AffiliationChanged,
+    /// The certificate has been superseded by another certificate.
+    Superseded,
+    /// The subject no longer exists, e.g. because the actor has been deleted.
+    CessationOfOperation,
+    /// The certificate has been put on hold temporarily.
+    CertificateHold,
+    /// A previously held certificate has been released from hold.
+    RemoveFromCrl,
+    /// The privileges granted to the subject have been withdrawn.
+    PrivilegeWithdrawn,
+    /// The private key of an attribute authority has been compromised.
+    AaCompromise,
+}
+
+impl RevocationReason {
+    /// Returns the RFC 5280 reason code of this reason.
+    pub fn code(&self) -> u8 {
+        match self {
+            RevocationReason::Unspecified => 0,
+            RevocationReason::KeyCompromise => 1,
+            RevocationReason::CaCompromise => 2,
+            RevocationReason::AffiliationChanged => 3,
+            RevocationReason::Superseded => 4,
+            RevocationReason::CessationOfOperation => 5,
+            RevocationReason::CertificateHold => 6,
+            RevocationReason::RemoveFromCrl => 8,
+            RevocationReason::PrivilegeWithdrawn => 9,
+            RevocationReason::AaCompromise => 10,
+        }
+    }
+
+    /// Returns the reason for an RFC 5280 reason code, or `None`, if the code is not assigned.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => RevocationReason::Unspecified,
+            1 => RevocationReason::KeyCompromise,
+            2 => RevocationReason::CaCompromise,
+            3 => RevocationReason::AffiliationChanged,
+            4 => RevocationReason::Superseded,
+            5 => RevocationReason::CessationOfOperation,
+            6 => RevocationReason::CertificateHold,
+            8 => RevocationReason::RemoveFromCrl,
+            9 => RevocationReason::PrivilegeWithdrawn,
+            10 => RevocationReason::AaCompromise,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The revocation status of a certificate, as reported by the home server which issued it.
+pub enum RevocationStatus {
+    /// The certificate has not been revoked.
+    Good,
+    /// The certificate has been revoked.
+    Revoked {
+        /// The point in time at which the certificate was revoked.
+        revoked_at: Timestamp,
+        /// The reason for the revocation.
+        reason: RevocationReason,
+    },
+    /// The status of the certificate is not known, e.g. because the home server does not know the
+    /// certificate, or because the status could not be retrieved.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A request for the [RevocationStatus] of a certificate, sent to the home server which issued it.
+///
+/// The nonce is echoed in the signed [StatusResponse], which prevents an attacker from replaying an
+/// older response stating that a since revoked certificate is [RevocationStatus::Good].
+///
+/// When the `serde` feature is enabled, a [StatusRequest] is (de-)serialized as an object with the
+/// [CertId] of the certificate and the hex encoded nonce.
+pub struct StatusRequest {
+    /// The [CertId] of the certificate whose status is requested.
+    pub cert_id: CertId,
+    /// A one-time nonce, which must be echoed in the response.
+    pub nonce: Vec<u8>,
+}
+
+impl StatusRequest {
+    /// Creates a new [StatusRequest] for the certificate with the given [CertId].
+    pub fn new(cert_id: CertId, nonce: &[u8]) -> Self {
+        Self {
+            cert_id,
+            nonce: nonce.to_vec(),
+        }
+    }
+
+    /// Creates a [StatusRequest] for `cert`, carrying a random nonce of [STATUS_NONCE_LENGTH]
+    /// bytes.
+    pub fn fresh<S: Signature, P: PublicKey<S>>(
+        cert: &IdCert<S, P>,
+        rng: &mut impl CryptoRngCore,
+    ) -> Result<Self, ConversionError> {
+        let mut nonce = vec![0u8; STATUS_NONCE_LENGTH];
+        rng.fill_bytes(&mut nonce);
+        Ok(Self {
+            cert_id: cert.cert_id()?,
+            nonce,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The answer of a home server to a [StatusRequest], signed with the key of the home server's
+/// [IdCert].
+///
+/// When the `serde` feature is enabled, a [StatusResponse] is (de-)serialized as an object with
+/// the [CertId], the hex encoded nonce, the status as one of `good`, `revoked` or `unknown`, the
+/// point in time and reason code of a revocation, the points in time the response was produced
+/// at and should be refreshed at, and the hex encoded signature.
+pub struct StatusResponse<S: Signature> {
+    /// The [CertId] of the certificate whose status is reported.
+    pub cert_id: CertId,
+    /// The nonce of the [StatusRequest] this response answers.
+    pub nonce: Vec<u8>,
+    /// The revocation status of the certificate.
+    pub status: RevocationStatus,
+    /// The point in time at which the response was produced.
+    pub produced_at: Timestamp,
+    /// The point in time after which the response must not be relied upon, if any.
+    pub next_update: Option<Timestamp>,
+    /// Signature over [StatusResponse::signed_payload()] by the home server which issued the
+    /// certificate.
+    pub signature: S,
+}
+
+impl<S: Signature> StatusResponse<S> {
+    /// Creates and signs a [StatusResponse] answering `request`. `signing_key` must be the key of
+    /// the home server which issued the certificate.
+    pub fn new(
+        request: &StatusRequest,
+        status: RevocationStatus,
+        produced_at: Timestamp,
+        next_update: Option<Timestamp>,
+        signing_key: &impl PrivateKey<S>,
+    ) -> Self {
+        log::trace!(
+            "[StatusResponse::new()] creating status response for {}",
+            request.cert_id
+        );
+        let signature = signing_key.sign(&signed_payload(
+            &request.cert_id,
+            &request.nonce,
+            &status,
+            produced_at,
+            next_update,
+        ));
+        Self {
+            cert_id: request.cert_id.clone(),
+            nonce: request.nonce.clone(),
+            status,
+            produced_at,
+            next_update,
+            signature,
+        }
+    }
+
+    /// Returns the payload signed by the home server: the [CertId], the hex encoded nonce, the
+    /// status, [StatusResponse::produced_at] and [StatusResponse::next_update] in Unix epoch
+    /// seconds, each separated by a newline and prefixed with [REVOCATION_STATUS_PAYLOAD_PREFIX].
+    /// A revoked status is followed by the point in time and the reason code of the revocation; a
+    /// missing `next_update` is written as `-`.
+    pub fn signed_payload(&self) -> Vec<u8> {
+        signed_payload(
+            &self.cert_id,
+            &self.nonce,
+            &self.status,
+            self.produced_at,
+            self.next_update,
+        )
+    }
+
+    /// Verifies that this response answers `request` and is current at `time`, and that it was
+    /// signed by `responder`, the home server which issued the certificate.
+    ///
+    /// Fails with [InvalidCert::InvalidProperties], if the [CertId] or nonce do not match the
+    /// request, or if the certificate was not issued by `responder`; with
+    /// [InvalidCert::InvalidValidity], if the response was produced after `time`, if `time` is
+    /// past [StatusResponse::next_update], or if `responder` is not valid at `time`; and with
+    /// [InvalidCert::PublicKeyError], if the signature is incorrect.
+    pub fn verify<P: PublicKey<S>>(
+        &self,
+        request: &StatusRequest,
+        responder: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        if self.cert_id != request.cert_id || self.nonce != request.nonce {
+            return Err(malformed("Status response does not answer the request"));
+        }
+        if !request.cert_id.is_issued_by(&responder.id_cert_tbs.subject) {
+            return Err(malformed(
+                "Status response was not signed by the issuer of the certificate",
+            ));
+        }
+        let stale = match self.next_update {
+            Some(next_update) => time > next_update,
+            None => false,
+        };
+        if self.produced_at > time || stale || !responder.valid_at(time) {
+            log::debug!(
+                "[StatusResponse::verify()] Status response for {} is not current at {}",
+                self.cert_id,
+                time
+            );
+            return Err(InvalidCert::InvalidValidity);
+        }
+        responder
+            .id_cert_tbs
+            .subject_public_key
+            .verify_signature(&self.signature, &self.signed_payload())?;
+        Ok(())
+    }
+}
+
+fn signed_payload(
+    cert_id: &CertId,
+    nonce: &[u8],
+    status: &RevocationStatus,
+    produced_at: Timestamp,
+    next_update: Option<Timestamp>,
+) -> Vec<u8> {
+    let status = match status {
+        RevocationStatus::Good => "good".to_string(),
+        RevocationStatus::Revoked { revoked_at, reason } => {
+            format!("revoked\n{}\n{}", revoked_at.unix_seconds(), reason.code())
+        }
+        RevocationStatus::Unknown => "unknown".to_string(),
+    };
+    let next_update = match next_update {
+        Some(next_update) => next_update.unix_seconds().to_string(),
+        None => "-".to_string(),
+    };
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        REVOCATION_STATUS_PAYLOAD_PREFIX,
+        cert_id,
+        encode_hex(nonce),
+        status,
+        produced_at.unix_seconds(),
+        next_update
+    )
+    .into_bytes()
+}
+
+fn malformed(reason: &str) -> InvalidCert {
+    InvalidCert::InvalidProperties(ConstraintError::Malformed(Some(reason.to_string())))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// The future returned by [RevocationCheck::status()].
+pub type StatusFuture<'a> = Pin<Box<dyn Future<Output = RevocationStatus> + Send + 'a>>;
+/// The future returned by [RevocationCheck::status()]. On `wasm32`, the future is not required to
+/// be [Send], since futures wrapping JavaScript promises are bound to the thread they were created
+/// on.
+#[cfg(target_arch = "wasm32")]
+pub type StatusFuture<'a> = Pin<Box<dyn Future<Output = RevocationStatus> + 'a>>;
+
+/// A source of [RevocationStatus]es, such as the
+/// [RevocationChecker](crate::api::revocation::RevocationChecker) querying the revocation endpoint
+/// of a home server, used by
+/// [IdCertChain::validate_chain_with_revocation()](super::chain::IdCertChain::validate_chain_with_revocation()).
+///
+/// Implementations must only report [RevocationStatus::Good] or [RevocationStatus::Revoked] based
+/// on a verified [StatusResponse], and report [RevocationStatus::Unknown] if the status could not
+/// be determined for any reason.
+pub trait RevocationCheck<S: Signature, P: PublicKey<S>> {
+    /// Determines the revocation status of `cert` at `time`. `issuer` is the certificate of the
+    /// home server which issued `cert`.
+    fn status<'a>(
+        &'a self,
+        cert: &'a IdCert<S, P>,
+        issuer: &'a IdCert<S, P>,
+        time: Timestamp,
+    ) -> StatusFuture<'a>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// How a [RevocationStatus::Unknown] is treated during chain validation.
+pub enum RevocationMode {
+    /// Certificates with an unknown status are accepted. Only certificates which are known to be
+    /// revoked are rejected.
+    #[default]
+    SoftFail,
+    /// Certificates with an unknown status are rejected with
+    /// [InvalidCert::RevocationStatusUnknown].
+    HardFail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Configuration of a [RevocationCache].
+pub struct RevocationCacheConfig {
+    /// How long a [RevocationStatus::Good] is served from the cache without asking the checker
+    /// again.
+    pub good_ttl: Duration,
+    /// How long a [RevocationStatus::Revoked] is served from the cache without asking the checker
+    /// again.
+    pub revoked_ttl: Duration,
+    /// How long a [RevocationStatus::Unknown] is served from the cache, so that an unreachable
+    /// revocation endpoint is not queried again for every validation.
+    pub unknown_ttl: Duration,
+    /// How long after its TTL has expired a status is still served from the cache while it is
+    /// being revalidated. Revalidation is driven by [RevocationCache::revalidate()].
+    pub stale_while_revalidate: Duration,
+    /// How a [RevocationStatus::Unknown] is treated by [RevocationCache::check()].
+    pub mode: RevocationMode,
+}
+
+impl Default for RevocationCacheConfig {
+    fn default() -> Self {
+        Self {
+            good_ttl: Duration::from_secs(3600),
+            revoked_ttl: Duration::from_secs(86_400),
+            unknown_ttl: Duration::from_secs(60),
+            stale_while_revalidate: Duration::from_secs(300),
+            mode: RevocationMode::SoftFail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedStatus {
+    status: RevocationStatus,
+    fetched_at: Timestamp,
+}
+
+#[derive(Debug)]
+struct CacheState<S: Signature, P: PublicKey<S>> {
+    entries: HashMap<CertId, CachedStatus>,
+    pending: HashMap<CertId, PendingCheck<S, P>>,
+}
+
+#[derive(Debug)]
+struct PendingCheck<S: Signature, P: PublicKey<S>> {
+    cert: IdCert<S, P>,
+    issuer: IdCert<S, P>,
+}
+
+#[derive(Debug)]
+/// A caching layer over a [RevocationCheck], such as a
+/// [RevocationChecker](crate::api::revocation::RevocationChecker).
+///
+/// Statuses are cached per [CertId] for the TTL configured for their kind in the
+/// [RevocationCacheConfig]. Once the TTL has expired, a status is still served for the
+/// `stale_while_revalidate` window, and the certificate is queued for revalidation by
+/// [RevocationCache::revalidate()], which callers typically run periodically. Past that window,
+/// the checker is queried before answering. A revoked status is never replaced by an unknown one,
+/// so that an unreachable revocation endpoint cannot unrevoke a certificate.
+///
+/// A [RevocationCache] implements [RevocationCheck] itself, and can therefore be passed to
+/// [IdCertChain::validate_chain_with_revocation()](super::chain::IdCertChain::validate_chain_with_revocation()).
+pub struct RevocationCache<S: Signature, P: PublicKey<S>, C: RevocationCheck<S, P>> {
+    checker: C,
+    config: RevocationCacheConfig,
+    state: Mutex<CacheState<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>, C: RevocationCheck<S, P>> RevocationCache<S, P, C> {
+    /// Creates an empty [RevocationCache] over `checker`, using the default
+    /// [RevocationCacheConfig].
+    pub fn new(checker: C) -> Self {
+        Self {
+            checker,
+            config: RevocationCacheConfig::default(),
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                pending: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Sets the [RevocationCacheConfig] of the cache.
+    pub fn with_config(mut self, config: RevocationCacheConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the [RevocationCacheConfig] of the cache.
+    pub fn config(&self) -> &RevocationCacheConfig {
+        &self.config
+    }
+
+    /// Returns the underlying [RevocationCheck].
+    pub fn checker(&self) -> &C {
+        &self.checker
+    }
+
+    /// Returns the cached status of the certificate with the given [CertId], regardless of its
+    /// age, if any.
+    pub fn cached(&self, cert_id: &CertId) -> Option<RevocationStatus> {
+        self.lock().entries.get(cert_id).map(|entry| entry.status)
+    }
+
+    /// Removes the cached status of the certificate with the given [CertId], e.g. after learning
+    /// about its revocation through other means.
+    pub fn invalidate(&self, cert_id: &CertId) {
+        let mut state = self.lock();
+        state.entries.remove(cert_id);
+        state.pending.remove(cert_id);
+    }
+
+    /// Removes all cached statuses.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.pending.clear();
+    }
+
+    /// Returns the number of cached statuses.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns `true`, if no statuses are cached.
+    pub fn is_empty(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    /// Returns the number of certificates queued for revalidation.
+    pub fn pending(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    /// Returns the status of `cert` at `time`, served from the cache where possible.
+    pub async fn lookup(
+        &self,
+        cert: &IdCert<S, P>,
+        issuer: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> RevocationStatus {
+        let cert_id = match cert.cert_id() {
+            Ok(cert_id) => cert_id,
+            Err(_) => return self.checker.status(cert, issuer, time).await,
+        };
+        {
+            let mut state = self.lock();
+            if let Some(entry) = state.entries.get(&cert_id).copied() {
+                let expires_at = entry.fetched_at.checked_add(self.ttl(&entry.status));
+                let stale_until = expires_at.and_then(|expires_at| {
+                    expires_at.checked_add(self.config.stale_while_revalidate)
+                });
+                if expires_at.map_or(true, |expires_at| time < expires_at) {
+                    return entry.status;
+                }
+                if stale_until.map_or(true, |stale_until| time < stale_until) {
+                    log::trace!(
+                        "[RevocationCache::lookup()] Serving stale status of {} while it is revalidated",
+                        cert_id
+                    );
+                    state
+                        .pending
+                        .entry(cert_id)
+                        .or_insert_with(|| PendingCheck {
+                            cert: cert.clone(),
+                            issuer: issuer.clone(),
+                        });
+                    return entry.status;
+                }
+            }
+        }
+        let status = self.checker.status(cert, issuer, time).await;
+        self.store(cert_id, status, time)
+    }
+
+    /// Like [RevocationCache::lookup()], but applies the [RevocationMode] of the
+    /// [RevocationCacheConfig]. Fails with [InvalidCert::Revoked], if `cert` has been revoked, and
+    /// with [InvalidCert::RevocationStatusUnknown], if its status is unknown and the mode is
+    /// [RevocationMode::HardFail].
+    pub async fn check(
+        &self,
+        cert: &IdCert<S, P>,
+        issuer: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<RevocationStatus, InvalidCert> {
+        match self.lookup(cert, issuer, time).await {
+            RevocationStatus::Revoked { revoked_at, .. } => Err(InvalidCert::Revoked(revoked_at)),
+            RevocationStatus::Unknown if self.config.mode == RevocationMode::HardFail => {
+                Err(InvalidCert::RevocationStatusUnknown)
+            }
+            status => Ok(status),
+        }
+    }
+
+    /// Queries the checker for every certificate queued for revalidation, and updates the cache
+    /// with the results. Returns the number of revalidated certificates.
+    pub async fn revalidate(&self, time: Timestamp) -> usize {
+        let pending: Vec<(CertId, PendingCheck<S, P>)> = self.lock().pending.drain().collect();
+        log::trace!(
+            "[RevocationCache::revalidate()] Revalidating {} certificates",
+            pending.len()
+        );
+        for (cert_id, check) in pending.iter() {
+            let status = self.checker.status(&check.cert, &check.issuer, time).await;
+            self.store(cert_id.clone(), status, time);
+        }
+        pending.len()
+    }
+
+    /// Caches `status`, unless it is unknown and a revoked status is cached, and returns the
+    /// status now cached.
+    fn store(
+        &self,
+        cert_id: CertId,
+        status: RevocationStatus,
+        time: Timestamp,
+    ) -> RevocationStatus {
+        let mut state = self.lock();
+        state.pending.remove(&cert_id);
+        let entry = state.entries.entry(cert_id).or_insert(CachedStatus {
+            status,
+            fetched_at: time,
+        });
+        if !(status == RevocationStatus::Unknown
+            && matches!(entry.status, RevocationStatus::Revoked { .. }))
+        {
+            entry.status = status;
+        }
+        entry.fetched_at = time;
+        entry.status
+    }
+
+    fn ttl(&self, status: &RevocationStatus) -> Duration {
+        match status {
+            RevocationStatus::Good => self.config.good_ttl,
+            RevocationStatus::Revoked { .. } => self.config.revoked_ttl,
+            RevocationStatus::Unknown => self.config.unknown_ttl,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState<S, P>> {
+        // The lock is never held across an await point or while calling into the checker.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S, P, C> RevocationCheck<S, P> for RevocationCache<S, P, C>
+where
+    S: Signature + Send + Sync,
+    P: PublicKey<S> + Send + Sync,
+    C: RevocationCheck<S, P> + Sync,
+{
+    fn status<'a>(
+        &'a self,
+        cert: &'a IdCert<S, P>,
+        issuer: &'a IdCert<S, P>,
+        time: Timestamp,
+    ) -> StatusFuture<'a> {
+        Box::pin(self.lookup(cert, issuer, time))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use serde::de::Error;
+    use serde::{Deserialize, Serialize};
+
+    use crate::certs::certid::CertId;
+    use crate::encoding::{decode_hex, encode_hex};
+    use crate::signature::Signature;
+    use crate::timestamp::Timestamp;
+
+    use super::{RevocationReason, RevocationStatus, StatusRequest, StatusResponse};
+
+    #[derive(Serialize, Deserialize)]
+    struct StatusRequestJson {
+        cert_id: CertId,
+        nonce: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct StatusResponseJson {
+        cert_id: CertId,
+        nonce: String,
+        status: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        revoked_at: Option<Timestamp>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<u8>,
+        produced_at: Timestamp,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_update: Option<Timestamp>,
+        signature: String,
+    }
+
+    impl Serialize for StatusRequest {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            StatusRequestJson {
+                cert_id: self.cert_id.clone(),
+                nonce: encode_hex(&self.nonce),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for StatusRequest {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = StatusRequestJson::deserialize(deserializer)?;
+            Ok(StatusRequest {
+                cert_id: json.cert_id,
+                nonce: decode_hex(&json.nonce).map_err(D::Error::custom)?,
+            })
+        }
+    }
+
+    impl<S: Signature> Serialize for StatusResponse<S> {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            let signature = self
+                .signature
+                .to_bitstring()
+                .map_err(serde::ser::Error::custom)?;
+            let (status, revoked_at, reason) = match self.status {
+                RevocationStatus::Good => ("good", None, None),
+                RevocationStatus::Revoked { revoked_at, reason } => {
+                    ("revoked", Some(revoked_at), Some(reason.code()))
+                }
+                RevocationStatus::Unknown => ("unknown", None, None),
+            };
+            StatusResponseJson {
+                cert_id: self.cert_id.clone(),
+                nonce: encode_hex(&self.nonce),
+                status: status.to_string(),
+                revoked_at,
+                reason,
+                produced_at: self.produced_at,
+                next_update: self.next_update,
+                signature: encode_hex(signature.raw_bytes()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, S: Signature> Deserialize<'de> for StatusResponse<S> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = StatusResponseJson::deserialize(deserializer)?;
+            let status = match (json.status.as_str(), json.revoked_at, json.reason) {
+                ("good", None, None) => RevocationStatus::Good,
+                ("unknown", None, None) => RevocationStatus::Unknown,
+                ("revoked", Some(revoked_at), Some(code)) => RevocationStatus::Revoked {
+                    revoked_at,
+                    reason: RevocationReason::from_code(code).ok_or_else(|| {
+                        D::Error::custom(format!("Unknown revocation reason code {}", code))
+                    })?,
+                },
+                (status, _, _) => {
+                    return Err(D::Error::custom(format!(
+                        "Invalid revocation status {}",
+                        status
+                    )))
+                }
+            };
+            let signature = decode_hex(&json.signature).map_err(D::Error::custom)?;
+            Ok(StatusResponse {
+                cert_id: json.cert_id,
+                nonce: decode_hex(&json.nonce).map_err(D::Error::custom)?,
+                status,
+                produced_at: json.produced_at,
+                next_update: json.next_update,
+                signature: S::from_bytes(&signature),
+            })
+        }
+    }
+}
@@ -0,0 +1,261 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use crate::errors::{ConstraintError, ConversionError, InvalidCert, InvalidInput, PublicKeyError};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::certid::CertId;
+use super::idcert::IdCert;
+
+/// The prefix of the payload signed when creating a [SupersededNotice].
+pub static SUPERSEDED_PAYLOAD_PREFIX: &str = "polyproto-superseded-v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A signed statement of a home server, declaring that an [IdCert] has been superseded by another
+/// one as the result of a completed key rotation.
+///
+/// A superseded certificate stays valid for verifying data which was signed before the
+/// certificate was superseded, but must not be used to establish new sessions. Use
+/// [SupersededCerts] to keep track of received notices.
+///
+/// When the `serde` feature is enabled, a [SupersededNotice] is (de-)serialized as an object with
+/// the [CertId]s of both certificates, the point in time of the rotation and the hex encoded
+/// signature.
+pub struct SupersededNotice<S: Signature> {
+    /// The [CertId] of the superseded certificate.
+    pub old: CertId,
+    /// The [CertId] of the certificate superseding it.
+    pub new: CertId,
+    /// The point in time at which the rotation completed.
+    pub superseded_at: Timestamp,
+    /// Signature over [SupersededNotice::signed_payload()] by the home server which issued the new
+    /// certificate.
+    pub signature: S,
+}
+
+impl<S: Signature> SupersededNotice<S> {
+    /// Creates and signs a [SupersededNotice] for a completed rotation from `old_cert` to
+    /// `new_cert`. `signing_key` must be the key of the home server which issued `new_cert`.
+    ///
+    /// Fails, if the subjects of both certificates differ, as a certificate can only be
+    /// superseded by a certificate of the same actor or home server.
+    pub fn new<P: PublicKey<S>>(
+        old_cert: &IdCert<S, P>,
+        new_cert: &IdCert<S, P>,
+        superseded_at: Timestamp,
+        signing_key: &impl PrivateKey<S>,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[SupersededNotice::new()] creating superseded notice");
+        if old_cert.id_cert_tbs.subject != new_cert.id_cert_tbs.subject {
+            return Err(InvalidInput::Malformed(
+                "A certificate can only be superseded by a certificate with the same subject"
+                    .to_string(),
+            )
+            .into());
+        }
+        let old = old_cert.cert_id()?;
+        let new = new_cert.cert_id()?;
+        let signature = signing_key.sign(&signed_payload(&old, &new, superseded_at));
+        Ok(Self {
+            old,
+            new,
+            superseded_at,
+            signature,
+        })
+    }
+
+    /// Returns the payload signed by the home server: the [CertId]s of the old and new
+    /// certificates and [SupersededNotice::superseded_at] in Unix epoch seconds, each separated by
+    /// a newline and prefixed with [SUPERSEDED_PAYLOAD_PREFIX].
+    pub fn signed_payload(&self) -> Vec<u8> {
+        signed_payload(&self.old, &self.new, self.superseded_at)
+    }
+
+    /// Verifies the signature of this notice using the public key of the home server which issued
+    /// the new certificate.
+    pub fn verify<P: PublicKey<S>>(&self, public_key: &P) -> Result<(), PublicKeyError> {
+        public_key.verify_signature(&self.signature, &self.signed_payload())
+    }
+}
+
+fn signed_payload(old: &CertId, new: &CertId, superseded_at: Timestamp) -> Vec<u8> {
+    format!(
+        "{}\n{}\n{}\n{}",
+        SUPERSEDED_PAYLOAD_PREFIX,
+        old,
+        new,
+        superseded_at.unix_seconds()
+    )
+    .into_bytes()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// What an [IdCert] is about to be used for. Determines whether a superseded certificate is
+/// still acceptable.
+pub enum CertUsage {
+    /// Establishing a new session. Superseded certificates are not acceptable.
+    NewSession,
+    /// Verifying data signed at the given point in time. Superseded certificates are acceptable,
+    /// if the data was signed before the certificate was superseded.
+    PastData {
+        /// The point in time at which the data was signed.
+        signed_at: Timestamp,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An entry of [SupersededCerts], describing by which certificate and when a certificate was
+/// superseded.
+pub struct Supersession {
+    /// The [CertId] of the certificate superseding the certificate.
+    pub superseded_by: CertId,
+    /// The point in time at which the certificate was superseded.
+    pub superseded_at: Timestamp,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A store of verified [SupersededNotice]s, used to decide whether an [IdCert] may be used for a
+/// given [CertUsage].
+pub struct SupersededCerts {
+    entries: HashMap<CertId, Supersession>,
+}
+
+impl SupersededCerts {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verifies a [SupersededNotice] using the public key of the home server which issued the new
+    /// certificate and, if the signature is valid, records the old certificate as superseded. If
+    /// the certificate has been recorded as superseded before, the earlier rotation is kept.
+    pub fn insert<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        notice: &SupersededNotice<S>,
+        public_key: &P,
+    ) -> Result<(), InvalidCert> {
+        notice.verify(public_key)?;
+        let entry = self
+            .entries
+            .entry(notice.old.clone())
+            .or_insert_with(|| Supersession {
+                superseded_by: notice.new.clone(),
+                superseded_at: notice.superseded_at,
+            });
+        if notice.superseded_at < entry.superseded_at {
+            entry.superseded_by = notice.new.clone();
+            entry.superseded_at = notice.superseded_at;
+        }
+        Ok(())
+    }
+
+    /// Returns the [Supersession] of the certificate with the given [CertId], if it has been
+    /// superseded.
+    pub fn get(&self, cert_id: &CertId) -> Option<&Supersession> {
+        self.entries.get(cert_id)
+    }
+
+    /// Returns the number of superseded certificates in this store.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true`, if no superseded certificates are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks whether `cert` may be used for `usage`. Fails with [InvalidCert::Superseded], if the
+    /// certificate has been superseded and `usage` is [CertUsage::NewSession], or if the data was
+    /// signed at or after the point in time the certificate was superseded.
+    pub fn check<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+        usage: CertUsage,
+    ) -> Result<(), InvalidCert> {
+        let cert_id = match cert.cert_id() {
+            Ok(cert_id) => cert_id,
+            Err(_) => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some("The issuer of the certificate cannot be DER encoded".to_string()),
+                )))
+            }
+        };
+        let supersession = match self.entries.get(&cert_id) {
+            Some(supersession) => supersession,
+            None => return Ok(()),
+        };
+        match usage {
+            CertUsage::PastData { signed_at } if signed_at < supersession.superseded_at => Ok(()),
+            _ => {
+                log::debug!(
+                    "[SupersededCerts::check()] Certificate {} was superseded by {}",
+                    cert_id,
+                    supersession.superseded_by
+                );
+                Err(InvalidCert::Superseded(supersession.superseded_at))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use serde::de::Error;
+    use serde::{Deserialize, Serialize};
+
+    use crate::certs::certid::CertId;
+    use crate::encoding::{decode_hex, encode_hex};
+    use crate::signature::Signature;
+    use crate::timestamp::Timestamp;
+
+    use super::SupersededNotice;
+
+    #[derive(Serialize, Deserialize)]
+    struct SupersededNoticeJson {
+        old: CertId,
+        new: CertId,
+        superseded_at: Timestamp,
+        signature: String,
+    }
+
+    impl<S: Signature> Serialize for SupersededNotice<S> {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            let signature = self
+                .signature
+                .to_bitstring()
+                .map_err(serde::ser::Error::custom)?;
+            SupersededNoticeJson {
+                old: self.old.clone(),
+                new: self.new.clone(),
+                superseded_at: self.superseded_at,
+                signature: encode_hex(signature.raw_bytes()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, S: Signature> Deserialize<'de> for SupersededNotice<S> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = SupersededNoticeJson::deserialize(deserializer)?;
+            let signature = decode_hex(&json.signature).map_err(D::Error::custom)?;
+            Ok(SupersededNotice {
+                old: json.old,
+                new: json.new,
+                superseded_at: json.superseded_at,
+                signature: S::from_bytes(&signature),
+            })
+        }
+    }
+}
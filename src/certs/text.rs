@@ -0,0 +1,272 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Write;
+
+use der::oid::ObjectIdentifier;
+
+use crate::errors::ConversionError;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+use super::capabilities::{ExtendedKeyUsage, KeyUsage};
+use super::claims::ClaimValue;
+use super::fingerprint::FingerprintDigest;
+use super::idcert::IdCert;
+use super::Target;
+
+/// The number of bytes per line when rendering keys and signatures as hex, as done by OpenSSL.
+const BYTES_PER_LINE: usize = 15;
+
+impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
+    /// Renders this certificate in a human-readable form modeled after the output of
+    /// `openssl x509 -text`: the subject, issuer, serial number, validity period, subject public
+    /// key, capabilities and other extensions, the signature, and the fingerprints of the
+    /// certificate and its key. Meant for support staff and CLI tools inspecting certificates.
+    ///
+    /// The output is not meant to be parsed; its layout may change between releases. The
+    /// certificate is rendered as is, without validating or verifying it.
+    pub fn to_text(&self) -> Result<String, ConversionError> {
+        let tbs = &self.id_cert_tbs;
+        let capabilities = &tbs.capabilities;
+        let mut text = String::new();
+        // Writing to a String never fails.
+        let _ = writeln!(text, "Certificate:");
+        let _ = writeln!(text, "    Data:");
+        let _ = writeln!(text, "        Version: 3 (0x2)");
+        let _ = writeln!(
+            text,
+            "        Serial Number: {}",
+            colon_hex(tbs.serial_number.as_bytes())
+        );
+        let _ = writeln!(
+            text,
+            "        Signature Algorithm: {}",
+            algorithm_name(&tbs.signature_algorithm.oid)
+        );
+        let _ = writeln!(text, "        Issuer: {}", tbs.issuer);
+        let _ = writeln!(text, "        Validity");
+        let _ = writeln!(
+            text,
+            "            Not Before: {}",
+            Timestamp::from(tbs.validity.not_before)
+        );
+        let _ = writeln!(
+            text,
+            "            Not After : {}",
+            Timestamp::from(tbs.validity.not_after)
+        );
+        let _ = writeln!(text, "        Subject: {}", tbs.subject);
+        let public_key_info = tbs.subject_public_key.public_key_info();
+        let _ = writeln!(text, "        Subject Public Key Info:");
+        let _ = writeln!(
+            text,
+            "            Public Key Algorithm: {}",
+            algorithm_name(&public_key_info.algorithm.oid)
+        );
+        write_hex_block(
+            &mut text,
+            16,
+            public_key_info.public_key_bitstring.raw_bytes(),
+        );
+
+        let _ = writeln!(text, "        polyproto Capabilities:");
+        let _ = writeln!(
+            text,
+            "            Target: {}",
+            match Target::detect(capabilities) {
+                Target::Actor => "Actor",
+                Target::HomeServer => "Home server",
+            }
+        );
+        let basic_constraints = &capabilities.basic_constraints;
+        let _ = write!(
+            text,
+            "            Basic Constraints: CA:{}",
+            match basic_constraints.ca {
+                true => "TRUE",
+                false => "FALSE",
+            }
+        );
+        match basic_constraints.path_length {
+            Some(path_length) => {
+                let _ = writeln!(text, ", pathlen:{}", path_length);
+            }
+            None => text.push('\n'),
+        }
+        let _ = writeln!(
+            text,
+            "            Key Usage: {}",
+            capabilities
+                .key_usage
+                .key_usages
+                .iter()
+                .map(key_usage_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if !capabilities
+            .extended_key_usage
+            .extended_key_usages
+            .is_empty()
+        {
+            let _ = writeln!(
+                text,
+                "            Extended Key Usage: {}",
+                capabilities
+                    .extended_key_usage
+                    .extended_key_usages
+                    .iter()
+                    .map(extended_key_usage_name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let _ = writeln!(text, "        Extensions:");
+        if let Some(subject_key_identifier) = &tbs.subject_key_identifier {
+            let _ = writeln!(
+                text,
+                "            Subject Key Identifier: {}",
+                colon_hex(subject_key_identifier.as_bytes())
+            );
+        }
+        if let Some(authority_key_identifier) = &tbs.authority_key_identifier {
+            let _ = writeln!(
+                text,
+                "            Authority Key Identifier: {}",
+                colon_hex(authority_key_identifier.as_bytes())
+            );
+        }
+        if !tbs.dns_names().is_empty() {
+            let _ = writeln!(
+                text,
+                "            Subject Alternative Name: {}",
+                tbs.dns_names()
+                    .iter()
+                    .map(|name| format!("DNS:{}", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if let Some(crl_distribution_point) = &tbs.crl_distribution_point {
+            let _ = writeln!(
+                text,
+                "            CRL Distribution Point: URI:{}",
+                crl_distribution_point
+            );
+        }
+        if !tbs.claims.is_empty() {
+            let _ = writeln!(text, "            Custom Claims:");
+            for (key, value) in tbs.claims.iter() {
+                let _ = writeln!(text, "                {}: {}", key, claim_value(value));
+            }
+        }
+        for extension in capabilities.custom_extensions.iter() {
+            let _ = writeln!(
+                text,
+                "            {}:{}",
+                extension.oid(),
+                match extension.is_critical() {
+                    true => " critical",
+                    false => "",
+                }
+            );
+            write_hex_block(&mut text, 16, extension.value());
+        }
+
+        let _ = writeln!(
+            text,
+            "    Signature Algorithm: {}",
+            algorithm_name(&tbs.signature_algorithm.oid)
+        );
+        let _ = writeln!(text, "    Signature Value:");
+        write_hex_block(&mut text, 8, self.signature.to_bitstring()?.raw_bytes());
+
+        let _ = writeln!(text, "Fingerprints:");
+        for (name, digest) in [
+            ("SHA-256", FingerprintDigest::Sha256),
+            ("SHA-512", FingerprintDigest::Sha512),
+        ] {
+            let _ = writeln!(
+                text,
+                "    {}: {}",
+                name,
+                self.fingerprint(digest)?.to_colon_hex()
+            );
+        }
+        let _ = writeln!(text, "    Key SHA-256: {}", self.key_fingerprint()?);
+        let _ = writeln!(text, "    SPKI pin-sha256: {}", self.spki_pin_sha256()?);
+        Ok(text)
+    }
+}
+
+/// Returns the name OpenSSL uses for the algorithm with the given OID, or the OID itself for
+/// algorithms unknown to polyproto.
+fn algorithm_name(oid: &ObjectIdentifier) -> String {
+    match oid.to_string().as_str() {
+        "1.3.101.112" => "ED25519".to_string(),
+        "1.2.840.10045.2.1" => "id-ecPublicKey".to_string(),
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256".to_string(),
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384".to_string(),
+        oid => oid.to_string(),
+    }
+}
+
+fn key_usage_name(key_usage: &KeyUsage) -> &'static str {
+    match key_usage {
+        KeyUsage::DigitalSignature => "Digital Signature",
+        KeyUsage::ContentCommitment => "Non Repudiation",
+        KeyUsage::KeyEncipherment => "Key Encipherment",
+        KeyUsage::DataEncipherment => "Data Encipherment",
+        KeyUsage::KeyAgreement => "Key Agreement",
+        KeyUsage::KeyCertSign => "Certificate Sign",
+        KeyUsage::CrlSign => "CRL Sign",
+        KeyUsage::EncipherOnly => "Encipher Only",
+        KeyUsage::DecipherOnly => "Decipher Only",
+    }
+}
+
+fn extended_key_usage_name(extended_key_usage: &ExtendedKeyUsage) -> String {
+    match extended_key_usage {
+        ExtendedKeyUsage::ServerAuth => "TLS Web Server Authentication".to_string(),
+        ExtendedKeyUsage::ClientAuth => "TLS Web Client Authentication".to_string(),
+        ExtendedKeyUsage::TimeStamping => "Time Stamping".to_string(),
+        ExtendedKeyUsage::OcspSigning => "OCSP Signing".to_string(),
+        ExtendedKeyUsage::AnyExtendedKeyUsage => "Any Extended Key Usage".to_string(),
+        ExtendedKeyUsage::Other(oid) => oid.to_string(),
+    }
+}
+
+fn claim_value(value: &ClaimValue) -> String {
+    match value {
+        ClaimValue::Text(text) => format!("{:?}", text),
+        ClaimValue::Bytes(bytes) => colon_hex(bytes),
+        ClaimValue::Integer(integer) => integer.to_string(),
+        ClaimValue::Bool(boolean) => boolean.to_string(),
+    }
+}
+
+/// Returns `bytes` as lowercase hex bytes separated by colons, e.g. `3a:4f:...`.
+fn colon_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Writes `bytes` as colon separated hex, wrapped into lines of [BYTES_PER_LINE] bytes indented by
+/// `indent` spaces.
+fn write_hex_block(text: &mut String, indent: usize, bytes: &[u8]) {
+    let lines = bytes.chunks(BYTES_PER_LINE).collect::<Vec<_>>();
+    for (index, line) in lines.iter().enumerate() {
+        let _ = write!(text, "{:indent$}{}", "", colon_hex(line), indent = indent);
+        match index + 1 < lines.len() {
+            true => text.push_str(":\n"),
+            false => text.push('\n'),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use crate::errors::InvalidCert;
+use crate::key::{KeyFingerprint, PublicKey};
+use crate::signature::Signature;
+
+use super::chain::IdCertChain;
+use super::domain_of;
+use super::idcert::IdCert;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A root of trust for the home server of a domain, registered in a [TrustStore].
+pub enum TrustAnchor<S: Signature, P: PublicKey<S>> {
+    /// The self-signed root certificate of the home server. Matches chains whose root is this
+    /// exact certificate.
+    Root(Box<IdCert<S, P>>),
+    /// The [KeyFingerprint] of a public key of the home server, also known as an SPKI pin. Matches
+    /// chains in which any issuer has this public key, which allows pinning the key of an
+    /// intermediate certificate as well as that of the root.
+    Pin(KeyFingerprint),
+}
+
+impl<S: Signature, P: PublicKey<S>> TrustAnchor<S, P> {
+    /// Returns `true`, if this anchor vouches for the issuers of `chain`.
+    pub fn matches(&self, chain: &IdCertChain<S, P>) -> bool {
+        match self {
+            TrustAnchor::Root(root) => chain.root() == Some(root.as_ref()),
+            TrustAnchor::Pin(pin) => chain.issuers().iter().any(|issuer| {
+                issuer
+                    .key_fingerprint()
+                    .is_ok_and(|fingerprint| fingerprint == *pin)
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// The [TrustAnchor]s an application trusts for the home server of each domain.
+///
+/// Certificates learned through discovery are only as trustworthy as the discovery path they were
+/// learned through. Pinning the root certificates or public keys of known home servers in a
+/// [TrustStore], and validating chains using [IdCertChain::validate_chain_anchored()], ensures
+/// that a compromised discovery path cannot introduce a home server certificate of its own.
+///
+/// Domains are compared case-insensitively. The domain of a chain is made up of the domain
+/// components of the subject of its root certificate, e.g. `polyphony.chat` for
+/// `DC=polyphony,DC=chat`.
+pub struct TrustStore<S: Signature, P: PublicKey<S>> {
+    anchors: BTreeMap<String, Vec<TrustAnchor<S, P>>>,
+    generation: u64,
+}
+
+impl<S: Signature, P: PublicKey<S>> Default for TrustStore<S, P> {
+    fn default() -> Self {
+        Self {
+            anchors: BTreeMap::new(),
+            generation: 0,
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> TrustStore<S, P> {
+    /// Creates an empty [TrustStore].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins `root` as the root certificate of the home server of `domain`.
+    pub fn with_root(mut self, domain: &str, root: IdCert<S, P>) -> Self {
+        self.add(domain, TrustAnchor::Root(Box::new(root)));
+        self
+    }
+
+    /// Pins the public key with the fingerprint `pin` for the home server of `domain`.
+    pub fn with_pin(mut self, domain: &str, pin: KeyFingerprint) -> Self {
+        self.add(domain, TrustAnchor::Pin(pin));
+        self
+    }
+
+    /// Registers `anchor` for the home server of `domain`. Returns `false`, if the anchor was
+    /// registered before.
+    pub fn add(&mut self, domain: &str, anchor: TrustAnchor<S, P>) -> bool {
+        let anchors = self.anchors.entry(domain.to_lowercase()).or_default();
+        if anchors.contains(&anchor) {
+            return false;
+        }
+        anchors.push(anchor);
+        self.generation += 1;
+        true
+    }
+
+    /// Removes `anchor` from the anchors of `domain`. Returns `true`, if it was registered.
+    pub fn remove(&mut self, domain: &str, anchor: &TrustAnchor<S, P>) -> bool {
+        let domain = domain.to_lowercase();
+        let Some(anchors) = self.anchors.get_mut(&domain) else {
+            return false;
+        };
+        let len = anchors.len();
+        anchors.retain(|registered| registered != anchor);
+        if anchors.len() == len {
+            return false;
+        }
+        if anchors.is_empty() {
+            self.anchors.remove(&domain);
+        }
+        self.generation += 1;
+        true
+    }
+
+    /// Removes all anchors of `domain`. Returns `true`, if there were any.
+    pub fn remove_domain(&mut self, domain: &str) -> bool {
+        if self.anchors.remove(&domain.to_lowercase()).is_none() {
+            return false;
+        }
+        self.generation += 1;
+        true
+    }
+
+    /// Returns the anchors registered for `domain`.
+    pub fn anchors(&self, domain: &str) -> &[TrustAnchor<S, P>] {
+        self.anchors
+            .get(&domain.to_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the domains with at least one anchor, in lexicographical order.
+    pub fn domains(&self) -> impl Iterator<Item = &str> {
+        self.anchors.keys().map(String::as_str)
+    }
+
+    /// Returns the number of domains with at least one anchor.
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Returns `true`, if no anchors are registered.
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Returns the generation of the trust store, which is incremented whenever an anchor is added
+    /// or removed. Use it with
+    /// [CacheGeneration::with_trust_store()](crate::cache::CacheGeneration::with_trust_store), so
+    /// that cached verification results are invalidated when the trust store changes.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Checks, that the issuers of `chain` are anchored in the trust store: one of the anchors
+    /// registered for the domain of the root certificate of `chain` must match the chain.
+    ///
+    /// Fails with [InvalidCert::NotAnchored], if no anchor matches, including when no anchor is
+    /// registered for the domain or the chain has no issuers. The chain itself is not validated;
+    /// see [IdCertChain::validate_chain_anchored()].
+    pub fn check(&self, chain: &IdCertChain<S, P>) -> Result<(), InvalidCert> {
+        let domain = chain
+            .root()
+            .map(|root| domain_of(&root.id_cert_tbs.subject))
+            .unwrap_or_default();
+        if self
+            .anchors(&domain)
+            .iter()
+            .any(|anchor| anchor.matches(chain))
+        {
+            return Ok(());
+        }
+        log::debug!(
+            "[TrustStore::check()] No anchor registered for {:?} matches the certificate chain",
+            domain
+        );
+        Err(InvalidCert::NotAnchored(domain))
+    }
+}
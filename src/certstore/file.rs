@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use der::pem::LineEnding;
+
+use crate::certs::certid::CertId;
+use crate::certs::idcert::IdCert;
+use crate::errors::CertStoreError;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+
+use super::CertStore;
+
+/// The file extension of the files holding stored certificates.
+pub const CERT_FILE_EXTENSION: &str = "pem";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A [CertStore] storing each certificate as a PEM file in a directory. The file of a certificate
+/// is named after its [CertId], e.g. `<issuer fingerprint>.08.pem`. Files not following this
+/// scheme are ignored.
+///
+/// Writes are atomic: certificates are written to a temporary file, which is then renamed.
+/// Certificates are loaded using [IdCert::from_pem_unchecked()]; anyone able to write to the
+/// directory can place arbitrary certificates in the store.
+pub struct FileCertStore {
+    directory: PathBuf,
+}
+
+impl FileCertStore {
+    /// Opens the certificate store in `directory`, creating the directory if it does not exist.
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self, CertStoreError> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        log::trace!(
+            "[FileCertStore::open()] Opened certificate store in {}",
+            directory.display()
+        );
+        Ok(Self { directory })
+    }
+
+    /// Returns the directory of this certificate store.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the path of the file holding the certificate stored under `cert_id`.
+    pub fn path_of(&self, cert_id: &CertId) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", cert_id, CERT_FILE_EXTENSION))
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> CertStore<S, P> for FileCertStore {
+    fn insert(&mut self, cert: &IdCert<S, P>) -> Result<CertId, CertStoreError> {
+        let cert_id = cert.cert_id()?;
+        log::trace!("[FileCertStore::insert()] Storing certificate {}", cert_id);
+        let pem = cert.to_pem(LineEnding::LF)?;
+        let path = self.path_of(&cert_id);
+        let temporary = path.with_extension(format!("{}.tmp", CERT_FILE_EXTENSION));
+        let result = fs::File::create(&temporary)
+            .and_then(|mut file| {
+                file.write_all(pem.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temporary, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result?;
+        Ok(cert_id)
+    }
+
+    fn get(&self, cert_id: &CertId) -> Result<Option<IdCert<S, P>>, CertStoreError> {
+        match fs::read_to_string(self.path_of(cert_id)) {
+            Ok(pem) => Ok(Some(IdCert::from_pem_unchecked(&pem)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn delete(&mut self, cert_id: &CertId) -> Result<bool, CertStoreError> {
+        match fs::remove_file(self.path_of(cert_id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<CertId>, CertStoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str())
+                != Some(CERT_FILE_EXTENSION)
+            {
+                continue;
+            }
+            match path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(CertId::from_str)
+            {
+                Some(Ok(id)) => ids.push(id),
+                _ => log::debug!(
+                    "[FileCertStore::list()] Ignoring unrelated file {}",
+                    path.display()
+                ),
+            }
+        }
+        ids.sort_by_cached_key(|id| id.to_string());
+        Ok(ids)
+    }
+}
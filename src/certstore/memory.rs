@@ -0,0 +1,67 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use crate::certs::certid::CertId;
+use crate::certs::idcert::IdCert;
+use crate::errors::CertStoreError;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+
+use super::CertStore;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A [CertStore] keeping certificates in memory, e.g. for tests or for caches which do not need to
+/// survive a restart.
+pub struct MemoryCertStore<S: Signature, P: PublicKey<S>> {
+    certs: HashMap<CertId, IdCert<S, P>>,
+}
+
+impl<S: Signature, P: PublicKey<S>> Default for MemoryCertStore<S, P> {
+    fn default() -> Self {
+        Self {
+            certs: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> MemoryCertStore<S, P> {
+    /// Creates an empty [MemoryCertStore].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of stored certificates.
+    pub fn len(&self) -> usize {
+        self.certs.len()
+    }
+
+    /// Returns `true`, if no certificates are stored.
+    pub fn is_empty(&self) -> bool {
+        self.certs.is_empty()
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> CertStore<S, P> for MemoryCertStore<S, P> {
+    fn insert(&mut self, cert: &IdCert<S, P>) -> Result<CertId, CertStoreError> {
+        let cert_id = cert.cert_id()?;
+        self.certs.insert(cert_id.clone(), cert.clone());
+        Ok(cert_id)
+    }
+
+    fn get(&self, cert_id: &CertId) -> Result<Option<IdCert<S, P>>, CertStoreError> {
+        Ok(self.certs.get(cert_id).cloned())
+    }
+
+    fn delete(&mut self, cert_id: &CertId) -> Result<bool, CertStoreError> {
+        Ok(self.certs.remove(cert_id).is_some())
+    }
+
+    fn list(&self) -> Result<Vec<CertId>, CertStoreError> {
+        let mut ids: Vec<CertId> = self.certs.keys().cloned().collect();
+        ids.sort_by_cached_key(|id| id.to_string());
+        Ok(ids)
+    }
+}
@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use der::asn1::Uint;
+
+use crate::certs::certid::CertId;
+use crate::certs::custody::uid;
+use crate::certs::idcert::IdCert;
+use crate::errors::CertStoreError;
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::types::FederationId;
+
+/// [FileCertStore](file::FileCertStore), storing each certificate as a PEM file in a directory.
+pub mod file;
+/// [MemoryCertStore](memory::MemoryCertStore), keeping certificates in memory.
+pub mod memory;
+#[cfg(feature = "sqlx")]
+/// [SqlCertStore](sql::SqlCertStore) and [SqlPkmStore](sql::SqlPkmStore), storing certificates
+/// and encrypted private key material in an SQL database using `sqlx`.
+pub mod sql;
+
+/// Persistent storage for [IdCert]s, addressed by their [CertId].
+///
+/// Clients use a [CertStore] to keep the certificates of their own sessions and of the actors they
+/// communicate with, home servers to keep the certificates they have issued. Certificates are not
+/// verified when they are stored or loaded: verify certificates before inserting them, and treat
+/// the certificates returned by a store backed by storage others can write to as unverified.
+pub trait CertStore<S: Signature, P: PublicKey<S>> {
+    /// Stores `cert` under its [CertId], replacing any certificate previously stored under it.
+    /// Returns the [CertId] of the certificate.
+    fn insert(&mut self, cert: &IdCert<S, P>) -> Result<CertId, CertStoreError>;
+    /// Returns the certificate stored under `cert_id`, or `None`, if there is none.
+    fn get(&self, cert_id: &CertId) -> Result<Option<IdCert<S, P>>, CertStoreError>;
+    /// Deletes the certificate stored under `cert_id`. Returns `false`, if there was none.
+    fn delete(&mut self, cert_id: &CertId) -> Result<bool, CertStoreError>;
+    /// Returns the [CertId]s of all stored certificates, in ascending order of their string
+    /// representation.
+    fn list(&self) -> Result<Vec<CertId>, CertStoreError>;
+
+    /// Returns all stored certificates, in the order of [CertStore::list()].
+    fn certs(&self) -> Result<Vec<IdCert<S, P>>, CertStoreError> {
+        let mut certs = Vec::new();
+        for cert_id in self.list()? {
+            certs.extend(self.get(&cert_id)?);
+        }
+        Ok(certs)
+    }
+
+    /// Returns all stored certificates with the serial number `serial`. Certificates issued by
+    /// different home servers may share a serial number.
+    fn get_by_serial(&self, serial: &Uint) -> Result<Vec<IdCert<S, P>>, CertStoreError> {
+        let mut certs = Vec::new();
+        for cert_id in self.list()? {
+            if &cert_id.serial == serial {
+                certs.extend(self.get(&cert_id)?);
+            }
+        }
+        Ok(certs)
+    }
+
+    /// Returns all stored actor certificates of `federation_id` which are valid at `time`.
+    fn get_valid_for(
+        &self,
+        federation_id: &FederationId,
+        time: Timestamp,
+    ) -> Result<Vec<IdCert<S, P>>, CertStoreError> {
+        Ok(self
+            .certs()?
+            .into_iter()
+            .filter(|cert| {
+                uid(&cert.id_cert_tbs.subject).as_deref() == Some(federation_id.as_str())
+                    && cert.valid_at(time)
+            })
+            .collect())
+    }
+}
+
+/// The future returned by the methods of [AsyncCertStore].
+#[cfg(not(target_arch = "wasm32"))]
+pub type CertStoreFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, CertStoreError>> + Send + 'a>>;
+/// The future returned by the methods of [AsyncCertStore]. On `wasm32`, the future is not required
+/// to be [Send], since futures wrapping JavaScript promises are bound to the thread they were
+/// created on.
+#[cfg(target_arch = "wasm32")]
+pub type CertStoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, CertStoreError>> + 'a>>;
+
+/// Like [CertStore], but for storage which can only be accessed asynchronously, such as an SQL
+/// database.
+pub trait AsyncCertStore<S: Signature, P: PublicKey<S>> {
+    /// Stores `cert` under its [CertId], replacing any certificate previously stored under it.
+    /// Returns the [CertId] of the certificate.
+    fn insert<'a>(&'a mut self, cert: &'a IdCert<S, P>) -> CertStoreFuture<'a, CertId>;
+    /// Returns the certificate stored under `cert_id`, or `None`, if there is none.
+    fn get<'a>(&'a self, cert_id: &'a CertId) -> CertStoreFuture<'a, Option<IdCert<S, P>>>;
+    /// Deletes the certificate stored under `cert_id`. Returns `false`, if there was none.
+    fn delete<'a>(&'a mut self, cert_id: &'a CertId) -> CertStoreFuture<'a, bool>;
+    /// Returns the [CertId]s of all stored certificates, in ascending order of their string
+    /// representation.
+    fn list(&self) -> CertStoreFuture<'_, Vec<CertId>>;
+    /// Returns all stored certificates with the serial number `serial`.
+    fn get_by_serial<'a>(&'a self, serial: &'a Uint) -> CertStoreFuture<'a, Vec<IdCert<S, P>>>;
+    /// Returns all stored actor certificates of `federation_id` which are valid at `time`.
+    fn get_valid_for<'a>(
+        &'a self,
+        federation_id: &'a FederationId,
+        time: Timestamp,
+    ) -> CertStoreFuture<'a, Vec<IdCert<S, P>>>;
+}
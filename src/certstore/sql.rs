@@ -0,0 +1,375 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use der::pem::LineEnding;
+use sqlx::{AnyPool, Row};
+
+use crate::certs::certid::CertId;
+use crate::certs::custody::uid;
+use crate::certs::idcert::IdCert;
+use crate::encoding::{decode_hex, encode_hex};
+use crate::errors::{CertStoreError, ConversionError};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::types::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfo};
+use crate::types::x509_cert::SerialNumber;
+use crate::types::{EncryptedPkm, FederationId, PrivateKeyInfo};
+
+use super::{AsyncCertStore, CertStoreFuture};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A step of the database schema used by [SqlCertStore] and [SqlPkmStore].
+pub struct Migration {
+    /// The schema version reached by applying this migration.
+    pub version: i64,
+    /// A short description of the migration.
+    pub description: &'static str,
+    /// The SQL statements of the migration, separated by semicolons.
+    pub sql: &'static str,
+}
+
+/// The migrations of the database schema, in ascending order of their versions. Applied by
+/// [migrate()].
+pub static MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "Create the certificate and encrypted private key material tables",
+    sql: "CREATE TABLE polyproto_id_certs (
+            cert_id TEXT PRIMARY KEY,
+            serial TEXT NOT NULL,
+            federation_id TEXT,
+            not_before BIGINT NOT NULL,
+            not_after BIGINT NOT NULL,
+            pem TEXT NOT NULL
+        );
+        CREATE INDEX polyproto_id_certs_serial ON polyproto_id_certs (serial);
+        CREATE INDEX polyproto_id_certs_federation_id ON polyproto_id_certs (federation_id);
+        CREATE TABLE polyproto_encrypted_pkms (
+            federation_id TEXT NOT NULL,
+            serial TEXT NOT NULL,
+            key_data TEXT NOT NULL,
+            encryption_algorithm TEXT NOT NULL,
+            PRIMARY KEY (federation_id, serial)
+        )",
+}];
+
+/// Brings the database schema of `pool` up to date by applying all [MIGRATIONS] which have not
+/// been applied yet, each in its own transaction. Returns the number of applied migrations.
+///
+/// The applied schema version is recorded in the `polyproto_schema_version` table. The statements
+/// only use SQL understood by both SQLite and PostgreSQL.
+pub async fn migrate(pool: &AnyPool) -> Result<usize, CertStoreError> {
+    sqlx::query("CREATE TABLE IF NOT EXISTS polyproto_schema_version (version BIGINT NOT NULL)")
+        .execute(pool)
+        .await?;
+    let current = schema_version(pool).await?;
+    let mut applied = 0;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        log::debug!(
+            "[migrate()] Applying migration {}: {}",
+            migration.version,
+            migration.description
+        );
+        let mut transaction = pool.begin().await?;
+        for statement in migration.sql.split(';') {
+            if !statement.trim().is_empty() {
+                sqlx::query(statement).execute(&mut *transaction).await?;
+            }
+        }
+        sqlx::query("DELETE FROM polyproto_schema_version")
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("INSERT INTO polyproto_schema_version (version) VALUES ($1)")
+            .bind(migration.version)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+/// Returns the schema version of the database of `pool`, or `0`, if no migration has been applied.
+pub async fn schema_version(pool: &AnyPool) -> Result<i64, CertStoreError> {
+    let row = sqlx::query("SELECT MAX(version) AS version FROM polyproto_schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.try_get::<Option<i64>, _>("version")?.unwrap_or(0))
+}
+
+#[derive(Debug, Clone)]
+/// An [AsyncCertStore] keeping certificates in the `polyproto_id_certs` table of an SQL database.
+///
+/// Next to the PEM encoded certificate, the table holds the serial number, the federation ID of
+/// actor certificates and the validity period in separate columns, so that the certificates of an
+/// actor can be looked up without decoding every stored certificate. Certificates are loaded using
+/// [IdCert::from_pem_unchecked()].
+pub struct SqlCertStore {
+    pool: AnyPool,
+}
+
+impl SqlCertStore {
+    /// Creates a [SqlCertStore] using `pool`, applying all pending [MIGRATIONS].
+    pub async fn new(pool: AnyPool) -> Result<Self, CertStoreError> {
+        migrate(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// Returns the connection pool of the store.
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+}
+
+fn certs_from_rows<S: Signature, P: PublicKey<S>>(
+    rows: Vec<sqlx::any::AnyRow>,
+) -> Result<Vec<IdCert<S, P>>, CertStoreError> {
+    let mut certs = Vec::with_capacity(rows.len());
+    for row in rows {
+        certs.push(IdCert::from_pem_unchecked(
+            &row.try_get::<String, _>("pem")?,
+        )?);
+    }
+    Ok(certs)
+}
+
+impl<S, P> AsyncCertStore<S, P> for SqlCertStore
+where
+    S: Signature + Send,
+    P: PublicKey<S> + Send,
+{
+    fn insert<'a>(&'a mut self, cert: &'a IdCert<S, P>) -> CertStoreFuture<'a, CertId> {
+        let prepared = cert.cert_id().and_then(|cert_id| {
+            let pem = cert.to_pem(LineEnding::LF)?;
+            Ok((cert_id, pem))
+        });
+        let serial = encode_hex(cert.id_cert_tbs.serial_number.as_bytes());
+        let federation_id = uid(&cert.id_cert_tbs.subject);
+        let not_before = Timestamp::from(cert.id_cert_tbs.validity.not_before).unix_seconds();
+        let not_after = Timestamp::from(cert.id_cert_tbs.validity.not_after).unix_seconds();
+        Box::pin(async move {
+            let (cert_id, pem) = prepared?;
+            log::trace!("[SqlCertStore::insert()] Storing certificate {}", cert_id);
+            let mut transaction = self.pool.begin().await?;
+            sqlx::query("DELETE FROM polyproto_id_certs WHERE cert_id = $1")
+                .bind(cert_id.to_string())
+                .execute(&mut *transaction)
+                .await?;
+            sqlx::query(
+                "INSERT INTO polyproto_id_certs
+                    (cert_id, serial, federation_id, not_before, not_after, pem)
+                    VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(cert_id.to_string())
+            .bind(serial)
+            .bind(federation_id)
+            .bind(not_before as i64)
+            .bind(not_after as i64)
+            .bind(pem)
+            .execute(&mut *transaction)
+            .await?;
+            transaction.commit().await?;
+            Ok(cert_id)
+        })
+    }
+
+    fn get<'a>(&'a self, cert_id: &'a CertId) -> CertStoreFuture<'a, Option<IdCert<S, P>>> {
+        Box::pin(async move {
+            let rows = sqlx::query("SELECT pem FROM polyproto_id_certs WHERE cert_id = $1")
+                .bind(cert_id.to_string())
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(certs_from_rows(rows)?.pop())
+        })
+    }
+
+    fn delete<'a>(&'a mut self, cert_id: &'a CertId) -> CertStoreFuture<'a, bool> {
+        Box::pin(async move {
+            let result = sqlx::query("DELETE FROM polyproto_id_certs WHERE cert_id = $1")
+                .bind(cert_id.to_string())
+                .execute(&self.pool)
+                .await?;
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    fn list(&self) -> CertStoreFuture<'_, Vec<CertId>> {
+        Box::pin(async move {
+            let rows = sqlx::query("SELECT cert_id FROM polyproto_id_certs ORDER BY cert_id")
+                .fetch_all(&self.pool)
+                .await?;
+            let mut ids = Vec::with_capacity(rows.len());
+            for row in rows {
+                ids.push(CertId::from_str(&row.try_get::<String, _>("cert_id")?)?);
+            }
+            Ok(ids)
+        })
+    }
+
+    fn get_by_serial<'a>(&'a self, serial: &'a Uint) -> CertStoreFuture<'a, Vec<IdCert<S, P>>> {
+        Box::pin(async move {
+            let rows = sqlx::query(
+                "SELECT pem FROM polyproto_id_certs WHERE serial = $1 ORDER BY cert_id",
+            )
+            .bind(encode_hex(serial.as_bytes()))
+            .fetch_all(&self.pool)
+            .await?;
+            certs_from_rows(rows)
+        })
+    }
+
+    fn get_valid_for<'a>(
+        &'a self,
+        federation_id: &'a FederationId,
+        time: Timestamp,
+    ) -> CertStoreFuture<'a, Vec<IdCert<S, P>>> {
+        Box::pin(async move {
+            let rows = sqlx::query(
+                "SELECT pem FROM polyproto_id_certs
+                    WHERE federation_id = $1 AND not_before <= $2 AND not_after >= $2
+                    ORDER BY cert_id",
+            )
+            .bind(federation_id.to_string())
+            .bind(time.unix_seconds() as i64)
+            .fetch_all(&self.pool)
+            .await?;
+            certs_from_rows(rows)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A store for the [EncryptedPkm]s home servers keep on behalf of their actors, in the
+/// `polyproto_encrypted_pkms` table of an SQL database. Entries are addressed by the
+/// [FederationId] of the actor and the serial number of the certificate the key material belongs
+/// to.
+pub struct SqlPkmStore {
+    pool: AnyPool,
+}
+
+impl SqlPkmStore {
+    /// Creates a [SqlPkmStore] using `pool`, applying all pending [MIGRATIONS].
+    pub async fn new(pool: AnyPool) -> Result<Self, CertStoreError> {
+        migrate(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// Returns the connection pool of the store.
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// Stores `pkm` for `federation_id`, replacing any key material previously stored for the
+    /// same serial number.
+    pub async fn put(
+        &self,
+        federation_id: &FederationId,
+        pkm: &EncryptedPkm,
+    ) -> Result<(), CertStoreError> {
+        let key_data = SubjectPublicKeyInfo::from(pkm.key_data.clone())
+            .to_der()
+            .map_err(ConversionError::from)?;
+        let encryption_algorithm = pkm
+            .encryption_algorithm
+            .to_der()
+            .map_err(ConversionError::from)?;
+        let serial = encode_hex(pkm.serial_number.as_bytes());
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM polyproto_encrypted_pkms WHERE federation_id = $1 AND serial = $2",
+        )
+        .bind(federation_id.to_string())
+        .bind(serial.clone())
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query(
+            "INSERT INTO polyproto_encrypted_pkms
+                (federation_id, serial, key_data, encryption_algorithm)
+                VALUES ($1, $2, $3, $4)",
+        )
+        .bind(federation_id.to_string())
+        .bind(serial)
+        .bind(encode_hex(&key_data))
+        .bind(encode_hex(&encryption_algorithm))
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Returns the key material stored for `federation_id` and the certificate with the serial
+    /// number `serial`, or `None`, if there is none.
+    pub async fn get(
+        &self,
+        federation_id: &FederationId,
+        serial: &SerialNumber,
+    ) -> Result<Option<EncryptedPkm>, CertStoreError> {
+        let rows = sqlx::query(
+            "SELECT serial, key_data, encryption_algorithm FROM polyproto_encrypted_pkms
+                WHERE federation_id = $1 AND serial = $2",
+        )
+        .bind(federation_id.to_string())
+        .bind(encode_hex(serial.as_bytes()))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(pkms_from_rows(rows)?.pop())
+    }
+
+    /// Returns all key material stored for `federation_id`, in ascending order of the hex encoded
+    /// serial numbers.
+    pub async fn list(
+        &self,
+        federation_id: &FederationId,
+    ) -> Result<Vec<EncryptedPkm>, CertStoreError> {
+        let rows = sqlx::query(
+            "SELECT serial, key_data, encryption_algorithm FROM polyproto_encrypted_pkms
+                WHERE federation_id = $1 ORDER BY serial",
+        )
+        .bind(federation_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        pkms_from_rows(rows)
+    }
+
+    /// Deletes the key material stored for `federation_id` and the certificate with the serial
+    /// number `serial`. Returns `false`, if there was none.
+    pub async fn delete(
+        &self,
+        federation_id: &FederationId,
+        serial: &SerialNumber,
+    ) -> Result<bool, CertStoreError> {
+        let result = sqlx::query(
+            "DELETE FROM polyproto_encrypted_pkms WHERE federation_id = $1 AND serial = $2",
+        )
+        .bind(federation_id.to_string())
+        .bind(encode_hex(serial.as_bytes()))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn pkms_from_rows(rows: Vec<sqlx::any::AnyRow>) -> Result<Vec<EncryptedPkm>, CertStoreError> {
+    let mut pkms = Vec::with_capacity(rows.len());
+    for row in rows {
+        let serial =
+            decode_hex(&row.try_get::<String, _>("serial")?).map_err(ConversionError::from)?;
+        let key_data =
+            decode_hex(&row.try_get::<String, _>("key_data")?).map_err(ConversionError::from)?;
+        let encryption_algorithm = decode_hex(&row.try_get::<String, _>("encryption_algorithm")?)
+            .map_err(ConversionError::from)?;
+        pkms.push(EncryptedPkm {
+            serial_number: SerialNumber::new(&serial).map_err(ConversionError::from)?,
+            key_data: PrivateKeyInfo::from(
+                SubjectPublicKeyInfo::from_der(&key_data).map_err(ConversionError::from)?,
+            ),
+            encryption_algorithm: AlgorithmIdentifierOwned::from_der(&encryption_algorithm)
+                .map_err(ConversionError::from)?,
+        });
+    }
+    Ok(pkms)
+}
@@ -2,85 +2,124 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::errors::{ERR_MSG_ACTOR_MISSING_SIGNING_CAPS, ERR_MSG_HOME_SERVER_MISSING_CA_ATTR};
+use crate::certs::capabilities::{
+    BasicConstraints, ExtendedKeyUsage, ExtendedKeyUsages, KeyUsages,
+};
+use crate::errors::KeyUsageViolation;
 
 use super::*;
 
 impl Constrained for Capabilities {
-    fn validate(&self, _target: Option<Target>) -> Result<(), ConstraintError> {
-        let is_ca = self.basic_constraints.ca;
+    fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
+        self.extended_key_usage.validate(target)?;
 
-        // Define the flags to check
-        let mut can_commit_content = false;
-        let mut can_sign = false;
-        let mut key_cert_sign = false;
-        let mut has_only_encipher = false;
-        let mut has_only_decipher = false;
-        let mut has_key_agreement = false;
-
-        // Iterate over all the entries in the KeyUsage vector, check if they exist/are true
-        for item in self.key_usage.key_usages.iter() {
-            if !has_only_encipher && item == &KeyUsage::EncipherOnly {
-                has_only_encipher = true;
-            }
-            if !has_only_decipher && item == &KeyUsage::DecipherOnly {
-                has_only_decipher = true;
-            }
-            if !has_key_agreement && item == &KeyUsage::KeyAgreement {
-                has_key_agreement = true;
-            }
-            if !has_key_agreement && item == &KeyUsage::ContentCommitment {
-                can_commit_content = true;
-            }
-            if !has_key_agreement && item == &KeyUsage::DigitalSignature {
-                can_sign = true;
-            }
-            if !has_key_agreement && item == &KeyUsage::KeyCertSign {
-                key_cert_sign = true;
+        // X.509 certificates must not carry the same extension more than once
+        for (index, item) in self.custom_extensions.iter().enumerate() {
+            if self.custom_extensions[..index]
+                .iter()
+                .any(|other| other.oid() == item.oid())
+            {
+                return Err(ConstraintError::Malformed(Some(format!(
+                    "The custom extension {} is attached more than once",
+                    item.oid()
+                ))));
             }
         }
 
-        // Non-CAs must be able to sign their messages. Whether with or without non-repudiation
-        // does not matter.
-        if !is_ca && !can_sign && !can_commit_content {
-            return Err(ConstraintError::Malformed(Some(
-                ERR_MSG_ACTOR_MISSING_SIGNING_CAPS.to_string(),
-            )));
-        }
+        check_key_usage(&self.key_usage, &self.basic_constraints).map_err(|violation| {
+            log::debug!("[Capabilities::validate()] {}", violation);
+            ConstraintError::InvalidKeyUsage(violation)
+        })
+    }
+}
 
-        // Certificates cannot be both non-repudiating and repudiating
-        if can_sign && can_commit_content {
-            return Err(ConstraintError::Malformed(Some(
-                "Cannot have both signing and non-repudiation signing capabilities".to_string(),
-            )));
-        }
+/// Checks the combination of `key_usage` and `basic_constraints` against RFC 5280 and the
+/// polyproto specification.
+fn check_key_usage(
+    key_usage: &KeyUsages,
+    basic_constraints: &BasicConstraints,
+) -> Result<(), KeyUsageViolation> {
+    let has = |flag: KeyUsage| key_usage.key_usages.contains(&flag);
+    let is_ca = basic_constraints.ca;
 
-        // If these Capabilities are for a CA, it also must have the KeyCertSign Capability set to
-        // true. Also, non-CAs are not allowed to have the KeyCertSign flag set to true.
-        if is_ca || key_cert_sign {
-            if !is_ca {
-                return Err(ConstraintError::Malformed(Some(
-                    "If KeyCertSign capability is wanted, CA flag must be true".to_string(),
-                )));
-            }
-            if !key_cert_sign {
+    // Non-CAs must be able to sign their messages. Whether with or without non-repudiation
+    // does not matter.
+    if !is_ca && !has(KeyUsage::DigitalSignature) && !has(KeyUsage::ContentCommitment) {
+        return Err(KeyUsageViolation::MissingSigningCapability);
+    }
+    // Certificates cannot be both non-repudiating and repudiating
+    if has(KeyUsage::DigitalSignature) && has(KeyUsage::ContentCommitment) {
+        return Err(KeyUsageViolation::SignatureAndContentCommitment);
+    }
+    if has(KeyUsage::KeyCertSign) && !is_ca {
+        return Err(KeyUsageViolation::KeyCertSignWithoutCa);
+    }
+    if is_ca && !has(KeyUsage::KeyCertSign) {
+        return Err(KeyUsageViolation::CaWithoutKeyCertSign);
+    }
+    if !is_ca && basic_constraints.path_length.is_some() {
+        return Err(KeyUsageViolation::PathLengthWithoutCa);
+    }
+    // See: <https://cryptography.io/en/latest/x509/reference/#cryptography.x509.KeyUsage.encipher_only>
+    // See: <https://cryptography.io/en/latest/x509/reference/#cryptography.x509.KeyUsage.decipher_only>
+    if has(KeyUsage::EncipherOnly) && !has(KeyUsage::KeyAgreement) {
+        return Err(KeyUsageViolation::EncipherOnlyWithoutKeyAgreement);
+    }
+    if has(KeyUsage::DecipherOnly) && !has(KeyUsage::KeyAgreement) {
+        return Err(KeyUsageViolation::DecipherOnlyWithoutKeyAgreement);
+    }
+    if has(KeyUsage::EncipherOnly) && has(KeyUsage::DecipherOnly) {
+        return Err(KeyUsageViolation::EncipherOnlyAndDecipherOnly);
+    }
+    Ok(())
+}
+
+impl Constrained for ExtendedKeyUsages {
+    /// Validates the [ExtendedKeyUsages] for the given `target`:
+    ///
+    /// - No purpose may be listed more than once.
+    /// - Actors may not use their keys to authenticate an instance, to sign revocation status
+    ///   responses, or for any purpose.
+    /// - Home servers, if they restrict the purposes of their keys at all, must be able to
+    ///   authenticate their instance.
+    fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
+        for (index, item) in self.extended_key_usages.iter().enumerate() {
+            if self.extended_key_usages[..index].contains(item) {
                 return Err(ConstraintError::Malformed(Some(format!(
-                    "{} Missing capability \"KeyCertSign\"",
-                    ERR_MSG_HOME_SERVER_MISSING_CA_ATTR
+                    "The ExtendedKeyUsage {} is listed more than once",
+                    item.oid()
                 ))));
             }
         }
-
-        // has_key_agreement needs to be true if has_only_encipher or _decipher are true.
-        // See: <https://cryptography.io/en/latest/x509/reference/#cryptography.x509.KeyUsage.encipher_only>
-        // See: <https://cryptography.io/en/latest/x509/reference/#cryptography.x509.KeyUsage.decipher_only>
-        if (has_only_encipher || has_only_decipher) && !has_key_agreement {
-            Err(ConstraintError::Malformed(Some(
-                "KeyAgreement capability needs to be true to use OnlyEncipher or OnlyDecipher"
-                    .to_string(),
-            )))
-        } else {
-            Ok(())
+        match target {
+            Some(Target::Actor) => {
+                for reserved in [
+                    ExtendedKeyUsage::ServerAuth,
+                    ExtendedKeyUsage::OcspSigning,
+                    ExtendedKeyUsage::AnyExtendedKeyUsage,
+                ] {
+                    if self.contains(&reserved) {
+                        return Err(ConstraintError::Malformed(Some(format!(
+                            "Actors must not have the ExtendedKeyUsage {:?}",
+                            reserved
+                        ))));
+                    }
+                }
+                Ok(())
+            }
+            Some(Target::HomeServer) => {
+                if self.is_empty()
+                    || self.contains(&ExtendedKeyUsage::ServerAuth)
+                    || self.contains(&ExtendedKeyUsage::AnyExtendedKeyUsage)
+                {
+                    Ok(())
+                } else {
+                    Err(ConstraintError::Malformed(Some(
+                        "Home servers restricting their ExtendedKeyUsages must have the ExtendedKeyUsage \"ServerAuth\"".to_string(),
+                    )))
+                }
+            }
+            None => Ok(()),
         }
     }
 }
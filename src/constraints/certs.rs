@@ -5,43 +5,57 @@
 use log::{debug, warn};
 
 use crate::errors::{
-    ERR_MSG_ACTOR_CANNOT_BE_CA, ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT,
-    ERR_MSG_HOME_SERVER_MISSING_CA_ATTR, ERR_MSG_SIGNATURE_MISMATCH,
+    ERR_MSG_ACTOR_CANNOT_BE_CA, ERR_MSG_ACTOR_SUBJECT_ALT_NAMES,
+    ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT, ERR_MSG_HOME_SERVER_MISSING_CA_ATTR,
+    ERR_MSG_SIGNATURE_MISMATCH,
 };
 
 use super::*;
 
+impl<S: Signature, P: PublicKey<S>> HasRules for IdCsrInner<S, P> {
+    fn rules() -> RuleSet<Self> {
+        RuleSet::new(vec![
+            Rule::new(
+                "idcsr.capabilities",
+                "PP2001",
+                "The requested capabilities must be valid",
+                |csr: &Self, target| csr.capabilities.validate(target),
+            )
+            .field("capabilities"),
+            Rule::new(
+                "idcsr.subject",
+                "PP2002",
+                "The subject must be a valid polyproto name",
+                |csr: &Self, target| csr.subject.validate(target),
+            )
+            .field("subject"),
+            Rule::new(
+                "idcsr.actor-not-ca",
+                "PP2003",
+                "Actors must not request CA capabilities",
+                |csr: &Self, _| actor_not_ca(&csr.capabilities),
+            )
+            .field("capabilities")
+            .applies_to(AppliesTo::Actor),
+            Rule::new(
+                "idcsr.home-server-ca",
+                "PP2004",
+                "Home servers must request CA capabilities",
+                |csr: &Self, _| home_server_ca(&csr.capabilities),
+            )
+            .field("capabilities")
+            .applies_to(AppliesTo::HomeServer),
+        ])
+    }
+}
+
 impl<S: Signature, P: PublicKey<S>> Constrained for IdCsrInner<S, P> {
     fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
         log::trace!(
-            "[IdCsrInner::validate()] validating capabilities for target: {:?}",
-            target
-        );
-        self.capabilities.validate(target)?;
-        log::trace!(
-            "[IdCsrInner::validate()] validating subject for target: {:?}",
+            "[IdCsrInner::validate()] validating rules for target: {:?}",
             target
         );
-        self.subject.validate(target)?;
-        if let Some(target) = target {
-            match target {
-                Target::Actor => {
-                    if self.capabilities.basic_constraints.ca {
-                        return Err(ConstraintError::Malformed(Some(
-                            ERR_MSG_ACTOR_CANNOT_BE_CA.to_string(),
-                        )));
-                    }
-                }
-                Target::HomeServer => {
-                    if !self.capabilities.basic_constraints.ca {
-                        return Err(ConstraintError::Malformed(Some(
-                            ERR_MSG_HOME_SERVER_MISSING_CA_ATTR.to_string(),
-                        )));
-                    }
-                }
-            }
-        }
-        Ok(())
+        Self::rules().check(self, target)
     }
 }
 
@@ -53,22 +67,70 @@ impl<S: Signature, P: PublicKey<S>> Constrained for IdCsr<S, P> {
         );
         self.inner_csr.validate(target)?;
         log::trace!("[IdCsr::validate()] verifying signature");
-        match self.inner_csr.subject_public_key.verify_signature(
-            &self.signature,
-            match &self.inner_csr.clone().to_der() {
-                Ok(data) => data,
-                Err(_) => {
-                    log::warn!("[IdCsr::validate()] DER conversion failure when converting inner IdCsr to DER. IdCsr is likely malformed");
-                    return Err(ConstraintError::Malformed(Some("DER conversion failure when converting inner IdCsr to DER. IdCsr is likely malformed".to_string())))}
-            }
-        ) {
-            Ok(_) => (),
+        verify_csr_signature(self)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> IdCsr<S, P> {
+    /// Checks all rules for the inner CSR and the signature of this [IdCsr], returning a
+    /// [ValidationReport] listing every violation. The signature is reported as the rule
+    /// `idcsr.signature` with the error code `PP2005`.
+    pub fn validate_report(&self, target: Option<Target>) -> ValidationReport {
+        let mut report = self.inner_csr.validate_report(target);
+        report.outcomes.push(RuleOutcome {
+            id: "idcsr.signature",
+            error_code: "PP2005",
+            severity: Severity::Error,
+            field: Some("signature"),
+            status: match verify_csr_signature(self) {
+                Ok(()) => RuleStatus::Passed,
+                Err(e) => RuleStatus::Failed(e),
+            },
+        });
+        report
+    }
+
+    /// Validates this [IdCsr] like [Constrained::validate()], but skips the rules disabled by
+    /// `policy` and additionally checks the constraints `policy` imposes on CSRs.
+    pub fn validate_with(
+        &self,
+        target: Option<Target>,
+        policy: &ValidationPolicy,
+    ) -> Result<(), ConstraintError> {
+        log::trace!(
+            "[IdCsr::validate_with()] validating with target {:?} and policy {:?}",
+            target,
+            policy
+        );
+        policy
+            .rules::<IdCsrInner<S, P>>()
+            .check(&self.inner_csr, target)?;
+        policy.check_common(
+            self.signature_algorithm.oid,
+            &self.inner_csr.capabilities,
+            target,
+        )?;
+        verify_csr_signature(self)
+    }
+}
+
+fn verify_csr_signature<S: Signature, P: PublicKey<S>>(
+    csr: &IdCsr<S, P>,
+) -> Result<(), ConstraintError> {
+    match csr.inner_csr.subject_public_key.verify_signature(
+        &csr.signature,
+        match &csr.inner_csr.to_der() {
+            Ok(data) => data,
             Err(_) => {
-                log::warn!(
-                    "[IdCsr::validate()] {}", ERR_MSG_SIGNATURE_MISMATCH);
-                return Err(ConstraintError::Malformed(Some(ERR_MSG_SIGNATURE_MISMATCH.to_string())))}
-        };
-        Ok(())
+                log::warn!("[IdCsr::validate()] DER conversion failure when converting inner IdCsr to DER. IdCsr is likely malformed");
+                return Err(ConstraintError::Malformed(Some("DER conversion failure when converting inner IdCsr to DER. IdCsr is likely malformed".to_string())))}
+        }
+    ) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            log::warn!(
+                "[IdCsr::validate()] {}", ERR_MSG_SIGNATURE_MISMATCH);
+            Err(ConstraintError::Malformed(Some(ERR_MSG_SIGNATURE_MISMATCH.to_string())))}
     }
 }
 
@@ -83,57 +145,168 @@ impl<S: Signature, P: PublicKey<S>> Constrained for IdCert<S, P> {
     }
 }
 
+impl<S: Signature, P: PublicKey<S>> IdCert<S, P> {
+    /// Checks all rules for the [IdCertTbs] of this [IdCert], returning a [ValidationReport]
+    /// listing every violation, instead of stopping at the first one like
+    /// [Constrained::validate()]. The signature of the certificate is not checked.
+    pub fn validate_report(&self, target: Option<Target>) -> ValidationReport {
+        self.id_cert_tbs.validate_report(target)
+    }
+
+    /// Validates this [IdCert] like [Constrained::validate()], but skips the rules disabled by
+    /// `policy` and additionally checks the constraints `policy` imposes on certificates. Like
+    /// [Constrained::validate()], neither the signature nor the validity at a point in time are
+    /// checked.
+    pub fn validate_with(
+        &self,
+        target: Option<Target>,
+        policy: &ValidationPolicy,
+    ) -> Result<(), ConstraintError> {
+        self.id_cert_tbs.validate_with(target, policy)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> IdCertTbs<S, P> {
+    /// Validates this [IdCertTbs] like [Constrained::validate()], but skips the rules disabled by
+    /// `policy` and additionally checks the constraints `policy` imposes on certificates.
+    pub fn validate_with(
+        &self,
+        target: Option<Target>,
+        policy: &ValidationPolicy,
+    ) -> Result<(), ConstraintError> {
+        log::trace!(
+            "[IdCertTbs::validate_with()] validating with target {:?} and policy {:?}",
+            target,
+            policy
+        );
+        policy.rules::<Self>().check(self, target)?;
+        policy.check_common(self.signature_algorithm.oid, &self.capabilities, target)?;
+        policy.check_validity(&self.validity)
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> HasRules for IdCertTbs<S, P> {
+    fn rules() -> RuleSet<Self> {
+        RuleSet::new(vec![
+            Rule::new(
+                "idcert.capabilities",
+                "PP1001",
+                "The capabilities must be valid",
+                |cert: &Self, target| cert.capabilities.validate(target),
+            )
+            .field("capabilities"),
+            Rule::new(
+                "idcert.claims",
+                "PP1002",
+                "The custom claims must be within the limits and use namespaced keys",
+                |cert: &Self, target| cert.claims.validate(target),
+            )
+            .field("claims"),
+            Rule::new(
+                "idcert.issuer",
+                "PP1003",
+                "The issuer must be a valid home server name",
+                |cert: &Self, _| cert.issuer.validate(Some(Target::HomeServer)),
+            )
+            .field("issuer"),
+            Rule::new(
+                "idcert.subject",
+                "PP1004",
+                "The subject must be a valid polyproto name",
+                |cert: &Self, target| cert.subject.validate(target),
+            )
+            .field("subject"),
+            Rule::new(
+                "idcert.domain-components",
+                "PP1005",
+                "The domain components of the issuer and the subject must be equal",
+                |cert: &Self, _| {
+                    log::trace!("[IdCertTbs::validate()] Issuer: {}", cert.issuer);
+                    log::trace!("[IdCertTbs::validate()] Subject: {}", cert.subject);
+                    match equal_domain_components(&cert.issuer, &cert.subject) {
+                        true => {
+                            debug!("Domain components of issuer and subject are equal");
+                            Ok(())
+                        }
+                        false => {
+                            warn!(
+                                "{}\nIssuer: {}\nSubject: {}",
+                                ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT, &cert.issuer, &cert.subject
+                            );
+                            Err(ConstraintError::Malformed(Some(
+                                ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT.to_string(),
+                            )))
+                        }
+                    }
+                },
+            )
+            .field("subject"),
+            Rule::new(
+                "idcert.actor-not-ca",
+                "PP1006",
+                "Actor certificates must not have CA capabilities",
+                |cert: &Self, _| actor_not_ca(&cert.capabilities),
+            )
+            .field("capabilities")
+            .applies_to(AppliesTo::Actor),
+            Rule::new(
+                "idcert.home-server-ca",
+                "PP1007",
+                "Home server certificates must have CA capabilities",
+                |cert: &Self, _| home_server_ca(&cert.capabilities),
+            )
+            .field("capabilities")
+            .applies_to(AppliesTo::HomeServer),
+            Rule::new(
+                "idcert.subject-alt-names",
+                "PP1008",
+                "Subject alternative names must only be carried by home server certificates, and lie within the domain of the subject",
+                |cert: &Self, _| subject_alt_names_within_domain(cert),
+            )
+            .field("subject_alt_names"),
+        ])
+    }
+}
+
 impl<S: Signature, P: PublicKey<S>> Constrained for IdCertTbs<S, P> {
     fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
         log::trace!(
-            "[IdCertTbs::validate()] validating capabilities for target: {:?}",
+            "[IdCertTbs::validate()] validating rules for target: {:?}",
             target
         );
-        self.capabilities.validate(target)?;
-        dbg!(self.issuer.to_string());
-        self.issuer.validate(Some(Target::HomeServer))?;
-        self.subject.validate(target)?;
-        log::trace!(
-            "[IdCertTbs::validate()] checking if domain components of issuer and subject are equal"
-        );
-        log::trace!(
-            "[IdCertTbs::validate()] Issuer: {}",
-            self.issuer.to_string()
-        );
-        log::trace!(
-            "[IdCertTbs::validate()] Subject: {}",
-            self.subject.to_string()
-        );
-        match equal_domain_components(&self.issuer, &self.subject) {
-            true => debug!("Domain components of issuer and subject are equal"),
-            false => {
-                warn!(
-                    "{}\nIssuer: {}\nSubject: {}",
-                    ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT, &self.issuer, &self.subject
-                );
-                return Err(ConstraintError::Malformed(Some(
-                    ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT.to_string(),
-                )));
-            }
-        }
-        if let Some(target) = target {
-            match target {
-                Target::Actor => {
-                    if self.capabilities.basic_constraints.ca {
-                        return Err(ConstraintError::Malformed(Some(
-                            ERR_MSG_ACTOR_CANNOT_BE_CA.to_string(),
-                        )));
-                    }
-                }
-                Target::HomeServer => {
-                    if !self.capabilities.basic_constraints.ca {
-                        return Err(ConstraintError::Malformed(Some(
-                            ERR_MSG_HOME_SERVER_MISSING_CA_ATTR.to_string(),
-                        )));
-                    }
-                }
-            }
-        }
-        Ok(())
+        Self::rules().check(self, target)
+    }
+}
+
+fn subject_alt_names_within_domain<S: Signature, P: PublicKey<S>>(
+    cert: &IdCertTbs<S, P>,
+) -> Result<(), ConstraintError> {
+    if cert.subject_alt_names.is_empty() {
+        return Ok(());
+    }
+    if !cert.capabilities.basic_constraints.ca {
+        return Err(ConstraintError::Malformed(Some(
+            ERR_MSG_ACTOR_SUBJECT_ALT_NAMES.to_string(),
+        )));
+    }
+    cert.subject_alt_names
+        .check_within(&domain_of(&cert.subject))
+}
+
+fn actor_not_ca(capabilities: &Capabilities) -> Result<(), ConstraintError> {
+    match capabilities.basic_constraints.ca {
+        true => Err(ConstraintError::Malformed(Some(
+            ERR_MSG_ACTOR_CANNOT_BE_CA.to_string(),
+        ))),
+        false => Ok(()),
+    }
+}
+
+fn home_server_ca(capabilities: &Capabilities) -> Result<(), ConstraintError> {
+    match capabilities.basic_constraints.ca {
+        true => Ok(()),
+        false => Err(ConstraintError::Malformed(Some(
+            ERR_MSG_HOME_SERVER_MISSING_CA_ATTR.to_string(),
+        ))),
     }
 }
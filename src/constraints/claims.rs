@@ -0,0 +1,64 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::certs::claims::{validate_key, MAX_CLAIMS, MAX_CLAIMS_LENGTH};
+
+use super::*;
+
+impl HasRules for CustomClaims {
+    fn rules() -> RuleSet<Self> {
+        RuleSet::new(vec![
+            Rule::new(
+                "claims.count",
+                "PP3001",
+                "There must be at most 16 custom claims",
+                |claims, _| match claims.len() > MAX_CLAIMS {
+                    true => Err(ConstraintError::OutOfBounds {
+                        lower: 0,
+                        upper: MAX_CLAIMS as i32,
+                        actual: claims.len().to_string(),
+                        reason: "Too many custom claims".to_string(),
+                    }),
+                    false => Ok(()),
+                },
+            ),
+            Rule::new(
+                "claims.keys",
+                "PP3002",
+                "The keys of custom claims must be of the form <namespace>/<name>",
+                |claims, _| {
+                    for (key, _) in claims.iter() {
+                        if let Err(e) = validate_key(key) {
+                            return Err(ConstraintError::Malformed(Some(e.to_string())));
+                        }
+                    }
+                    Ok(())
+                },
+            ),
+            Rule::new(
+                "claims.length",
+                "PP3003",
+                "The encoded custom claims must be at most 1024 bytes long",
+                |claims, _| {
+                    let length = claims.to_cbor().len();
+                    match length > MAX_CLAIMS_LENGTH {
+                        true => Err(ConstraintError::OutOfBounds {
+                            lower: 0,
+                            upper: MAX_CLAIMS_LENGTH as i32,
+                            actual: length.to_string(),
+                            reason: "The encoded custom claims are too long".to_string(),
+                        }),
+                        false => Ok(()),
+                    }
+                },
+            ),
+        ])
+    }
+}
+
+impl Constrained for CustomClaims {
+    fn validate(&self, target: Option<Target>) -> Result<(), ConstraintError> {
+        Self::rules().check(self, target)
+    }
+}
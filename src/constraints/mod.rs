@@ -2,18 +2,21 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use der::Length;
 use regex::Regex;
 use x509_cert::name::{Name, RelativeDistinguishedName};
 
 use crate::certs::capabilities::{Capabilities, KeyUsage};
+use crate::certs::claims::CustomClaims;
 use crate::certs::idcert::IdCert;
 use crate::certs::idcerttbs::IdCertTbs;
 use crate::certs::idcsr::{IdCsr, IdCsrInner};
-use crate::certs::{equal_domain_components, SessionId, Target};
+use crate::certs::policy::ValidationPolicy;
+use crate::certs::{domain_of, equal_domain_components, Target};
 use crate::errors::ConstraintError;
 use crate::key::PublicKey;
+use crate::rules::{AppliesTo, HasRules, Rule, RuleOutcome, RuleSet, RuleStatus, ValidationReport};
 use crate::signature::Signature;
+use crate::verifier::Severity;
 use crate::{
     Constrained, OID_RDN_COMMON_NAME, OID_RDN_DOMAIN_COMPONENT, OID_RDN_UID,
     OID_RDN_UNIQUE_IDENTIFIER,
@@ -21,8 +24,8 @@ use crate::{
 
 mod capabilities;
 mod certs;
+mod claims;
 mod name;
-mod session_id;
 #[cfg(feature = "types")]
 mod types;
 
@@ -190,8 +193,9 @@ mod name_constraints {
 
 #[cfg(test)]
 mod session_id_constraints {
+    use std::str::FromStr;
 
-    use crate::certs::SessionId;
+    use crate::types::SessionId;
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), test)]
@@ -210,4 +214,40 @@ mod session_id_constraints {
     fn thirtythree_length_session_id_fails() {
         assert!(SessionId::new_validated("111111111111111111111111112222223").is_err())
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn session_id_charset() {
+        assert!(SessionId::new_validated("client-1.cool_session").is_ok());
+        for id in ["client 1", "client,1", "client=1", "client+1", "client\n1"] {
+            assert!(SessionId::new_validated(id).is_err(), "{id}");
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn session_id_from_str_and_name() {
+        let session_id = SessionId::from_str("client1").unwrap();
+        assert_eq!(session_id.to_string(), "client1");
+        assert_eq!(session_id.as_str(), "client1");
+        assert_eq!(
+            SessionId::from_name(&session_id.to_rdn_sequence()),
+            Some(session_id)
+        );
+        assert!(SessionId::from_str("").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn session_id_serde() {
+        let session_id = SessionId::from_str("client1").unwrap();
+        let json = serde_json::to_string(&session_id).unwrap();
+        assert_eq!(json, "\"client1\"");
+        assert_eq!(
+            serde_json::from_str::<SessionId>(&json).unwrap(),
+            session_id
+        );
+        assert!(serde_json::from_str::<SessionId>("\"client 1\"").is_err());
+    }
 }
@@ -3,6 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::errors::ERR_MSG_DC_UID_MISMATCH;
+use crate::types::SessionId;
 
 use x509_cert::attr::AttributeTypeAndValue;
 
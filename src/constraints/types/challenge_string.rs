@@ -2,8 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::errors::ERR_MSG_CHALLENGE_STRING_LENGTH;
-use crate::types::ChallengeString;
+use regex::Regex;
+
+use crate::errors::{
+    ERR_MSG_CHALLENGE_BINDING_DOMAIN, ERR_MSG_CHALLENGE_BINDING_DOMAIN_MISMATCH,
+    ERR_MSG_CHALLENGE_STRING_LENGTH,
+};
+use crate::types::{ChallengeBinding, ChallengePurpose, ChallengeString};
 
 use super::*;
 
@@ -17,6 +22,30 @@ impl Constrained for ChallengeString {
                 reason: ERR_MSG_CHALLENGE_STRING_LENGTH.to_string(),
             });
         }
+        if let Some(binding) = &self.binding {
+            binding.validate(None)?;
+        }
+        Ok(())
+    }
+}
+
+impl Constrained for ChallengeBinding {
+    fn validate(&self, _target: Option<Target>) -> Result<(), ConstraintError> {
+        self.federation_id.validate(None)?;
+        let domain_regex = Regex::new(r"^[a-z0-9-]+(\.[a-z0-9-]+)*$").unwrap();
+        if !domain_regex.is_match(&self.server_domain) {
+            return Err(ConstraintError::Malformed(Some(
+                ERR_MSG_CHALLENGE_BINDING_DOMAIN.to_string(),
+            )));
+        }
+        // Actors register and prove key possession on their own home server. Migrations are the
+        // only flow in which the challenge is issued by a server other than the actor's.
+        let fid_domain = self.federation_id.split('@').nth(1).unwrap_or_default();
+        if self.purpose != ChallengePurpose::Migration && fid_domain != self.server_domain {
+            return Err(ConstraintError::Malformed(Some(
+                ERR_MSG_CHALLENGE_BINDING_DOMAIN_MISMATCH.to_string(),
+            )));
+        }
         Ok(())
     }
 }
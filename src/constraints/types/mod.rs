@@ -4,6 +4,7 @@
 
 mod challenge_string;
 mod federation_id;
+mod session_id;
 
 use crate::certs::Target;
 use crate::errors::ConstraintError;
@@ -2,10 +2,16 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use der::Length;
+
+use crate::errors::ERR_MSG_SESSION_ID_CHARSET;
+use crate::types::SessionId;
+
 use super::*;
 
 impl Constrained for SessionId {
-    /// [SessionId] must be longer than 0 and not longer than 32 characters to be deemed valid.
+    /// [SessionId] must be longer than 0 and not longer than 32 characters, and must only consist
+    /// of ASCII letters, digits, `.`, `_` and `-` to be deemed valid.
     fn validate(&self, _target: Option<Target>) -> Result<(), ConstraintError> {
         if self.len() > Length::new(32) || self.len() == Length::ZERO {
             return Err(ConstraintError::OutOfBounds {
@@ -15,6 +21,15 @@ impl Constrained for SessionId {
                 reason: "SessionId too long".to_string(),
             });
         }
+        if !self
+            .as_str()
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        {
+            return Err(ConstraintError::Malformed(Some(
+                ERR_MSG_SESSION_ID_CHARSET.to_string(),
+            )));
+        }
         Ok(())
     }
 }
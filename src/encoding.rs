@@ -0,0 +1,303 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::errors::{DerViolation, InvalidInput};
+
+/// The deepest nesting of constructed elements accepted by [check_strict_der()]. Certificates and
+/// CSRs nest far less deeply.
+const MAX_DER_DEPTH: usize = 32;
+
+/// The universal tag numbers of the string and time types, which DER requires to use the
+/// primitive encoding.
+const STRING_TAG_NUMBERS: [u8; 15] = [3, 4, 12, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 30];
+
+/// Encodes bytes as a lowercase hex string.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    let mut string = String::with_capacity(bytes.len() * 2);
+    for byte in bytes.iter() {
+        string.push_str(&format!("{:02x}", byte));
+    }
+    string
+}
+
+/// Encodes bytes as an unpadded base64url string, as defined in RFC 4648, section 5.
+pub(crate) fn encode_base64url(bytes: &[u8]) -> String {
+    encode_base64_with(bytes, BASE64URL_ALPHABET, false)
+}
+
+/// Encodes bytes as a padded base64 string, as defined in RFC 4648, section 4.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    encode_base64_with(bytes, BASE64_ALPHABET, true)
+}
+
+/// Decodes a padded base64 string, as defined in RFC 4648, section 4.
+pub(crate) fn decode_base64(s: &str) -> Result<Vec<u8>, InvalidInput> {
+    let malformed = || InvalidInput::Malformed(format!("Invalid base64 string: {}", s));
+    if s.len() % 4 != 0 || !s.is_ascii() {
+        return Err(malformed());
+    }
+    let unpadded = s.trim_end_matches('=');
+    if s.len() - unpadded.len() > 2 {
+        return Err(malformed());
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+    for chunk in unpadded.as_bytes().chunks(4) {
+        let mut block = 0u32;
+        for (index, character) in chunk.iter().enumerate() {
+            let value = match BASE64_ALPHABET.iter().position(|c| c == character) {
+                Some(value) => value as u32,
+                None => return Err(malformed()),
+            };
+            block |= value << (18 - 6 * index);
+        }
+        if chunk.len() == 1 {
+            return Err(malformed());
+        }
+        for index in 0..chunk.len() - 1 {
+            bytes.push((block >> (16 - 8 * index)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn encode_base64_with(bytes: &[u8], alphabet: &[u8; 64], padding: bool) -> String {
+    let mut string = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let block = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..=chunk.len() {
+            string.push(alphabet[(block >> (18 - 6 * index) & 0x3f) as usize] as char);
+        }
+        if padding {
+            for _ in chunk.len()..3 {
+                string.push('=');
+            }
+        }
+    }
+    string
+}
+
+/// Decodes a hex string, accepting both upper- and lowercase digits.
+pub(crate) fn decode_hex(s: &str) -> Result<Vec<u8>, InvalidInput> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(InvalidInput::Malformed(format!(
+            "Invalid hex string: {}",
+            s
+        )));
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 2);
+    for index in (0..s.len()).step_by(2) {
+        match u8::from_str_radix(&s[index..index + 2], 16) {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => {
+                return Err(InvalidInput::Malformed(format!(
+                    "Invalid hex string: {}",
+                    s
+                )))
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Checks that `encoded`, the re-encoding of the value decoded from `input`, is equal to `input`.
+pub(crate) fn check_canonical_der(input: &[u8], encoded: &[u8]) -> Result<(), DerViolation> {
+    if input == encoded {
+        return Ok(());
+    }
+    let offset = input
+        .iter()
+        .zip(encoded)
+        .position(|(input, encoded)| input != encoded)
+        .unwrap_or_else(|| input.len().min(encoded.len()));
+    Err(DerViolation::NonCanonical { offset })
+}
+
+/// Checks that `bytes` consist of exactly one element encoded following the rules DER adds to BER:
+/// lengths must be definite and minimally encoded, and strings must use the primitive encoding.
+/// The contents of primitive elements, such as the encoding of an INTEGER, are not checked.
+pub(crate) fn check_strict_der(bytes: &[u8]) -> Result<(), DerViolation> {
+    let end = check_der_element(bytes, 0, 0)?;
+    if end != bytes.len() {
+        return Err(DerViolation::TrailingBytes {
+            count: bytes.len() - end,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the element starting at `offset`, and all elements nested within it. Returns the offset
+/// of the end of the element.
+fn check_der_element(bytes: &[u8], offset: usize, depth: usize) -> Result<usize, DerViolation> {
+    if depth > MAX_DER_DEPTH {
+        return Err(DerViolation::NestingTooDeep { offset });
+    }
+    let truncated = DerViolation::Truncated { offset };
+    let tag = *bytes.get(offset).ok_or(truncated)?;
+    let mut position = offset + 1;
+    if tag & 0x1f == 0x1f {
+        // High tag numbers are encoded in base 128, with the last byte having its high bit unset.
+        while bytes.get(position).ok_or(truncated)? & 0x80 != 0 {
+            position += 1;
+        }
+        position += 1;
+    }
+    let first_length_byte = *bytes.get(position).ok_or(truncated)?;
+    position += 1;
+    let length = match first_length_byte {
+        0x00..=0x7f => first_length_byte as usize,
+        0x80 => return Err(DerViolation::IndefiniteLength { offset }),
+        _ => {
+            let length_bytes = bytes
+                .get(position..position + (first_length_byte & 0x7f) as usize)
+                .ok_or(truncated)?;
+            position += length_bytes.len();
+            if length_bytes[0] == 0 {
+                return Err(DerViolation::NonMinimalLength { offset });
+            }
+            if length_bytes.len() > std::mem::size_of::<usize>() {
+                return Err(truncated);
+            }
+            let length = length_bytes
+                .iter()
+                .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+            if length < 0x80 {
+                return Err(DerViolation::NonMinimalLength { offset });
+            }
+            length
+        }
+    };
+    let end = position
+        .checked_add(length)
+        .filter(|end| *end <= bytes.len())
+        .ok_or(truncated)?;
+    if tag & 0x20 != 0 {
+        if tag & 0xc0 == 0 && STRING_TAG_NUMBERS.contains(&(tag & 0x1f)) {
+            return Err(DerViolation::ConstructedString { offset });
+        }
+        let contents = &bytes[..end];
+        while position < end {
+            position = check_der_element(contents, position, depth + 1)?;
+        }
+    }
+    Ok(end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn base64url_test_vectors() {
+        // RFC 4648, section 10, without padding.
+        for (input, output) in [
+            ("", ""),
+            ("f", "Zg"),
+            ("fo", "Zm8"),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg"),
+            ("fooba", "Zm9vYmE"),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode_base64url(input.as_bytes()), output);
+        }
+        assert_eq!(encode_base64url(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn base64_test_vectors() {
+        // RFC 4648, section 10.
+        for (input, output) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode_base64(input.as_bytes()), output);
+            assert_eq!(decode_base64(output).unwrap(), input.as_bytes());
+        }
+        assert_eq!(encode_base64(&[0xfb, 0xff]), "+/8=");
+        assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+        for invalid in ["Zg=", "Z===", "Zm9v-_8=", "Zm9vYmFy===="] {
+            assert!(decode_base64(invalid).is_err());
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn strict_der() {
+        // SEQUENCE { INTEGER 1, OCTET STRING "ab" }
+        check_strict_der(&[0x30, 0x07, 0x02, 0x01, 0x01, 0x04, 0x02, b'a', b'b']).unwrap();
+        // A context-specific tag number above 30.
+        check_strict_der(&[0xbf, 0x81, 0x00, 0x03, 0x02, 0x01, 0x01]).unwrap();
+        let mut long = vec![0x04, 0x81, 0x80];
+        long.extend([0; 0x80]);
+        check_strict_der(&long).unwrap();
+
+        for (input, violation) in [
+            (
+                &[0x02, 0x01, 0x01, 0x00][..],
+                DerViolation::TrailingBytes { count: 1 },
+            ),
+            (
+                &[0x30, 0x04, 0x02, 0x81, 0x01, 0x01],
+                DerViolation::NonMinimalLength { offset: 2 },
+            ),
+            (
+                &[0x04, 0x82, 0x00, 0x01, 0x00],
+                DerViolation::NonMinimalLength { offset: 0 },
+            ),
+            (
+                &[0x30, 0x80, 0x02, 0x01, 0x01, 0x00, 0x00],
+                DerViolation::IndefiniteLength { offset: 0 },
+            ),
+            (
+                &[0x24, 0x04, 0x04, 0x02, b'a', b'b'],
+                DerViolation::ConstructedString { offset: 0 },
+            ),
+            (
+                &[0x30, 0x03, 0x02, 0x02, 0x01],
+                DerViolation::Truncated { offset: 2 },
+            ),
+            (&[], DerViolation::Truncated { offset: 0 }),
+        ] {
+            assert_eq!(check_strict_der(input), Err(violation));
+        }
+
+        let mut nested = Vec::new();
+        for _ in 0..=MAX_DER_DEPTH + 1 {
+            nested.insert(0, nested.len() as u8);
+            nested.insert(0, 0x30);
+        }
+        assert!(matches!(
+            check_strict_der(&nested),
+            Err(DerViolation::NestingTooDeep { .. })
+        ));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn canonical_der() {
+        check_canonical_der(&[0x05, 0x00], &[0x05, 0x00]).unwrap();
+        assert_eq!(
+            check_canonical_der(&[0x02, 0x02, 0x00, 0x01], &[0x02, 0x01, 0x01]),
+            Err(DerViolation::NonCanonical { offset: 1 })
+        );
+        assert_eq!(
+            check_canonical_der(&[0x05, 0x00, 0x00], &[0x05, 0x00]),
+            Err(DerViolation::NonCanonical { offset: 2 })
+        );
+    }
+}
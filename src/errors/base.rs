@@ -22,6 +22,139 @@ pub enum ConstraintError {
         /// Additional context
         reason: String,
     },
+    #[error(transparent)]
+    /// The [Capabilities](crate::certs::capabilities::Capabilities) combine flags in a way RFC 5280
+    /// or the polyproto specification forbids
+    InvalidKeyUsage(#[from] KeyUsageViolation),
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Combinations of [KeyUsage](crate::certs::capabilities::KeyUsage) flags and
+/// [BasicConstraints](crate::certs::capabilities::BasicConstraints) rejected when validating
+/// [Capabilities](crate::certs::capabilities::Capabilities).
+pub enum KeyUsageViolation {
+    #[error("Non-CA certificates require the DigitalSignature or ContentCommitment flag")]
+    /// A non-CA certificate can sign neither with nor without non-repudiation
+    MissingSigningCapability,
+    #[error("The DigitalSignature and ContentCommitment flags cannot be combined")]
+    /// A certificate cannot be both non-repudiating and repudiating
+    SignatureAndContentCommitment,
+    #[error("The KeyCertSign flag requires the CA flag of the BasicConstraints")]
+    /// The KeyCertSign flag is set on a certificate which is not a CA, see RFC 5280, section
+    /// 4.2.1.3
+    KeyCertSignWithoutCa,
+    #[error("CA certificates require the KeyCertSign flag")]
+    /// The CA flag is set, but the certificate may not sign certificates, see RFC 5280, section
+    /// 4.2.1.9
+    CaWithoutKeyCertSign,
+    #[error("A path length constraint requires the CA flag of the BasicConstraints")]
+    /// A path length constraint is set on a certificate which is not a CA, see RFC 5280, section
+    /// 4.2.1.9
+    PathLengthWithoutCa,
+    #[error("The EncipherOnly flag requires the KeyAgreement flag")]
+    /// The EncipherOnly flag is meaningless without the KeyAgreement flag, see RFC 5280, section
+    /// 4.2.1.3
+    EncipherOnlyWithoutKeyAgreement,
+    #[error("The DecipherOnly flag requires the KeyAgreement flag")]
+    /// The DecipherOnly flag is meaningless without the KeyAgreement flag, see RFC 5280, section
+    /// 4.2.1.3
+    DecipherOnlyWithoutKeyAgreement,
+    #[error("The EncipherOnly and DecipherOnly flags cannot be combined")]
+    /// The key may be used either only for enciphering or only for deciphering data while
+    /// performing key agreement
+    EncipherOnlyAndDecipherOnly,
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Deviations from the Distinguished Encoding Rules rejected when decoding with the strict
+/// [DerMode](crate::certs::policy::DerMode). Offsets are byte offsets into the DER input.
+pub enum DerViolation {
+    #[error("{count} bytes follow the end of the encoded value")]
+    /// The input continues after the end of the encoded value
+    TrailingBytes {
+        /// The number of bytes following the encoded value
+        count: usize,
+    },
+    #[error("The length of the element at offset {offset} is not minimally encoded")]
+    /// A length is encoded in the long form while the short form suffices, or with leading zero
+    /// bytes, see X.690, section 10.1
+    NonMinimalLength {
+        /// The offset of the element
+        offset: usize,
+    },
+    #[error("The element at offset {offset} has an indefinite length")]
+    /// A BER indefinite length is used, see X.690, section 10.1
+    IndefiniteLength {
+        /// The offset of the element
+        offset: usize,
+    },
+    #[error("The string at offset {offset} uses the constructed encoding")]
+    /// A string or time type is split into segments using the constructed encoding, see X.690,
+    /// section 10.2
+    ConstructedString {
+        /// The offset of the element
+        offset: usize,
+    },
+    #[error("The element at offset {offset} extends beyond the end of the input")]
+    /// The input ends before the element does
+    Truncated {
+        /// The offset of the element
+        offset: usize,
+    },
+    #[error("The element at offset {offset} is nested too deeply")]
+    /// Elements are nested deeper than any certificate or CSR would nest them
+    NestingTooDeep {
+        /// The offset of the element
+        offset: usize,
+    },
+    #[error("The input differs from its canonical encoding at offset {offset}")]
+    /// Encoding the decoded value again does not reproduce the input, for example because the
+    /// elements of a SET OF are not sorted or the input contains values polyproto does not
+    /// preserve
+    NonCanonical {
+        /// The offset of the first byte in which the input and its re-encoding differ
+        offset: usize,
+    },
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Fields of a certificate or CSR exceeding the [SizeLimits](crate::certs::limits::SizeLimits)
+/// enforced when decoding it.
+pub enum SizeLimitViolation {
+    #[error("The serial number is {actual} bytes long, exceeding the limit of {limit} bytes")]
+    /// The serial number is too long
+    SerialNumberLength {
+        /// The maximum length, in bytes
+        limit: usize,
+        /// The actual length, in bytes
+        actual: usize,
+    },
+    #[error(
+        "A name has at least {actual} relative distinguished names, exceeding the limit of {limit}"
+    )]
+    /// The issuer or subject name has too many relative distinguished names
+    RdnCount {
+        /// The maximum number of relative distinguished names
+        limit: usize,
+        /// The number of relative distinguished names counted when the limit was exceeded
+        actual: usize,
+    },
+    #[error("There are {actual} extensions or attributes, exceeding the limit of {limit}")]
+    /// There are too many extensions or CSR attributes
+    ExtensionCount {
+        /// The maximum number of extensions
+        limit: usize,
+        /// The actual number of extensions
+        actual: usize,
+    },
+    #[error("An extension value is {actual} bytes long, exceeding the limit of {limit} bytes")]
+    /// The value of an extension or CSR attribute is too long
+    ExtensionValueLength {
+        /// The maximum length, in bytes
+        limit: usize,
+        /// The actual length, in bytes
+        actual: usize,
+    },
 }
 
 /// Represents errors for invalid input. Differs from [ConstraintError], in that `ConstraintError` is
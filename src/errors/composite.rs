@@ -5,7 +5,11 @@
 use spki::ObjectIdentifier;
 use thiserror::Error;
 
-use super::base::{ConstraintError, InvalidInput};
+use crate::certs::chain::RejectedIssuer;
+use crate::timestamp::Timestamp;
+use crate::verifier::BudgetResource;
+
+use super::base::{ConstraintError, DerViolation, InvalidInput, SizeLimitViolation};
 
 #[derive(Error, Debug, PartialEq, Clone)]
 /// Errors that can occur when validating a certificate
@@ -16,9 +20,74 @@ pub enum InvalidCert {
     #[error(transparent)]
     /// The certificate does not pass validation of polyproto constraints
     InvalidProperties(#[from] ConstraintError),
+    #[error(transparent)]
+    /// The encoding of the certificate violates the strict DER rules of a
+    /// [ValidationPolicy](crate::certs::policy::ValidationPolicy)
+    StrictDer(#[from] DerViolation),
+    #[error(transparent)]
+    /// A field of the certificate exceeds the [SizeLimits](crate::certs::limits::SizeLimits)
+    /// enforced when decoding it
+    SizeLimit(#[from] SizeLimitViolation),
     #[error("The validity period of the certificate is invalid, or the certificate is expired")]
     /// The certificate is expired or has an invalid validity period
     InvalidValidity,
+    #[error("The certificate was superseded at {0}")]
+    /// The certificate has been superseded by another certificate at the given point in time, and
+    /// may not be used for new sessions or for data signed after that point in time
+    Superseded(Timestamp),
+    #[error("The verification budget for {0:?} has been exceeded")]
+    /// Verifying the certificate would exceed the
+    /// [VerificationBudget](crate::verifier::VerificationBudget) of the verifier
+    BudgetExceeded(BudgetResource),
+    #[error("The certificate was revoked at {0}")]
+    /// The certificate is listed as revoked at the given point in time in a certificate
+    /// revocation list of its issuer
+    Revoked(Timestamp),
+    #[error("The revocation status of the certificate could not be determined")]
+    /// The revocation status of the certificate is unknown, and the
+    /// [RevocationMode](crate::certs::status::RevocationMode) requires it to be known
+    RevocationStatusUnknown,
+    #[error("The certificate is not included in the certificate transparency log")]
+    /// The certificate is not included in the [CertLog](crate::certs::certlog::CertLog) committed
+    /// to by a signed tree head
+    NotLogged,
+    #[error("No issuer of the certificate is anchored in the trust store for the domain {0:?}")]
+    /// None of the issuers of the certificate matches a
+    /// [TrustAnchor](crate::certs::truststore::TrustAnchor) registered in the
+    /// [TrustStore](crate::certs::truststore::TrustStore) for the domain of the issuing home
+    /// server
+    NotAnchored(String),
+    #[error("No public key of the certificate chain matches a pinned key")]
+    /// None of the certificates presented carries a public key pinned in the
+    /// [PinSet](crate::certs::pinning::PinSet) consulted during validation, which indicates that
+    /// the key of the home server has been substituted
+    PinMismatch,
+    #[error("The certificate is not valid for the domain {0:?}")]
+    /// The domain of an instance is neither listed in the
+    /// [SubjectAltNames](crate::certs::san::SubjectAltNames) of its home server certificate, nor,
+    /// if the certificate carries none, made up by the domain components of its subject
+    DomainMismatch(String),
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Errors that can occur when checking whether an [IdCsr](crate::certs::idcsr::IdCsr) is fresh
+/// and has not been used before
+pub enum StaleCsr {
+    #[error("The CSR does not carry a creation time and a nonce")]
+    /// The CSR lacks the metadata required to check its freshness
+    MissingMetadata,
+    #[error("The CSR expired at {0}")]
+    /// The expiry time set by the subject has passed
+    Expired(Timestamp),
+    #[error("The CSR was created at {0}, which is too long ago")]
+    /// The CSR is older than the maximum age accepted by the home server
+    TooOld(Timestamp),
+    #[error("The CSR claims to have been created in the future, at {0}")]
+    /// The creation time of the CSR lies in the future, beyond the tolerated clock skew
+    CreatedInFuture(Timestamp),
+    #[error("The nonce of the CSR has been used before")]
+    /// A CSR with the same nonce has been accepted before
+    Replayed,
 }
 
 #[derive(Error, Debug, PartialEq, Hash, Clone, Copy)]
@@ -30,6 +99,9 @@ pub enum PublicKeyError {
     #[error("The provided PublicKeyInfo could not be made into a PublicKey")]
     /// The provided PublicKey is invalid
     BadPublicKeyInfo,
+    #[error("The signature algorithm {0} is not supported")]
+    /// No implementation of the signature algorithm with the given OID is available
+    UnsupportedAlgorithm(ObjectIdentifier),
 }
 
 #[derive(Error, Debug, PartialEq, Clone)]
@@ -41,6 +113,14 @@ pub enum ConversionError {
     #[error(transparent)]
     /// The input was invalid - Either malformed or out of bounds
     InvalidInput(#[from] InvalidInput),
+    #[error(transparent)]
+    /// The input violates the strict DER rules of a
+    /// [ValidationPolicy](crate::certs::policy::ValidationPolicy)
+    StrictDer(#[from] DerViolation),
+    #[error(transparent)]
+    /// A field of the input exceeds the [SizeLimits](crate::certs::limits::SizeLimits) enforced
+    /// when decoding it
+    SizeLimit(#[from] SizeLimitViolation),
     #[error("Encountered DER encoding error")]
     /// An error occurred while parsing a DER encoded object
     DerError(der::Error),
@@ -56,6 +136,35 @@ pub enum ConversionError {
     #[error(transparent)]
     /// The source or target certificate is invalid
     InvalidCert(#[from] InvalidCert),
+    #[error("The signer failed to produce a signature: {0}")]
+    /// An [AsyncPrivateKey](crate::key::AsyncPrivateKey), such as a remote signer backed by a KMS
+    /// or HSM, failed to produce a signature
+    SignerError(String),
+    #[cfg(feature = "pkcs8")]
+    #[error("Encountered PKCS#8 error")]
+    /// An error occurred while encoding, decoding, encrypting or decrypting a PKCS#8 document
+    Pkcs8Error(pkcs8::Error),
+    #[cfg(feature = "openssh")]
+    #[error("Encountered OpenSSH key format error")]
+    /// An error occurred while decoding or decrypting an OpenSSH key
+    OpenSshError(ssh_key::Error),
+    #[cfg(feature = "age")]
+    #[error("Encountered age error: {0}")]
+    /// An error occurred while decrypting an age encrypted identity backup
+    AgeError(String),
+    #[cfg(feature = "hpke")]
+    #[error("Encountered HPKE error: {0}")]
+    /// An error occurred while sealing or opening private key material using HPKE
+    HpkeError(String),
+    #[cfg(feature = "frost")]
+    #[error("Encountered threshold signature error: {0}")]
+    /// An error occurred while generating, splitting or using a threshold key
+    ThresholdError(String),
+    #[cfg(feature = "pkcs12")]
+    #[error("Encountered PKCS#12 error: {0}")]
+    /// A PKCS#12 file is not integrity protected, fails its integrity check or uses an
+    /// unsupported algorithm or bag type
+    Pkcs12Error(String),
 }
 #[cfg(feature = "reqwest")]
 #[derive(Error, Debug)]
@@ -73,6 +182,190 @@ pub enum RequestError {
     #[error(transparent)]
     /// The URL could not be parsed
     UrlError(#[from] url::ParseError),
+    #[error("Failed to replay recorded interaction: {0}")]
+    /// A [Vcr](crate::api::vcr::Vcr) could not replay a response for the request
+    ReplayError(String),
+    #[error("Rate limit exceeded, retry after {0:?}")]
+    /// The [RateLimiter](crate::api::ratelimit::RateLimiter) of the client does not permit another
+    /// request yet
+    RateLimited(std::time::Duration),
+    #[error("No home server configured for domain {0}")]
+    /// A [MultiClient](crate::api::multi::MultiClient) has no home server for the domain of a
+    /// federation ID
+    UnknownHomeServer(String),
+}
+
+#[cfg(feature = "types")]
+#[derive(Error, Debug, PartialEq, Clone, Copy)]
+/// Errors that can occur when verifying the response to a challenge string
+pub enum ChallengeError {
+    #[error("The challenge has expired")]
+    /// The challenge was completed after it expired
+    Expired,
+    #[error("The challenge is not bound to a federation ID, purpose and server domain")]
+    /// The challenge has no binding, and can therefore not be used in a bound context
+    Unbound,
+    #[error("The challenge was issued for {actual:?}, but was used for {expected:?}")]
+    /// The challenge was issued for a different flow
+    PurposeMismatch {
+        /// The purpose the challenge was used for
+        expected: crate::types::ChallengePurpose,
+        /// The purpose the challenge was issued for
+        actual: crate::types::ChallengePurpose,
+    },
+    #[error("The challenge was issued for a different federation ID or server")]
+    /// The challenge was issued for a different actor or home server
+    BindingMismatch,
+    #[error(transparent)]
+    /// The signature of the response is invalid
+    PublicKeyError(#[from] PublicKeyError),
+}
+
+#[cfg(feature = "webhooks")]
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when verifying a received webhook
+pub enum WebhookError {
+    #[error("The signature of the webhook is missing, malformed or does not match the payload")]
+    /// The signature is missing, malformed or does not match the payload
+    BadSignature,
+    #[error("The timestamp of the webhook is outside of the accepted tolerance")]
+    /// The webhook was signed too long ago, or too far in the future
+    OutsideTolerance,
+    #[error(transparent)]
+    /// A header or the payload of the webhook is malformed
+    InvalidInput(#[from] InvalidInput),
+}
+
+#[cfg(all(feature = "types", feature = "serde"))]
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when verifying the signature of a
+/// [GatewayEvent](crate::gateway::GatewayEvent)
+pub enum GatewayEventError {
+    #[error("The event must be signed, but carries no signature")]
+    /// The event must be signed, but carries no signature
+    MissingSignature,
+    #[error("The certificate {0} which signed the event is unknown")]
+    /// The certificate which signed the event, or the certificate of its issuer, is not in the
+    /// certificate cache
+    UnknownSigner(crate::certs::certid::CertId),
+    #[error("The certificate which signed the event is invalid: {0}")]
+    /// The certificate which signed the event is invalid at the time the event was received
+    UntrustedSigner(InvalidCert),
+    #[error("The signature of the event is malformed or does not match the event")]
+    /// The signature is malformed or does not match the event
+    BadSignature,
+    #[error(transparent)]
+    /// The event is malformed
+    ConversionError(#[from] ConversionError),
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when submitting a job to a
+/// [VerificationQueue](crate::verifier::queue::VerificationQueue), or when running it
+pub enum QueueError {
+    #[error("The peer exceeded its rate limit, retry after {0:?}")]
+    /// The peer submitted too many jobs in too short a time
+    RateLimited(std::time::Duration),
+    #[error("Too many jobs of the peer are pending")]
+    /// The peer has too many pending jobs
+    PeerQueueFull,
+    #[error("Too many jobs are pending")]
+    /// The queue has too many pending jobs
+    QueueFull,
+    #[error("The verification queue has been closed")]
+    /// The queue was closed before the job was run
+    Closed,
+    #[error(transparent)]
+    /// The job was run, and the verification failed
+    InvalidCert(#[from] InvalidCert),
+}
+
+#[cfg(feature = "types")]
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when reading from or writing to a
+/// [KeyStore](crate::keystore::KeyStore)
+pub enum KeyStoreError {
+    #[error("The key store backend failed: {0}")]
+    /// The underlying storage, such as the file system or the keychain of the operating system,
+    /// could not be accessed
+    Backend(String),
+    #[error(transparent)]
+    /// A stored key or key identifier could not be decoded
+    ConversionError(#[from] ConversionError),
+}
+
+#[cfg(feature = "types")]
+impl From<std::io::Error> for KeyStoreError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Backend(value.to_string())
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when reading from or writing to a
+/// [CertStore](crate::certstore::CertStore) or another store of certificate related data
+pub enum CertStoreError {
+    #[error("The certificate store backend failed: {0}")]
+    /// The underlying storage, such as the file system, could not be accessed
+    Backend(String),
+    #[error(transparent)]
+    /// A stored certificate or certificate identifier could not be decoded or encoded
+    ConversionError(#[from] ConversionError),
+}
+
+#[cfg(feature = "types")]
+impl From<std::io::Error> for CertStoreError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Backend(value.to_string())
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl From<sqlx::Error> for CertStoreError {
+    fn from(value: sqlx::Error) -> Self {
+        Self::Backend(value.to_string())
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for CertStoreError {
+    fn from(value: redis::RedisError) -> Self {
+        Self::Backend(value.to_string())
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when building an [IdCertChain](crate::certs::chain::IdCertChain) from a
+/// [CertPool](crate::certs::chain::CertPool)
+pub enum ChainBuildError {
+    #[error(
+        "No issuer of {subject} leading to a self-signed root was found in the certificate pool"
+    )]
+    /// None of the certificates in the pool could be used as the issuer of the certificate with
+    /// the given subject. `rejected` lists the certificates whose subject matched the issuer of
+    /// the certificate, and why each of them was rejected
+    NoIssuer {
+        /// The subject of the certificate whose issuer could not be found
+        subject: String,
+        /// The candidate issuers and the reasons for their rejection
+        rejected: Vec<RejectedIssuer>,
+    },
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when decoding a bundle of certificates using
+/// [IdCert::many_from_pem()](crate::certs::idcert::IdCert::many_from_pem) or
+/// [IdCert::many_from_der()](crate::certs::idcert::IdCert::many_from_der)
+pub enum BundleError {
+    #[error("Entry {index} of the bundle is invalid: {error}")]
+    /// The entry at the zero-based position `index` of the bundle is malformed, is not a
+    /// certificate or does not pass validation
+    InvalidEntry {
+        /// The zero-based position of the entry in the bundle
+        index: usize,
+        /// Why the entry was rejected
+        error: ConversionError,
+    },
 }
 
 impl From<der::Error> for ConversionError {
@@ -86,3 +379,63 @@ impl From<der::oid::Error> for ConversionError {
         Self::ConstOidError(value)
     }
 }
+
+#[cfg(feature = "pkcs8")]
+impl From<pkcs8::Error> for ConversionError {
+    fn from(value: pkcs8::Error) -> Self {
+        Self::Pkcs8Error(value)
+    }
+}
+
+#[cfg(feature = "openssh")]
+impl From<ssh_key::Error> for ConversionError {
+    fn from(value: ssh_key::Error) -> Self {
+        Self::OpenSshError(value)
+    }
+}
+
+#[cfg(feature = "age")]
+impl From<age::DecryptError> for ConversionError {
+    fn from(value: age::DecryptError) -> Self {
+        Self::AgeError(value.to_string())
+    }
+}
+
+#[cfg(feature = "hpke")]
+impl From<hpke::HpkeError> for ConversionError {
+    fn from(value: hpke::HpkeError) -> Self {
+        Self::HpkeError(value.to_string())
+    }
+}
+
+#[cfg(feature = "frost")]
+impl From<frost_ed25519::Error> for ConversionError {
+    fn from(value: frost_ed25519::Error) -> Self {
+        Self::ThresholdError(value.to_string())
+    }
+}
+
+#[cfg(feature = "test-utils")]
+#[derive(Error, Debug, PartialEq, Clone)]
+/// Errors that can occur when running OpenSSL using [OpenSsl](crate::interop::OpenSsl)
+pub enum InteropError {
+    #[error("Could not run OpenSSL: {0}")]
+    /// The OpenSSL executable could not be started, or its input and output files could not be
+    /// written or read
+    Unavailable(String),
+    #[error("OpenSSL rejected the input of `{command}`: {stderr}")]
+    /// OpenSSL ran, but exited unsuccessfully
+    Rejected {
+        /// The OpenSSL subcommand, such as `verify`
+        command: String,
+        /// What OpenSSL wrote to its standard error stream
+        stderr: String,
+    },
+}
+
+#[cfg(feature = "test-utils")]
+impl From<std::io::Error> for InteropError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Unavailable(value.to_string())
+    }
+}
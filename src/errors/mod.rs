@@ -8,6 +8,8 @@ pub static ERR_MSG_HOME_SERVER_MISSING_CA_ATTR: &str =
     "Home servers CSRs and Certificates must have the \"CA\" capability set to true!";
 pub static ERR_MSG_ACTOR_CANNOT_BE_CA: &str =
     "Actor CSRs and Certificates must not have \"CA\" capabilities!";
+pub static ERR_MSG_ACTOR_SUBJECT_ALT_NAMES: &str =
+    "Actor Certificates must not carry subject alternative names!";
 pub static ERR_MSG_SIGNATURE_MISMATCH: &str =
     "Provided signature does not match computed signature!";
 pub static ERR_MSG_ACTOR_MISSING_SIGNING_CAPS: &str =
@@ -18,12 +20,35 @@ pub static ERR_MSG_DC_MISMATCH_ISSUER_SUBJECT: &str =
     "The domain components of the issuer and the subject do not match!";
 pub static ERR_CERTIFICATE_TO_DER_ERROR: &str =
     "The certificate seems to be malformed, as it cannot be converted to DER.";
+pub static ERR_MSG_GUEST_MISSING_MARKER: &str =
+    "Guest certificates must carry the \"polyproto-guest\" organizational unit!";
+pub static ERR_MSG_GUEST_CAPABILITIES: &str =
+    "Guest certificates must not have capabilities beyond the default guest capabilities!";
+pub static ERR_MSG_TARGET_AMBIGUOUS: &str =
+    "The target cannot be inferred, as the capabilities and the subject contradict each other!";
+pub static ERR_MSG_POLICY_ALGORITHM: &str =
+    "The signature algorithm is not allowed by the validation policy!";
+pub static ERR_MSG_POLICY_KEY_USAGE: &str =
+    "A key usage required by the validation policy is missing!";
+pub static ERR_MSG_CRL_ISSUER_MISMATCH: &str =
+    "The issuer of the CRL or CRL issuer designation does not match the expected signer!";
+pub static ERR_MSG_CRL_SIGNER_MISSING_CRL_SIGN: &str =
+    "Delegated CRL issuers must have the \"CrlSign\" capability and must not be CAs!";
 #[cfg(feature = "types")]
 pub static ERR_MSG_CHALLENGE_STRING_LENGTH: &str =
     "Challenge strings must be between 32 and 255 bytes long!";
 #[cfg(feature = "types")]
+pub static ERR_MSG_CHALLENGE_BINDING_DOMAIN: &str =
+    "The server domain of a challenge binding must be a valid, lowercase domain name!";
+#[cfg(feature = "types")]
+pub static ERR_MSG_CHALLENGE_BINDING_DOMAIN_MISMATCH: &str =
+    "Registration and key trial challenges must be issued by the home server of the federation ID!";
+#[cfg(feature = "types")]
 pub static ERR_MSG_FEDERATION_ID_REGEX: &str =
     "Federation IDs must match the regex: \\b([a-z0-9._%+-]+)@([a-z0-9-]+(\\.[a-z0-9-]+)*)";
+#[cfg(feature = "types")]
+pub static ERR_MSG_SESSION_ID_CHARSET: &str =
+    "Session IDs must only consist of ASCII letters, digits, '.', '_' and '-'!";
 /// "Base" error types which can be combined into "composite" error types
 pub mod base;
 /// "Composite" error types which consist of one or more "base" error types
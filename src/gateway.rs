@@ -0,0 +1,298 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{CacheGeneration, GenerationalCache};
+use crate::certs::certid::CertId;
+use crate::certs::idcert::IdCert;
+use crate::encoding::{decode_hex, encode_hex};
+use crate::errors::{ConversionError, GatewayEventError, InvalidInput};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+/// The prefix of the payload signed for a [GatewayEvent].
+pub static GATEWAY_EVENT_PAYLOAD_PREFIX: &str = "polyproto-gateway-event-v1";
+
+/// Names of the gateway events which must be signed by default: broadcasts announcing a change to
+/// the identity of an actor or a home server.
+pub const SIGNED_EVENTS: [&str; 2] = [
+    "actor_certificate_invalidation",
+    "server_certificate_change",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// The signature of a [GatewayEvent].
+pub struct EventSignature {
+    /// The [CertId] of the ID-Cert whose key signed the event.
+    pub cert: CertId,
+    /// The hex encoded signature over [GatewayEvent::signed_payload()].
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A single event received from, or sent over, the gateway of a home server.
+pub struct GatewayEvent {
+    /// The name of the event, such as `actor_certificate_invalidation`.
+    pub name: String,
+    /// The payload of the event.
+    pub data: serde_json::Value,
+    /// The signature of the event, if it has been signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<EventSignature>,
+}
+
+impl GatewayEvent {
+    /// Creates a new, unsigned [GatewayEvent].
+    pub fn new(name: &str, data: serde_json::Value) -> Self {
+        Self {
+            name: name.to_string(),
+            data,
+            signature: None,
+        }
+    }
+
+    /// Creates a new [GatewayEvent], signed by `signing_key`. `cert_id` must identify the ID-Cert
+    /// containing the public key belonging to `signing_key`.
+    pub fn signed<S: Signature>(
+        name: &str,
+        data: serde_json::Value,
+        cert_id: CertId,
+        signing_key: &impl PrivateKey<S>,
+    ) -> Result<Self, ConversionError> {
+        let mut event = Self::new(name, data);
+        let signature = signing_key.sign(&event.signed_payload()?).to_bitstring()?;
+        event.signature = Some(EventSignature {
+            cert: cert_id,
+            signature: encode_hex(signature.raw_bytes()),
+        });
+        Ok(event)
+    }
+
+    /// Returns the payload which is signed for this event: [GATEWAY_EVENT_PAYLOAD_PREFIX], the
+    /// name of the event and the compact JSON encoding of its data, separated by `.`. Objects in
+    /// the data are encoded with their keys in sorted order.
+    pub fn signed_payload(&self) -> Result<Vec<u8>, ConversionError> {
+        let data = match serde_json::to_vec(&self.data) {
+            Ok(data) => data,
+            Err(e) => return Err(InvalidInput::Malformed(e.to_string()).into()),
+        };
+        let mut payload = format!("{}.{}.", GATEWAY_EVENT_PAYLOAD_PREFIX, self.name).into_bytes();
+        payload.extend_from_slice(&data);
+        Ok(payload)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A [GatewayEvent] which passed through an [EventVerifier].
+pub enum ReceivedEvent<S: Signature, P: PublicKey<S>> {
+    /// An event which does not need to be signed. Any signature it carries has not been checked.
+    Unsigned(GatewayEvent),
+    /// A signed event, whose signature has been verified.
+    Verified {
+        /// The event.
+        event: GatewayEvent,
+        /// The ID-Cert whose key signed the event.
+        signer: Box<IdCert<S, P>>,
+    },
+    /// An event which must be signed, but whose signature could not be verified. The event must
+    /// not be acted upon.
+    Unverifiable {
+        /// The event.
+        event: GatewayEvent,
+        /// Why the signature could not be verified.
+        error: GatewayEventError,
+    },
+}
+
+impl<S: Signature, P: PublicKey<S>> ReceivedEvent<S, P> {
+    /// Returns the event, regardless of whether it has been verified.
+    pub fn event(&self) -> &GatewayEvent {
+        match self {
+            ReceivedEvent::Unsigned(event) => event,
+            ReceivedEvent::Verified { event, .. } => event,
+            ReceivedEvent::Unverifiable { event, .. } => event,
+        }
+    }
+
+    /// Returns `true`, if the event can be delivered: it either does not need to be signed, or its
+    /// signature has been verified.
+    pub fn is_deliverable(&self) -> bool {
+        !matches!(self, ReceivedEvent::Unverifiable { .. })
+    }
+
+    /// Returns the event if it can be delivered, and the reason why it could not be verified
+    /// otherwise.
+    pub fn into_result(self) -> Result<GatewayEvent, GatewayEventError> {
+        match self {
+            ReceivedEvent::Unsigned(event) | ReceivedEvent::Verified { event, .. } => Ok(event),
+            ReceivedEvent::Unverifiable { error, .. } => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Verifies the signatures of [GatewayEvent]s received from a gateway, before they are delivered
+/// to the application.
+///
+/// Events whose name is in the set of signed events, which defaults to [SIGNED_EVENTS], must be
+/// signed using the key of a trusted home server certificate, or of an actor certificate in the
+/// certificate cache of the verifier. Actor certificates must have been issued by one of the trusted home server
+/// certificates, and all certificates must be valid at the time the event is received. Events
+/// failing these checks are returned as [ReceivedEvent::Unverifiable] instead of being delivered.
+///
+/// The certificate cache is a [GenerationalCache]: changing the [CacheGeneration] of the verifier
+/// evicts all certificates cached under a previous generation.
+pub struct EventVerifier<S: Signature, P: PublicKey<S>> {
+    home_server_certs: Vec<IdCert<S, P>>,
+    certs: GenerationalCache<CertId, IdCert<S, P>>,
+    generation: CacheGeneration,
+    signed_events: BTreeSet<String>,
+}
+
+impl<S: Signature, P: PublicKey<S>> Default for EventVerifier<S, P> {
+    fn default() -> Self {
+        Self {
+            home_server_certs: Vec::new(),
+            certs: GenerationalCache::new(),
+            generation: CacheGeneration::new(),
+            signed_events: SIGNED_EVENTS.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> EventVerifier<S, P> {
+    /// Creates a new [EventVerifier] without trusted home servers, requiring the events in
+    /// [SIGNED_EVENTS] to be signed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts a home server certificate, both for signing events and for issuing the certificates
+    /// of actors. Trusted home server certificates are not part of the certificate cache, and are
+    /// kept when the [CacheGeneration] changes.
+    pub fn with_home_server_cert(mut self, cert: IdCert<S, P>) -> Self {
+        self.home_server_certs.push(cert);
+        self
+    }
+
+    /// Requires the event with the given name to be signed.
+    pub fn with_signed_event(mut self, name: &str) -> Self {
+        self.signed_events.insert(name.to_string());
+        self
+    }
+
+    /// Sets the [CacheGeneration] of the certificate cache, such as the generation of the trust
+    /// store of the home server the gateway belongs to.
+    pub fn with_generation(mut self, generation: CacheGeneration) -> Self {
+        self.set_generation(generation);
+        self
+    }
+
+    /// Sets the [CacheGeneration] of the certificate cache. Certificates cached under another
+    /// generation are evicted.
+    pub fn set_generation(&mut self, generation: CacheGeneration) {
+        self.generation = generation;
+        self.certs.purge_stale(generation);
+    }
+
+    /// Returns `true`, if the event with the given name must be signed.
+    pub fn requires_signature(&self, name: &str) -> bool {
+        self.signed_events.contains(name)
+    }
+
+    /// Adds the certificate of an actor or home server to the certificate cache, so that events
+    /// signed by it can be verified. The certificate itself is verified when an event signed by it
+    /// is received.
+    pub fn cache_cert(&mut self, cert: IdCert<S, P>) -> Result<(), ConversionError> {
+        let cert_id = cert.cert_id()?;
+        log::trace!(
+            "[EventVerifier::cache_cert()] Caching certificate {}",
+            cert_id
+        );
+        self.certs.insert(cert_id, cert, self.generation);
+        Ok(())
+    }
+
+    /// Verifies an event received at `time`.
+    pub fn verify(&mut self, event: GatewayEvent, time: Timestamp) -> ReceivedEvent<S, P> {
+        if !self.requires_signature(&event.name) {
+            return ReceivedEvent::Unsigned(event);
+        }
+        match self.verify_signed(&event, time) {
+            Ok(signer) => ReceivedEvent::Verified {
+                event,
+                signer: Box::new(signer),
+            },
+            Err(error) => {
+                log::warn!(
+                    "[EventVerifier::verify()] Event {} could not be verified: {}",
+                    event.name,
+                    error
+                );
+                ReceivedEvent::Unverifiable { event, error }
+            }
+        }
+    }
+
+    fn verify_signed(
+        &mut self,
+        event: &GatewayEvent,
+        time: Timestamp,
+    ) -> Result<IdCert<S, P>, GatewayEventError> {
+        let signature = match &event.signature {
+            Some(signature) => signature,
+            None => return Err(GatewayEventError::MissingSignature),
+        };
+        let signer = self.signer(&signature.cert, time)?;
+        let bytes =
+            decode_hex(&signature.signature).map_err(|_| GatewayEventError::BadSignature)?;
+        let payload = event.signed_payload()?;
+        signer
+            .id_cert_tbs
+            .subject_public_key
+            .verify_signature(&S::from_bytes(&bytes), &payload)
+            .map_err(|_| GatewayEventError::BadSignature)?;
+        Ok(signer)
+    }
+
+    /// Looks up and verifies the certificate identified by `cert_id`: either one of the trusted
+    /// home server certificates, or a cached actor certificate issued by one of them.
+    fn signer(
+        &mut self,
+        cert_id: &CertId,
+        time: Timestamp,
+    ) -> Result<IdCert<S, P>, GatewayEventError> {
+        if let Some(home_server_cert) = self
+            .home_server_certs
+            .iter()
+            .find(|cert| cert.cert_id().as_ref() == Ok(cert_id))
+        {
+            log::trace!("[EventVerifier::signer()] Signed by a home server");
+            home_server_cert
+                .full_verify_home_server(time)
+                .map_err(GatewayEventError::UntrustedSigner)?;
+            return Ok(home_server_cert.clone());
+        }
+        let actor_cert = match self.certs.get(cert_id, self.generation) {
+            Some(cert) => cert.clone(),
+            None => return Err(GatewayEventError::UnknownSigner(cert_id.clone())),
+        };
+        let issuer = match self
+            .home_server_certs
+            .iter()
+            .find(|cert| cert_id.is_issued_by(&cert.id_cert_tbs.subject))
+        {
+            Some(issuer) => issuer,
+            None => return Err(GatewayEventError::UnknownSigner(cert_id.clone())),
+        };
+        actor_cert
+            .full_verify_actor(time, &issuer.id_cert_tbs.subject_public_key)
+            .map_err(GatewayEventError::UntrustedSigner)?;
+        Ok(actor_cert)
+    }
+}
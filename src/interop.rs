@@ -0,0 +1,353 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use der::pem::LineEnding;
+use x509_cert::name::Name;
+
+use crate::certs::capabilities::{Capabilities, KeyUsage};
+use crate::errors::InteropError;
+use crate::timestamp::Timestamp;
+
+/// The environment variable naming the OpenSSL executable used by [OpenSsl::new()].
+pub const OPENSSL_ENV: &str = "POLYPROTO_OPENSSL";
+
+/// Runs the `openssl` command line tool, to check that certificates produced by this crate parse
+/// and verify in OpenSSL, and to produce certificates using OpenSSL which can then be parsed and
+/// verified using this crate.
+///
+/// Certificates and keys are passed in and returned as DER. Every method runs OpenSSL
+/// in a fresh temporary directory, which is removed afterwards. Certificates are issued using the
+/// subcommands `req` and `x509`, which OpenSSL supports since version 1.1.1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenSsl {
+    program: PathBuf,
+}
+
+/// The issuer of a certificate produced by [OpenSsl::issue_certificate()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Issuer<'a> {
+    /// The DER encoded certificate of the issuer.
+    pub certificate: &'a [u8],
+    /// The DER encoded PKCS#8 private key of the issuer.
+    pub private_key: &'a [u8],
+}
+
+impl Default for OpenSsl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenSsl {
+    /// Uses the executable named by the environment variable [OPENSSL_ENV], or `openssl` from the
+    /// `PATH`, if the variable is not set.
+    pub fn new() -> Self {
+        Self::with_program(std::env::var_os(OPENSSL_ENV).unwrap_or_else(|| "openssl".into()))
+    }
+
+    /// Uses the executable at `program`.
+    pub fn with_program(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+
+    /// Returns the version string of OpenSSL, such as `OpenSSL 3.0.13 30 Jan 2024`.
+    pub fn version(&self) -> Result<String, InteropError> {
+        let output = self.run(&mut self.command("version"))?;
+        Ok(String::from_utf8_lossy(&output).trim().to_string())
+    }
+
+    /// Whether OpenSSL can be run. Harnesses use this to skip their checks on machines without
+    /// OpenSSL.
+    pub fn is_available(&self) -> bool {
+        self.version().is_ok()
+    }
+
+    /// Parses the DER encoded certificate `der`, returning OpenSSL's textual description of it.
+    pub fn parse_certificate(&self, der: &[u8]) -> Result<String, InteropError> {
+        let directory = WorkDir::new()?;
+        let cert = directory.write("cert.der", der)?;
+        let output = self.run(
+            self.command("x509")
+                .args(["-inform", "DER", "-noout", "-text", "-in"])
+                .arg(&cert),
+        )?;
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+
+    /// Verifies the DER encoded certificate `der` at `time`, using the DER encoded certificate
+    /// `trusted` as the only trust anchor. `trusted` must be the issuer of `der`, or `der` itself,
+    /// if it is self-signed. The verification uses OpenSSL's strict X.509 checks, which reject
+    /// certificates that are not correctly DER encoded.
+    pub fn verify_certificate(
+        &self,
+        der: &[u8],
+        trusted: &[u8],
+        time: Timestamp,
+    ) -> Result<(), InteropError> {
+        let directory = WorkDir::new()?;
+        let cert = directory.write_pem("cert.pem", "CERTIFICATE", der)?;
+        let ca = directory.write_pem("ca.pem", "CERTIFICATE", trusted)?;
+        self.run(
+            self.command("verify")
+                .args(["-x509_strict", "-attime"])
+                .arg(time.unix_seconds().to_string())
+                .arg("-CAfile")
+                .arg(&ca)
+                .arg(&cert),
+        )?;
+        Ok(())
+    }
+
+    /// Generates an Ed25519 key pair, returning the DER encoded PKCS#8 private key.
+    pub fn generate_ed25519_key(&self) -> Result<Vec<u8>, InteropError> {
+        let directory = WorkDir::new()?;
+        let key = directory.path("key.der");
+        self.run(
+            self.command("genpkey")
+                .args(["-algorithm", "ED25519", "-outform", "DER", "-out"])
+                .arg(&key),
+        )?;
+        directory.read(&key)
+    }
+
+    /// Issues a certificate for the key pair of the DER encoded PKCS#8 private key `private_key`,
+    /// returning the DER encoded certificate. The certificate is valid from now on for `days`
+    /// days, carries `subject` and the extensions corresponding to `capabilities`, and is signed
+    /// by `issuer`, or by `private_key` itself, if `issuer` is `None`.
+    ///
+    /// Custom extensions of `capabilities` are left out. The subject key identifier and, if the
+    /// certificate is not self-signed, the authority key identifier extensions are added.
+    pub fn issue_certificate(
+        &self,
+        private_key: &[u8],
+        subject: &Name,
+        capabilities: &Capabilities,
+        serial_number: u64,
+        days: u32,
+        issuer: Option<Issuer<'_>>,
+    ) -> Result<Vec<u8>, InteropError> {
+        log::trace!(
+            "[OpenSsl::issue_certificate()] Issuing certificate for {} using OpenSSL",
+            subject
+        );
+        let directory = WorkDir::new()?;
+        let key = directory.write_pem("key.pem", "PRIVATE KEY", private_key)?;
+        let config = directory.write(
+            "openssl.cnf",
+            openssl_config(capabilities, issuer.is_some()).as_bytes(),
+        )?;
+        let cert = directory.path("cert.der");
+        let subject = openssl_subject(subject);
+        let serial_number = serial_number.to_string();
+        let days = days.to_string();
+        match issuer {
+            None => {
+                self.run(
+                    self.command("req")
+                        .args(["-x509", "-new", "-config"])
+                        .arg(&config)
+                        .args(["-extensions", "extensions", "-key"])
+                        .arg(&key)
+                        .arg("-subj")
+                        .arg(&subject)
+                        .arg("-set_serial")
+                        .arg(&serial_number)
+                        .arg("-days")
+                        .arg(&days)
+                        .args(["-outform", "DER", "-out"])
+                        .arg(&cert),
+                )?;
+            }
+            Some(issuer) => {
+                let csr = directory.path("csr.pem");
+                self.run(
+                    self.command("req")
+                        .args(["-new", "-config"])
+                        .arg(&config)
+                        .arg("-key")
+                        .arg(&key)
+                        .arg("-subj")
+                        .arg(&subject)
+                        .arg("-out")
+                        .arg(&csr),
+                )?;
+                let ca = directory.write_pem("ca.pem", "CERTIFICATE", issuer.certificate)?;
+                let ca_key =
+                    directory.write_pem("ca-key.pem", "PRIVATE KEY", issuer.private_key)?;
+                self.run(
+                    self.command("x509")
+                        .args(["-req", "-in"])
+                        .arg(&csr)
+                        .arg("-CA")
+                        .arg(&ca)
+                        .arg("-CAkey")
+                        .arg(&ca_key)
+                        .arg("-extfile")
+                        .arg(&config)
+                        .args(["-extensions", "extensions", "-set_serial"])
+                        .arg(&serial_number)
+                        .arg("-days")
+                        .arg(&days)
+                        .args(["-outform", "DER", "-out"])
+                        .arg(&cert),
+                )?;
+            }
+        }
+        directory.read(&cert)
+    }
+
+    /// Returns a [Command] running the OpenSSL subcommand `subcommand`.
+    fn command(&self, subcommand: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        command.arg(subcommand);
+        command
+    }
+
+    /// Runs `command`, returning its standard output.
+    fn run(&self, command: &mut Command) -> Result<Vec<u8>, InteropError> {
+        let subcommand = command
+            .get_args()
+            .next()
+            .map(|subcommand| subcommand.to_string_lossy().to_string())
+            .unwrap_or_default();
+        log::trace!("[OpenSsl::run()] Running `openssl {}`", subcommand);
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(InteropError::Rejected {
+                command: subcommand,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Returns an OpenSSL configuration file, whose section `extensions` contains the extensions
+/// corresponding to `capabilities`.
+fn openssl_config(capabilities: &Capabilities, with_authority_key_identifier: bool) -> String {
+    let mut config = String::from(
+        "[req]\ndistinguished_name = distinguished_name\nstring_mask = utf8only\nutf8 = yes\n\n\
+         [distinguished_name]\n\n[extensions]\n",
+    );
+    let basic_constraints = &capabilities.basic_constraints;
+    let _ = write!(
+        config,
+        "basicConstraints = critical,CA:{}",
+        if basic_constraints.ca {
+            "TRUE"
+        } else {
+            "FALSE"
+        }
+    );
+    if let Some(path_length) = basic_constraints.path_length {
+        let _ = write!(config, ",pathlen:{}", path_length);
+    }
+    config.push('\n');
+    if !capabilities.key_usage.key_usages.is_empty() {
+        let key_usages: Vec<&str> = capabilities
+            .key_usage
+            .key_usages
+            .iter()
+            .map(|key_usage| match key_usage {
+                KeyUsage::DigitalSignature => "digitalSignature",
+                KeyUsage::ContentCommitment => "nonRepudiation",
+                KeyUsage::KeyEncipherment => "keyEncipherment",
+                KeyUsage::DataEncipherment => "dataEncipherment",
+                KeyUsage::KeyAgreement => "keyAgreement",
+                KeyUsage::KeyCertSign => "keyCertSign",
+                KeyUsage::CrlSign => "cRLSign",
+                KeyUsage::EncipherOnly => "encipherOnly",
+                KeyUsage::DecipherOnly => "decipherOnly",
+            })
+            .collect();
+        let _ = writeln!(config, "keyUsage = critical,{}", key_usages.join(","));
+    }
+    let extended_key_usages = &capabilities.extended_key_usage.extended_key_usages;
+    if !extended_key_usages.is_empty() {
+        let oids: Vec<String> = extended_key_usages
+            .iter()
+            .map(|usage| usage.oid().to_string())
+            .collect();
+        let _ = writeln!(config, "extendedKeyUsage = {}", oids.join(","));
+    }
+    config.push_str("subjectKeyIdentifier = hash\n");
+    if with_authority_key_identifier {
+        config.push_str("authorityKeyIdentifier = keyid:always\n");
+    }
+    config
+}
+
+/// Formats `name` for the `-subj` argument of OpenSSL, keeping the order of its RDNs and
+/// identifying its attributes by their OIDs.
+fn openssl_subject(name: &Name) -> String {
+    let mut subject = String::new();
+    for rdn in name.0.iter() {
+        subject.push('/');
+        for (index, ava) in rdn.0.iter().enumerate() {
+            if index > 0 {
+                subject.push('+');
+            }
+            let _ = write!(subject, "{}=", ava.oid);
+            for c in String::from_utf8_lossy(ava.value.value()).chars() {
+                if "/+=\\".contains(c) {
+                    subject.push('\\');
+                }
+                subject.push(c);
+            }
+        }
+    }
+    subject
+}
+
+/// A temporary directory holding the input and output files of OpenSSL, which is removed when
+/// dropped.
+struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    fn new() -> Result<Self, InteropError> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "polyproto-openssl-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    fn path(&self, file_name: &str) -> PathBuf {
+        self.path.join(file_name)
+    }
+
+    fn write(&self, file_name: &str, contents: &[u8]) -> Result<PathBuf, InteropError> {
+        let path = self.path(file_name);
+        std::fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    fn write_pem(&self, file_name: &str, label: &str, der: &[u8]) -> Result<PathBuf, InteropError> {
+        let pem = der::pem::encode_string(label, LineEnding::LF, der)
+            .map_err(|e| InteropError::Unavailable(e.to_string()))?;
+        self.write(file_name, pem.as_bytes())
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, InteropError> {
+        Ok(std::fs::read(path)?)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
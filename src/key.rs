@@ -2,11 +2,17 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::future::Future;
+use std::pin::Pin;
+
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha256};
 use spki::AlgorithmIdentifierOwned;
 
 use crate::certs::PublicKeyInfo;
-use crate::errors::{ConversionError, PublicKeyError};
-use crate::signature::Signature;
+use crate::encoding::{decode_base64, decode_hex, encode_base64, encode_base64url, encode_hex};
+use crate::errors::{ConversionError, InvalidInput, PublicKeyError};
+use crate::signature::{Signature, StreamSigner, StreamVerifier};
 
 /// A cryptographic private key generated by a [AlgorithmIdentifierOwned], with
 /// a corresponding [PublicKey]
@@ -21,6 +27,52 @@ pub trait PrivateKey<S: Signature>: PartialEq + Eq {
     fn algorithm_identifier(&self) -> AlgorithmIdentifierOwned {
         S::algorithm_identifier()
     }
+    /// Returns a [StreamSigner], creating a
+    /// [DetachedSignature](crate::signature::DetachedSignature) for data fed to it in chunks.
+    fn sign_stream(&self) -> StreamSigner<'_, S, Self> {
+        StreamSigner::new(self)
+    }
+}
+
+/// The future returned by [AsyncPrivateKey::sign()].
+#[cfg(not(target_arch = "wasm32"))]
+pub type SignFuture<'a, S> = Pin<Box<dyn Future<Output = Result<S, ConversionError>> + Send + 'a>>;
+/// The future returned by [AsyncPrivateKey::sign()]. On `wasm32`, the future is not required to be
+/// [Send], since futures wrapping JavaScript promises, such as those of the WebCrypto API, are
+/// bound to the thread they were created on.
+#[cfg(target_arch = "wasm32")]
+pub type SignFuture<'a, S> = Pin<Box<dyn Future<Output = Result<S, ConversionError>> + 'a>>;
+
+/// A private key, for which signing is an asynchronous operation, with a corresponding
+/// [PublicKey].
+///
+/// Home servers often keep their CA key in a key management service (KMS) or hardware security
+/// module (HSM), where the key never leaves the device and creating a signature is a remote
+/// procedure call. Implementing this trait for such a signer allows issuing certificates using
+/// [IdCert::from_ca_csr_async()](crate::certs::idcert::IdCert::from_ca_csr_async) and
+/// [IdCert::from_actor_csr_async()](crate::certs::idcert::IdCert::from_actor_csr_async) without
+/// blocking.
+pub trait AsyncPrivateKey<S: Signature> {
+    /// The public key type corresponding to this private key.
+    type PublicKey: PublicKey<S>;
+    /// Returns the public key corresponding to this private key. Implementations are expected to
+    /// fetch and cache the public key when the signer is created.
+    fn pubkey(&self) -> &Self::PublicKey;
+    /// Creates a [Signature] for the given data. Fails, if the signer could not produce a
+    /// signature, e.g. because the remote service is unavailable.
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignFuture<'a, S>;
+    /// Returns the [AlgorithmIdentifierOwned] associated with this key's signature algorithm.
+    fn algorithm_identifier(&self) -> AlgorithmIdentifierOwned {
+        S::algorithm_identifier()
+    }
+}
+
+/// A [PrivateKey] which can be freshly generated from a cryptographically secure random number
+/// generator. Allows generic code and tests to create key pairs for any signature algorithm in a
+/// uniform way.
+pub trait KeyGen<S: Signature>: PrivateKey<S> + Sized {
+    /// Generates a new private key, and with it, its corresponding [PublicKey].
+    fn generate(rng: &mut impl CryptoRngCore) -> Self;
 }
 
 /// A cryptographic public key generated by a [SignatureAlgorithm].
@@ -37,4 +89,219 @@ pub trait PublicKey<S: Signature>: PartialEq + Eq + Clone {
     }
     /// Creates a new [Self] from a [PublicKeyInfo].
     fn try_from_public_key_info(public_key_info: PublicKeyInfo) -> Result<Self, ConversionError>;
+    /// Returns the [KeyFingerprint] of this key: the SHA-256 hash of its DER encoded
+    /// `SubjectPublicKeyInfo`.
+    fn fingerprint(&self) -> Result<KeyFingerprint, ConversionError> {
+        KeyFingerprint::from_public_key_info(&self.public_key_info())
+    }
+    /// Returns a [StreamVerifier], verifying a
+    /// [DetachedSignature](crate::signature::DetachedSignature) for data fed to it in chunks.
+    fn verify_stream(&self) -> StreamVerifier<'_, S, Self> {
+        StreamVerifier::new(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The fingerprint of a [PublicKey]: the SHA-256 hash of its DER encoded `SubjectPublicKeyInfo`.
+///
+/// Since the `SubjectPublicKeyInfo` includes the algorithm identifier, keys of different
+/// algorithms never share a fingerprint, and the fingerprint of a key does not depend on which
+/// backend produced it. Clients can display the fingerprint, or a prefix of it, for out-of-band
+/// verification; servers can use it to index keys.
+///
+/// [Display](std::fmt::Display) and [FromStr](std::str::FromStr) use the lowercase hex encoding.
+pub struct KeyFingerprint([u8; 32]);
+
+impl KeyFingerprint {
+    /// Computes the fingerprint of a [PublicKeyInfo].
+    pub fn from_public_key_info(public_key_info: &PublicKeyInfo) -> Result<Self, ConversionError> {
+        Ok(Self(Sha256::digest(public_key_info.to_der()?).into()))
+    }
+
+    /// Creates a [KeyFingerprint] from the raw SHA-256 hash.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the raw SHA-256 hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns the fingerprint as a lowercase hex string of 64 characters.
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.0)
+    }
+
+    /// Returns the fingerprint as an unpadded base64url string of 43 characters.
+    pub fn to_base64url(&self) -> String {
+        encode_base64url(&self.0)
+    }
+
+    /// Returns the fingerprint as a padded base64 string of 44 characters, the format of the
+    /// `pin-sha256` directive of RFC 7469.
+    pub fn to_pin_sha256(&self) -> String {
+        encode_base64(&self.0)
+    }
+
+    /// Parses a fingerprint from the format of the `pin-sha256` directive of RFC 7469, a padded
+    /// base64 string.
+    pub fn from_pin_sha256(pin: &str) -> Result<Self, InvalidInput> {
+        match <[u8; 32]>::try_from(decode_base64(pin)?) {
+            Ok(bytes) => Ok(Self(bytes)),
+            Err(bytes) => Err(InvalidInput::Length {
+                min_length: 32,
+                max_length: 32,
+                actual_length: bytes.len().to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl std::str::FromStr for KeyFingerprint {
+    type Err = InvalidInput;
+
+    /// Parses a hex encoded fingerprint, accepting both upper- and lowercase digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match <[u8; 32]>::try_from(decode_hex(s)?) {
+            Ok(bytes) => Ok(Self(bytes)),
+            Err(bytes) => Err(InvalidInput::Length {
+                min_length: 32,
+                max_length: 32,
+                actual_length: bytes.len().to_string(),
+            }),
+        }
+    }
+}
+
+impl From<KeyFingerprint> for [u8; 32] {
+    fn from(value: KeyFingerprint) -> Self {
+        value.0
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The key derivation function used to derive the encryption key from a password when encrypting a
+/// private key into an encrypted PKCS#8 (PBES2) document. The derived key is always used with
+/// AES-256-CBC.
+pub enum Pbes2Kdf {
+    /// scrypt with a CPU/memory cost of `2^log_n`, a block size of 8 and no parallelization.
+    Scrypt {
+        /// The base 2 logarithm of the CPU/memory cost parameter `N`.
+        log_n: u8,
+    },
+    /// PBKDF2 with HMAC-SHA256 as the pseudorandom function.
+    Pbkdf2 {
+        /// The number of iterations.
+        iterations: u32,
+    },
+}
+
+#[cfg(feature = "pkcs8")]
+impl Default for Pbes2Kdf {
+    /// scrypt with the parameters recommended by the `scrypt` crate, i.e. `log_n = 17`.
+    fn default() -> Self {
+        Self::Scrypt { log_n: 17 }
+    }
+}
+
+/// A [PrivateKey] which can be encoded as, and decoded from, a PKCS#8 `PrivateKeyInfo` document.
+///
+/// Besides plaintext documents, this trait allows writing and reading password-protected PKCS#8
+/// documents (`EncryptedPrivateKeyInfo`, using PBES2 as defined in RFC 8018), so that keys used to
+/// sign [IdCsr](crate::certs::idcsr::IdCsr)s or to issue [IdCert](crate::certs::idcert::IdCert)s,
+/// such as the CA key of a home server, never have to be stored in plaintext.
+#[cfg(feature = "pkcs8")]
+pub trait Pkcs8PrivateKey<S: Signature>: PrivateKey<S> + Sized {
+    /// Encodes this private key as a plaintext PKCS#8 `PrivateKeyInfo` document.
+    fn to_pkcs8_der(&self) -> Result<pkcs8::SecretDocument, ConversionError>;
+    /// Decodes a private key from a plaintext, DER encoded PKCS#8 `PrivateKeyInfo` document.
+    fn from_pkcs8_der(der: &[u8]) -> Result<Self, ConversionError>;
+
+    /// Encrypts this private key with a key derived from `password` using `kdf`, and encodes it
+    /// as a DER encoded PKCS#8 `EncryptedPrivateKeyInfo` document. Salt and IV are drawn from `rng`.
+    fn to_encrypted_pkcs8_der(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        password: impl AsRef<[u8]>,
+        kdf: Pbes2Kdf,
+    ) -> Result<pkcs8::SecretDocument, ConversionError> {
+        log::trace!("[Pkcs8PrivateKey::to_encrypted_pkcs8_der()] encrypting private key");
+        let mut salt = [0u8; 16];
+        rng.fill_bytes(&mut salt);
+        let mut iv = [0u8; 16];
+        rng.fill_bytes(&mut iv);
+        let params = match kdf {
+            Pbes2Kdf::Scrypt { log_n } => {
+                let scrypt_params = match pkcs8::pkcs5::scrypt::Params::new(log_n, 8, 1, 32) {
+                    Ok(params) => params,
+                    Err(_) => {
+                        return Err(InvalidInput::Malformed(format!(
+                            "Invalid scrypt parameter log_n = {}",
+                            log_n
+                        ))
+                        .into())
+                    }
+                };
+                pkcs8::pkcs5::pbes2::Parameters::scrypt_aes256cbc(scrypt_params, &salt, &iv)
+            }
+            Pbes2Kdf::Pbkdf2 { iterations } => {
+                pkcs8::pkcs5::pbes2::Parameters::pbkdf2_sha256_aes256cbc(iterations, &salt, &iv)
+            }
+        }
+        .map_err(pkcs8::Error::from)?;
+        let document = self.to_pkcs8_der()?;
+        let private_key_info = pkcs8::PrivateKeyInfo::try_from(document.as_bytes())?;
+        Ok(private_key_info.encrypt_with_params(params, password)?)
+    }
+
+    /// Like [Self::to_encrypted_pkcs8_der()], but returns the document PEM encoded, with the label
+    /// `ENCRYPTED PRIVATE KEY`.
+    fn to_encrypted_pkcs8_pem(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        password: impl AsRef<[u8]>,
+        kdf: Pbes2Kdf,
+    ) -> Result<String, ConversionError> {
+        use pkcs8::der::pem::PemLabel;
+        let document = self.to_encrypted_pkcs8_der(rng, password, kdf)?;
+        Ok(document
+            .to_pem(
+                pkcs8::EncryptedPrivateKeyInfo::PEM_LABEL,
+                pkcs8::LineEnding::LF,
+            )?
+            .to_string())
+    }
+
+    /// Decrypts and decodes a private key from a DER encoded PKCS#8 `EncryptedPrivateKeyInfo`
+    /// document. Fails, if the document is malformed or the password is wrong.
+    fn from_encrypted_pkcs8_der(
+        der: &[u8],
+        password: impl AsRef<[u8]>,
+    ) -> Result<Self, ConversionError> {
+        log::trace!("[Pkcs8PrivateKey::from_encrypted_pkcs8_der()] decrypting private key");
+        let encrypted = pkcs8::EncryptedPrivateKeyInfo::try_from(der)?;
+        let document = encrypted.decrypt(password)?;
+        Self::from_pkcs8_der(document.as_bytes())
+    }
+
+    /// Decrypts and decodes a private key from a PEM encoded PKCS#8 `EncryptedPrivateKeyInfo`
+    /// document. Fails, if the PEM label is not `ENCRYPTED PRIVATE KEY`, if the document is
+    /// malformed or if the password is wrong.
+    fn from_encrypted_pkcs8_pem(
+        pem: &str,
+        password: impl AsRef<[u8]>,
+    ) -> Result<Self, ConversionError> {
+        use pkcs8::der::pem::PemLabel;
+        let (label, document) = pkcs8::SecretDocument::from_pem(pem)?;
+        pkcs8::EncryptedPrivateKeyInfo::validate_pem_label(label).map_err(der::Error::from)?;
+        Self::from_encrypted_pkcs8_der(document.as_bytes(), password)
+    }
 }
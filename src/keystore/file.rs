@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use der::zeroize::Zeroizing;
+
+use crate::errors::KeyStoreError;
+
+use super::{KeyId, KeyStore};
+
+/// The file extension of the files holding stored keys.
+pub const KEY_FILE_EXTENSION: &str = "der";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A [KeyStore] storing each key in its own file in a directory. The file of a key is named after
+/// its [KeyId], e.g. `flori@polyphony.chat.08.der`. Files not following this scheme are ignored.
+///
+/// Writes are atomic: documents are written to a temporary file, which is then renamed. On Unix,
+/// the directory is created with mode `0700` and key files are created with mode `0600`. The key
+/// files are not encrypted.
+pub struct FileKeyStore {
+    directory: PathBuf,
+}
+
+impl FileKeyStore {
+    /// Opens the key store in `directory`, creating the directory if it does not exist.
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self, KeyStoreError> {
+        let directory = directory.into();
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&directory)?;
+        log::trace!(
+            "[FileKeyStore::open()] Opened key store in {}",
+            directory.display()
+        );
+        Ok(Self { directory })
+    }
+
+    /// Returns the directory of this key store.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the path of the file holding the key stored under `id`.
+    pub fn path_of(&self, id: &KeyId) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", id, KEY_FILE_EXTENSION))
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn get(&self, id: &KeyId) -> Result<Option<Zeroizing<Vec<u8>>>, KeyStoreError> {
+        match fs::read(self.path_of(id)) {
+            Ok(document) => Ok(Some(Zeroizing::new(document))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&mut self, id: &KeyId, document: &[u8]) -> Result<(), KeyStoreError> {
+        log::trace!("[FileKeyStore::put()] Storing key {}", id);
+        let path = self.path_of(id);
+        let temporary = path.with_extension(format!("{}.tmp", KEY_FILE_EXTENSION));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let result = options
+            .open(&temporary)
+            .and_then(|mut file| {
+                file.write_all(document)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temporary, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        Ok(result?)
+    }
+
+    fn delete(&mut self, id: &KeyId) -> Result<bool, KeyStoreError> {
+        match fs::remove_file(self.path_of(id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<KeyId>, KeyStoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(KEY_FILE_EXTENSION)
+            {
+                continue;
+            }
+            match path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(KeyId::from_str)
+            {
+                Some(Ok(id)) => ids.push(id),
+                _ => log::debug!(
+                    "[FileKeyStore::list()] Ignoring unrelated file {}",
+                    path.display()
+                ),
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
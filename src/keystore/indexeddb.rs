@@ -0,0 +1,326 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::zeroize::Zeroizing;
+use js_sys::{Array, Function, Promise, Reflect, Uint8Array};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    IdbDatabase, IdbFactory, IdbObjectStore, IdbRequest, IdbTransaction, IdbTransactionMode,
+};
+
+use crate::errors::KeyStoreError;
+
+use super::KeyId;
+#[cfg(target_arch = "wasm32")]
+use super::{AsyncKeyStore, KeyStoreFuture};
+
+/// The default name of the IndexedDB database keys and certificates are stored in.
+pub const DEFAULT_DATABASE: &str = "polyproto";
+/// The version of the database schema.
+const DATABASE_VERSION: u32 = 1;
+/// The name of the object store holding private keys.
+const KEYS: &str = "keys";
+/// The name of the object store holding certificates.
+const CERTIFICATES: &str = "certificates";
+
+#[derive(Debug, Clone)]
+/// An [AsyncKeyStore](super::AsyncKeyStore) storing keys in the IndexedDB of a browser or web
+/// worker, so that browser clients keep their identities across page reloads.
+///
+/// The database holds two object stores, both keyed by the string representation of a [KeyId]:
+/// one for private keys, and one for the DER encoded
+/// [IdCert](crate::certs::idcert::IdCert)s certifying them. Documents are stored as
+/// `Uint8Array`s.
+///
+/// IndexedDB is readable by any script running on the same origin, so the documents should be
+/// encrypted PKCS#8 documents, created by
+/// [Pkcs8PrivateKey::to_encrypted_pkcs8_der()](crate::key::Pkcs8PrivateKey::to_encrypted_pkcs8_der)
+/// with a password only the user knows. Keys held by WebCrypto do not need to be stored here:
+/// the non-extractable `CryptoKey`s of a
+/// [WebCryptoSigner](crate::backends::webcrypto::WebCryptoSigner) can be persisted directly.
+///
+/// IndexedDB operations are asynchronous, so [IndexedDbKeyStore] implements
+/// [AsyncKeyStore](super::AsyncKeyStore) on `wasm32` targets, but not
+/// [KeyStore](super::KeyStore). All methods fail with [KeyStoreError::Backend], if IndexedDB is not
+/// available or rejects the operation.
+pub struct IndexedDbKeyStore {
+    database: IdbDatabase,
+}
+
+impl IndexedDbKeyStore {
+    /// Opens the key store in the database named `name`, creating the database if it does not
+    /// exist.
+    pub async fn open(name: &str) -> Result<Self, KeyStoreError> {
+        log::trace!("[IndexedDbKeyStore::open()] Opening database {}", name);
+        let request = indexed_db()?
+            .open_with_u32(name, DATABASE_VERSION)
+            .map_err(indexeddb_error)?;
+        let upgrade_request = request.clone();
+        let on_upgrade_needed = Closure::once(move || {
+            // Creating the object stores can only fail, if they already exist.
+            if let Ok(database) = upgrade_request.result() {
+                let database: IdbDatabase = database.unchecked_into();
+                let names = database.object_store_names();
+                for store in [KEYS, CERTIFICATES] {
+                    if !names.contains(store) {
+                        let _ = database.create_object_store(store);
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade_needed.as_ref().unchecked_ref()));
+        let result = request_result(&request).await;
+        request.set_onupgradeneeded(None);
+        Ok(Self {
+            database: result?.unchecked_into(),
+        })
+    }
+
+    /// Returns the name of the database of this key store.
+    pub fn name(&self) -> String {
+        self.database.name()
+    }
+
+    /// Closes the connection to the database. All further operations on this key store fail.
+    pub fn close(&self) {
+        self.database.close()
+    }
+
+    /// Returns the document stored under `id`, or `None`, if there is none.
+    pub async fn get_key(&self, id: &KeyId) -> Result<Option<Zeroizing<Vec<u8>>>, KeyStoreError> {
+        Ok(self.read(KEYS, id).await?.map(Zeroizing::new))
+    }
+
+    /// Stores `document` under `id`, replacing any document previously stored under `id`.
+    pub async fn put_key(&self, id: &KeyId, document: &[u8]) -> Result<(), KeyStoreError> {
+        log::trace!("[IndexedDbKeyStore::put_key()] Storing key {}", id);
+        self.write(KEYS, id, document).await
+    }
+
+    /// Deletes the document stored under `id`. Returns `false`, if there was none.
+    pub async fn delete_key(&self, id: &KeyId) -> Result<bool, KeyStoreError> {
+        self.remove(KEYS, id).await
+    }
+
+    /// Returns the [KeyId]s of all stored keys, in ascending order.
+    pub async fn list_keys(&self) -> Result<Vec<KeyId>, KeyStoreError> {
+        self.ids(KEYS).await
+    }
+
+    /// Returns the DER encoded certificate stored under `id`, or `None`, if there is none.
+    pub async fn get_certificate(&self, id: &KeyId) -> Result<Option<Vec<u8>>, KeyStoreError> {
+        self.read(CERTIFICATES, id).await
+    }
+
+    /// Stores the DER encoded certificate `der` under `id`, replacing any certificate previously
+    /// stored under `id`.
+    pub async fn put_certificate(&self, id: &KeyId, der: &[u8]) -> Result<(), KeyStoreError> {
+        log::trace!(
+            "[IndexedDbKeyStore::put_certificate()] Storing certificate {}",
+            id
+        );
+        self.write(CERTIFICATES, id, der).await
+    }
+
+    /// Deletes the certificate stored under `id`. Returns `false`, if there was none.
+    pub async fn delete_certificate(&self, id: &KeyId) -> Result<bool, KeyStoreError> {
+        self.remove(CERTIFICATES, id).await
+    }
+
+    /// Returns the [KeyId]s of all stored certificates, in ascending order.
+    pub async fn list_certificates(&self) -> Result<Vec<KeyId>, KeyStoreError> {
+        self.ids(CERTIFICATES).await
+    }
+
+    fn store(
+        &self,
+        name: &str,
+        mode: IdbTransactionMode,
+    ) -> Result<(IdbTransaction, IdbObjectStore), KeyStoreError> {
+        let transaction = self
+            .database
+            .transaction_with_str_and_mode(name, mode)
+            .map_err(indexeddb_error)?;
+        let store = transaction.object_store(name).map_err(indexeddb_error)?;
+        Ok((transaction, store))
+    }
+
+    async fn read(&self, name: &str, id: &KeyId) -> Result<Option<Vec<u8>>, KeyStoreError> {
+        let (_, store) = self.store(name, IdbTransactionMode::Readonly)?;
+        let request = store
+            .get(&JsValue::from_str(&id.to_string()))
+            .map_err(indexeddb_error)?;
+        let value = request_result(&request).await?;
+        if value.is_undefined() {
+            return Ok(None);
+        }
+        match value.dyn_into::<Uint8Array>() {
+            Ok(array) => Ok(Some(array.to_vec())),
+            Err(_) => Err(KeyStoreError::Backend(format!(
+                "The value stored under {} is not a byte array",
+                id
+            ))),
+        }
+    }
+
+    async fn write(&self, name: &str, id: &KeyId, document: &[u8]) -> Result<(), KeyStoreError> {
+        let (transaction, store) = self.store(name, IdbTransactionMode::Readwrite)?;
+        store
+            .put_with_key(
+                &Uint8Array::from(document),
+                &JsValue::from_str(&id.to_string()),
+            )
+            .map_err(indexeddb_error)?;
+        transaction_complete(&transaction).await
+    }
+
+    async fn remove(&self, name: &str, id: &KeyId) -> Result<bool, KeyStoreError> {
+        let (transaction, store) = self.store(name, IdbTransactionMode::Readwrite)?;
+        let key = JsValue::from_str(&id.to_string());
+        // Deleting a missing key succeeds, so count first to be able to report it.
+        let count = store.count_with_key(&key).map_err(indexeddb_error)?;
+        let existed = request_result(&count).await?.as_f64().unwrap_or(0.0) > 0.0;
+        store.delete(&key).map_err(indexeddb_error)?;
+        transaction_complete(&transaction).await?;
+        Ok(existed)
+    }
+
+    async fn ids(&self, name: &str) -> Result<Vec<KeyId>, KeyStoreError> {
+        let (_, store) = self.store(name, IdbTransactionMode::Readonly)?;
+        let request = store.get_all_keys().map_err(indexeddb_error)?;
+        let keys: Array = request_result(&request).await?.unchecked_into();
+        let mut ids = Vec::new();
+        for key in keys.iter() {
+            match key.as_string().map(|key| KeyId::from_str(&key)) {
+                Some(Ok(id)) => ids.push(id),
+                _ => log::debug!(
+                    "[IndexedDbKeyStore::ids()] Ignoring unrelated key {:?}",
+                    key
+                ),
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AsyncKeyStore for IndexedDbKeyStore {
+    fn get<'a>(&'a self, id: &'a KeyId) -> KeyStoreFuture<'a, Option<Zeroizing<Vec<u8>>>> {
+        Box::pin(self.get_key(id))
+    }
+
+    fn put<'a>(&'a mut self, id: &'a KeyId, document: &'a [u8]) -> KeyStoreFuture<'a, ()> {
+        Box::pin(self.put_key(id, document))
+    }
+
+    fn delete<'a>(&'a mut self, id: &'a KeyId) -> KeyStoreFuture<'a, bool> {
+        Box::pin(self.delete_key(id))
+    }
+
+    fn list(&self) -> KeyStoreFuture<'_, Vec<KeyId>> {
+        Box::pin(self.list_keys())
+    }
+}
+
+/// Returns the `IDBFactory` of the global scope, which may be a window or a worker.
+fn indexed_db() -> Result<IdbFactory, KeyStoreError> {
+    let factory = Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))
+        .map_err(indexeddb_error)?;
+    if factory.is_undefined() || factory.is_null() {
+        return Err(KeyStoreError::Backend(
+            "IndexedDB is not available in this environment".to_string(),
+        ));
+    }
+    Ok(factory.unchecked_into())
+}
+
+/// Waits for `request` to succeed, and returns its result.
+async fn request_result(request: &IdbRequest) -> Result<JsValue, KeyStoreError> {
+    let mut handlers = None;
+    let promise = Promise::new(&mut |resolve: Function, reject: Function| {
+        let success = request.clone();
+        let on_success = Closure::once(move || {
+            let _ = resolve.call1(&JsValue::UNDEFINED, &success.result().unwrap_or_default());
+        });
+        let failure = request.clone();
+        let on_error = Closure::once(move || {
+            let error = failure.error().ok().flatten().map(JsValue::from);
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.unwrap_or_default());
+        });
+        handlers = Some((on_success, on_error));
+    });
+    if let Some((on_success, on_error)) = &handlers {
+        request.set_onsuccess(Some(on_success.as_ref().unchecked_ref()));
+        request.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    }
+    let result = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    result.map_err(indexeddb_error)
+}
+
+/// Waits for `transaction` to be committed.
+async fn transaction_complete(transaction: &IdbTransaction) -> Result<(), KeyStoreError> {
+    let mut handlers = None;
+    let promise = Promise::new(&mut |resolve: Function, reject: Function| {
+        let on_complete = Closure::once(move || {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        });
+        let failure = transaction.clone();
+        let on_error = Closure::once(move || {
+            let error = failure.error().map(JsValue::from);
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.unwrap_or_default());
+        });
+        handlers = Some((on_complete, on_error));
+    });
+    if let Some((on_complete, on_error)) = &handlers {
+        transaction.set_oncomplete(Some(on_complete.as_ref().unchecked_ref()));
+        transaction.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        transaction.set_onabort(Some(on_error.as_ref().unchecked_ref()));
+    }
+    let result = JsFuture::from(promise).await;
+    transaction.set_oncomplete(None);
+    transaction.set_onerror(None);
+    transaction.set_onabort(None);
+    result.map(|_| ()).map_err(indexeddb_error)
+}
+
+fn indexeddb_error(error: JsValue) -> KeyStoreError {
+    log::debug!(
+        "[IndexedDbKeyStore] IndexedDB operation failed: {:?}",
+        error
+    );
+    KeyStoreError::Backend(format!("IndexedDB operation failed: {:?}", error))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use std::str::FromStr;
+
+    use super::IndexedDbKeyStore;
+    use crate::keystore::{AsyncKeyStore, KeyId};
+
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    async fn put_get_delete() {
+        let mut store = IndexedDbKeyStore::open("polyproto-test").await.unwrap();
+        let id = KeyId::from_str("flori@polyphony.chat.08").unwrap();
+        store.put(&id, b"key").await.unwrap();
+        store.put_certificate(&id, b"certificate").await.unwrap();
+        assert_eq!(store.get(&id).await.unwrap().unwrap().as_slice(), b"key");
+        assert_eq!(
+            store.get_certificate(&id).await.unwrap().unwrap(),
+            b"certificate"
+        );
+        assert_eq!(store.list().await.unwrap(), vec![id.clone()]);
+        assert!(store.delete(&id).await.unwrap());
+        assert!(!store.delete(&id).await.unwrap());
+        assert!(store.get(&id).await.unwrap().is_none());
+        assert!(store.delete_certificate(&id).await.unwrap());
+    }
+}
@@ -0,0 +1,145 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::zeroize::Zeroizing;
+use keyring::Entry;
+
+use crate::encoding::{decode_hex, encode_hex};
+use crate::errors::{ConversionError, KeyStoreError};
+
+use super::{KeyId, KeyStore};
+
+/// The default service name under which keys are stored.
+pub const DEFAULT_SERVICE: &str = "polyproto";
+/// The account name of the entry listing the [KeyId]s of all stored keys. Can not collide with a
+/// [KeyId], since it does not contain an `@`.
+const INDEX_ACCOUNT: &str = "polyproto-keystore-index";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A [KeyStore] storing keys in the keychain of the operating system. Which keychain is used
+/// depends on the enabled features:
+///
+/// - `keychain-macos`: the macOS Keychain
+/// - `keychain-windows`: the Windows Credential Manager, which protects stored credentials using
+///   DPAPI
+/// - `keychain-secret-service`: the Secret Service of the desktop environment, such as GNOME
+///   Keyring or KWallet, on Linux and the BSDs
+///
+/// Without any of these features, or on other platforms, keys are only kept in memory by the mock
+/// keychain of the `keyring` crate, which forgets them immediately.
+///
+/// Each key is stored as a hex encoded generic password, with the service name of the store and
+/// the [KeyId] as account name. Keychains can not be enumerated portably, so the [KeyId]s of all
+/// stored keys are additionally kept in an index entry.
+pub struct KeychainKeyStore {
+    service: String,
+}
+
+impl Default for KeychainKeyStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_SERVICE)
+    }
+}
+
+impl KeychainKeyStore {
+    /// Creates a key store using the service name `service`. Applications should use their own
+    /// service name, so that their keys are kept apart from those of other applications.
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+        }
+    }
+
+    /// Returns the service name of this key store.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    fn entry(&self, account: &str) -> Result<Entry, KeyStoreError> {
+        Entry::new(&self.service, account).map_err(keychain_error)
+    }
+
+    fn read(&self, account: &str) -> Result<Option<Zeroizing<String>>, KeyStoreError> {
+        match self.entry(account)?.get_password() {
+            Ok(password) => Ok(Some(Zeroizing::new(password))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keychain_error(e)),
+        }
+    }
+
+    fn write_index(&self, ids: &[KeyId]) -> Result<(), KeyStoreError> {
+        let entry = self.entry(INDEX_ACCOUNT)?;
+        if ids.is_empty() {
+            return match entry.delete_password() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(keychain_error(e)),
+            };
+        }
+        let index = ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        entry.set_password(&index).map_err(keychain_error)
+    }
+}
+
+impl KeyStore for KeychainKeyStore {
+    fn get(&self, id: &KeyId) -> Result<Option<Zeroizing<Vec<u8>>>, KeyStoreError> {
+        match self.read(&id.to_string())? {
+            Some(password) => Ok(Some(Zeroizing::new(
+                decode_hex(&password).map_err(ConversionError::from)?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, id: &KeyId, document: &[u8]) -> Result<(), KeyStoreError> {
+        log::trace!("[KeychainKeyStore::put()] Storing key {}", id);
+        let password = Zeroizing::new(encode_hex(document));
+        self.entry(&id.to_string())?
+            .set_password(&password)
+            .map_err(keychain_error)?;
+        let mut ids = self.list()?;
+        if let Err(index) = ids.binary_search(id) {
+            ids.insert(index, id.clone());
+            self.write_index(&ids)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &KeyId) -> Result<bool, KeyStoreError> {
+        let deleted = match self.entry(&id.to_string())?.delete_password() {
+            Ok(()) => true,
+            Err(keyring::Error::NoEntry) => false,
+            Err(e) => return Err(keychain_error(e)),
+        };
+        let mut ids = self.list()?;
+        if let Ok(index) = ids.binary_search(id) {
+            ids.remove(index);
+            self.write_index(&ids)?;
+        }
+        Ok(deleted)
+    }
+
+    fn list(&self) -> Result<Vec<KeyId>, KeyStoreError> {
+        let index = match self.read(INDEX_ACCOUNT)? {
+            Some(index) => index,
+            None => return Ok(Vec::new()),
+        };
+        let mut ids = Vec::new();
+        for line in index.lines().filter(|line| !line.is_empty()) {
+            ids.push(KeyId::from_str(line)?);
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+fn keychain_error(error: keyring::Error) -> KeyStoreError {
+    log::debug!("[KeychainKeyStore] Keychain operation failed: {}", error);
+    KeyStoreError::Backend(error.to_string())
+}
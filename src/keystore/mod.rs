@@ -0,0 +1,162 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use der::zeroize::Zeroizing;
+
+use crate::encoding::{decode_hex, encode_hex};
+use crate::errors::{ConversionError, InvalidInput, KeyStoreError};
+use crate::types::FederationId;
+
+/// [FileKeyStore](file::FileKeyStore), storing each key in its own file in a directory.
+pub mod file;
+#[cfg(feature = "indexeddb")]
+/// [IndexedDbKeyStore](indexeddb::IndexedDbKeyStore), storing keys and certificates in the
+/// IndexedDB of a browser.
+pub mod indexeddb;
+#[cfg(feature = "keychain")]
+/// [KeychainKeyStore](keychain::KeychainKeyStore), storing keys in the keychain of the operating
+/// system.
+pub mod keychain;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Identifies a private key in a [KeyStore] by the [FederationId] of the actor or home server it
+/// belongs to, and the serial number of the certificate it was certified with. An actor usually
+/// holds one key per session, and each of these keys is certified by its own certificate.
+///
+/// A [KeyId] has a string representation of the form
+/// `<federation ID>.<hex encoded serial number>`, which is produced by its
+/// [Display](std::fmt::Display) implementation and parsed by its [FromStr] implementation.
+pub struct KeyId {
+    /// The [FederationId] of the owner of the key.
+    pub federation_id: FederationId,
+    /// The serial number of the certificate of the key.
+    pub serial: Uint,
+}
+
+impl KeyId {
+    /// Creates a new [KeyId].
+    pub fn new(federation_id: FederationId, serial: Uint) -> Self {
+        Self {
+            federation_id,
+            serial,
+        }
+    }
+}
+
+impl std::fmt::Display for KeyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}",
+            self.federation_id,
+            encode_hex(self.serial.as_bytes())
+        )
+    }
+}
+
+impl FromStr for KeyId {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The domain of a federation ID may contain dots, but the hex encoded serial can not.
+        let (federation_id, serial) = match s.rsplit_once('.') {
+            Some(parts) => parts,
+            None => {
+                return Err(
+                    InvalidInput::Malformed(format!("Invalid key identifier: {}", s)).into(),
+                )
+            }
+        };
+        Ok(Self {
+            federation_id: FederationId::new(federation_id)?,
+            serial: Uint::new(&decode_hex(serial)?)?,
+        })
+    }
+}
+
+/// Persistent storage for private keys, addressed by [KeyId].
+///
+/// Keys are stored as DER encoded PKCS#8 `PrivateKeyInfo` documents, so that any
+/// [Pkcs8PrivateKey](crate::key::Pkcs8PrivateKey) can be stored. With the `pkcs8` feature,
+/// [KeyStore::get_key()] and [KeyStore::put_key()] convert between documents and keys.
+///
+/// Whether the documents are protected at rest depends on the implementation: the
+/// [KeychainKeyStore](keychain::KeychainKeyStore) relies on the keychain of the operating system,
+/// while the [FileKeyStore](file::FileKeyStore) only restricts the file permissions. Store
+/// encrypted PKCS#8 documents in a [FileKeyStore](file::FileKeyStore), if the key material must
+/// not be readable by anyone with access to the files.
+pub trait KeyStore {
+    /// Returns the document stored under `id`, or `None`, if there is none.
+    fn get(&self, id: &KeyId) -> Result<Option<Zeroizing<Vec<u8>>>, KeyStoreError>;
+    /// Stores `document` under `id`, replacing any document previously stored under `id`.
+    fn put(&mut self, id: &KeyId, document: &[u8]) -> Result<(), KeyStoreError>;
+    /// Deletes the document stored under `id`. Returns `false`, if there was none.
+    fn delete(&mut self, id: &KeyId) -> Result<bool, KeyStoreError>;
+    /// Returns the [KeyId]s of all stored documents, in ascending order.
+    fn list(&self) -> Result<Vec<KeyId>, KeyStoreError>;
+
+    /// Returns the [KeyId]s of all stored documents belonging to `federation_id`, in ascending
+    /// order of their serial numbers.
+    fn list_for(&self, federation_id: &FederationId) -> Result<Vec<KeyId>, KeyStoreError> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|id| &id.federation_id == federation_id)
+            .collect())
+    }
+
+    /// Returns the private key stored under `id`, decoded from its PKCS#8 document, or `None`, if
+    /// there is none.
+    #[cfg(feature = "pkcs8")]
+    fn get_key<S, K>(&self, id: &KeyId) -> Result<Option<K>, KeyStoreError>
+    where
+        Self: Sized,
+        S: crate::signature::Signature,
+        K: crate::key::Pkcs8PrivateKey<S>,
+    {
+        match self.get(id)? {
+            Some(document) => Ok(Some(K::from_pkcs8_der(&document)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores `key` under `id` as a plaintext PKCS#8 document.
+    #[cfg(feature = "pkcs8")]
+    fn put_key<S, K>(&mut self, id: &KeyId, key: &K) -> Result<(), KeyStoreError>
+    where
+        Self: Sized,
+        S: crate::signature::Signature,
+        K: crate::key::Pkcs8PrivateKey<S>,
+    {
+        self.put(id, key.to_pkcs8_der()?.as_bytes())
+    }
+}
+
+/// The future returned by the methods of [AsyncKeyStore].
+#[cfg(not(target_arch = "wasm32"))]
+pub type KeyStoreFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, KeyStoreError>> + Send + 'a>>;
+/// The future returned by the methods of [AsyncKeyStore]. On `wasm32`, the future is not required
+/// to be [Send], since futures wrapping JavaScript promises, such as those of IndexedDB, are bound
+/// to the thread they were created on.
+#[cfg(target_arch = "wasm32")]
+pub type KeyStoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, KeyStoreError>> + 'a>>;
+
+/// Like [KeyStore], but for storage which can only be accessed asynchronously, such as the
+/// IndexedDB of a browser.
+pub trait AsyncKeyStore {
+    /// Returns the document stored under `id`, or `None`, if there is none.
+    fn get<'a>(&'a self, id: &'a KeyId) -> KeyStoreFuture<'a, Option<Zeroizing<Vec<u8>>>>;
+    /// Stores `document` under `id`, replacing any document previously stored under `id`.
+    fn put<'a>(&'a mut self, id: &'a KeyId, document: &'a [u8]) -> KeyStoreFuture<'a, ()>;
+    /// Deletes the document stored under `id`. Returns `false`, if there was none.
+    fn delete<'a>(&'a mut self, id: &'a KeyId) -> KeyStoreFuture<'a, bool>;
+    /// Returns the [KeyId]s of all stored documents, in ascending order.
+    fn list(&self) -> KeyStoreFuture<'_, Vec<KeyId>>;
+}
@@ -67,6 +67,18 @@ will have to use the `wasm` feature:
 polyproto = { version = "0", features = ["wasm"] }
 ```
 
+## FIPS
+
+The `fips` feature swaps in the FIPS validated build of aws-lc, exposed as the
+`backends::aws_lc` backends, and disables the backends which are not FIPS validated at compile
+time. Building with this feature requires CMake and Go. `backends::compatibility_matrix()` reports
+which algorithms are available in a build, and whether they are FIPS validated.
+
+```toml
+[dependencies]
+polyproto = { version = "0", features = ["fips"] }
+```
+
 ## HTTP API client through `reqwest`
 
 If the `reqwest` feature is activated, this crate offers a polyproto HTTP API client, using the
@@ -89,7 +101,8 @@ request routes and methods through the exported `static` `Route`s.
 [Matrix-invite]: https://matrix.to/#/#polyproto:tu-dresden.de
 */
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "alloc-stats"), forbid(unsafe_code))]
+#![cfg_attr(feature = "alloc-stats", deny(unsafe_code))]
 #![warn(
     missing_docs,
     missing_debug_implementations,
@@ -104,28 +117,98 @@ pub const OID_RDN_COMMON_NAME: &str = "2.5.4.3";
 pub const OID_RDN_UNIQUE_IDENTIFIER: &str = "0.9.2342.19200300.100.1.44";
 /// The OID for the `uid` RDN
 pub const OID_RDN_UID: &str = "0.9.2342.19200300.100.1.1";
+/// The OID for the `organizationalUnit` RDN
+pub const OID_RDN_ORGANIZATIONAL_UNIT: &str = "2.5.4.11";
 
 use certs::Target;
 use errors::base::ConstraintError;
 
+#[cfg(feature = "alloc-stats")]
+/// An allocation counting [GlobalAlloc](std::alloc::GlobalAlloc) wrapper and the
+/// [AllocStats](alloc_stats::AllocStats) it records, for keeping track of the allocations made
+/// by parsing and verification.
+pub mod alloc_stats;
 #[cfg(feature = "reqwest")]
 /// Ready-to-use API routes, implemented using `reqwest`
 pub mod api;
+#[cfg(feature = "proptest")]
+/// Strategies and [Arbitrary](proptest::arbitrary::Arbitrary) implementations generating valid
+/// certificates, CSRs and their parts for property tests, together with a cheap dummy signature
+/// backend.
+pub mod arbitrary;
+/// Ready-made implementations of the signature and key traits for common algorithms, each behind
+/// its own feature flag.
+pub mod backends;
+/// [GenerationalCache](cache::GenerationalCache), whose entries are invalidated automatically when
+/// the profile, algorithm policy or trust store they were computed under changes.
+pub mod cache;
 /// Generic polyproto certificate types and traits.
 pub mod certs;
+#[cfg(feature = "types")]
+/// The [CertStore](certstore::CertStore) trait for persisting ID-Certs, with in-memory and file
+/// system implementations.
+pub mod certstore;
 /// Error types used in this crate
 pub mod errors;
+#[cfg(all(feature = "types", feature = "serde"))]
+/// Verification of signed gateway events, such as identity-change broadcasts, against cached
+/// actor and home server certificates.
+pub mod gateway;
+#[cfg(feature = "test-utils")]
+/// [OpenSsl](interop::OpenSsl), running the `openssl` command line tool to check that certificates
+/// produced by this crate parse and verify in OpenSSL, and to produce certificates using OpenSSL
+/// for parsing and verifying them using this crate.
+pub mod interop;
 /// Generic polyproto public- and private key traits.
 pub mod key;
+#[cfg(feature = "types")]
+/// The [KeyStore](keystore::KeyStore) trait for persisting private keys, with a file system
+/// implementation and, behind the `keychain` features, implementations using the keychain of the
+/// operating system.
+pub mod keystore;
+/// [AlgorithmRegistry](registry::AlgorithmRegistry), mapping the OIDs of signature algorithms to
+/// verification functions, for verifying certificates whose algorithm is only known at runtime.
+pub mod registry;
+/// A table-driven engine for polyproto constraints: each constraint is a [Rule](rules::Rule) with
+/// an ID, the targets it applies to, a check and an error code, collected in a
+/// [RuleSet](rules::RuleSet) which powers [Constrained::validate()] and produces
+/// [ValidationReport](rules::ValidationReport)s and rule listings.
+pub mod rules;
+/// [self_test()], running known-answer tests for the cryptographic backends compiled into this
+/// build.
+pub mod selftest;
 /// Generic polyproto signature traits.
 pub mod signature;
+#[cfg(feature = "test-utils")]
+/// Frozen known-answer [TestVector](test_vectors::TestVector)s of valid and invalid actor and home
+/// server certificates, together with the outcome of verifying them, for checking other polyproto
+/// implementations for conformance with this crate.
+pub mod test_vectors;
+/// The [Timestamp](timestamp::Timestamp) type, used to represent points in time in UTC.
+pub mod timestamp;
 #[cfg(feature = "types")]
 /// Types used in polyproto and the polyproto HTTP/REST APIs
 pub mod types;
+#[cfg(feature = "pkcs8")]
+/// [IdentityVault](vault::IdentityVault), holding the certificates and private keys of an identity,
+/// and import paths for identity backups, such as PEM bundles encrypted using age and PKCS#12
+/// files.
+pub mod vault;
+/// The [Verifier](verifier::Verifier) facade, which enforces a
+/// [VerificationBudget](verifier::VerificationBudget) on expensive verification operations and
+/// reports non-fatal [ValidationWarning](verifier::ValidationWarning)s.
+pub mod verifier;
+#[cfg(feature = "webhooks")]
+/// Signed webhook delivery and verification, for forwarding certificate issuance and revocation
+/// events to external systems
+pub mod webhooks;
 
 mod constraints;
+mod encoding;
 
 pub use der;
+pub use rand_core;
+pub use selftest::self_test;
 pub use spki;
 pub use x509_cert::name::*;
 
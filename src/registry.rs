@@ -0,0 +1,208 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use der::{Decode, Encode};
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier};
+use x509_cert::Certificate;
+
+use crate::certs::PublicKeyInfo;
+use crate::errors::{ConversionError, InvalidCert, PublicKeyError};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+
+/// Verifies a raw signature over `data`, using the public key described by a [PublicKeyInfo].
+/// Returns [PublicKeyError::BadPublicKeyInfo], if the [PublicKeyInfo] does not describe a key of
+/// the algorithm, and [PublicKeyError::BadSignature], if the signature does not match the data.
+pub type VerifyFn = fn(
+    public_key_info: &PublicKeyInfo,
+    signature: &[u8],
+    data: &[u8],
+) -> Result<(), PublicKeyError>;
+
+#[derive(Debug, Clone, Copy)]
+/// A signature algorithm in an [AlgorithmRegistry].
+pub struct RegisteredAlgorithm {
+    /// The human-readable name of the algorithm, such as `Ed25519`.
+    pub name: &'static str,
+    /// The function verifying signatures of the algorithm.
+    pub verify: VerifyFn,
+}
+
+#[derive(Debug, Clone, Default)]
+/// A registry of signature algorithms, keyed by the OID of their [AlgorithmIdentifierOwned], for
+/// verifying signatures and certificates of algorithms which are only known at runtime.
+///
+/// The [Signature] and [PublicKey] traits require the algorithm of a certificate to be fixed at
+/// compile time. Tools which must handle whatever algorithm a peer uses, such as certificate
+/// inspectors or servers federating with unknown peers, can instead look up the algorithm of a
+/// certificate in a registry. [AlgorithmRegistry::with_builtin_algorithms()] contains the
+/// algorithms of the [backends](crate::backends) compiled into this build; other algorithms, such
+/// as composite signatures of a specific pair of algorithms, can be added using
+/// [AlgorithmRegistry::with_algorithm()] or [AlgorithmRegistry::register()].
+pub struct AlgorithmRegistry {
+    algorithms: BTreeMap<ObjectIdentifier, RegisteredAlgorithm>,
+}
+
+impl AlgorithmRegistry {
+    /// Creates a new, empty [AlgorithmRegistry].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an [AlgorithmRegistry] containing the signature algorithms of the
+    /// [backends](crate::backends) compiled into this build, as listed by
+    /// [compatibility_matrix()](crate::backends::compatibility_matrix), except for composite
+    /// signatures. Where multiple backends provide the same algorithm, the RustCrypto backend is
+    /// preferred.
+    pub fn with_builtin_algorithms() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = Self::new();
+        #[cfg(any(feature = "aws-lc", feature = "fips"))]
+        {
+            use crate::backends::aws_lc::{ed25519, p256};
+            registry = registry
+                .with_algorithm::<ed25519::Ed25519Signature, ed25519::Ed25519PublicKey>("Ed25519")
+                .with_algorithm::<p256::P256Signature, p256::P256PublicKey>(
+                    "ECDSA P-256 with SHA-256",
+                );
+        }
+        #[cfg(all(feature = "ed25519", not(feature = "fips")))]
+        {
+            use crate::backends::ed25519;
+            registry = registry
+                .with_algorithm::<ed25519::Ed25519Signature, ed25519::Ed25519PublicKey>("Ed25519");
+        }
+        #[cfg(all(feature = "p256", not(feature = "fips")))]
+        {
+            use crate::backends::p256;
+            registry = registry.with_algorithm::<p256::P256Signature, p256::P256PublicKey>(
+                "ECDSA P-256 with SHA-256",
+            );
+        }
+        registry
+    }
+
+    /// Registers the signature algorithm `S` with its public key type `P` under the OID of
+    /// [Signature::algorithm_identifier()], replacing any algorithm registered under the same OID.
+    pub fn with_algorithm<S: Signature, P: PublicKey<S>>(mut self, name: &'static str) -> Self {
+        self.register(
+            S::algorithm_identifier().oid,
+            RegisteredAlgorithm {
+                name,
+                verify: verify_with::<S, P>,
+            },
+        );
+        self
+    }
+
+    /// Registers an algorithm under the given OID, returning the algorithm previously registered
+    /// under this OID, if any.
+    pub fn register(
+        &mut self,
+        oid: ObjectIdentifier,
+        algorithm: RegisteredAlgorithm,
+    ) -> Option<RegisteredAlgorithm> {
+        log::trace!(
+            "[AlgorithmRegistry::register()] Registering {} as {}",
+            oid,
+            algorithm.name
+        );
+        self.algorithms.insert(oid, algorithm)
+    }
+
+    /// Removes the algorithm registered under the given OID, returning it, if any.
+    pub fn unregister(&mut self, oid: &ObjectIdentifier) -> Option<RegisteredAlgorithm> {
+        self.algorithms.remove(oid)
+    }
+
+    /// Returns the algorithm registered under the given OID, if any.
+    pub fn get(&self, oid: &ObjectIdentifier) -> Option<&RegisteredAlgorithm> {
+        self.algorithms.get(oid)
+    }
+
+    /// Returns `true`, if an algorithm is registered under the given OID.
+    pub fn contains(&self, oid: &ObjectIdentifier) -> bool {
+        self.algorithms.contains_key(oid)
+    }
+
+    /// Returns an iterator over the registered algorithms and their OIDs, ordered by OID.
+    pub fn iter(&self) -> impl Iterator<Item = (&ObjectIdentifier, &RegisteredAlgorithm)> {
+        self.algorithms.iter()
+    }
+
+    /// Verifies a raw signature over `data`, using the algorithm identified by `algorithm` and the
+    /// public key described by `public_key_info`. Fails with
+    /// [PublicKeyError::UnsupportedAlgorithm], if no algorithm is registered under the OID of
+    /// `algorithm`.
+    pub fn verify(
+        &self,
+        algorithm: &AlgorithmIdentifierOwned,
+        public_key_info: &PublicKeyInfo,
+        signature: &[u8],
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        match self.get(&algorithm.oid) {
+            Some(registered) => (registered.verify)(public_key_info, signature, data),
+            None => {
+                log::debug!(
+                    "[AlgorithmRegistry::verify()] No algorithm registered for {}",
+                    algorithm.oid
+                );
+                Err(PublicKeyError::UnsupportedAlgorithm(algorithm.oid))
+            }
+        }
+    }
+
+    /// Verifies the signature of a DER encoded X.509 certificate, such as an ID-Cert, using the
+    /// public key of its issuer. Only the signature is checked; the certificate is not checked
+    /// against the polyproto constraints, and its validity period is not checked. Fails, if the
+    /// certificate cannot be decoded, if the signature algorithm of the certificate differs from
+    /// the one stated in its `TBSCertificate`, or if the signature does not verify.
+    pub fn verify_certificate(
+        &self,
+        cert_der: &[u8],
+        issuer: &PublicKeyInfo,
+    ) -> Result<(), ConversionError> {
+        let cert = Certificate::from_der(cert_der)?;
+        if cert.signature_algorithm != cert.tbs_certificate.signature {
+            return Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature).into());
+        }
+        let signature = match cert.signature.as_bytes() {
+            Some(signature) => signature,
+            None => return Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature).into()),
+        };
+        self.verify(
+            &cert.signature_algorithm,
+            issuer,
+            signature,
+            &cert.tbs_certificate.to_der()?,
+        )
+        .map_err(InvalidCert::PublicKeyError)?;
+        Ok(())
+    }
+
+    /// Like [AlgorithmRegistry::verify_certificate()], but verifies the signature using the
+    /// subject public key of the certificate itself, as for a self-signed home server certificate.
+    pub fn verify_self_signed(&self, cert_der: &[u8]) -> Result<(), ConversionError> {
+        let cert = Certificate::from_der(cert_der)?;
+        let subject_public_key =
+            PublicKeyInfo::from(cert.tbs_certificate.subject_public_key_info.clone());
+        self.verify_certificate(cert_der, &subject_public_key)
+    }
+}
+
+/// The [VerifyFn] of a signature algorithm `S` with the public key type `P`.
+fn verify_with<S: Signature, P: PublicKey<S>>(
+    public_key_info: &PublicKeyInfo,
+    signature: &[u8],
+    data: &[u8],
+) -> Result<(), PublicKeyError> {
+    let public_key = match P::try_from_public_key_info(public_key_info.clone()) {
+        Ok(public_key) => public_key,
+        Err(_) => return Err(PublicKeyError::BadPublicKeyInfo),
+    };
+    public_key.verify_signature(&S::from_bytes(signature), data)
+}
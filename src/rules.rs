@@ -0,0 +1,431 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+
+use crate::certs::Target;
+use crate::errors::ConstraintError;
+use crate::verifier::Severity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The [Target]s a [Rule] applies to.
+pub enum AppliesTo {
+    /// The rule is always checked, regardless of the target.
+    Always,
+    /// The rule is only checked when validating for [Target::Actor].
+    Actor,
+    /// The rule is only checked when validating for [Target::HomeServer].
+    HomeServer,
+}
+
+impl AppliesTo {
+    /// Returns `true`, if a rule with this applicability is checked when validating for `target`.
+    pub fn applies(&self, target: Option<Target>) -> bool {
+        match self {
+            AppliesTo::Always => true,
+            AppliesTo::Actor => target == Some(Target::Actor),
+            AppliesTo::HomeServer => target == Some(Target::HomeServer),
+        }
+    }
+}
+
+impl Display for AppliesTo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppliesTo::Always => write!(f, "always"),
+            AppliesTo::Actor => write!(f, "actor"),
+            AppliesTo::HomeServer => write!(f, "home server"),
+        }
+    }
+}
+
+/// The check of a [Rule]. Receives the value to validate and the [Target] it is validated for.
+/// Non-capturing closures can be used as checks.
+pub type Check<T> = fn(&T, Option<Target>) -> Result<(), ConstraintError>;
+
+/// A single polyproto constraint on values of type `T`.
+pub struct Rule<T> {
+    /// A unique, stable identifier of the rule, such as `idcert.actor-not-ca`.
+    pub id: &'static str,
+    /// A stable error code, reported when the rule is violated, such as `PP1006`.
+    pub error_code: &'static str,
+    /// A short, human-readable description of what the rule requires.
+    pub description: &'static str,
+    /// The [Target]s the rule applies to.
+    pub applies_to: AppliesTo,
+    /// How severe a violation of the rule is. Only violations of rules with [Severity::Error]
+    /// make a value invalid.
+    pub severity: Severity,
+    /// The field of the value the rule concerns, such as `subject`. `None`, if the rule concerns
+    /// the value as a whole.
+    pub field: Option<&'static str>,
+    /// The check of the rule.
+    pub check: Check<T>,
+}
+
+impl<T> Rule<T> {
+    /// Creates a new rule of [Severity::Error], which always applies.
+    pub const fn new(
+        id: &'static str,
+        error_code: &'static str,
+        description: &'static str,
+        check: Check<T>,
+    ) -> Self {
+        Self {
+            id,
+            error_code,
+            description,
+            applies_to: AppliesTo::Always,
+            severity: Severity::Error,
+            field: None,
+            check,
+        }
+    }
+
+    /// Restricts the rule to the given [AppliesTo].
+    pub const fn applies_to(mut self, applies_to: AppliesTo) -> Self {
+        self.applies_to = applies_to;
+        self
+    }
+
+    /// Sets the [Severity] of a violation of the rule.
+    pub const fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Sets the field of the value the rule concerns.
+    pub const fn field(mut self, field: &'static str) -> Self {
+        self.field = Some(field);
+        self
+    }
+}
+
+impl<T> Clone for Rule<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Rule<T> {}
+
+impl<T> std::fmt::Debug for Rule<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rule")
+            .field("id", &self.id)
+            .field("error_code", &self.error_code)
+            .field("description", &self.description)
+            .field("applies_to", &self.applies_to)
+            .field("severity", &self.severity)
+            .field("field", &self.field)
+            .finish()
+    }
+}
+
+/// An ordered table of the [Rule]s for values of type `T`.
+///
+/// [RuleSet::check()] powers the `validate()` implementation of the [Constrained](crate::Constrained)
+/// trait for types implementing [HasRules], [RuleSet::report()] produces a [ValidationReport]
+/// checking all rules, and the [Display] implementation renders the table as a Markdown listing of
+/// the rules.
+pub struct RuleSet<T> {
+    rules: Vec<Rule<T>>,
+}
+
+impl<T> Default for RuleSet<T> {
+    fn default() -> Self {
+        Self { rules: Vec::new() }
+    }
+}
+
+impl<T> Clone for RuleSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for RuleSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuleSet")
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+impl<T> RuleSet<T> {
+    /// Creates a [RuleSet] from a table of rules. Rules are checked in order.
+    pub fn new(rules: Vec<Rule<T>>) -> Self {
+        Self { rules }
+    }
+
+    /// Appends a rule to the table. Panics, if a rule with the same ID already exists.
+    pub fn with_rule(mut self, rule: Rule<T>) -> Self {
+        assert!(
+            self.get(rule.id).is_none(),
+            "A rule with the ID {} already exists",
+            rule.id
+        );
+        self.rules.push(rule);
+        self
+    }
+
+    /// Removes the rules with the given IDs from the table. Unknown IDs are ignored.
+    pub fn without_rules(mut self, ids: &[&str]) -> Self {
+        self.rules.retain(|rule| !ids.contains(&rule.id));
+        self
+    }
+
+    /// Returns the rule with the given ID, if any.
+    pub fn get(&self, id: &str) -> Option<&Rule<T>> {
+        self.rules.iter().find(|rule| rule.id == id)
+    }
+
+    /// Returns an iterator over the rules, in the order they are checked.
+    pub fn iter(&self) -> impl Iterator<Item = &Rule<T>> {
+        self.rules.iter()
+    }
+
+    /// Returns the number of rules.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns `true`, if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Checks the rules applying to `target` in order, returning the error of the first violated
+    /// rule of [Severity::Error]. Violations of less severe rules are only logged.
+    pub fn check(&self, value: &T, target: Option<Target>) -> Result<(), ConstraintError> {
+        for rule in self.rules.iter() {
+            if !rule.applies_to.applies(target) {
+                continue;
+            }
+            log::trace!("[RuleSet::check()] Checking rule {}", rule.id);
+            if let Err(e) = (rule.check)(value, target) {
+                log::debug!(
+                    "[RuleSet::check()] Rule {} ({}) violated with severity {}: {}",
+                    rule.id,
+                    rule.error_code,
+                    rule.severity,
+                    e
+                );
+                if rule.severity == Severity::Error {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks all rules against `value`, without stopping at the first violated rule.
+    pub fn report(&self, value: &T, target: Option<Target>) -> ValidationReport {
+        let outcomes = self
+            .rules
+            .iter()
+            .map(|rule| RuleOutcome {
+                id: rule.id,
+                error_code: rule.error_code,
+                severity: rule.severity,
+                field: rule.field,
+                status: match rule.applies_to.applies(target) {
+                    true => match (rule.check)(value, target) {
+                        Ok(()) => RuleStatus::Passed,
+                        Err(e) => RuleStatus::Failed(e),
+                    },
+                    false => RuleStatus::NotApplicable,
+                },
+            })
+            .collect();
+        ValidationReport { outcomes }
+    }
+}
+
+impl<T> Display for RuleSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "| ID | Error code | Applies to | Severity | Field | Description |"
+        )?;
+        writeln!(f, "| --- | --- | --- | --- | --- | --- |")?;
+        for rule in self.rules.iter() {
+            writeln!(
+                f,
+                "| `{}` | `{}` | {} | {} | {} | {} |",
+                rule.id,
+                rule.error_code,
+                rule.applies_to,
+                rule.severity,
+                rule.field
+                    .map(|field| format!("`{}`", field))
+                    .unwrap_or_default(),
+                rule.description
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Types whose [Constrained](crate::Constrained) implementation is declared as a [RuleSet].
+pub trait HasRules: Sized {
+    /// Returns the rules for this type.
+    fn rules() -> RuleSet<Self>;
+
+    /// Checks all rules for this type against `self`, returning a [ValidationReport] listing
+    /// every violation, instead of stopping at the first one like
+    /// [Constrained::validate()](crate::Constrained::validate()).
+    fn validate_report(&self, target: Option<Target>) -> ValidationReport {
+        Self::rules().report(self, target)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The status of a single rule in a [ValidationReport].
+pub enum RuleStatus {
+    /// The value satisfies the rule.
+    Passed,
+    /// The value violates the rule.
+    Failed(ConstraintError),
+    /// The rule does not apply to the [Target] the value was validated for.
+    NotApplicable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The outcome of checking a single rule, as part of a [ValidationReport].
+pub struct RuleOutcome {
+    /// The ID of the rule.
+    pub id: &'static str,
+    /// The error code of the rule.
+    pub error_code: &'static str,
+    /// The [Severity] of the rule.
+    pub severity: Severity,
+    /// The field of the value the rule concerns, if any.
+    pub field: Option<&'static str>,
+    /// Whether the rule was satisfied.
+    pub status: RuleStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The outcomes of checking all rules of a [RuleSet] against a value, in the order of the rules.
+/// Unlike `validate()` of the [Constrained](crate::Constrained) trait, a report does not stop at
+/// the first violated rule.
+pub struct ValidationReport {
+    /// The outcome of every rule.
+    pub outcomes: Vec<RuleOutcome>,
+}
+
+impl ValidationReport {
+    /// Returns `true`, if no rule of [Severity::Error] was violated, i.e. if `validate()` of the
+    /// [Constrained](crate::Constrained) trait succeeds for the value.
+    pub fn passed(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Returns the outcomes of all violated rules of [Severity::Error].
+    pub fn errors(&self) -> impl Iterator<Item = &RuleOutcome> {
+        self.failures()
+            .filter(|outcome| outcome.severity == Severity::Error)
+    }
+
+    /// Returns the outcomes of all violated rules, regardless of their [Severity].
+    pub fn failures(&self) -> impl Iterator<Item = &RuleOutcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.status, RuleStatus::Failed(_)))
+    }
+
+    /// Returns `true`, if the rule with the given ID was violated.
+    pub fn failed(&self, id: &str) -> bool {
+        self.failures().any(|outcome| outcome.id == id)
+    }
+
+    /// Returns the outcome of the rule with the given ID, if the rule set contains it.
+    pub fn outcome(&self, id: &str) -> Option<&RuleOutcome> {
+        self.outcomes.iter().find(|outcome| outcome.id == id)
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for outcome in self.outcomes.iter() {
+            match &outcome.status {
+                RuleStatus::Passed => writeln!(f, "[pass] {}", outcome.id)?,
+                RuleStatus::NotApplicable => writeln!(f, "[skip] {}", outcome.id)?,
+                RuleStatus::Failed(e) => {
+                    let tag = match outcome.severity {
+                        Severity::Error => "FAIL",
+                        Severity::Warning => "WARN",
+                        Severity::Info => "INFO",
+                    };
+                    write!(f, "[{}] {} ({})", tag, outcome.id, outcome.error_code)?;
+                    if let Some(field) = outcome.field {
+                        write!(f, " in `{}`", field)?;
+                    }
+                    writeln!(f, ": {}", e)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Even(u32);
+
+    impl HasRules for Even {
+        fn rules() -> RuleSet<Self> {
+            RuleSet::new(vec![
+                Rule::new(
+                    "even.even",
+                    "T0001",
+                    "The value must be even",
+                    |value, _| match value.0 % 2 {
+                        0 => Ok(()),
+                        _ => Err(ConstraintError::Malformed(None)),
+                    },
+                ),
+                Rule::new(
+                    "even.small",
+                    "T0002",
+                    "Actors must be small",
+                    |value: &Even, _| match value.0 < 10 {
+                        true => Ok(()),
+                        false => Err(ConstraintError::Malformed(None)),
+                    },
+                )
+                .applies_to(AppliesTo::Actor),
+            ])
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn rule_table() {
+        let rules = Even::rules();
+        assert!(rules.check(&Even(12), None).is_ok());
+        assert!(rules.check(&Even(12), Some(Target::Actor)).is_err());
+        assert!(rules.check(&Even(3), Some(Target::HomeServer)).is_err());
+
+        let report = rules.report(&Even(13), Some(Target::Actor));
+        assert!(!report.passed());
+        assert!(report.failed("even.even"));
+        assert!(report.failed("even.small"));
+        let report = rules.report(&Even(13), None);
+        assert_eq!(
+            report.outcome("even.small").unwrap().status,
+            RuleStatus::NotApplicable
+        );
+
+        assert!(rules
+            .to_string()
+            .contains("| `even.small` | `T0002` | actor |"));
+    }
+}
@@ -0,0 +1,463 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use der::{Decode, DecodePem, Encode};
+
+/// Test vectors of the known-answer tests. Which of them are used depends on the enabled
+/// features.
+#[allow(dead_code)]
+mod vectors {
+    use crate::encoding::decode_hex;
+
+    /// Seed of the Ed25519 key of RFC 8032, section 7.1, test 1.
+    pub(super) const ED25519_SEED: &str =
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    /// Public key of RFC 8032, section 7.1, test 1.
+    pub(super) const ED25519_PUBLIC_KEY: &str =
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    /// Signature over the empty message of RFC 8032, section 7.1, test 1.
+    pub(super) const ED25519_SIGNATURE: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+
+    /// Private key of RFC 6979, appendix A.2.5.
+    pub(super) const P256_PRIVATE_KEY: &str =
+        "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+    /// Uncompressed SEC1 encoding of the public key of RFC 6979, appendix A.2.5.
+    pub(super) const P256_PUBLIC_KEY: &str = "0460fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb67903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299";
+    /// DER encoding of the deterministic ECDSA signature with SHA-256 over the message `sample` of
+    /// RFC 6979, appendix A.2.5.
+    pub(super) const P256_SIGNATURE: &str = "3046022100efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716022100f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8";
+    /// The message signed in RFC 6979, appendix A.2.5.
+    pub(super) const P256_MESSAGE: &[u8] = b"sample";
+
+    /// Password of the PBES2 known-answer tests. The expected ciphertexts were computed using the
+    /// PBKDF2 and scrypt implementations of Python's `hashlib` and AES-256-CBC of OpenSSL.
+    pub(super) const PBES2_PASSWORD: &[u8] = b"password";
+    pub(super) const PBES2_SALT: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+    pub(super) const PBES2_IV: [u8; 16] = [
+        16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+    ];
+    pub(super) const PBES2_PLAINTEXT: &[u8] = b"polyproto self-test";
+    /// AES-256-CBC ciphertext of [PBES2_PLAINTEXT], keyed by PBKDF2-HMAC-SHA256 with 1000
+    /// iterations.
+    pub(super) const PBKDF2_CIPHERTEXT: &str =
+        "25c57ec6092504358fbd696a38290bc34b81b173f59ba659416f0ec4623c1498";
+    /// AES-256-CBC ciphertext of [PBES2_PLAINTEXT], keyed by scrypt with `N = 16`, `r = 8` and
+    /// `p = 1`.
+    pub(super) const SCRYPT_CIPHERTEXT: &str =
+        "511cf607a3b5fa4bcf1b14be711a209c23d99ac143ebbca20f313ac25fc2b523";
+
+    pub(super) fn hex<const N: usize>(hex: &str) -> Result<[u8; N], String> {
+        let bytes = decode_hex(hex).map_err(|e| e.to_string())?;
+        bytes
+            .try_into()
+            .map_err(|_| "Test vector has the wrong length".to_string())
+    }
+}
+
+/// A self-signed home server certificate, signed using the Ed25519 key of RFC 8032, section 7.1,
+/// test 1.
+const REFERENCE_CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBPDCB76ADAgECAgEBMAUGAytlcDA0MRcwFQYKCZImiZPyLGQBGRYHZXhhbXBs
+ZTEZMBcGCgmSJomT8ixkARkWCXBvbHlwcm90bzAeFw0yNDAxMDEwMDAwMDBaFw00
+OTAxMDEwMDAwMDBaMDQxFzAVBgoJkiaJk/IsZAEZFgdleGFtcGxlMRkwFwYKCZIm
+iZPyLGQBGRYJcG9seXByb3RvMCowBQYDK2VwAyEA11qYAYKxCrfVS/7TyWQHOg7h
+cvPapiMlrwIaaPcHURqjJjAkMBIGA1UdEwEB/wQIMAYBAf8CAQAwDgYDVR0PAQH/
+BAQDAgEGMAUGAytlcANBAFKUGm7xNmVLuokqeVc8h4n1bxqdg4fSMlkYYlgOSDWq
+BjQgkkKH33ACdxjUdjJbGtI+s18ER4Ivv47eurbrMgA=
+-----END CERTIFICATE-----";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The kind of primitive exercised by a [SelfTestResult].
+pub enum SelfTestKind {
+    /// A signature algorithm.
+    Signature,
+    /// A key derivation function, together with the cipher it keys.
+    Kdf,
+    /// An authenticated encryption scheme.
+    Aead,
+    /// DER encoding and decoding.
+    Encoding,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The outcome of a single check of [self_test()].
+pub struct SelfTestResult {
+    /// The name of the check, naming the algorithm and the module providing it.
+    pub name: &'static str,
+    /// The kind of primitive exercised by the check.
+    pub kind: SelfTestKind,
+    /// Why the check failed, or `None`, if it passed.
+    pub error: Option<String>,
+}
+
+impl SelfTestResult {
+    /// Returns `true`, if the check passed.
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// The report returned by [self_test()], listing the outcome of every check run.
+pub struct SelfTestReport {
+    /// The outcomes of all checks, in the order they were run.
+    pub results: Vec<SelfTestResult>,
+}
+
+impl SelfTestReport {
+    /// Returns `true`, if all checks passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(SelfTestResult::passed)
+    }
+
+    /// Returns the checks which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestResult> {
+        self.results.iter().filter(|result| !result.passed())
+    }
+
+    fn run(&mut self, name: &'static str, kind: SelfTestKind, check: fn() -> Result<(), String>) {
+        // Some backends panic instead of returning an error when they are in an error state, e.g.
+        // aws-lc after a failed FIPS self-test.
+        let error = match catch_unwind(AssertUnwindSafe(check)) {
+            Ok(Ok(())) => None,
+            Ok(Err(error)) => Some(error),
+            Err(_) => Some("The check panicked".to_string()),
+        };
+        if let Some(error) = &error {
+            log::error!("[self_test()] {} failed: {}", name, error);
+        }
+        self.results.push(SelfTestResult { name, kind, error });
+    }
+}
+
+impl std::fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in self.results.iter() {
+            match &result.error {
+                None => writeln!(f, "ok: {}", result.name)?,
+                Some(error) => writeln!(f, "FAILED: {}: {}", result.name, error)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs known-answer tests for every signature, KDF and AEAD backend compiled into this build,
+/// and checks that a reference certificate survives a DER round-trip unchanged. Meant to be run
+/// by servers at startup, to catch miscompiled or misconfigured cryptography before serving
+/// traffic:
+///
+/// ```
+/// let report = polyproto::self_test();
+/// assert!(report.passed(), "{}", report);
+/// ```
+///
+/// Signature backends are checked against the test vectors of RFC 8032 (Ed25519) and RFC 6979
+/// (ECDSA P-256), where signing is deterministic, and by a sign and verify round-trip otherwise.
+/// With the `pkcs8` feature, PBES2 with PBKDF2 and scrypt is checked against precomputed
+/// ciphertexts. With the `age` feature, age encryption is checked by an encrypt and decrypt
+/// round-trip. Hardware and browser backends, such as PKCS#11 tokens, FIDO2 authenticators and
+/// WebCrypto, are not checked.
+pub fn self_test() -> SelfTestReport {
+    log::trace!("[self_test()] Running self-test");
+    let mut report = SelfTestReport::default();
+    #[cfg(all(feature = "ed25519", not(feature = "fips")))]
+    report.run(
+        "Ed25519 (ed25519)",
+        SelfTestKind::Signature,
+        rustcrypto::ed25519,
+    );
+    #[cfg(all(feature = "p256", not(feature = "fips")))]
+    report.run(
+        "ECDSA P-256 with SHA-256 (p256)",
+        SelfTestKind::Signature,
+        rustcrypto::p256,
+    );
+    #[cfg(any(feature = "aws-lc", feature = "fips"))]
+    {
+        report.run(
+            "Ed25519 (aws_lc::ed25519)",
+            SelfTestKind::Signature,
+            aws_lc::ed25519,
+        );
+        report.run(
+            "ECDSA P-256 with SHA-256 (aws_lc::p256)",
+            SelfTestKind::Signature,
+            aws_lc::p256,
+        );
+    }
+    #[cfg(feature = "pkcs8")]
+    {
+        report.run(
+            "PBES2 with PBKDF2-HMAC-SHA256 and AES-256-CBC (pkcs8)",
+            SelfTestKind::Kdf,
+            pbes2::pbkdf2,
+        );
+        report.run(
+            "PBES2 with scrypt and AES-256-CBC (pkcs8)",
+            SelfTestKind::Kdf,
+            pbes2::scrypt,
+        );
+    }
+    #[cfg(feature = "age")]
+    report.run(
+        "age with X25519 and ChaCha20-Poly1305 (age)",
+        SelfTestKind::Aead,
+        age_round_trip,
+    );
+    report.run(
+        "DER round-trip of the reference certificate",
+        SelfTestKind::Encoding,
+        reference_cert,
+    );
+    report
+}
+
+fn reference_cert() -> Result<(), String> {
+    let cert = x509_cert::Certificate::from_pem(REFERENCE_CERT).map_err(|e| e.to_string())?;
+    let der = cert.to_der().map_err(|e| e.to_string())?;
+    let decoded = x509_cert::Certificate::from_der(&der).map_err(|e| e.to_string())?;
+    if decoded != cert || decoded.to_der().map_err(|e| e.to_string())? != der {
+        return Err("The certificate changed during the round-trip".to_string());
+    }
+    #[cfg(all(feature = "ed25519", not(feature = "fips")))]
+    {
+        use crate::backends::ed25519::{Ed25519PublicKey, Ed25519Signature};
+        use crate::certs::idcert::IdCert;
+        use crate::timestamp::Timestamp;
+
+        let id_cert = IdCert::<Ed25519Signature, Ed25519PublicKey>::from_der_unchecked(&der)
+            .map_err(|e| e.to_string())?;
+        id_cert
+            .full_verify_home_server(Timestamp::from(id_cert.id_cert_tbs.validity.not_before))
+            .map_err(|e| e.to_string())?;
+        if id_cert.to_der().map_err(|e| e.to_string())? != der {
+            return Err("The IdCert changed during the round-trip".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(any(feature = "ed25519", feature = "p256"), not(feature = "fips")))]
+mod rustcrypto {
+    use super::vectors;
+
+    #[cfg(feature = "ed25519")]
+    pub(super) fn ed25519() -> Result<(), String> {
+        use crate::backends::ed25519::Ed25519PrivateKey;
+        use crate::key::{PrivateKey, PublicKey};
+        use crate::signature::Signature;
+
+        let key = Ed25519PrivateKey::from_bytes(&vectors::hex(vectors::ED25519_SEED)?);
+        if key.pubkey().to_bytes() != vectors::hex::<32>(vectors::ED25519_PUBLIC_KEY)? {
+            return Err("Derived the wrong public key".to_string());
+        }
+        let signature = key.sign(b"");
+        if signature.as_signature().to_bytes() != vectors::hex::<64>(vectors::ED25519_SIGNATURE)? {
+            return Err("Created the wrong signature".to_string());
+        }
+        key.pubkey()
+            .verify_signature(&signature, b"")
+            .map_err(|e| e.to_string())?;
+        if key.pubkey().verify_signature(&signature, b"x").is_ok() {
+            return Err("Accepted a signature over another message".to_string());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "p256")]
+    pub(super) fn p256() -> Result<(), String> {
+        use crate::backends::p256::P256PrivateKey;
+        use crate::encoding::decode_hex;
+        use crate::key::{PrivateKey, PublicKey};
+        use crate::signature::Signature;
+
+        let key = P256PrivateKey::from_bytes(&vectors::hex(vectors::P256_PRIVATE_KEY)?)
+            .map_err(|e| e.to_string())?;
+        if key.pubkey().to_sec1_bytes()
+            != decode_hex(vectors::P256_PUBLIC_KEY).map_err(|e| e.to_string())?
+        {
+            return Err("Derived the wrong public key".to_string());
+        }
+        let signature = key.sign(vectors::P256_MESSAGE);
+        if signature.as_signature()
+            != decode_hex(vectors::P256_SIGNATURE)
+                .map_err(|e| e.to_string())?
+                .as_slice()
+        {
+            return Err("Created the wrong signature".to_string());
+        }
+        key.pubkey()
+            .verify_signature(&signature, vectors::P256_MESSAGE)
+            .map_err(|e| e.to_string())?;
+        if key.pubkey().verify_signature(&signature, b"x").is_ok() {
+            return Err("Accepted a signature over another message".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "aws-lc", feature = "fips"))]
+mod aws_lc {
+    use super::vectors;
+
+    use crate::encoding::decode_hex;
+    use crate::key::{PrivateKey, PublicKey};
+    use crate::signature::Signature;
+
+    pub(super) fn ed25519() -> Result<(), String> {
+        use crate::backends::aws_lc::ed25519::Ed25519PrivateKey;
+
+        let key = Ed25519PrivateKey::from_seed(&vectors::hex(vectors::ED25519_SEED)?)
+            .map_err(|e| e.to_string())?;
+        if key.pubkey().to_bytes() != vectors::hex::<32>(vectors::ED25519_PUBLIC_KEY)? {
+            return Err("Derived the wrong public key".to_string());
+        }
+        let signature = key.sign(b"");
+        if signature.as_signature()
+            != decode_hex(vectors::ED25519_SIGNATURE)
+                .map_err(|e| e.to_string())?
+                .as_slice()
+        {
+            return Err("Created the wrong signature".to_string());
+        }
+        key.pubkey()
+            .verify_signature(&signature, b"")
+            .map_err(|e| e.to_string())
+    }
+
+    pub(super) fn p256() -> Result<(), String> {
+        use crate::backends::aws_lc::p256::{P256PrivateKey, P256PublicKey, P256Signature};
+
+        // aws-lc signs with random nonces, so only verification can be checked against the test
+        // vector.
+        let public_key = P256PublicKey::from_sec1_bytes(
+            &decode_hex(vectors::P256_PUBLIC_KEY).map_err(|e| e.to_string())?,
+        )
+        .map_err(|e| e.to_string())?;
+        let signature = P256Signature::from_bytes(
+            &decode_hex(vectors::P256_SIGNATURE).map_err(|e| e.to_string())?,
+        );
+        public_key
+            .verify_signature(&signature, vectors::P256_MESSAGE)
+            .map_err(|e| e.to_string())?;
+        if public_key.verify_signature(&signature, b"x").is_ok() {
+            return Err("Accepted a signature over another message".to_string());
+        }
+        let key = P256PrivateKey::gen_keypair();
+        key.pubkey()
+            .verify_signature(&key.sign(vectors::P256_MESSAGE), vectors::P256_MESSAGE)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+mod pbes2 {
+    use super::vectors;
+
+    use pkcs8::pkcs5::pbes2::Parameters;
+
+    use crate::encoding::decode_hex;
+
+    pub(super) fn pbkdf2() -> Result<(), String> {
+        let parameters =
+            Parameters::pbkdf2_sha256_aes256cbc(1000, &vectors::PBES2_SALT, &vectors::PBES2_IV)
+                .map_err(|e| e.to_string())?;
+        check(&parameters, vectors::PBKDF2_CIPHERTEXT)
+    }
+
+    pub(super) fn scrypt() -> Result<(), String> {
+        let scrypt_params =
+            pkcs8::pkcs5::scrypt::Params::new(4, 8, 1, 32).map_err(|e| e.to_string())?;
+        let parameters =
+            Parameters::scrypt_aes256cbc(scrypt_params, &vectors::PBES2_SALT, &vectors::PBES2_IV)
+                .map_err(|e| e.to_string())?;
+        check(&parameters, vectors::SCRYPT_CIPHERTEXT)
+    }
+
+    fn check(parameters: &Parameters, ciphertext: &str) -> Result<(), String> {
+        let ciphertext = decode_hex(ciphertext).map_err(|e| e.to_string())?;
+        let encrypted = parameters
+            .encrypt(vectors::PBES2_PASSWORD, vectors::PBES2_PLAINTEXT)
+            .map_err(|e| e.to_string())?;
+        if encrypted != ciphertext {
+            return Err("Created the wrong ciphertext".to_string());
+        }
+        let decrypted = parameters
+            .decrypt(vectors::PBES2_PASSWORD, &ciphertext)
+            .map_err(|e| e.to_string())?;
+        if decrypted != vectors::PBES2_PLAINTEXT {
+            return Err("Decrypted the wrong plaintext".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "age")]
+fn age_round_trip() -> Result<(), String> {
+    use std::io::{Read, Write};
+
+    let identity = age::x25519::Identity::generate();
+    let recipient = identity.to_public();
+    let encryptor =
+        age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient))
+            .map_err(|e| e.to_string())?;
+    let mut ciphertext = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut ciphertext)
+        .map_err(|e| e.to_string())?;
+    writer
+        .write_all(vectors::PBES2_PLAINTEXT)
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    let decryptor = age::Decryptor::new(ciphertext.as_slice()).map_err(|e| e.to_string())?;
+    let mut reader = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| e.to_string())?;
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|e| e.to_string())?;
+    if plaintext != vectors::PBES2_PLAINTEXT {
+        return Err("Decrypted the wrong plaintext".to_string());
+    }
+    // Flipping a bit of the payload must be detected.
+    if let Some(last) = ciphertext.last_mut() {
+        *last ^= 1;
+    }
+    let tampered = age::Decryptor::new(ciphertext.as_slice())
+        .map_err(|e| e.to_string())?
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| e.to_string())?
+        .read_to_end(&mut Vec::new());
+    if tampered.is_ok() {
+        return Err("Accepted a tampered ciphertext".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn self_test_passes() {
+        let report = self_test();
+        assert!(report.passed(), "{}", report);
+        assert!(report
+            .results
+            .iter()
+            .any(|result| result.kind == SelfTestKind::Encoding));
+        assert_eq!(
+            report
+                .results
+                .iter()
+                .any(|result| result.kind == SelfTestKind::Kdf),
+            cfg!(feature = "pkcs8")
+        );
+    }
+}
@@ -2,8 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
 use spki::{AlgorithmIdentifierOwned, SignatureBitStringEncoding};
 
+use crate::errors::PublicKeyError;
+use crate::key::{PrivateKey, PublicKey};
+
 /// A signature value, generated using a [SignatureAlgorithm]
 pub trait Signature: PartialEq + Eq + SignatureBitStringEncoding + Clone + ToString {
     /// The underlying signature type
@@ -15,3 +21,270 @@ pub trait Signature: PartialEq + Eq + SignatureBitStringEncoding + Clone + ToStr
     /// From a byte slice, create a new [Self]
     fn from_bytes(signature: &[u8]) -> Self;
 }
+
+/// The prefix of the payload signed for a [DetachedSignature].
+pub static STREAM_PAYLOAD_PREFIX: &str = "polyproto-stream-v1";
+
+/// The length of the digest of a signed stream, in bytes.
+pub const STREAM_DIGEST_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A signature over a stream of data, such as a large file upload or a RawR resource, which is
+/// stored or transmitted separately from the data.
+///
+/// Since not every signature algorithm can sign data incrementally, the data is hashed using
+/// SHA-256 while it is being streamed, and the signature is created over
+/// [DetachedSignature::signed_payload()], which binds the digest and the length of the data. Use
+/// [PrivateKey::sign_stream()] to create, and [PublicKey::verify_stream()] to verify a
+/// [DetachedSignature] without buffering the data in memory.
+///
+/// When the `serde` feature is enabled, a [DetachedSignature] is (de-)serialized as an object with
+/// the OID of the signature algorithm, the length of the data and the hex encoded digest and
+/// signature.
+pub struct DetachedSignature<S: Signature> {
+    /// The length of the signed data, in bytes.
+    pub content_length: u64,
+    /// The SHA-256 digest of the signed data.
+    pub digest: [u8; STREAM_DIGEST_LENGTH],
+    /// The signature over [DetachedSignature::signed_payload()].
+    pub signature: S,
+}
+
+impl<S: Signature> DetachedSignature<S> {
+    /// Signs `data` at once. Equivalent to streaming `data` through a [StreamSigner].
+    pub fn sign(signing_key: &impl PrivateKey<S>, data: &[u8]) -> Self {
+        let mut signer = StreamSigner::new(signing_key);
+        signer.update(data);
+        signer.finalize()
+    }
+
+    /// Returns the payload which is signed: [STREAM_PAYLOAD_PREFIX], the length of the data as a
+    /// big endian 64 bit integer and the digest of the data.
+    pub fn signed_payload(&self) -> Vec<u8> {
+        stream_payload(self.content_length, &self.digest)
+    }
+
+    /// Verifies this signature for `data`, available at once.
+    pub fn verify<P: PublicKey<S>>(
+        &self,
+        public_key: &P,
+        data: &[u8],
+    ) -> Result<(), PublicKeyError> {
+        let mut verifier = StreamVerifier::new(public_key);
+        verifier.update(data);
+        verifier.finalize(self)
+    }
+}
+
+fn stream_payload(content_length: u64, digest: &[u8; STREAM_DIGEST_LENGTH]) -> Vec<u8> {
+    let mut payload = STREAM_PAYLOAD_PREFIX.as_bytes().to_vec();
+    payload.extend_from_slice(&content_length.to_be_bytes());
+    payload.extend_from_slice(digest);
+    payload
+}
+
+#[derive(Debug, Clone, Default)]
+struct StreamDigest {
+    hasher: Sha256,
+    content_length: u64,
+}
+
+impl StreamDigest {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.content_length += data.len() as u64;
+    }
+
+    fn finalize(self) -> (u64, [u8; STREAM_DIGEST_LENGTH]) {
+        (self.content_length, self.hasher.finalize().into())
+    }
+}
+
+/// Signs a stream of data, which is fed to it in chunks using [StreamSigner::update()] or its
+/// [Write](std::io::Write) implementation, creating a [DetachedSignature]. Created using
+/// [PrivateKey::sign_stream()].
+pub struct StreamSigner<'a, S: Signature, K: PrivateKey<S> + ?Sized> {
+    signing_key: &'a K,
+    digest: StreamDigest,
+    _signature: PhantomData<S>,
+}
+
+impl<S: Signature, K: PrivateKey<S> + ?Sized> std::fmt::Debug for StreamSigner<'_, S, K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamSigner")
+            .field("content_length", &self.digest.content_length)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, S: Signature, K: PrivateKey<S> + ?Sized> StreamSigner<'a, S, K> {
+    /// Creates a new [StreamSigner], signing with `signing_key`.
+    pub fn new(signing_key: &'a K) -> Self {
+        Self {
+            signing_key,
+            digest: StreamDigest::default(),
+            _signature: PhantomData,
+        }
+    }
+
+    /// Feeds the next chunk of data to the signer.
+    pub fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+    }
+
+    /// Returns the number of bytes fed to the signer so far.
+    pub fn content_length(&self) -> u64 {
+        self.digest.content_length
+    }
+
+    /// Signs the data fed to the signer.
+    pub fn finalize(self) -> DetachedSignature<S> {
+        let (content_length, digest) = self.digest.finalize();
+        log::trace!(
+            "[StreamSigner::finalize()] Signing stream of {} bytes",
+            content_length
+        );
+        DetachedSignature {
+            content_length,
+            digest,
+            signature: self
+                .signing_key
+                .sign(&stream_payload(content_length, &digest)),
+        }
+    }
+}
+
+impl<S: Signature, K: PrivateKey<S> + ?Sized> std::io::Write for StreamSigner<'_, S, K> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Verifies a [DetachedSignature] for a stream of data, which is fed to it in chunks using
+/// [StreamVerifier::update()] or its [Write](std::io::Write) implementation. Created using
+/// [PublicKey::verify_stream()].
+pub struct StreamVerifier<'a, S: Signature, P: PublicKey<S>> {
+    public_key: &'a P,
+    digest: StreamDigest,
+    _signature: PhantomData<S>,
+}
+
+impl<S: Signature, P: PublicKey<S>> std::fmt::Debug for StreamVerifier<'_, S, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamVerifier")
+            .field("content_length", &self.digest.content_length)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, S: Signature, P: PublicKey<S>> StreamVerifier<'a, S, P> {
+    /// Creates a new [StreamVerifier], verifying with `public_key`.
+    pub fn new(public_key: &'a P) -> Self {
+        Self {
+            public_key,
+            digest: StreamDigest::default(),
+            _signature: PhantomData,
+        }
+    }
+
+    /// Feeds the next chunk of data to the verifier.
+    pub fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+    }
+
+    /// Returns the number of bytes fed to the verifier so far.
+    pub fn content_length(&self) -> u64 {
+        self.digest.content_length
+    }
+
+    /// Verifies `signature` for the data fed to the verifier. Fails with
+    /// [PublicKeyError::BadSignature], if the length or digest of the data do not match the
+    /// signature, or if the signature is invalid.
+    pub fn finalize(self, signature: &DetachedSignature<S>) -> Result<(), PublicKeyError> {
+        let (content_length, digest) = self.digest.finalize();
+        if content_length != signature.content_length || digest != signature.digest {
+            log::debug!("[StreamVerifier::finalize()] Stream does not match the signed digest");
+            return Err(PublicKeyError::BadSignature);
+        }
+        self.public_key
+            .verify_signature(&signature.signature, &signature.signed_payload())
+    }
+}
+
+impl<S: Signature, P: PublicKey<S>> std::io::Write for StreamVerifier<'_, S, P> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use serde::de::Error;
+    use serde::{Deserialize, Serialize};
+
+    use crate::encoding::{decode_hex, encode_hex};
+
+    use super::{DetachedSignature, Signature, STREAM_DIGEST_LENGTH};
+
+    #[derive(Serialize, Deserialize)]
+    struct DetachedSignatureJson {
+        algorithm: String,
+        content_length: u64,
+        digest: String,
+        signature: String,
+    }
+
+    impl<S: Signature> Serialize for DetachedSignature<S> {
+        fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+        where
+            Ser: serde::Serializer,
+        {
+            let signature = self
+                .signature
+                .to_bitstring()
+                .map_err(serde::ser::Error::custom)?;
+            DetachedSignatureJson {
+                algorithm: S::algorithm_identifier().oid.to_string(),
+                content_length: self.content_length,
+                digest: encode_hex(&self.digest),
+                signature: encode_hex(signature.raw_bytes()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, S: Signature> Deserialize<'de> for DetachedSignature<S> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = DetachedSignatureJson::deserialize(deserializer)?;
+            let algorithm = S::algorithm_identifier().oid.to_string();
+            if json.algorithm != algorithm {
+                return Err(D::Error::custom(format!(
+                    "Expected a signature using {}, got {}",
+                    algorithm, json.algorithm
+                )));
+            }
+            let digest = decode_hex(&json.digest).map_err(D::Error::custom)?;
+            let digest = <[u8; STREAM_DIGEST_LENGTH]>::try_from(digest)
+                .map_err(|_| D::Error::custom("The digest must be 32 bytes long"))?;
+            let signature = decode_hex(&json.signature).map_err(D::Error::custom)?;
+            Ok(DetachedSignature {
+                content_length: json.content_length,
+                digest,
+                signature: S::from_bytes(&signature),
+            })
+        }
+    }
+}
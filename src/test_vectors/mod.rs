@@ -0,0 +1,154 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::pem::PemLabel;
+use x509_cert::Certificate;
+
+use crate::certs::Target;
+use crate::errors::InvalidCert;
+use crate::timestamp::Timestamp;
+
+/// Secret key of the Ed25519 key of the home server `polyphony.chat`, which signs all test
+/// vectors. Published, so that the vectors can be regenerated and extended.
+pub const HOME_SERVER_SECRET_KEY: [u8; 32] = [1; 32];
+/// Public key belonging to [HOME_SERVER_SECRET_KEY]. Actor certificates are verified against it.
+pub const HOME_SERVER_PUBLIC_KEY: [u8; 32] = [
+    138, 136, 227, 221, 116, 9, 241, 149, 253, 82, 219, 45, 60, 186, 93, 114, 202, 103, 9, 191, 29,
+    148, 18, 27, 243, 116, 136, 1, 180, 15, 111, 92,
+];
+/// Secret key of the Ed25519 key of the actor `flori@polyphony.chat`, session `client1`.
+pub const ACTOR_SECRET_KEY: [u8; 32] = [2; 32];
+/// Start of the validity period of the certificates which are not expired, in seconds since the
+/// Unix epoch: 2024-01-01T00:00:00Z.
+pub const NOT_BEFORE: u64 = 1704067200;
+/// End of the validity period of the certificates which are not expired, in seconds since the
+/// Unix epoch: 2034-01-01T00:00:00Z.
+pub const NOT_AFTER: u64 = 2019686400;
+/// The point in time at which the test vectors are verified, in seconds since the Unix epoch:
+/// 2025-01-01T00:00:00Z. Verifying them at another point in time may change their outcome.
+pub const VERIFICATION_TIME: u64 = 1735689600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The outcome a conforming implementation produces when verifying a [TestVector].
+pub enum ExpectedOutcome {
+    /// The certificate is valid.
+    Valid,
+    /// The signature of the certificate does not verify against the public key of its issuer.
+    InvalidSignature,
+    /// The certificate is not valid at [VERIFICATION_TIME].
+    Expired,
+    /// The certificate is correctly signed, but violates a polyproto constraint.
+    ConstraintViolation,
+}
+
+impl ExpectedOutcome {
+    /// Maps the result of verifying a certificate using this crate to the outcome it represents.
+    /// Returns `None` for errors none of the test vectors are expected to produce.
+    pub fn from_result<T>(result: &Result<T, InvalidCert>) -> Option<Self> {
+        match result {
+            Ok(_) => Some(Self::Valid),
+            Err(InvalidCert::PublicKeyError(_)) => Some(Self::InvalidSignature),
+            Err(InvalidCert::InvalidValidity) => Some(Self::Expired),
+            Err(InvalidCert::InvalidProperties(_)) => Some(Self::ConstraintViolation),
+            Err(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A frozen Ed25519 ID-Cert, together with the outcome of verifying it as [TestVector::target] at
+/// [VERIFICATION_TIME], using [HOME_SERVER_PUBLIC_KEY] as the public key of the home server.
+///
+/// The outcome is the one of a full verification, as performed by
+/// [IdCert::from_der_with()](crate::certs::idcert::IdCert::from_der_with) using the default
+/// [ValidationPolicy](crate::certs::policy::ValidationPolicy). Each invalid test vector has
+/// exactly one defect, so the outcome does not depend on the order of the checks.
+pub struct TestVector {
+    /// Short, unique name of the test vector, which is also the name of its fixture.
+    pub name: &'static str,
+    /// What the test vector exercises.
+    pub description: &'static str,
+    /// The target the certificate is verified as.
+    pub target: Target,
+    /// The PEM encoding of the certificate.
+    pub pem: &'static str,
+    /// The outcome of verifying the certificate.
+    pub expected: ExpectedOutcome,
+}
+
+impl TestVector {
+    /// The DER encoding of the certificate.
+    pub fn der(&self) -> Vec<u8> {
+        let (label, der) = der::pem::decode_vec(self.pem.as_bytes())
+            .expect("The fixtures of the test vectors are valid PEM");
+        debug_assert_eq!(label, Certificate::PEM_LABEL);
+        der
+    }
+
+    /// [VERIFICATION_TIME] as a [Timestamp].
+    pub fn time(&self) -> Timestamp {
+        Timestamp::from_unix_seconds(VERIFICATION_TIME)
+    }
+}
+
+/// All test vectors.
+pub const ALL: &[TestVector] = &[
+    TestVector {
+        name: "home_server_valid",
+        description: "A self-signed home server certificate",
+        target: Target::HomeServer,
+        pem: include_str!("home_server_valid.pem"),
+        expected: ExpectedOutcome::Valid,
+    },
+    TestVector {
+        name: "actor_valid",
+        description: "An actor certificate issued by the home server",
+        target: Target::Actor,
+        pem: include_str!("actor_valid.pem"),
+        expected: ExpectedOutcome::Valid,
+    },
+    TestVector {
+        name: "actor_bad_signature",
+        description: "actor_valid, with the first byte of its signature flipped",
+        target: Target::Actor,
+        pem: include_str!("actor_bad_signature.pem"),
+        expected: ExpectedOutcome::InvalidSignature,
+    },
+    TestVector {
+        name: "actor_expired",
+        description: "An actor certificate whose validity period ended a year before the \
+                      verification time",
+        target: Target::Actor,
+        pem: include_str!("actor_expired.pem"),
+        expected: ExpectedOutcome::Expired,
+    },
+    TestVector {
+        name: "actor_issuer_domain_mismatch",
+        description: "An actor certificate of an actor of polyphony.chat, issued by example.com",
+        target: Target::Actor,
+        pem: include_str!("actor_issuer_domain_mismatch.pem"),
+        expected: ExpectedOutcome::ConstraintViolation,
+    },
+    TestVector {
+        name: "actor_with_ca_flag",
+        description: "An actor certificate with the capabilities of a home server, including the \
+                      CA flag",
+        target: Target::Actor,
+        pem: include_str!("actor_with_ca_flag.pem"),
+        expected: ExpectedOutcome::ConstraintViolation,
+    },
+    TestVector {
+        name: "home_server_without_ca_flag",
+        description: "A self-signed home server certificate with the capabilities of an actor, \
+                      lacking the CA flag",
+        target: Target::HomeServer,
+        pem: include_str!("home_server_without_ca_flag.pem"),
+        expected: ExpectedOutcome::ConstraintViolation,
+    },
+];
+
+/// Returns the test vector named `name`, if there is one.
+pub fn by_name(name: &str) -> Option<&'static TestVector> {
+    ALL.iter().find(|vector| vector.name == name)
+}
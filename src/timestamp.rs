@@ -0,0 +1,806 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use der::asn1::UtcTime;
+use x509_cert::time::{Time, Validity};
+
+use crate::errors::{ConversionError, InvalidInput};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+/// A point in time in UTC, with a precision of one second.
+///
+/// [Timestamp] is used wherever polyproto deals with points in time, instead of bare integers.
+/// Internally, it is stored as the number of seconds since the Unix epoch, which is unambiguous
+/// with regards to time zones.
+///
+/// A [Timestamp] can be converted to and from both Unix epoch seconds and RFC 3339 strings. When
+/// the `serde` feature is enabled, it is serialized as Unix epoch seconds, and can be deserialized
+/// from either Unix epoch seconds or an RFC 3339 string. Use the [rfc3339] module with
+/// `#[serde(with = "...")]` to serialize it as an RFC 3339 string instead.
+pub struct Timestamp {
+    unix_seconds: u64,
+}
+
+impl Timestamp {
+    /// The Unix epoch, `1970-01-01T00:00:00Z`.
+    pub const UNIX_EPOCH: Timestamp = Timestamp { unix_seconds: 0 };
+
+    /// Creates a [Timestamp] from the number of seconds since the Unix epoch.
+    pub const fn from_unix_seconds(unix_seconds: u64) -> Self {
+        Self { unix_seconds }
+    }
+
+    /// Returns the number of seconds since the Unix epoch.
+    pub const fn unix_seconds(&self) -> u64 {
+        self.unix_seconds
+    }
+
+    /// Returns the current time according to the system clock. Times before the Unix epoch are
+    /// clamped to [Timestamp::UNIX_EPOCH].
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// Returns the [Timestamp] `duration` after `self`, discarding sub-second precision. Returns
+    /// `None` on overflow.
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        self.unix_seconds
+            .checked_add(duration.as_secs())
+            .map(Self::from_unix_seconds)
+    }
+
+    /// Returns the [Timestamp] `duration` before `self`, discarding sub-second precision. Returns
+    /// `None`, if the result would lie before the Unix epoch.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        self.unix_seconds
+            .checked_sub(duration.as_secs())
+            .map(Self::from_unix_seconds)
+    }
+
+    /// Formats this [Timestamp] as an RFC 3339 string in UTC, e.g. `2024-05-01T12:00:00Z`.
+    pub fn to_rfc3339(&self) -> String {
+        let days = (self.unix_seconds / 86_400) as i64;
+        let seconds_of_day = self.unix_seconds % 86_400;
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            seconds_of_day / 3600,
+            (seconds_of_day % 3600) / 60,
+            seconds_of_day % 60
+        )
+    }
+
+    /// Parses an RFC 3339 string, such as `2024-05-01T12:00:00Z` or
+    /// `2024-05-01T14:00:00.123+02:00`. Offsets are applied to convert the time to UTC, and
+    /// fractional seconds are discarded. Points in time before the Unix epoch are rejected.
+    pub fn from_rfc3339(s: &str) -> Result<Self, InvalidInput> {
+        let malformed = || InvalidInput::Malformed(format!("Invalid RFC 3339 timestamp: {}", s));
+        let bytes = s.as_bytes();
+        if bytes.len() < 20 || !s.is_ascii() {
+            return Err(malformed());
+        }
+        if bytes[4] != b'-'
+            || bytes[7] != b'-'
+            || !matches!(bytes[10], b'T' | b't' | b' ')
+            || bytes[13] != b':'
+            || bytes[16] != b':'
+        {
+            return Err(malformed());
+        }
+        let number = |part: &str| -> Result<i64, InvalidInput> {
+            if !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(malformed());
+            }
+            part.parse::<i64>().map_err(|_| malformed())
+        };
+        let year = number(&s[0..4])?;
+        let month = number(&s[5..7])?;
+        let day = number(&s[8..10])?;
+        let hour = number(&s[11..13])?;
+        let minute = number(&s[14..16])?;
+        let second = number(&s[17..19])?;
+        if !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return Err(malformed());
+        }
+        let mut rest = &s[19..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let digits = fraction.bytes().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 {
+                return Err(malformed());
+            }
+            rest = &fraction[digits..];
+        }
+        let offset_seconds = match rest {
+            "Z" | "z" => 0,
+            _ => {
+                let rest_bytes = rest.as_bytes();
+                if rest_bytes.len() != 6 || rest_bytes[3] != b':' {
+                    return Err(malformed());
+                }
+                let sign = match rest_bytes[0] {
+                    b'+' => 1,
+                    b'-' => -1,
+                    _ => return Err(malformed()),
+                };
+                let offset_hours = number(&rest[1..3])?;
+                let offset_minutes = number(&rest[4..6])?;
+                if offset_hours > 23 || offset_minutes > 59 {
+                    return Err(malformed());
+                }
+                sign * (offset_hours * 3600 + offset_minutes * 60)
+            }
+        };
+        let local_seconds =
+            days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+        let utc_seconds = local_seconds - offset_seconds;
+        if utc_seconds < 0 {
+            return Err(malformed());
+        }
+        Ok(Self::from_unix_seconds(utc_seconds as u64))
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_rfc3339())
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = InvalidInput;
+
+    /// Parses either an RFC 3339 string or a decimal number of seconds since the Unix epoch.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return match s.parse::<u64>() {
+                Ok(seconds) => Ok(Self::from_unix_seconds(seconds)),
+                Err(_) => Err(InvalidInput::Malformed(format!(
+                    "Invalid Unix timestamp: {}",
+                    s
+                ))),
+            };
+        }
+        Self::from_rfc3339(s)
+    }
+}
+
+impl From<u64> for Timestamp {
+    fn from(value: u64) -> Self {
+        Self::from_unix_seconds(value)
+    }
+}
+
+impl From<Timestamp> for u64 {
+    fn from(value: Timestamp) -> Self {
+        value.unix_seconds
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(value: SystemTime) -> Self {
+        match value.duration_since(UNIX_EPOCH) {
+            Ok(duration) => Self::from_unix_seconds(duration.as_secs()),
+            Err(_) => Self::UNIX_EPOCH,
+        }
+    }
+}
+
+impl TryFrom<Timestamp> for SystemTime {
+    type Error = ConversionError;
+
+    /// Fails, if the [Timestamp] lies beyond the range of [SystemTime] on this platform.
+    fn try_from(value: Timestamp) -> Result<Self, Self::Error> {
+        match UNIX_EPOCH.checked_add(Duration::from_secs(value.unix_seconds)) {
+            Some(time) => Ok(time),
+            None => Err(InvalidInput::Malformed(format!(
+                "{} seconds after the Unix epoch cannot be represented as a SystemTime",
+                value.unix_seconds
+            ))
+            .into()),
+        }
+    }
+}
+
+impl From<Time> for Timestamp {
+    fn from(value: Time) -> Self {
+        Self::from_unix_seconds(value.to_unix_duration().as_secs())
+    }
+}
+
+impl TryFrom<Timestamp> for Time {
+    type Error = ConversionError;
+
+    /// Converts a [Timestamp] into a [Time], using `UTCTime` for dates before the year 2050 and
+    /// `GeneralizedTime` otherwise, as required by RFC 5280. Fails for dates after the year 9999.
+    fn try_from(value: Timestamp) -> Result<Self, Self::Error> {
+        let time = SystemTime::try_from(value)?;
+        match UtcTime::from_system_time(time) {
+            Ok(utc_time) => Ok(Time::UtcTime(utc_time)),
+            Err(_) => Ok(Time::try_from(time)?),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    /// Converts a [chrono::DateTime], discarding sub-second precision. Times before the Unix epoch
+    /// are clamped to [Timestamp::UNIX_EPOCH].
+    fn from(value: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::from_unix_seconds(u64::try_from(value.timestamp()).unwrap_or(0))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Timestamp {
+    /// Converts a [time::OffsetDateTime], discarding sub-second precision. Times before the Unix
+    /// epoch are clamped to [Timestamp::UNIX_EPOCH].
+    fn from(value: time::OffsetDateTime) -> Self {
+        Self::from_unix_seconds(u64::try_from(value.unix_timestamp()).unwrap_or(0))
+    }
+}
+
+/// Helpers for creating the [Validity] of a certificate from a [Duration] or from points in time,
+/// without converting them to [Time] by hand. `not_before` and `not_after` can be given as anything
+/// convertible into a [Timestamp], such as a [SystemTime], or, with the `chrono` or `time`
+/// features enabled, a `chrono::DateTime<Utc>` or a `time::OffsetDateTime`.
+///
+/// Bring this trait into scope to call `Validity::between()` and `Validity::starting_at()`. For a
+/// validity period starting now, use [Validity::from_now()], which `x509_cert` already provides.
+pub trait ValidityExt: Sized {
+    /// Creates a [Validity] starting at `not_before` and ending after `lifetime`, discarding
+    /// sub-second precision. Fails, if `lifetime` is shorter than one second, or overflows.
+    fn starting_at(
+        not_before: impl Into<Timestamp>,
+        lifetime: Duration,
+    ) -> Result<Self, ConversionError>;
+
+    /// Creates a [Validity] from `not_before` until `not_after`. Fails, if `not_before` does not
+    /// lie before `not_after`, or if either cannot be encoded.
+    fn between(
+        not_before: impl Into<Timestamp>,
+        not_after: impl Into<Timestamp>,
+    ) -> Result<Self, ConversionError>;
+}
+
+impl ValidityExt for Validity {
+    fn starting_at(
+        not_before: impl Into<Timestamp>,
+        lifetime: Duration,
+    ) -> Result<Self, ConversionError> {
+        let not_before = not_before.into();
+        let not_after = not_before.checked_add(lifetime).ok_or_else(|| {
+            InvalidInput::Malformed("Lifetime overflows the validity period".to_string())
+        })?;
+        Self::between(not_before, not_after)
+    }
+
+    fn between(
+        not_before: impl Into<Timestamp>,
+        not_after: impl Into<Timestamp>,
+    ) -> Result<Self, ConversionError> {
+        let (not_before, not_after) = (not_before.into(), not_after.into());
+        if not_before >= not_after {
+            return Err(InvalidInput::Malformed(format!(
+                "The start of the validity period ({}) must lie before its end ({})",
+                not_before, not_after
+            ))
+            .into());
+        }
+        Ok(Validity {
+            not_before: Time::try_from(not_before)?,
+            not_after: Time::try_from(not_after)?,
+        })
+    }
+}
+
+/// A source of the current time.
+///
+/// polyproto never reads the system clock when validating certificates or signatures; all
+/// validation methods take the point in time to validate at as an argument. Components which act
+/// on the current time by themselves, such as the [HttpClient](crate::api::HttpClient) verifying
+/// certificates fetched from a home server, instead ask a [TimeSource]. Replacing the
+/// [SystemTimeSource] with a [FixedTimeSource] makes them deterministic in tests, and an
+/// [OffsetTimeSource] corrects a system clock which is known to be off.
+pub trait TimeSource: std::fmt::Debug + Send + Sync {
+    /// Returns the current time according to this source.
+    fn now(&self) -> Timestamp;
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for Box<T> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for std::sync::Arc<T> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// A [TimeSource] reading the system clock, like [Timestamp::now()].
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+#[derive(Debug, Default)]
+/// A [TimeSource] which always returns the same [Timestamp], until it is changed using
+/// [FixedTimeSource::set()] or [FixedTimeSource::advance()]. Changes are visible to everyone
+/// sharing the source, e.g. through an [Arc](std::sync::Arc).
+pub struct FixedTimeSource {
+    unix_seconds: AtomicU64,
+}
+
+impl FixedTimeSource {
+    /// Creates a new [FixedTimeSource] returning `time`.
+    pub fn new(time: impl Into<Timestamp>) -> Self {
+        Self {
+            unix_seconds: AtomicU64::new(time.into().unix_seconds()),
+        }
+    }
+
+    /// Makes the source return `time` from now on.
+    pub fn set(&self, time: impl Into<Timestamp>) {
+        self.unix_seconds
+            .store(time.into().unix_seconds(), Ordering::SeqCst);
+    }
+
+    /// Moves the time returned by the source forward by `duration`, discarding sub-second
+    /// precision. Saturates instead of overflowing.
+    pub fn advance(&self, duration: Duration) {
+        let _ = self
+            .unix_seconds
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |seconds| {
+                Some(seconds.saturating_add(duration.as_secs()))
+            });
+    }
+}
+
+impl Clone for FixedTimeSource {
+    fn clone(&self) -> Self {
+        Self::new(self.now())
+    }
+}
+
+impl TimeSource for FixedTimeSource {
+    fn now(&self) -> Timestamp {
+        Timestamp::from_unix_seconds(self.unix_seconds.load(Ordering::SeqCst))
+    }
+}
+
+#[derive(Debug)]
+/// A [TimeSource] shifting the time of another source by a constant offset, e.g. to correct a
+/// system clock which is known to be off, or to validate as if it were some time later.
+///
+/// The returned time never goes backwards: if the underlying source is set back, the source
+/// keeps returning the latest time it has returned, until the underlying source catches up.
+/// Shifted times before the Unix epoch are clamped to [Timestamp::UNIX_EPOCH].
+pub struct OffsetTimeSource<T: TimeSource = SystemTimeSource> {
+    inner: T,
+    offset_seconds: i64,
+    latest: AtomicU64,
+}
+
+impl<T: TimeSource> OffsetTimeSource<T> {
+    /// Creates a new [OffsetTimeSource] returning the time of `inner`, shifted `duration` ahead.
+    pub fn ahead(inner: T, duration: Duration) -> Self {
+        Self::with_offset_seconds(inner, i64::try_from(duration.as_secs()).unwrap_or(i64::MAX))
+    }
+
+    /// Creates a new [OffsetTimeSource] returning the time of `inner`, shifted `duration` back.
+    pub fn behind(inner: T, duration: Duration) -> Self {
+        Self::with_offset_seconds(
+            inner,
+            i64::try_from(duration.as_secs()).map_or(i64::MIN, |seconds| -seconds),
+        )
+    }
+
+    /// Creates a new [OffsetTimeSource] returning the time of `inner`, shifted by
+    /// `offset_seconds`. Negative offsets shift the time back.
+    pub fn with_offset_seconds(inner: T, offset_seconds: i64) -> Self {
+        Self {
+            inner,
+            offset_seconds,
+            latest: AtomicU64::new(0),
+        }
+    }
+
+    /// The offset applied to the time of the underlying source, in seconds.
+    pub fn offset_seconds(&self) -> i64 {
+        self.offset_seconds
+    }
+
+    /// The underlying source.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: TimeSource> TimeSource for OffsetTimeSource<T> {
+    fn now(&self) -> Timestamp {
+        let seconds = self.inner.now().unix_seconds();
+        let shifted = if self.offset_seconds >= 0 {
+            seconds.saturating_add(self.offset_seconds.unsigned_abs())
+        } else {
+            seconds.saturating_sub(self.offset_seconds.unsigned_abs())
+        };
+        let previous = self.latest.fetch_max(shifted, Ordering::SeqCst);
+        Timestamp::from_unix_seconds(previous.max(shifted))
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Number of days since the Unix epoch for a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date in the proleptic Gregorian calendar for a number of days since the Unix epoch.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use std::str::FromStr;
+
+    use serde::de::Visitor;
+    use serde::{Deserialize, Serialize};
+
+    use super::Timestamp;
+
+    impl Serialize for Timestamp {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_u64(self.unix_seconds)
+        }
+    }
+
+    pub(super) struct TimestampVisitor;
+
+    impl<'de> Visitor<'de> for TimestampVisitor {
+        type Value = Timestamp;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("seconds since the Unix epoch or an RFC 3339 string")
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Timestamp::from_unix_seconds(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            match u64::try_from(v) {
+                Ok(v) => Ok(Timestamp::from_unix_seconds(v)),
+                Err(_) => Err(E::custom(
+                    "timestamps before the Unix epoch are not supported",
+                )),
+            }
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Timestamp::from_str(v).map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Timestamp {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_any(TimestampVisitor)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+/// (De-)serialize a [Timestamp] as an RFC 3339 string, using `#[serde(with = "...")]`.
+/// Deserialization also accepts Unix epoch seconds.
+pub mod rfc3339 {
+    use serde::{Deserializer, Serializer};
+
+    use super::Timestamp;
+
+    /// Serializes a [Timestamp] as an RFC 3339 string.
+    pub fn serialize<S>(timestamp: &Timestamp, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&timestamp.to_rfc3339())
+    }
+
+    /// Deserializes a [Timestamp] from an RFC 3339 string or Unix epoch seconds.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(super::serde_support::TimestampVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn rfc3339_roundtrip() {
+        for seconds in [
+            0u64,
+            951_782_400,
+            1_709_164_800,
+            1_714_564_800,
+            4_102_444_799,
+        ] {
+            let timestamp = Timestamp::from_unix_seconds(seconds);
+            assert_eq!(
+                Timestamp::from_rfc3339(&timestamp.to_rfc3339()).unwrap(),
+                timestamp
+            );
+        }
+        assert_eq!(
+            Timestamp::from_unix_seconds(1_714_564_800).to_rfc3339(),
+            "2024-05-01T12:00:00Z"
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn time_uses_utc_time_before_2050() {
+        assert!(matches!(
+            Time::try_from(Timestamp::from_unix_seconds(1_714_564_800)).unwrap(),
+            Time::UtcTime(_)
+        ));
+        assert!(matches!(
+            Time::try_from(Timestamp::from_unix_seconds(2_524_608_000)).unwrap(),
+            Time::GeneralTime(_)
+        ));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn rfc3339_offsets_are_applied() {
+        let utc = Timestamp::from_rfc3339("2024-05-01T12:00:00Z").unwrap();
+        assert_eq!(
+            Timestamp::from_rfc3339("2024-05-01T13:00:00+01:00").unwrap(),
+            utc
+        );
+        assert_eq!(
+            Timestamp::from_rfc3339("2024-05-01t11:00:00.999-01:00").unwrap(),
+            utc
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn malformed_rfc3339() {
+        for input in [
+            "",
+            "2024-05-01",
+            "2024-05-01T12:00:00",
+            "2024-13-01T12:00:00Z",
+            "2023-02-29T12:00:00Z",
+            "2024-05-01T24:00:00Z",
+            "2024-05-01T12:00:00.Z",
+            "2024-05-01T12:00:00+0100",
+            "1969-12-31T23:59:59Z",
+        ] {
+            assert!(Timestamp::from_rfc3339(input).is_err(), "{}", input);
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn from_str_accepts_both_formats() {
+        assert_eq!(
+            Timestamp::from_str("1714564800").unwrap(),
+            Timestamp::from_str("2024-05-01T12:00:00Z").unwrap()
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn validity_helpers() {
+        let validity = Validity::between(
+            Timestamp::from_unix_seconds(1_714_564_800),
+            UNIX_EPOCH + Duration::from_secs(1_714_651_200),
+        )
+        .unwrap();
+        assert_eq!(
+            Timestamp::from(validity.not_before),
+            Timestamp::from_unix_seconds(1_714_564_800)
+        );
+        assert_eq!(
+            Timestamp::from(validity.not_after),
+            Timestamp::from_unix_seconds(1_714_651_200)
+        );
+        assert!(Validity::between(100u64, 100u64).is_err());
+        assert!(Validity::between(101u64, 100u64).is_err());
+
+        let validity = Validity::starting_at(
+            Timestamp::from_unix_seconds(1_714_564_800),
+            Duration::from_millis(86_400_999),
+        )
+        .unwrap();
+        assert_eq!(
+            Timestamp::from(validity.not_after),
+            Timestamp::from_unix_seconds(1_714_651_200)
+        );
+        assert!(Validity::starting_at(100u64, Duration::from_millis(999)).is_err());
+        assert!(Validity::starting_at(Timestamp::now(), Duration::MAX).is_err());
+        assert!(Validity::between(
+            Timestamp::UNIX_EPOCH,
+            Timestamp::from_unix_seconds(u64::MAX)
+        )
+        .is_err());
+        assert!(
+            Validity::starting_at(Timestamp::UNIX_EPOCH, Duration::from_secs(u64::MAX / 2))
+                .is_err()
+        );
+        assert!(SystemTime::try_from(Timestamp::from_unix_seconds(u64::MAX)).is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn time_sources() {
+        let fixed = std::sync::Arc::new(FixedTimeSource::new(Timestamp::from_unix_seconds(100)));
+        assert_eq!(fixed.now().unix_seconds(), 100);
+        fixed.advance(Duration::from_millis(30_500));
+        assert_eq!(fixed.now().unix_seconds(), 130);
+        fixed.advance(Duration::MAX);
+        assert_eq!(fixed.now().unix_seconds(), u64::MAX);
+        fixed.set(SystemTime::UNIX_EPOCH + Duration::from_secs(200));
+        assert_eq!(fixed.clone().now().unix_seconds(), 200);
+
+        let ahead = OffsetTimeSource::ahead(fixed.clone(), Duration::from_secs(50));
+        assert_eq!(ahead.now().unix_seconds(), 250);
+        let behind = OffsetTimeSource::behind(fixed.clone(), Duration::from_secs(50));
+        assert_eq!(behind.offset_seconds(), -50);
+        assert_eq!(behind.now().unix_seconds(), 150);
+        // The offset source never goes backwards, and clamps to the Unix epoch.
+        fixed.set(Timestamp::from_unix_seconds(180));
+        assert_eq!(behind.now().unix_seconds(), 150);
+        fixed.set(Timestamp::from_unix_seconds(210));
+        assert_eq!(behind.now().unix_seconds(), 160);
+        let before_epoch = OffsetTimeSource::with_offset_seconds(fixed.clone(), -1000);
+        assert_eq!(before_epoch.now(), Timestamp::UNIX_EPOCH);
+
+        let system: Box<dyn TimeSource> = Box::new(OffsetTimeSource::ahead(
+            SystemTimeSource,
+            Duration::from_secs(3600),
+        ));
+        assert!(system.now() > SystemTimeSource.now());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn from_chrono() {
+        let time = chrono::DateTime::<chrono::Utc>::from_timestamp(1_714_564_800, 500).unwrap();
+        assert_eq!(
+            Timestamp::from(time),
+            Timestamp::from_unix_seconds(1_714_564_800)
+        );
+        let validity = Validity::between(time, time + chrono::Duration::days(1)).unwrap();
+        assert_eq!(
+            Timestamp::from(validity.not_after),
+            Timestamp::from_unix_seconds(1_714_651_200)
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn from_time() {
+        let time = time::OffsetDateTime::from_unix_timestamp(1_714_564_800).unwrap();
+        assert_eq!(
+            Timestamp::from(time),
+            Timestamp::from_unix_seconds(1_714_564_800)
+        );
+        assert_eq!(
+            Timestamp::from(time::OffsetDateTime::UNIX_EPOCH - time::Duration::days(1)),
+            Timestamp::UNIX_EPOCH
+        );
+        let validity = Validity::between(time, time + time::Duration::days(1)).unwrap();
+        assert_eq!(
+            Timestamp::from(validity.not_after),
+            Timestamp::from_unix_seconds(1_714_651_200)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn serde_formats() {
+        let timestamp = Timestamp::from_unix_seconds(1_714_564_800);
+        assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1714564800");
+        assert_eq!(
+            serde_json::from_str::<Timestamp>("1714564800").unwrap(),
+            timestamp
+        );
+        assert_eq!(
+            serde_json::from_str::<Timestamp>("\"2024-05-01T12:00:00Z\"").unwrap(),
+            timestamp
+        );
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapper {
+            #[serde(with = "crate::timestamp::rfc3339")]
+            time: Timestamp,
+        }
+        let json = serde_json::to_string(&Wrapper { time: timestamp }).unwrap();
+        assert_eq!(json, r#"{"time":"2024-05-01T12:00:00Z"}"#);
+        assert_eq!(
+            serde_json::from_str::<Wrapper>(&json).unwrap().time,
+            timestamp
+        );
+    }
+}
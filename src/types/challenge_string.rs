@@ -2,13 +2,134 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use crate::errors::{ChallengeError, ConstraintError};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+use crate::Constrained;
+
+use super::FederationId;
+
+/// The prefix of the payload signed when completing a [ChallengeString] with a [ChallengeBinding].
+pub static CHALLENGE_PAYLOAD_PREFIX: &str = "polyproto-challenge-v1";
+
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// A struct that holds a challenge string and its expiration time.
 pub struct ChallengeString {
     /// The challenge, as generated by the polyproto home server.
     pub challenge: String,
-    /// An expiry date in seconds since the Unix epoch, after which the challenge cannot be completed
-    /// any longer.
-    pub expires: u64,
+    /// The point in time after which the challenge cannot be completed any longer. Transmitted as
+    /// seconds since the Unix epoch.
+    pub expires: Timestamp,
+    /// The context this challenge was issued for. Unbound challenges can be completed in any
+    /// context, and should only be accepted for backwards compatibility.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub binding: Option<ChallengeBinding>,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The flow a [ChallengeString] was issued for.
+pub enum ChallengePurpose {
+    /// Registering a new actor, or a new session of an actor.
+    Registration,
+    /// Proving possession of the private key belonging to an ID-Cert.
+    KeyTrial,
+    /// Migrating an actor from one home server to another.
+    Migration,
+}
+
+impl ChallengePurpose {
+    /// Returns the name of this purpose, as used in the signed payload and in JSON.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChallengePurpose::Registration => "registration",
+            ChallengePurpose::KeyTrial => "key_trial",
+            ChallengePurpose::Migration => "migration",
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Binds a [ChallengeString] to the actor, flow and server it was issued for, so that a completed
+/// challenge cannot be relayed into a different context.
+pub struct ChallengeBinding {
+    /// The federation ID of the actor expected to complete the challenge.
+    pub federation_id: FederationId,
+    /// The flow the challenge was issued for.
+    pub purpose: ChallengePurpose,
+    /// The domain of the home server which issued the challenge.
+    pub server_domain: String,
+}
+
+impl ChallengeString {
+    /// Creates a new [ChallengeString], validating it against the polyproto specification.
+    pub fn new(
+        challenge: &str,
+        expires: Timestamp,
+        binding: Option<ChallengeBinding>,
+    ) -> Result<Self, ConstraintError> {
+        let challenge_string = Self {
+            challenge: challenge.to_string(),
+            expires,
+            binding,
+        };
+        challenge_string.validate(None)?;
+        Ok(challenge_string)
+    }
+
+    /// Returns the payload an actor signs to complete this challenge.
+    ///
+    /// For unbound challenges, this is the challenge itself. For bound challenges, the payload
+    /// additionally contains the purpose, federation ID, server domain and expiry, each separated
+    /// by a newline and prefixed with [CHALLENGE_PAYLOAD_PREFIX].
+    pub fn signed_payload(&self) -> Vec<u8> {
+        match &self.binding {
+            None => self.challenge.as_bytes().to_vec(),
+            Some(binding) => format!(
+                "{}\n{}\n{}\n{}\n{}\n{}",
+                CHALLENGE_PAYLOAD_PREFIX,
+                binding.purpose.as_str(),
+                binding.federation_id,
+                binding.server_domain,
+                self.expires.unix_seconds(),
+                self.challenge
+            )
+            .into_bytes(),
+        }
+    }
+
+    /// Verifies the response of an actor to this challenge. Succeeds, if the challenge has not
+    /// expired at `now`, is bound to exactly the `expected` [ChallengeBinding], and `signature` is
+    /// a valid signature of [ChallengeString::signed_payload()] by `public_key`.
+    pub fn verify_response<S: Signature, P: PublicKey<S>>(
+        &self,
+        expected: &ChallengeBinding,
+        signature: &S,
+        public_key: &P,
+        now: Timestamp,
+    ) -> Result<(), ChallengeError> {
+        if now > self.expires {
+            return Err(ChallengeError::Expired);
+        }
+        match &self.binding {
+            None => return Err(ChallengeError::Unbound),
+            Some(binding) if binding.purpose != expected.purpose => {
+                return Err(ChallengeError::PurposeMismatch {
+                    expected: expected.purpose,
+                    actual: binding.purpose,
+                })
+            }
+            Some(binding) if binding != expected => return Err(ChallengeError::BindingMismatch),
+            Some(_) => (),
+        }
+        public_key.verify_signature(signature, &self.signed_payload())?;
+        Ok(())
+    }
 }
@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A single API or gateway endpoint of a polyproto home server, as returned by the
+/// `GET /.p2/core/v1/endpoints` discovery route.
+///
+/// Larger home servers may publish several endpoints. Clients should prefer endpoints with a lower
+/// `priority` value, and distribute requests between endpoints of equal priority proportionally to
+/// their `weight`. This mirrors the semantics of DNS `SRV` records.
+pub struct Endpoint {
+    /// The base URL of the endpoint, e.g. `https://api.polyphony.chat`.
+    pub url: String,
+    /// The priority of this endpoint. Lower values are preferred. Endpoints with a higher value
+    /// are only used if all endpoints with a lower value are considered unhealthy.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: u16,
+    /// The relative weight of this endpoint among endpoints of the same priority. An endpoint with
+    /// a weight of `0` is only selected if no other endpoint of the same priority is available.
+    #[cfg_attr(feature = "serde", serde(default = "default_weight"))]
+    pub weight: u16,
+    /// Whether this is an API or a gateway endpoint. Defaults to [EndpointKind::Api].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: EndpointKind,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// The kind of an [Endpoint].
+pub enum EndpointKind {
+    /// An endpoint serving the HTTP API of the home server.
+    #[default]
+    Api,
+    /// A WebSocket gateway endpoint of the home server.
+    Gateway,
+}
+
+#[cfg(feature = "serde")]
+fn default_weight() -> u16 {
+    1
+}
+
+impl Endpoint {
+    /// Creates a new [EndpointKind::Api] [Endpoint] with the given `url`, `priority` and `weight`.
+    pub fn new(url: &str, priority: u16, weight: u16) -> Self {
+        Self {
+            url: url.to_string(),
+            priority,
+            weight,
+            kind: EndpointKind::Api,
+        }
+    }
+
+    /// Sets the [EndpointKind] of this endpoint.
+    pub fn with_kind(mut self, kind: EndpointKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
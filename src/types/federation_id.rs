@@ -63,3 +63,46 @@ impl std::fmt::Display for FederationId {
         write!(f, "{}", self.inner)
     }
 }
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use serde::de::Visitor;
+    use serde::{Deserialize, Serialize};
+
+    use super::FederationId;
+
+    impl Serialize for FederationId {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_str(&self.inner)
+        }
+    }
+
+    struct FederationIdVisitor;
+
+    impl<'de> Visitor<'de> for FederationIdVisitor {
+        type Value = FederationId;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a valid polyproto federation ID")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            FederationId::new(v).map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for FederationId {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_str(FederationIdVisitor)
+        }
+    }
+}
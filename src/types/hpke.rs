@@ -0,0 +1,273 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use ::hpke::aead::ChaCha20Poly1305;
+use ::hpke::kdf::HkdfSha256;
+use ::hpke::kem::X25519HkdfSha256;
+use ::hpke::{Deserializable, Kem, OpModeR, OpModeS, Serializable};
+use der::asn1::BitString;
+use der::zeroize::Zeroizing;
+use pkcs8::SecretDocument;
+use rand_core::CryptoRngCore;
+use spki::ObjectIdentifier;
+
+use crate::errors::{ConversionError, InvalidInput};
+use crate::key::Pkcs8PrivateKey;
+use crate::signature::Signature;
+
+use super::encrypted_pkm::{EncryptedPkm, PrivateKeyInfo};
+use super::spki::AlgorithmIdentifierOwned;
+use super::x509_cert::SerialNumber;
+
+/// Object Identifier of the encryption algorithm of an [EncryptedPkm] sealed by this module, in the
+/// private arc of polyproto: HPKE (RFC 9180) in base mode, with DHKEM(X25519, HKDF-SHA256),
+/// HKDF-SHA256 and ChaCha20-Poly1305.
+pub const OID_HPKE_X25519_CHACHA20POLY1305: &str = "1.3.6.1.4.1.18227.5.1";
+/// The HPKE `info` parameter used when sealing private key material.
+pub static HPKE_INFO: &str = "polyproto-pkm-v1";
+
+/// The length of an encoded [DevicePublicKey] and of the encapsulated key prepended to the
+/// ciphertext, in bytes.
+pub const ENCAPPED_KEY_LENGTH: usize = 32;
+
+type DeviceKem = X25519HkdfSha256;
+
+#[derive(Clone, PartialEq, Eq)]
+/// The X25519 secret key of a device, used to receive private key material from other devices of
+/// the same actor. Unlike signing keys, this key is never certified; the public key is exchanged
+/// out of band during onboarding, for example by scanning a QR code.
+pub struct DeviceSecretKey {
+    key: <DeviceKem as Kem>::PrivateKey,
+}
+
+impl std::fmt::Debug for DeviceSecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceSecretKey").finish_non_exhaustive()
+    }
+}
+
+impl DeviceSecretKey {
+    /// Generates a new, random secret key.
+    pub fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        let (key, _) = DeviceKem::gen_keypair(rng);
+        Self { key }
+    }
+
+    /// Returns the public key belonging to this secret key.
+    pub fn public_key(&self) -> DevicePublicKey {
+        DevicePublicKey {
+            key: DeviceKem::sk_to_pk(&self.key),
+        }
+    }
+
+    /// Decodes a secret key from its 32 byte encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConversionError> {
+        Ok(Self {
+            key: <DeviceKem as Kem>::PrivateKey::from_bytes(bytes)?,
+        })
+    }
+
+    /// Encodes this secret key as 32 bytes.
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(self.key.to_bytes().to_vec())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The X25519 public key of a device, which private key material can be sealed to. See
+/// [DeviceSecretKey].
+pub struct DevicePublicKey {
+    key: <DeviceKem as Kem>::PublicKey,
+}
+
+impl DevicePublicKey {
+    /// Decodes a public key from its 32 byte encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConversionError> {
+        Ok(Self {
+            key: <DeviceKem as Kem>::PublicKey::from_bytes(bytes)?,
+        })
+    }
+
+    /// Encodes this public key as 32 bytes.
+    pub fn to_bytes(&self) -> [u8; ENCAPPED_KEY_LENGTH] {
+        self.key.to_bytes().into()
+    }
+}
+
+/// Encrypts a plaintext, DER encoded PKCS#8 `PrivateKeyInfo` document to the device owning
+/// `recipient`, returning an [EncryptedPkm] for the certificate with the given serial number.
+///
+/// The `PrivateKeyInfo` of the [EncryptedPkm] carries the algorithm of the private key in the
+/// clear. Its bit string holds the encapsulated key, followed by the ciphertext. The serial number
+/// is authenticated as associated data, so the [EncryptedPkm] cannot be passed off as the key
+/// material of another certificate.
+pub fn seal_pkcs8(
+    rng: &mut impl CryptoRngCore,
+    recipient: &DevicePublicKey,
+    serial_number: SerialNumber,
+    pkcs8_der: &[u8],
+) -> Result<EncryptedPkm, ConversionError> {
+    log::trace!("[seal_pkcs8()] sealing private key material");
+    let algorithm = private_key_algorithm(pkcs8_der)?;
+    let (encapped_key, ciphertext) =
+        ::hpke::single_shot_seal::<ChaCha20Poly1305, HkdfSha256, DeviceKem, _>(
+            &OpModeS::Base,
+            &recipient.key,
+            HPKE_INFO.as_bytes(),
+            pkcs8_der,
+            serial_number.as_bytes(),
+            rng,
+        )?;
+    let mut sealed = encapped_key.to_bytes().to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(EncryptedPkm {
+        serial_number,
+        key_data: PrivateKeyInfo {
+            algorithm,
+            encrypted_private_key_bitstring: BitString::from_bytes(&sealed)?,
+        },
+        encryption_algorithm: spki::AlgorithmIdentifierOwned {
+            oid: ObjectIdentifier::from_str(OID_HPKE_X25519_CHACHA20POLY1305)?,
+            parameters: None,
+        }
+        .into(),
+    })
+}
+
+/// Decrypts an [EncryptedPkm] sealed to `recipient` using [seal_pkcs8()], returning the plaintext,
+/// DER encoded PKCS#8 `PrivateKeyInfo` document. Fails, if the [EncryptedPkm] uses another
+/// encryption algorithm, was sealed to another device or has been tampered with, or if the
+/// algorithm of the decrypted key does not match the algorithm stated in the [EncryptedPkm].
+pub fn open_pkcs8(
+    recipient: &DeviceSecretKey,
+    encrypted_pkm: &EncryptedPkm,
+) -> Result<SecretDocument, ConversionError> {
+    log::trace!("[open_pkcs8()] opening private key material");
+    if encrypted_pkm.encryption_algorithm.oid.to_string() != OID_HPKE_X25519_CHACHA20POLY1305 {
+        return Err(InvalidInput::Malformed(format!(
+            "Unsupported encryption algorithm {}, expected {}",
+            encrypted_pkm.encryption_algorithm.oid, OID_HPKE_X25519_CHACHA20POLY1305
+        ))
+        .into());
+    }
+    let sealed = match encrypted_pkm
+        .key_data
+        .encrypted_private_key_bitstring
+        .as_bytes()
+    {
+        Some(sealed) if sealed.len() > ENCAPPED_KEY_LENGTH => sealed,
+        _ => {
+            return Err(InvalidInput::Malformed(
+                "The sealed private key material is too short".to_string(),
+            )
+            .into())
+        }
+    };
+    let (encapped_key, ciphertext) = sealed.split_at(ENCAPPED_KEY_LENGTH);
+    let plaintext = Zeroizing::new(::hpke::single_shot_open::<
+        ChaCha20Poly1305,
+        HkdfSha256,
+        DeviceKem,
+    >(
+        &OpModeR::Base,
+        &recipient.key,
+        &<DeviceKem as Kem>::EncappedKey::from_bytes(encapped_key)?,
+        HPKE_INFO.as_bytes(),
+        ciphertext,
+        encrypted_pkm.serial_number.as_bytes(),
+    )?);
+    if private_key_algorithm(&plaintext)? != encrypted_pkm.key_data.algorithm {
+        return Err(InvalidInput::Malformed(
+            "The algorithm of the decrypted private key does not match the stated algorithm"
+                .to_string(),
+        )
+        .into());
+    }
+    Ok(SecretDocument::try_from(plaintext.as_slice())?)
+}
+
+/// Like [seal_pkcs8()], but encodes `private_key` as a PKCS#8 document first.
+pub fn seal_private_key<S: Signature>(
+    rng: &mut impl CryptoRngCore,
+    recipient: &DevicePublicKey,
+    serial_number: SerialNumber,
+    private_key: &impl Pkcs8PrivateKey<S>,
+) -> Result<EncryptedPkm, ConversionError> {
+    seal_pkcs8(
+        rng,
+        recipient,
+        serial_number,
+        private_key.to_pkcs8_der()?.as_bytes(),
+    )
+}
+
+/// Like [open_pkcs8()], but decodes the decrypted PKCS#8 document as a private key of type `K`.
+pub fn open_private_key<S: Signature, K: Pkcs8PrivateKey<S>>(
+    recipient: &DeviceSecretKey,
+    encrypted_pkm: &EncryptedPkm,
+) -> Result<K, ConversionError> {
+    K::from_pkcs8_der(open_pkcs8(recipient, encrypted_pkm)?.as_bytes())
+}
+
+fn private_key_algorithm(pkcs8_der: &[u8]) -> Result<AlgorithmIdentifierOwned, ConversionError> {
+    let private_key_info = pkcs8::PrivateKeyInfo::try_from(pkcs8_der)?;
+    Ok(spki::AlgorithmIdentifierOwned {
+        oid: private_key_info.algorithm.oid,
+        parameters: private_key_info.algorithm.parameters.map(der::Any::from),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The Ed25519 private key of RFC 8410, section 10.3, as a PKCS#8 `PrivateKeyInfo` document.
+    const PKCS8_ED25519: [u8; 48] = [
+        0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04,
+        0x20, 0xd4, 0xee, 0x72, 0xdb, 0xf9, 0x13, 0x58, 0x4a, 0xd5, 0xb6, 0xd8, 0xf1, 0xf7, 0x69,
+        0xf8, 0xad, 0x3a, 0xfe, 0x7c, 0x28, 0xcb, 0xf1, 0xd4, 0xfb, 0xe0, 0x97, 0xa8, 0x8f, 0x44,
+        0x75, 0x58, 0x42,
+    ];
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn seal_and_open() {
+        let mut rng = rand::rngs::OsRng;
+        let device = DeviceSecretKey::generate(&mut rng);
+        let recipient = DevicePublicKey::from_bytes(&device.public_key().to_bytes()).unwrap();
+        let encrypted_pkm = seal_pkcs8(
+            &mut rng,
+            &recipient,
+            SerialNumber::from(7u128),
+            &PKCS8_ED25519,
+        )
+        .unwrap();
+        assert_eq!(
+            encrypted_pkm.key_data.algorithm.oid.to_string(),
+            "1.3.101.112"
+        );
+        let restored = DeviceSecretKey::from_bytes(&device.to_bytes()).unwrap();
+        assert_eq!(
+            open_pkcs8(&restored, &encrypted_pkm).unwrap().as_bytes(),
+            PKCS8_ED25519
+        );
+
+        // Sealed to another device.
+        let other_device = DeviceSecretKey::generate(&mut rng);
+        assert!(open_pkcs8(&other_device, &encrypted_pkm).is_err());
+        // Passed off as the key material of another certificate.
+        let mut other_serial = encrypted_pkm.clone();
+        other_serial.serial_number = SerialNumber::from(8u128);
+        assert!(open_pkcs8(&device, &other_serial).is_err());
+        // Unknown encryption algorithm.
+        let mut other_algorithm = encrypted_pkm.clone();
+        other_algorithm.encryption_algorithm.oid = ObjectIdentifier::new_unwrap("1.3.101.110");
+        assert!(open_pkcs8(&device, &other_algorithm).is_err());
+        // Not a PKCS#8 document.
+        assert!(seal_pkcs8(&mut rng, &recipient, SerialNumber::from(7u128), &[0u8; 16]).is_err());
+    }
+}
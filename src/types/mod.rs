@@ -10,8 +10,17 @@ pub mod challenge_string;
 pub mod der;
 /// Module defining the [EncryptedPkm] type, as well as related subtypes.
 pub mod encrypted_pkm;
+/// Module defining the [Endpoint] type, used to describe the API and gateway endpoints of a
+/// home server.
+pub mod endpoint;
 /// Module defining the [FederationId] type.
 pub mod federation_id;
+#[cfg(feature = "hpke")]
+/// HPKE helpers sealing PKCS#8 private key material to the X25519 key of another device as an
+/// [EncryptedPkm], and opening it on that device, for onboarding additional devices of an actor.
+pub mod hpke;
+/// Module defining the [SessionId] type.
+pub mod session_id;
 /// This module contains wrappers for types from the `spki` crate which interface directly with the
 /// HTTP API of polyproto. These wrappers enable the types to be serialized and deserialized using
 /// the `serde` crate, if the `serde` feature is enabled.
@@ -23,7 +32,9 @@ pub mod x509_cert;
 
 pub use challenge_string::*;
 pub use encrypted_pkm::*;
+pub use endpoint::*;
 pub use federation_id::*;
+pub use session_id::*;
 
 /// Module defining the [Route] type, as well as `static` endpoints and their associated HTTP methods
 /// for the polyproto API. These `static`s can be used as a single source of truth for the API endpoints
@@ -61,6 +72,11 @@ pub mod routes {
                 path: "/.p2/core/v1/idcert/server",
             };
 
+            pub static GET_ENDPOINTS: Route = Route {
+                method: http::Method::GET,
+                path: "/.p2/core/v1/endpoints",
+            };
+
             pub static GET_SERVER_PUBLIC_KEY: Route = Route {
                 method: http::Method::GET,
                 path: "/.p2/core/v1/key/server",
@@ -71,6 +87,16 @@ pub mod routes {
                 path: "/.p2/core/v1/idcert/actor/",
             };
 
+            pub static GET_SUPERSEDED_NOTICE: Route = Route {
+                method: http::Method::GET,
+                path: "/.p2/core/v1/idcert/superseded/",
+            };
+
+            pub static CHECK_REVOCATION_STATUS: Route = Route {
+                method: http::Method::POST,
+                path: "/.p2/core/v1/idcert/status",
+            };
+
             pub static UPDATE_SESSION_IDCERT: Route = Route {
                 method: http::Method::PUT,
                 path: "/.p2/core/v1/session/idcert/extern",
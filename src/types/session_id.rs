@@ -0,0 +1,159 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
+use x509_cert::name::{Name, RdnSequence};
+
+use crate::errors::ConstraintError;
+use crate::types::der::asn1::Ia5String;
+use crate::{Constrained, OID_RDN_UNIQUE_IDENTIFIER};
+
+/// polyproto client Session ID. Must be unique for each client. Must be between 1 and =32
+/// characters in length, and may only consist of ASCII letters, digits, `.`, `_` and `-`. The
+/// session ID is used to uniquely identify a client in the context of polyproto. Client
+/// certificates will change over time, but the session ID of a particular client will remain the
+/// same.
+///
+/// [Constrained] is implemented for this type, meaning it can be validated using `.validate()`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SessionId {
+    /// The session ID, represented as an [Ia5String].
+    session_id: Ia5String,
+}
+
+impl Deref for SessionId {
+    type Target = Ia5String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.session_id
+    }
+}
+
+impl DerefMut for SessionId {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.session_id
+    }
+}
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.session_id.fmt(f)
+    }
+}
+
+impl SessionId {
+    #[allow(clippy::new_ret_no_self)]
+    /// Creates a new [SessionId] which can be converted into an [Attribute] using `.as_attribute()`,
+    /// if needed. Checks if the input is a valid Ia5String and if the [SessionId] constraints have
+    /// been violated.
+    ///
+    /// [Attribute]: x509_cert::attr::Attribute
+    pub fn new_validated(id: &str) -> Result<Self, ConstraintError> {
+        let ia5string = match der::asn1::Ia5String::new(id) {
+            Ok(string) => string,
+            Err(_) => {
+                return Err(ConstraintError::Malformed(Some(
+                    "Invalid Ia5String passed as SessionId".to_string(),
+                )))
+            }
+        };
+
+        let session_id = SessionId {
+            session_id: ia5string.into(),
+        };
+        session_id.validate(None)?;
+        Ok(session_id)
+    }
+
+    /// Returns the session ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.session_id.as_str()
+    }
+
+    /// Converts this [SessionId] into a [Name] for use in a certificate.
+    pub fn to_rdn_sequence(&self) -> Name {
+        RdnSequence::from_str(&format!("uniqueIdentifier={}", self)).expect(
+            "Illegal state. Please report this error to https://github.com/polyphony-chat/polyproto",
+        )
+    }
+
+    /// Reads the [SessionId] from the uniqueIdentifier attribute of `name`. Returns `None`, if
+    /// `name` carries no uniqueIdentifier, such as the name of a home server, or if its value is
+    /// not a valid [SessionId].
+    pub fn from_name(name: &Name) -> Option<Self> {
+        let attribute = name
+            .0
+            .iter()
+            .flat_map(|rdn| rdn.0.iter())
+            .find(|attribute| attribute.oid.to_string() == OID_RDN_UNIQUE_IDENTIFIER)?;
+        SessionId::new_validated(&String::from_utf8_lossy(attribute.value.value())).ok()
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = ConstraintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SessionId::new_validated(s)
+    }
+}
+
+impl From<SessionId> for Ia5String {
+    fn from(value: SessionId) -> Self {
+        value.session_id
+    }
+}
+
+impl TryFrom<Ia5String> for SessionId {
+    type Error = ConstraintError;
+
+    fn try_from(value: Ia5String) -> Result<Self, Self::Error> {
+        SessionId::new_validated(value.to_string().as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use serde::de::Visitor;
+    use serde::{Deserialize, Serialize};
+
+    use super::SessionId;
+
+    impl Serialize for SessionId {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    struct SessionIdVisitor;
+
+    impl<'de> Visitor<'de> for SessionIdVisitor {
+        type Value = SessionId;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a valid polyproto session ID")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            SessionId::new_validated(v).map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SessionId {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_str(SessionIdVisitor)
+        }
+    }
+}
@@ -3,9 +3,11 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 
 use log::trace;
 
+use crate::encoding::{decode_hex, encode_hex};
 use crate::errors::{ConversionError, InvalidInput};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -109,6 +111,129 @@ impl SerialNumber {
     }
 }
 
+impl SerialNumber {
+    /// Create a new [`SerialNumber`] from the big endian bytes of a positive integer, as parsed
+    /// by the string conversions below.
+    fn from_magnitude(bytes: &[u8]) -> Result<Self, InvalidInput> {
+        if bytes.iter().all(|byte| *byte == 0) {
+            return Err(InvalidInput::Malformed(
+                "Serial numbers must be positive".to_string(),
+            ));
+        }
+        SerialNumber::new(bytes).map_err(|error| InvalidInput::Malformed(error.to_string()))
+    }
+
+    /// The big endian bytes of the serial number, without leading zeros.
+    fn magnitude(&self) -> &[u8] {
+        let bytes = self.as_bytes();
+        let start = bytes
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(bytes.len().saturating_sub(1));
+        &bytes[start..]
+    }
+
+    /// Returns the serial number as a lowercase hex string without leading zero bytes, e.g.
+    /// `1a2b3c`.
+    pub fn to_hex(&self) -> String {
+        encode_hex(self.magnitude())
+    }
+
+    /// Parses a serial number from a hex string, such as one returned by
+    /// [`SerialNumber::to_hex()`]. Accepts upper- and lowercase digits and an odd number of
+    /// digits. Fails, if the serial number is not positive or longer than 20 octets.
+    pub fn from_hex(s: &str) -> Result<Self, InvalidInput> {
+        let bytes = match s.len() % 2 {
+            0 => decode_hex(s)?,
+            _ => decode_hex(&format!("0{}", s))?,
+        };
+        Self::from_magnitude(&bytes)
+    }
+
+    /// Returns the serial number as uppercase hex bytes separated by colons, e.g. `1A:2B:3C`, the
+    /// format most certificate viewers display serial numbers in.
+    pub fn to_colon_hex(&self) -> String {
+        self.magnitude()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Returns the serial number as a decimal string, e.g. `1715004`.
+    pub fn to_decimal(&self) -> String {
+        // Repeatedly divides the big endian magnitude by 10, collecting the remainders.
+        let mut bytes = self.magnitude().to_vec();
+        let mut digits = Vec::new();
+        while bytes.iter().any(|byte| *byte != 0) {
+            let mut remainder = 0u16;
+            for byte in bytes.iter_mut() {
+                let value = (remainder << 8) | *byte as u16;
+                *byte = (value / 10) as u8;
+                remainder = value % 10;
+            }
+            digits.push(b'0' + remainder as u8);
+        }
+        if digits.is_empty() {
+            digits.push(b'0');
+        }
+        digits.reverse();
+        String::from_utf8(digits).unwrap_or_default()
+    }
+
+    /// Parses a serial number from a decimal string, such as one returned by
+    /// [`SerialNumber::to_decimal()`]. Fails, if the serial number is not positive or longer than
+    /// 20 octets.
+    pub fn from_decimal(s: &str) -> Result<Self, InvalidInput> {
+        if s.is_empty() || !s.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(InvalidInput::Malformed(format!(
+                "Invalid decimal string: {}",
+                s
+            )));
+        }
+        // Multiplies the big endian magnitude by 10 and adds each digit in turn.
+        let mut bytes: Vec<u8> = Vec::new();
+        for digit in s.bytes() {
+            let mut carry = (digit - b'0') as u16;
+            for byte in bytes.iter_mut().rev() {
+                let value = *byte as u16 * 10 + carry;
+                *byte = value as u8;
+                carry = value >> 8;
+            }
+            if carry > 0 {
+                bytes.insert(0, carry as u8);
+            }
+            if bytes.len() > 21 {
+                return Err(InvalidInput::Length {
+                    min_length: 1,
+                    max_length: 20,
+                    actual_length: bytes.len().to_string(),
+                });
+            }
+        }
+        Self::from_magnitude(&bytes)
+    }
+}
+
+impl std::fmt::Display for SerialNumber {
+    /// Formats the serial number as colon-separated uppercase hex bytes, like
+    /// [`SerialNumber::to_colon_hex()`].
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_colon_hex())
+    }
+}
+
+impl FromStr for SerialNumber {
+    type Err = InvalidInput;
+
+    /// Parses a hex encoded serial number, optionally separated by colons, as returned by
+    /// [`SerialNumber::to_hex()`] and [`SerialNumber::to_colon_hex()`]. Use
+    /// [`SerialNumber::from_decimal()`] for decimal serial numbers.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(&s.replace(':', ""))
+    }
+}
+
 impl TryFrom<SerialNumber> for u128 {
     type Error = ConversionError;
 
@@ -184,6 +309,8 @@ mod test {
     use log::trace;
     use serde_json::json;
 
+    use std::str::FromStr;
+
     use crate::testing_utils::init_logger;
 
     use super::SerialNumber;
@@ -224,6 +351,49 @@ mod test {
         }
     }
 
+    #[test]
+    fn string_conversions() {
+        init_logger();
+        let serial_number = SerialNumber::from(0x1a2b3cu128);
+        assert_eq!(serial_number.to_hex(), "1a2b3c");
+        assert_eq!(serial_number.to_colon_hex(), "1A:2B:3C");
+        assert_eq!(serial_number.to_string(), "1A:2B:3C");
+        assert_eq!(serial_number.to_decimal(), "1715004");
+        assert_eq!(SerialNumber::from_hex("1A2B3C").unwrap(), serial_number);
+        assert_eq!(SerialNumber::from_hex("a2b3c").unwrap().to_hex(), "0a2b3c");
+        assert_eq!(SerialNumber::from_str("1A:2B:3C").unwrap(), serial_number);
+        assert_eq!(SerialNumber::from_str("1a2b3c").unwrap(), serial_number);
+        assert_eq!(
+            SerialNumber::from_decimal("1715004").unwrap(),
+            serial_number
+        );
+
+        let large = SerialNumber::new(&[0x7f; 20]).unwrap();
+        assert_eq!(
+            SerialNumber::from_str(&large.to_colon_hex()).unwrap(),
+            large
+        );
+        assert_eq!(
+            SerialNumber::from_decimal(&large.to_decimal()).unwrap(),
+            large
+        );
+        let high_bit = SerialNumber::from(u128::MAX);
+        assert_eq!(high_bit.to_hex(), "ff".repeat(16));
+        assert_eq!(high_bit.to_decimal(), u128::MAX.to_string());
+        assert_eq!(
+            SerialNumber::from_hex(&high_bit.to_hex()).unwrap(),
+            high_bit
+        );
+
+        assert!(SerialNumber::from_hex("").is_err());
+        assert!(SerialNumber::from_hex("00").is_err());
+        assert!(SerialNumber::from_hex("zz").is_err());
+        assert!(SerialNumber::from_hex(&"ff".repeat(20)).is_err());
+        assert!(SerialNumber::from_decimal("0").is_err());
+        assert!(SerialNumber::from_decimal("12a").is_err());
+        assert!(SerialNumber::from_decimal(&"9".repeat(60)).is_err());
+    }
+
     #[test]
     fn try_as_u128() {
         init_logger();
@@ -0,0 +1,783 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use der::pem::LineEnding;
+use der::zeroize::Zeroizing;
+use pkcs8::der::pem::PemLabel as _;
+
+use crate::certs::idcert::IdCert;
+use crate::certs::pem::{document_label, load_any, split_documents, PemLabels, PemObject};
+use crate::certs::Target;
+use crate::errors::{ConversionError, InvalidInput};
+use crate::key::{Pkcs8PrivateKey, PrivateKey};
+use crate::signature::Signature;
+use crate::Constrained;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An [IdCert] together with the private key belonging to its subject.
+pub struct VaultEntry<S: Signature, K: PrivateKey<S>> {
+    /// The certificate.
+    pub id_cert: IdCert<S, K::PublicKey>,
+    /// The private key, whose public key is the subject public key of `id_cert`.
+    pub private_key: K,
+    /// The [Target] of the certificate, detected from its capabilities.
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The identity material of an actor or home server: its [IdCert]s with their private keys, and
+/// additional certificates without a private key, such as the certificate of the home server of
+/// an actor.
+///
+/// An [IdentityVault] can be written to and read from a PEM bundle, which concatenates the PEM
+/// documents of the certificates and of the plaintext PKCS#8 private keys. With the `age`
+/// feature, vaults can be restored from bundles which were encrypted using
+/// [age](https://age-encryption.org), either to a passphrase or to age identities, as commonly
+/// done by existing backup tooling. With the `pkcs12` feature, vaults can also be moved to and
+/// from tools and platforms which only speak PKCS#12 (.p12/.pfx).
+pub struct IdentityVault<S: Signature, K: Pkcs8PrivateKey<S>> {
+    entries: Vec<VaultEntry<S, K>>,
+    certificates: Vec<IdCert<S, K::PublicKey>>,
+}
+
+impl<S: Signature, K: Pkcs8PrivateKey<S>> Default for IdentityVault<S, K> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            certificates: Vec::new(),
+        }
+    }
+}
+
+impl<S: Signature, K: Pkcs8PrivateKey<S>> IdentityVault<S, K> {
+    /// Creates an empty [IdentityVault].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an [IdCert] with its private key. The certificate is validated against the polyproto
+    /// constraints for its detected [Target]. Fails, if the public key of `private_key` is not the
+    /// subject public key of `id_cert`.
+    pub fn insert(
+        &mut self,
+        id_cert: IdCert<S, K::PublicKey>,
+        private_key: K,
+    ) -> Result<(), ConversionError> {
+        if private_key.pubkey() != &id_cert.id_cert_tbs.subject_public_key {
+            return Err(InvalidInput::Malformed(
+                "The private key does not belong to the subject of the certificate".to_string(),
+            )
+            .into());
+        }
+        let target = Target::detect(&id_cert.id_cert_tbs.capabilities);
+        id_cert.validate(Some(target))?;
+        self.entries.push(VaultEntry {
+            id_cert,
+            private_key,
+            target,
+        });
+        Ok(())
+    }
+
+    /// Adds an [IdCert] for which no private key is held. The certificate is validated against
+    /// the polyproto constraints for its detected [Target].
+    pub fn insert_certificate(
+        &mut self,
+        id_cert: IdCert<S, K::PublicKey>,
+    ) -> Result<(), ConversionError> {
+        id_cert.validate(Some(Target::detect(&id_cert.id_cert_tbs.capabilities)))?;
+        self.certificates.push(id_cert);
+        Ok(())
+    }
+
+    /// Returns the certificates held together with their private keys.
+    pub fn entries(&self) -> &[VaultEntry<S, K>] {
+        &self.entries
+    }
+
+    /// Returns the certificates held without a private key.
+    pub fn certificates(&self) -> &[IdCert<S, K::PublicKey>] {
+        &self.certificates
+    }
+
+    /// Returns the first entry with the given [Target], if any.
+    pub fn entry_for(&self, target: Target) -> Option<&VaultEntry<S, K>> {
+        self.entries.iter().find(|entry| entry.target == target)
+    }
+
+    /// Returns the number of entries, not counting certificates without a private key.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true`, if the vault holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Reads an [IdentityVault] from a PEM bundle, as written by [Self::to_pem_bundle()].
+    ///
+    /// Certificates may use the standard or the target-specific [PemLabels]. Private keys must be
+    /// plaintext PKCS#8 documents with the label `PRIVATE KEY`, and are paired with the
+    /// certificate carrying their public key, regardless of the order of the documents.
+    /// Certificates without a matching private key are kept in [Self::certificates()]. Fails, if a
+    /// document can not be decoded, if a certificate is invalid, if the bundle contains a CSR or CRL, or
+    /// if a private key does not belong to any certificate.
+    ///
+    /// The signatures of the certificates are not verified; use
+    /// [IdCert::full_verify_actor()] or [IdCert::full_verify_home_server()] before trusting them.
+    pub fn from_pem_bundle(bundle: &str) -> Result<Self, ConversionError> {
+        let mut certificates = Vec::new();
+        let mut keys = Vec::new();
+        for document in split_documents(bundle)? {
+            if document_label(document) == Some(pkcs8::PrivateKeyInfo::PEM_LABEL) {
+                let (_, der) = pkcs8::SecretDocument::from_pem(document)?;
+                keys.push(K::from_pkcs8_der(der.as_bytes())?);
+                continue;
+            }
+            match load_any::<S, K::PublicKey>(document)? {
+                PemObject::Certificate { cert, .. } => certificates.push(cert),
+                PemObject::CertificateRequest { .. } => {
+                    return Err(InvalidInput::Malformed(
+                        "Identity backups must not contain certificate signing requests"
+                            .to_string(),
+                    )
+                    .into())
+                }
+                PemObject::RevocationList { .. } => {
+                    return Err(InvalidInput::Malformed(
+                        "Identity backups must not contain certificate revocation lists"
+                            .to_string(),
+                    )
+                    .into())
+                }
+            }
+        }
+        log::trace!(
+            "[IdentityVault::from_pem_bundle()] Found {} certificates and {} private keys",
+            certificates.len(),
+            keys.len()
+        );
+        Self::from_parts(certificates, keys)
+    }
+
+    /// Pairs each private key in `keys` with the certificate carrying its public key. Fails, if a
+    /// certificate is invalid or if a private key does not belong to any certificate.
+    fn from_parts(
+        certificates: Vec<IdCert<S, K::PublicKey>>,
+        mut keys: Vec<K>,
+    ) -> Result<Self, ConversionError> {
+        let mut vault = Self::new();
+        for cert in certificates {
+            match keys
+                .iter()
+                .position(|key| key.pubkey() == &cert.id_cert_tbs.subject_public_key)
+            {
+                Some(index) => vault.insert(cert, keys.swap_remove(index))?,
+                None => vault.insert_certificate(cert)?,
+            }
+        }
+        if !keys.is_empty() {
+            return Err(InvalidInput::Malformed(format!(
+                "{} private key(s) in the backup do not belong to any certificate",
+                keys.len()
+            ))
+            .into());
+        }
+        Ok(vault)
+    }
+
+    /// Writes this vault as a PEM bundle, which can be read using [Self::from_pem_bundle()]. Each
+    /// certificate is followed by its private key; certificates without a private key come last.
+    ///
+    /// The private keys are written **in plaintext**. The bundle is meant to be encrypted, e.g.
+    /// using age, before it is stored anywhere.
+    pub fn to_pem_bundle(
+        &self,
+        line_ending: LineEnding,
+        labels: PemLabels,
+    ) -> Result<Zeroizing<String>, ConversionError> {
+        let mut bundle = Zeroizing::new(String::new());
+        for entry in self.entries.iter() {
+            bundle.push_str(
+                &entry
+                    .id_cert
+                    .clone()
+                    .to_pem_with_labels(line_ending, labels)?,
+            );
+            let key = entry.private_key.to_pkcs8_der()?;
+            bundle.push_str(&key.to_pem(pkcs8::PrivateKeyInfo::PEM_LABEL, line_ending)?);
+        }
+        for cert in self.certificates.iter() {
+            bundle.push_str(&cert.to_pem_with_labels(line_ending, labels)?);
+        }
+        Ok(bundle)
+    }
+
+    /// Decrypts an age encrypted PEM bundle using one of `identities`, and reads an
+    /// [IdentityVault] from it using [Self::from_pem_bundle()]. Both binary and ASCII-armored age
+    /// files are accepted.
+    #[cfg(feature = "age")]
+    pub fn from_age(
+        ciphertext: &[u8],
+        identities: &[&dyn age::Identity],
+    ) -> Result<Self, ConversionError> {
+        let decryptor = age::Decryptor::new(age::armor::ArmoredReader::new(ciphertext))?;
+        Self::from_age_decryptor(decryptor, identities)
+    }
+
+    /// Decrypts a PEM bundle, which was encrypted to `passphrase` using age, and reads an
+    /// [IdentityVault] from it using [Self::from_pem_bundle()]. Both binary and ASCII-armored age
+    /// files are accepted. Fails, if the file was not encrypted to a passphrase or if the
+    /// passphrase is wrong.
+    #[cfg(feature = "age")]
+    pub fn from_age_passphrase(
+        ciphertext: &[u8],
+        passphrase: &str,
+    ) -> Result<Self, ConversionError> {
+        let decryptor = age::Decryptor::new(age::armor::ArmoredReader::new(ciphertext))?;
+        if !decryptor.is_scrypt() {
+            return Err(ConversionError::AgeError(
+                "The age file is not encrypted to a passphrase".to_string(),
+            ));
+        }
+        let identity =
+            age::scrypt::Identity::new(age::secrecy::SecretString::from(passphrase.to_string()));
+        Self::from_age_decryptor(decryptor, &[&identity])
+    }
+
+    #[cfg(feature = "age")]
+    fn from_age_decryptor<R: std::io::Read>(
+        decryptor: age::Decryptor<R>,
+        identities: &[&dyn age::Identity],
+    ) -> Result<Self, ConversionError> {
+        use std::io::Read;
+        log::trace!("[IdentityVault::from_age()] Decrypting identity backup");
+        let mut reader = decryptor.decrypt(identities.iter().copied())?;
+        let mut bundle = Zeroizing::new(String::new());
+        if let Err(e) = reader.read_to_string(&mut bundle) {
+            return Err(ConversionError::AgeError(e.to_string()));
+        }
+        Self::from_pem_bundle(&bundle)
+    }
+}
+
+#[cfg(feature = "pkcs12")]
+mod pkcs12_support {
+    use cms::content_info::{CmsVersion, ContentInfo};
+    use cms::encrypted_data::EncryptedData;
+    use cms::enveloped_data::EncryptedContentInfo;
+    use der::asn1::{OctetString, SetOfVec};
+    use der::oid::ObjectIdentifier;
+    use der::{Any, AnyRef, Decode, Encode, Tag, TagNumber, Tagged};
+    use hmac::{Hmac, Mac};
+    use pkcs12::cert_type::CertBag;
+    use pkcs12::digest_info::DigestInfo;
+    use pkcs12::kdf::{derive_key_utf8, Pkcs12KeyType};
+    use pkcs12::mac_data::MacData;
+    use pkcs12::pfx::{Pfx, Version};
+    use pkcs12::safe_bag::{SafeBag, SafeContents};
+    use pkcs12::{
+        PKCS_12_CERT_BAG_OID, PKCS_12_KEY_BAG_OID, PKCS_12_PKCS8_KEY_BAG_OID, PKCS_12_X509_CERT_OID,
+    };
+    use pkcs8::pkcs5::{pbes2, EncryptionScheme};
+    use rand_core::CryptoRngCore;
+    use sha2::{Digest, Sha256};
+    use spki::AlgorithmIdentifierOwned;
+    use x509_cert::attr::{Attribute, Attributes};
+
+    use super::IdentityVault;
+    use crate::certs::idcert::IdCert;
+    use crate::errors::{ConversionError, InvalidInput};
+    use crate::key::{Pbes2Kdf, Pkcs8PrivateKey};
+    use crate::signature::Signature;
+
+    const ID_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+    const ID_ENCRYPTED_DATA: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.6");
+    const ID_PBES2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.5.13");
+    const ID_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+    const ID_LOCAL_KEY_ID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.21");
+
+    impl<S: Signature, K: Pkcs8PrivateKey<S>> IdentityVault<S, K> {
+        /// Writes this vault as a password protected PKCS#12 (.p12/.pfx) file, which can be read
+        /// using [Self::from_pkcs12()] and by tools and platforms which import PKCS#12 files,
+        /// such as OpenSSL, browsers and operating system key stores.
+        ///
+        /// The file is laid out like those written by OpenSSL 3: the certificates are encrypted
+        /// using PBES2 with PBKDF2-HMAC-SHA256 and AES-256-CBC, each private key is stored as an
+        /// encrypted PKCS#8 document, and the file is integrity protected with an HMAC-SHA256 MAC.
+        /// All keys are derived from `password` using `iterations` iterations. Each private key
+        /// is linked to its certificate using a `localKeyId` attribute. Salts and IVs are drawn
+        /// from `rng`.
+        pub fn to_pkcs12(
+            &self,
+            rng: &mut impl CryptoRngCore,
+            password: &str,
+            iterations: u32,
+        ) -> Result<Vec<u8>, ConversionError> {
+            log::trace!(
+                "[IdentityVault::to_pkcs12()] Encoding {} entries and {} certificates",
+                self.entries.len(),
+                self.certificates.len()
+            );
+            let mut cert_bags = SafeContents::new();
+            let mut key_bags = SafeContents::new();
+            for entry in self.entries.iter() {
+                let cert = entry.id_cert.to_der()?;
+                let local_key_id = local_key_id(&cert)?;
+                cert_bags.push(cert_bag(&cert, Some(local_key_id.clone()))?);
+                let key = entry.private_key.to_encrypted_pkcs8_der(
+                    rng,
+                    password,
+                    Pbes2Kdf::Pbkdf2 { iterations },
+                )?;
+                key_bags.push(SafeBag {
+                    bag_id: PKCS_12_PKCS8_KEY_BAG_OID,
+                    bag_value: key.as_bytes().to_vec(),
+                    bag_attributes: Some(local_key_id),
+                });
+            }
+            for cert in self.certificates.iter() {
+                cert_bags.push(cert_bag(&cert.to_der()?, None)?);
+            }
+
+            let auth_safe = vec![
+                encrypted_data(rng, password, iterations, &cert_bags.to_der()?)?,
+                data(key_bags.to_der()?)?,
+            ]
+            .to_der()?;
+            let mut mac_salt = [0u8; 16];
+            rng.fill_bytes(&mut mac_salt);
+            let iterations = match i32::try_from(iterations) {
+                Ok(iterations) => iterations,
+                Err(_) => {
+                    return Err(InvalidInput::Malformed(format!(
+                        "Invalid iteration count {}",
+                        iterations
+                    ))
+                    .into())
+                }
+            };
+            let mac_data = MacData {
+                mac: DigestInfo {
+                    algorithm: AlgorithmIdentifierOwned {
+                        oid: ID_SHA256,
+                        parameters: None,
+                    },
+                    digest: OctetString::new(
+                        mac(password, &mac_salt, iterations, &auth_safe)?
+                            .finalize()
+                            .into_bytes()
+                            .to_vec(),
+                    )?,
+                },
+                mac_salt: OctetString::new(mac_salt.to_vec())?,
+                iterations,
+            };
+            Ok(Pfx {
+                version: Version::V3,
+                auth_safe: data(auth_safe)?,
+                mac_data: Some(mac_data),
+            }
+            .to_der()?)
+        }
+
+        /// Reads an [IdentityVault] from a password protected PKCS#12 (.p12/.pfx) file, as
+        /// written by [Self::to_pkcs12()] or by other tools. Private keys are paired with the
+        /// certificate carrying their public key, like in [Self::from_pem_bundle()].
+        ///
+        /// The MAC of the file is verified before anything is decrypted; only HMAC-SHA256 MACs and
+        /// PBES2 encryption are supported. Files using the legacy algorithms of PKCS#12, such as
+        /// those written by `openssl pkcs12 -export -legacy`, are rejected. Fails, if the password
+        /// is wrong, if a bag other than a certificate or private key bag is found, or for the
+        /// same reasons as [Self::from_pem_bundle()].
+        ///
+        /// The signatures of the certificates are not verified; use
+        /// [IdCert::full_verify_actor()] or [IdCert::full_verify_home_server()] before trusting
+        /// them.
+        pub fn from_pkcs12(bytes: &[u8], password: &str) -> Result<Self, ConversionError> {
+            let pfx = Pfx::from_der(bytes)?;
+            let auth_safe = data_content(&pfx.auth_safe)?;
+            match pfx.mac_data {
+                Some(mac_data) => verify_mac(password, &mac_data, &auth_safe)?,
+                None => {
+                    return Err(ConversionError::Pkcs12Error(
+                        "The PKCS#12 file is not integrity protected".to_string(),
+                    ))
+                }
+            }
+
+            let mut certificates = Vec::new();
+            let mut keys = Vec::new();
+            for content_info in Vec::<ContentInfo>::from_der(&auth_safe)? {
+                let safe_contents = match content_info.content_type {
+                    ID_DATA => data_content(&content_info)?,
+                    ID_ENCRYPTED_DATA => encrypted_data_content(&content_info, password)?,
+                    content_type => {
+                        return Err(ConversionError::Pkcs12Error(format!(
+                            "Unsupported content type {}",
+                            content_type
+                        )))
+                    }
+                };
+                for bag in SafeContents::from_der(&safe_contents)? {
+                    let bag_value = bag_value(&bag)?;
+                    match bag.bag_id {
+                        PKCS_12_CERT_BAG_OID => {
+                            let cert_bag = CertBag::from_der(bag_value)?;
+                            if cert_bag.cert_id != PKCS_12_X509_CERT_OID {
+                                return Err(ConversionError::Pkcs12Error(format!(
+                                    "Unsupported certificate type {}",
+                                    cert_bag.cert_id
+                                )));
+                            }
+                            certificates
+                                .push(IdCert::from_der_unchecked(cert_bag.cert_value.as_bytes())?);
+                        }
+                        PKCS_12_PKCS8_KEY_BAG_OID => {
+                            keys.push(K::from_encrypted_pkcs8_der(bag_value, password)?)
+                        }
+                        PKCS_12_KEY_BAG_OID => keys.push(K::from_pkcs8_der(bag_value)?),
+                        bag_id => {
+                            return Err(ConversionError::Pkcs12Error(format!(
+                                "Unsupported bag type {}",
+                                bag_id
+                            )))
+                        }
+                    }
+                }
+            }
+            log::trace!(
+                "[IdentityVault::from_pkcs12()] Found {} certificates and {} private keys",
+                certificates.len(),
+                keys.len()
+            );
+            Self::from_parts(certificates, keys)
+        }
+    }
+
+    /// Returns the DER encoding of the value of `bag`. [SafeBag]s decoded by the `pkcs12` crate
+    /// keep the explicit `[0]` tag around their value, while encoding expects it to be absent.
+    fn bag_value(bag: &SafeBag) -> Result<&[u8], ConversionError> {
+        let value = AnyRef::from_der(&bag.bag_value)?;
+        match value.tag() {
+            Tag::ContextSpecific {
+                constructed: true,
+                number: TagNumber::N0,
+            } => Ok(value.value()),
+            _ => Ok(&bag.bag_value),
+        }
+    }
+
+    /// Returns a `localKeyId` attribute identifying the certificate `cert` and its private key.
+    fn local_key_id(cert: &[u8]) -> Result<Attributes, ConversionError> {
+        let attribute = Attribute {
+            oid: ID_LOCAL_KEY_ID,
+            values: SetOfVec::try_from(vec![Any::encode_from(&OctetString::new(
+                Sha256::digest(cert).to_vec(),
+            )?)?])?,
+        };
+        Ok(SetOfVec::try_from(vec![attribute])?)
+    }
+
+    fn cert_bag(cert: &[u8], attributes: Option<Attributes>) -> Result<SafeBag, ConversionError> {
+        let cert_bag = CertBag {
+            cert_id: PKCS_12_X509_CERT_OID,
+            cert_value: OctetString::new(cert)?,
+        };
+        Ok(SafeBag {
+            bag_id: PKCS_12_CERT_BAG_OID,
+            bag_value: cert_bag.to_der()?,
+            bag_attributes: attributes,
+        })
+    }
+
+    /// Wraps `content` in a `ContentInfo` of type `data`.
+    fn data(content: Vec<u8>) -> Result<ContentInfo, ConversionError> {
+        Ok(ContentInfo {
+            content_type: ID_DATA,
+            content: Any::encode_from(&OctetString::new(content)?)?,
+        })
+    }
+
+    /// Encrypts `content` using PBES2, and wraps it in a `ContentInfo` of type `encryptedData`.
+    fn encrypted_data(
+        rng: &mut impl CryptoRngCore,
+        password: &str,
+        iterations: u32,
+        content: &[u8],
+    ) -> Result<ContentInfo, ConversionError> {
+        let mut salt = [0u8; 16];
+        rng.fill_bytes(&mut salt);
+        let mut iv = [0u8; 16];
+        rng.fill_bytes(&mut iv);
+        let params = pbes2::Parameters::pbkdf2_sha256_aes256cbc(iterations, &salt, &iv)
+            .map_err(pkcs8::Error::from)?;
+        let ciphertext = params
+            .encrypt(password, content)
+            .map_err(pkcs8::Error::from)?;
+        let encrypted_data = EncryptedData {
+            version: CmsVersion::V0,
+            enc_content_info: EncryptedContentInfo {
+                content_type: ID_DATA,
+                content_enc_alg: AlgorithmIdentifierOwned::from_der(
+                    &EncryptionScheme::from(params).to_der()?,
+                )?,
+                encrypted_content: Some(OctetString::new(ciphertext)?),
+            },
+            unprotected_attrs: None,
+        };
+        Ok(ContentInfo {
+            content_type: ID_ENCRYPTED_DATA,
+            content: Any::encode_from(&encrypted_data)?,
+        })
+    }
+
+    /// Returns the content of a `ContentInfo` of type `data`.
+    fn data_content(content_info: &ContentInfo) -> Result<Vec<u8>, ConversionError> {
+        if content_info.content_type != ID_DATA {
+            return Err(ConversionError::Pkcs12Error(format!(
+                "Expected content of type data, found {}",
+                content_info.content_type
+            )));
+        }
+        Ok(content_info
+            .content
+            .decode_as::<OctetString>()?
+            .into_bytes())
+    }
+
+    /// Decrypts the content of a `ContentInfo` of type `encryptedData`.
+    fn encrypted_data_content(
+        content_info: &ContentInfo,
+        password: &str,
+    ) -> Result<Vec<u8>, ConversionError> {
+        let encrypted = content_info
+            .content
+            .decode_as::<EncryptedData>()?
+            .enc_content_info;
+        if encrypted.content_enc_alg.oid != ID_PBES2 {
+            return Err(ConversionError::Pkcs12Error(format!(
+                "Unsupported encryption algorithm {}",
+                encrypted.content_enc_alg.oid
+            )));
+        }
+        let algorithm = encrypted.content_enc_alg.to_der()?;
+        let scheme =
+            EncryptionScheme::try_from(algorithm.as_slice()).map_err(pkcs8::Error::from)?;
+        let ciphertext = match encrypted.encrypted_content {
+            Some(ciphertext) => ciphertext,
+            None => {
+                return Err(ConversionError::Pkcs12Error(
+                    "The encrypted content is missing".to_string(),
+                ))
+            }
+        };
+        Ok(scheme
+            .decrypt(password, ciphertext.as_bytes())
+            .map_err(pkcs8::Error::from)?)
+    }
+
+    /// Returns an HMAC-SHA256 instance over `content`, keyed with the PKCS#12 MAC key derived from
+    /// `password`.
+    fn mac(
+        password: &str,
+        salt: &[u8],
+        iterations: i32,
+        content: &[u8],
+    ) -> Result<Hmac<Sha256>, ConversionError> {
+        let key = derive_key_utf8::<Sha256>(password, salt, Pkcs12KeyType::Mac, iterations, 32)?;
+        let mut mac = match Hmac::<Sha256>::new_from_slice(&key) {
+            Ok(mac) => mac,
+            Err(e) => return Err(InvalidInput::Malformed(e.to_string()).into()),
+        };
+        mac.update(content);
+        Ok(mac)
+    }
+
+    fn verify_mac(
+        password: &str,
+        mac_data: &MacData,
+        content: &[u8],
+    ) -> Result<(), ConversionError> {
+        if mac_data.mac.algorithm.oid != ID_SHA256 {
+            return Err(ConversionError::Pkcs12Error(format!(
+                "Unsupported MAC algorithm {}",
+                mac_data.mac.algorithm.oid
+            )));
+        }
+        mac(
+            password,
+            mac_data.mac_salt.as_bytes(),
+            mac_data.iterations,
+            content,
+        )?
+        .verify_slice(mac_data.mac.digest.as_bytes())
+        .map_err(|_| {
+            ConversionError::Pkcs12Error(
+                "The MAC of the PKCS#12 file does not match; the password is wrong or the file is corrupted"
+                    .to_string(),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns a vault holding an actor certificate with its private key, and the certificate of
+    /// its home server.
+    #[cfg(all(
+        any(feature = "age", feature = "pkcs12"),
+        feature = "ed25519",
+        not(feature = "fips")
+    ))]
+    fn actor_vault() -> IdentityVault<
+        crate::backends::ed25519::Ed25519Signature,
+        crate::backends::ed25519::Ed25519PrivateKey,
+    > {
+        use std::str::FromStr;
+        use std::time::Duration;
+
+        use der::asn1::Uint;
+        use x509_cert::name::Name;
+        use x509_cert::time::Validity;
+
+        use crate::backends::ed25519::{Ed25519PrivateKey, Ed25519Signature};
+        use crate::certs::capabilities::Capabilities;
+        use crate::certs::idcsr::IdCsr;
+
+        crate::testing_utils::init_logger();
+        let home_server_key = Ed25519PrivateKey::gen_keypair(&mut rand::rngs::OsRng);
+        let actor_key = Ed25519PrivateKey::gen_keypair(&mut rand::rngs::OsRng);
+        let home_server_subject = Name::from_str("DC=polyphony,DC=chat").unwrap();
+        let validity = Validity::from_now(Duration::from_secs(60)).unwrap();
+        let home_server_cert = IdCert::from_ca_csr(
+            IdCsr::new(
+                &home_server_subject,
+                &home_server_key,
+                &Capabilities::home_server_default(),
+                Some(Target::HomeServer),
+            )
+            .unwrap(),
+            &home_server_key,
+            Uint::new(&[1]).unwrap(),
+            home_server_subject.clone(),
+            validity,
+        )
+        .unwrap();
+        let actor_cert = IdCert::from_actor_csr(
+            IdCsr::new(
+                &Name::from_str(
+                    "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+                )
+                .unwrap(),
+                &actor_key,
+                &Capabilities::actor_default(),
+                Some(Target::Actor),
+            )
+            .unwrap(),
+            &home_server_key,
+            Uint::new(&[2]).unwrap(),
+            home_server_subject,
+            validity,
+        )
+        .unwrap();
+
+        let mut vault = IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::new();
+        assert!(vault
+            .insert(actor_cert.clone(), home_server_key.clone())
+            .is_err());
+        vault.insert(actor_cert, actor_key).unwrap();
+        vault.insert_certificate(home_server_cert).unwrap();
+        vault
+    }
+
+    #[cfg(all(feature = "age", feature = "ed25519", not(feature = "fips")))]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn import_age_encrypted_backup() {
+        use std::io::Write;
+
+        use crate::backends::ed25519::{Ed25519PrivateKey, Ed25519Signature};
+
+        let vault = actor_vault();
+        let bundle = vault
+            .to_pem_bundle(LineEnding::LF, PemLabels::TargetSpecific)
+            .unwrap();
+        assert_eq!(
+            IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_pem_bundle(&bundle).unwrap(),
+            vault
+        );
+
+        let mut recipient =
+            age::scrypt::Recipient::new(age::secrecy::SecretString::from("hunter2".to_string()));
+        recipient.set_work_factor(2);
+        let encryptor =
+            age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient))
+                .unwrap();
+        let mut ciphertext = Vec::new();
+        let mut armored =
+            age::armor::ArmoredWriter::wrap_output(&mut ciphertext, age::armor::Format::AsciiArmor)
+                .unwrap();
+        let mut writer = encryptor.wrap_output(&mut armored).unwrap();
+        writer.write_all(bundle.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        armored.finish().unwrap();
+
+        let restored = IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_age_passphrase(
+            &ciphertext,
+            "hunter2",
+        )
+        .unwrap();
+        assert_eq!(restored, vault);
+        assert_eq!(
+            restored.entry_for(Target::Actor).unwrap().id_cert,
+            vault.entries()[0].id_cert
+        );
+        assert!(
+            IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_age_passphrase(
+                &ciphertext,
+                "hunter3",
+            )
+            .is_err()
+        );
+        let x25519 = age::x25519::Identity::generate();
+        assert!(matches!(
+            IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_age(&ciphertext, &[&x25519]),
+            Err(ConversionError::AgeError(_))
+        ));
+    }
+
+    #[cfg(all(feature = "pkcs12", feature = "ed25519", not(feature = "fips")))]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn pkcs12_round_trip() {
+        use crate::backends::ed25519::{Ed25519PrivateKey, Ed25519Signature};
+
+        let vault = actor_vault();
+        let p12 = vault
+            .to_pkcs12(&mut rand::rngs::OsRng, "hunter2", 2048)
+            .unwrap();
+        let restored =
+            IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_pkcs12(&p12, "hunter2")
+                .unwrap();
+        assert_eq!(restored, vault);
+        assert!(matches!(
+            IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_pkcs12(&p12, "hunter3"),
+            Err(ConversionError::Pkcs12Error(_))
+        ));
+        let mut corrupted = p12.clone();
+        let index = corrupted.len() / 2;
+        corrupted[index] ^= 0x01;
+        assert!(
+            IdentityVault::<Ed25519Signature, Ed25519PrivateKey>::from_pkcs12(
+                &corrupted, "hunter2"
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,498 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use spki::ObjectIdentifier;
+
+use crate::certs::capabilities::OID_KEY_USAGE;
+use crate::certs::chain::validate_certs;
+use crate::certs::crl::IdCrl;
+use crate::certs::idcert::IdCert;
+use crate::certs::superseded::{CertUsage, SupersededCerts};
+use crate::certs::Target;
+use crate::errors::{ConstraintError, InvalidCert};
+use crate::key::PublicKey;
+use crate::signature::Signature;
+use crate::timestamp::Timestamp;
+
+/// The [VerificationQueue](queue::VerificationQueue), which schedules verification jobs of many
+/// peers fairly, by priority and subject to rate limits.
+pub mod queue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The resources which are limited by a [VerificationBudget].
+pub enum BudgetResource {
+    /// The number of signature verifications.
+    SignatureVerifications,
+    /// The length of a certificate chain.
+    ChainLength,
+    /// The number of certificate revocation list entries examined.
+    CrlEntries,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Upper bounds for the amount of work a [Verifier] may perform.
+///
+/// Verifying signatures, walking certificate chains and examining revocation lists is expensive.
+/// Inbound messages from untrusted peers should be verified using a [Verifier] with a budget, so
+/// that a malicious peer cannot trigger an unbounded amount of work with a single message.
+pub struct VerificationBudget {
+    /// The maximum number of signature verifications.
+    pub max_signature_verifications: usize,
+    /// The maximum number of certificates in a certificate chain.
+    pub max_chain_length: usize,
+    /// The maximum number of certificate revocation list entries examined.
+    pub max_crl_entries: usize,
+}
+
+impl Default for VerificationBudget {
+    fn default() -> Self {
+        Self {
+            max_signature_verifications: 16,
+            max_chain_length: 4,
+            max_crl_entries: 10_000,
+        }
+    }
+}
+
+impl VerificationBudget {
+    /// A budget which never runs out. Should only be used for trusted input.
+    pub fn unlimited() -> Self {
+        Self {
+            max_signature_verifications: usize::MAX,
+            max_chain_length: usize::MAX,
+            max_crl_entries: usize::MAX,
+        }
+    }
+
+    /// Returns the limit for the given [BudgetResource].
+    pub fn limit(&self, resource: BudgetResource) -> usize {
+        match resource {
+            BudgetResource::SignatureVerifications => self.max_signature_verifications,
+            BudgetResource::ChainLength => self.max_chain_length,
+            BudgetResource::CrlEntries => self.max_crl_entries,
+        }
+    }
+}
+
+/// OIDs of signature algorithms which are considered deprecated. Certificates signed using one of
+/// these algorithms produce a [ValidationWarning::DeprecatedAlgorithm].
+pub const DEPRECATED_SIGNATURE_ALGORITHMS: &[&str] = &[
+    // md5WithRSAEncryption
+    "1.2.840.113549.1.1.4",
+    // sha1WithRSAEncryption
+    "1.2.840.113549.1.1.5",
+    // ecdsa-with-SHA1
+    "1.2.840.10045.4.1",
+    // id-dsa-with-sha1
+    "1.2.840.10040.4.3",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The severity of a [ValidationWarning] or a violated [Rule](crate::rules::Rule), ordered from
+/// least to most severe.
+pub enum Severity {
+    /// Purely informational. No action is required.
+    Info,
+    /// The certificate is valid, but should be looked at, as it may stop being valid or stop
+    /// interoperating in the future.
+    Warning,
+    /// The value is invalid. Never used for [ValidationWarning]s.
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A non-fatal finding from validating an [IdCert].
+///
+/// Unlike an [InvalidCert] error, a [ValidationWarning] does not cause validation to fail. Warnings
+/// are returned alongside success by the `*_with_warnings` methods of the [Verifier], so that
+/// callers can surface advisories without failing interoperation.
+pub enum ValidationWarning {
+    /// The certificate expires within the
+    /// [expiry threshold](WarningPolicy::expiry_threshold).
+    ExpiresSoon {
+        /// The time left until the end of the validity period of the certificate.
+        remaining: Duration,
+    },
+    /// The certificate is signed using one of the [DEPRECATED_SIGNATURE_ALGORITHMS].
+    DeprecatedAlgorithm {
+        /// The OID of the deprecated signature algorithm.
+        oid: ObjectIdentifier,
+    },
+    /// The certificate is missing an extension which is not required, but recommended.
+    MissingRecommendedExtension {
+        /// The OID of the missing extension.
+        oid: ObjectIdentifier,
+    },
+}
+
+impl ValidationWarning {
+    /// Returns the [Severity] of this warning.
+    pub fn severity(&self) -> Severity {
+        match self {
+            ValidationWarning::ExpiresSoon { .. } => Severity::Warning,
+            ValidationWarning::DeprecatedAlgorithm { .. } => Severity::Warning,
+            ValidationWarning::MissingRecommendedExtension { .. } => Severity::Info,
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationWarning::ExpiresSoon { remaining } => write!(
+                f,
+                "The certificate expires in {} seconds",
+                remaining.as_secs()
+            ),
+            ValidationWarning::DeprecatedAlgorithm { oid } => write!(
+                f,
+                "The certificate is signed using the deprecated algorithm {}",
+                oid
+            ),
+            ValidationWarning::MissingRecommendedExtension { oid } => write!(
+                f,
+                "The certificate is missing the recommended extension {}",
+                oid
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Configures which [ValidationWarning]s a [Verifier] produces.
+pub struct WarningPolicy {
+    /// Certificates expiring within this duration produce a [ValidationWarning::ExpiresSoon].
+    pub expiry_threshold: Duration,
+}
+
+impl Default for WarningPolicy {
+    /// An expiry threshold of seven days.
+    fn default() -> Self {
+        Self {
+            expiry_threshold: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl WarningPolicy {
+    /// Inspects an [IdCert] for [ValidationWarning]s at the given time. Does not validate or
+    /// verify the certificate.
+    pub fn inspect<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+        let not_after = Timestamp::from(cert.id_cert_tbs.validity.not_after);
+        let remaining =
+            Duration::from_secs(not_after.unix_seconds().saturating_sub(time.unix_seconds()));
+        if remaining < self.expiry_threshold {
+            warnings.push(ValidationWarning::ExpiresSoon { remaining });
+        }
+        let algorithm = cert.id_cert_tbs.signature_algorithm.oid;
+        if DEPRECATED_SIGNATURE_ALGORITHMS
+            .iter()
+            .any(|deprecated| algorithm.to_string().as_str() == *deprecated)
+        {
+            warnings.push(ValidationWarning::DeprecatedAlgorithm { oid: algorithm });
+        }
+        if cert
+            .id_cert_tbs
+            .capabilities
+            .key_usage
+            .key_usages
+            .is_empty()
+        {
+            warnings.push(ValidationWarning::MissingRecommendedExtension {
+                oid: ObjectIdentifier::from_str(OID_KEY_USAGE).unwrap(),
+            });
+        }
+        for warning in warnings.iter() {
+            log::debug!("[WarningPolicy::inspect()] {}", warning);
+        }
+        warnings
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// A facade for verifying [IdCert]s and signatures, which keeps track of the work performed and
+/// enforces a [VerificationBudget].
+///
+/// A [Verifier] is meant to be created per inbound request or message. Once the budget is
+/// exhausted, all further operations fail with [InvalidCert::BudgetExceeded] until
+/// [Verifier::reset()] is called.
+pub struct Verifier {
+    budget: VerificationBudget,
+    warning_policy: WarningPolicy,
+    clock_skew_tolerance: Duration,
+    signature_verifications: usize,
+    crl_entries: usize,
+}
+
+impl Verifier {
+    /// Creates a new [Verifier] with the given [VerificationBudget].
+    pub fn new(budget: VerificationBudget) -> Self {
+        Self {
+            budget,
+            warning_policy: WarningPolicy::default(),
+            clock_skew_tolerance: Duration::ZERO,
+            signature_verifications: 0,
+            crl_entries: 0,
+        }
+    }
+
+    /// Sets the [WarningPolicy] used by the `*_with_warnings` methods.
+    pub fn with_warning_policy(mut self, warning_policy: WarningPolicy) -> Self {
+        self.warning_policy = warning_policy;
+        self
+    }
+
+    /// Sets the clock skew tolerated when checking the validity period of certificates, see
+    /// [IdCert::valid_at_with_tolerance()]. Defaults to no tolerance.
+    pub fn with_clock_skew_tolerance(mut self, tolerance: Duration) -> Self {
+        self.clock_skew_tolerance = tolerance;
+        self
+    }
+
+    /// Returns the [VerificationBudget] of this [Verifier].
+    pub fn budget(&self) -> &VerificationBudget {
+        &self.budget
+    }
+
+    /// Returns the [WarningPolicy] of this [Verifier].
+    pub fn warning_policy(&self) -> &WarningPolicy {
+        &self.warning_policy
+    }
+
+    /// Returns the clock skew tolerated when checking the validity period of certificates.
+    pub fn clock_skew_tolerance(&self) -> Duration {
+        self.clock_skew_tolerance
+    }
+
+    /// Returns the number of signature verifications performed so far.
+    pub fn signature_verifications(&self) -> usize {
+        self.signature_verifications
+    }
+
+    /// Returns the number of certificate revocation list entries examined so far.
+    pub fn crl_entries_examined(&self) -> usize {
+        self.crl_entries
+    }
+
+    /// Resets all counters, restoring the full budget.
+    pub fn reset(&mut self) {
+        self.signature_verifications = 0;
+        self.crl_entries = 0;
+    }
+
+    /// Verifies `signature` over `data` using `public_key`, consuming one signature verification
+    /// from the budget.
+    pub fn verify_signature<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        public_key: &P,
+        signature: &S,
+        data: &[u8],
+    ) -> Result<(), InvalidCert> {
+        self.charge_signature_verification()?;
+        Ok(public_key.verify_signature(signature, data)?)
+    }
+
+    /// Verifies an actor [IdCert] like [IdCert::full_verify_actor()], consuming one signature
+    /// verification from the budget. The validity period is checked with the
+    /// [clock skew tolerance](Verifier::with_clock_skew_tolerance()) of this [Verifier].
+    pub fn verify_actor<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+        home_server_public_key: &P,
+    ) -> Result<(), InvalidCert> {
+        self.charge_signature_verification()?;
+        self.check_validity(cert, time)?;
+        cert.verify_signature(home_server_public_key)
+    }
+
+    /// Like [Self::verify_actor()], but additionally rejects certificates which have been
+    /// superseded according to `superseded`, unless they are used to verify data signed before
+    /// the certificate was superseded. For [CertUsage::NewSession], the validity of the certificate
+    /// is checked at `time`; for [CertUsage::PastData], at the time the data was signed.
+    pub fn verify_actor_for_usage<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+        usage: CertUsage,
+        time: Timestamp,
+        home_server_public_key: &P,
+        superseded: &SupersededCerts,
+    ) -> Result<(), InvalidCert> {
+        superseded.check(cert, usage)?;
+        let time = match usage {
+            CertUsage::NewSession => time,
+            CertUsage::PastData { signed_at } => signed_at,
+        };
+        self.verify_actor(cert, time, home_server_public_key)
+    }
+
+    /// Verifies a home server [IdCert] like [IdCert::full_verify_home_server()], consuming one
+    /// signature verification from the budget. The validity period is checked with the
+    /// [clock skew tolerance](Verifier::with_clock_skew_tolerance()) of this [Verifier].
+    pub fn verify_home_server<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        self.charge_signature_verification()?;
+        self.check_validity(cert, time)?;
+        cert.verify_signature(&cert.id_cert_tbs.subject_public_key)
+    }
+
+    /// Verifies a certificate chain, ordered from the actor certificate to the self-signed root
+    /// certificate of the home server, like
+    /// [IdCertChain::validate_chain()](crate::certs::chain::IdCertChain::validate_chain()) with
+    /// [Target::Actor]: the actor certificate must be valid for [Target::Actor], every issuer must
+    /// be a home server certificate marked as a CA whose path length constraint is not exceeded,
+    /// every certificate must be issued and signed by the certificate following it, and the root
+    /// certificate must be self-signed. The validity periods are checked with the
+    /// [clock skew tolerance](Verifier::with_clock_skew_tolerance()) of this [Verifier].
+    ///
+    /// The length of the chain is checked against the budget before any signature is verified.
+    /// Each certificate in the chain consumes one signature verification, all of which are charged
+    /// before the chain is validated.
+    pub fn verify_chain<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        chain: &[IdCert<S, P>],
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        if chain.len() > self.budget.max_chain_length {
+            log::debug!(
+                "[Verifier::verify_chain()] Chain of length {} exceeds budget",
+                chain.len()
+            );
+            return Err(InvalidCert::BudgetExceeded(BudgetResource::ChainLength));
+        }
+        let (leaf, issuers) = match chain.split_first() {
+            Some(split) => split,
+            None => {
+                return Err(InvalidCert::InvalidProperties(ConstraintError::Malformed(
+                    Some("Certificate chain is empty".to_string()),
+                )))
+            }
+        };
+        for _ in chain {
+            self.charge_signature_verification()?;
+        }
+        validate_certs(
+            leaf,
+            issuers,
+            time,
+            Target::Actor,
+            self.clock_skew_tolerance,
+        )
+    }
+
+    /// Like [Self::verify_actor()], but additionally returns the [ValidationWarning]s for the
+    /// certificate on success.
+    pub fn verify_actor_with_warnings<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+        home_server_public_key: &P,
+    ) -> Result<Vec<ValidationWarning>, InvalidCert> {
+        self.verify_actor(cert, time, home_server_public_key)?;
+        Ok(self.warning_policy.inspect(cert, time))
+    }
+
+    /// Like [Self::verify_home_server()], but additionally returns the [ValidationWarning]s for
+    /// the certificate on success.
+    pub fn verify_home_server_with_warnings<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<Vec<ValidationWarning>, InvalidCert> {
+        self.verify_home_server(cert, time)?;
+        Ok(self.warning_policy.inspect(cert, time))
+    }
+
+    /// Like [Self::verify_chain()], but additionally returns the [ValidationWarning]s for every
+    /// certificate in the chain on success. The warnings are returned in the order of the chain,
+    /// each paired with the index of the certificate it concerns.
+    pub fn verify_chain_with_warnings<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        chain: &[IdCert<S, P>],
+        time: Timestamp,
+    ) -> Result<Vec<(usize, ValidationWarning)>, InvalidCert> {
+        self.verify_chain(chain, time)?;
+        Ok(chain
+            .iter()
+            .enumerate()
+            .flat_map(|(index, cert)| {
+                self.warning_policy
+                    .inspect(cert, time)
+                    .into_iter()
+                    .map(move |warning| (index, warning))
+            })
+            .collect())
+    }
+
+    /// Consumes `entries` certificate revocation list entries from the budget. Must be called
+    /// before examining the entries.
+    pub fn charge_crl_entries(&mut self, entries: usize) -> Result<(), InvalidCert> {
+        let total = self.crl_entries.saturating_add(entries);
+        if total > self.budget.max_crl_entries {
+            return Err(InvalidCert::BudgetExceeded(BudgetResource::CrlEntries));
+        }
+        self.crl_entries = total;
+        Ok(())
+    }
+
+    /// Checks `cert` against a certificate revocation list using [IdCrl::check()], consuming one
+    /// CRL entry from the budget for every entry of the CRL. The CRL is expected to have been
+    /// verified by the caller.
+    pub fn check_revocation<S: Signature, P: PublicKey<S>>(
+        &mut self,
+        cert: &IdCert<S, P>,
+        crl: &IdCrl<S>,
+    ) -> Result<(), InvalidCert> {
+        self.charge_crl_entries(crl.id_crl_tbs.revoked.len())?;
+        crl.check(cert)
+    }
+
+    fn check_validity<S: Signature, P: PublicKey<S>>(
+        &self,
+        cert: &IdCert<S, P>,
+        time: Timestamp,
+    ) -> Result<(), InvalidCert> {
+        if !cert.valid_at_with_tolerance(time, self.clock_skew_tolerance) {
+            log::debug!(
+                "[Verifier] Certificate is not valid at {}, tolerating a clock skew of {} seconds",
+                time,
+                self.clock_skew_tolerance.as_secs()
+            );
+            return Err(InvalidCert::InvalidValidity);
+        }
+        Ok(())
+    }
+
+    fn charge_signature_verification(&mut self) -> Result<(), InvalidCert> {
+        if self.signature_verifications >= self.budget.max_signature_verifications {
+            log::debug!("[Verifier] Signature verification budget exceeded");
+            return Err(InvalidCert::BudgetExceeded(
+                BudgetResource::SignatureVerifications,
+            ));
+        }
+        self.signature_verifications += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,579 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::errors::{InvalidCert, QueueError};
+use crate::timestamp::Timestamp;
+
+use super::{VerificationBudget, Verifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The priority class of a job in a [VerificationQueue].
+pub enum Priority {
+    /// Verifications a user is waiting for, such as those of incoming messages. Run before
+    /// [Priority::Backfill] jobs.
+    Interactive,
+    /// Verifications which are not time-critical, such as those of fetched message history.
+    Backfill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A token bucket limiting how many jobs a single peer may submit to a [VerificationQueue].
+pub struct RateLimit {
+    /// The number of jobs a peer may submit at once.
+    pub burst: u32,
+    /// The number of jobs a peer may submit per second, once the burst is used up.
+    pub per_second: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Configuration of a [VerificationQueue].
+pub struct QueueConfig {
+    /// The [VerificationBudget] of the [Verifier] each job is run with.
+    pub budget: VerificationBudget,
+    /// The maximum number of pending jobs of all peers.
+    pub max_pending: usize,
+    /// The maximum number of pending jobs of a single peer.
+    pub max_pending_per_peer: usize,
+    /// The rate at which a single peer may submit jobs, or `None` for no limit.
+    pub rate_limit: Option<RateLimit>,
+    /// The number of [Priority::Interactive] jobs run in a row while [Priority::Backfill] jobs are
+    /// waiting, before one backfill job is run. Keeps a steady stream of interactive jobs from
+    /// starving backfill entirely.
+    pub interactive_burst: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            budget: VerificationBudget::default(),
+            max_pending: 4096,
+            max_pending_per_peer: 64,
+            rate_limit: Some(RateLimit {
+                burst: 64,
+                per_second: 16,
+            }),
+            interactive_burst: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// Counters describing the work done by a [VerificationQueue], e.g. for exporting them to a
+/// metrics system.
+pub struct QueueMetrics {
+    /// The number of accepted jobs.
+    pub submitted: u64,
+    /// The number of jobs which have been run.
+    pub completed: u64,
+    /// The number of jobs which have been run, and failed.
+    pub failed: u64,
+    /// The number of jobs which have been run, and exceeded their [VerificationBudget].
+    pub budget_exceeded: u64,
+    /// The number of signature verifications performed by all jobs.
+    pub signature_verifications: u64,
+    /// The number of jobs rejected, because their peer exceeded its [RateLimit].
+    pub rate_limited: u64,
+    /// The number of jobs rejected, because the queue or the queue of their peer was full.
+    pub rejected_full: u64,
+    /// The number of pending [Priority::Interactive] jobs.
+    pub pending_interactive: usize,
+    /// The number of pending [Priority::Backfill] jobs.
+    pub pending_backfill: usize,
+}
+
+type Job = Box<dyn FnOnce(&mut Verifier) -> Result<(), InvalidCert> + Send>;
+
+struct TokenBucket {
+    tokens: u32,
+    refilled_at: Timestamp,
+}
+
+struct Peer {
+    interactive: VecDeque<Job>,
+    backfill: VecDeque<Job>,
+    bucket: Option<TokenBucket>,
+}
+
+impl Peer {
+    fn pending(&self) -> usize {
+        self.interactive.len() + self.backfill.len()
+    }
+
+    fn jobs(&mut self, priority: Priority) -> &mut VecDeque<Job> {
+        match priority {
+            Priority::Interactive => &mut self.interactive,
+            Priority::Backfill => &mut self.backfill,
+        }
+    }
+}
+
+struct State<K> {
+    config: QueueConfig,
+    peers: HashMap<K, Peer>,
+    /// Peers with pending interactive jobs, in round-robin order.
+    interactive: VecDeque<K>,
+    /// Peers with pending backfill jobs, in round-robin order.
+    backfill: VecDeque<K>,
+    interactive_streak: usize,
+    metrics: QueueMetrics,
+    workers: Vec<Waker>,
+    closed: bool,
+}
+
+impl<K: Eq + Hash + Clone> State<K> {
+    fn pending(&self) -> usize {
+        self.metrics.pending_interactive + self.metrics.pending_backfill
+    }
+
+    fn take_token(&mut self, peer: &K, now: Timestamp) -> Result<(), QueueError> {
+        let rate_limit = match self.config.rate_limit {
+            Some(rate_limit) => rate_limit,
+            None => return Ok(()),
+        };
+        let bucket = match self.peers.get_mut(peer) {
+            Some(Peer {
+                bucket: Some(bucket),
+                ..
+            }) => bucket,
+            _ => return Ok(()),
+        };
+        let elapsed = now
+            .unix_seconds()
+            .saturating_sub(bucket.refilled_at.unix_seconds());
+        if elapsed > 0 {
+            let refill = elapsed.saturating_mul(rate_limit.per_second as u64);
+            bucket.tokens = (bucket.tokens as u64)
+                .saturating_add(refill)
+                .min(rate_limit.burst as u64) as u32;
+            bucket.refilled_at = now;
+        }
+        if bucket.tokens == 0 {
+            return Err(QueueError::RateLimited(Duration::from_secs(1)));
+        }
+        bucket.tokens -= 1;
+        Ok(())
+    }
+
+    /// Forgets peers without pending jobs, whose rate limit would have recovered completely by
+    /// `now`, since their state is no longer needed.
+    fn prune(&mut self, now: Timestamp) -> usize {
+        let rate_limit = self.config.rate_limit;
+        let before = self.peers.len();
+        self.peers.retain(|_, peer| {
+            peer.pending() > 0
+                || match (&peer.bucket, rate_limit) {
+                    (Some(bucket), Some(rate_limit)) => {
+                        let elapsed = now
+                            .unix_seconds()
+                            .saturating_sub(bucket.refilled_at.unix_seconds());
+                        (bucket.tokens as u64)
+                            .saturating_add(elapsed.saturating_mul(rate_limit.per_second as u64))
+                            < rate_limit.burst as u64
+                    }
+                    _ => false,
+                }
+        });
+        before - self.peers.len()
+    }
+
+    /// Pops the next job, alternating between peers within each priority class.
+    fn next_job(&mut self) -> Option<Job> {
+        let backfill_waiting = !self.backfill.is_empty();
+        let backfill_due =
+            backfill_waiting && self.interactive_streak >= self.config.interactive_burst;
+        let priority = if !self.interactive.is_empty() && !backfill_due {
+            self.interactive_streak += 1;
+            Priority::Interactive
+        } else if backfill_waiting {
+            self.interactive_streak = 0;
+            Priority::Backfill
+        } else {
+            return None;
+        };
+        let ring = match priority {
+            Priority::Interactive => &mut self.interactive,
+            Priority::Backfill => &mut self.backfill,
+        };
+        let key = ring.pop_front()?;
+        let peer = self.peers.get_mut(&key)?;
+        let job = peer.jobs(priority).pop_front()?;
+        if !peer.jobs(priority).is_empty() {
+            ring.push_back(key);
+        }
+        match priority {
+            Priority::Interactive => self.metrics.pending_interactive -= 1,
+            Priority::Backfill => self.metrics.pending_backfill -= 1,
+        }
+        Some(job)
+    }
+}
+
+/// A queue scheduling verification jobs submitted on behalf of peers, such as remote home servers
+/// or connected clients.
+///
+/// Without a queue, a single hostile peer flooding a home server with certificates can keep it
+/// busy verifying signatures, starving the verifications of all other peers. A
+/// [VerificationQueue] prevents this by
+///
+/// - limiting the rate at which each peer may submit jobs using a [RateLimit],
+/// - bounding the number of pending jobs, both in total and per peer,
+/// - taking turns between peers with pending jobs, so that a peer with many pending jobs does not
+///   delay the jobs of other peers, and
+/// - running [Priority::Interactive] jobs before [Priority::Backfill] jobs.
+///
+/// Each job is run with a fresh [Verifier], which enforces the [VerificationBudget] of the
+/// [QueueConfig].
+///
+/// The queue does not depend on an async runtime. [VerificationQueue::submit()] returns a
+/// [VerificationTicket], a future resolving to the result of the job. Jobs are run by
+/// [VerificationQueue::run()], which should be spawned as one or more tasks of the runtime of the
+/// application, or synchronously by [VerificationQueue::run_pending()]. Since verifying signatures
+/// is CPU-bound, [VerificationQueue::run()] should be spawned on a thread suitable for blocking
+/// work.
+///
+/// [VerificationQueue] is cheap to clone; all clones share the same queue.
+pub struct VerificationQueue<K> {
+    state: Arc<Mutex<State<K>>>,
+}
+
+impl<K> Clone for VerificationQueue<K> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> std::fmt::Debug for VerificationQueue<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("VerificationQueue")
+            .field("config", &state.config)
+            .field("metrics", &state.metrics)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for VerificationQueue<K> {
+    fn default() -> Self {
+        Self::new(QueueConfig::default())
+    }
+}
+
+impl<K: Eq + Hash + Clone> VerificationQueue<K> {
+    /// Creates a new, empty [VerificationQueue].
+    pub fn new(config: QueueConfig) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                config,
+                peers: HashMap::new(),
+                interactive: VecDeque::new(),
+                backfill: VecDeque::new(),
+                interactive_streak: 0,
+                metrics: QueueMetrics::default(),
+                workers: Vec::new(),
+                closed: false,
+            })),
+        }
+    }
+
+    /// Returns the [QueueConfig] of this queue.
+    pub fn config(&self) -> QueueConfig {
+        self.lock().config
+    }
+
+    /// Returns a snapshot of the [QueueMetrics] of this queue.
+    pub fn metrics(&self) -> QueueMetrics {
+        self.lock().metrics
+    }
+
+    /// Returns the number of pending jobs.
+    pub fn pending(&self) -> usize {
+        self.lock().pending()
+    }
+
+    /// Returns the number of pending jobs of `peer`.
+    pub fn pending_for(&self, peer: &K) -> usize {
+        self.lock().peers.get(peer).map_or(0, Peer::pending)
+    }
+
+    /// Forgets the state of peers which have no pending jobs and whose [RateLimit] has fully
+    /// recovered at `time`. Should be called periodically, so that the queue does not keep growing
+    /// with the number of peers ever seen. Returns the number of peers forgotten.
+    pub fn prune(&self, time: Timestamp) -> usize {
+        self.lock().prune(time)
+    }
+
+    /// Submits `job` on behalf of `peer`. `job` is run with a [Verifier] enforcing the
+    /// [VerificationBudget] of the queue, and should perform all its verifications through it.
+    ///
+    /// Fails with [QueueError::RateLimited], if `peer` exceeded its [RateLimit] at `time`, with
+    /// [QueueError::PeerQueueFull] or [QueueError::QueueFull], if too many jobs are pending, and
+    /// with [QueueError::Closed], if the queue has been closed.
+    pub fn submit<T, F>(
+        &self,
+        peer: K,
+        priority: Priority,
+        time: Timestamp,
+        job: F,
+    ) -> Result<VerificationTicket<T>, QueueError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Verifier) -> Result<T, InvalidCert> + Send + 'static,
+    {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(QueueError::Closed);
+        }
+        let peer_pending = state.peers.get(&peer).map_or(0, Peer::pending);
+        if peer_pending >= state.config.max_pending_per_peer {
+            state.metrics.rejected_full += 1;
+            return Err(QueueError::PeerQueueFull);
+        }
+        if state.pending() >= state.config.max_pending {
+            state.metrics.rejected_full += 1;
+            return Err(QueueError::QueueFull);
+        }
+        if let Err(e) = state.take_token(&peer, time) {
+            state.metrics.rate_limited += 1;
+            return Err(e);
+        }
+
+        let slot = Arc::new(Mutex::new(Slot::Pending(None)));
+        let mut completion = Completion {
+            slot: Some(slot.clone()),
+        };
+        let job: Job = Box::new(move |verifier| {
+            let result = job(verifier);
+            let outcome = result.as_ref().map(|_| ()).map_err(Clone::clone);
+            completion.complete(result.map_err(QueueError::from));
+            outcome
+        });
+
+        let rate_limit = state.config.rate_limit;
+        let entry = state.peers.entry(peer.clone()).or_insert_with(|| Peer {
+            interactive: VecDeque::new(),
+            backfill: VecDeque::new(),
+            bucket: rate_limit.map(|rate_limit| TokenBucket {
+                // The job being submitted uses up the first token.
+                tokens: rate_limit.burst.saturating_sub(1),
+                refilled_at: time,
+            }),
+        });
+        let was_idle = entry.jobs(priority).is_empty();
+        entry.jobs(priority).push_back(job);
+        if was_idle {
+            match priority {
+                Priority::Interactive => state.interactive.push_back(peer),
+                Priority::Backfill => state.backfill.push_back(peer),
+            }
+        }
+        match priority {
+            Priority::Interactive => state.metrics.pending_interactive += 1,
+            Priority::Backfill => state.metrics.pending_backfill += 1,
+        }
+        state.metrics.submitted += 1;
+        for worker in state.workers.drain(..) {
+            worker.wake();
+        }
+        Ok(VerificationTicket { slot })
+    }
+
+    /// Runs the next pending job, if any. Returns `false`, if no job was pending.
+    pub fn run_one(&self) -> bool {
+        let job = match self.lock().next_job() {
+            Some(job) => job,
+            None => return false,
+        };
+        self.execute(job);
+        true
+    }
+
+    /// Runs pending jobs until none are left. Returns the number of jobs run.
+    pub fn run_pending(&self) -> usize {
+        let mut count = 0;
+        while self.run_one() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Runs jobs as they are submitted, until the queue is closed using
+    /// [VerificationQueue::close()]. Multiple instances of this future may run concurrently.
+    pub async fn run(&self) {
+        while let Some(job) = (NextJob { queue: self }).await {
+            self.execute(job);
+        }
+        log::trace!("[VerificationQueue::run()] Queue closed, stopping worker");
+    }
+
+    /// Closes the queue. Further submissions fail with [QueueError::Closed], pending jobs are
+    /// dropped and their [VerificationTicket]s resolve to [QueueError::Closed], and all
+    /// [VerificationQueue::run()] futures complete.
+    pub fn close(&self) {
+        let (peers, workers) = {
+            let mut state = self.lock();
+            state.closed = true;
+            state.interactive.clear();
+            state.backfill.clear();
+            state.metrics.pending_interactive = 0;
+            state.metrics.pending_backfill = 0;
+            (
+                std::mem::take(&mut state.peers),
+                std::mem::take(&mut state.workers),
+            )
+        };
+        // Dropping the jobs resolves their tickets, which must happen without holding the lock.
+        drop(peers);
+        for worker in workers {
+            worker.wake();
+        }
+    }
+
+    fn execute(&self, job: Job) {
+        let budget = self.lock().config.budget;
+        let mut verifier = Verifier::new(budget);
+        let result = job(&mut verifier);
+        let mut state = self.lock();
+        state.metrics.completed += 1;
+        state.metrics.signature_verifications += verifier.signature_verifications() as u64;
+        match result {
+            Ok(()) => (),
+            Err(InvalidCert::BudgetExceeded(resource)) => {
+                log::debug!(
+                    "[VerificationQueue] Job exceeded its budget for {:?}",
+                    resource
+                );
+                state.metrics.failed += 1;
+                state.metrics.budget_exceeded += 1;
+            }
+            Err(_) => state.metrics.failed += 1,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<K>> {
+        // A panicking job never holds the lock, so the state can not be left inconsistent.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+struct NextJob<'a, K> {
+    queue: &'a VerificationQueue<K>,
+}
+
+impl<K: Eq + Hash + Clone> Future for NextJob<'_, K> {
+    type Output = Option<Job>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.queue.lock();
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        match state.next_job() {
+            Some(job) => Poll::Ready(Some(job)),
+            None => {
+                state.workers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+enum Slot<T> {
+    Pending(Option<Waker>),
+    Done(Result<T, QueueError>),
+    Taken,
+}
+
+/// Resolves the [VerificationTicket] of a job. Resolves it to [QueueError::Closed], if the job is
+/// dropped without being run.
+struct Completion<T> {
+    slot: Option<Arc<Mutex<Slot<T>>>>,
+}
+
+impl<T> Completion<T> {
+    fn complete(&mut self, result: Result<T, QueueError>) {
+        let slot = match self.slot.take() {
+            Some(slot) => slot,
+            None => return,
+        };
+        let mut slot = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Slot::Pending(Some(waker)) = std::mem::replace(&mut *slot, Slot::Done(result)) {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        self.complete(Err(QueueError::Closed));
+    }
+}
+
+/// A future resolving to the result of a job submitted to a [VerificationQueue].
+pub struct VerificationTicket<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> std::fmt::Debug for VerificationTicket<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VerificationTicket")
+            .field("done", &self.is_done())
+            .finish()
+    }
+}
+
+impl<T> VerificationTicket<T> {
+    /// Returns `true`, if the job has been run or dropped, i.e. if awaiting this ticket completes
+    /// immediately.
+    pub fn is_done(&self) -> bool {
+        !matches!(*self.lock(), Slot::Pending(_))
+    }
+
+    /// Returns the result of the job without waiting, or `None`, if it has not been run yet or the
+    /// result has already been taken.
+    pub fn try_take(&self) -> Option<Result<T, QueueError>> {
+        let mut slot = self.lock();
+        match std::mem::replace(&mut *slot, Slot::Taken) {
+            Slot::Done(result) => Some(result),
+            other => {
+                *slot = other;
+                None
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Slot<T>> {
+        self.slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Future for VerificationTicket<T> {
+    type Output = Result<T, QueueError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.lock();
+        match std::mem::replace(&mut *slot, Slot::Taken) {
+            Slot::Done(result) => Poll::Ready(result),
+            Slot::Pending(_) => {
+                *slot = Slot::Pending(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            Slot::Taken => Poll::Ready(Err(QueueError::Closed)),
+        }
+    }
+}
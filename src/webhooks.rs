@@ -0,0 +1,475 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::api::HttpResult;
+use crate::certs::certid::CertId;
+use crate::encoding::{decode_hex, encode_hex};
+use crate::errors::{InvalidInput, WebhookError};
+use crate::key::{PrivateKey, PublicKey};
+use crate::signature::Signature;
+use crate::timestamp::{SystemTimeSource, TimeSource, Timestamp};
+
+/// The name of the HTTP header carrying the signature of a webhook.
+pub static HEADER_SIGNATURE: &str = "X-P2-Webhook-Signature";
+/// The name of the HTTP header carrying the time at which a webhook was signed, in seconds since
+/// the Unix epoch.
+pub static HEADER_TIMESTAMP: &str = "X-P2-Webhook-Timestamp";
+/// The default maximum age of a received webhook, after which it is rejected to prevent replays.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// An event on a home server which operators may want to forward to external systems.
+pub enum WebhookEvent {
+    /// An ID-Cert has been issued.
+    IdCertIssued {
+        /// The issued certificate.
+        cert_id: CertId,
+        /// The subject of the issued certificate, as an RFC 4514 string.
+        subject: String,
+        /// The end of the validity period of the issued certificate.
+        not_after: Timestamp,
+    },
+    /// An ID-Cert has been revoked.
+    IdCertRevoked {
+        /// The revoked certificate.
+        cert_id: CertId,
+        /// A human-readable reason for the revocation.
+        reason: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// The JSON body of a webhook request.
+pub struct WebhookPayload {
+    /// An identifier for this payload, which stays the same across delivery attempts. Receivers
+    /// can use it to deduplicate deliveries.
+    pub id: String,
+    /// The time at which the event occurred.
+    pub time: Timestamp,
+    /// The event itself.
+    pub event: WebhookEvent,
+}
+
+impl WebhookPayload {
+    /// Creates a new [WebhookPayload] for an event which occurred at `time`. The `id` is derived
+    /// from the event and the time.
+    pub fn new(event: WebhookEvent, time: Timestamp) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(time.unix_seconds().to_be_bytes());
+        hasher.update(serde_json::to_vec(&event).unwrap_or_default());
+        Self {
+            id: encode_hex(&hasher.finalize()[..16]),
+            time,
+            event,
+        }
+    }
+}
+
+/// Authenticates outgoing webhooks, by creating the value of the [HEADER_SIGNATURE] header for a
+/// message.
+pub trait WebhookSigner: Send + Sync {
+    /// Returns the value of the [HEADER_SIGNATURE] header for `message`.
+    fn signature_header(&self, message: &[u8]) -> String;
+}
+
+#[derive(Clone)]
+/// Authenticates webhooks using HMAC-SHA256 with a secret shared between sender and receiver.
+///
+/// The resulting signature header has the form `hmac-sha256=<hex encoded MAC>`.
+pub struct HmacSigner {
+    secret: Vec<u8>,
+}
+
+impl HmacSigner {
+    /// Creates a new [HmacSigner] with the given shared secret.
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+}
+
+impl std::fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+impl WebhookSigner for HmacSigner {
+    fn signature_header(&self, message: &[u8]) -> String {
+        format!(
+            "hmac-sha256={}",
+            encode_hex(&hmac_sha256(&self.secret, message))
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Authenticates webhooks by signing them with the private key belonging to an ID-Cert.
+///
+/// The resulting signature header has the form `cert=<CertId>;sig=<hex encoded signature>`, so
+/// that receivers can look up the public key of the sender before verifying the signature.
+pub struct CertSigner<S: Signature, K: PrivateKey<S>> {
+    key: K,
+    cert_id: CertId,
+    s: PhantomData<S>,
+}
+
+impl<S: Signature, K: PrivateKey<S>> CertSigner<S, K> {
+    /// Creates a new [CertSigner] from a private key and the [CertId] of the ID-Cert containing
+    /// the corresponding public key.
+    pub fn new(key: K, cert_id: CertId) -> Self {
+        Self {
+            key,
+            cert_id,
+            s: PhantomData,
+        }
+    }
+}
+
+impl<S: Signature + Send + Sync, K: PrivateKey<S> + Send + Sync> WebhookSigner
+    for CertSigner<S, K>
+{
+    fn signature_header(&self, message: &[u8]) -> String {
+        let signature = match self.key.sign(message).to_bitstring() {
+            Ok(bitstring) => encode_hex(bitstring.raw_bytes()),
+            Err(_) => String::new(),
+        };
+        format!("cert={};sig={}", self.cert_id, signature)
+    }
+}
+
+/// The message which is signed for a webhook: The timestamp in seconds since the Unix epoch, a
+/// `.` and the request body.
+fn signed_message(time: Timestamp, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.", time.unix_seconds()).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+fn hmac_sha256(secret: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn check_timestamp(
+    timestamp_header: &str,
+    now: Timestamp,
+    tolerance: Duration,
+) -> Result<Timestamp, WebhookError> {
+    let time = match timestamp_header.trim().parse::<u64>() {
+        Ok(seconds) => Timestamp::from_unix_seconds(seconds),
+        Err(_) => {
+            return Err(InvalidInput::Malformed(format!(
+                "Invalid {} header: {}",
+                HEADER_TIMESTAMP, timestamp_header
+            ))
+            .into())
+        }
+    };
+    let difference = time.unix_seconds().abs_diff(now.unix_seconds());
+    if difference > tolerance.as_secs() {
+        return Err(WebhookError::OutsideTolerance);
+    }
+    Ok(time)
+}
+
+fn parse_payload(body: &[u8]) -> Result<WebhookPayload, WebhookError> {
+    match serde_json::from_slice(body) {
+        Ok(payload) => Ok(payload),
+        Err(e) => Err(InvalidInput::Malformed(e.to_string()).into()),
+    }
+}
+
+/// Verifies a webhook authenticated using an [HmacSigner], and returns its payload.
+///
+/// `signature_header` and `timestamp_header` are the values of the [HEADER_SIGNATURE] and
+/// [HEADER_TIMESTAMP] headers of the request. Webhooks signed more than `tolerance` before or
+/// after `now` are rejected.
+pub fn verify_hmac(
+    secret: &[u8],
+    signature_header: &str,
+    timestamp_header: &str,
+    body: &[u8],
+    now: Timestamp,
+    tolerance: Duration,
+) -> Result<WebhookPayload, WebhookError> {
+    let mac = match signature_header.trim().strip_prefix("hmac-sha256=") {
+        Some(mac) => decode_hex(mac).map_err(|_| WebhookError::BadSignature)?,
+        None => return Err(WebhookError::BadSignature),
+    };
+    let time = check_timestamp(timestamp_header, now, tolerance)?;
+    let mut verifier = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    verifier.update(&signed_message(time, body));
+    if verifier.verify_slice(&mac).is_err() {
+        return Err(WebhookError::BadSignature);
+    }
+    parse_payload(body)
+}
+
+/// Returns the [CertId] of the ID-Cert whose key signed a webhook authenticated using a
+/// [CertSigner]. Receivers use this to look up the public key to pass to [verify_cert_signed()].
+pub fn signing_cert_id(signature_header: &str) -> Result<CertId, WebhookError> {
+    let (cert_id, _) = split_cert_signature_header(signature_header)?;
+    Ok(cert_id)
+}
+
+fn split_cert_signature_header(signature_header: &str) -> Result<(CertId, Vec<u8>), WebhookError> {
+    let mut cert_id = None;
+    let mut signature = None;
+    for part in signature_header.trim().split(';') {
+        match part.split_once('=') {
+            Some(("cert", value)) => {
+                cert_id = Some(CertId::from_str(value).map_err(|_| WebhookError::BadSignature)?)
+            }
+            Some(("sig", value)) => {
+                signature = Some(decode_hex(value).map_err(|_| WebhookError::BadSignature)?)
+            }
+            _ => return Err(WebhookError::BadSignature),
+        }
+    }
+    match (cert_id, signature) {
+        (Some(cert_id), Some(signature)) => Ok((cert_id, signature)),
+        _ => Err(WebhookError::BadSignature),
+    }
+}
+
+/// Verifies a webhook authenticated using a [CertSigner], and returns the [CertId] of the
+/// signing ID-Cert together with the payload.
+///
+/// `public_key` must be the public key of the ID-Cert identified by [signing_cert_id()]. The
+/// caller is responsible for checking that this ID-Cert is valid and trusted.
+pub fn verify_cert_signed<S: Signature, P: PublicKey<S>>(
+    public_key: &P,
+    signature_header: &str,
+    timestamp_header: &str,
+    body: &[u8],
+    now: Timestamp,
+    tolerance: Duration,
+) -> Result<(CertId, WebhookPayload), WebhookError> {
+    let (cert_id, signature) = split_cert_signature_header(signature_header)?;
+    let time = check_timestamp(timestamp_header, now, tolerance)?;
+    if public_key
+        .verify_signature(&S::from_bytes(&signature), &signed_message(time, body))
+        .is_err()
+    {
+        return Err(WebhookError::BadSignature);
+    }
+    Ok((cert_id, parse_payload(body)?))
+}
+
+#[derive(Clone)]
+/// Delivers signed [WebhookPayload]s to a single receiver, retrying failed deliveries with
+/// exponential backoff.
+///
+/// A delivery is retried if the receiver cannot be reached, or responds with a server error or
+/// `429 Too Many Requests`. Other error responses are not retried.
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+    url: Url,
+    signer: Arc<dyn WebhookSigner>,
+    max_attempts: u32,
+    retry_delay: Duration,
+    time_source: Arc<dyn TimeSource>,
+}
+
+impl std::fmt::Debug for WebhookDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookDispatcher")
+            .field("url", &self.url)
+            .field("max_attempts", &self.max_attempts)
+            .field("retry_delay", &self.retry_delay)
+            .field("time_source", &self.time_source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebhookDispatcher {
+    /// Creates a new [WebhookDispatcher] delivering to `url`, which tries each delivery up to 5
+    /// times, starting with a delay of one second between attempts.
+    pub fn new(url: &str, signer: impl WebhookSigner + 'static) -> HttpResult<Self> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url: Url::parse(url)?,
+            signer: Arc::new(signer),
+            max_attempts: 5,
+            retry_delay: Duration::from_secs(1),
+            time_source: Arc::new(SystemTimeSource),
+        })
+    }
+
+    /// Sets the maximum number of delivery attempts and the delay before the first retry. The
+    /// delay doubles with every further retry. A `max_attempts` of `0` is treated as `1`.
+    pub fn with_retry(mut self, max_attempts: u32, retry_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Sets the [reqwest::Client] used for deliveries.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sets the [TimeSource] used to timestamp payloads and deliveries. Defaults to the
+    /// [SystemTimeSource].
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Arc::new(time_source);
+        self
+    }
+
+    /// Delivers an event which occurred just now, returning the delivered [WebhookPayload].
+    pub async fn deliver(&self, event: WebhookEvent) -> HttpResult<WebhookPayload> {
+        let payload = WebhookPayload::new(event, self.time_source.now());
+        self.deliver_payload(&payload).await?;
+        Ok(payload)
+    }
+
+    /// Delivers a [WebhookPayload]. Each attempt is signed anew, so that retries are not
+    /// rejected by receivers for being too old.
+    pub async fn deliver_payload(&self, payload: &WebhookPayload) -> HttpResult<()> {
+        let body = serde_json::to_vec(payload)?;
+        let mut delay = self.retry_delay;
+        let mut attempt = 1;
+        loop {
+            let time = self.time_source.now();
+            let result = self
+                .client
+                .post(self.url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(HEADER_TIMESTAMP, time.unix_seconds().to_string())
+                .header(
+                    HEADER_SIGNATURE,
+                    self.signer.signature_header(&signed_message(time, &body)),
+                )
+                .body(body.clone())
+                .send()
+                .await;
+            let retryable = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if !retryable || attempt >= self.max_attempts {
+                result?.error_for_status()?;
+                return Ok(());
+            }
+            log::debug!(
+                "[WebhookDispatcher::deliver_payload()] Delivery of {} failed (attempt {}/{}), retrying in {:?}",
+                payload.id,
+                attempt,
+                self.max_attempts,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            delay = delay.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use der::asn1::Uint;
+    use x509_cert::name::Name;
+
+    use super::*;
+
+    fn payload() -> WebhookPayload {
+        let cert_id = CertId::new(
+            &Name::from_str("DC=polyphony,DC=chat").unwrap(),
+            Uint::new(&[1]).unwrap(),
+        )
+        .unwrap();
+        WebhookPayload::new(
+            WebhookEvent::IdCertRevoked {
+                cert_id,
+                reason: None,
+            },
+            Timestamp::from_unix_seconds(1000),
+        )
+    }
+
+    #[test]
+    fn hmac_roundtrip() {
+        let body = serde_json::to_vec(&payload()).unwrap();
+        let time = Timestamp::from_unix_seconds(1000);
+        let header = HmacSigner::new(b"secret").signature_header(&signed_message(time, &body));
+        let verified = verify_hmac(
+            b"secret",
+            &header,
+            "1000",
+            &body,
+            Timestamp::from_unix_seconds(1100),
+            DEFAULT_TOLERANCE,
+        )
+        .unwrap();
+        assert_eq!(verified, payload());
+        assert_eq!(
+            verify_hmac(
+                b"wrong",
+                &header,
+                "1000",
+                &body,
+                Timestamp::from_unix_seconds(1100),
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::BadSignature)
+        );
+        assert_eq!(
+            verify_hmac(
+                b"secret",
+                &header,
+                "1001",
+                &body,
+                Timestamp::from_unix_seconds(1100),
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::BadSignature)
+        );
+        assert_eq!(
+            verify_hmac(
+                b"secret",
+                &header,
+                "1000",
+                &body,
+                Timestamp::from_unix_seconds(5000),
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::OutsideTolerance)
+        );
+    }
+
+    #[test]
+    fn payload_id_is_stable() {
+        assert_eq!(payload().id, payload().id);
+        assert_eq!(payload().id.len(), 32);
+    }
+
+    #[test]
+    fn malformed_cert_signature_header() {
+        assert!(signing_cert_id("").is_err());
+        assert!(signing_cert_id("cert=abc;sig=00").is_err());
+        assert!(signing_cert_id("hmac-sha256=00").is_err());
+    }
+}
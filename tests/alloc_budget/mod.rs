@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Allocation budgets for the parse and verify paths. The budgets are roughly twice the number of
+//! allocations measured when they were set, so that they only fail on real regressions. Signature
+//! verification does not allocate at all. If a change legitimately needs more allocations, raise
+//! the budget in the same change.
+
+use polyproto::alloc_stats::{measure, AllocStats, CountingAllocator};
+use polyproto::certs::borrowed::IdCertRef;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::{
+    actor_csr, gen_priv_key, home_server_id_cert, init_logger, Ed25519PublicKey, Ed25519Signature,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+
+/// Asserts that `stats` does not exceed `budget` allocations.
+fn assert_within_budget(operation: &str, stats: AllocStats, budget: u64) {
+    log::info!("{} made {} allocations", operation, stats.allocations);
+    assert!(
+        stats.allocations <= budget,
+        "{} made {} allocations, exceeding its budget of {}",
+        operation,
+        stats.allocations,
+        budget
+    );
+}
+
+#[test]
+fn counters_are_recorded() {
+    let (vector, stats) = measure(|| vec![0u8; 1024]);
+    assert_eq!(vector.len(), 1024);
+    assert_eq!(stats.allocations, 1);
+    assert_eq!(stats.bytes_allocated, 1024);
+    assert_eq!(stats.net_bytes(), 1024);
+    let (_, stats) = measure(|| drop(vector));
+    assert_eq!(stats.deallocations, 1);
+    assert_eq!(stats.net_bytes(), -1024);
+    assert!(AllocStats::global().allocations >= AllocStats::current_thread().allocations);
+}
+
+#[test]
+fn id_csr_from_der_budget() {
+    init_logger();
+    let der = actor_csr("flori", &gen_priv_key()).to_der().unwrap();
+    let (csr, stats) = measure(|| {
+        IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der(&der, Some(Target::Actor))
+    });
+    csr.unwrap();
+    assert_within_budget("IdCsr::from_der()", stats, 2500);
+}
+
+#[test]
+fn id_cert_from_der_budget() {
+    init_logger();
+    let cert = home_server_id_cert();
+    let der = cert.to_der().unwrap();
+    let key = cert.id_cert_tbs.subject_public_key.clone();
+    let (decoded, stats) = measure(|| {
+        IdCert::<Ed25519Signature, Ed25519PublicKey>::from_der(
+            &der,
+            Target::HomeServer,
+            Timestamp::from_unix_seconds(100),
+            &key,
+        )
+    });
+    decoded.unwrap();
+    assert_within_budget("IdCert::from_der()", stats, 250);
+}
+
+#[test]
+fn id_cert_ref_budget() {
+    init_logger();
+    let der = home_server_id_cert().to_der().unwrap();
+    let (result, stats) = measure(|| {
+        let cert_ref = IdCertRef::from_der(&der)?;
+        Ok::<_, polyproto::errors::ConversionError>(
+            cert_ref.subject().attributes().count() + cert_ref.extensions().count(),
+        )
+    });
+    assert!(result.unwrap() > 0);
+    assert_within_budget("IdCertRef::from_der()", stats, 0);
+}
+
+#[test]
+fn signature_verification_budget() {
+    init_logger();
+    let key = gen_priv_key();
+    let signature = key.sign(b"polyproto");
+    let (result, stats) = measure(|| {
+        polyproto::key::PublicKey::verify_signature(key.pubkey(), &signature, b"polyproto")
+    });
+    result.unwrap();
+    assert_within_budget("PublicKey::verify_signature()", stats, 0);
+}
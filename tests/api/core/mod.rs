@@ -8,27 +8,36 @@ use httptest::matchers::{eq, json_decoded, matches, request};
 use httptest::responders::{json_encoded, status_code};
 use httptest::*;
 use polyproto::api::core::current_unix_time;
+use polyproto::api::revocation::RevocationChecker;
 use polyproto::certs::capabilities::Capabilities;
 use polyproto::certs::idcert::IdCert;
 use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::status::{
+    RevocationCheck, RevocationReason, RevocationStatus, StatusRequest, StatusResponse,
+};
+use polyproto::certs::superseded::SupersededNotice;
 use polyproto::certs::SessionId;
-use polyproto::key::PublicKey;
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::timestamp::{FixedTimeSource, Timestamp};
 use polyproto::types::routes::core::v1::{
-    DELETE_ENCRYPTED_PKM, DELETE_SESSION, GET_ACTOR_IDCERTS, GET_CHALLENGE_STRING,
-    GET_ENCRYPTED_PKM, GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT, GET_SERVER_PUBLIC_IDCERT,
-    GET_SERVER_PUBLIC_KEY, ROTATE_SERVER_IDENTITY_KEY, ROTATE_SESSION_IDCERT,
-    UPDATE_SESSION_IDCERT, UPLOAD_ENCRYPTED_PKM,
+    CHECK_REVOCATION_STATUS, DELETE_ENCRYPTED_PKM, DELETE_SESSION, GET_ACTOR_IDCERTS,
+    GET_CHALLENGE_STRING, GET_ENCRYPTED_PKM, GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT, GET_ENDPOINTS,
+    GET_SERVER_PUBLIC_IDCERT, GET_SERVER_PUBLIC_KEY, GET_SUPERSEDED_NOTICE,
+    ROTATE_SERVER_IDENTITY_KEY, ROTATE_SESSION_IDCERT, UPDATE_SESSION_IDCERT, UPLOAD_ENCRYPTED_PKM,
 };
 use polyproto::types::spki::AlgorithmIdentifierOwned;
 use polyproto::types::x509_cert::SerialNumber;
-use polyproto::types::{EncryptedPkm, PrivateKeyInfo};
+use polyproto::types::{EncryptedPkm, Endpoint, EndpointKind, PrivateKeyInfo};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use serde_json::json;
 use spki::ObjectIdentifier;
 use x509_cert::time::Validity;
 
 use crate::common::{
-    actor_id_cert, actor_subject, default_validity, gen_priv_key, home_server_id_cert,
-    home_server_subject, init_logger, Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature,
+    actor_csr, actor_id_cert, actor_subject, default_validity, gen_priv_key, home_server_csr,
+    home_server_id_cert, home_server_subject, init_logger, Ed25519PrivateKey, Ed25519PublicKey,
+    Ed25519Signature,
 };
 
 /// Correctly format the server URL for the test.
@@ -54,7 +63,137 @@ async fn get_challenge_string() {
     let client = polyproto::api::HttpClient::new(&url).unwrap();
     let challenge_string = client.get_challenge_string().await.unwrap();
     assert_eq!(challenge_string.challenge, "a".repeat(32));
-    assert_eq!(challenge_string.expires, 1);
+    assert_eq!(challenge_string.expires, Timestamp::from_unix_seconds(1));
+}
+
+#[tokio::test]
+async fn get_challenge_string_failover() {
+    init_logger();
+    let server = Server::run();
+    let failing_server = Server::run();
+    failing_server.expect(
+        Expectation::matching(request::method_path(
+            GET_CHALLENGE_STRING.method.as_str(),
+            GET_CHALLENGE_STRING.path,
+        ))
+        .respond_with(status_code(503)),
+    );
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_CHALLENGE_STRING.method.as_str(),
+            GET_CHALLENGE_STRING.path,
+        ))
+        .respond_with(json_encoded(json!({
+            "challenge": "a".repeat(32),
+            "expires": 1
+        }))),
+    );
+    let endpoints = [
+        Endpoint::new(&server_url(&failing_server), 0, 1),
+        Endpoint::new(&server_url(&server), 1, 1),
+    ];
+    let client = polyproto::api::HttpClient::new_with_endpoints(&endpoints).unwrap();
+    let challenge_string = client.get_challenge_string().await.unwrap();
+    assert_eq!(challenge_string.challenge, "a".repeat(32));
+}
+
+#[tokio::test]
+async fn non_idempotent_requests_do_not_fail_over_on_server_errors() {
+    init_logger();
+    let server = Server::run();
+    let failing_server = Server::run();
+    failing_server.expect(
+        Expectation::matching(request::method_path(
+            UPLOAD_ENCRYPTED_PKM.method.as_str(),
+            UPLOAD_ENCRYPTED_PKM.path,
+        ))
+        .respond_with(status_code(503)),
+    );
+    server.expect(
+        Expectation::matching(request::method_path(
+            UPLOAD_ENCRYPTED_PKM.method.as_str(),
+            UPLOAD_ENCRYPTED_PKM.path,
+        ))
+        .times(0)
+        .respond_with(status_code(201)),
+    );
+    let endpoints = [
+        Endpoint::new(&server_url(&failing_server), 0, 1),
+        Endpoint::new(&server_url(&server), 1, 1),
+    ];
+    let client = polyproto::api::HttpClient::new_with_endpoints(&endpoints).unwrap();
+    // The failing server may have stored the key material before responding with an error.
+    let _ = client.upload_encrypted_pkm(vec![encrypted_pkm(1)]).await;
+}
+
+#[tokio::test]
+async fn get_endpoints() {
+    init_logger();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_ENDPOINTS.method.as_str(),
+            GET_ENDPOINTS.path,
+        ))
+        .respond_with(json_encoded(json!([
+            { "url": "https://api.polyphony.chat", "priority": 0, "weight": 3 },
+            { "url": "https://fallback.polyphony.chat", "priority": 1 }
+        ]))),
+    );
+    let client = polyproto::api::HttpClient::new(&server_url(&server)).unwrap();
+    let endpoints = client.get_endpoints().await.unwrap();
+    assert_eq!(
+        endpoints,
+        vec![
+            Endpoint::new("https://api.polyphony.chat", 0, 3),
+            Endpoint::new("https://fallback.polyphony.chat", 1, 1),
+        ]
+    );
+    polyproto::api::HttpClient::new_with_endpoints(&endpoints).unwrap();
+}
+
+#[tokio::test]
+async fn gateway_endpoints_are_not_used_for_requests() {
+    init_logger();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_ENDPOINTS.method.as_str(),
+            GET_ENDPOINTS.path,
+        ))
+        .respond_with(json_encoded(json!([
+            { "url": "wss://gateway.polyphony.chat", "priority": 0, "kind": "gateway" },
+            { "url": server_url(&server), "priority": 1 }
+        ]))),
+    );
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_CHALLENGE_STRING.method.as_str(),
+            GET_CHALLENGE_STRING.path,
+        ))
+        .times(2)
+        .respond_with(json_encoded(json!({
+            "challenge": "a".repeat(32),
+            "expires": 1
+        }))),
+    );
+    let client = polyproto::api::HttpClient::new(&server_url(&server)).unwrap();
+    let endpoints = client.get_endpoints().await.unwrap();
+    let gateway =
+        Endpoint::new("wss://gateway.polyphony.chat", 0, 1).with_kind(EndpointKind::Gateway);
+    assert_eq!(
+        endpoints,
+        vec![gateway.clone(), Endpoint::new(&server_url(&server), 1, 1)]
+    );
+
+    let client = polyproto::api::HttpClient::new_with_endpoints(&endpoints).unwrap();
+    assert_eq!(client.gateway_endpoints(), std::slice::from_ref(&gateway));
+    assert_eq!(client.endpoint_pool().unwrap().len(), 1);
+    // Both requests are sent to the API endpoint, although the gateway endpoint is preferred.
+    for _ in 0..2 {
+        client.get_challenge_string().await.unwrap();
+    }
+    assert!(polyproto::api::HttpClient::new_with_endpoints(&[gateway]).is_err());
 }
 
 #[tokio::test]
@@ -64,7 +203,7 @@ async fn rotate_server_identity_key() {
     let id_csr = IdCsr::new(
         &home_server_subject(),
         &home_server_signing_key,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(polyproto::certs::Target::HomeServer),
     )
     .unwrap();
@@ -158,7 +297,40 @@ async fn get_server_id_cert() {
     );
 
     let cert = client
-        .get_server_id_cert::<Ed25519Signature, Ed25519PublicKey>(Some(10))
+        .get_server_id_cert::<Ed25519Signature, Ed25519PublicKey>(Some(
+            Timestamp::from_unix_seconds(10),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(cert.to_pem(der::pem::LineEnding::LF).unwrap(), cert_pem);
+}
+
+#[tokio::test]
+async fn get_server_id_cert_uses_time_source() {
+    init_logger();
+    let id_cert = home_server_id_cert();
+    let cert_pem = id_cert.to_pem(der::pem::LineEnding::LF).unwrap();
+    let server = Server::run();
+    let url = server_url(&server);
+    let mut client = polyproto::api::HttpClient::new(&url).unwrap();
+    server.expect(
+        Expectation::matching(method_path(
+            GET_SERVER_PUBLIC_IDCERT.method.as_str(),
+            GET_SERVER_PUBLIC_IDCERT.path,
+        ))
+        .times(2)
+        .respond_with(json_encoded(json!(cert_pem))),
+    );
+
+    // The certificate is only valid from 10 to 1000 seconds after the Unix epoch.
+    assert!(client
+        .get_server_id_cert::<Ed25519Signature, Ed25519PublicKey>(None)
+        .await
+        .is_err());
+    client.set_time_source(FixedTimeSource::new(Timestamp::from_unix_seconds(100)));
+    assert_eq!(client.time_source().now().unix_seconds(), 100);
+    let cert = client
+        .get_server_id_cert::<Ed25519Signature, Ed25519PublicKey>(None)
         .await
         .unwrap();
     assert_eq!(cert.to_pem(der::pem::LineEnding::LF).unwrap(), cert_pem);
@@ -203,7 +375,7 @@ async fn get_actor_id_certs() {
     let certs = client
         .get_actor_id_certs::<Ed25519Signature, Ed25519PublicKey>(
             "flori@polyphony.chat",
-            Some(12345),
+            Some(Timestamp::from_unix_seconds(12345)),
             Some(&SessionId::new_validated("cool_session_id").unwrap()),
         )
         .await
@@ -232,7 +404,7 @@ async fn get_actor_id_certs() {
     let certs = client
         .get_actor_id_certs::<Ed25519Signature, Ed25519PublicKey>(
             "flori@polyphony.chat",
-            Some(12345),
+            Some(Timestamp::from_unix_seconds(12345)),
             Some(&SessionId::new_validated("cool_session_id").unwrap()),
         )
         .await
@@ -264,7 +436,7 @@ async fn get_actor_id_certs() {
     let certs = client
         .get_actor_id_certs::<Ed25519Signature, Ed25519PublicKey>(
             "flori@polyphony.chat",
-            Some(12345),
+            Some(Timestamp::from_unix_seconds(12345)),
             None,
         )
         .await
@@ -318,7 +490,7 @@ async fn get_actor_id_certs() {
 async fn update_session_id_cert() {
     init_logger();
     let id_cert = actor_id_cert("flori");
-    let cert_pem = id_cert.clone().to_pem(der::pem::LineEnding::LF).unwrap();
+    let cert_pem = id_cert.to_pem(der::pem::LineEnding::LF).unwrap();
     let server = Server::run();
     server.expect(
         Expectation::matching(all_of![
@@ -366,7 +538,7 @@ async fn rotate_session_id_cert() {
     let id_csr = IdCsr::new(
         &actor_subject("flori"),
         &actor_signing_key,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(polyproto::certs::Target::Actor),
     )
     .unwrap();
@@ -378,7 +550,7 @@ async fn rotate_session_id_cert() {
         default_validity(),
     )
     .unwrap();
-    let csr_pem = id_csr.clone().to_pem(der::pem::LineEnding::LF).unwrap();
+    let csr_pem = id_csr.to_pem(der::pem::LineEnding::LF).unwrap();
     let server = Server::run();
     server.expect(
         Expectation::matching(all_of![
@@ -506,3 +678,137 @@ async fn get_pkm_upload_size_limit() {
     let resp = client.get_pkm_upload_size_limit().await.unwrap();
     assert_eq!(resp, limit);
 }
+
+#[tokio::test]
+async fn get_superseded_notice() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let old_cert = actor_id_cert("flori");
+    let new_cert = actor_id_cert("flori");
+    let notice = SupersededNotice::new(
+        &old_cert,
+        &new_cert,
+        Timestamp::from_unix_seconds(500),
+        &home_server_key,
+    )
+    .unwrap();
+    let cert_id = old_cert.cert_id().unwrap();
+
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_SUPERSEDED_NOTICE.method.as_str(),
+            format!("{}{}", GET_SUPERSEDED_NOTICE.path, cert_id),
+        ))
+        .respond_with(json_encoded(json!(notice))),
+    );
+    let url = server_url(&server);
+    let client = polyproto::api::HttpClient::new(&url).unwrap();
+    let received = client
+        .get_superseded_notice::<Ed25519Signature>(&cert_id)
+        .await
+        .unwrap();
+    assert_eq!(received, notice);
+    received.verify(home_server_key.pubkey()).unwrap();
+}
+
+#[tokio::test]
+async fn check_revocation_status() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let issuer = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    // The checker draws the same nonce from an identically seeded RNG.
+    let request = StatusRequest::fresh(&actor, &mut StdRng::seed_from_u64(7)).unwrap();
+    let status = RevocationStatus::Revoked {
+        revoked_at: Timestamp::from(50),
+        reason: RevocationReason::Superseded,
+    };
+    let response = StatusResponse::new(
+        &request,
+        status,
+        Timestamp::from(100),
+        None,
+        &home_server_key,
+    );
+
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path(
+                CHECK_REVOCATION_STATUS.method.as_str(),
+                CHECK_REVOCATION_STATUS.path,
+            ),
+            request::body(json_decoded(eq(json!(request)))),
+        ])
+        .respond_with(json_encoded(json!(response))),
+    );
+    let url = server_url(&server);
+    let client = polyproto::api::HttpClient::new(&url).unwrap();
+    let checker = RevocationChecker::new(client, StdRng::seed_from_u64(7));
+    assert_eq!(
+        checker
+            .check(&actor, &issuer, Timestamp::from(150))
+            .await
+            .unwrap(),
+        status
+    );
+}
+
+#[tokio::test]
+async fn check_revocation_status_forged() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let issuer = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor = actor_id_cert("flori");
+    let request = StatusRequest::fresh(&actor, &mut StdRng::seed_from_u64(7)).unwrap();
+    let forged = StatusResponse::new(
+        &request,
+        RevocationStatus::Good,
+        Timestamp::from(100),
+        None,
+        &gen_priv_key(),
+    );
+
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path(
+            CHECK_REVOCATION_STATUS.method.as_str(),
+            CHECK_REVOCATION_STATUS.path,
+        ))
+        .times(2)
+        .respond_with(json_encoded(json!(forged))),
+    );
+    let url = server_url(&server);
+    let client = polyproto::api::HttpClient::new(&url).unwrap();
+    let checker = RevocationChecker::new(client, StdRng::seed_from_u64(7));
+    assert!(checker
+        .check(&actor, &issuer, Timestamp::from(150))
+        .await
+        .is_err());
+    assert_eq!(
+        checker.status(&actor, &issuer, Timestamp::from(150)).await,
+        RevocationStatus::Unknown
+    );
+}
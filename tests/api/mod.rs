@@ -3,10 +3,27 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 pub(crate) mod core;
+pub(crate) mod multi;
+pub(crate) mod prefetch;
+pub(crate) mod resolver;
+pub(crate) mod vcr;
 
 use super::*;
-use polyproto::types::ChallengeString;
+use polyproto::errors::ChallengeError;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
 use polyproto::types::FederationId;
+use polyproto::types::{ChallengeBinding, ChallengePurpose, ChallengeString};
+
+use crate::common::gen_priv_key;
+
+fn binding(purpose: ChallengePurpose) -> ChallengeBinding {
+    ChallengeBinding {
+        federation_id: FederationId::new("flori@polyphony.chat").unwrap(),
+        purpose,
+        server_domain: "polyphony.chat".to_string(),
+    }
+}
 
 #[test]
 fn challenge_string_length() {
@@ -14,24 +31,28 @@ fn challenge_string_length() {
     let mut twofivefive = String::from_utf8(vec![121; 255]).unwrap();
     let challenge = ChallengeString {
         challenge: thirtytwo.clone(),
-        expires: 1,
+        expires: Timestamp::from_unix_seconds(1),
+        binding: None,
     };
     assert!(challenge.validate(None).is_ok());
     let challenge = ChallengeString {
         challenge: twofivefive.clone(),
-        expires: 1,
+        expires: Timestamp::from_unix_seconds(1),
+        binding: None,
     };
     assert!(challenge.validate(None).is_ok());
     thirtytwo.pop().unwrap(); // String is now 31 characters long
     let challenge = ChallengeString {
         challenge: thirtytwo,
-        expires: 1,
+        expires: Timestamp::from_unix_seconds(1),
+        binding: None,
     };
     assert!(challenge.validate(None).is_err());
     twofivefive.push('a'); // String is now 256 characters long
     let challenge = ChallengeString {
         challenge: twofivefive,
-        expires: 1,
+        expires: Timestamp::from_unix_seconds(1),
+        binding: None,
     };
     assert!(challenge.validate(None).is_err());
 }
@@ -57,3 +78,112 @@ fn invalid_federation_id() {
         "example@com".to_string()
     );
 }
+
+#[test]
+fn bound_challenge_response() {
+    let key = gen_priv_key();
+    let challenge = ChallengeString::new(
+        &"a".repeat(32),
+        Timestamp::from_unix_seconds(100),
+        Some(binding(ChallengePurpose::Registration)),
+    )
+    .unwrap();
+    let signature = key.sign(&challenge.signed_payload());
+    let now = Timestamp::from_unix_seconds(50);
+    challenge
+        .verify_response(
+            &binding(ChallengePurpose::Registration),
+            &signature,
+            key.pubkey(),
+            now,
+        )
+        .unwrap();
+    assert_eq!(
+        challenge.verify_response(
+            &binding(ChallengePurpose::Migration),
+            &signature,
+            key.pubkey(),
+            now
+        ),
+        Err(ChallengeError::PurposeMismatch {
+            expected: ChallengePurpose::Migration,
+            actual: ChallengePurpose::Registration
+        })
+    );
+    let mut other_actor = binding(ChallengePurpose::Registration);
+    other_actor.federation_id = FederationId::new("alice@polyphony.chat").unwrap();
+    assert_eq!(
+        challenge.verify_response(&other_actor, &signature, key.pubkey(), now),
+        Err(ChallengeError::BindingMismatch)
+    );
+    assert_eq!(
+        challenge.verify_response(
+            &binding(ChallengePurpose::Registration),
+            &signature,
+            key.pubkey(),
+            Timestamp::from_unix_seconds(101)
+        ),
+        Err(ChallengeError::Expired)
+    );
+}
+
+#[test]
+fn bound_challenge_payload_differs_by_purpose() {
+    let registration = ChallengeString::new(
+        &"a".repeat(32),
+        Timestamp::from_unix_seconds(100),
+        Some(binding(ChallengePurpose::Registration)),
+    )
+    .unwrap();
+    let mut key_trial = registration.clone();
+    key_trial.binding = Some(binding(ChallengePurpose::KeyTrial));
+    assert_ne!(registration.signed_payload(), key_trial.signed_payload());
+    let key = gen_priv_key();
+    // A signature for one flow must not be accepted in another, even if the binding is rewritten.
+    let signature = key.sign(&registration.signed_payload());
+    assert!(key_trial
+        .verify_response(
+            &binding(ChallengePurpose::KeyTrial),
+            &signature,
+            key.pubkey(),
+            Timestamp::from_unix_seconds(50)
+        )
+        .is_err());
+}
+
+#[test]
+fn challenge_binding_domain() {
+    let mut foreign = binding(ChallengePurpose::Registration);
+    foreign.server_domain = "example.com".to_string();
+    assert!(ChallengeString::new(
+        &"a".repeat(32),
+        Timestamp::UNIX_EPOCH,
+        Some(foreign.clone())
+    )
+    .is_err());
+    foreign.purpose = ChallengePurpose::Migration;
+    assert!(ChallengeString::new(&"a".repeat(32), Timestamp::UNIX_EPOCH, Some(foreign)).is_ok());
+}
+
+#[test]
+fn challenge_string_serde() {
+    let unbound: ChallengeString = serde_json::from_str(&format!(
+        r#"{{"challenge":"{}","expires":1}}"#,
+        "a".repeat(32)
+    ))
+    .unwrap();
+    assert_eq!(unbound.binding, None);
+    let bound = ChallengeString::new(
+        &"a".repeat(32),
+        Timestamp::from_unix_seconds(1),
+        Some(binding(ChallengePurpose::KeyTrial)),
+    )
+    .unwrap();
+    let json = serde_json::to_value(&bound).unwrap();
+    assert_eq!(json["binding"]["purpose"], "key_trial");
+    assert_eq!(json["binding"]["federation_id"], "flori@polyphony.chat");
+    assert_eq!(
+        serde_json::from_value::<ChallengeString>(json).unwrap(),
+        bound
+    );
+}
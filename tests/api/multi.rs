@@ -0,0 +1,172 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use httptest::matchers::{all_of, contains, request};
+use httptest::responders::json_encoded;
+use httptest::*;
+use polyproto::api::multi::{HomeServer, MultiClient};
+use polyproto::api::ratelimit::RateLimit;
+use polyproto::api::HttpClient;
+use polyproto::certs::idcert::IdCert;
+use polyproto::errors::{InvalidCert, PublicKeyError, RequestError};
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::types::routes::core::v1::GET_CHALLENGE_STRING;
+use polyproto::types::FederationId;
+use serde_json::json;
+
+use crate::common::{
+    actor_csr, default_validity, gen_priv_key, home_server_subject, init_logger, Ed25519PublicKey,
+    Ed25519Signature,
+};
+
+type TestMultiClient = MultiClient<Ed25519Signature, Ed25519PublicKey>;
+
+/// A server answering challenge string requests which carry the given authorization token.
+fn challenge_server(token: &'static str, challenge: &str) -> Server {
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path(
+                GET_CHALLENGE_STRING.method.as_str(),
+                GET_CHALLENGE_STRING.path
+            ),
+            request::headers(contains(("authorization", token))),
+        ])
+        .times(1..)
+        .respond_with(json_encoded(json!({
+            "challenge": challenge,
+            "expires": 1
+        }))),
+    );
+    server
+}
+
+fn home_server(server: &Server, token: &str) -> HomeServer<Ed25519Signature, Ed25519PublicKey> {
+    HomeServer::new(HttpClient::new(&format!("http://{}", server.addr())).unwrap())
+        .with_credentials(token)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn routes_by_federation_id() {
+    init_logger();
+    let polyphony = challenge_server("polyphony-token", &"a".repeat(32));
+    let example = challenge_server("example-token", &"b".repeat(32));
+    let mut multi = TestMultiClient::new();
+    multi.insert("polyphony.chat", home_server(&polyphony, "polyphony-token"));
+    multi.insert("Example.com", home_server(&example, "example-token"));
+    assert_eq!(multi.len(), 2);
+    assert_eq!(
+        multi.domains().collect::<Vec<_>>(),
+        vec!["example.com", "polyphony.chat"]
+    );
+
+    let flori = FederationId::new("flori@polyphony.chat").unwrap();
+    let alice = FederationId::new("alice@example.com").unwrap();
+    assert_eq!(
+        multi
+            .client_for(&flori)
+            .unwrap()
+            .get_challenge_string()
+            .await
+            .unwrap()
+            .challenge,
+        "a".repeat(32)
+    );
+    assert_eq!(
+        multi
+            .client_for(&alice)
+            .unwrap()
+            .get_challenge_string()
+            .await
+            .unwrap()
+            .challenge,
+        "b".repeat(32)
+    );
+
+    let unknown = FederationId::new("bob@unknown.org").unwrap();
+    assert!(matches!(
+        multi.route(&unknown),
+        Err(RequestError::UnknownHomeServer(domain)) if domain == "unknown.org"
+    ));
+    assert!(multi.remove("EXAMPLE.COM").is_some());
+    assert!(multi.route(&alice).is_err());
+}
+
+#[tokio::test]
+async fn per_home_server_rate_limits() {
+    init_logger();
+    let limited = challenge_server("limited-token", &"a".repeat(32));
+    let unlimited = challenge_server("unlimited-token", &"b".repeat(32));
+    let mut multi = TestMultiClient::new();
+    multi.insert(
+        "limited.chat",
+        home_server(&limited, "limited-token")
+            .with_rate_limit(RateLimit::new(1, Duration::from_secs(3600))),
+    );
+    multi.insert("unlimited.chat", home_server(&unlimited, "unlimited-token"));
+
+    let limited_actor = FederationId::new("flori@limited.chat").unwrap();
+    let unlimited_actor = FederationId::new("flori@unlimited.chat").unwrap();
+    let client = multi.client_for(&limited_actor).unwrap();
+    client.get_challenge_string().await.unwrap();
+    assert!(matches!(
+        client.get_challenge_string().await,
+        Err(RequestError::RateLimited(retry_after)) if retry_after > Duration::from_secs(3500)
+    ));
+    for _ in 0..3 {
+        multi
+            .client_for(&unlimited_actor)
+            .unwrap()
+            .get_challenge_string()
+            .await
+            .unwrap();
+    }
+}
+
+#[test]
+fn per_home_server_trust_store() {
+    init_logger();
+    let home_key = gen_priv_key();
+    let other_key = gen_priv_key();
+    let actor_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        &home_key,
+        Uint::new(&[8]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let time = Timestamp::from_unix_seconds(100);
+
+    let mut home_server: HomeServer<Ed25519Signature, Ed25519PublicKey> =
+        HomeServer::new(HttpClient::new("https://polyphony.chat").unwrap())
+            .with_gateway("wss://gateway.polyphony.chat")
+            .unwrap();
+    assert_eq!(
+        home_server.gateway().unwrap().as_str(),
+        "wss://gateway.polyphony.chat/"
+    );
+    assert_eq!(
+        home_server.verify_actor(&cert, time),
+        Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature))
+    );
+    home_server.trust(other_key.pubkey().clone());
+    assert!(home_server.verify_actor(&cert, time).is_err());
+    home_server.trust(home_key.pubkey().clone());
+    home_server.trust(home_key.pubkey().clone());
+    assert_eq!(home_server.trusted_keys().len(), 2);
+    // Adding a key which is already trusted does not change the trust store.
+    assert_eq!(home_server.trust_store_generation(), 2);
+    assert!(home_server.verify_actor(&cert, time).is_ok());
+    assert!(home_server.distrust(home_key.pubkey()));
+    assert!(!home_server.distrust(home_key.pubkey()));
+    assert_eq!(home_server.trust_store_generation(), 3);
+    assert!(home_server.verify_actor(&cert, time).is_err());
+}
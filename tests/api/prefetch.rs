@@ -0,0 +1,126 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use httptest::matchers::{all_of, matches, request};
+use httptest::responders::json_encoded;
+use httptest::*;
+use polyproto::api::multi::{HomeServer, MultiClient};
+use polyproto::api::prefetch::{CertPrefetcher, PrefetchConfig};
+use polyproto::api::HttpClient;
+use polyproto::certs::idcert::IdCert;
+use polyproto::gateway::GatewayEvent;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::types::routes::core::v1::GET_ACTOR_IDCERTS;
+use polyproto::types::FederationId;
+use serde_json::json;
+
+use crate::common::{
+    actor_csr, default_validity, gen_priv_key, home_server_subject, init_logger, Ed25519PrivateKey,
+    Ed25519PublicKey, Ed25519Signature,
+};
+
+/// Expects requests for the certificates of `cn@polyphony.chat`, answering with a certificate
+/// issued by `home_server_key`.
+fn expect_actor_certs(server: &Server, home_server_key: &Ed25519PrivateKey, cn: &str, serial: u8) {
+    let id_cert = IdCert::from_actor_csr(
+        actor_csr(cn, &gen_priv_key()),
+        home_server_key,
+        Uint::new(&[serial]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method(GET_ACTOR_IDCERTS.method.as_str()),
+            request::path(matches(format!("^{}{}@.*$", GET_ACTOR_IDCERTS.path, cn))),
+        ])
+        .times(1..)
+        .respond_with(json_encoded(json!([{
+            "id_cert": id_cert.to_pem(der::pem::LineEnding::LF).unwrap(),
+            "invalidated": false
+        }]))),
+    );
+}
+
+#[tokio::test]
+async fn prefetch_active_peers() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let server = Server::run();
+    expect_actor_certs(&server, &home_server_key, "flori", 2);
+    expect_actor_certs(&server, &home_server_key, "alice", 3);
+    let mut multi = MultiClient::<Ed25519Signature, Ed25519PublicKey>::new();
+    multi.insert(
+        "polyphony.chat",
+        HomeServer::new(HttpClient::new(&format!("http://{}", server.addr())).unwrap())
+            .with_trusted_key(home_server_key.pubkey().clone()),
+    );
+    let prefetcher = CertPrefetcher::new(PrefetchConfig {
+        max_concurrency: 1,
+        max_cached: 1,
+        ..Default::default()
+    });
+    let flori = FederationId::new("flori@polyphony.chat").unwrap();
+    let alice = FederationId::new("alice@polyphony.chat").unwrap();
+    let now = Timestamp::from_unix_seconds(100);
+
+    let message = GatewayEvent::new(
+        "message_create",
+        json!({
+            "author": "flori@polyphony.chat",
+            "mentions": ["alice@polyphony.chat"],
+            "content": "hello"
+        }),
+    );
+    assert_eq!(prefetcher.observe_event(&message, now), 2);
+    // A single appearance is not enough activity.
+    assert_eq!(prefetcher.run(&multi, now).await, 0);
+    prefetcher.observe(&flori, now);
+    assert_eq!(prefetcher.activity(&flori, now), 2);
+    assert_eq!(prefetcher.run(&multi, now).await, 1);
+    assert!(prefetcher.cert(&flori).is_some());
+    assert!(prefetcher.cert(&alice).is_none());
+    // The certificate is fresh, so it is not fetched again.
+    assert_eq!(prefetcher.run(&multi, now).await, 0);
+
+    // The cache is full, so the certificate of a more active peer replaces it.
+    prefetcher.observe(&alice, now);
+    prefetcher.observe(&alice, now);
+    assert_eq!(prefetcher.run(&multi, now).await, 1);
+    assert!(prefetcher.cert(&alice).is_some());
+    assert!(prefetcher.cert(&flori).is_none());
+    let metrics = prefetcher.metrics();
+    assert_eq!(metrics.prefetched, 2);
+    assert_eq!(metrics.evicted, 1);
+    assert_eq!(metrics.skipped, 1);
+
+    // Once the activity window has passed, peers are forgotten.
+    let later = Timestamp::from_unix_seconds(100 + 601);
+    assert_eq!(prefetcher.prune(later), 2);
+    assert!(prefetcher.cert(&alice).is_none());
+}
+
+#[tokio::test]
+async fn failed_prefetches_are_retried_later() {
+    init_logger();
+    let multi = MultiClient::<Ed25519Signature, Ed25519PublicKey>::new();
+    let prefetcher = CertPrefetcher::new(PrefetchConfig::default());
+    let bob = FederationId::new("bob@unknown.org").unwrap();
+    let now = Timestamp::from_unix_seconds(100);
+    prefetcher.observe(&bob, now);
+    prefetcher.observe(&bob, now);
+    assert!(matches!(
+        prefetcher.prefetch_next(&multi, now).await,
+        Some((federation_id, Err(_))) if federation_id == bob
+    ));
+    assert_eq!(prefetcher.metrics().failed, 1);
+    assert!(prefetcher.prefetch_next(&multi, now).await.is_none());
+    let much_later = Timestamp::from_unix_seconds(100 + 3600);
+    prefetcher.observe(&bob, much_later);
+    prefetcher.observe(&bob, much_later);
+    assert!(prefetcher.prefetch_next(&multi, much_later).await.is_some());
+}
@@ -0,0 +1,174 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use httptest::matchers::{all_of, matches, request};
+use httptest::responders::json_encoded;
+use httptest::*;
+use polyproto::api::multi::{HomeServer, MultiClient};
+use polyproto::api::resolver::CertResolver;
+use polyproto::api::HttpClient;
+use polyproto::certs::idcert::IdCert;
+use polyproto::errors::RequestError;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::types::routes::core::v1::{GET_ACTOR_IDCERTS, GET_SERVER_PUBLIC_IDCERT};
+use serde_json::json;
+
+use crate::common::{
+    actor_csr, default_validity, gen_priv_key, home_server_csr, home_server_subject, init_logger,
+    Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature,
+};
+
+type Resolver = CertResolver<Ed25519Signature, Ed25519PublicKey>;
+
+/// Expects `times` requests for the certificate of the home server, answering with a certificate
+/// of `home_server_key`.
+fn expect_server_cert(server: &Server, home_server_key: &Ed25519PrivateKey, times: usize) {
+    let id_cert = IdCert::from_ca_csr(
+        home_server_csr(home_server_key),
+        home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method(GET_SERVER_PUBLIC_IDCERT.method.as_str()),
+            request::path(GET_SERVER_PUBLIC_IDCERT.path),
+        ])
+        .times(times)
+        .respond_with(json_encoded(json!(id_cert
+            .to_pem(der::pem::LineEnding::LF)
+            .unwrap()))),
+    );
+}
+
+/// Expects `times` requests for the certificates of `cn@polyphony.chat`, answering with a
+/// certificate issued by `home_server_key`.
+fn expect_actor_certs(
+    server: &Server,
+    home_server_key: &Ed25519PrivateKey,
+    cn: &str,
+    times: usize,
+) {
+    let id_cert = IdCert::from_actor_csr(
+        actor_csr(cn, &gen_priv_key()),
+        home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method(GET_ACTOR_IDCERTS.method.as_str()),
+            request::path(matches(format!("^{}{}@.*$", GET_ACTOR_IDCERTS.path, cn))),
+        ])
+        .times(times)
+        .respond_with(json_encoded(json!([{
+            "id_cert": id_cert.to_pem(der::pem::LineEnding::LF).unwrap(),
+            "invalidated": false
+        }]))),
+    );
+}
+
+#[tokio::test]
+async fn resolve_with_discovery() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let server = Server::run();
+    expect_server_cert(&server, &home_server_key, 1);
+    expect_actor_certs(&server, &home_server_key, "flori", 1);
+    // The mock server stands in for every domain.
+    let resolver =
+        Resolver::new(MultiClient::new()).with_discovery(&format!("http://{}", server.addr()));
+    let now = Timestamp::from_unix_seconds(100);
+
+    let id_cert = resolver
+        .actor_cert("flori@polyphony.chat", now)
+        .await
+        .unwrap();
+    id_cert
+        .full_verify_actor(now, home_server_key.pubkey())
+        .unwrap();
+    // Both the certificate of the actor and the one of the home server are cached.
+    assert_eq!(
+        resolver
+            .actor_cert("flori@polyphony.chat", now)
+            .await
+            .unwrap(),
+        id_cert
+    );
+    let server_cert = resolver.server_cert("Polyphony.chat", now).await.unwrap();
+    assert_eq!(
+        &server_cert.id_cert_tbs.subject_public_key,
+        home_server_key.pubkey()
+    );
+    assert_eq!(
+        resolver
+            .home_server("polyphony.chat")
+            .unwrap()
+            .trusted_keys(),
+        &[home_server_key.pubkey().clone()]
+    );
+}
+
+#[tokio::test]
+async fn resolve_rejects_untrusted() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let server = Server::run();
+    expect_server_cert(&server, &home_server_key, 2);
+    expect_actor_certs(&server, &home_server_key, "flori", 2);
+    let resolver = Resolver::new(MultiClient::new());
+    let now = Timestamp::from_unix_seconds(100);
+
+    // Without discovery, only registered home servers are used.
+    assert!(matches!(
+        resolver.actor_cert("flori@polyphony.chat", now).await,
+        Err(RequestError::UnknownHomeServer(domain)) if domain == "polyphony.chat"
+    ));
+
+    let url = format!("http://{}", server.addr());
+    resolver.insert_home_server(
+        "polyphony.chat",
+        HomeServer::new(HttpClient::new(&url).unwrap())
+            .with_trusted_key(home_server_key.pubkey().clone()),
+    );
+    resolver
+        .actor_cert("flori@polyphony.chat", now)
+        .await
+        .unwrap();
+    resolver.server_cert("polyphony.chat", now).await.unwrap();
+
+    // Replacing the home server invalidates the cached certificates, which are no longer trusted.
+    resolver.insert_home_server(
+        "polyphony.chat",
+        HomeServer::new(HttpClient::new(&url).unwrap())
+            .with_trusted_key(gen_priv_key().pubkey().clone()),
+    );
+    assert!(resolver
+        .actor_cert("flori@polyphony.chat", now)
+        .await
+        .is_err());
+    assert!(resolver.server_cert("polyphony.chat", now).await.is_err());
+}
+
+#[tokio::test]
+async fn discovery_checks_domain() {
+    init_logger();
+    let server = Server::run();
+    expect_server_cert(&server, &gen_priv_key(), 1);
+    let resolver =
+        Resolver::new(MultiClient::new()).with_discovery(&format!("http://{}", server.addr()));
+
+    // The home server presents a certificate for polyphony.chat.
+    assert!(resolver
+        .server_cert("example.com", Timestamp::from_unix_seconds(100))
+        .await
+        .is_err());
+    assert!(resolver.home_server("example.com").is_none());
+}
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use httptest::matchers::request;
+use httptest::responders::json_encoded;
+use httptest::*;
+use polyproto::api::vcr::{Cassette, MatchMode, Redaction, Vcr, REDACTED};
+use polyproto::api::HttpClient;
+use polyproto::errors::RequestError;
+use polyproto::types::routes::core::v1::{
+    GET_CHALLENGE_STRING, GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT,
+};
+use serde_json::json;
+
+use crate::common::init_logger;
+
+/// Records a challenge string request followed by an upload size limit request.
+async fn record() -> Cassette {
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_CHALLENGE_STRING.method.as_str(),
+            GET_CHALLENGE_STRING.path,
+        ))
+        .respond_with(json_encoded(json!({
+            "challenge": "a".repeat(32),
+            "expires": 1
+        }))),
+    );
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT.method.as_str(),
+            GET_ENCRYPTED_PKM_UPLOAD_SIZE_LIMIT.path,
+        ))
+        .respond_with(json_encoded(1000)),
+    );
+    let mut client = HttpClient::new(&format!("http://{}", server.addr())).unwrap();
+    let vcr = Vcr::record(Redaction::default());
+    client.set_vcr(Some(vcr.clone()));
+    client.get_challenge_string().await.unwrap();
+    assert_eq!(client.get_pkm_upload_size_limit().await.unwrap(), 1000);
+    vcr.cassette()
+}
+
+/// A client which cannot reach any server, so that every response must come from the [Vcr].
+fn offline_client(cassette: Cassette, mode: MatchMode) -> HttpClient {
+    let mut client = HttpClient::new("http://127.0.0.1:1").unwrap();
+    client.set_vcr(Some(Vcr::replay(cassette, mode)));
+    client
+}
+
+#[tokio::test]
+async fn record_and_replay() {
+    init_logger();
+    let cassette = record().await;
+    assert_eq!(cassette.interactions.len(), 2);
+    let path = std::env::temp_dir().join(format!("polyproto-vcr-{}.json", std::process::id()));
+    cassette.save(&path).unwrap();
+    let loaded = Cassette::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, cassette);
+
+    let client = offline_client(loaded, MatchMode::Strict);
+    let challenge = client.get_challenge_string().await.unwrap();
+    assert_eq!(challenge.challenge, "a".repeat(32));
+    assert_eq!(client.get_pkm_upload_size_limit().await.unwrap(), 1000);
+    assert_eq!(client.vcr().unwrap().remaining(), 0);
+    // Every interaction is only replayed once.
+    assert!(matches!(
+        client.get_challenge_string().await,
+        Err(RequestError::ReplayError(_))
+    ));
+}
+
+#[tokio::test]
+async fn strict_and_lenient_matching() {
+    init_logger();
+    let cassette = record().await;
+
+    let strict = offline_client(cassette.clone(), MatchMode::Strict);
+    assert!(matches!(
+        strict.get_pkm_upload_size_limit().await,
+        Err(RequestError::ReplayError(_))
+    ));
+
+    let lenient = offline_client(cassette, MatchMode::Lenient);
+    assert_eq!(lenient.get_pkm_upload_size_limit().await.unwrap(), 1000);
+    lenient.get_challenge_string().await.unwrap();
+}
+
+#[tokio::test]
+async fn secrets_are_redacted() {
+    init_logger();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path(
+            GET_CHALLENGE_STRING.method.as_str(),
+            GET_CHALLENGE_STRING.path,
+        ))
+        .respond_with(
+            json_encoded(json!({
+                "challenge": "a".repeat(32),
+                "expires": 1
+            }))
+            .insert_header("Set-Cookie", "session=secret"),
+        ),
+    );
+    let mut client = HttpClient::new(&format!("http://{}", server.addr())).unwrap();
+    let vcr = Vcr::record(Redaction::default().json_field("challenge"));
+    client.set_vcr(Some(vcr.clone()));
+    // The caller still receives the unredacted response.
+    let challenge = client.get_challenge_string().await.unwrap();
+    assert_eq!(challenge.challenge, "a".repeat(32));
+
+    let response = &vcr.cassette().interactions[0].response;
+    let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(body["challenge"], REDACTED);
+    assert!(response
+        .headers
+        .iter()
+        .any(|(name, value)| name == "set-cookie" && value == REDACTED));
+}
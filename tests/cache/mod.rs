@@ -0,0 +1,84 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use polyproto::cache::{CacheGeneration, ValidationCache};
+use polyproto::certs::idcert::IdCert;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::verifier::WarningPolicy;
+
+use crate::common::*;
+
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validation_cache_invalidated_on_change() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[8]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let cert_id = cert.cert_id().unwrap();
+    let time = Timestamp::from_unix_seconds(100);
+    let policy = WarningPolicy::default();
+    let generation = CacheGeneration::new()
+        .with_spec_profile(&policy)
+        .with_trust_store(1);
+
+    let mut cache = ValidationCache::new();
+    let mut verifications = 0;
+    let mut verify = || {
+        verifications += 1;
+        cert.full_verify_actor(time, home_server_key.pubkey())
+    };
+    assert!(cache
+        .get_or_insert_with(cert_id.clone(), generation, &mut verify)
+        .is_ok());
+    assert!(cache
+        .get_or_insert_with(cert_id.clone(), generation, &mut verify)
+        .is_ok());
+    assert_eq!(cache.get(&cert_id, generation), Some(&Ok(())));
+
+    // Equal policies result in the same generation.
+    assert_eq!(
+        CacheGeneration::new()
+            .with_spec_profile(&WarningPolicy::default())
+            .with_trust_store(1),
+        generation
+    );
+    // Changing the policy invalidates the entry.
+    let changed_policy = CacheGeneration::new()
+        .with_spec_profile(&WarningPolicy {
+            expiry_threshold: Duration::from_secs(60),
+        })
+        .with_trust_store(1);
+    assert_eq!(cache.get(&cert_id, changed_policy), None);
+    assert!(cache.is_empty());
+    assert!(cache
+        .get_or_insert_with(cert_id.clone(), changed_policy, &mut verify)
+        .is_ok());
+    // So does changing the trust store or the algorithm policy.
+    let changed_trust_store = changed_policy.with_trust_store(2);
+    assert_eq!(cache.purge_stale(changed_trust_store), 1);
+    cache.insert(cert_id.clone(), Ok(()), changed_trust_store);
+    assert_eq!(
+        cache.get(
+            &cert_id,
+            changed_trust_store.with_algorithm_policy(&["1.3.101.112"])
+        ),
+        None
+    );
+    assert_eq!(cache.invalidations(), 3);
+    assert_eq!(verifications, 2);
+}
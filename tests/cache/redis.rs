@@ -0,0 +1,186 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use polyproto::cache::redis::{CachedServerCert, RedisCertCache, DEFAULT_KEY_PREFIX};
+use polyproto::errors::InvalidCert;
+use polyproto::timestamp::Timestamp;
+use redis::aio::ConnectionLike;
+use redis::{Arg, Cmd, Pipeline, RedisFuture, Value};
+
+use crate::common::*;
+
+#[derive(Debug)]
+struct Stored {
+    value: Vec<u8>,
+    ttl: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+/// An in-memory stand-in for a Redis server, understanding the commands sent by
+/// [RedisCertCache]. Keys map to their value and the expiry passed to `SET`, which is recorded
+/// but not enforced.
+struct FakeRedis {
+    entries: Arc<Mutex<HashMap<String, Stored>>>,
+}
+
+impl FakeRedis {
+    fn ttl(&self, key: &str) -> Option<u64> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|stored| stored.ttl)
+    }
+
+    fn execute(&self, cmd: &Cmd) -> Value {
+        let args: Vec<Vec<u8>> = cmd
+            .args_iter()
+            .map(|arg| match arg {
+                Arg::Simple(arg) => arg.to_vec(),
+                Arg::Cursor => unreachable!(),
+            })
+            .collect();
+        let key = String::from_utf8(args[1].clone()).unwrap();
+        let mut entries = self.entries.lock().unwrap();
+        match args[0].as_slice() {
+            b"SET" => {
+                assert_eq!(args[3], b"EX");
+                let ttl = String::from_utf8(args[4].clone()).unwrap().parse().unwrap();
+                entries.insert(
+                    key,
+                    Stored {
+                        value: args[2].clone(),
+                        ttl,
+                    },
+                );
+                Value::Okay
+            }
+            b"GET" => match entries.get(&key) {
+                Some(stored) => Value::BulkString(stored.value.clone()),
+                None => Value::Nil,
+            },
+            b"DEL" => Value::Int(entries.remove(&key).is_some() as i64),
+            command => panic!("unexpected command {:?}", String::from_utf8_lossy(command)),
+        }
+    }
+}
+
+impl ConnectionLike for FakeRedis {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let value = self.execute(cmd);
+        Box::pin(async move { Ok(value) })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        _cmd: &'a Pipeline,
+        _offset: usize,
+        _count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        unimplemented!("RedisCertCache does not send pipelines")
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
+
+#[tokio::test]
+async fn redis_cert_cache_round_trip() {
+    init_logger();
+    let redis = FakeRedis::default();
+    let cache = RedisCertCache::new(redis.clone());
+    let cert = home_server_id_cert();
+    let now = Timestamp::from_unix_seconds(100);
+
+    assert!(cache
+        .insert("polyphony.chat", &cert, &Ok(()), now)
+        .await
+        .unwrap());
+    // The entry expires together with the certificate.
+    assert_eq!(
+        redis.ttl(&format!("{}polyphony.chat", DEFAULT_KEY_PREFIX)),
+        Some(900)
+    );
+    // A second process sharing the connection sees the same entry.
+    let other = RedisCertCache::new(redis.clone());
+    let cached: CachedServerCert<Ed25519Signature, Ed25519PublicKey> =
+        other.get("polyphony.chat", now).await.unwrap().unwrap();
+    assert!(cached.is_valid());
+    assert_eq!(cached.cert, cert);
+
+    // Entries whose certificate has expired are not returned, even if Redis still holds them.
+    let expired: Option<CachedServerCert<Ed25519Signature, Ed25519PublicKey>> = cache
+        .get("polyphony.chat", Timestamp::from_unix_seconds(2000))
+        .await
+        .unwrap();
+    assert!(expired.is_none());
+
+    assert!(cache.remove("polyphony.chat").await.unwrap());
+    assert!(!cache.remove("polyphony.chat").await.unwrap());
+    let removed: Option<CachedServerCert<Ed25519Signature, Ed25519PublicKey>> =
+        cache.get("polyphony.chat", now).await.unwrap();
+    assert!(removed.is_none());
+}
+
+#[tokio::test]
+async fn redis_cert_cache_expiry() {
+    init_logger();
+    let redis = FakeRedis::default();
+    let cache = RedisCertCache::new(redis.clone())
+        .with_prefix("test:")
+        .with_negative_ttl(Duration::from_secs(60));
+    let cert = home_server_id_cert();
+
+    // Rejected certificates are cached for the negative TTL, together with the reason.
+    let rejected = Err(InvalidCert::InvalidValidity);
+    assert!(cache
+        .insert(
+            "evil.example",
+            &cert,
+            &rejected,
+            Timestamp::from_unix_seconds(100)
+        )
+        .await
+        .unwrap());
+    assert_eq!(redis.ttl("test:evil.example"), Some(60));
+    let cached: CachedServerCert<Ed25519Signature, Ed25519PublicKey> = cache
+        .get("evil.example", Timestamp::from_unix_seconds(100))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!cached.is_valid());
+    assert_eq!(
+        cached.validation_error,
+        Some(InvalidCert::InvalidValidity.to_string())
+    );
+
+    // The negative TTL never outlives the certificate.
+    cache
+        .insert(
+            "evil.example",
+            &cert,
+            &rejected,
+            Timestamp::from_unix_seconds(990),
+        )
+        .await
+        .unwrap();
+    assert_eq!(redis.ttl("test:evil.example"), Some(10));
+
+    // Expired certificates are not cached at all.
+    assert!(!cache
+        .insert(
+            "old.example",
+            &cert,
+            &Ok(()),
+            Timestamp::from_unix_seconds(1000)
+        )
+        .await
+        .unwrap());
+    assert_eq!(redis.ttl("test:old.example"), None);
+}
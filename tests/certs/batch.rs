@@ -0,0 +1,74 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::batch::validate_all;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::policy::ValidationPolicy;
+use polyproto::certs::Target;
+use polyproto::errors::InvalidCert;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_all_in_parallel() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let mut certs: Vec<_> = (1..=16u8)
+        .map(|serial| {
+            IdCert::from_actor_csr(
+                actor_csr("flori", &gen_priv_key()),
+                &home_server_key,
+                Uint::new(&[serial]).unwrap(),
+                home_server_subject(),
+                default_validity(),
+            )
+            .unwrap()
+        })
+        .collect();
+    // A certificate signed by another key fails, without affecting the others.
+    certs[5] = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &gen_priv_key(),
+        Uint::new(&[6]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+
+    let policy = ValidationPolicy::default();
+    let results = validate_all(
+        &certs,
+        Some(Target::Actor),
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+        &policy,
+    );
+    assert_eq!(results.len(), certs.len());
+    for (index, result) in results.iter().enumerate() {
+        assert_eq!(result.is_ok(), index != 5, "certificate {}", index);
+    }
+
+    let expired = validate_all(
+        &certs,
+        None,
+        Timestamp::from_unix_seconds(5000),
+        home_server_key.pubkey(),
+        &policy,
+    );
+    assert!(expired
+        .iter()
+        .all(|result| matches!(result, Err(InvalidCert::InvalidValidity))));
+    assert!(validate_all(
+        &certs[..0],
+        None,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+        &policy
+    )
+    .is_empty());
+}
@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use der::{Decode, Encode};
+use polyproto::certs::borrowed::{IdCertRef, IdCsrRef};
+use polyproto::certs::capabilities::{OID_BASIC_CONSTRAINTS, OID_KEY_USAGE};
+use polyproto::certs::idcert::IdCert;
+use polyproto::key::PrivateKey;
+use polyproto::signature::Signature;
+use polyproto::OID_RDN_COMMON_NAME;
+use spki::ObjectIdentifier;
+use x509_cert::Certificate;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn borrowed_cert() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1, 2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let der = cert.to_der().unwrap();
+    let cert_ref = IdCertRef::from_der(&der).unwrap();
+
+    assert_eq!(cert_ref.der(), der.as_slice());
+    assert_eq!(cert_ref.tbs_der(), cert.signature_data().unwrap());
+    assert_eq!(cert_ref.serial_number().as_bytes(), &[1, 2]);
+    assert_eq!(
+        cert_ref.issuer().to_name().unwrap(),
+        cert.id_cert_tbs.issuer
+    );
+    assert_eq!(
+        cert_ref.subject().der(),
+        cert.id_cert_tbs.subject.to_der().unwrap()
+    );
+    assert_eq!(
+        cert_ref
+            .subject()
+            .get_str(ObjectIdentifier::from_str(OID_RDN_COMMON_NAME).unwrap()),
+        Some("flori")
+    );
+    assert_eq!(cert_ref.validity(), default_validity());
+    assert!(cert_ref.valid_at(100));
+    assert!(!cert_ref.valid_at(1001));
+
+    let extensions = Certificate::from_der(&der)
+        .unwrap()
+        .tbs_certificate
+        .extensions
+        .unwrap();
+    assert_eq!(cert_ref.extensions().count(), extensions.len());
+    for (extension_ref, extension) in cert_ref.extensions().zip(&extensions) {
+        assert_eq!(extension_ref.oid, extension.extn_id);
+        assert_eq!(extension_ref.critical, extension.critical);
+        assert_eq!(extension_ref.value, extension.extn_value.as_bytes());
+    }
+    assert!(cert_ref
+        .extension(ObjectIdentifier::from_str(OID_KEY_USAGE).unwrap())
+        .is_some());
+
+    cert_ref
+        .verify_signature::<Ed25519Signature, _>(home_server_key.pubkey())
+        .unwrap();
+    assert!(cert_ref
+        .verify_signature::<Ed25519Signature, _>(gen_priv_key().pubkey())
+        .is_err());
+    assert_eq!(cert_ref.to_id_cert().unwrap(), cert);
+
+    assert!(IdCertRef::from_der(&der[..der.len() - 1]).is_err());
+    let mut trailing = der.clone();
+    trailing.push(0);
+    assert!(IdCertRef::from_der(&trailing).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn borrowed_csr() {
+    init_logger();
+    let csr = actor_csr("flori", &gen_priv_key());
+    let der = csr.to_der().unwrap();
+    let csr_ref = IdCsrRef::from_der(&der).unwrap();
+
+    assert_eq!(csr_ref.info_der(), csr.signature_data().unwrap());
+    assert_eq!(csr_ref.subject().to_name().unwrap(), csr.inner_csr.subject);
+    assert!(csr_ref
+        .attribute(ObjectIdentifier::from_str(OID_BASIC_CONSTRAINTS).unwrap())
+        .is_some());
+    assert_eq!(csr_ref.extensions().count(), 0);
+    assert_eq!(
+        csr_ref.signature_algorithm().oid,
+        Ed25519Signature::algorithm_identifier().oid
+    );
+    csr_ref
+        .verify_signature::<Ed25519Signature, Ed25519PublicKey>()
+        .unwrap();
+    assert_eq!(csr_ref.to_id_csr().unwrap(), csr);
+
+    let mut tampered = der.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(IdCsrRef::from_der(&tampered)
+        .unwrap()
+        .verify_signature::<Ed25519Signature, Ed25519PublicKey>()
+        .is_err());
+    // Certificates are not CSRs.
+    assert!(IdCsrRef::from_der(&actor_id_cert("flori").to_der().unwrap()).is_err());
+}
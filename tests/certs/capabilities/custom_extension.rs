@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{OctetString, Uint, Utf8StringRef};
+use der::Encode;
+use polyproto::certs::capabilities::{Capabilities, CustomExtension, OID_BASIC_CONSTRAINTS};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::errors::ConversionError;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::Constrained;
+use spki::ObjectIdentifier;
+use x509_cert::ext::{Extension, Extensions};
+
+use crate::common::*;
+
+fn oid() -> ObjectIdentifier {
+    ObjectIdentifier::from_str("1.3.6.1.4.1.57423.2.1").unwrap()
+}
+
+fn value() -> Vec<u8> {
+    Utf8StringRef::new("chat.polyphony")
+        .unwrap()
+        .to_der()
+        .unwrap()
+}
+
+#[test]
+fn custom_extension_values() {
+    let custom_extension = CustomExtension::new(oid(), false, &value()).unwrap();
+    assert_eq!(custom_extension.oid(), oid());
+    assert!(!custom_extension.is_critical());
+    assert_eq!(custom_extension.value(), value().as_slice());
+
+    assert!(CustomExtension::new(oid(), false, &[]).is_err());
+    assert!(CustomExtension::new(oid(), false, &[0x0c, 0x05, b'p']).is_err());
+    assert!(CustomExtension::new(
+        ObjectIdentifier::from_str(OID_BASIC_CONSTRAINTS).unwrap(),
+        false,
+        &value()
+    )
+    .is_err());
+
+    let extension = Extension::try_from(custom_extension.clone()).unwrap();
+    assert_eq!(
+        CustomExtension::try_from(extension.clone()).unwrap(),
+        custom_extension
+    );
+    let mut critical = extension;
+    critical.critical = true;
+    assert!(matches!(
+        CustomExtension::try_from(critical),
+        Err(ConversionError::UnknownCriticalExtension { .. })
+    ));
+}
+
+#[test]
+fn issue_cert_with_custom_extension() {
+    init_logger();
+    let capabilities = Capabilities::actor_default()
+        .with_custom_extension(CustomExtension::new(oid(), false, &value()).unwrap())
+        .unwrap();
+    assert!(capabilities
+        .clone()
+        .with_custom_extension(CustomExtension::new(oid(), true, &value()).unwrap())
+        .is_err());
+
+    let key = gen_priv_key();
+    let csr = IdCsr::new(
+        &actor_subject("flori"),
+        &key,
+        &capabilities,
+        Some(Target::Actor),
+    )
+    .unwrap();
+    let csr = IdCsr::from_der(&csr.to_der().unwrap(), Some(Target::Actor)).unwrap();
+    assert_eq!(csr.inner_csr.capabilities, capabilities);
+
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        csr,
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let decoded = IdCert::from_der(
+        &cert.to_der().unwrap(),
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+    )
+    .unwrap();
+    let custom_extension = decoded
+        .id_cert_tbs
+        .capabilities
+        .custom_extension(oid())
+        .unwrap();
+    assert_eq!(custom_extension.value(), value().as_slice());
+}
+
+#[test]
+fn preserve_unknown_non_critical_extensions() {
+    init_logger();
+    let mut extensions = Extensions::try_from(Capabilities::actor_default()).unwrap();
+    extensions.push(Extension {
+        extn_id: oid(),
+        critical: false,
+        extn_value: OctetString::new(value()).unwrap(),
+    });
+    let capabilities = Capabilities::try_from(extensions.clone()).unwrap();
+    assert_eq!(capabilities.custom_extensions.len(), 1);
+    assert_eq!(
+        capabilities.custom_extension(oid()).unwrap().value(),
+        value().as_slice()
+    );
+    assert_eq!(Extensions::try_from(capabilities).unwrap(), extensions);
+
+    let mut critical = extensions.clone();
+    critical[2].critical = true;
+    assert!(matches!(
+        Capabilities::try_from(critical),
+        Err(ConversionError::UnknownCriticalExtension { .. })
+    ));
+
+    // Extensions must not be attached more than once.
+    let mut duplicate = Capabilities::try_from(extensions).unwrap();
+    duplicate
+        .custom_extensions
+        .push(CustomExtension::new(oid(), false, &value()).unwrap());
+    assert!(duplicate.validate(None).is_err());
+}
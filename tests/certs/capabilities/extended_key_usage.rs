@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use polyproto::certs::capabilities::{
+    Capabilities, ExtendedKeyUsage, ExtendedKeyUsages, OID_EXTENDED_KEY_USAGE,
+};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::Constrained;
+use spki::ObjectIdentifier;
+use x509_cert::attr::{Attribute, Attributes};
+use x509_cert::ext::{Extension, Extensions};
+
+use crate::common::*;
+
+fn custom_purpose() -> ExtendedKeyUsage {
+    ExtendedKeyUsage::Other(ObjectIdentifier::from_str("1.3.6.1.4.1.57423.1.1").unwrap())
+}
+
+#[test]
+fn extended_key_usage_oids() {
+    for extended_key_usage in [
+        ExtendedKeyUsage::ServerAuth,
+        ExtendedKeyUsage::ClientAuth,
+        ExtendedKeyUsage::TimeStamping,
+        ExtendedKeyUsage::OcspSigning,
+        ExtendedKeyUsage::AnyExtendedKeyUsage,
+        custom_purpose(),
+    ] {
+        assert_eq!(
+            ExtendedKeyUsage::from(extended_key_usage.oid()),
+            extended_key_usage
+        );
+    }
+    assert_eq!(
+        ExtendedKeyUsage::from(ObjectIdentifier::from_str("1.3.6.1.5.5.7.3.2").unwrap()),
+        ExtendedKeyUsage::ClientAuth
+    );
+}
+
+#[test]
+fn extended_key_usages_conversions() {
+    init_logger();
+    let extended_key_usages =
+        ExtendedKeyUsages::new(&[ExtendedKeyUsage::ClientAuth, custom_purpose()]);
+
+    let extension = Extension::try_from(extended_key_usages.clone()).unwrap();
+    assert_eq!(extension.extn_id.to_string(), OID_EXTENDED_KEY_USAGE);
+    assert!(!extension.critical);
+    assert_eq!(
+        ExtendedKeyUsages::try_from(extension.clone()).unwrap(),
+        extended_key_usages
+    );
+    let mut critical = extension;
+    critical.critical = true;
+    assert_eq!(
+        ExtendedKeyUsages::try_from(critical).unwrap(),
+        extended_key_usages
+    );
+
+    let attribute = Attribute::try_from(extended_key_usages.clone()).unwrap();
+    assert_eq!(
+        ExtendedKeyUsages::try_from(attribute).unwrap(),
+        extended_key_usages
+    );
+
+    assert!(Extension::try_from(ExtendedKeyUsages::default()).is_err());
+    assert!(Attribute::try_from(ExtendedKeyUsages::default()).is_err());
+}
+
+#[test]
+fn capabilities_carry_extended_key_usage() {
+    init_logger();
+    let capabilities =
+        Capabilities::actor_default().with_extended_key_usage(ExtendedKeyUsages::new(&[
+            ExtendedKeyUsage::ClientAuth,
+            ExtendedKeyUsage::TimeStamping,
+        ]));
+    let extensions = Extensions::try_from(capabilities.clone()).unwrap();
+    assert_eq!(extensions.len(), 3);
+    assert_eq!(Capabilities::try_from(extensions).unwrap(), capabilities);
+    let attributes = Attributes::try_from(capabilities.clone()).unwrap();
+    assert_eq!(Capabilities::try_from(attributes).unwrap(), capabilities);
+
+    // Capabilities without extended key usages do not carry the extension.
+    let extensions = Extensions::try_from(Capabilities::actor_default()).unwrap();
+    assert_eq!(extensions.len(), 2);
+
+    let key = gen_priv_key();
+    let csr = IdCsr::new(
+        &actor_subject("flori"),
+        &key,
+        &capabilities,
+        Some(Target::Actor),
+    )
+    .unwrap();
+    assert_eq!(csr.inner_csr.capabilities, capabilities);
+
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        csr,
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let decoded = IdCert::from_der(
+        &cert.to_der().unwrap(),
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded.id_cert_tbs.capabilities, capabilities);
+}
+
+#[test]
+fn extended_key_usage_rules_for_targets() {
+    init_logger();
+    let actor = |usages: &[ExtendedKeyUsage]| {
+        Capabilities::actor_default().with_extended_key_usage(ExtendedKeyUsages::new(usages))
+    };
+    let home_server = |usages: &[ExtendedKeyUsage]| {
+        Capabilities::home_server_default().with_extended_key_usage(ExtendedKeyUsages::new(usages))
+    };
+
+    actor(&[ExtendedKeyUsage::ClientAuth, custom_purpose()])
+        .validate(Some(Target::Actor))
+        .unwrap();
+    for reserved in [
+        ExtendedKeyUsage::ServerAuth,
+        ExtendedKeyUsage::OcspSigning,
+        ExtendedKeyUsage::AnyExtendedKeyUsage,
+    ] {
+        assert!(actor(&[reserved]).validate(Some(Target::Actor)).is_err());
+        actor(&[reserved]).validate(None).unwrap();
+    }
+    assert!(
+        actor(&[ExtendedKeyUsage::ClientAuth, ExtendedKeyUsage::ClientAuth])
+            .validate(None)
+            .is_err()
+    );
+
+    home_server(&[]).validate(Some(Target::HomeServer)).unwrap();
+    home_server(&[ExtendedKeyUsage::ServerAuth, ExtendedKeyUsage::OcspSigning])
+        .validate(Some(Target::HomeServer))
+        .unwrap();
+    home_server(&[ExtendedKeyUsage::AnyExtendedKeyUsage])
+        .validate(Some(Target::HomeServer))
+        .unwrap();
+    assert!(home_server(&[ExtendedKeyUsage::ClientAuth])
+        .validate(Some(Target::HomeServer))
+        .is_err());
+
+    let key = gen_priv_key();
+    assert!(IdCsr::new(
+        &actor_subject("flori"),
+        &key,
+        &actor(&[ExtendedKeyUsage::ServerAuth]),
+        Some(Target::Actor)
+    )
+    .is_err());
+}
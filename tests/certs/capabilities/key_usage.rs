@@ -4,7 +4,9 @@
 
 use der::asn1::BitString;
 use log::trace;
-use polyproto::certs::capabilities::{KeyUsage, KeyUsages};
+use polyproto::certs::capabilities::{BasicConstraints, Capabilities, KeyUsage, KeyUsages};
+use polyproto::errors::{ConstraintError, KeyUsageViolation};
+use polyproto::Constrained;
 
 use crate::common::init_logger;
 
@@ -63,6 +65,22 @@ fn to_bitstring() {
     assert_eq!(bitstring.raw_bytes(), &[255]);
 }
 
+#[test]
+fn to_bitstring_leaves_out_trailing_zero_bits() {
+    init_logger();
+    let bitstring = KeyUsages::new(&[KeyUsage::DigitalSignature]).to_bitstring();
+    assert_eq!(bitstring.raw_bytes(), &[128]);
+    assert_eq!(bitstring.unused_bits(), 7);
+
+    let bitstring = KeyUsages::new(&[KeyUsage::KeyCertSign, KeyUsage::CrlSign]).to_bitstring();
+    assert_eq!(bitstring.raw_bytes(), &[6]);
+    assert_eq!(bitstring.unused_bits(), 1);
+
+    let bitstring = KeyUsages::new(&[]).to_bitstring();
+    assert!(bitstring.raw_bytes().is_empty());
+    assert_eq!(bitstring.unused_bits(), 0);
+}
+
 #[test]
 fn from_bitstring() {
     let bitstring = BitString::new(7, [128, 255]).unwrap();
@@ -112,3 +130,68 @@ fn from_bitstring() {
     expected.sort();
     assert_eq!(key_usages.key_usages, expected);
 }
+
+fn capabilities(key_usages: &[KeyUsage], ca: bool, path_length: Option<u64>) -> Capabilities {
+    Capabilities {
+        key_usage: KeyUsages::new(key_usages),
+        basic_constraints: BasicConstraints { ca, path_length },
+        ..Capabilities::actor_default()
+    }
+}
+
+#[test]
+fn reject_invalid_key_usage_combinations() {
+    init_logger();
+    use KeyUsage::*;
+    for (capabilities, violation) in [
+        (
+            capabilities(&[KeyEncipherment], false, None),
+            KeyUsageViolation::MissingSigningCapability,
+        ),
+        (
+            capabilities(&[DigitalSignature, ContentCommitment], false, None),
+            KeyUsageViolation::SignatureAndContentCommitment,
+        ),
+        (
+            capabilities(&[DigitalSignature, KeyCertSign], false, None),
+            KeyUsageViolation::KeyCertSignWithoutCa,
+        ),
+        (
+            capabilities(&[CrlSign], true, Some(0)),
+            KeyUsageViolation::CaWithoutKeyCertSign,
+        ),
+        (
+            capabilities(&[DigitalSignature], false, Some(0)),
+            KeyUsageViolation::PathLengthWithoutCa,
+        ),
+        (
+            capabilities(&[DigitalSignature, EncipherOnly], false, None),
+            KeyUsageViolation::EncipherOnlyWithoutKeyAgreement,
+        ),
+        (
+            capabilities(&[DigitalSignature, DecipherOnly], false, None),
+            KeyUsageViolation::DecipherOnlyWithoutKeyAgreement,
+        ),
+        (
+            capabilities(
+                &[DigitalSignature, KeyAgreement, EncipherOnly, DecipherOnly],
+                false,
+                None,
+            ),
+            KeyUsageViolation::EncipherOnlyAndDecipherOnly,
+        ),
+    ] {
+        assert_eq!(
+            capabilities.validate(None),
+            Err(ConstraintError::InvalidKeyUsage(violation))
+        );
+    }
+
+    // The order of the flags does not matter.
+    capabilities(&[KeyAgreement, EncipherOnly, DigitalSignature], false, None)
+        .validate(None)
+        .unwrap();
+    capabilities(&[CrlSign, KeyCertSign], true, None)
+        .validate(None)
+        .unwrap();
+}
@@ -2,4 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod custom_extension;
+mod extended_key_usage;
 mod key_usage;
+mod presets;
@@ -0,0 +1,175 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use polyproto::certs::capabilities::{Capabilities, KeyUsage};
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::Constrained;
+use x509_cert::ext::Extensions;
+
+use crate::common::{actor_subject, gen_priv_key, home_server_subject, init_logger};
+
+#[test]
+fn presets_pass_validation_for_their_targets() {
+    init_logger();
+    let key = gen_priv_key();
+    for (capabilities, subject, target) in [
+        (
+            Capabilities::actor_default(),
+            actor_subject("flori"),
+            Target::Actor,
+        ),
+        (
+            Capabilities::service_default(),
+            actor_subject("bridge"),
+            Target::Actor,
+        ),
+        (
+            Capabilities::home_server_default(),
+            home_server_subject(),
+            Target::HomeServer,
+        ),
+        (
+            Capabilities::default_actor(),
+            actor_subject("flori"),
+            Target::Actor,
+        ),
+        (
+            Capabilities::default_home_server(),
+            home_server_subject(),
+            Target::HomeServer,
+        ),
+    ] {
+        capabilities.validate(Some(target)).unwrap();
+        let csr = IdCsr::new(&subject, &key, &capabilities, Some(target)).unwrap();
+        assert_eq!(csr.inner_csr.capabilities, capabilities);
+        let extensions = Extensions::try_from(capabilities.clone()).unwrap();
+        assert_eq!(Capabilities::try_from(extensions).unwrap(), capabilities);
+    }
+    assert!(IdCsr::new(
+        &home_server_subject(),
+        &key,
+        &Capabilities::actor_default(),
+        Some(Target::HomeServer)
+    )
+    .is_err());
+    assert!(IdCsr::new(
+        &actor_subject("flori"),
+        &key,
+        &Capabilities::home_server_default(),
+        Some(Target::Actor)
+    )
+    .is_err());
+}
+
+#[test]
+fn presets_match_recommendations() {
+    let actor = Capabilities::actor_default();
+    assert_eq!(actor.key_usage.key_usages, vec![KeyUsage::DigitalSignature]);
+    assert!(!actor.basic_constraints.ca);
+    assert!(Capabilities::default().key_usage.key_usages.is_empty());
+    assert!(!Capabilities::default().basic_constraints.ca);
+    assert_eq!(Capabilities::default_guest(), actor);
+    assert_eq!(Capabilities::default_actor(), actor);
+
+    let home_server = Capabilities::home_server_default();
+    assert!(home_server
+        .key_usage
+        .key_usages
+        .contains(&KeyUsage::KeyCertSign));
+    assert!(home_server
+        .key_usage
+        .key_usages
+        .contains(&KeyUsage::CrlSign));
+    assert!(home_server.basic_constraints.ca);
+    assert_eq!(home_server.basic_constraints.path_length, Some(0));
+
+    // Unlike home_server_default(), default_home_server() allows issuing intermediate CAs.
+    let home_server = Capabilities::default_home_server();
+    assert_eq!(
+        home_server.key_usage.key_usages,
+        vec![KeyUsage::KeyCertSign]
+    );
+    assert!(home_server.basic_constraints.ca);
+    assert_eq!(home_server.basic_constraints.path_length, Some(1));
+
+    let service = Capabilities::service_default();
+    assert!(!service.basic_constraints.ca);
+    assert!(!service
+        .key_usage
+        .key_usages
+        .contains(&KeyUsage::ContentCommitment));
+}
+
+#[test]
+fn builder_produces_presets() {
+    let actor = Capabilities::builder()
+        .with_digital_signature()
+        .build()
+        .unwrap();
+    assert_eq!(actor, Capabilities::actor_default());
+    assert_eq!(actor, Capabilities::default_actor());
+
+    let home_server = Capabilities::builder()
+        .with_crl_sign()
+        .with_ca(Some(0))
+        .build()
+        .unwrap();
+    let mut key_usages = home_server.key_usage.key_usages.clone();
+    key_usages.sort();
+    assert_eq!(key_usages, vec![KeyUsage::KeyCertSign, KeyUsage::CrlSign]);
+    assert_eq!(
+        home_server.basic_constraints,
+        Capabilities::home_server_default().basic_constraints
+    );
+    home_server.validate(Some(Target::HomeServer)).unwrap();
+    assert_eq!(
+        Capabilities::builder().with_ca(Some(1)).build().unwrap(),
+        Capabilities::default_home_server()
+    );
+
+    let service = Capabilities::builder()
+        .with_digital_signature()
+        .with_key_encipherment()
+        .build()
+        .unwrap();
+    assert_eq!(service, Capabilities::service_default());
+}
+
+#[test]
+fn builder_keeps_flags_consistent() {
+    // ContentCommitment replaces DigitalSignature, and vice versa.
+    let capabilities = Capabilities::builder()
+        .with_digital_signature()
+        .with_content_commitment()
+        .build()
+        .unwrap();
+    assert_eq!(
+        capabilities.key_usage.key_usages,
+        vec![KeyUsage::ContentCommitment]
+    );
+    let capabilities = Capabilities::builder()
+        .with_content_commitment()
+        .with_digital_signature()
+        .with_digital_signature()
+        .build()
+        .unwrap();
+    assert_eq!(
+        capabilities.key_usage.key_usages,
+        vec![KeyUsage::DigitalSignature]
+    );
+
+    // Neither a signing flag nor the CA flag.
+    assert!(Capabilities::builder().build().is_err());
+    assert!(Capabilities::builder()
+        .with_key_encipherment()
+        .build()
+        .is_err());
+    // KeyCertSign without the CA flag.
+    assert!(Capabilities::builder()
+        .with_digital_signature()
+        .with_key_usage(KeyUsage::KeyCertSign)
+        .build()
+        .is_err());
+}
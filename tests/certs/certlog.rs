@@ -0,0 +1,124 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use polyproto::certs::certlog::{leaf_hash, CertLog, InclusionProof, SignedTreeHead};
+use polyproto::certs::idcert::IdCert;
+use polyproto::errors::{InvalidCert, PublicKeyError};
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn logged_certs(count: usize) -> (CertLog, Vec<Cert>) {
+    let certs: Vec<Cert> = (0..count).map(|_| actor_id_cert("flori")).collect();
+    let mut log = CertLog::new();
+    for (index, cert) in certs.iter().enumerate() {
+        assert_eq!(log.append(cert).unwrap(), index as u64);
+    }
+    (log, certs)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn inclusion_proofs() {
+    init_logger();
+    let (log, certs) = logged_certs(9);
+    assert_eq!(log.size(), 9);
+    for tree_size in 1..=9u64 {
+        let root = log.root_hash_at(tree_size).unwrap();
+        for (index, cert) in certs.iter().enumerate().take(tree_size as usize) {
+            let proof = log.inclusion_proof(index as u64, tree_size).unwrap();
+            let leaf = leaf_hash(cert).unwrap();
+            assert!(proof.verify(&leaf, &root));
+            assert!(!proof.verify(&leaf_hash(&certs[(index + 1) % 9]).unwrap(), &root));
+        }
+    }
+    assert_eq!(log.root_hash_at(9), Some(log.root_hash()));
+    assert!(log.root_hash_at(10).is_none());
+    assert!(log.inclusion_proof(3, 3).is_none());
+    assert!(log.inclusion_proof(0, 10).is_none());
+    assert_eq!(log.position(&certs[4]), Some(4));
+    assert_eq!(log.position(&actor_id_cert("flori")), None);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn consistency_proofs() {
+    init_logger();
+    let (log, _) = logged_certs(8);
+    for new_size in 0..=8u64 {
+        let new_root = log.root_hash_at(new_size).unwrap();
+        for old_size in 0..=new_size {
+            let old_root = log.root_hash_at(old_size).unwrap();
+            let proof = log.consistency_proof(old_size, new_size).unwrap();
+            assert!(proof.verify(&old_root, &new_root));
+            if old_size > 0 && old_size < new_size {
+                assert!(!proof.verify(&new_root, &new_root));
+            }
+        }
+    }
+    assert!(log.consistency_proof(5, 4).is_none());
+    assert!(log.consistency_proof(4, 9).is_none());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verify_logged() {
+    init_logger();
+    let key = gen_priv_key();
+    let (mut log, certs) = logged_certs(5);
+    let tree_head = log.sign_tree_head(Timestamp::from(100), &key);
+    let proof = log.inclusion_proof(2, tree_head.tree_size).unwrap();
+    tree_head
+        .verify_logged(&certs[2], &proof, key.pubkey())
+        .unwrap();
+    assert_eq!(
+        tree_head.verify_logged(&certs[3], &proof, key.pubkey()),
+        Err(InvalidCert::NotLogged)
+    );
+    assert_eq!(
+        tree_head.verify_logged(&certs[2], &proof, gen_priv_key().pubkey()),
+        Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature))
+    );
+
+    // A certificate logged after the tree head was signed is not covered by it.
+    let late = actor_id_cert("flori");
+    log.append(&late).unwrap();
+    let proof = log.inclusion_proof(5, log.size()).unwrap();
+    assert_eq!(
+        tree_head.verify_logged(&late, &proof, key.pubkey()),
+        Err(InvalidCert::NotLogged)
+    );
+    let new_head = log.sign_tree_head(Timestamp::from(200), &key);
+    new_head.verify_logged(&late, &proof, key.pubkey()).unwrap();
+    assert!(log
+        .consistency_proof(tree_head.tree_size, new_head.tree_size)
+        .unwrap()
+        .verify(&tree_head.root_hash, &new_head.root_hash));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn certlog_serde_roundtrip() {
+    init_logger();
+    let key = gen_priv_key();
+    let (log, _) = logged_certs(3);
+    let tree_head = log.sign_tree_head(Timestamp::from(100), &key);
+    let json = serde_json::to_string(&tree_head).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SignedTreeHead<Ed25519Signature>>(&json).unwrap(),
+        tree_head
+    );
+    let proof = log.inclusion_proof(1, 3).unwrap();
+    let json = serde_json::to_value(&proof).unwrap();
+    assert_eq!(
+        serde_json::from_value::<InclusionProof>(json.clone()).unwrap(),
+        proof
+    );
+    let mut truncated = json;
+    truncated["path"][0] = "abcd".into();
+    assert!(serde_json::from_value::<InclusionProof>(truncated).is_err());
+}
@@ -0,0 +1,432 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::UtcTime;
+use polyproto::certs::chain::{CertPool, IdCertChain, IssuerRejection, RejectedIssuer};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::status::{
+    RevocationCheck, RevocationMode, RevocationReason, RevocationStatus, StatusFuture,
+};
+use polyproto::certs::Target;
+use polyproto::errors::{ChainBuildError, InvalidCert, PublicKeyError};
+use polyproto::timestamp::Timestamp;
+use x509_cert::time::{Time, Validity};
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn validity(not_before: u64, not_after: u64) -> Validity {
+    Validity {
+        not_before: Time::UtcTime(
+            UtcTime::from_unix_duration(Duration::from_secs(not_before)).unwrap(),
+        ),
+        not_after: Time::UtcTime(
+            UtcTime::from_unix_duration(Duration::from_secs(not_after)).unwrap(),
+        ),
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_chain() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let root = home_server_cert(&root_key, &root_key, Some(0));
+    let chain = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity()),
+        vec![root],
+    );
+    assert_eq!(chain.len(), 2);
+    chain
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .unwrap();
+    assert_eq!(
+        chain.validate_chain(Timestamp::from(2000), Target::Actor),
+        Err(InvalidCert::InvalidValidity)
+    );
+    assert!(chain
+        .validate_chain(Timestamp::from(100), Target::HomeServer)
+        .is_err());
+
+    let certs: Vec<Cert> = chain.clone().into();
+    assert_eq!(IdCertChain::from_certs(certs).unwrap(), chain);
+    assert!(IdCertChain::<Ed25519Signature, Ed25519PublicKey>::from_certs(Vec::new()).is_none());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_chain_with_tolerance() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let root = home_server_cert(&root_key, &root_key, Some(0));
+    let chain = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity()),
+        vec![root],
+    );
+    let tolerance = Duration::from_secs(60);
+    for time in [0, 1060] {
+        assert_eq!(
+            chain.validate_chain(Timestamp::from(time), Target::Actor),
+            Err(InvalidCert::InvalidValidity)
+        );
+        chain
+            .validate_chain_with_tolerance(Timestamp::from(time), Target::Actor, tolerance)
+            .unwrap();
+    }
+    assert_eq!(
+        chain.validate_chain_with_tolerance(Timestamp::from(1061), Target::Actor, tolerance),
+        Err(InvalidCert::InvalidValidity)
+    );
+
+    // The tolerance does not allow certificates to outlive their issuers.
+    let root_key = gen_priv_key();
+    let root = home_server_cert(&root_key, &root_key, Some(0));
+    let outlives_issuer = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, validity(10, 1030)),
+        vec![root],
+    );
+    assert_eq!(
+        outlives_issuer.validate_chain_with_tolerance(
+            Timestamp::from(100),
+            Target::Actor,
+            tolerance
+        ),
+        Err(InvalidCert::InvalidValidity)
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_chain_rejects_bad_links() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let root = home_server_cert(&root_key, &root_key, Some(0));
+
+    let wrong_issuer = IdCertChain::new(
+        actor_cert(
+            "flori",
+            &gen_priv_key(),
+            &gen_priv_key(),
+            2,
+            default_validity(),
+        ),
+        vec![root.clone()],
+    );
+    assert_eq!(
+        wrong_issuer.validate_chain(Timestamp::from(100), Target::Actor),
+        Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature))
+    );
+
+    let outlives_issuer = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, validity(10, 5000)),
+        vec![root.clone()],
+    );
+    assert_eq!(
+        outlives_issuer.validate_chain(Timestamp::from(100), Target::Actor),
+        Err(InvalidCert::InvalidValidity)
+    );
+
+    let no_issuers = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity()),
+        Vec::new(),
+    );
+    assert!(no_issuers
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .is_err());
+
+    let not_ca = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity()),
+        vec![actor_cert(
+            "flori",
+            &gen_priv_key(),
+            &root_key,
+            2,
+            default_validity(),
+        )],
+    );
+    assert!(not_ca
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_chain_path_length() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let intermediate_key = gen_priv_key();
+    let intermediate = home_server_cert(&intermediate_key, &root_key, Some(0));
+    let actor = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &intermediate_key,
+        2,
+        default_validity(),
+    );
+
+    let chain = IdCertChain::new(
+        actor.clone(),
+        vec![
+            intermediate.clone(),
+            home_server_cert(&root_key, &root_key, Some(1)),
+        ],
+    );
+    chain
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .unwrap();
+
+    let chain = IdCertChain::new(
+        actor,
+        vec![
+            intermediate,
+            home_server_cert(&root_key, &root_key, Some(0)),
+        ],
+    );
+    assert!(chain
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_chain_path_length_counts_ca_leaf() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let home_server = home_server_cert(&gen_priv_key(), &root_key, None);
+    for (path_length, valid) in [(Some(0), false), (Some(1), true), (None, true)] {
+        let chain = IdCertChain::new(
+            home_server.clone(),
+            vec![home_server_cert(&root_key, &root_key, path_length)],
+        );
+        assert_eq!(
+            chain
+                .validate_chain(Timestamp::from(100), Target::HomeServer)
+                .is_ok(),
+            valid,
+            "{path_length:?}"
+        );
+    }
+
+    let intermediate_key = gen_priv_key();
+    let home_server = home_server_cert(&gen_priv_key(), &intermediate_key, None);
+    for (root_path_length, intermediate_path_length, valid) in [
+        (Some(1), Some(0), false),
+        (Some(1), Some(1), false),
+        (Some(2), Some(0), false),
+        (Some(2), Some(1), true),
+        (None, None, true),
+    ] {
+        let chain = IdCertChain::new(
+            home_server.clone(),
+            vec![
+                home_server_cert(&intermediate_key, &root_key, intermediate_path_length),
+                home_server_cert(&root_key, &root_key, root_path_length),
+            ],
+        );
+        assert_eq!(
+            chain
+                .validate_chain(Timestamp::from(100), Target::HomeServer)
+                .is_ok(),
+            valid,
+            "{root_path_length:?}, {intermediate_path_length:?}"
+        );
+    }
+
+    let pool = CertPool::new().with_cert(home_server_cert(&root_key, &root_key, Some(0)));
+    let leaf = home_server_cert(&gen_priv_key(), &root_key, None);
+    match pool.build_chain(leaf.clone(), Timestamp::from(100)) {
+        Err(ChainBuildError::NoIssuer { rejected, .. }) => assert_eq!(
+            rejected,
+            vec![RejectedIssuer {
+                index: 0,
+                reason: IssuerRejection::PathLengthExceeded
+            }]
+        ),
+        other => panic!("Expected NoIssuer, got {:?}", other),
+    }
+    let pool = CertPool::new().with_cert(home_server_cert(&root_key, &root_key, Some(1)));
+    pool.build_chain(leaf, Timestamp::from(100))
+        .unwrap()
+        .validate_chain(Timestamp::from(100), Target::HomeServer)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn build_chain() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let intermediate_key = gen_priv_key();
+    let root = home_server_cert(&root_key, &root_key, Some(1));
+    let intermediate = home_server_cert(&intermediate_key, &root_key, Some(0));
+    let other_root_key = gen_priv_key();
+    let pool: CertPool<Ed25519Signature, Ed25519PublicKey> = vec![
+        home_server_cert(&other_root_key, &other_root_key, Some(1)),
+        root.clone(),
+        intermediate.clone(),
+    ]
+    .into();
+
+    let actor = actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity());
+    let chain = pool
+        .build_chain(actor.clone(), Timestamp::from(100))
+        .unwrap();
+    assert_eq!(chain, IdCertChain::new(actor, vec![root.clone()]));
+    chain
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .unwrap();
+
+    let actor = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &intermediate_key,
+        2,
+        default_validity(),
+    );
+    let chain = pool
+        .build_chain(actor.clone(), Timestamp::from(100))
+        .unwrap();
+    assert_eq!(chain, IdCertChain::new(actor, vec![intermediate, root]));
+    chain
+        .validate_chain(Timestamp::from(100), Target::Actor)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn build_chain_diagnostics() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let actor = actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity());
+
+    assert_eq!(
+        CertPool::new().build_chain(actor.clone(), Timestamp::from(100)),
+        Err(ChainBuildError::NoIssuer {
+            subject: actor.id_cert_tbs.subject.to_string(),
+            rejected: Vec::new()
+        })
+    );
+
+    let pool = CertPool::new()
+        .with_cert(home_server_cert(&gen_priv_key(), &gen_priv_key(), None))
+        .with_cert(home_server_cert(&root_key, &root_key, None))
+        .with_cert(actor_cert(
+            "flori",
+            &gen_priv_key(),
+            &root_key,
+            2,
+            default_validity(),
+        ));
+    match pool.build_chain(actor.clone(), Timestamp::from(2000)) {
+        Err(ChainBuildError::NoIssuer { rejected, .. }) => assert_eq!(
+            rejected,
+            vec![
+                RejectedIssuer {
+                    index: 0,
+                    reason: IssuerRejection::InvalidValidity
+                },
+                RejectedIssuer {
+                    index: 1,
+                    reason: IssuerRejection::InvalidValidity
+                },
+            ]
+        ),
+        other => panic!("Expected NoIssuer, got {:?}", other),
+    }
+    match pool.build_chain(actor.clone(), Timestamp::from(100)) {
+        Ok(chain) => assert_eq!(chain.len(), 2),
+        other => panic!("Expected a chain, got {:?}", other),
+    }
+
+    let intermediate_key = gen_priv_key();
+    let pool = CertPool::new()
+        .with_cert(home_server_cert(&intermediate_key, &root_key, Some(0)))
+        .with_cert(home_server_cert(&root_key, &root_key, Some(0)));
+    let actor = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &intermediate_key,
+        2,
+        default_validity(),
+    );
+    match pool.build_chain(actor, Timestamp::from(100)) {
+        Err(ChainBuildError::NoIssuer { rejected, .. }) => assert_eq!(
+            rejected,
+            vec![
+                RejectedIssuer {
+                    index: 0,
+                    reason: IssuerRejection::NoPathToRoot
+                },
+                RejectedIssuer {
+                    index: 1,
+                    reason: IssuerRejection::BadSignature
+                },
+            ]
+        ),
+        other => panic!("Expected NoIssuer, got {:?}", other),
+    }
+}
+
+struct FixedStatus(RevocationStatus);
+
+impl RevocationCheck<Ed25519Signature, Ed25519PublicKey> for FixedStatus {
+    fn status<'a>(&'a self, _: &'a Cert, _: &'a Cert, _: Timestamp) -> StatusFuture<'a> {
+        Box::pin(async move { self.0 })
+    }
+}
+
+#[tokio::test]
+async fn validate_chain_with_revocation() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let root = home_server_cert(&root_key, &root_key, Some(0));
+    let chain = IdCertChain::new(
+        actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity()),
+        vec![root],
+    );
+    let time = Timestamp::from(100);
+
+    let good = FixedStatus(RevocationStatus::Good);
+    let unknown = FixedStatus(RevocationStatus::Unknown);
+    let revoked = FixedStatus(RevocationStatus::Revoked {
+        revoked_at: Timestamp::from(50),
+        reason: RevocationReason::KeyCompromise,
+    });
+    for mode in [RevocationMode::SoftFail, RevocationMode::HardFail] {
+        chain
+            .validate_chain_with_revocation(time, Target::Actor, &good, mode)
+            .await
+            .unwrap();
+        assert_eq!(
+            chain
+                .validate_chain_with_revocation(time, Target::Actor, &revoked, mode)
+                .await,
+            Err(InvalidCert::Revoked(Timestamp::from(50)))
+        );
+    }
+    chain
+        .validate_chain_with_revocation(time, Target::Actor, &unknown, RevocationMode::SoftFail)
+        .await
+        .unwrap();
+    assert_eq!(
+        chain
+            .validate_chain_with_revocation(time, Target::Actor, &unknown, RevocationMode::HardFail)
+            .await,
+        Err(InvalidCert::RevocationStatusUnknown)
+    );
+    assert_eq!(
+        chain
+            .validate_chain_with_revocation(
+                Timestamp::from(2000),
+                Target::Actor,
+                &good,
+                RevocationMode::SoftFail
+            )
+            .await,
+        Err(InvalidCert::InvalidValidity)
+    );
+}
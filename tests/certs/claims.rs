@@ -0,0 +1,152 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::{OctetString, Uint};
+use der::Encode;
+use polyproto::certs::claims::{ClaimValue, CustomClaims, OID_CUSTOM_CLAIMS};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcerttbs::IdCertTbs;
+use polyproto::certs::Target;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::Constrained;
+use spki::ObjectIdentifier;
+use x509_cert::ext::Extension;
+use x509_cert::TbsCertificate;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn issue_cert_with_claims() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let actor_key = gen_priv_key();
+    let claims = CustomClaims::new()
+        .with("chat.polyphony/role", "moderator")
+        .unwrap()
+        .with("chat.polyphony/tenant", 42u64)
+        .unwrap();
+    let tbs = IdCertTbs::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        Uint::new(&[1]).unwrap(),
+        home_server_key.algorithm_identifier(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+    .with_claims(claims.clone());
+    let cert = IdCert::from_tbs(tbs, &home_server_key, Target::Actor).unwrap();
+
+    let der = cert.to_der().unwrap();
+    let decoded = IdCert::from_der(
+        &der,
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded, cert);
+    assert_eq!(decoded.id_cert_tbs.claims(), &claims);
+    assert_eq!(
+        decoded.id_cert_tbs.claim("chat.polyphony/role"),
+        Some(&ClaimValue::Text("moderator".to_string()))
+    );
+    assert_eq!(
+        decoded
+            .id_cert_tbs
+            .claims()
+            .namespace("chat.polyphony")
+            .count(),
+        2
+    );
+
+    // Certificates without claims do not carry the extension.
+    let plain = IdCert::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let tbs = TbsCertificate::try_from(plain.id_cert_tbs.into_inner()).unwrap();
+    assert!(tbs
+        .extensions
+        .unwrap()
+        .iter()
+        .all(|extension| extension.extn_id.to_string() != OID_CUSTOM_CLAIMS));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn reject_invalid_claims_extension() {
+    init_logger();
+    let claims = CustomClaims::new()
+        .with("chat.polyphony/role", "admin")
+        .unwrap();
+    let mut extension = Extension::try_from(claims.clone()).unwrap();
+    assert!(!extension.critical);
+    assert_eq!(CustomClaims::try_from(extension.clone()).unwrap(), claims);
+
+    extension.critical = true;
+    assert!(CustomClaims::try_from(extension).is_err());
+
+    // Valid CBOR, but the key is not namespaced.
+    let mut cbor = vec![0xa1, 0x64];
+    cbor.extend_from_slice(b"role");
+    cbor.push(0xf5);
+    let extension = Extension {
+        extn_id: ObjectIdentifier::from_str(OID_CUSTOM_CLAIMS).unwrap(),
+        critical: false,
+        extn_value: OctetString::new(cbor).unwrap(),
+    };
+    assert!(CustomClaims::try_from(extension).is_err());
+
+    // The extension may only be present once.
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let mut tbs = TbsCertificate::try_from(cert.id_cert_tbs.into_inner()).unwrap();
+    let extensions = tbs.extensions.as_mut().unwrap();
+    extensions.push(Extension::try_from(claims.clone()).unwrap());
+    extensions.push(Extension::try_from(claims).unwrap());
+    assert!(
+        IdCertTbs::<Ed25519Signature, Ed25519PublicKey>::from_der_unchecked(&tbs.to_der().unwrap())
+            .is_err()
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn claim_limits() {
+    init_logger();
+    let mut claims = CustomClaims::new();
+    for i in 0..16 {
+        claims
+            .insert(&format!("chat.polyphony/claim-{}", i), i as u64)
+            .unwrap();
+    }
+    assert!(claims.validate(None).is_ok());
+    claims.insert("chat.polyphony/one-too-many", true).unwrap();
+    assert!(claims.validate(None).is_err());
+    assert!(Extension::try_from(claims)
+        .is_ok_and(|extension| CustomClaims::try_from(extension).is_err()));
+
+    let large = CustomClaims::new()
+        .with("chat.polyphony/blob", vec![0u8; 2048])
+        .unwrap();
+    assert!(large.validate(None).is_err());
+    assert!(Extension::try_from(large).is_err());
+
+    assert!(CustomClaims::new().with("role", "admin").is_err());
+}
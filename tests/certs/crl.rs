@@ -0,0 +1,243 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use der::pem::LineEnding;
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::crl::{CrlIssuerDesignation, IdCrl, RevokedEntry};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::pem::{load_any, PemObject};
+use polyproto::errors::InvalidCert;
+use polyproto::timestamp::Timestamp;
+use polyproto::verifier::{VerificationBudget, Verifier};
+use polyproto::Name;
+
+use crate::common::*;
+
+type Designation = CrlIssuerDesignation<Ed25519Signature, Ed25519PublicKey>;
+
+fn home_server() -> (
+    Ed25519PrivateKey,
+    IdCert<Ed25519Signature, Ed25519PublicKey>,
+) {
+    let priv_key = gen_priv_key();
+    let cert = IdCert::from_ca_csr(
+        home_server_csr(&priv_key),
+        &priv_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    (priv_key, cert)
+}
+
+fn crl_issuer_subject() -> Name {
+    Name::from_str("CN=revocation,DC=polyphony,DC=chat").unwrap()
+}
+
+fn designate(
+    crl_issuer_key: &Ed25519PrivateKey,
+    home_server_key: &Ed25519PrivateKey,
+    capabilities: &Capabilities,
+) -> Result<Designation, polyproto::errors::ConversionError> {
+    let csr = IdCsr::new(&crl_issuer_subject(), crl_issuer_key, capabilities, None)?;
+    CrlIssuerDesignation::issue(
+        csr,
+        home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+}
+
+fn revoked() -> Vec<RevokedEntry> {
+    vec![RevokedEntry {
+        serial_number: Uint::new(&[8]).unwrap(),
+        revocation_date: Timestamp::from_unix_seconds(50),
+    }]
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn direct_crl() {
+    init_logger();
+    let (home_server_key, home_server_cert) = home_server();
+    let crl = IdCrl::new(
+        home_server_subject(),
+        Timestamp::from_unix_seconds(100),
+        Some(Timestamp::from_unix_seconds(200)),
+        revoked(),
+        &home_server_key,
+    )
+    .unwrap();
+    crl.verify(&home_server_cert, Timestamp::from_unix_seconds(150))
+        .unwrap();
+    assert!(crl
+        .verify(&home_server_cert, Timestamp::from_unix_seconds(201))
+        .is_err());
+    assert!(crl.is_revoked(&Uint::new(&[8]).unwrap()));
+    assert!(!crl.is_revoked(&Uint::new(&[9]).unwrap()));
+
+    let der = crl.to_der().unwrap();
+    let decoded = IdCrl::<Ed25519Signature>::from_der_unchecked(&der).unwrap();
+    assert_eq!(decoded, crl);
+    decoded
+        .verify(&home_server_cert, Timestamp::from_unix_seconds(150))
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn delegated_crl() {
+    init_logger();
+    let (home_server_key, home_server_cert) = home_server();
+    let crl_issuer_key = gen_priv_key();
+    let designation = designate(
+        &crl_issuer_key,
+        &home_server_key,
+        &Capabilities::default_crl_issuer(),
+    )
+    .unwrap();
+    let crl = IdCrl::new(
+        crl_issuer_subject(),
+        Timestamp::from_unix_seconds(100),
+        None,
+        revoked(),
+        &crl_issuer_key,
+    )
+    .unwrap();
+    let time = Timestamp::from_unix_seconds(150);
+    crl.verify_delegated(&home_server_cert, &designation, time)
+        .unwrap();
+    // A delegated CRL is not signed by the home server key itself.
+    assert!(crl.verify(&home_server_cert, time).is_err());
+
+    // The designation must have been issued by this home server.
+    let (_, other_home_server_cert) = home_server();
+    assert!(crl
+        .verify_delegated(&other_home_server_cert, &designation, time)
+        .is_err());
+
+    // A CRL signed by any other key is rejected, even when claiming to come from the designated
+    // issuer.
+    let forged = IdCrl::new(
+        crl_issuer_subject(),
+        Timestamp::from_unix_seconds(100),
+        None,
+        Vec::new(),
+        &gen_priv_key(),
+    )
+    .unwrap();
+    assert!(forged
+        .verify_delegated(&home_server_cert, &designation, time)
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn designation_requires_crl_sign() {
+    let (home_server_key, _) = home_server();
+    assert!(designate(
+        &gen_priv_key(),
+        &home_server_key,
+        &Capabilities::actor_default()
+    )
+    .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn designation_must_be_signed_by_home_server() {
+    let (_, home_server_cert) = home_server();
+    let crl_issuer_key = gen_priv_key();
+    let designation = designate(
+        &crl_issuer_key,
+        &gen_priv_key(),
+        &Capabilities::default_crl_issuer(),
+    )
+    .unwrap();
+    assert!(designation
+        .verify(&home_server_cert, Timestamp::from_unix_seconds(150))
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn crl_pem_roundtrip() {
+    init_logger();
+    let (home_server_key, home_server_cert) = home_server();
+    let crl = IdCrl::new(
+        home_server_subject(),
+        Timestamp::from_unix_seconds(100),
+        None,
+        revoked(),
+        &home_server_key,
+    )
+    .unwrap();
+    let pem = crl.to_pem(LineEnding::LF).unwrap();
+    assert!(pem.starts_with("-----BEGIN X509 CRL-----"));
+    let time = Timestamp::from_unix_seconds(150);
+    assert_eq!(IdCrl::from_pem(&pem, &home_server_cert, time).unwrap(), crl);
+    let (_, other_home_server_cert) = home_server();
+    assert!(IdCrl::from_pem(&pem, &other_home_server_cert, time).is_err());
+    assert_eq!(
+        IdCrl::from_der(&crl.to_der().unwrap(), &home_server_cert, time).unwrap(),
+        crl
+    );
+
+    match load_any::<Ed25519Signature, Ed25519PublicKey>(&pem).unwrap() {
+        PemObject::RevocationList { crl: loaded } => assert_eq!(loaded, crl),
+        other => panic!("Expected a CRL, got {:?}", other),
+    }
+    // A certificate is not a CRL.
+    let cert_pem = home_server_cert.to_pem(LineEnding::LF).unwrap();
+    assert!(IdCrl::<Ed25519Signature>::from_pem_unchecked(&cert_pem).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn check_cert_against_crl() {
+    init_logger();
+    let (home_server_key, _) = home_server();
+    let crl = IdCrl::new(
+        home_server_subject(),
+        Timestamp::from_unix_seconds(100),
+        None,
+        revoked(),
+        &home_server_key,
+    )
+    .unwrap();
+    let issue = |serial: u8| {
+        IdCert::from_actor_csr(
+            actor_csr("flori", &gen_priv_key()),
+            &home_server_key,
+            Uint::new(&[serial]).unwrap(),
+            home_server_subject(),
+            default_validity(),
+        )
+        .unwrap()
+    };
+    let revoked_cert = issue(8);
+    let valid_cert = issue(9);
+    assert_eq!(
+        crl.check(&revoked_cert),
+        Err(InvalidCert::Revoked(Timestamp::from_unix_seconds(50)))
+    );
+    crl.check(&valid_cert).unwrap();
+
+    let mut verifier = Verifier::new(VerificationBudget {
+        max_crl_entries: 1,
+        ..Default::default()
+    });
+    verifier.check_revocation(&valid_cert, &crl).unwrap();
+    assert_eq!(verifier.crl_entries_examined(), 1);
+    assert!(matches!(
+        verifier.check_revocation(&revoked_cert, &crl),
+        Err(InvalidCert::BudgetExceeded(_))
+    ));
+}
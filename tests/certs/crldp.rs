@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::claims::CustomClaims;
+use polyproto::certs::crldp::{CrlDistributionPoint, OID_CRL_DISTRIBUTION_POINTS};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcerttbs::IdCertTbs;
+use polyproto::certs::Target;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use x509_cert::ext::Extension;
+use x509_cert::TbsCertificate;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn issue_cert_with_crl_distribution_point() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let distribution_point =
+        CrlDistributionPoint::new("https://polyphony.chat/.p2/core/v1/crl").unwrap();
+    let tbs = IdCertTbs::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        Uint::new(&[1]).unwrap(),
+        home_server_key.algorithm_identifier(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+    .with_claims(
+        CustomClaims::new()
+            .with("chat.polyphony/role", "moderator")
+            .unwrap(),
+    )
+    .with_crl_distribution_point(distribution_point.clone());
+    let cert = IdCert::from_tbs(tbs, &home_server_key, Target::Actor).unwrap();
+    assert_eq!(
+        cert.crl_distribution_point(),
+        Some("https://polyphony.chat/.p2/core/v1/crl")
+    );
+
+    let decoded = IdCert::from_der(
+        &cert.to_der().unwrap(),
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded, cert);
+    assert_eq!(
+        decoded.id_cert_tbs.crl_distribution_point,
+        Some(distribution_point)
+    );
+
+    let tbs = TbsCertificate::try_from(decoded.id_cert_tbs.into_inner()).unwrap();
+    let extension = tbs
+        .extensions
+        .unwrap()
+        .into_iter()
+        .find(|extension| extension.extn_id.to_string() == OID_CRL_DISTRIBUTION_POINTS)
+        .unwrap();
+    assert!(!extension.critical);
+
+    // Certificates without a distribution point do not carry the extension.
+    let plain = home_server_id_cert();
+    assert_eq!(plain.crl_distribution_point(), None);
+    let tbs = TbsCertificate::try_from(plain.id_cert_tbs.into_inner()).unwrap();
+    assert!(!tbs
+        .extensions
+        .unwrap()
+        .iter()
+        .any(|extension| extension.extn_id.to_string() == OID_CRL_DISTRIBUTION_POINTS));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn crl_distribution_point_urls() {
+    CrlDistributionPoint::new("http://localhost:3000/crl").unwrap();
+    assert!(CrlDistributionPoint::new("ftp://polyphony.chat/crl").is_err());
+    assert!(CrlDistributionPoint::new("https://").is_err());
+    assert!(CrlDistributionPoint::new("https://polyphony.chat/a crl").is_err());
+    assert!(CrlDistributionPoint::new("https://polyphöny.chat/crl").is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn crl_distribution_point_extension() {
+    let distribution_point = CrlDistributionPoint::new("https://polyphony.chat/crl").unwrap();
+    let extension = Extension::try_from(distribution_point.clone()).unwrap();
+    assert_eq!(
+        CrlDistributionPoint::try_from(extension.clone()).unwrap(),
+        distribution_point
+    );
+
+    let mut critical = extension.clone();
+    critical.critical = true;
+    assert!(CrlDistributionPoint::try_from(critical).is_err());
+
+    let claims = Extension::try_from(CustomClaims::new()).unwrap();
+    assert!(CrlDistributionPoint::try_from(claims).is_err());
+}
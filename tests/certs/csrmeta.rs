@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::csrmeta::{CsrGenerator, CsrMetadata, CsrReplayGuard};
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::errors::StaleCsr;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+fn csr_with_metadata(
+    key: &Ed25519PrivateKey,
+    metadata: &CsrMetadata,
+) -> IdCsr<Ed25519Signature, Ed25519PublicKey> {
+    IdCsr::new_with_metadata(
+        &actor_subject("flori"),
+        key,
+        &Capabilities::actor_default(),
+        metadata,
+        Some(Target::Actor),
+    )
+    .unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn metadata_roundtrip() {
+    init_logger();
+    let key = gen_priv_key();
+    let metadata = CsrMetadata::new()
+        .with_created_at(Timestamp::from_unix_seconds(1_700_000_000))
+        .with_expires_at(Timestamp::from_unix_seconds(1_700_000_600))
+        .with_nonce(b"nonce");
+    let csr = csr_with_metadata(&key, &metadata);
+    let der = csr.to_der().unwrap();
+    let decoded =
+        IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der(&der, Some(Target::Actor)).unwrap();
+    assert_eq!(decoded.inner_csr.metadata, metadata);
+    assert_eq!(decoded, csr);
+    // CSRs without metadata are unaffected.
+    let plain = actor_csr("flori", &key);
+    assert!(plain.inner_csr.metadata.is_empty());
+    let der = plain.to_der().unwrap();
+    assert_eq!(
+        IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der(&der, Some(Target::Actor)).unwrap(),
+        plain
+    );
+    // Metadata expiring before it was created is rejected.
+    assert!(IdCsr::new_with_metadata(
+        &actor_subject("flori"),
+        &key,
+        &Capabilities::actor_default(),
+        &CsrMetadata::new()
+            .with_created_at(Timestamp::from_unix_seconds(10))
+            .with_expires_at(Timestamp::from_unix_seconds(5)),
+        Some(Target::Actor),
+    )
+    .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn replay_guard_rejects_stale_and_replayed_csrs() {
+    init_logger();
+    let key = gen_priv_key();
+    let created_at = Timestamp::from_unix_seconds(10_000);
+    let mut guard =
+        CsrReplayGuard::new(Duration::from_secs(3600)).with_clock_skew(Duration::from_secs(60));
+    let csr = csr_with_metadata(
+        &key,
+        &CsrMetadata::fresh(created_at, Duration::from_secs(600), &mut rand::rngs::OsRng),
+    );
+    let now = Timestamp::from_unix_seconds(10_100);
+    guard.accept(&csr, now).unwrap();
+    assert_eq!(guard.accept(&csr, now), Err(StaleCsr::Replayed));
+    assert_eq!(guard.len(), 1);
+
+    let expired = csr_with_metadata(
+        &key,
+        &CsrMetadata::fresh(created_at, Duration::from_secs(60), &mut rand::rngs::OsRng),
+    );
+    assert_eq!(
+        guard.accept(&expired, now),
+        Err(StaleCsr::Expired(Timestamp::from_unix_seconds(10_060)))
+    );
+    let old = csr_with_metadata(
+        &key,
+        &CsrMetadata::new()
+            .with_created_at(created_at)
+            .with_nonce(b"a"),
+    );
+    assert_eq!(
+        guard.accept(&old, Timestamp::from_unix_seconds(13_600)),
+        Err(StaleCsr::TooOld(created_at))
+    );
+    let future = csr_with_metadata(
+        &key,
+        &CsrMetadata::new()
+            .with_created_at(Timestamp::from_unix_seconds(10_200))
+            .with_nonce(b"b"),
+    );
+    assert_eq!(
+        guard.accept(&future, now),
+        Err(StaleCsr::CreatedInFuture(Timestamp::from_unix_seconds(
+            10_200
+        )))
+    );
+    assert_eq!(
+        guard.accept(&actor_csr("flori", &key), now),
+        Err(StaleCsr::MissingMetadata)
+    );
+
+    guard.prune(Timestamp::from_unix_seconds(13_599));
+    assert_eq!(guard.len(), 1);
+    guard.prune(Timestamp::from_unix_seconds(13_600));
+    assert!(guard.is_empty());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn generator_regenerates_expired_csrs() {
+    init_logger();
+    let key = gen_priv_key();
+    let mut generator = CsrGenerator::new(
+        &actor_subject("flori"),
+        &Capabilities::actor_default(),
+        Some(Target::Actor),
+        Duration::from_secs(600),
+    );
+    let mut rng = rand::rngs::OsRng;
+    let first = generator
+        .csr(&key, &mut rng, Timestamp::from_unix_seconds(1000))
+        .unwrap();
+    assert_eq!(
+        generator
+            .csr(&key, &mut rng, Timestamp::from_unix_seconds(1599))
+            .unwrap(),
+        first
+    );
+    let renewed = generator
+        .csr(&key, &mut rng, Timestamp::from_unix_seconds(1600))
+        .unwrap();
+    assert_ne!(renewed, first);
+    assert_eq!(
+        renewed.inner_csr.metadata.created_at,
+        Some(Timestamp::from_unix_seconds(1600))
+    );
+    generator.mark_used();
+    let next = generator
+        .csr(&key, &mut rng, Timestamp::from_unix_seconds(1600))
+        .unwrap();
+    assert_ne!(
+        next.inner_csr.metadata.nonce,
+        renewed.inner_csr.metadata.nonce
+    );
+    // A new signing key results in a new CSR.
+    let other_key = gen_priv_key();
+    let rekeyed = generator
+        .csr(&other_key, &mut rng, Timestamp::from_unix_seconds(1600))
+        .unwrap();
+    assert_eq!(&rekeyed.inner_csr.subject_public_key, other_key.pubkey());
+}
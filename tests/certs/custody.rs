@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::crl::{IdCrl, RevokedEntry};
+use polyproto::certs::custody::{
+    CustodyEvent, CustodyExport, IdentityStatement, IdentityStatementKind,
+};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::superseded::SupersededNotice;
+use polyproto::timestamp::Timestamp;
+use polyproto::types::FederationId;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn export_and_verify_custody_bundle() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let federation_id = FederationId::new("flori@polyphony.chat").unwrap();
+
+    let old_key = gen_priv_key();
+    let old_cert = actor_cert("flori", &old_key, &home_server_key, 2, default_validity());
+    let new_key = gen_priv_key();
+    let new_cert = actor_cert("flori", &new_key, &home_server_key, 3, default_validity());
+    let notice = SupersededNotice::new(
+        &old_cert,
+        &new_cert,
+        Timestamp::from_unix_seconds(200),
+        &home_server_key,
+    )
+    .unwrap();
+    let alias = IdentityStatement::new(
+        federation_id.clone(),
+        IdentityStatementKind::Alias(FederationId::new("flori@example.com").unwrap()),
+        Timestamp::from_unix_seconds(250),
+        &new_cert,
+        &new_key,
+    )
+    .unwrap();
+    let crl = IdCrl::new(
+        home_server_subject(),
+        Timestamp::from_unix_seconds(300),
+        None,
+        vec![RevokedEntry {
+            serial_number: Uint::new(&[2]).unwrap(),
+            revocation_date: Timestamp::from_unix_seconds(300),
+        }],
+        &home_server_key,
+    )
+    .unwrap();
+
+    // Events are added out of order.
+    let bundle = CustodyExport::new(federation_id.clone())
+        .with_revocation(old_cert.cert_id().unwrap(), crl)
+        .with_statement(alias)
+        .with_cert(new_cert.clone())
+        .with_rotation(notice)
+        .with_cert(old_cert.clone())
+        .sign(
+            &home_server_cert,
+            &home_server_key,
+            Timestamp::from_unix_seconds(400),
+        )
+        .unwrap();
+    assert_eq!(bundle.events.len(), 5);
+    assert_eq!(bundle.certs().count(), 2);
+    assert!(matches!(
+        bundle.events.last(),
+        Some(CustodyEvent::Revoked(_))
+    ));
+    bundle
+        .verify(std::slice::from_ref(&home_server_cert))
+        .unwrap();
+
+    // Unknown exporter.
+    let other_home_server_key = gen_priv_key();
+    let other_home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&other_home_server_key),
+        &other_home_server_key,
+        Uint::new(&[9]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    assert!(bundle.verify(&[other_home_server_cert]).is_err());
+
+    // Removing an event invalidates the signature of the bundle.
+    let mut tampered = bundle.clone();
+    tampered.events.remove(2);
+    assert!(tampered
+        .verify(std::slice::from_ref(&home_server_cert))
+        .is_err());
+
+    // Certificates of other actors are rejected, even when signed by the exporter.
+    let other_cert = actor_cert(
+        "someone",
+        &gen_priv_key(),
+        &home_server_key,
+        4,
+        default_validity(),
+    );
+    let mixed = CustodyExport::new(federation_id.clone())
+        .with_cert(old_cert.clone())
+        .with_cert(other_cert)
+        .sign(
+            &home_server_cert,
+            &home_server_key,
+            Timestamp::from_unix_seconds(400),
+        )
+        .unwrap();
+    assert!(mixed
+        .verify(std::slice::from_ref(&home_server_cert))
+        .is_err());
+
+    // Events must not refer to certificates missing from the bundle.
+    let notice = SupersededNotice::new(
+        &old_cert,
+        &new_cert,
+        Timestamp::from_unix_seconds(200),
+        &home_server_key,
+    )
+    .unwrap();
+    let incomplete = CustodyExport::new(federation_id)
+        .with_cert(old_cert)
+        .with_rotation(notice)
+        .sign(
+            &home_server_cert,
+            &home_server_key,
+            Timestamp::from_unix_seconds(400),
+        )
+        .unwrap();
+    assert!(incomplete.verify(&[home_server_cert]).is_err());
+}
@@ -0,0 +1,136 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::dynamic::{DynIdCert, DynIdCsr};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::Target;
+use polyproto::errors::{InvalidCert, PublicKeyError};
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::registry::AlgorithmRegistry;
+use polyproto::signature::Signature;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+fn registry() -> AlgorithmRegistry {
+    AlgorithmRegistry::new().with_algorithm::<Ed25519Signature, Ed25519PublicKey>("Ed25519")
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn dyn_id_cert_roundtrip() {
+    init_logger();
+    let registry = registry();
+    let time = Timestamp::from_unix_seconds(100);
+    let home_server_key = gen_priv_key();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let home_server_der = home_server_cert.to_der().unwrap();
+    let home_server_public_key = home_server_key.pubkey().public_key_info();
+    let dyn_home_server_cert = DynIdCert::from_der(
+        &home_server_der,
+        Target::HomeServer,
+        time,
+        &registry,
+        &home_server_public_key,
+    )
+    .unwrap();
+    assert_eq!(
+        dyn_home_server_cert.subject_public_key(),
+        &home_server_public_key
+    );
+
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let dyn_actor_cert = DynIdCert::try_from(actor_cert.clone()).unwrap();
+    dyn_actor_cert
+        .full_verify_actor(time, &registry, &home_server_public_key)
+        .unwrap();
+    assert_eq!(dyn_actor_cert.cert_id(), actor_cert.cert_id());
+    assert_eq!(
+        dyn_actor_cert.signature_algorithm(),
+        &Ed25519Signature::algorithm_identifier()
+    );
+    assert_eq!(
+        dyn_actor_cert.to_der().unwrap(),
+        actor_cert.to_der().unwrap()
+    );
+    assert_eq!(
+        dyn_actor_cert
+            .to_id_cert::<Ed25519Signature, Ed25519PublicKey>()
+            .unwrap(),
+        actor_cert
+    );
+
+    // Expired.
+    assert_eq!(
+        dyn_actor_cert.full_verify_actor(
+            Timestamp::from_unix_seconds(5000),
+            &registry,
+            &home_server_public_key
+        ),
+        Err(InvalidCert::InvalidValidity)
+    );
+    // Signed by another home server.
+    assert_eq!(
+        dyn_actor_cert.full_verify_actor(
+            time,
+            &registry,
+            &gen_priv_key().pubkey().public_key_info()
+        ),
+        Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature))
+    );
+    // Unknown algorithm.
+    assert_eq!(
+        dyn_actor_cert.full_verify_actor(time, &AlgorithmRegistry::new(), &home_server_public_key),
+        Err(InvalidCert::PublicKeyError(
+            PublicKeyError::UnsupportedAlgorithm(Ed25519Signature::algorithm_identifier().oid)
+        ))
+    );
+    // An actor certificate is not a home server certificate.
+    assert!(DynIdCert::from_der(
+        &actor_cert.to_der().unwrap(),
+        Target::HomeServer,
+        time,
+        &registry,
+        &home_server_public_key,
+    )
+    .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn dyn_id_csr_roundtrip() {
+    let registry = registry();
+    let actor_key = gen_priv_key();
+    let csr = actor_csr("flori", &actor_key);
+    let der = csr.to_der().unwrap();
+    let dyn_csr = DynIdCsr::from_der(&der, Some(Target::Actor), &registry).unwrap();
+    assert_eq!(
+        dyn_csr.subject_public_key(),
+        &actor_key.pubkey().public_key_info()
+    );
+    assert_eq!(
+        dyn_csr
+            .to_id_csr::<Ed25519Signature, Ed25519PublicKey>()
+            .unwrap(),
+        csr
+    );
+    assert_eq!(DynIdCsr::try_from(csr).unwrap(), dyn_csr);
+    assert!(DynIdCsr::from_der(&der, Some(Target::HomeServer), &registry).is_err());
+    assert!(DynIdCsr::from_der(&der, Some(Target::Actor), &AlgorithmRegistry::new()).is_err());
+}
@@ -0,0 +1,91 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use polyproto::certs::capabilities::{Capabilities, KeyUsage, KeyUsages};
+use polyproto::certs::guest::{GuestCertBuilder, GuestProfile, MAX_GUEST_LIFETIME};
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+fn guest_builder(
+    priv_key: &Ed25519PrivateKey,
+) -> GuestCertBuilder<Ed25519Signature, Ed25519PublicKey> {
+    GuestCertBuilder::new(
+        actor_csr("guest1", priv_key),
+        Uint::new(&[42]).unwrap(),
+        home_server_subject(),
+    )
+    .not_before(Timestamp::from_unix_seconds(100))
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn build_guest_cert() {
+    init_logger();
+    let actor_key = gen_priv_key();
+    let home_server_key = gen_priv_key();
+    let cert = guest_builder(&actor_key).build(&home_server_key).unwrap();
+    assert!(cert.is_guest());
+    assert_eq!(cert.id_cert_tbs.capabilities, Capabilities::default_guest());
+    assert!(cert.valid_at(Timestamp::from_unix_seconds(100 + 5 * 60)));
+    assert!(!cert.valid_at(Timestamp::from_unix_seconds(100 + 11 * 60)));
+    cert.full_verify_actor(Timestamp::from_unix_seconds(200), home_server_key.pubkey())
+        .unwrap();
+    GuestProfile::default().validate_cert(&cert).unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn guest_cert_capabilities_are_restricted() {
+    let actor_key = gen_priv_key();
+    let mut capabilities = Capabilities::actor_default();
+    capabilities.key_usage = KeyUsages::new(&[KeyUsage::ContentCommitment]);
+    let csr = IdCsr::new(
+        &actor_subject("guest1"),
+        &actor_key,
+        &capabilities,
+        Some(Target::Actor),
+    )
+    .unwrap();
+    let cert = GuestCertBuilder::new(csr, Uint::new(&[42]).unwrap(), home_server_subject())
+        .build(&gen_priv_key())
+        .unwrap();
+    assert_eq!(cert.id_cert_tbs.capabilities, Capabilities::default_guest());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn guest_lifetime_is_bounded() {
+    let actor_key = gen_priv_key();
+    assert!(guest_builder(&actor_key)
+        .lifetime(MAX_GUEST_LIFETIME + Duration::from_secs(1))
+        .build(&gen_priv_key())
+        .is_err());
+    assert!(guest_builder(&actor_key)
+        .lifetime(Duration::ZERO)
+        .build(&gen_priv_key())
+        .is_err());
+    let cert = guest_builder(&actor_key)
+        .lifetime(MAX_GUEST_LIFETIME)
+        .build(&gen_priv_key())
+        .unwrap();
+    let strict = GuestProfile {
+        max_lifetime: Duration::from_secs(60),
+    };
+    assert!(strict.validate_cert(&cert).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn regular_actor_cert_is_not_a_guest() {
+    let cert = actor_id_cert("flori");
+    assert!(!cert.is_guest());
+    assert!(GuestProfile::default().validate_cert(&cert).is_err());
+}
@@ -11,16 +11,18 @@ use der::asn1::{BitString, Ia5String, Uint, UtcTime};
 use der::{Decode, Encode};
 use ed25519_dalek::{Signature as Ed25519DalekSignature, Signer, SigningKey, VerifyingKey};
 use polyproto::certs::capabilities::{self, Capabilities};
+use polyproto::certs::fingerprint::{CertFingerprint, FingerprintDigest};
 use polyproto::certs::idcert::IdCert;
-use polyproto::certs::{PublicKeyInfo, Target};
+use polyproto::certs::{actor_name, PublicKeyInfo, SessionId, Target};
 use polyproto::errors::composite::ConversionError;
-use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::key::{AsyncPrivateKey, KeyFingerprint, KeyGen, PrivateKey, PublicKey, SignFuture};
 use polyproto::signature::Signature;
+use polyproto::timestamp::Timestamp;
 use rand::rngs::OsRng;
 use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SignatureBitStringEncoding};
 use thiserror::Error;
 use x509_cert::attr::Attributes;
-use x509_cert::name::RdnSequence;
+use x509_cert::name::{Name, RdnSequence};
 use x509_cert::request::CertReq;
 use x509_cert::time::{Time, Validity};
 use x509_cert::Certificate;
@@ -36,7 +38,7 @@ fn test_create_actor_cert() {
     println!("Private Key is: {:?}", priv_key.key.to_bytes());
     println!("Public Key is: {:?}", priv_key.public_key.key.to_bytes());
     println!();
-    let mut capabilities = Capabilities::default_actor();
+    let mut capabilities = Capabilities::actor_default();
     capabilities
         .key_usage
         .key_usages
@@ -66,7 +68,7 @@ fn test_create_actor_cert() {
         },
     )
     .unwrap();
-    let cert_data = cert.clone().to_der().unwrap();
+    let cert_data = cert.to_der().unwrap();
     let data = Certificate::try_from(cert).unwrap().to_der().unwrap();
     assert_eq!(cert_data, data);
 }
@@ -84,7 +86,7 @@ fn test_create_ca_cert() {
     let csr = polyproto::certs::idcsr::IdCsr::new(
         &RdnSequence::from_str("CN=root,DC=polyphony,DC=chat").unwrap(),
         &priv_key,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(Target::HomeServer),
     )
     .unwrap();
@@ -103,7 +105,7 @@ fn test_create_ca_cert() {
         },
     )
     .unwrap();
-    let cert_data = cert.clone().to_der().unwrap();
+    let cert_data = cert.to_der().unwrap();
     let data = Certificate::try_from(cert).unwrap().to_der().unwrap();
     assert_eq!(cert_data, data);
 }
@@ -124,7 +126,7 @@ fn mismatched_dcs_in_csr_and_cert() {
         )
         .unwrap(),
         &priv_key,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(Target::Actor),
     )
     .unwrap();
@@ -143,7 +145,7 @@ fn mismatched_dcs_in_csr_and_cert() {
         },
     )
     .unwrap();
-    let cert_data = cert.clone().to_der().unwrap();
+    let cert_data = cert.to_der().unwrap();
     let data = Certificate::try_from(cert).unwrap().to_der().unwrap();
     assert_eq!(cert_data, data);
 }
@@ -162,7 +164,7 @@ fn cert_from_pem() {
         )
         .unwrap(),
         &priv_key_actor,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(Target::Actor),
     )
     .unwrap();
@@ -182,11 +184,11 @@ fn cert_from_pem() {
         },
     )
     .unwrap();
-    let data = cert.clone().to_pem(der::pem::LineEnding::LF).unwrap();
+    let data = cert.to_pem(der::pem::LineEnding::LF).unwrap();
     let cert_from_pem = IdCert::from_pem(
         &data,
         polyproto::certs::Target::Actor,
-        10,
+        Timestamp::from_unix_seconds(10),
         &priv_key_home_server.public_key,
     )
     .unwrap();
@@ -199,7 +201,7 @@ fn cert_from_pem() {
     let csr = polyproto::certs::idcsr::IdCsr::new(
         &RdnSequence::from_str("CN=root,DC=polyphony,DC=chat").unwrap(),
         &priv_key_actor,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(Target::HomeServer),
     )
     .unwrap();
@@ -218,11 +220,11 @@ fn cert_from_pem() {
         },
     )
     .unwrap();
-    let data = cert.clone().to_pem(der::pem::LineEnding::LF).unwrap();
+    let data = cert.to_pem(der::pem::LineEnding::LF).unwrap();
     let cert_from_pem = IdCert::from_pem(
         &data,
         polyproto::certs::Target::Actor,
-        10,
+        Timestamp::from_unix_seconds(10),
         &priv_key_home_server.public_key,
     )
     .unwrap();
@@ -247,7 +249,7 @@ fn cert_from_der() {
         )
         .unwrap(),
         &priv_key_actor,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(Target::Actor),
     )
     .unwrap();
@@ -267,11 +269,11 @@ fn cert_from_der() {
         },
     )
     .unwrap();
-    let data = cert.clone().to_der().unwrap();
+    let data = cert.to_der().unwrap();
     let cert_from_der = IdCert::from_der(
         &data,
         polyproto::certs::Target::Actor,
-        10,
+        Timestamp::from_unix_seconds(10),
         &priv_key_home_server.public_key,
     )
     .unwrap();
@@ -284,7 +286,7 @@ fn cert_from_der() {
     let csr = polyproto::certs::idcsr::IdCsr::new(
         &RdnSequence::from_str("CN=root,DC=polyphony,DC=chat").unwrap(),
         &priv_key_actor,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(Target::HomeServer),
     )
     .unwrap();
@@ -303,11 +305,11 @@ fn cert_from_der() {
         },
     )
     .unwrap();
-    let data = cert.clone().to_der().unwrap();
+    let data = cert.to_der().unwrap();
     let cert_from_der = IdCert::from_der(
         &data,
         polyproto::certs::Target::Actor,
-        10,
+        Timestamp::from_unix_seconds(10),
         &priv_key_home_server.public_key,
     )
     .unwrap();
@@ -317,3 +319,401 @@ fn cert_from_der() {
     );
     assert_eq!(cert_from_der, cert);
 }
+
+fn self_signed_home_server_cert<S: Signature, K: KeyGen<S>>() -> IdCert<S, K::PublicKey> {
+    let priv_key = K::generate(&mut OsRng);
+    let csr = polyproto::certs::idcsr::IdCsr::new(
+        &home_server_subject(),
+        &priv_key,
+        &Capabilities::home_server_default(),
+        Some(Target::HomeServer),
+    )
+    .unwrap();
+    IdCert::from_ca_csr(
+        csr,
+        &priv_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn generic_key_generation() {
+    let cert = self_signed_home_server_cert::<Ed25519Signature, Ed25519PrivateKey>();
+    cert.full_verify_home_server(Timestamp::from_unix_seconds(100))
+        .unwrap();
+}
+
+/// Stands in for a signer backed by a KMS or HSM, where creating a signature is a remote call.
+struct RemoteSigner {
+    key: Ed25519PrivateKey,
+    available: bool,
+}
+
+impl AsyncPrivateKey<Ed25519Signature> for RemoteSigner {
+    type PublicKey = Ed25519PublicKey;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        self.key.pubkey()
+    }
+
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignFuture<'a, Ed25519Signature> {
+        Box::pin(async move {
+            tokio::task::yield_now().await;
+            if !self.available {
+                return Err(ConversionError::SignerError(
+                    "KMS is unavailable".to_string(),
+                ));
+            }
+            Ok(self.key.sign(data))
+        })
+    }
+}
+
+#[tokio::test]
+async fn issue_certs_with_async_signer() {
+    init_logger();
+    let signer = RemoteSigner {
+        key: gen_priv_key(),
+        available: true,
+    };
+    let home_server_cert = IdCert::from_ca_csr_async(
+        home_server_csr(&signer.key),
+        &signer,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .await
+    .unwrap();
+    home_server_cert
+        .full_verify_home_server(Timestamp::from_unix_seconds(100))
+        .unwrap();
+    let actor_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr_async(
+        actor_csr("flori", &actor_key),
+        &signer,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .await
+    .unwrap();
+    actor_cert
+        .full_verify_actor(Timestamp::from_unix_seconds(100), signer.pubkey())
+        .unwrap();
+}
+
+#[tokio::test]
+async fn async_signer_failure() {
+    let signer = RemoteSigner {
+        key: gen_priv_key(),
+        available: false,
+    };
+    let result = IdCert::from_actor_csr_async(
+        actor_csr("flori", &gen_priv_key()),
+        &signer,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .await;
+    assert_eq!(
+        result,
+        Err(ConversionError::SignerError(
+            "KMS is unavailable".to_string()
+        ))
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn subject_key_fingerprint() {
+    use sha2::Digest;
+
+    init_logger();
+    let key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &key),
+        &gen_priv_key(),
+        Uint::new(&[8]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let fingerprint = key.pubkey().fingerprint().unwrap();
+    assert_eq!(cert.key_fingerprint().unwrap(), fingerprint);
+    assert_ne!(gen_priv_key().pubkey().fingerprint().unwrap(), fingerprint);
+    let spki = key.pubkey().public_key_info().to_der().unwrap();
+    assert_eq!(
+        fingerprint.as_bytes().as_slice(),
+        sha2::Sha256::digest(spki).as_slice()
+    );
+
+    let hex = fingerprint.to_hex();
+    assert_eq!(hex.len(), 64);
+    assert_eq!(fingerprint.to_string(), hex);
+    assert_eq!(KeyFingerprint::from_str(&hex).unwrap(), fingerprint);
+    assert_eq!(
+        KeyFingerprint::from_str(&hex.to_uppercase()).unwrap(),
+        fingerprint
+    );
+    assert!(KeyFingerprint::from_str(&hex[..62]).is_err());
+    let base64url = fingerprint.to_base64url();
+    assert_eq!(base64url.len(), 43);
+    assert!(base64url
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn cert_fingerprint() {
+    use sha2::Digest;
+
+    init_logger();
+    let cert = actor_id_cert("flori");
+    let der = cert.to_der().unwrap();
+    let sha256 = cert.fingerprint(FingerprintDigest::Sha256).unwrap();
+    assert_eq!(sha256.digest(), FingerprintDigest::Sha256);
+    assert_eq!(sha256.as_bytes(), sha2::Sha256::digest(&der).as_slice());
+    let sha512 = cert.fingerprint(FingerprintDigest::Sha512).unwrap();
+    assert_eq!(sha512.as_bytes(), sha2::Sha512::digest(&der).as_slice());
+    // The fingerprint covers the whole certificate, not only its key.
+    assert_ne!(
+        actor_id_cert("flori")
+            .fingerprint(FingerprintDigest::Sha256)
+            .unwrap(),
+        sha256
+    );
+
+    let hex = sha256.to_hex();
+    assert_eq!(hex.len(), 64);
+    assert_eq!(sha256.to_string(), hex);
+    let colon_hex = sha256.to_colon_hex();
+    assert_eq!(colon_hex.len(), 95);
+    assert_eq!(colon_hex.replace(':', ""), hex.to_uppercase());
+    assert_eq!(sha256.to_base64().len(), 44);
+    assert_eq!(sha512.to_base64().len(), 88);
+    for encoded in [hex, colon_hex] {
+        assert_eq!(CertFingerprint::from_str(&encoded).unwrap(), sha256);
+    }
+    assert_eq!(
+        CertFingerprint::from_str(&sha512.to_colon_hex()).unwrap(),
+        sha512
+    );
+    assert!(CertFingerprint::from_str(&sha256.to_hex()[..62]).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verify_signature() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    actor_cert
+        .verify_signature(home_server_key.pubkey())
+        .unwrap();
+    assert_eq!(
+        actor_cert.verify_signature(gen_priv_key().pubkey()),
+        Err(polyproto::errors::InvalidCert::PublicKeyError(
+            polyproto::errors::PublicKeyError::BadSignature
+        ))
+    );
+    // The signature does not cover the encoding of the whole certificate.
+    assert!(home_server_key
+        .pubkey()
+        .verify_signature(&actor_cert.signature, &actor_cert.to_der().unwrap())
+        .is_err());
+
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    home_server_cert
+        .verify_signature(&home_server_cert.id_cert_tbs.subject_public_key)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn session_id_accessors() {
+    init_logger();
+    let session_id = SessionId::from_str("client1").unwrap();
+    assert_eq!(
+        actor_name("flori", "polyphony.chat", &session_id).unwrap(),
+        Name::from_str(
+            "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1"
+        )
+        .unwrap()
+    );
+    assert!(actor_name("flori", "polyphony.chat,DC=evil", &session_id).is_err());
+
+    let actor_csr = actor_csr("flori", &gen_priv_key());
+    assert_eq!(actor_csr.session_id(), Some(session_id.clone()));
+    let actor_cert = actor_id_cert("flori");
+    assert_eq!(actor_cert.session_id(), Some(session_id.clone()));
+    assert_eq!(actor_cert.id_cert_tbs.session_id(), Some(session_id));
+
+    assert_eq!(home_server_csr(&gen_priv_key()).session_id(), None);
+    assert_eq!(home_server_id_cert().session_id(), None);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn valid_at_time_types() {
+    init_logger();
+    let cert = actor_id_cert("flori");
+    // The default validity period lasts from 10 until 1000 seconds after the Unix epoch.
+    for (seconds, valid) in [(9, false), (10, true), (1000, true), (1001, false)] {
+        assert_eq!(cert.valid_at(seconds), valid, "{seconds}");
+        assert_eq!(
+            cert.valid_at(Timestamp::from_unix_seconds(seconds)),
+            valid,
+            "{seconds}"
+        );
+        assert_eq!(
+            cert.valid_at(std::time::UNIX_EPOCH + Duration::from_secs(seconds)),
+            valid,
+            "{seconds}"
+        );
+    }
+    assert!(cert.valid_at(std::time::UNIX_EPOCH + Duration::from_millis(1_000_999)));
+    assert!(!cert.valid_at(std::time::SystemTime::now()));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn infer_target() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let home_server_cert = home_server_id_cert();
+    assert_eq!(actor_cert.infer_target().unwrap(), Target::Actor);
+    assert_eq!(home_server_cert.infer_target().unwrap(), Target::HomeServer);
+
+    // Without a target, certificates are verified for the inferred target.
+    let time = Timestamp::from_unix_seconds(100);
+    let der = actor_cert.to_der().unwrap();
+    assert_eq!(
+        IdCert::from_der(&der, None, time, home_server_key.pubkey()).unwrap(),
+        actor_cert
+    );
+    assert!(IdCert::from_der(&der, None, time, gen_priv_key().pubkey()).is_err());
+    let pem = home_server_cert
+        .clone()
+        .to_pem(der::pem::LineEnding::LF)
+        .unwrap();
+    assert_eq!(
+        IdCert::from_pem(&pem, None, time, gen_priv_key().pubkey()).unwrap(),
+        home_server_cert
+    );
+
+    // Capabilities and subject must agree.
+    let mut contradicting = actor_cert.clone();
+    contradicting.id_cert_tbs.capabilities = Capabilities::home_server_default();
+    assert!(contradicting.infer_target().is_err());
+    let mut contradicting = home_server_cert;
+    contradicting.id_cert_tbs.capabilities = Capabilities::actor_default();
+    assert!(contradicting.infer_target().is_err());
+    let mut missing_session = actor_cert;
+    missing_session.id_cert_tbs.subject =
+        Name::from_str("CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat").unwrap();
+    assert!(missing_session.infer_target().is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn cached_der_encoding() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let mut cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let uncached = Certificate::try_from(cert.clone())
+        .unwrap()
+        .to_der()
+        .unwrap();
+    assert_eq!(cert.to_der().unwrap(), uncached);
+
+    let first = cert.id_cert_tbs.encoded_der().unwrap();
+    let second = cert.id_cert_tbs.encoded_der().unwrap();
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert_eq!(cert.signature_data().unwrap(), first);
+    // Clones share the cached encoding.
+    let clone = cert.clone();
+    assert_eq!(
+        clone.id_cert_tbs.encoded_der().unwrap().as_ptr(),
+        first.as_ptr()
+    );
+    cert.verify_signature(home_server_key.pubkey()).unwrap();
+
+    // Changing a field invalidates the cached encoding.
+    cert.id_cert_tbs.serial_number = Uint::new(&[2]).unwrap();
+    let changed = Certificate::try_from(cert.clone()).unwrap();
+    assert_eq!(
+        cert.id_cert_tbs.encoded_der().unwrap(),
+        changed.tbs_certificate.to_der().unwrap()
+    );
+    assert_eq!(cert.to_der().unwrap(), changed.to_der().unwrap());
+    assert!(cert.verify_signature(home_server_key.pubkey()).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn decoded_cert_caches_input_der() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let der = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+    .to_der()
+    .unwrap();
+    let mut cert = IdCert::<Ed25519Signature, Ed25519PublicKey>::from_der_unchecked(&der).unwrap();
+
+    // The encoding of a decoded certificate is the input it was decoded from.
+    let first = cert.id_cert_tbs.encoded_der().unwrap();
+    let second = cert.id_cert_tbs.encoded_der().unwrap();
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert!(der.windows(first.len()).any(|window| window == first));
+    assert_eq!(cert.to_der().unwrap(), der);
+    cert.verify_signature(home_server_key.pubkey()).unwrap();
+
+    // Changing a field invalidates the seeded encoding.
+    cert.id_cert_tbs.serial_number = Uint::new(&[2]).unwrap();
+    let changed = Certificate::try_from(cert.clone()).unwrap();
+    assert_eq!(cert.to_der().unwrap(), changed.to_der().unwrap());
+    assert_ne!(cert.to_der().unwrap(), der);
+    assert!(cert.verify_signature(home_server_key.pubkey()).is_err());
+}
@@ -42,22 +42,22 @@ fn csr_from_pem() {
         )
         .unwrap(),
         &priv_key,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(Target::Actor),
     )
     .unwrap();
-    let data = csr.clone().to_pem(der::pem::LineEnding::LF).unwrap();
+    let data = csr.to_pem(der::pem::LineEnding::LF).unwrap();
     let csr_from_pem = IdCsr::from_pem(&data, Some(polyproto::certs::Target::Actor)).unwrap();
     assert_eq!(csr_from_pem, csr);
 
     let csr = polyproto::certs::idcsr::IdCsr::new(
         &RdnSequence::from_str("CN=root,DC=polyphony,DC=chat").unwrap(),
         &priv_key,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(Target::HomeServer),
     )
     .unwrap();
-    let data = csr.clone().to_pem(der::pem::LineEnding::LF).unwrap();
+    let data = csr.to_pem(der::pem::LineEnding::LF).unwrap();
     let csr_from_pem = IdCsr::from_pem(&data, Some(polyproto::certs::Target::HomeServer)).unwrap();
     assert_eq!(csr_from_pem, csr);
 }
@@ -72,7 +72,7 @@ fn test_create_invalid_actor_csr() {
     println!("Public Key is: {:?}", priv_key.public_key.key.to_bytes());
     println!();
 
-    let mut capabilities = Capabilities::default_actor();
+    let mut capabilities = Capabilities::actor_default();
     // This is not allowed in actor certificates/csrs
     capabilities
         .key_usage
@@ -104,22 +104,92 @@ fn csr_from_der() {
         )
         .unwrap(),
         &priv_key,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(Target::Actor),
     )
     .unwrap();
-    let data = csr.clone().to_der().unwrap();
+    let data = csr.to_der().unwrap();
     let csr_from_der = IdCsr::from_der(&data, Some(polyproto::certs::Target::Actor)).unwrap();
     assert_eq!(csr_from_der, csr);
 
     let csr = polyproto::certs::idcsr::IdCsr::new(
         &RdnSequence::from_str("CN=root,DC=polyphony,DC=chat").unwrap(),
         &priv_key,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(Target::HomeServer),
     )
     .unwrap();
-    let data = csr.clone().to_der().unwrap();
+    let data = csr.to_der().unwrap();
     let csr_from_der = IdCsr::from_der(&data, Some(polyproto::certs::Target::HomeServer)).unwrap();
     assert_eq!(csr_from_der, csr);
 }
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn renewal_from_cert() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let actor_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        &home_server_key,
+        Uint::new(&[8]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+
+    // Renewing with the same key
+    let csr = IdCsr::renewal_from_cert(&actor_cert, &actor_key).unwrap();
+    assert_eq!(csr.inner_csr.subject, actor_cert.id_cert_tbs.subject);
+    assert_eq!(
+        csr.inner_csr.capabilities,
+        actor_cert.id_cert_tbs.capabilities
+    );
+    assert_eq!(
+        csr.inner_csr.subject_public_key,
+        actor_cert.id_cert_tbs.subject_public_key
+    );
+    let renewed = IdCert::from_actor_csr(
+        csr,
+        &home_server_key,
+        Uint::new(&[9]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    assert_eq!(renewed.id_cert_tbs.subject, actor_cert.id_cert_tbs.subject);
+
+    // Renewing with a new key
+    let new_key = gen_priv_key();
+    let csr = IdCsr::renewal_from_cert(&actor_cert, &new_key).unwrap();
+    assert_eq!(csr.inner_csr.subject, actor_cert.id_cert_tbs.subject);
+    assert_eq!(&csr.inner_csr.subject_public_key, new_key.pubkey());
+    assert_ne!(
+        csr.inner_csr.subject_public_key,
+        actor_cert.id_cert_tbs.subject_public_key
+    );
+
+    // Renewing a home server certificate
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[8]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let csr = IdCsr::renewal_from_cert(&home_server_cert, &home_server_key).unwrap();
+    assert_eq!(
+        csr.inner_csr.capabilities,
+        Capabilities::home_server_default()
+    );
+    IdCert::from_ca_csr(
+        csr,
+        &home_server_key,
+        Uint::new(&[9]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+}
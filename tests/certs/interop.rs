@@ -0,0 +1,150 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::policy::{DerMode, ValidationPolicy};
+use polyproto::certs::Target;
+use polyproto::interop::{Issuer, OpenSsl};
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+/// Returns OpenSSL, or `None` if it is not installed, in which case the calling test is skipped.
+fn openssl() -> Option<OpenSsl> {
+    init_logger();
+    let openssl = OpenSsl::new();
+    match openssl.version() {
+        Ok(version) => {
+            log::info!("Using {}", version);
+            Some(openssl)
+        }
+        Err(e) => {
+            log::warn!("Skipping OpenSSL interoperability test: {}", e);
+            None
+        }
+    }
+}
+
+fn strict_policy() -> ValidationPolicy {
+    ValidationPolicy {
+        der_mode: DerMode::Strict,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn id_certs_verify_in_openssl() {
+    let Some(openssl) = openssl() else {
+        return;
+    };
+    let home_server_key = gen_priv_key();
+    let home_server = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+    .to_der()
+    .unwrap();
+    let actor = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+    .to_der()
+    .unwrap();
+    let time = Timestamp::from_unix_seconds(100);
+
+    let text = openssl.parse_certificate(&home_server).unwrap();
+    assert!(text.contains("CA:TRUE"), "{}", text);
+    assert!(text.contains("Certificate Sign, CRL Sign"), "{}", text);
+    let text = openssl.parse_certificate(&actor).unwrap();
+    assert!(text.contains("CA:FALSE"), "{}", text);
+    assert!(text.contains("Digital Signature"), "{}", text);
+
+    openssl
+        .verify_certificate(&home_server, &home_server, time)
+        .unwrap();
+    openssl
+        .verify_certificate(&actor, &home_server, time)
+        .unwrap();
+    // OpenSSL checks the validity period and the issuer, too.
+    assert!(openssl
+        .verify_certificate(&actor, &home_server, Timestamp::from_unix_seconds(2000))
+        .is_err());
+    assert!(openssl.verify_certificate(&actor, &actor, time).is_err());
+}
+
+#[test]
+fn openssl_certs_verify_here() {
+    let Some(openssl) = openssl() else {
+        return;
+    };
+    let home_server_key = openssl.generate_ed25519_key().unwrap();
+    let home_server_der = openssl
+        .issue_certificate(
+            &home_server_key,
+            &home_server_subject(),
+            &Capabilities::home_server_default(),
+            1,
+            30,
+            None,
+        )
+        .unwrap();
+    let actor_der = openssl
+        .issue_certificate(
+            &openssl.generate_ed25519_key().unwrap(),
+            &actor_subject("flori"),
+            &Capabilities::actor_default(),
+            2,
+            30,
+            Some(Issuer {
+                certificate: &home_server_der,
+                private_key: &home_server_key,
+            }),
+        )
+        .unwrap();
+    let time = Timestamp::now();
+    let policy = strict_policy();
+
+    let unchecked = Cert::from_der_unchecked(&home_server_der).unwrap();
+    let home_server_public_key = unchecked.id_cert_tbs.subject_public_key.clone();
+    let home_server = Cert::from_der_with(
+        &home_server_der,
+        Target::HomeServer,
+        time,
+        &home_server_public_key,
+        &policy,
+    )
+    .unwrap();
+    assert_eq!(
+        home_server.id_cert_tbs.capabilities.key_usage,
+        Capabilities::home_server_default().key_usage
+    );
+    assert_eq!(home_server.to_der().unwrap(), home_server_der);
+
+    let actor = Cert::from_der_with(
+        &actor_der,
+        Target::Actor,
+        time,
+        &home_server_public_key,
+        &policy,
+    )
+    .unwrap();
+    assert_eq!(
+        actor.id_cert_tbs.capabilities.basic_constraints,
+        Capabilities::actor_default().basic_constraints
+    );
+    assert_eq!(actor.id_cert_tbs.subject, actor_subject("flori"));
+    assert_eq!(actor.to_der().unwrap(), actor_der);
+}
@@ -0,0 +1,197 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use der::Encode;
+use polyproto::certs::chain::{CertPool, IdCertChain, IssuerRejection, RejectedIssuer};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::keyid::{
+    KeyIdentifier, KEY_IDENTIFIER_LENGTH, OID_AUTHORITY_KEY_IDENTIFIER, OID_SUBJECT_KEY_IDENTIFIER,
+};
+use polyproto::certs::Target;
+use polyproto::errors::ChainBuildError;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use x509_cert::TbsCertificate;
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn root_cert(key: &Ed25519PrivateKey) -> Cert {
+    IdCert::from_ca_csr(
+        home_server_csr(key),
+        key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+}
+
+/// Re-signs `cert` with `key`, keeping its key identifiers.
+fn resign(cert: &Cert, key: &Ed25519PrivateKey) -> Cert {
+    IdCert {
+        signature: key.sign(&cert.id_cert_tbs.to_der().unwrap()),
+        id_cert_tbs: cert.id_cert_tbs.clone(),
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn issue_cert_with_key_identifiers() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let root = root_cert(&root_key);
+    let root_key_identifier = KeyIdentifier::from_public_key(root_key.pubkey());
+    assert_eq!(root_key_identifier.as_bytes().len(), KEY_IDENTIFIER_LENGTH);
+    assert_eq!(
+        root.id_cert_tbs.subject_key_identifier,
+        Some(root_key_identifier.clone())
+    );
+    assert_eq!(
+        root.id_cert_tbs.authority_key_identifier,
+        Some(root_key_identifier.clone())
+    );
+
+    let actor = actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity());
+    assert_eq!(
+        actor.id_cert_tbs.subject_key_identifier,
+        Some(KeyIdentifier::from_public_key(
+            &actor.id_cert_tbs.subject_public_key
+        ))
+    );
+    assert_eq!(
+        actor.id_cert_tbs.authority_key_identifier,
+        Some(root_key_identifier)
+    );
+
+    let decoded = IdCert::from_der(
+        &actor.to_der().unwrap(),
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        root_key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded, actor);
+
+    let tbs = TbsCertificate::try_from(decoded.id_cert_tbs.into_inner()).unwrap();
+    let extensions = tbs.extensions.unwrap();
+    for oid in [OID_SUBJECT_KEY_IDENTIFIER, OID_AUTHORITY_KEY_IDENTIFIER] {
+        let extension = extensions
+            .iter()
+            .find(|extension| extension.extn_id.to_string() == oid)
+            .unwrap();
+        assert!(!extension.critical);
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn decode_cert_without_key_identifiers() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let mut actor = actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity());
+    actor.id_cert_tbs.subject_key_identifier = None;
+    actor.id_cert_tbs.authority_key_identifier = None;
+    let actor = resign(&actor, &root_key);
+    let decoded = IdCert::from_der(
+        &actor.to_der().unwrap(),
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        root_key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded.id_cert_tbs.subject_key_identifier, None);
+    assert_eq!(decoded.id_cert_tbs.authority_key_identifier, None);
+
+    // Chains fall back to comparing names.
+    let pool: CertPool<Ed25519Signature, Ed25519PublicKey> = vec![root_cert(&root_key)].into();
+    pool.build_chain(decoded, Timestamp::from_unix_seconds(100))
+        .unwrap()
+        .validate_chain(Timestamp::from_unix_seconds(100), Target::Actor)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn key_identifier_extensions() {
+    let key_identifier = KeyIdentifier::new(&[1, 2, 3]).unwrap();
+    assert_eq!(key_identifier.to_hex(), "010203");
+    assert_eq!(key_identifier.to_string(), "010203");
+    assert!(KeyIdentifier::new(&[]).is_err());
+
+    let subject = key_identifier.to_subject_key_identifier().unwrap();
+    assert_eq!(
+        KeyIdentifier::from_subject_key_identifier(&subject).unwrap(),
+        key_identifier
+    );
+    let authority = key_identifier.to_authority_key_identifier().unwrap();
+    assert_eq!(
+        KeyIdentifier::from_authority_key_identifier(&authority).unwrap(),
+        Some(key_identifier)
+    );
+    assert!(KeyIdentifier::from_subject_key_identifier(&authority).is_err());
+    assert!(KeyIdentifier::from_authority_key_identifier(&subject).is_err());
+
+    let mut critical = subject.clone();
+    critical.critical = true;
+    assert!(KeyIdentifier::from_subject_key_identifier(&critical).is_err());
+
+    let mut empty = authority.clone();
+    empty.extn_value = der::asn1::OctetString::new(
+        x509_cert::ext::pkix::AuthorityKeyIdentifier::default()
+            .to_der()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        KeyIdentifier::from_authority_key_identifier(&empty).unwrap(),
+        None
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn build_chain_matches_key_identifiers() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let other_key = gen_priv_key();
+    let root = root_cert(&root_key);
+    let other = root_cert(&other_key);
+    let pool: CertPool<Ed25519Signature, Ed25519PublicKey> =
+        vec![other.clone(), root.clone()].into();
+
+    let actor = actor_cert("flori", &gen_priv_key(), &root_key, 2, default_validity());
+    assert_eq!(
+        pool.build_chain(actor.clone(), Timestamp::from_unix_seconds(100))
+            .unwrap(),
+        IdCertChain::new(actor.clone(), vec![root.clone()])
+    );
+
+    // A certificate signed by the root, but naming another key as its authority.
+    let mut mislabeled = actor.clone();
+    mislabeled.id_cert_tbs.authority_key_identifier =
+        other.id_cert_tbs.subject_key_identifier.clone();
+    let mislabeled = resign(&mislabeled, &root_key);
+    assert_eq!(
+        pool.build_chain(mislabeled.clone(), Timestamp::from_unix_seconds(100)),
+        Err(ChainBuildError::NoIssuer {
+            subject: mislabeled.id_cert_tbs.subject.to_string(),
+            rejected: vec![
+                RejectedIssuer {
+                    index: 0,
+                    reason: IssuerRejection::BadSignature
+                },
+                RejectedIssuer {
+                    index: 1,
+                    reason: IssuerRejection::KeyIdentifierMismatch
+                },
+            ]
+        })
+    );
+    assert!(IdCertChain::new(mislabeled, vec![root])
+        .validate_chain(Timestamp::from_unix_seconds(100), Target::Actor)
+        .is_err());
+}
@@ -0,0 +1,183 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::{OctetString, Uint};
+use der::{Decode, Encode};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::limits::SizeLimits;
+use polyproto::certs::policy::ValidationPolicy;
+use polyproto::certs::Target;
+use polyproto::errors::{ConversionError, InvalidCert, SizeLimitViolation};
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use spki::ObjectIdentifier;
+use x509_cert::ext::Extension;
+use x509_cert::Certificate;
+
+use crate::common::*;
+
+fn decode(
+    der: &[u8],
+    home_server_key: &Ed25519PrivateKey,
+    policy: &ValidationPolicy,
+) -> Result<IdCert<Ed25519Signature, Ed25519PublicKey>, InvalidCert> {
+    IdCert::from_der_with(
+        der,
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+        policy,
+    )
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn certificate_size_limits() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1, 2, 3]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let der = cert.to_der().unwrap();
+    let limits = SizeLimits::default();
+    limits.check_certificate(&der).unwrap();
+    SizeLimits::unlimited().check_certificate(&der).unwrap();
+
+    let short_serial = ValidationPolicy {
+        size_limits: SizeLimits {
+            max_serial_number_length: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        decode(&der, &home_server_key, &short_serial),
+        Err(InvalidCert::SizeLimit(
+            SizeLimitViolation::SerialNumberLength {
+                limit: 2,
+                actual: 3
+            }
+        ))
+    );
+    decode(&der, &home_server_key, &ValidationPolicy::default()).unwrap();
+
+    let certificate = Certificate::from_der(&der).unwrap();
+    let mut many_rdns = certificate.clone();
+    let rdn = many_rdns.tbs_certificate.issuer.0[0].clone();
+    while many_rdns.tbs_certificate.issuer.0.len() <= limits.max_rdns {
+        many_rdns.tbs_certificate.issuer.0.push(rdn.clone());
+    }
+    let many_rdns = many_rdns.to_der().unwrap();
+    assert_eq!(
+        IdCert::from_der(
+            &many_rdns,
+            Target::Actor,
+            Timestamp::from_unix_seconds(100),
+            home_server_key.pubkey(),
+        ),
+        Err(InvalidCert::SizeLimit(SizeLimitViolation::RdnCount {
+            limit: limits.max_rdns,
+            actual: limits.max_rdns + 1
+        }))
+    );
+
+    let extension = |arc: u32, length: usize| Extension {
+        extn_id: ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57423.2")
+            .push_arc(arc)
+            .unwrap(),
+        critical: false,
+        extn_value: OctetString::new(vec![0x05; length]).unwrap(),
+    };
+    let mut many_extensions = certificate.clone();
+    let extensions = many_extensions.tbs_certificate.extensions.as_mut().unwrap();
+    let mut arc = 0;
+    while extensions.len() <= limits.max_extensions {
+        extensions.push(extension(arc, 1));
+        arc += 1;
+    }
+    assert_eq!(
+        decode(
+            &many_extensions.to_der().unwrap(),
+            &home_server_key,
+            &ValidationPolicy::default()
+        ),
+        Err(InvalidCert::SizeLimit(SizeLimitViolation::ExtensionCount {
+            limit: limits.max_extensions,
+            actual: limits.max_extensions + 1
+        }))
+    );
+
+    let mut long_extension = certificate;
+    long_extension
+        .tbs_certificate
+        .extensions
+        .as_mut()
+        .unwrap()
+        .push(extension(0, limits.max_extension_value_length + 1));
+    let long_extension = long_extension.to_der().unwrap();
+    assert_eq!(
+        limits.check_certificate(&long_extension),
+        Err(ConversionError::SizeLimit(
+            SizeLimitViolation::ExtensionValueLength {
+                limit: limits.max_extension_value_length,
+                actual: limits.max_extension_value_length + 1
+            }
+        ))
+    );
+    SizeLimits::unlimited()
+        .check_certificate(&long_extension)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn csr_size_limits() {
+    init_logger();
+    let csr = actor_csr("flori", &gen_priv_key());
+    let der = csr.to_der().unwrap();
+    SizeLimits::default().check_csr(&der).unwrap();
+    assert_eq!(IdCsr::from_der(&der, Some(Target::Actor)).unwrap(), csr);
+
+    let few_rdns = ValidationPolicy {
+        size_limits: SizeLimits {
+            max_rdns: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(matches!(
+        IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der_with(
+            &der,
+            Some(Target::Actor),
+            &few_rdns
+        ),
+        Err(ConversionError::SizeLimit(SizeLimitViolation::RdnCount {
+            limit: 1,
+            ..
+        }))
+    ));
+    let no_extensions = ValidationPolicy {
+        size_limits: SizeLimits {
+            max_extensions: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(matches!(
+        IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der_with(
+            &der,
+            Some(Target::Actor),
+            &no_extensions
+        ),
+        Err(ConversionError::SizeLimit(
+            SizeLimitViolation::ExtensionCount { limit: 0, .. }
+        ))
+    ));
+}
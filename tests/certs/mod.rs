@@ -2,6 +2,40 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+#[cfg(feature = "rayon")]
+mod batch;
+mod borrowed;
 mod capabilities;
+mod certlog;
+mod chain;
+mod claims;
+mod crl;
+mod crldp;
+mod csrmeta;
+mod custody;
+mod dynamic;
+mod guest;
 mod idcert;
 mod idcsr;
+#[cfg(all(feature = "test-utils", not(target_arch = "wasm32")))]
+mod interop;
+mod keyid;
+mod limits;
+mod pem;
+mod pinning;
+mod policy;
+#[cfg(feature = "rcgen")]
+mod rcgen;
+mod rotation;
+#[cfg(feature = "rustls")]
+mod rustls;
+mod san;
+mod security;
+mod serial;
+mod shared;
+mod status;
+mod superseded;
+#[cfg(feature = "test-utils")]
+mod test_vectors;
+mod text;
+mod truststore;
@@ -0,0 +1,158 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::pem::LineEnding;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::pem::{
+    load_any, PemLabels, PemObject, PEM_LABEL_ACTOR_CERTIFICATE,
+    PEM_LABEL_ACTOR_CERTIFICATE_REQUEST, PEM_LABEL_HOME_SERVER_CERTIFICATE,
+};
+use polyproto::certs::Target;
+use polyproto::errors::BundleError;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+type Csr = IdCsr<Ed25519Signature, Ed25519PublicKey>;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn target_specific_labels() {
+    init_logger();
+    let cert = home_server_id_cert();
+    let standard = cert.to_pem(LineEnding::LF).unwrap();
+    assert!(standard.starts_with("-----BEGIN CERTIFICATE-----"));
+    let specific = cert
+        .clone()
+        .to_pem_with_labels(LineEnding::LF, PemLabels::TargetSpecific)
+        .unwrap();
+    assert!(specific.starts_with(&format!(
+        "-----BEGIN {}-----",
+        PEM_LABEL_HOME_SERVER_CERTIFICATE
+    )));
+    assert_eq!(Cert::from_pem_unchecked(&standard).unwrap(), cert);
+    assert_eq!(Cert::from_pem_unchecked(&specific).unwrap(), cert);
+    // A home server certificate is not accepted where an actor certificate is expected.
+    let key = gen_priv_key();
+    assert!(Cert::from_pem(
+        &specific,
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        key.pubkey()
+    )
+    .is_err());
+
+    let csr = actor_csr("flori", &key);
+    let specific = csr
+        .clone()
+        .to_pem_with_labels(LineEnding::LF, PemLabels::TargetSpecific)
+        .unwrap();
+    assert!(specific.starts_with(&format!(
+        "-----BEGIN {}-----",
+        PEM_LABEL_ACTOR_CERTIFICATE_REQUEST
+    )));
+    assert_eq!(Csr::from_pem(&specific, Some(Target::Actor)).unwrap(), csr);
+    assert_eq!(Csr::from_pem(&specific, None).unwrap(), csr);
+    assert!(Csr::from_pem(&specific, Some(Target::HomeServer)).is_err());
+    // Certificates are not accepted where CSRs are expected.
+    assert!(Csr::from_pem_unchecked(&standard).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn load_any_detects_target() {
+    init_logger();
+    let home_server_cert = home_server_id_cert();
+    for labels in [PemLabels::Standard, PemLabels::TargetSpecific] {
+        let pem = home_server_cert
+            .clone()
+            .to_pem_with_labels(LineEnding::LF, labels)
+            .unwrap();
+        match load_any::<Ed25519Signature, Ed25519PublicKey>(&pem).unwrap() {
+            PemObject::Certificate { cert, target } => {
+                assert_eq!(cert, home_server_cert);
+                assert_eq!(target, Target::HomeServer);
+            }
+            other => panic!("expected a certificate, got {:?}", other),
+        }
+    }
+
+    let csr = actor_csr("flori", &gen_priv_key());
+    let pem = csr
+        .clone()
+        .to_pem_with_labels(LineEnding::LF, PemLabels::TargetSpecific)
+        .unwrap();
+    let loaded = load_any::<Ed25519Signature, Ed25519PublicKey>(&pem).unwrap();
+    assert_eq!(loaded.target(), Target::Actor);
+    assert_eq!(
+        loaded,
+        PemObject::CertificateRequest {
+            csr,
+            target: Target::Actor
+        }
+    );
+
+    // A label contradicting the capabilities of the object is rejected.
+    let mislabeled = pem.replace("POLYPROTO ACTOR", "POLYPROTO HOME SERVER");
+    assert!(load_any::<Ed25519Signature, Ed25519PublicKey>(&mislabeled).is_err());
+    let unknown = pem.replace("POLYPROTO ACTOR CERTIFICATE REQUEST", "PRIVATE KEY");
+    assert!(load_any::<Ed25519Signature, Ed25519PublicKey>(&unknown).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn bundles_decode_in_order() {
+    init_logger();
+    let actor = actor_id_cert("flori");
+    let home_server = home_server_id_cert();
+    let bundle = format!(
+        "leaf\n{}issuer\n{}",
+        actor.to_pem(LineEnding::LF).unwrap(),
+        home_server
+            .to_pem_with_labels(LineEnding::CRLF, PemLabels::TargetSpecific)
+            .unwrap()
+    );
+    assert_eq!(
+        Cert::many_from_pem(&bundle).unwrap(),
+        vec![actor.clone(), home_server.clone()]
+    );
+    let mut der = actor.to_der().unwrap();
+    der.extend(home_server.to_der().unwrap());
+    assert_eq!(
+        Cert::many_from_der(&der).unwrap(),
+        vec![actor.clone(), home_server.clone()]
+    );
+    assert_eq!(Cert::many_from_pem("no certificates").unwrap(), vec![]);
+    assert_eq!(Cert::many_from_der(&[]).unwrap(), vec![]);
+
+    // Errors name the first entry which could not be decoded.
+    let csr = actor_csr("flori", &gen_priv_key())
+        .to_pem(LineEnding::LF)
+        .unwrap();
+    let with_csr = format!("{}{}", actor.to_pem(LineEnding::LF).unwrap(), csr);
+    assert!(matches!(
+        Cert::many_from_pem(&with_csr),
+        Err(BundleError::InvalidEntry { index: 1, .. })
+    ));
+    let mislabeled = bundle.replace(
+        PEM_LABEL_HOME_SERVER_CERTIFICATE,
+        PEM_LABEL_ACTOR_CERTIFICATE,
+    );
+    assert!(matches!(
+        Cert::many_from_pem(&mislabeled),
+        Err(BundleError::InvalidEntry { index: 1, .. })
+    ));
+    let truncated = &bundle[..bundle.len() - 10];
+    assert!(matches!(
+        Cert::many_from_pem(truncated),
+        Err(BundleError::InvalidEntry { index: 1, .. })
+    ));
+    assert!(matches!(
+        Cert::many_from_der(&der[..der.len() - 1]),
+        Err(BundleError::InvalidEntry { index: 1, .. })
+    ));
+}
@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::chain::IdCertChain;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::pinning::PinSet;
+use polyproto::certs::Target;
+use polyproto::errors::InvalidCert;
+use polyproto::key::KeyFingerprint;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn root(key: &Ed25519PrivateKey) -> Cert {
+    IdCert::from_ca_csr(
+        home_server_csr(key),
+        key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn spki_pin_sha256() {
+    // The SHA-256 hash of the empty string, as a pin.
+    let pin = "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+    let fingerprint = KeyFingerprint::from_pin_sha256(pin).unwrap();
+    assert_eq!(
+        fingerprint.to_hex(),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(fingerprint.to_pin_sha256(), pin);
+    assert!(KeyFingerprint::from_pin_sha256("Zm9vYmFy").is_err());
+    assert!(KeyFingerprint::from_pin_sha256(&pin[1..]).is_err());
+
+    let cert = home_server_id_cert();
+    let pin = cert.spki_pin_sha256().unwrap();
+    assert_eq!(pin.len(), 44);
+    assert_eq!(
+        KeyFingerprint::from_pin_sha256(&pin).unwrap(),
+        cert.key_fingerprint().unwrap()
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn pin_set_detects_key_substitution() {
+    init_logger();
+    let key = gen_priv_key();
+    let backup_key = gen_priv_key();
+    let root = root(&key);
+    let time = Timestamp::from(100);
+    let pins = PinSet::new()
+        .with_pin_sha256(&root.spki_pin_sha256().unwrap())
+        .unwrap()
+        .with_pin_sha256(&self::root(&backup_key).spki_pin_sha256().unwrap())
+        .unwrap()
+        .with_expiry(Timestamp::from(500));
+    assert_eq!(pins.len(), 2);
+    pins.check(&root, time).unwrap();
+    pins.check(&self::root(&backup_key), time).unwrap();
+
+    // The substituted certificate is perfectly valid, but carries another key.
+    let substituted = self::root(&gen_priv_key());
+    substituted.full_verify_home_server(time).unwrap();
+    assert_eq!(
+        pins.check(&substituted, time),
+        Err(InvalidCert::PinMismatch)
+    );
+    let leaf = actor_id_cert("flori");
+    let chain = IdCertChain::new(
+        IdCert::from_actor_csr(
+            actor_csr("flori", &gen_priv_key()),
+            &key,
+            Uint::new(&[2]).unwrap(),
+            home_server_subject(),
+            default_validity(),
+        )
+        .unwrap(),
+        vec![root],
+    );
+    chain
+        .validate_chain_pinned(time, Target::Actor, &pins)
+        .unwrap();
+    let substituted_chain = IdCertChain::new(leaf, vec![substituted.clone()]);
+    assert!(substituted_chain
+        .validate_chain_pinned(time, Target::Actor, &pins)
+        .is_err());
+    assert_eq!(
+        pins.check_chain(&substituted_chain, time),
+        Err(InvalidCert::PinMismatch)
+    );
+
+    // Expired and empty pin sets no longer restrict validation.
+    pins.check(&substituted, Timestamp::from(500)).unwrap();
+    PinSet::new().check(&substituted, time).unwrap();
+}
@@ -0,0 +1,332 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::Uint;
+use der::{Decode, Encode};
+use polyproto::certs::capabilities::KeyUsage;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::policy::{DerMode, ValidationPolicy};
+use polyproto::certs::Target;
+use polyproto::errors::{ConstraintError, ConversionError, DerViolation, InvalidCert};
+use polyproto::key::PrivateKey;
+use polyproto::signature::Signature;
+use polyproto::timestamp::Timestamp;
+use polyproto::{Constrained, Name};
+use spki::ObjectIdentifier;
+use x509_cert::Certificate;
+
+use crate::common::*;
+
+fn issue_actor_cert(
+    home_server_key: &Ed25519PrivateKey,
+) -> IdCert<Ed25519Signature, Ed25519PublicKey> {
+    IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn default_policy_matches_validate() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = issue_actor_cert(&home_server_key);
+    let policy = ValidationPolicy::default();
+    cert.validate_with(Some(Target::Actor), &policy).unwrap();
+    let der = cert.to_der().unwrap();
+    let decoded = IdCert::from_der_with(
+        &der,
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+        &policy,
+    )
+    .unwrap();
+    assert_eq!(decoded, cert);
+
+    let mut mismatched = cert;
+    mismatched.id_cert_tbs.issuer = Name::from_str("DC=example,DC=com").unwrap();
+    assert!(mismatched.validate(Some(Target::Actor)).is_err());
+    assert!(mismatched
+        .validate_with(Some(Target::Actor), &policy)
+        .is_err());
+    // Disabled rules are not checked.
+    let relaxed = ValidationPolicy {
+        disabled_rules: vec!["idcert.domain-components"],
+        ..Default::default()
+    };
+    mismatched
+        .validate_with(Some(Target::Actor), &relaxed)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn tightened_policy() {
+    init_logger();
+    let cert = issue_actor_cert(&gen_priv_key());
+    let ed25519 = Ed25519Signature::algorithm_identifier().oid;
+
+    let allowed = ValidationPolicy {
+        allowed_algorithms: Some(vec![ed25519]),
+        ..Default::default()
+    };
+    cert.validate_with(Some(Target::Actor), &allowed).unwrap();
+    let other = ValidationPolicy {
+        allowed_algorithms: Some(vec![ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2")]),
+        ..Default::default()
+    };
+    assert!(cert.validate_with(Some(Target::Actor), &other).is_err());
+
+    // The default validity period spans 990 seconds.
+    let short = ValidationPolicy {
+        max_validity: Some(Duration::from_secs(989)),
+        ..Default::default()
+    };
+    assert!(matches!(
+        cert.validate_with(None, &short),
+        Err(ConstraintError::OutOfBounds { .. })
+    ));
+    let long = ValidationPolicy {
+        max_validity: Some(Duration::from_secs(990)),
+        ..Default::default()
+    };
+    cert.validate_with(None, &long).unwrap();
+
+    let key_usages = ValidationPolicy {
+        required_actor_key_usages: vec![KeyUsage::DigitalSignature, KeyUsage::ContentCommitment],
+        ..Default::default()
+    };
+    assert!(cert
+        .validate_with(Some(Target::Actor), &key_usages)
+        .is_err());
+    // Required key usages only apply to their target.
+    cert.validate_with(None, &key_usages).unwrap();
+
+    let csr = actor_csr("flori", &gen_priv_key());
+    csr.validate_with(Some(Target::Actor), &allowed).unwrap();
+    assert!(csr.validate_with(Some(Target::Actor), &other).is_err());
+    assert!(csr.validate_with(Some(Target::Actor), &key_usages).is_err());
+    let der = csr.to_der().unwrap();
+    assert_eq!(
+        IdCsr::from_der_with(&der, Some(Target::Actor), &allowed).unwrap(),
+        csr
+    );
+    assert!(IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der_with(
+        &der,
+        Some(Target::Actor),
+        &other
+    )
+    .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn from_der_with_skew_and_der_mode() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = issue_actor_cert(&home_server_key);
+    let der = cert.to_der().unwrap();
+    let from_der = |der: &[u8], time: u64, policy: &ValidationPolicy| {
+        IdCert::from_der_with(
+            der,
+            Target::Actor,
+            Timestamp::from_unix_seconds(time),
+            home_server_key.pubkey(),
+            policy,
+        )
+    };
+
+    let lenient = ValidationPolicy::default();
+    assert!(matches!(
+        from_der(&der, 1005, &lenient),
+        Err(InvalidCert::InvalidValidity)
+    ));
+    let skewed = ValidationPolicy {
+        clock_skew_tolerance: Duration::from_secs(10),
+        ..Default::default()
+    };
+    from_der(&der, 1005, &skewed).unwrap();
+
+    let strict = ValidationPolicy {
+        der_mode: DerMode::Strict,
+        ..Default::default()
+    };
+    from_der(&der, 100, &strict).unwrap();
+    // Reordering the extensions yields an encoding polyproto does not produce.
+    let mut certificate = Certificate::from_der(&der).unwrap();
+    certificate
+        .tbs_certificate
+        .extensions
+        .as_mut()
+        .unwrap()
+        .reverse();
+    let reordered = certificate.to_der().unwrap();
+    assert_ne!(reordered, der);
+    assert!(matches!(
+        from_der(&reordered, 100, &strict),
+        Err(InvalidCert::StrictDer(DerViolation::NonCanonical { .. }))
+    ));
+    assert!(!matches!(
+        from_der(&reordered, 100, &lenient),
+        Err(InvalidCert::InvalidProperties(_))
+    ));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn strict_der_rejects_ber_artifacts() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = issue_actor_cert(&home_server_key);
+    let der = cert.to_der().unwrap();
+    let strict = ValidationPolicy {
+        der_mode: DerMode::Strict,
+        ..Default::default()
+    };
+    let from_der = |der: &[u8], policy: &ValidationPolicy| {
+        IdCert::from_der_with(
+            der,
+            Target::Actor,
+            Timestamp::from_unix_seconds(100),
+            home_server_key.pubkey(),
+            policy,
+        )
+    };
+
+    let mut trailing = der.clone();
+    trailing.extend([0, 0]);
+    assert_eq!(
+        from_der(&trailing, &strict),
+        Err(InvalidCert::StrictDer(DerViolation::TrailingBytes {
+            count: 2
+        }))
+    );
+
+    // The serial number is the second element of the TBSCertificate, preceded by the version.
+    let tbs_offset = 4;
+    let serial_offset = tbs_offset + 4 + 5;
+    assert_eq!(der[serial_offset..serial_offset + 3], [0x02, 0x01, 0x01]);
+    let lengthen = |der: &[u8], offset: usize| {
+        // Encodes the length of the element at `offset` using the long form. Both the certificate
+        // and the TBSCertificate use a two-byte long form length.
+        let mut ber = der.to_vec();
+        ber.insert(offset + 1, 0x81);
+        for outer in [0, tbs_offset] {
+            let length = u16::from_be_bytes([ber[outer + 2], ber[outer + 3]]) + 1;
+            ber[outer + 2..outer + 4].copy_from_slice(&length.to_be_bytes());
+        }
+        ber
+    };
+    let non_minimal = lengthen(&der, serial_offset);
+    assert_eq!(
+        from_der(&non_minimal, &strict),
+        Err(InvalidCert::StrictDer(DerViolation::NonMinimalLength {
+            offset: serial_offset
+        }))
+    );
+    // The lenient decoder fails on the same input, without naming the violation.
+    assert!(matches!(
+        from_der(&non_minimal, &ValidationPolicy::default()),
+        Err(InvalidCert::InvalidProperties(_))
+    ));
+
+    let mut constructed = der.clone();
+    constructed[serial_offset] = 0x24;
+    assert!(matches!(
+        from_der(&constructed, &strict),
+        Err(InvalidCert::StrictDer(_))
+    ));
+
+    let pem = cert.to_pem(der::pem::LineEnding::LF).unwrap();
+    assert_eq!(
+        IdCert::from_pem_with(
+            &pem,
+            Target::Actor,
+            Timestamp::from_unix_seconds(100),
+            home_server_key.pubkey(),
+            &strict,
+        )
+        .unwrap(),
+        cert
+    );
+
+    let csr = actor_csr("flori", &gen_priv_key());
+    let mut trailing = csr.to_der().unwrap();
+    trailing.push(0);
+    assert_eq!(
+        IdCsr::<Ed25519Signature, Ed25519PublicKey>::from_der_with(
+            &trailing,
+            Some(Target::Actor),
+            &strict
+        ),
+        Err(ConversionError::StrictDer(DerViolation::TrailingBytes {
+            count: 1
+        }))
+    );
+    let pem = csr.to_pem(der::pem::LineEnding::LF).unwrap();
+    assert_eq!(
+        IdCsr::from_pem_with(&pem, Some(Target::Actor), &strict).unwrap(),
+        csr
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn canonical_encoding() {
+    init_logger();
+    let cert = issue_actor_cert(&gen_priv_key());
+    let der = cert.to_der().unwrap();
+    assert!(cert.is_canonical(&der));
+    cert.check_canonical(&der).unwrap();
+
+    let mut certificate = Certificate::from_der(&der).unwrap();
+    certificate
+        .tbs_certificate
+        .extensions
+        .as_mut()
+        .unwrap()
+        .reverse();
+    let reordered = certificate.to_der().unwrap();
+    let decoded =
+        IdCert::<Ed25519Signature, Ed25519PublicKey>::from_der_unchecked(&reordered).unwrap();
+    assert!(!decoded.is_canonical(&reordered));
+    let offset = der
+        .iter()
+        .zip(&reordered)
+        .position(|(a, b)| a != b)
+        .unwrap();
+    assert_eq!(
+        decoded.check_canonical(&reordered),
+        Err(ConversionError::StrictDer(DerViolation::NonCanonical {
+            offset
+        }))
+    );
+    // The signature is verified over the transmitted bytes, not over the canonical encoding.
+    assert_eq!(
+        decoded.signature_data().unwrap(),
+        certificate.tbs_certificate.to_der().unwrap()
+    );
+
+    let csr = actor_csr("flori", &gen_priv_key());
+    let der = csr.to_der().unwrap();
+    assert!(csr.is_canonical(&der));
+    let mut trailing = der.clone();
+    trailing.push(0);
+    assert_eq!(
+        csr.check_canonical(&trailing),
+        Err(ConversionError::StrictDer(DerViolation::NonCanonical {
+            offset: der.len()
+        }))
+    );
+}
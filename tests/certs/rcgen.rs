@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcerttbs::IdCertTbs;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::rcgen::{distinguished_name_from_name, name_from_distinguished_name};
+use polyproto::certs::Target;
+use polyproto::errors::ConversionError;
+use polyproto::key::PrivateKey;
+use polyproto::signature::Signature;
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, DnValue, IsCa, KeyUsagePurpose,
+    SerialNumber,
+};
+
+use crate::common::*;
+
+type Csr = IdCsr<Ed25519Signature, Ed25519PublicKey>;
+type CertTbs = IdCertTbs<Ed25519Signature, Ed25519PublicKey>;
+
+const DOMAIN_COMPONENT: &[u64] = &[0, 9, 2342, 19200300, 100, 1, 25];
+const UID: &[u64] = &[0, 9, 2342, 19200300, 100, 1, 1];
+const UNIQUE_IDENTIFIER: &[u64] = &[0, 9, 2342, 19200300, 100, 1, 44];
+
+/// [CertificateParams] describing the actor `flori@polyphony.chat`, built using `rcgen` alone.
+fn actor_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name = DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CustomDnType(UNIQUE_IDENTIFIER.to_vec()), "client1");
+    params
+        .distinguished_name
+        .push(DnType::CustomDnType(UID.to_vec()), "flori@polyphony.chat");
+    params.distinguished_name.push(
+        DnType::CustomDnType(DOMAIN_COMPONENT.to_vec()),
+        DnValue::Ia5String("polyphony.chat".try_into().unwrap()),
+    );
+    params.distinguished_name.push(DnType::CommonName, "flori");
+    params.is_ca = IsCa::ExplicitNoCa;
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.serial_number = Some(SerialNumber::from_slice(&[1, 2, 3]));
+    params
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn domain_components_are_joined_into_one_attribute() {
+    init_logger();
+    let name = actor_subject("flori");
+    let distinguished_name = distinguished_name_from_name(&name).unwrap();
+    assert_eq!(
+        distinguished_name.get(&DnType::CustomDnType(DOMAIN_COMPONENT.to_vec())),
+        Some(&DnValue::Ia5String("polyphony.chat".try_into().unwrap()))
+    );
+    assert_eq!(
+        name_from_distinguished_name(&distinguished_name).unwrap(),
+        name
+    );
+    assert_eq!(
+        name_from_distinguished_name(&actor_params().distinguished_name).unwrap(),
+        name
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn id_csr_round_trips_through_rcgen_params() {
+    init_logger();
+    let priv_key = gen_priv_key();
+    for (csr, target) in [
+        (actor_csr("flori", &priv_key), Target::Actor),
+        (home_server_csr(&priv_key), Target::HomeServer),
+    ] {
+        let params = CertificateParams::try_from(&csr).unwrap();
+        let converted = Csr::from_rcgen_params(&params, &priv_key, Some(target)).unwrap();
+        assert_eq!(converted.inner_csr.subject, csr.inner_csr.subject);
+        assert_eq!(converted.inner_csr.capabilities, csr.inner_csr.capabilities);
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn id_cert_tbs_round_trips_through_rcgen_params() {
+    init_logger();
+    let id_cert = home_server_id_cert();
+    let tbs = id_cert.id_cert_tbs.into_inner();
+    let params = CertificateParams::try_from(&tbs).unwrap();
+    assert_eq!(params.is_ca, IsCa::Ca(BasicConstraints::Constrained(0)));
+    let converted = CertTbs::from_rcgen_params(
+        &params,
+        tbs.subject_public_key.clone(),
+        tbs.signature_algorithm.clone(),
+        tbs.issuer.clone(),
+        Some(Target::HomeServer),
+    )
+    .unwrap();
+    assert_eq!(converted.serial_number, tbs.serial_number);
+    assert_eq!(converted.subject, tbs.subject);
+    assert_eq!(converted.validity, tbs.validity);
+    assert_eq!(converted.capabilities, tbs.capabilities);
+    assert_eq!(converted.subject_key_identifier, tbs.subject_key_identifier);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn rcgen_params_are_checked_against_polyproto_constraints() {
+    init_logger();
+    let priv_key = gen_priv_key();
+    let params = actor_params();
+    assert_eq!(
+        Capabilities::try_from(&params).unwrap(),
+        Capabilities::actor_default()
+    );
+    let tbs = CertTbs::from_rcgen_params(
+        &params,
+        priv_key.pubkey().clone(),
+        Ed25519Signature::algorithm_identifier(),
+        home_server_subject(),
+        Some(Target::Actor),
+    )
+    .unwrap();
+    assert_eq!(tbs.subject, actor_subject("flori"));
+
+    let mut with_ca_flag = actor_params();
+    with_ca_flag.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    assert!(CertTbs::from_rcgen_params(
+        &with_ca_flag,
+        priv_key.pubkey().clone(),
+        Ed25519Signature::algorithm_identifier(),
+        home_server_subject(),
+        Some(Target::Actor),
+    )
+    .is_err());
+    assert!(Csr::from_rcgen_params(&with_ca_flag, &priv_key, Some(Target::Actor)).is_err());
+
+    let mut without_serial_number = actor_params();
+    without_serial_number.serial_number = None;
+    assert!(matches!(
+        CertTbs::from_rcgen_params(
+            &without_serial_number,
+            priv_key.pubkey().clone(),
+            Ed25519Signature::algorithm_identifier(),
+            home_server_subject(),
+            Some(Target::Actor),
+        ),
+        Err(ConversionError::InvalidInput(_))
+    ));
+}
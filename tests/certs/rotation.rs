@@ -0,0 +1,219 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use polyproto::certs::chain::{CertPool, IdCertChain};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::rotation::{cross_sign, KeyRotation};
+use polyproto::certs::Target;
+use polyproto::errors::InvalidCert;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn rotate_actor_key() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let old_key = gen_priv_key();
+    let old_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &old_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    assert!(KeyRotation::new(old_cert.clone(), gen_priv_key()).is_err());
+    let mut rotation = KeyRotation::new(old_cert.clone(), old_key.clone())
+        .unwrap()
+        .with_overlap(Duration::from_secs(100));
+    assert_eq!(
+        rotation.rotation_due_at(),
+        Timestamp::from_unix_seconds(900)
+    );
+    assert!(!rotation.needs_rotation(Timestamp::from_unix_seconds(500)));
+    assert!(rotation.needs_rotation(Timestamp::from_unix_seconds(900)));
+    assert!(rotation
+        .complete(old_cert.clone(), Timestamp::from_unix_seconds(500))
+        .is_err());
+
+    let new_key = gen_priv_key();
+    let csr = rotation.begin(new_key.clone()).unwrap();
+    assert_eq!(&csr.inner_csr.subject_public_key, new_key.pubkey());
+    assert!(!rotation.needs_rotation(Timestamp::from_unix_seconds(900)));
+    let new_cert = IdCert::from_actor_csr(
+        csr,
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    // The old certificate does not certify the key of the rotation.
+    assert!(rotation
+        .complete(old_cert.clone(), Timestamp::from_unix_seconds(500))
+        .is_err());
+    rotation
+        .complete(new_cert.clone(), Timestamp::from_unix_seconds(500))
+        .unwrap();
+    assert!(rotation.pending().is_none());
+    assert_eq!(rotation.signing_key(), &new_key);
+    assert_eq!(rotation.current().id_cert, new_cert);
+    assert_eq!(
+        rotation.previous()[0].retired_at,
+        Some(Timestamp::from_unix_seconds(500))
+    );
+
+    let verifying = rotation.verifying_keys(Timestamp::from_unix_seconds(550));
+    assert_eq!(verifying.len(), 2);
+    assert_eq!(verifying[0].private_key, new_key);
+    assert_eq!(verifying[1].private_key, old_key);
+    assert_eq!(
+        rotation
+            .verifying_keys(Timestamp::from_unix_seconds(700))
+            .len(),
+        1
+    );
+    assert_eq!(rotation.prune(Timestamp::from_unix_seconds(550)), 0);
+    assert_eq!(rotation.prune(Timestamp::from_unix_seconds(700)), 1);
+    assert!(rotation.previous().is_empty());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn cross_sign_home_server_key() {
+    init_logger();
+    let old_key = gen_priv_key();
+    let old_root = IdCert::from_ca_csr(
+        home_server_csr(&old_key),
+        &old_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let mut rotation = KeyRotation::new(old_root.clone(), old_key.clone())
+        .unwrap()
+        .with_overlap(Duration::from_secs(100));
+    assert!(rotation
+        .cross_sign([Uint::new(&[3]).unwrap(), Uint::new(&[4]).unwrap()])
+        .unwrap()
+        .is_none());
+
+    let new_key = gen_priv_key();
+    let csr = rotation.begin(new_key.clone()).unwrap();
+    let new_root = IdCert::from_ca_csr(
+        csr,
+        &new_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    rotation
+        .complete(new_root.clone(), Timestamp::from_unix_seconds(500))
+        .unwrap();
+    let cross_certs = rotation
+        .cross_sign([Uint::new(&[3]).unwrap(), Uint::new(&[4]).unwrap()])
+        .unwrap()
+        .unwrap();
+    assert!(cross_certs.new_by_previous.is_cross_signed());
+    assert!(cross_certs.previous_by_new.is_cross_signed());
+    assert!(!new_root.is_cross_signed());
+    assert_eq!(
+        cross_certs.new_by_previous.id_cert_tbs.subject_public_key,
+        new_root.id_cert_tbs.subject_public_key
+    );
+    assert_eq!(
+        Timestamp::from(cross_certs.new_by_previous.id_cert_tbs.validity.not_after),
+        Timestamp::from_unix_seconds(600)
+    );
+
+    // Clients trusting the previous key accept actors issued under the new key during the
+    // overlap window.
+    let actor = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &new_key,
+        Uint::new(&[5]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let chain = IdCertChain::new(
+        actor.clone(),
+        vec![cross_certs.new_by_previous.clone(), old_root.clone()],
+    );
+    chain
+        .validate_chain(Timestamp::from_unix_seconds(550), Target::Actor)
+        .unwrap();
+    assert_eq!(
+        chain.validate_chain(Timestamp::from_unix_seconds(700), Target::Actor),
+        Err(InvalidCert::InvalidValidity)
+    );
+    let pool: CertPool<Ed25519Signature, Ed25519PublicKey> =
+        vec![old_root.clone(), cross_certs.new_by_previous.clone()].into();
+    assert_eq!(
+        pool.build_chain(actor.clone(), Timestamp::from_unix_seconds(550))
+            .unwrap(),
+        chain
+    );
+    IdCertChain::new(actor, vec![new_root.clone()])
+        .validate_chain(Timestamp::from_unix_seconds(700), Target::Actor)
+        .unwrap();
+
+    // Clients trusting the new key accept actors issued under the previous key.
+    let actor = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &old_key,
+        Uint::new(&[6]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    IdCertChain::new(
+        actor.clone(),
+        vec![cross_certs.previous_by_new, new_root.clone()],
+    )
+    .validate_chain(Timestamp::from_unix_seconds(550), Target::Actor)
+    .unwrap();
+
+    // A certificate of the new key which is not marked as a cross-certificate counts towards
+    // the path length of the previous root.
+    let intermediate = IdCert::from_ca_csr(
+        home_server_csr(&new_key),
+        &old_key,
+        Uint::new(&[7]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    assert!(!intermediate.is_cross_signed());
+    assert!(
+        IdCertChain::new(actor.clone(), vec![intermediate, old_root.clone()])
+            .validate_chain(Timestamp::from_unix_seconds(550), Target::Actor)
+            .is_err()
+    );
+
+    assert!(cross_sign(
+        &actor,
+        &old_root,
+        &old_key,
+        Uint::new(&[8]).unwrap(),
+        default_validity()
+    )
+    .is_err());
+    assert!(cross_sign(
+        &new_root,
+        &old_root,
+        &new_key,
+        Uint::new(&[8]).unwrap(),
+        default_validity()
+    )
+    .is_err());
+}
@@ -0,0 +1,299 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use polyproto::certs::chain::IdCertChain;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::rustls::{
+    certificate_chain, certified_key, signature_schemes, verify_handshake_signature, IdCertVerifier,
+};
+use polyproto::certs::truststore::TrustStore;
+use polyproto::certs::Target;
+use polyproto::errors::{ConversionError, InvalidCert};
+use polyproto::key::PrivateKey;
+use polyproto::signature::Signature;
+use polyproto::types::FederationId;
+use rustls::client::danger::ServerCertVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::server::danger::ClientCertVerifier;
+use rustls::{CertificateError, Error, SignatureScheme};
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn now() -> UnixTime {
+    UnixTime::since_unix_epoch(Duration::from_secs(100))
+}
+
+fn der(cert: &Cert) -> CertificateDer<'static> {
+    CertificateDer::try_from(cert).unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn id_cert_chain_converts_to_certificate_ders() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let home_server = home_server_cert(&home_server_key, &home_server_key, Some(0));
+    let actor = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &home_server_key,
+        2,
+        default_validity(),
+    );
+    let ders =
+        certificate_chain(&IdCertChain::new(actor.clone(), vec![home_server.clone()])).unwrap();
+    assert_eq!(ders.len(), 2);
+    assert_eq!(Cert::try_from(&ders[0]).unwrap(), actor);
+    assert_eq!(Cert::try_from(&ders[1]).unwrap(), home_server);
+    assert_eq!(
+        signature_schemes::<Ed25519Signature>(),
+        vec![SignatureScheme::ED25519]
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn client_certs_must_be_anchored() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let home_server = home_server_cert(&home_server_key, &home_server_key, Some(0));
+    let actor = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &home_server_key,
+        2,
+        default_validity(),
+    );
+    let verifier = IdCertVerifier::new(
+        TrustStore::new().with_root("polyphony.chat", home_server.clone()),
+        Target::Actor,
+    );
+    assert_eq!(ClientCertVerifier::root_hint_subjects(&verifier).len(), 1);
+    assert!(verifier
+        .verify_client_cert(&der(&actor), &[der(&home_server)], now())
+        .is_ok());
+    assert_eq!(
+        verifier
+            .verify_client_cert(&der(&actor), &[], now())
+            .unwrap_err(),
+        Error::InvalidCertificate(CertificateError::UnknownIssuer)
+    );
+    assert_eq!(
+        verifier
+            .verify_client_cert(
+                &der(&actor),
+                &[der(&home_server)],
+                UnixTime::since_unix_epoch(Duration::from_secs(2000))
+            )
+            .unwrap_err(),
+        Error::InvalidCertificate(CertificateError::Expired)
+    );
+
+    let other_home_server = home_server_id_cert();
+    let verifier = IdCertVerifier::new(
+        TrustStore::new().with_root("polyphony.chat", other_home_server),
+        Target::Actor,
+    );
+    assert_eq!(
+        verifier
+            .verify_client_cert(&der(&actor), &[der(&home_server)], now())
+            .unwrap_err(),
+        Error::InvalidCertificate(CertificateError::UnknownIssuer)
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn self_signed_server_certs_are_checked_against_the_server_name() {
+    init_logger();
+    let home_server = home_server_id_cert();
+    let verifier = IdCertVerifier::new(
+        TrustStore::new().with_root("polyphony.chat", home_server.clone()),
+        Target::HomeServer,
+    );
+    assert!(verifier
+        .verify_server_cert(
+            &der(&home_server),
+            &[],
+            &ServerName::try_from("polyphony.chat").unwrap(),
+            &[],
+            now(),
+        )
+        .is_ok());
+    assert_eq!(
+        verifier
+            .verify_server_cert(
+                &der(&home_server),
+                &[],
+                &ServerName::try_from("example.com").unwrap(),
+                &[],
+                now(),
+            )
+            .unwrap_err(),
+        Error::InvalidCertificate(CertificateError::NotValidForName)
+    );
+    assert_eq!(
+        verifier
+            .verify_server_cert(
+                &der(&home_server_id_cert()),
+                &[],
+                &ServerName::try_from("polyphony.chat").unwrap(),
+                &[],
+                now(),
+            )
+            .unwrap_err(),
+        Error::InvalidCertificate(CertificateError::UnknownIssuer)
+    );
+    assert!(matches!(
+        verifier.verify_server_cert(
+            &CertificateDer::from(vec![0x30, 0x00]),
+            &[],
+            &ServerName::try_from("polyphony.chat").unwrap(),
+            &[],
+            now(),
+        ),
+        Err(Error::InvalidCertificate(CertificateError::BadEncoding))
+    ));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn handshake_signatures_are_verified_with_the_certificate_key() {
+    init_logger();
+    let key = gen_priv_key();
+    let cert = der(&home_server_cert(&key, &key, Some(0)));
+    let message = b"TLS 1.3, server CertificateVerify";
+    let signature = key.sign(message).as_signature().to_bytes();
+    assert!(
+        verify_handshake_signature::<Ed25519Signature, Ed25519PublicKey>(
+            message,
+            &cert,
+            SignatureScheme::ED25519,
+            &signature
+        )
+        .is_ok()
+    );
+    assert_eq!(
+        verify_handshake_signature::<Ed25519Signature, Ed25519PublicKey>(
+            b"something else",
+            &cert,
+            SignatureScheme::ED25519,
+            &signature
+        )
+        .unwrap_err(),
+        Error::InvalidCertificate(CertificateError::BadSignature)
+    );
+    assert!(matches!(
+        verify_handshake_signature::<Ed25519Signature, Ed25519PublicKey>(
+            message,
+            &cert,
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            &signature
+        ),
+        Err(Error::PeerMisbehaved(_))
+    ));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn id_cert_is_usable_as_a_client_certificate() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let actor_key = gen_priv_key();
+    let home_server = home_server_cert(&home_server_key, &home_server_key, Some(0));
+    let actor = actor_cert("flori", &actor_key, &home_server_key, 2, default_validity());
+    let chain = IdCertChain::new(actor.clone(), vec![home_server.clone()]);
+    let certified = certified_key(&chain, actor_key).unwrap();
+    assert_eq!(certified.cert, vec![der(&actor), der(&home_server)]);
+    assert!(certified.keys_match().is_ok());
+
+    assert!(certified
+        .key
+        .choose_scheme(&[SignatureScheme::ECDSA_NISTP256_SHA256])
+        .is_none());
+    let signer = certified
+        .key
+        .choose_scheme(&[
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::ED25519,
+        ])
+        .unwrap();
+    assert_eq!(signer.scheme(), SignatureScheme::ED25519);
+    let message = b"TLS 1.3, client CertificateVerify";
+    let signature = signer.sign(message).unwrap();
+    assert!(
+        verify_handshake_signature::<Ed25519Signature, Ed25519PublicKey>(
+            message,
+            certified.end_entity_cert().unwrap(),
+            signer.scheme(),
+            &signature
+        )
+        .is_ok()
+    );
+
+    let verifier = IdCertVerifier::new(
+        TrustStore::new().with_root("polyphony.chat", home_server),
+        Target::Actor,
+    );
+    assert!(verifier
+        .verify_client_cert(&certified.cert[0], &certified.cert[1..], now())
+        .is_ok());
+
+    assert!(matches!(
+        certified_key(&chain, gen_priv_key()),
+        Err(ConversionError::InvalidInput(_))
+    ));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn peer_certificates_map_to_federation_id() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let home_server = home_server_cert(&home_server_key, &home_server_key, Some(0));
+    let actor = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &home_server_key,
+        2,
+        default_validity(),
+    );
+    let trust_store = TrustStore::new().with_root("polyphony.chat", home_server.clone());
+    let (federation_id, session_id) = FederationId::try_from_peer_certificates(
+        &[der(&actor), der(&home_server)],
+        &trust_store,
+        now(),
+    )
+    .unwrap();
+    assert_eq!(
+        federation_id,
+        FederationId::new("flori@polyphony.chat").unwrap()
+    );
+    assert_eq!(Some(session_id), actor.session_id());
+
+    assert!(matches!(
+        FederationId::try_from_peer_certificates(&[], &trust_store, now()),
+        Err(ConversionError::InvalidInput(_))
+    ));
+    assert!(matches!(
+        FederationId::try_from_peer_certificates(&[der(&actor)], &trust_store, now()),
+        Err(ConversionError::InvalidCert(InvalidCert::NotAnchored(_)))
+    ));
+    assert!(matches!(
+        FederationId::try_from_peer_certificates(&[der(&home_server)], &trust_store, now()),
+        Err(ConversionError::InvalidCert(_))
+    ));
+    assert!(matches!(
+        FederationId::try_from_peer_certificates(
+            &[der(&actor), der(&home_server)],
+            &TrustStore::new().with_root("polyphony.chat", home_server_id_cert()),
+            now()
+        ),
+        Err(ConversionError::InvalidCert(InvalidCert::NotAnchored(_)))
+    ));
+}
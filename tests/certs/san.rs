@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::{Ia5String, OctetString, Uint};
+use der::Encode;
+use polyproto::certs::crldp::CrlDistributionPoint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcerttbs::IdCertTbs;
+use polyproto::certs::san::{SubjectAltNames, OID_SUBJECT_ALT_NAME};
+use polyproto::certs::Target;
+use polyproto::errors::InvalidCert;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::SubjectAltName;
+use x509_cert::ext::Extension;
+use x509_cert::TbsCertificate;
+
+use crate::common::*;
+
+fn home_server_tbs(key: &Ed25519PrivateKey) -> IdCertTbs<Ed25519Signature, Ed25519PublicKey> {
+    IdCertTbs::from_ca_csr(
+        home_server_csr(key),
+        Uint::new(&[1]).unwrap(),
+        key.algorithm_identifier(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn issue_home_server_cert_with_subject_alt_names() {
+    init_logger();
+    let key = gen_priv_key();
+    let subject_alt_names = SubjectAltNames::new()
+        .with_dns_name("polyphony.chat")
+        .unwrap()
+        .with_dns_name("API.polyphony.chat")
+        .unwrap();
+    let tbs = home_server_tbs(&key).with_subject_alt_names(subject_alt_names.clone());
+    let cert = IdCert::from_tbs(tbs, &key, Target::HomeServer).unwrap();
+    assert_eq!(cert.dns_names(), ["polyphony.chat", "api.polyphony.chat"]);
+    assert_eq!(cert.subject_alt_names(), &subject_alt_names);
+
+    let decoded = IdCert::from_der(
+        &cert.to_der().unwrap(),
+        Target::HomeServer,
+        Timestamp::from_unix_seconds(100),
+        key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded, cert);
+
+    cert.check_domain("polyphony.chat").unwrap();
+    cert.check_domain("Api.Polyphony.Chat").unwrap();
+    assert_eq!(
+        cert.check_domain("chat.polyphony.chat"),
+        Err(InvalidCert::DomainMismatch(
+            "chat.polyphony.chat".to_string()
+        ))
+    );
+
+    let tbs = TbsCertificate::try_from(decoded.id_cert_tbs.into_inner()).unwrap();
+    let extension = tbs
+        .extensions
+        .unwrap()
+        .into_iter()
+        .find(|extension| extension.extn_id.to_string() == OID_SUBJECT_ALT_NAME)
+        .unwrap();
+    assert!(!extension.critical);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn check_domain_without_subject_alt_names() {
+    init_logger();
+    let cert = home_server_id_cert();
+    assert!(cert.dns_names().is_empty());
+    cert.check_domain("polyphony.chat").unwrap();
+    cert.check_domain("POLYPHONY.chat").unwrap();
+    assert!(cert.check_domain("api.polyphony.chat").is_err());
+
+    // Certificates without subject alternative names do not carry the extension.
+    let tbs = TbsCertificate::try_from(cert.id_cert_tbs.into_inner()).unwrap();
+    assert!(!tbs
+        .extensions
+        .unwrap()
+        .iter()
+        .any(|extension| extension.extn_id.to_string() == OID_SUBJECT_ALT_NAME));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn reject_subject_alt_names_outside_domain() {
+    init_logger();
+    let key = gen_priv_key();
+    for name in [
+        "example.com",
+        "polyphony.chat.example.com",
+        "notpolyphony.chat",
+    ] {
+        let tbs = home_server_tbs(&key)
+            .with_subject_alt_names(SubjectAltNames::new().with_dns_name(name).unwrap());
+        assert!(IdCert::from_tbs(tbs, &key, Target::HomeServer).is_err());
+    }
+
+    // Actor certificates must not carry subject alternative names at all.
+    let tbs = IdCertTbs::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        Uint::new(&[2]).unwrap(),
+        key.algorithm_identifier(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+    .with_subject_alt_names(
+        SubjectAltNames::new()
+            .with_dns_name("polyphony.chat")
+            .unwrap(),
+    );
+    assert!(IdCert::from_tbs(tbs, &key, Target::Actor).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn subject_alt_names_dns_names() {
+    let mut subject_alt_names = SubjectAltNames::new();
+    assert!(subject_alt_names.add_dns_name("polyphony.chat").unwrap());
+    assert!(!subject_alt_names.add_dns_name("Polyphony.Chat").unwrap());
+    assert!(subject_alt_names.add_dns_name("localhost").unwrap());
+    assert_eq!(subject_alt_names.len(), 2);
+    assert!(subject_alt_names.contains("POLYPHONY.CHAT"));
+
+    for name in [
+        "",
+        "*.polyphony.chat",
+        "-polyphony.chat",
+        "polyphony-.chat",
+        "polyphony..chat",
+        "polyphony.chat.",
+        "polyphöny.chat",
+        "poly_phony.chat",
+    ] {
+        assert!(
+            SubjectAltNames::new().with_dns_name(name).is_err(),
+            "{name}"
+        );
+    }
+    assert!(SubjectAltNames::new()
+        .with_dns_name(&format!("{}.chat", "a".repeat(64)))
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn subject_alt_names_extension() {
+    let subject_alt_names = SubjectAltNames::new()
+        .with_dns_name("polyphony.chat")
+        .unwrap();
+    let extension = Extension::try_from(subject_alt_names.clone()).unwrap();
+    assert_eq!(
+        SubjectAltNames::try_from(extension.clone()).unwrap(),
+        subject_alt_names
+    );
+    assert!(Extension::try_from(SubjectAltNames::new()).is_err());
+
+    let mut critical = extension.clone();
+    critical.critical = true;
+    assert!(SubjectAltNames::try_from(critical).is_err());
+
+    let uri = SubjectAltName(vec![GeneralName::UniformResourceIdentifier(
+        Ia5String::new("https://polyphony.chat").unwrap(),
+    )]);
+    let mut uri_extension = extension.clone();
+    uri_extension.extn_value = OctetString::new(uri.to_der().unwrap()).unwrap();
+    assert!(SubjectAltNames::try_from(uri_extension).is_err());
+
+    let distribution_point =
+        Extension::try_from(CrlDistributionPoint::new("https://polyphony.chat/crl").unwrap())
+            .unwrap();
+    assert!(SubjectAltNames::try_from(distribution_point).is_err());
+}
@@ -0,0 +1,87 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use polyproto::certs::security::{
+    SecurityFacts, SecurityLevel, SecurityPolicy, StandardSecurityPolicy,
+};
+use x509_cert::time::{Time, Validity};
+
+use crate::common::*;
+
+/// A policy treating every key as hardware-backed, e.g. for a deployment which only issues
+/// certificates for keys held in an HSM.
+struct HsmOnly;
+
+impl SecurityPolicy for HsmOnly {
+    fn security_level(&self, facts: &SecurityFacts) -> SecurityLevel {
+        match StandardSecurityPolicy::default().security_level(facts) {
+            SecurityLevel::Strong => SecurityLevel::HardwareBacked,
+            level => level,
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn security_levels() {
+    init_logger();
+    let cert = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &gen_priv_key(),
+        1,
+        default_validity(),
+    );
+    let facts = SecurityFacts::of(&cert);
+    assert_eq!(facts.public_key_algorithm.to_string(), "1.3.101.112");
+    assert_eq!(facts.validity, Duration::from_secs(990));
+    assert_eq!(cert.security_level(), SecurityLevel::Strong);
+    assert!(cert.security_level().meets(SecurityLevel::Basic));
+    assert!(!cert.security_level().meets(SecurityLevel::HardwareBacked));
+    assert_eq!(
+        cert.security_level_with(&HsmOnly),
+        SecurityLevel::HardwareBacked
+    );
+
+    // Long-lived certificates are only basic.
+    let long_lived = actor_cert(
+        "flori",
+        &gen_priv_key(),
+        &gen_priv_key(),
+        1,
+        Validity {
+            not_before: Time::UtcTime(
+                x509_cert::der::asn1::UtcTime::from_unix_duration(Duration::from_secs(10)).unwrap(),
+            ),
+            not_after: Time::UtcTime(
+                x509_cert::der::asn1::UtcTime::from_unix_duration(Duration::from_secs(
+                    10 + 365 * 24 * 60 * 60,
+                ))
+                .unwrap(),
+            ),
+        },
+    );
+    assert_eq!(long_lived.security_level(), SecurityLevel::Basic);
+
+    // Unknown key algorithms are insufficient.
+    let mut policy = StandardSecurityPolicy::default();
+    policy.key_strengths.clear();
+    assert_eq!(
+        cert.security_level_with(&policy),
+        SecurityLevel::Insufficient
+    );
+    policy.key_strengths.insert("1.3.101.112".to_string(), 128);
+    policy
+        .hardware_backed_algorithms
+        .push("1.3.101.112".to_string());
+    assert_eq!(
+        cert.security_level_with(&policy),
+        SecurityLevel::HardwareBacked
+    );
+
+    assert!(SecurityLevel::HardwareBacked > SecurityLevel::Strong);
+    assert_eq!(SecurityLevel::HardwareBacked.to_string(), "hardware-backed");
+}
@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::serial::{
+    check_serial_number, SerialNumberGenerator, DEFAULT_RANDOM_SERIAL_NUMBER_LENGTH,
+};
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn check_serial_numbers() {
+    check_serial_number(&Uint::new(&[1]).unwrap()).unwrap();
+    check_serial_number(&Uint::new(&[0x7f; 20]).unwrap()).unwrap();
+    assert!(check_serial_number(&Uint::new(&[0]).unwrap()).is_err());
+    // The leading zero byte required to keep the value positive exceeds the limit.
+    assert!(check_serial_number(&Uint::new(&[0xff; 20]).unwrap()).is_err());
+    assert!(check_serial_number(&Uint::new(&[1; 21]).unwrap()).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn generate_serial_numbers() {
+    init_logger();
+    let now = Timestamp::from_unix_seconds(1_700_000_000);
+    let mut rng = rand::rngs::OsRng;
+
+    let mut random = SerialNumberGenerator::default();
+    assert_eq!(random, SerialNumberGenerator::random());
+    let first = random.generate(now, &mut rng).unwrap();
+    assert!(first.as_bytes().len() <= DEFAULT_RANDOM_SERIAL_NUMBER_LENGTH);
+    assert_ne!(first, random.generate(now, &mut rng).unwrap());
+    let mut random = SerialNumberGenerator::random_with_length(20).unwrap();
+    for _ in 0..32 {
+        check_serial_number(&random.generate(now, &mut rng).unwrap()).unwrap();
+    }
+    assert!(SerialNumberGenerator::random_with_length(0).is_err());
+    assert!(SerialNumberGenerator::random_with_length(21).is_err());
+
+    let mut uuid = SerialNumberGenerator::uuid_v7();
+    let first = uuid.generate(now, &mut rng).unwrap();
+    let later = uuid
+        .generate(Timestamp::from_unix_seconds(1_700_000_001), &mut rng)
+        .unwrap();
+    assert!(first.as_bytes() < later.as_bytes());
+    let bytes = first.as_bytes();
+    assert_eq!(bytes.len(), 16);
+    assert_eq!(&bytes[..6], &1_700_000_000_000u64.to_be_bytes()[2..]);
+    // Version 7 and the RFC 9562 variant.
+    assert_eq!(bytes[6] >> 4, 7);
+    assert_eq!(bytes[8] >> 6, 0b10);
+
+    let mut counter = SerialNumberGenerator::counter(255).unwrap();
+    assert_eq!(counter.generate(now, &mut rng).unwrap().as_bytes(), &[255]);
+    assert_eq!(counter.generate(now, &mut rng).unwrap().as_bytes(), &[1, 0]);
+    assert_eq!(counter, SerialNumberGenerator::Counter { next: 257 });
+    assert!(SerialNumberGenerator::counter(0).is_err());
+    let mut exhausted = SerialNumberGenerator::counter(u128::MAX).unwrap();
+    assert!(exhausted.generate(now, &mut rng).is_err());
+
+    let priv_key = gen_priv_key();
+    IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &priv_key,
+        SerialNumberGenerator::random()
+            .generate(now, &mut rng)
+            .unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+}
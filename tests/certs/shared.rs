@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::shared::SharedIdCert;
+use polyproto::certs::Target;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::Constrained;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn shared_id_cert() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let shared = SharedIdCert::from(cert.clone());
+    let clone = shared.clone();
+    assert!(shared.ptr_eq(&clone));
+    assert_eq!(shared, cert);
+    assert_eq!(
+        shared.id_cert_tbs.serial_number,
+        cert.id_cert_tbs.serial_number
+    );
+    shared.validate(Some(Target::Actor)).unwrap();
+    shared.verify_signature(home_server_key.pubkey()).unwrap();
+
+    let der = shared.to_der().unwrap();
+    let decoded = SharedIdCert::from_der(
+        &der,
+        Target::Actor,
+        Timestamp::from_unix_seconds(100),
+        home_server_key.pubkey(),
+    )
+    .unwrap();
+    assert_eq!(decoded, shared);
+    assert!(!decoded.ptr_eq(&shared));
+
+    // Handles can be passed between threads.
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    assert_send_sync(&clone);
+    assert_eq!(clone.cert_id().unwrap(), cert.cert_id().unwrap());
+
+    drop(clone);
+    assert_eq!(shared.into_id_cert(), cert);
+    assert_eq!(IdCert::from(decoded), cert);
+}
@@ -0,0 +1,263 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use der::asn1::Uint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::status::{
+    RevocationCache, RevocationCacheConfig, RevocationCheck, RevocationMode, RevocationReason,
+    RevocationStatus, StatusFuture, StatusRequest, StatusResponse, STATUS_NONCE_LENGTH,
+};
+use polyproto::errors::{InvalidCert, PublicKeyError};
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn issuer_and_actor(key: &Ed25519PrivateKey) -> (Cert, Cert) {
+    let issuer = IdCert::from_ca_csr(
+        home_server_csr(key),
+        key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    (issuer, actor)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn revocation_reason_codes() {
+    for code in 0..=10 {
+        match RevocationReason::from_code(code) {
+            Some(reason) => assert_eq!(reason.code(), code),
+            None => assert_eq!(code, 7),
+        }
+    }
+    assert!(RevocationReason::from_code(11).is_none());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verify_status_response() {
+    init_logger();
+    let key = gen_priv_key();
+    let (issuer, actor) = issuer_and_actor(&key);
+    let request = StatusRequest::fresh(&actor, &mut rand::thread_rng()).unwrap();
+    assert_eq!(request.nonce.len(), STATUS_NONCE_LENGTH);
+    assert_eq!(request.cert_id, actor.cert_id().unwrap());
+
+    let status = RevocationStatus::Revoked {
+        revoked_at: Timestamp::from(50),
+        reason: RevocationReason::KeyCompromise,
+    };
+    let response = StatusResponse::new(
+        &request,
+        status,
+        Timestamp::from(100),
+        Some(Timestamp::from(200)),
+        &key,
+    );
+    response
+        .verify(&request, &issuer, Timestamp::from(150))
+        .unwrap();
+    assert_eq!(
+        response.verify(&request, &issuer, Timestamp::from(250)),
+        Err(InvalidCert::InvalidValidity)
+    );
+    assert_eq!(
+        response.verify(&request, &issuer, Timestamp::from(50)),
+        Err(InvalidCert::InvalidValidity)
+    );
+
+    let other_request = StatusRequest::new(request.cert_id.clone(), &[1, 2, 3]);
+    assert!(response
+        .verify(&other_request, &issuer, Timestamp::from(150))
+        .is_err());
+
+    let mut tampered = response.clone();
+    tampered.status = RevocationStatus::Good;
+    assert_eq!(
+        tampered.verify(&request, &issuer, Timestamp::from(150)),
+        Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature))
+    );
+
+    let forged = StatusResponse::new(
+        &request,
+        RevocationStatus::Good,
+        Timestamp::from(100),
+        None,
+        &gen_priv_key(),
+    );
+    assert_eq!(
+        forged.verify(&request, &issuer, Timestamp::from(150)),
+        Err(InvalidCert::PublicKeyError(PublicKeyError::BadSignature))
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn status_serde_roundtrip() {
+    init_logger();
+    let key = gen_priv_key();
+    let (_, actor) = issuer_and_actor(&key);
+    let request = StatusRequest::fresh(&actor, &mut rand::thread_rng()).unwrap();
+    let json = serde_json::to_string(&request).unwrap();
+    assert_eq!(
+        serde_json::from_str::<StatusRequest>(&json).unwrap(),
+        request
+    );
+
+    for status in [
+        RevocationStatus::Good,
+        RevocationStatus::Unknown,
+        RevocationStatus::Revoked {
+            revoked_at: Timestamp::from(50),
+            reason: RevocationReason::CessationOfOperation,
+        },
+    ] {
+        let response = StatusResponse::new(&request, status, Timestamp::from(100), None, &key);
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<StatusResponse<Ed25519Signature>>(&json).unwrap(),
+            response
+        );
+    }
+
+    let response = StatusResponse::new(
+        &request,
+        RevocationStatus::Good,
+        Timestamp::from(100),
+        None,
+        &key,
+    );
+    let mut json = serde_json::to_value(&response).unwrap();
+    json["status"] = "revoked".into();
+    assert!(serde_json::from_value::<StatusResponse<Ed25519Signature>>(json).is_err());
+}
+
+struct CountingCheck {
+    status: Mutex<RevocationStatus>,
+    calls: AtomicUsize,
+}
+
+impl CountingCheck {
+    fn new(status: RevocationStatus) -> Self {
+        Self {
+            status: Mutex::new(status),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    fn set(&self, status: RevocationStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl RevocationCheck<Ed25519Signature, Ed25519PublicKey> for CountingCheck {
+    fn status<'a>(&'a self, _: &'a Cert, _: &'a Cert, _: Timestamp) -> StatusFuture<'a> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let status = *self.status.lock().unwrap();
+        Box::pin(async move { status })
+    }
+}
+
+#[tokio::test]
+async fn revocation_cache_ttl() {
+    init_logger();
+    let key = gen_priv_key();
+    let (issuer, actor) = issuer_and_actor(&key);
+    let cache = RevocationCache::new(CountingCheck::new(RevocationStatus::Good)).with_config(
+        RevocationCacheConfig {
+            good_ttl: Duration::from_secs(100),
+            stale_while_revalidate: Duration::from_secs(50),
+            ..Default::default()
+        },
+    );
+
+    let status = cache.lookup(&actor, &issuer, Timestamp::from(100)).await;
+    assert_eq!(status, RevocationStatus::Good);
+    cache.lookup(&actor, &issuer, Timestamp::from(199)).await;
+    assert_eq!(cache.checker().calls(), 1);
+    assert_eq!(cache.len(), 1);
+
+    // Past the TTL, but within the stale window: served from the cache, queued for revalidation.
+    let revoked = RevocationStatus::Revoked {
+        revoked_at: Timestamp::from(190),
+        reason: RevocationReason::KeyCompromise,
+    };
+    cache.checker().set(revoked);
+    let status = cache.lookup(&actor, &issuer, Timestamp::from(220)).await;
+    assert_eq!(status, RevocationStatus::Good);
+    assert_eq!(cache.checker().calls(), 1);
+    assert_eq!(cache.pending(), 1);
+    assert_eq!(cache.revalidate(Timestamp::from(221)).await, 1);
+    assert_eq!(cache.pending(), 0);
+    assert_eq!(cache.cached(&actor.cert_id().unwrap()), Some(revoked));
+
+    // An unreachable endpoint does not replace a revoked status.
+    cache.invalidate(&actor.cert_id().unwrap());
+    assert!(cache.is_empty());
+    cache.lookup(&actor, &issuer, Timestamp::from(300)).await;
+    cache.checker().set(RevocationStatus::Unknown);
+    let status = cache
+        .lookup(&actor, &issuer, Timestamp::from(300 + 86_400 + 400))
+        .await;
+    assert_eq!(status, revoked);
+}
+
+#[tokio::test]
+async fn revocation_cache_mode() {
+    init_logger();
+    let key = gen_priv_key();
+    let (issuer, actor) = issuer_and_actor(&key);
+    let time = Timestamp::from(100);
+
+    let soft = RevocationCache::new(CountingCheck::new(RevocationStatus::Unknown));
+    assert_eq!(
+        soft.check(&actor, &issuer, time).await,
+        Ok(RevocationStatus::Unknown)
+    );
+    let hard = RevocationCache::new(CountingCheck::new(RevocationStatus::Unknown)).with_config(
+        RevocationCacheConfig {
+            mode: RevocationMode::HardFail,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        hard.check(&actor, &issuer, time).await,
+        Err(InvalidCert::RevocationStatusUnknown)
+    );
+    // Unknown statuses are cached briefly, so the endpoint is not queried for every validation.
+    hard.check(&actor, &issuer, Timestamp::from(150)).await.ok();
+    assert_eq!(hard.checker().calls(), 1);
+    hard.check(&actor, &issuer, Timestamp::from(500)).await.ok();
+    assert_eq!(hard.checker().calls(), 2);
+
+    let revoked = RevocationCache::new(CountingCheck::new(RevocationStatus::Revoked {
+        revoked_at: Timestamp::from(50),
+        reason: RevocationReason::Unspecified,
+    }));
+    assert_eq!(
+        revoked.check(&actor, &issuer, time).await,
+        Err(InvalidCert::Revoked(Timestamp::from(50)))
+    );
+}
@@ -0,0 +1,167 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::superseded::{CertUsage, SupersededCerts, SupersededNotice};
+use polyproto::errors::InvalidCert;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::verifier::Verifier;
+
+use crate::common::*;
+
+struct Rotation {
+    home_server_key: Ed25519PrivateKey,
+    old_cert: IdCert<Ed25519Signature, Ed25519PublicKey>,
+    new_cert: IdCert<Ed25519Signature, Ed25519PublicKey>,
+    notice: SupersededNotice<Ed25519Signature>,
+}
+
+fn rotation() -> Rotation {
+    let home_server_key = gen_priv_key();
+    let issue = |serial: u8| {
+        IdCert::from_actor_csr(
+            actor_csr("flori", &gen_priv_key()),
+            &home_server_key,
+            Uint::new(&[serial]).unwrap(),
+            home_server_subject(),
+            default_validity(),
+        )
+        .unwrap()
+    };
+    let old_cert = issue(1);
+    let new_cert = issue(2);
+    let notice = SupersededNotice::new(
+        &old_cert,
+        &new_cert,
+        Timestamp::from_unix_seconds(500),
+        &home_server_key,
+    )
+    .unwrap();
+    Rotation {
+        home_server_key,
+        old_cert,
+        new_cert,
+        notice,
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn superseded_cert_usage() {
+    init_logger();
+    let rotation = rotation();
+    assert_eq!(rotation.notice.old, rotation.old_cert.cert_id().unwrap());
+    assert_eq!(rotation.notice.new, rotation.new_cert.cert_id().unwrap());
+    let mut superseded = SupersededCerts::new();
+    superseded
+        .insert(&rotation.notice, rotation.home_server_key.pubkey())
+        .unwrap();
+    assert_eq!(
+        superseded.check(&rotation.old_cert, CertUsage::NewSession),
+        Err(InvalidCert::Superseded(Timestamp::from_unix_seconds(500)))
+    );
+    superseded
+        .check(
+            &rotation.old_cert,
+            CertUsage::PastData {
+                signed_at: Timestamp::from_unix_seconds(499),
+            },
+        )
+        .unwrap();
+    assert!(superseded
+        .check(
+            &rotation.old_cert,
+            CertUsage::PastData {
+                signed_at: Timestamp::from_unix_seconds(500),
+            },
+        )
+        .is_err());
+    superseded
+        .check(&rotation.new_cert, CertUsage::NewSession)
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn forged_notice_is_rejected() {
+    init_logger();
+    let rotation = rotation();
+    let mut superseded = SupersededCerts::new();
+    assert!(superseded
+        .insert(&rotation.notice, gen_priv_key().pubkey())
+        .is_err());
+    let mut tampered = rotation.notice.clone();
+    tampered.superseded_at = Timestamp::from_unix_seconds(900);
+    assert!(superseded
+        .insert(&tampered, rotation.home_server_key.pubkey())
+        .is_err());
+    assert!(superseded.is_empty());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn notice_requires_same_subject() {
+    let home_server_key = gen_priv_key();
+    let issue = |cn: &str| {
+        IdCert::from_actor_csr(
+            actor_csr(cn, &gen_priv_key()),
+            &home_server_key,
+            Uint::new(&[1]).unwrap(),
+            home_server_subject(),
+            default_validity(),
+        )
+        .unwrap()
+    };
+    assert!(SupersededNotice::new(
+        &issue("flori"),
+        &issue("bitfl0wer"),
+        Timestamp::from_unix_seconds(500),
+        &home_server_key,
+    )
+    .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verifier_respects_supersession() {
+    init_logger();
+    let rotation = rotation();
+    let mut superseded = SupersededCerts::new();
+    superseded
+        .insert(&rotation.notice, rotation.home_server_key.pubkey())
+        .unwrap();
+    let mut verifier = Verifier::default();
+    assert!(verifier
+        .verify_actor_for_usage(
+            &rotation.old_cert,
+            CertUsage::NewSession,
+            Timestamp::from_unix_seconds(600),
+            rotation.home_server_key.pubkey(),
+            &superseded,
+        )
+        .is_err());
+    verifier
+        .verify_actor_for_usage(
+            &rotation.old_cert,
+            CertUsage::PastData {
+                signed_at: Timestamp::from_unix_seconds(100),
+            },
+            Timestamp::from_unix_seconds(600),
+            rotation.home_server_key.pubkey(),
+            &superseded,
+        )
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn notice_serde_roundtrip() {
+    let rotation = rotation();
+    let json = serde_json::to_string(&rotation.notice).unwrap();
+    let decoded: SupersededNotice<Ed25519Signature> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, rotation.notice);
+    decoded.verify(rotation.home_server_key.pubkey()).unwrap();
+}
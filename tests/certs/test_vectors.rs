@@ -0,0 +1,74 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use ed25519_dalek::VerifyingKey;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::policy::ValidationPolicy;
+use polyproto::test_vectors::{self, ExpectedOutcome, HOME_SERVER_PUBLIC_KEY};
+
+use crate::common::*;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn home_server_public_key() -> Ed25519PublicKey {
+    Ed25519PublicKey {
+        key: VerifyingKey::from_bytes(&HOME_SERVER_PUBLIC_KEY).unwrap(),
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_vectors_have_expected_outcomes() {
+    init_logger();
+    let public_key = home_server_public_key();
+    let policy = ValidationPolicy::default();
+    for vector in test_vectors::ALL {
+        let from_pem = Cert::from_pem_with(
+            vector.pem,
+            vector.target,
+            vector.time(),
+            &public_key,
+            &policy,
+        );
+        assert_eq!(
+            ExpectedOutcome::from_result(&from_pem),
+            Some(vector.expected),
+            "{}: {:?}",
+            vector.name,
+            from_pem.err()
+        );
+        let from_der = Cert::from_der_with(
+            &vector.der(),
+            vector.target,
+            vector.time(),
+            &public_key,
+            &policy,
+        );
+        assert_eq!(
+            ExpectedOutcome::from_result(&from_der),
+            Some(vector.expected),
+            "{}: {:?}",
+            vector.name,
+            from_der.err()
+        );
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_vectors_reencode_identically() {
+    for vector in test_vectors::ALL {
+        let cert = Cert::from_der_unchecked(&vector.der()).unwrap();
+        assert_eq!(cert.to_der().unwrap(), vector.der(), "{}", vector.name);
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_vector_names_are_unique() {
+    for vector in test_vectors::ALL {
+        assert_eq!(test_vectors::by_name(vector.name), Some(vector));
+    }
+    assert!(test_vectors::by_name("unknown").is_none());
+}
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use polyproto::certs::fingerprint::FingerprintDigest;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn to_text_renders_certificate_details() {
+    init_logger();
+    let home_server = home_server_id_cert();
+    let text = home_server.to_text().unwrap();
+    for line in [
+        "Certificate:",
+        "        Serial Number: 08",
+        "        Signature Algorithm: ED25519",
+        &format!("        Issuer: {}", home_server.id_cert_tbs.issuer),
+        "            Not Before: 1970-01-01T00:00:10Z",
+        "            Not After : 1970-01-01T00:16:40Z",
+        &format!("        Subject: {}", home_server.id_cert_tbs.subject),
+        "            Public Key Algorithm: ED25519",
+        "            Target: Home server",
+        "            Basic Constraints: CA:TRUE, pathlen:0",
+        &format!(
+            "    SHA-256: {}",
+            home_server
+                .fingerprint(FingerprintDigest::Sha256)
+                .unwrap()
+                .to_colon_hex()
+        ),
+        &format!(
+            "    Key SHA-256: {}",
+            home_server.key_fingerprint().unwrap()
+        ),
+    ] {
+        assert!(
+            text.lines().any(|candidate| candidate == line),
+            "missing line {:?} in\n{}",
+            line,
+            text
+        );
+    }
+    assert!(text.contains("Certificate Sign"));
+
+    let actor = actor_id_cert("flori").to_text().unwrap();
+    assert!(actor.contains("            Target: Actor\n"));
+    assert!(actor.contains("            Basic Constraints: CA:FALSE\n"));
+    assert!(actor.contains("            Key Usage: Digital Signature\n"));
+}
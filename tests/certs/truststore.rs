@@ -0,0 +1,105 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::chain::IdCertChain;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::truststore::{TrustAnchor, TrustStore};
+use polyproto::certs::Target;
+use polyproto::errors::InvalidCert;
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::timestamp::Timestamp;
+
+use crate::common::*;
+
+type Chain = IdCertChain<Ed25519Signature, Ed25519PublicKey>;
+
+fn chain(root_key: &Ed25519PrivateKey) -> Chain {
+    let root = IdCert::from_ca_csr(
+        home_server_csr(root_key),
+        root_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let leaf = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        root_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    IdCertChain::new(leaf, vec![root])
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn trust_store_root_anchor() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let chain = chain(&root_key);
+    let time = Timestamp::from(100);
+
+    let mut trust_store =
+        TrustStore::new().with_root("Polyphony.chat", chain.root().unwrap().clone());
+    assert_eq!(
+        trust_store.domains().collect::<Vec<_>>(),
+        vec!["polyphony.chat"]
+    );
+    chain
+        .validate_chain_anchored(time, Target::Actor, &trust_store)
+        .unwrap();
+
+    // A root which validates just as well, but was not pinned, is refused.
+    let impostor = self::chain(&gen_priv_key());
+    impostor.validate_chain(time, Target::Actor).unwrap();
+    assert_eq!(
+        impostor.validate_chain_anchored(time, Target::Actor, &trust_store),
+        Err(InvalidCert::NotAnchored("polyphony.chat".to_string()))
+    );
+
+    // Invalid chains are refused regardless of the trust store.
+    assert_eq!(
+        chain.validate_chain_anchored(Timestamp::from(2000), Target::Actor, &trust_store),
+        Err(InvalidCert::InvalidValidity)
+    );
+
+    // Anchors only vouch for their own domain.
+    let generation = trust_store.generation();
+    assert!(trust_store.remove_domain("polyphony.chat"));
+    assert!(trust_store.add(
+        "example.com",
+        TrustAnchor::Root(Box::new(chain.root().unwrap().clone()))
+    ));
+    assert!(trust_store.generation() > generation);
+    assert_eq!(
+        chain.validate_chain_anchored(time, Target::Actor, &trust_store),
+        Err(InvalidCert::NotAnchored("polyphony.chat".to_string()))
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn trust_store_pin_anchor() {
+    init_logger();
+    let root_key = gen_priv_key();
+    let chain = chain(&root_key);
+    let pin = root_key.pubkey().fingerprint().unwrap();
+
+    let mut trust_store = TrustStore::new().with_pin("polyphony.chat", pin);
+    assert_eq!(trust_store.check(&chain), Ok(()));
+    // Pins match any certificate with the pinned key, such as a reissued root.
+    assert_eq!(trust_store.check(&self::chain(&root_key)), Ok(()));
+    assert!(trust_store.check(&self::chain(&gen_priv_key())).is_err());
+
+    assert!(!trust_store.add("polyphony.chat", TrustAnchor::Pin(pin)));
+    assert!(trust_store.remove("polyphony.chat", &TrustAnchor::Pin(pin)));
+    assert!(trust_store.is_empty());
+    assert_eq!(
+        trust_store.check(&chain),
+        Err(InvalidCert::NotAnchored("polyphony.chat".to_string()))
+    );
+}
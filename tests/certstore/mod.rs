@@ -0,0 +1,122 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::{Uint, UtcTime};
+use polyproto::certs::idcert::IdCert;
+use polyproto::certstore::file::FileCertStore;
+use polyproto::certstore::memory::MemoryCertStore;
+use polyproto::certstore::CertStore;
+use polyproto::timestamp::Timestamp;
+use polyproto::types::FederationId;
+use x509_cert::time::{Time, Validity};
+
+use crate::common::*;
+
+#[cfg(feature = "sqlx")]
+mod sql;
+
+type Cert = IdCert<Ed25519Signature, Ed25519PublicKey>;
+
+fn cert(cn: &str, serial: u8, not_after: u64) -> Cert {
+    let key = gen_priv_key();
+    IdCert::from_actor_csr(
+        actor_csr(cn, &key),
+        &key,
+        Uint::new(&[serial]).unwrap(),
+        home_server_subject(),
+        Validity {
+            not_before: Time::UtcTime(
+                UtcTime::from_unix_duration(Duration::from_secs(10)).unwrap(),
+            ),
+            not_after: Time::UtcTime(
+                UtcTime::from_unix_duration(Duration::from_secs(not_after)).unwrap(),
+            ),
+        },
+    )
+    .unwrap()
+}
+
+fn exercise(store: &mut impl CertStore<Ed25519Signature, Ed25519PublicKey>) {
+    let flori_1 = cert("flori", 1, 1000);
+    let flori_2 = cert("flori", 2, 100);
+    let bitfl0wer = cert("bitfl0wer", 3, 1000);
+    assert!(store.list().unwrap().is_empty());
+    assert_eq!(store.get(&flori_1.cert_id().unwrap()).unwrap(), None);
+
+    for cert in [&flori_1, &flori_2, &bitfl0wer] {
+        let cert_id = store.insert(cert).unwrap();
+        assert_eq!(cert_id, cert.cert_id().unwrap());
+    }
+    let replacement = cert("flori", 1, 1000);
+    store.insert(&replacement).unwrap();
+    let flori_1 = replacement;
+    assert_eq!(
+        store.get(&flori_1.cert_id().unwrap()).unwrap(),
+        Some(flori_1.clone())
+    );
+    assert_eq!(store.list().unwrap().len(), 3);
+    assert_eq!(store.certs().unwrap().len(), 3);
+    assert_eq!(
+        store.get_by_serial(&Uint::new(&[3]).unwrap()).unwrap(),
+        vec![bitfl0wer.clone()]
+    );
+    assert!(store
+        .get_by_serial(&Uint::new(&[4]).unwrap())
+        .unwrap()
+        .is_empty());
+
+    let flori = FederationId::new("flori@polyphony.chat").unwrap();
+    assert_eq!(
+        store
+            .get_valid_for(&flori, Timestamp::from(50))
+            .unwrap()
+            .len(),
+        2
+    );
+    assert_eq!(
+        store.get_valid_for(&flori, Timestamp::from(500)).unwrap(),
+        vec![flori_1.clone()]
+    );
+    assert!(store
+        .get_valid_for(&flori, Timestamp::from(5000))
+        .unwrap()
+        .is_empty());
+
+    assert!(store.delete(&flori_1.cert_id().unwrap()).unwrap());
+    assert!(!store.delete(&flori_1.cert_id().unwrap()).unwrap());
+    assert_eq!(store.get(&flori_1.cert_id().unwrap()).unwrap(), None);
+    assert_eq!(store.list().unwrap().len(), 2);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn memory_cert_store() {
+    init_logger();
+    let mut store = MemoryCertStore::new();
+    exercise(&mut store);
+    assert_eq!(store.len(), 2);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn file_cert_store() {
+    init_logger();
+    let directory = std::env::temp_dir().join(format!(
+        "polyproto-certstore-{}-{}",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    let mut store = FileCertStore::open(&directory).unwrap();
+    exercise(&mut store);
+    std::fs::write(directory.join("README.txt"), b"unrelated").unwrap();
+    std::fs::write(directory.join("not-a-cert-id.pem"), b"unrelated").unwrap();
+    // Reopening the store sees the same certificates.
+    let store = FileCertStore::open(&directory).unwrap();
+    let cert_ids = CertStore::<Ed25519Signature, Ed25519PublicKey>::list(&store).unwrap();
+    assert_eq!(cert_ids.len(), 2);
+    assert!(store.path_of(&cert_ids[0]).exists());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::{BitString, Uint};
+use polyproto::certstore::sql::{migrate, schema_version, SqlCertStore, SqlPkmStore, MIGRATIONS};
+use polyproto::certstore::AsyncCertStore;
+use polyproto::timestamp::Timestamp;
+use polyproto::types::spki::AlgorithmIdentifierOwned;
+use polyproto::types::x509_cert::SerialNumber;
+use polyproto::types::{EncryptedPkm, FederationId, PrivateKeyInfo};
+use spki::ObjectIdentifier;
+use sqlx::any::AnyPoolOptions;
+use sqlx::AnyPool;
+
+use super::{cert, Cert};
+use crate::common::*;
+
+async fn pool() -> AnyPool {
+    sqlx::any::install_default_drivers();
+    // Every connection to `sqlite::memory:` opens its own database.
+    AnyPoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+}
+
+fn encrypted_pkm(serial: u8) -> EncryptedPkm {
+    EncryptedPkm {
+        serial_number: SerialNumber::new(&[serial]).unwrap(),
+        key_data: PrivateKeyInfo {
+            algorithm: AlgorithmIdentifierOwned::new(
+                ObjectIdentifier::new("1.3.101.112").unwrap(),
+                None,
+            ),
+            encrypted_private_key_bitstring: BitString::from_bytes(&[serial; 48]).unwrap(),
+        },
+        encryption_algorithm: AlgorithmIdentifierOwned::new(
+            ObjectIdentifier::new("2.16.840.1.101.3.4.1.46").unwrap(),
+            None,
+        ),
+    }
+}
+
+#[tokio::test]
+async fn sql_migrations() {
+    init_logger();
+    let pool = pool().await;
+    assert_eq!(schema_version(&pool).await.ok(), None);
+    assert_eq!(migrate(&pool).await.unwrap(), MIGRATIONS.len());
+    assert_eq!(migrate(&pool).await.unwrap(), 0);
+    assert_eq!(
+        schema_version(&pool).await.unwrap(),
+        MIGRATIONS.last().unwrap().version
+    );
+}
+
+#[tokio::test]
+async fn sql_cert_store() {
+    init_logger();
+    let mut store = SqlCertStore::new(pool().await).await.unwrap();
+    let flori_1 = cert("flori", 1, 1000);
+    let flori_2 = cert("flori", 2, 100);
+    let bitfl0wer = cert("bitfl0wer", 3, 1000);
+    for cert in [&flori_1, &flori_2, &bitfl0wer] {
+        assert_eq!(store.insert(cert).await.unwrap(), cert.cert_id().unwrap());
+    }
+    let replacement = cert("flori", 1, 1000);
+    store.insert(&replacement).await.unwrap();
+    let flori_1 = replacement;
+    assert_eq!(
+        store.get(&flori_1.cert_id().unwrap()).await.unwrap(),
+        Some(flori_1.clone())
+    );
+    assert_eq!(
+        AsyncCertStore::<Ed25519Signature, Ed25519PublicKey>::list(&store)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+    assert_eq!(
+        store
+            .get_by_serial(&Uint::new(&[3]).unwrap())
+            .await
+            .unwrap(),
+        vec![bitfl0wer.clone()]
+    );
+
+    let flori = FederationId::new("flori@polyphony.chat").unwrap();
+    let valid: Vec<Cert> = store
+        .get_valid_for(&flori, Timestamp::from(50))
+        .await
+        .unwrap();
+    assert_eq!(valid.len(), 2);
+    assert_eq!(
+        store
+            .get_valid_for(&flori, Timestamp::from(500))
+            .await
+            .unwrap(),
+        vec![flori_1.clone()]
+    );
+
+    let cert_id = flori_1.cert_id().unwrap();
+    assert!(
+        AsyncCertStore::<Ed25519Signature, Ed25519PublicKey>::delete(&mut store, &cert_id)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !AsyncCertStore::<Ed25519Signature, Ed25519PublicKey>::delete(&mut store, &cert_id)
+            .await
+            .unwrap()
+    );
+    let missing: Option<Cert> = store.get(&cert_id).await.unwrap();
+    assert_eq!(missing, None);
+}
+
+#[tokio::test]
+async fn sql_pkm_store() {
+    init_logger();
+    let pool = pool().await;
+    let store = SqlPkmStore::new(pool.clone()).await.unwrap();
+    // Both stores share the schema of the same database.
+    SqlCertStore::new(pool).await.unwrap();
+    let flori = FederationId::new("flori@polyphony.chat").unwrap();
+    let bitfl0wer = FederationId::new("bitfl0wer@polyphony.chat").unwrap();
+
+    store.put(&flori, &encrypted_pkm(2)).await.unwrap();
+    store.put(&flori, &encrypted_pkm(1)).await.unwrap();
+    store.put(&bitfl0wer, &encrypted_pkm(1)).await.unwrap();
+    store.put(&flori, &encrypted_pkm(1)).await.unwrap();
+    assert_eq!(
+        store.list(&flori).await.unwrap(),
+        vec![encrypted_pkm(1), encrypted_pkm(2)]
+    );
+    let serial = SerialNumber::new(&[1]).unwrap();
+    assert_eq!(
+        store.get(&bitfl0wer, &serial).await.unwrap(),
+        Some(encrypted_pkm(1))
+    );
+    assert!(store.delete(&flori, &serial).await.unwrap());
+    assert!(!store.delete(&flori, &serial).await.unwrap());
+    assert_eq!(store.get(&flori, &serial).await.unwrap(), None);
+    assert_eq!(store.list(&bitfl0wer).await.unwrap().len(), 1);
+}
@@ -11,9 +11,10 @@ use ed25519_dalek::{Signature as Ed25519DalekSignature, SigningKey, VerifyingKey
 use polyproto::certs::capabilities::Capabilities;
 use polyproto::certs::idcert::IdCert;
 use polyproto::certs::idcsr::IdCsr;
-use polyproto::certs::PublicKeyInfo;
+use polyproto::certs::{actor_name, PublicKeyInfo, SessionId};
 use polyproto::errors::composite::ConversionError;
-use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::key::{KeyGen, PrivateKey, PublicKey};
+use polyproto::rand_core::CryptoRngCore;
 use polyproto::signature::Signature;
 use polyproto::Name;
 use rand::rngs::OsRng;
@@ -28,10 +29,11 @@ pub fn init_logger() {
 }
 
 pub fn actor_subject(cn: &str) -> Name {
-    Name::from_str(&format!(
-        "CN={},DC=polyphony,DC=chat,UID={}@polyphony.chat,uniqueIdentifier=client1",
-        cn, cn
-    ))
+    actor_name(
+        cn,
+        "polyphony.chat",
+        &SessionId::from_str("client1").unwrap(),
+    )
     .unwrap()
 }
 
@@ -76,7 +78,7 @@ pub fn actor_csr(
     IdCsr::new(
         &actor_subject(cn),
         priv_key,
-        &Capabilities::default_actor(),
+        &Capabilities::actor_default(),
         Some(polyproto::certs::Target::Actor),
     )
     .unwrap()
@@ -105,12 +107,56 @@ pub fn home_server_csr(priv_key: &Ed25519PrivateKey) -> IdCsr<Ed25519Signature,
     IdCsr::new(
         &home_server_subject(),
         priv_key,
-        &Capabilities::default_home_server(),
+        &Capabilities::home_server_default(),
         Some(polyproto::certs::Target::HomeServer),
     )
     .unwrap()
 }
 
+/// Issues an actor certificate for `cn`, holding `priv_key`, signed by `issuer_key`.
+pub fn actor_cert(
+    cn: &str,
+    priv_key: &Ed25519PrivateKey,
+    issuer_key: &Ed25519PrivateKey,
+    serial: u8,
+    validity: Validity,
+) -> IdCert<Ed25519Signature, Ed25519PublicKey> {
+    IdCert::from_actor_csr(
+        actor_csr(cn, priv_key),
+        issuer_key,
+        Uint::new(&[serial]).unwrap(),
+        home_server_subject(),
+        validity,
+    )
+    .unwrap()
+}
+
+/// Issues a home server certificate holding `priv_key`, signed by `issuer_key`, with the given
+/// path length constraint. Self-signed, if both keys are the same.
+pub fn home_server_cert(
+    priv_key: &Ed25519PrivateKey,
+    issuer_key: &Ed25519PrivateKey,
+    path_length: Option<u64>,
+) -> IdCert<Ed25519Signature, Ed25519PublicKey> {
+    let mut capabilities = Capabilities::home_server_default();
+    capabilities.basic_constraints.path_length = path_length;
+    let csr = IdCsr::new(
+        &home_server_subject(),
+        priv_key,
+        &capabilities,
+        Some(polyproto::certs::Target::HomeServer),
+    )
+    .unwrap();
+    IdCert::from_ca_csr(
+        csr,
+        issuer_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap()
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) struct Ed25519Signature {
     pub(crate) signature: Ed25519DalekSignature,
@@ -206,6 +252,17 @@ impl Ed25519PrivateKey {
     }
 }
 
+// Implementing `KeyGen` allows generic code to create key pairs of our type.
+impl KeyGen<Ed25519Signature> for Ed25519PrivateKey {
+    fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        let key = SigningKey::generate(rng);
+        let public_key = Ed25519PublicKey {
+            key: key.verifying_key(),
+        };
+        Self { public_key, key }
+    }
+}
+
 // Same thing as above for the public key type.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Ed25519PublicKey {
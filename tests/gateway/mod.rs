@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::cache::CacheGeneration;
+use polyproto::certs::idcert::IdCert;
+use polyproto::errors::GatewayEventError;
+use polyproto::gateway::{EventVerifier, GatewayEvent, ReceivedEvent};
+use polyproto::timestamp::Timestamp;
+use serde_json::json;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verify_signed_gateway_events() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let time = Timestamp::from_unix_seconds(100);
+    let mut verifier = EventVerifier::new()
+        .with_home_server_cert(home_server_cert.clone())
+        .with_signed_event("identity_change");
+
+    // Events which need not be signed are delivered as they are.
+    let heartbeat = GatewayEvent::new("heartbeat", json!({ "s": 1 }));
+    assert!(matches!(
+        verifier.verify(heartbeat, time),
+        ReceivedEvent::Unsigned(_)
+    ));
+
+    // Signed by the home server.
+    let invalidation = GatewayEvent::signed(
+        "actor_certificate_invalidation",
+        json!({ "serial": "02", "invalidSince": 100 }),
+        home_server_cert.cert_id().unwrap(),
+        &home_server_key,
+    )
+    .unwrap();
+    let received = verifier.verify(invalidation.clone(), time);
+    match &received {
+        ReceivedEvent::Verified { signer, .. } => assert_eq!(**signer, home_server_cert),
+        other => panic!("Expected a verified event, got {:?}", other),
+    }
+
+    // Signatures survive a round trip through JSON.
+    let json = serde_json::to_string(&invalidation).unwrap();
+    let decoded: GatewayEvent = serde_json::from_str(&json).unwrap();
+    assert!(verifier.verify(decoded, time).is_deliverable());
+
+    // Signed by an actor, whose certificate is not cached yet.
+    let identity_change = GatewayEvent::signed(
+        "identity_change",
+        json!({ "displayName": "flori" }),
+        actor_cert.cert_id().unwrap(),
+        &actor_key,
+    )
+    .unwrap();
+    assert!(matches!(
+        verifier.verify(identity_change.clone(), time),
+        ReceivedEvent::Unverifiable {
+            error: GatewayEventError::UnknownSigner(_),
+            ..
+        }
+    ));
+    verifier.cache_cert(actor_cert.clone()).unwrap();
+    assert!(verifier
+        .verify(identity_change.clone(), time)
+        .is_deliverable());
+
+    // Expired signer certificate.
+    assert!(matches!(
+        verifier.verify(identity_change.clone(), Timestamp::from_unix_seconds(5000)),
+        ReceivedEvent::Unverifiable {
+            error: GatewayEventError::UntrustedSigner(_),
+            ..
+        }
+    ));
+
+    // Tampered data.
+    let mut tampered = identity_change.clone();
+    tampered.data = json!({ "displayName": "mallory" });
+    assert_eq!(
+        verifier.verify(tampered, time).into_result(),
+        Err(GatewayEventError::BadSignature)
+    );
+
+    // Missing signature.
+    let mut unsigned = identity_change.clone();
+    unsigned.signature = None;
+    assert_eq!(
+        verifier.verify(unsigned, time).into_result(),
+        Err(GatewayEventError::MissingSignature)
+    );
+
+    // Changing the generation evicts the cached actor certificate, but keeps the trusted home
+    // server certificate.
+    verifier.set_generation(CacheGeneration::new().with_trust_store(1));
+    assert!(!verifier.verify(identity_change, time).is_deliverable());
+    assert!(verifier.verify(invalidation, time).is_deliverable());
+}
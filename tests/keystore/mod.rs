@@ -0,0 +1,81 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use polyproto::keystore::file::FileKeyStore;
+use polyproto::keystore::{KeyId, KeyStore};
+use polyproto::types::FederationId;
+
+use crate::common::*;
+
+fn key_id(federation_id: &str, serial: u8) -> KeyId {
+    KeyId::new(
+        FederationId::new(federation_id).unwrap(),
+        Uint::new(&[serial]).unwrap(),
+    )
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn key_id_roundtrip() {
+    let id = key_id("flori@polyphony.chat", 8);
+    assert_eq!(id.to_string(), "flori@polyphony.chat.08");
+    assert_eq!(KeyId::from_str(&id.to_string()).unwrap(), id);
+    assert!(KeyId::from_str("flori@polyphony.chat").is_err());
+    assert!(KeyId::from_str("not a federation id.08").is_err());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn file_key_store() {
+    init_logger();
+    let directory = std::env::temp_dir().join(format!(
+        "polyproto-keystore-{}-{}",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    let mut store = FileKeyStore::open(&directory).unwrap();
+    let flori_1 = key_id("flori@polyphony.chat", 1);
+    let flori_2 = key_id("flori@polyphony.chat", 2);
+    let bitfl0wer = key_id("bitfl0wer@polyphony.chat", 1);
+    assert!(store.list().unwrap().is_empty());
+    assert_eq!(store.get(&flori_1).unwrap(), None);
+
+    store.put(&flori_2, b"second key").unwrap();
+    store.put(&flori_1, b"first key").unwrap();
+    store.put(&bitfl0wer, b"another key").unwrap();
+    store.put(&flori_1, b"replaced key").unwrap();
+    std::fs::write(directory.join("README.txt"), b"unrelated").unwrap();
+    assert_eq!(
+        store.get(&flori_1).unwrap().unwrap().as_slice(),
+        b"replaced key"
+    );
+    assert_eq!(
+        store.list().unwrap(),
+        vec![bitfl0wer.clone(), flori_1.clone(), flori_2.clone()]
+    );
+    assert_eq!(
+        store.list_for(&flori_1.federation_id).unwrap(),
+        vec![flori_1.clone(), flori_2.clone()]
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(store.path_of(&flori_1))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    assert!(store.delete(&flori_1).unwrap());
+    assert!(!store.delete(&flori_1).unwrap());
+    assert_eq!(store.get(&flori_1).unwrap(), None);
+    // Reopening the store sees the same keys.
+    let store = FileKeyStore::open(&directory).unwrap();
+    assert_eq!(store.list().unwrap(), vec![bitfl0wer, flori_2]);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -2,8 +2,24 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+#[cfg(feature = "alloc-stats")]
+pub(crate) mod alloc_budget;
 pub(crate) mod api;
+pub(crate) mod cache;
 pub(crate) mod certs;
+#[cfg(feature = "types")]
+pub(crate) mod certstore;
 pub(crate) mod common;
+#[cfg(feature = "serde")]
+pub(crate) mod gateway;
+pub(crate) mod keystore;
+#[cfg(feature = "proptest")]
+pub(crate) mod properties;
+pub(crate) mod registry;
+pub(crate) mod rules;
+pub(crate) mod signature;
+pub(crate) mod verifier;
+#[cfg(feature = "webhooks")]
+pub(crate) mod webhooks;
 
 use polyproto::Constrained;
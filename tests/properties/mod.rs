@@ -0,0 +1,89 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::pem::LineEnding;
+use ed25519_dalek::SigningKey;
+use polyproto::arbitrary::{id_cert, DummyPublicKey, DummySignature};
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::certs::Target;
+use polyproto::types::{FederationId, SessionId};
+use polyproto::Constrained;
+use proptest::prelude::*;
+use x509_cert::ext::Extensions;
+
+use crate::common::*;
+
+type DummyCert = IdCert<DummySignature, DummyPublicKey>;
+type DummyCsr = IdCsr<DummySignature, DummyPublicKey>;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn federation_id_round_trip(federation_id in any::<FederationId>()) {
+        prop_assert_eq!(FederationId::new(&federation_id.to_string()).unwrap(), federation_id);
+    }
+
+    #[test]
+    fn session_id_is_valid(session_id in any::<SessionId>()) {
+        session_id.validate(None).unwrap();
+    }
+
+    #[test]
+    fn capabilities_round_trip(capabilities in any::<Capabilities>()) {
+        let target = Target::detect(&capabilities);
+        capabilities.validate(Some(target)).unwrap();
+        let extensions = Extensions::try_from(capabilities.clone()).unwrap();
+        prop_assert_eq!(Capabilities::try_from(extensions).unwrap(), capabilities);
+    }
+
+    #[test]
+    fn id_csr_round_trip(csr in any::<DummyCsr>()) {
+        let target = Target::detect(&csr.inner_csr.capabilities);
+        let der = csr.to_der().unwrap();
+        prop_assert_eq!(&DummyCsr::from_der(&der, Some(target)).unwrap(), &csr);
+        prop_assert!(csr.is_canonical(&der));
+        let pem = csr.to_pem(LineEnding::LF).unwrap();
+        prop_assert_eq!(DummyCsr::from_pem(&pem, Some(target)).unwrap(), csr);
+    }
+
+    #[test]
+    fn id_cert_round_trip(cert in any::<DummyCert>()) {
+        let der = cert.to_der().unwrap();
+        let decoded = DummyCert::from_der_unchecked(&der).unwrap();
+        prop_assert_eq!(&decoded, &cert);
+        prop_assert!(cert.is_canonical(&der));
+        decoded.validate(Some(decoded.infer_target().unwrap())).unwrap();
+        let pem = cert.to_pem(LineEnding::LF).unwrap();
+        prop_assert_eq!(DummyCert::from_pem_unchecked(&pem).unwrap(), cert);
+    }
+}
+
+fn ed25519_key() -> impl Strategy<Value = Ed25519PrivateKey> + Clone {
+    any::<[u8; 32]>().prop_map(|bytes| {
+        let key = SigningKey::from_bytes(&bytes);
+        Ed25519PrivateKey {
+            public_key: Ed25519PublicKey {
+                key: key.verifying_key(),
+            },
+            key,
+        }
+    })
+}
+
+proptest! {
+    // Real signatures are slow to create, so fewer cases are run with them.
+    #![proptest_config(ProptestConfig::with_cases(8))]
+
+    #[test]
+    fn id_cert_round_trip_ed25519(cert in id_cert(Target::Actor, ed25519_key())) {
+        let der = cert.to_der().unwrap();
+        prop_assert_eq!(
+            IdCert::<Ed25519Signature, Ed25519PublicKey>::from_der_unchecked(&der).unwrap(),
+            cert
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use der::asn1::Uint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::errors::{ConversionError, InvalidCert, PublicKeyError};
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::registry::AlgorithmRegistry;
+use polyproto::signature::Signature;
+
+use crate::common::*;
+
+fn registry() -> AlgorithmRegistry {
+    AlgorithmRegistry::new().with_algorithm::<Ed25519Signature, Ed25519PublicKey>("Ed25519")
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verify_certificates_of_runtime_algorithm() {
+    init_logger();
+    let registry = registry();
+    let oid = Ed25519Signature::algorithm_identifier().oid;
+    assert_eq!(registry.get(&oid).unwrap().name, "Ed25519");
+
+    let home_server_key = gen_priv_key();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    registry
+        .verify_self_signed(&home_server_cert.to_der().unwrap())
+        .unwrap();
+
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor_der = actor_cert.to_der().unwrap();
+    let issuer = home_server_key.pubkey().public_key_info();
+    registry.verify_certificate(&actor_der, &issuer).unwrap();
+
+    // Signed by another key.
+    let other_issuer = gen_priv_key().pubkey().public_key_info();
+    assert_eq!(
+        registry.verify_certificate(&actor_der, &other_issuer),
+        Err(ConversionError::InvalidCert(InvalidCert::PublicKeyError(
+            PublicKeyError::BadSignature
+        )))
+    );
+    // Not a certificate.
+    assert!(registry.verify_certificate(&[0u8; 16], &issuer).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn unknown_algorithm() {
+    let mut registry = registry();
+    let oid = Ed25519Signature::algorithm_identifier().oid;
+    assert!(registry.unregister(&oid).is_some());
+    assert!(!registry.contains(&oid));
+    let key = gen_priv_key();
+    assert_eq!(
+        registry.verify(
+            &Ed25519Signature::algorithm_identifier(),
+            &key.pubkey().public_key_info(),
+            &[0u8; 64],
+            b"polyproto",
+        ),
+        Err(PublicKeyError::UnsupportedAlgorithm(oid))
+    );
+}
+
+#[cfg(feature = "ed25519")]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn builtin_algorithms() {
+    let registry = AlgorithmRegistry::with_builtin_algorithms();
+    assert!(registry.contains(&Ed25519Signature::algorithm_identifier().oid));
+    let home_server_cert = home_server_id_cert();
+    registry
+        .verify_self_signed(&home_server_cert.to_der().unwrap())
+        .unwrap();
+}
@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use der::asn1::Uint;
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcerttbs::IdCertTbs;
+use polyproto::certs::Target;
+use polyproto::errors::ConstraintError;
+use polyproto::key::PrivateKey;
+use polyproto::rules::{HasRules, Rule, RuleStatus};
+use polyproto::verifier::Severity;
+use polyproto::{Constrained, Name};
+
+use crate::common::*;
+
+type Tbs = IdCertTbs<Ed25519Signature, Ed25519PublicKey>;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn id_cert_tbs_report() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let mut tbs = Tbs::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        Uint::new(&[1]).unwrap(),
+        home_server_key.algorithm_identifier(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let rules = Tbs::rules();
+    assert!(rules.report(&tbs, Some(Target::Actor)).passed());
+
+    tbs.capabilities = Capabilities::home_server_default();
+    tbs.issuer = Name::from_str("DC=example,DC=com").unwrap();
+    // validate() stops at the first violated rule, while the report lists all of them.
+    assert!(tbs.validate(Some(Target::Actor)).is_err());
+    let report = rules.report(&tbs, Some(Target::Actor));
+    assert!(report.failed("idcert.actor-not-ca"));
+    assert!(report.failed("idcert.domain-components"));
+    assert!(!report.failed("idcert.issuer"));
+    assert_eq!(report.failures().count(), 2);
+    assert_eq!(
+        report.outcome("idcert.home-server-ca").unwrap().status,
+        RuleStatus::NotApplicable
+    );
+    assert!(report
+        .to_string()
+        .contains("[FAIL] idcert.actor-not-ca (PP1006)"));
+
+    let listing = rules.to_string();
+    for rule in rules.iter() {
+        assert!(listing.contains(rule.id));
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn extend_rule_set() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let tbs = Tbs::from_actor_csr(
+        actor_csr("flori", &gen_priv_key()),
+        Uint::new(&[1]).unwrap(),
+        home_server_key.algorithm_identifier(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let rules = Tbs::rules().with_rule(Rule::new(
+        "deployment.odd-serial",
+        "X0001",
+        "Serial numbers must be odd",
+        |tbs: &Tbs, _| match tbs.serial_number.as_bytes().last() {
+            Some(byte) if byte % 2 == 1 => Ok(()),
+            _ => Err(ConstraintError::Malformed(None)),
+        },
+    ));
+    assert_eq!(rules.len(), Tbs::rules().len() + 1);
+    assert!(rules.check(&tbs, Some(Target::Actor)).is_ok());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn validate_report_lists_all_violations() {
+    init_logger();
+    let mut cert = actor_id_cert("flori");
+    assert!(cert.validate_report(Some(Target::Actor)).passed());
+
+    cert.id_cert_tbs.capabilities = Capabilities::home_server_default();
+    cert.id_cert_tbs.issuer = Name::from_str("DC=example,DC=com").unwrap();
+    let report = cert.validate_report(Some(Target::Actor));
+    assert!(!report.passed());
+    let failures = report.failures().collect::<Vec<_>>();
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0].id, "idcert.domain-components");
+    assert_eq!(failures[0].field, Some("subject"));
+    assert_eq!(failures[1].id, "idcert.actor-not-ca");
+    assert_eq!(failures[1].field, Some("capabilities"));
+    assert!(failures
+        .iter()
+        .all(|failure| failure.severity == Severity::Error));
+    assert!(report
+        .to_string()
+        .contains("[FAIL] idcert.actor-not-ca (PP1006) in `capabilities`: "));
+
+    let mut csr = actor_csr("flori", &gen_priv_key());
+    assert!(csr.validate_report(Some(Target::Actor)).passed());
+    csr.inner_csr.capabilities = Capabilities::home_server_default();
+    let report = csr.validate_report(Some(Target::Actor));
+    assert!(report.failed("idcsr.actor-not-ca"));
+    // The signature no longer covers the modified capabilities.
+    assert!(report.failed("idcsr.signature"));
+    assert_eq!(report.errors().count(), 2);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn less_severe_rules_do_not_fail_validation() {
+    init_logger();
+    let tbs = actor_id_cert("flori").id_cert_tbs;
+    let rules = Tbs::rules().with_rule(
+        Rule::new(
+            "deployment.no-claims",
+            "X0002",
+            "Certificates should carry custom claims",
+            |tbs: &Tbs, _| match tbs.claims.is_empty() {
+                true => Err(ConstraintError::Malformed(None)),
+                false => Ok(()),
+            },
+        )
+        .severity(Severity::Warning)
+        .field("claims"),
+    );
+    assert!(rules.check(&tbs, Some(Target::Actor)).is_ok());
+    let report = rules.report(&tbs, Some(Target::Actor));
+    assert!(report.passed());
+    assert!(report.failed("deployment.no-claims"));
+    assert_eq!(report.errors().count(), 0);
+    assert!(report
+        .to_string()
+        .contains("[WARN] deployment.no-claims (X0002) in `claims`"));
+    assert!(rules
+        .to_string()
+        .contains("| `deployment.no-claims` | `X0002` | always | warning | `claims` |"));
+}
@@ -0,0 +1,67 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::Write;
+
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::signature::DetachedSignature;
+
+use crate::common::*;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn stream_signature() {
+    init_logger();
+    let key = gen_priv_key();
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+    let mut signer = key.sign_stream();
+    for chunk in data.chunks(4096) {
+        signer.write_all(chunk).unwrap();
+    }
+    assert_eq!(signer.content_length(), 100_000);
+    let signature: DetachedSignature<Ed25519Signature> = signer.finalize();
+    assert_eq!(signature, DetachedSignature::sign(&key, &data));
+
+    // Chunk boundaries do not matter.
+    let mut verifier = key.pubkey().verify_stream();
+    for chunk in data.chunks(1000) {
+        verifier.update(chunk);
+    }
+    verifier.finalize(&signature).unwrap();
+    signature.verify(key.pubkey(), &data).unwrap();
+
+    // Truncated or modified data.
+    assert!(signature.verify(key.pubkey(), &data[..99_999]).is_err());
+    let mut modified = data.clone();
+    modified[50_000] ^= 1;
+    assert!(signature.verify(key.pubkey(), &modified).is_err());
+    // Another key.
+    assert!(signature.verify(gen_priv_key().pubkey(), &data).is_err());
+    // A forged digest does not match the signature.
+    let mut forged = DetachedSignature::sign(&key, b"other data");
+    forged.signature = signature.signature.clone();
+    assert!(forged.verify(key.pubkey(), b"other data").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn detached_signature_serde() {
+    let key = gen_priv_key();
+    let signature = DetachedSignature::sign(&key, b"RawR resource");
+    let json = serde_json::to_value(&signature).unwrap();
+    assert_eq!(json["content_length"], 13);
+    assert_eq!(json["digest"].as_str().unwrap().len(), 64);
+    let decoded: DetachedSignature<Ed25519Signature> =
+        serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(decoded, signature);
+    decoded.verify(key.pubkey(), b"RawR resource").unwrap();
+
+    let mut other_algorithm = json;
+    other_algorithm["algorithm"] = "1.2.840.10045.4.3.2".into();
+    assert!(
+        serde_json::from_value::<DetachedSignature<Ed25519Signature>>(other_algorithm).is_err()
+    );
+}
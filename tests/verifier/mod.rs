@@ -0,0 +1,242 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod queue;
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use polyproto::certs::idcert::IdCert;
+use polyproto::errors::InvalidCert;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::verifier::{
+    BudgetResource, Severity, ValidationWarning, VerificationBudget, Verifier, WarningPolicy,
+};
+
+use crate::common::*;
+
+fn chain() -> Vec<IdCert<Ed25519Signature, Ed25519PublicKey>> {
+    let home_server_key = gen_priv_key();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    vec![actor_cert, home_server_cert]
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn verify_chain_within_budget() {
+    init_logger();
+    let mut verifier = Verifier::default();
+    verifier
+        .verify_chain(&chain(), Timestamp::from_unix_seconds(100))
+        .unwrap();
+    assert_eq!(verifier.signature_verifications(), 2);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn signature_budget_exceeded() {
+    init_logger();
+    let mut verifier = Verifier::new(VerificationBudget {
+        max_signature_verifications: 1,
+        ..Default::default()
+    });
+    assert_eq!(
+        verifier.verify_chain(&chain(), Timestamp::from_unix_seconds(100)),
+        Err(InvalidCert::BudgetExceeded(
+            BudgetResource::SignatureVerifications
+        ))
+    );
+    verifier.reset();
+    let chain = chain();
+    verifier
+        .verify_home_server(&chain[1], Timestamp::from_unix_seconds(100))
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn chain_length_budget_exceeded() {
+    init_logger();
+    let mut verifier = Verifier::new(VerificationBudget {
+        max_chain_length: 1,
+        ..Default::default()
+    });
+    assert_eq!(
+        verifier.verify_chain(&chain(), Timestamp::from_unix_seconds(100)),
+        Err(InvalidCert::BudgetExceeded(BudgetResource::ChainLength))
+    );
+    assert_eq!(verifier.signature_verifications(), 0);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn chain_with_wrong_issuer_key() {
+    init_logger();
+    let other_root = chain().pop().unwrap();
+    let mut forged_chain = chain();
+    forged_chain[1] = other_root;
+    let mut verifier = Verifier::default();
+    assert!(verifier
+        .verify_chain(&forged_chain, Timestamp::from_unix_seconds(100))
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn chain_issued_by_actor() {
+    init_logger();
+    let home_server_key = gen_priv_key();
+    let home_server_cert = IdCert::from_ca_csr(
+        home_server_csr(&home_server_key),
+        &home_server_key,
+        Uint::new(&[1]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    let actor_key = gen_priv_key();
+    let actor_cert = IdCert::from_actor_csr(
+        actor_csr("flori", &actor_key),
+        &home_server_key,
+        Uint::new(&[2]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    // An actor certificate is not a CA, and must not be able to issue other certificates.
+    let mut issued_by_actor = IdCert::from_actor_csr(
+        actor_csr("mallory", &gen_priv_key()),
+        &home_server_key,
+        Uint::new(&[3]).unwrap(),
+        home_server_subject(),
+        default_validity(),
+    )
+    .unwrap();
+    issued_by_actor.id_cert_tbs.issuer = actor_subject("flori");
+    issued_by_actor.signature = actor_key.sign(&issued_by_actor.id_cert_tbs.to_der().unwrap());
+    let mut verifier = Verifier::default();
+    assert!(matches!(
+        verifier.verify_chain(
+            &[issued_by_actor, actor_cert, home_server_cert.clone()],
+            Timestamp::from_unix_seconds(100)
+        ),
+        Err(InvalidCert::InvalidProperties(_))
+    ));
+    assert!(matches!(
+        verifier.verify_chain(&[home_server_cert], Timestamp::from_unix_seconds(100)),
+        Err(InvalidCert::InvalidProperties(_))
+    ));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn crl_entry_budget() {
+    let mut verifier = Verifier::new(VerificationBudget {
+        max_crl_entries: 10,
+        ..Default::default()
+    });
+    verifier.charge_crl_entries(6).unwrap();
+    assert_eq!(
+        verifier.charge_crl_entries(6),
+        Err(InvalidCert::BudgetExceeded(BudgetResource::CrlEntries))
+    );
+    assert_eq!(verifier.crl_entries_examined(), 6);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn chain_expiring_soon_warns() {
+    init_logger();
+    let mut verifier = Verifier::default();
+    let warnings = verifier
+        .verify_chain_with_warnings(&chain(), Timestamp::from_unix_seconds(100))
+        .unwrap();
+    let expected = ValidationWarning::ExpiresSoon {
+        remaining: Duration::from_secs(900),
+    };
+    assert_eq!(warnings, vec![(0, expected), (1, expected)]);
+    assert_eq!(expected.severity(), Severity::Warning);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn warning_policy_threshold() {
+    init_logger();
+    let chain = chain();
+    let mut verifier = Verifier::default().with_warning_policy(WarningPolicy {
+        expiry_threshold: Duration::from_secs(60),
+    });
+    assert!(verifier
+        .verify_home_server_with_warnings(&chain[1], Timestamp::from_unix_seconds(100))
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        verifier
+            .verify_actor_with_warnings(
+                &chain[0],
+                Timestamp::from_unix_seconds(950),
+                &chain[1].id_cert_tbs.subject_public_key
+            )
+            .unwrap(),
+        vec![ValidationWarning::ExpiresSoon {
+            remaining: Duration::from_secs(50)
+        }]
+    );
+    assert!(verifier
+        .verify_home_server_with_warnings(&chain[1], Timestamp::from_unix_seconds(2000))
+        .is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn clock_skew_tolerance() {
+    init_logger();
+    let chain = chain();
+    let actor_cert = &chain[0];
+    // The default validity period lasts from 10 until 1000 seconds after the Unix epoch.
+    let tolerance = Duration::from_secs(5);
+    for (seconds, valid) in [(4, false), (5, true), (1005, true), (1006, false)] {
+        assert_eq!(
+            actor_cert.valid_at_with_tolerance(seconds, tolerance),
+            valid,
+            "{seconds}"
+        );
+    }
+    assert!(actor_cert.valid_at_with_tolerance(u64::MAX, Duration::MAX));
+
+    let mut verifier = Verifier::default();
+    assert_eq!(verifier.clock_skew_tolerance(), Duration::ZERO);
+    assert_eq!(
+        verifier.verify_chain(&chain, Timestamp::from_unix_seconds(5)),
+        Err(InvalidCert::InvalidValidity)
+    );
+    let mut verifier = Verifier::default().with_clock_skew_tolerance(tolerance);
+    verifier
+        .verify_chain(&chain, Timestamp::from_unix_seconds(5))
+        .unwrap();
+    verifier
+        .verify_chain(&chain, Timestamp::from_unix_seconds(1005))
+        .unwrap();
+    assert_eq!(
+        verifier.verify_chain(&chain, Timestamp::from_unix_seconds(1006)),
+        Err(InvalidCert::InvalidValidity)
+    );
+}
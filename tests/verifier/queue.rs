@@ -0,0 +1,214 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use polyproto::errors::{InvalidCert, QueueError};
+use polyproto::timestamp::Timestamp;
+use polyproto::verifier::queue::{Priority, QueueConfig, RateLimit, VerificationQueue};
+use polyproto::verifier::{BudgetResource, VerificationBudget};
+
+use super::chain;
+use crate::common::*;
+
+const NOW: Timestamp = Timestamp::from_unix_seconds(100);
+
+fn unlimited() -> QueueConfig {
+    QueueConfig {
+        rate_limit: None,
+        ..Default::default()
+    }
+}
+
+/// Submits a job recording `label` in `log` when it is run.
+fn submit_logged(
+    queue: &VerificationQueue<&'static str>,
+    log: &Arc<Mutex<Vec<String>>>,
+    peer: &'static str,
+    priority: Priority,
+    label: &str,
+) {
+    let log = log.clone();
+    let label = label.to_string();
+    queue
+        .submit(peer, priority, NOW, move |_| {
+            log.lock().unwrap().push(label);
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn peers_take_turns() {
+    init_logger();
+    let queue = VerificationQueue::new(unlimited());
+    let log = Arc::new(Mutex::new(Vec::new()));
+    for index in 0..4 {
+        submit_logged(
+            &queue,
+            &log,
+            "flood",
+            Priority::Interactive,
+            &format!("flood{}", index),
+        );
+    }
+    submit_logged(&queue, &log, "alice", Priority::Interactive, "alice0");
+    submit_logged(&queue, &log, "bob", Priority::Interactive, "bob0");
+    submit_logged(&queue, &log, "alice", Priority::Interactive, "alice1");
+    assert_eq!(queue.pending_for(&"flood"), 4);
+    assert_eq!(queue.run_pending(), 7);
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["flood0", "alice0", "bob0", "flood1", "alice1", "flood2", "flood3"]
+    );
+    assert_eq!(queue.metrics().completed, 7);
+    assert_eq!(queue.pending(), 0);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn interactive_before_backfill() {
+    init_logger();
+    let queue = VerificationQueue::new(QueueConfig {
+        interactive_burst: 2,
+        ..unlimited()
+    });
+    let log = Arc::new(Mutex::new(Vec::new()));
+    submit_logged(&queue, &log, "alice", Priority::Backfill, "backfill0");
+    submit_logged(&queue, &log, "alice", Priority::Backfill, "backfill1");
+    for index in 0..5 {
+        submit_logged(
+            &queue,
+            &log,
+            "bob",
+            Priority::Interactive,
+            &format!("interactive{}", index),
+        );
+    }
+    assert_eq!(queue.metrics().pending_backfill, 2);
+    assert_eq!(queue.metrics().pending_interactive, 5);
+    queue.run_pending();
+    // Backfill gets a turn after every two interactive jobs, so it is not starved.
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            "interactive0",
+            "interactive1",
+            "backfill0",
+            "interactive2",
+            "interactive3",
+            "backfill1",
+            "interactive4"
+        ]
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn rate_limits_and_bounds() {
+    init_logger();
+    let queue = VerificationQueue::new(QueueConfig {
+        max_pending: 4,
+        max_pending_per_peer: 3,
+        rate_limit: Some(RateLimit {
+            burst: 2,
+            per_second: 1,
+        }),
+        ..Default::default()
+    });
+    let submit = |peer: &'static str, time: Timestamp| {
+        queue.submit(peer, Priority::Interactive, time, |_| Ok(()))
+    };
+    submit("flood", NOW).unwrap();
+    submit("flood", NOW).unwrap();
+    assert_eq!(
+        submit("flood", NOW).unwrap_err(),
+        QueueError::RateLimited(Duration::from_secs(1))
+    );
+    // Other peers are not affected by the rate limit of a peer.
+    submit("alice", NOW).unwrap();
+    let later = Timestamp::from_unix_seconds(101);
+    submit("flood", later).unwrap();
+    assert_eq!(
+        submit("flood", Timestamp::from_unix_seconds(110)).unwrap_err(),
+        QueueError::PeerQueueFull
+    );
+    assert_eq!(submit("bob", later).unwrap_err(), QueueError::QueueFull);
+    let metrics = queue.metrics();
+    assert_eq!(metrics.submitted, 4);
+    assert_eq!(metrics.rate_limited, 1);
+    assert_eq!(metrics.rejected_full, 2);
+
+    queue.run_pending();
+    // The rate limit of "flood" has not recovered yet, but that of "alice" has.
+    assert_eq!(queue.prune(later), 1);
+    assert_eq!(queue.prune(Timestamp::from_unix_seconds(110)), 1);
+}
+
+#[tokio::test]
+async fn run_jobs_with_budget() {
+    init_logger();
+    let queue = VerificationQueue::new(QueueConfig {
+        budget: VerificationBudget {
+            max_signature_verifications: 1,
+            ..Default::default()
+        },
+        ..unlimited()
+    });
+    let worker = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.run().await }
+    });
+
+    let chain = chain();
+    let home_server_cert = chain[1].clone();
+    let within_budget = queue
+        .submit("alice", Priority::Interactive, NOW, move |verifier| {
+            verifier.verify_home_server(&home_server_cert, NOW)?;
+            Ok(verifier.signature_verifications())
+        })
+        .unwrap();
+    let over_budget = queue
+        .submit("bob", Priority::Backfill, NOW, move |verifier| {
+            verifier.verify_chain(&chain, NOW)
+        })
+        .unwrap();
+    assert_eq!(within_budget.await, Ok(1));
+    assert_eq!(
+        over_budget.await,
+        Err(QueueError::InvalidCert(InvalidCert::BudgetExceeded(
+            BudgetResource::SignatureVerifications
+        )))
+    );
+    let metrics = queue.metrics();
+    assert_eq!(metrics.completed, 2);
+    assert_eq!(metrics.failed, 1);
+    assert_eq!(metrics.budget_exceeded, 1);
+    assert_eq!(metrics.signature_verifications, 2);
+
+    queue.close();
+    worker.await.unwrap();
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn close_resolves_pending_tickets() {
+    init_logger();
+    let queue = VerificationQueue::new(unlimited());
+    let ticket = queue
+        .submit("alice", Priority::Interactive, NOW, |_| Ok(()))
+        .unwrap();
+    assert!(!ticket.is_done());
+    queue.close();
+    assert_eq!(ticket.try_take(), Some(Err(QueueError::Closed)));
+    assert_eq!(
+        queue
+            .submit("alice", Priority::Interactive, NOW, |_| Ok(()))
+            .unwrap_err(),
+        QueueError::Closed
+    );
+    assert!(!queue.run_one());
+}
@@ -0,0 +1,102 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use der::asn1::Uint;
+use httptest::matchers::{contains, key, request};
+use httptest::responders::{cycle, status_code};
+use httptest::{all_of, Expectation, Server};
+use polyproto::certs::certid::CertId;
+use polyproto::errors::WebhookError;
+use polyproto::key::PrivateKey;
+use polyproto::timestamp::Timestamp;
+use polyproto::webhooks::*;
+
+use crate::common::*;
+
+fn event() -> WebhookEvent {
+    WebhookEvent::IdCertRevoked {
+        cert_id: CertId::new(&home_server_subject(), Uint::new(&[7]).unwrap()).unwrap(),
+        reason: Some("key compromise".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn deliver_with_retry() {
+    init_logger();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/hook"),
+            request::headers(contains(key("x-p2-webhook-signature"))),
+            request::headers(contains(key("x-p2-webhook-timestamp"))),
+        ])
+        .times(2)
+        .respond_with(cycle(vec![
+            Box::new(status_code(503)),
+            Box::new(status_code(204)),
+        ])),
+    );
+    let dispatcher =
+        WebhookDispatcher::new(&server.url("/hook").to_string(), HmacSigner::new(b"secret"))
+            .unwrap()
+            .with_retry(3, Duration::from_millis(10));
+    let payload = dispatcher.deliver(event()).await.unwrap();
+    assert_eq!(payload.event, event());
+}
+
+#[tokio::test]
+async fn deliver_gives_up_on_client_error() {
+    init_logger();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("POST", "/hook"))
+            .times(1)
+            .respond_with(status_code(400)),
+    );
+    let dispatcher =
+        WebhookDispatcher::new(&server.url("/hook").to_string(), HmacSigner::new(b"secret"))
+            .unwrap()
+            .with_retry(3, Duration::from_millis(10));
+    assert!(dispatcher.deliver(event()).await.is_err());
+}
+
+#[test]
+fn cert_signed_roundtrip() {
+    let priv_key = gen_priv_key();
+    let cert_id = CertId::new(&home_server_subject(), Uint::new(&[1]).unwrap()).unwrap();
+    let signer = CertSigner::new(priv_key.clone(), cert_id.clone());
+    let time = Timestamp::from_unix_seconds(1000);
+    let body = serde_json::to_vec(&WebhookPayload::new(event(), time)).unwrap();
+    let mut message = b"1000.".to_vec();
+    message.extend_from_slice(&body);
+    let header = signer.signature_header(&message);
+
+    assert_eq!(signing_cert_id(&header).unwrap(), cert_id);
+    let (signing_cert, payload) = verify_cert_signed(
+        priv_key.pubkey(),
+        &header,
+        "1000",
+        &body,
+        time,
+        DEFAULT_TOLERANCE,
+    )
+    .unwrap();
+    assert_eq!(signing_cert, cert_id);
+    assert_eq!(payload.event, event());
+
+    let other_key = gen_priv_key();
+    assert_eq!(
+        verify_cert_signed(
+            other_key.pubkey(),
+            &header,
+            "1000",
+            &body,
+            time,
+            DEFAULT_TOLERANCE,
+        ),
+        Err(WebhookError::BadSignature)
+    );
+}